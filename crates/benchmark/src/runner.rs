@@ -19,7 +19,7 @@ use tokio::{
     time::{Duration, Instant},
 };
 use tracing::debug;
-use utils::config::ClientConfig;
+use utils::config::{default_client_health_check_interval, default_resolve_interval, ClientConfig};
 use xline_client::{types::kv::PutRequest, ClientOptions};
 
 use crate::{args::Commands, bench_client::BenchClient, Benchmark};
@@ -167,6 +167,8 @@ impl CommandRunner {
             3,
             true,
             Duration::from_secs(1),
+            default_client_health_check_interval(),
+            default_resolve_interval(),
         ));
         let addrs = self
             .args
@@ -49,6 +49,12 @@ pub trait Command: pri::Serializable + ConflictCheck + PbCodec {
     /// Returns `true` if the command is read-only
     fn is_read_only(&self) -> bool;
 
+    /// Scheduling priority of the command
+    #[inline]
+    fn priority(&self) -> Priority {
+        Priority::Normal
+    }
+
     /// Prepare the command
     ///
     /// # Errors
@@ -91,6 +97,20 @@ pub trait Command: pri::Serializable + ConflictCheck + PbCodec {
     }
 }
 
+/// Scheduling priority of a command, the commands ready to be executed or after synced
+/// are handed to the workers in the order of their priorities
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Priority {
+    /// Scheduled before the others
+    High,
+    /// The default priority
+    #[default]
+    Normal,
+    /// Scheduled after the others
+    Low,
+}
+
 /// Check conflict of two keys
 pub trait ConflictCheck {
     /// check if this keys conflicts with the `other` key
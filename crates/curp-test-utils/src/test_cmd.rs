@@ -422,11 +422,7 @@ impl TestCE {
         after_sync_sender: mpsc::UnboundedSender<(TestCommand, LogIndex)>,
         engine_cfg: EngineConfig,
     ) -> Self {
-        let engine_type = match engine_cfg {
-            EngineConfig::Memory => EngineType::Memory,
-            EngineConfig::RocksDB(path) => EngineType::Rocks(path),
-            _ => unreachable!("Not supported storage type"),
-        };
+        let engine_type = EngineType::try_from(&engine_cfg).unwrap();
         let store =
             Arc::new(Engine::new(engine_type, &[TEST_TABLE, REVISION_TABLE, META_TABLE]).unwrap());
         let rev = store
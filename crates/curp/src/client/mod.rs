@@ -32,7 +32,10 @@ use tonic::transport::ClientTlsConfig;
 use tracing::debug;
 #[cfg(madsim)]
 use utils::ClientTlsConfig;
-use utils::{build_endpoint, config::ClientConfig};
+use utils::{
+    build_endpoint,
+    config::{ClientConfig, GrpcCompression},
+};
 
 use self::{
     retry::{Retry, RetryConfig},
@@ -43,7 +46,7 @@ use crate::{
     members::ServerId,
     rpc::{
         protocol_client::ProtocolClient, ConfChange, FetchClusterRequest, FetchClusterResponse,
-        Member, ProposeId, Protocol, ReadState,
+        FromTonicChannel, Member, ProposeId, Protocol, ReadState,
     },
 };
 
@@ -178,6 +181,8 @@ pub struct ClientBuilder {
     config: ClientConfig,
     /// Client tls config
     tls_config: Option<ClientTlsConfig>,
+    /// Compression of the sent requests
+    compression: GrpcCompression,
 }
 
 /// A client builder with bypass with local server
@@ -251,6 +256,14 @@ impl ClientBuilder {
         self
     }
 
+    /// Set the compression of the sent requests
+    #[inline]
+    #[must_use]
+    pub fn compression(mut self, compression: GrpcCompression) -> Self {
+        self.compression = compression;
+        self
+    }
+
     /// Discover the initial states from some endpoints
     ///
     /// # Errors
@@ -263,6 +276,7 @@ impl ClientBuilder {
             .iter()
             .map(|addr| {
                 let tls_config = self.tls_config.clone();
+                let compression = self.compression;
                 async move {
                     let endpoint = build_endpoint(addr, tls_config.as_ref()).map_err(|e| {
                         tonic::Status::internal(format!("create endpoint failed, error: {e}"))
@@ -270,7 +284,7 @@ impl ClientBuilder {
                     let channel = endpoint.connect().await.map_err(|e| {
                         tonic::Status::cancelled(format!("cannot connect to addr, error: {e}"))
                     })?;
-                    let mut protocol_client = ProtocolClient::new(channel);
+                    let mut protocol_client = ProtocolClient::from_channel(channel, compression);
                     let mut req = tonic::Request::new(FetchClusterRequest::default());
                     req.set_timeout(propose_timeout);
                     let fetch_cluster_res = protocol_client.fetch_cluster(req).await?.into_inner();
@@ -315,6 +329,8 @@ impl ClientBuilder {
             builder.set_leader_state(id, term);
         }
         builder.set_is_raw_curp(self.is_raw_curp);
+        builder.set_compression(self.compression);
+        builder.set_resolve_interval(*self.config.resolve_interval());
         builder
     }
 
@@ -343,12 +359,19 @@ impl ClientBuilder {
     }
 
     /// Spawn background tasks for the client
+    #[allow(clippy::ignored_unit_patterns)] // tokio select internal triggered
     fn spawn_bg_tasks(&self, state: Arc<state::State>) -> JoinHandle<()> {
-        let interval = *self.config.keep_alive_interval();
+        let config = stream::StreamingConfig::new(
+            *self.config.keep_alive_interval(),
+            *self.config.health_check_interval(),
+        );
         tokio::spawn(async move {
-            let stream = stream::Streaming::new(state, stream::StreamingConfig::new(interval));
-            stream.keep_heartbeat().await;
-            debug!("keep heartbeat task shutdown");
+            let stream = stream::Streaming::new(state, config);
+            tokio::select! {
+                _ = stream.keep_heartbeat() => {}
+                _ = stream.keep_checking_health() => {}
+            }
+            debug!("client background tasks shutdown");
         })
     }
 
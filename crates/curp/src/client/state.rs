@@ -2,6 +2,7 @@ use std::{
     cmp::Ordering,
     collections::{hash_map::Entry, HashMap, HashSet},
     sync::{atomic::AtomicU64, Arc},
+    time::Duration,
 };
 
 use event_listener::Event;
@@ -10,6 +11,7 @@ use tokio::sync::RwLock;
 #[cfg(not(madsim))]
 use tonic::transport::ClientTlsConfig;
 use tracing::{debug, info};
+use utils::config::GrpcCompression;
 #[cfg(madsim)]
 use utils::ClientTlsConfig;
 
@@ -44,6 +46,10 @@ struct StateStatic {
     leader_notifier: Arc<Event>,
     /// Client tls config
     tls_config: Option<ClientTlsConfig>,
+    /// Compression of the sent requests
+    compression: GrpcCompression,
+    /// Interval to re-resolve the hostnames in the addresses of the members
+    resolve_interval: Duration,
 }
 
 /// Mutable client state
@@ -56,6 +62,8 @@ struct StateMut {
     cluster_version: u64,
     /// Members' connect, calibrated by the server.
     connects: HashMap<ServerId, Arc<dyn ConnectApi>>,
+    /// Members that failed the last health check
+    unhealthy: HashSet<ServerId>,
 }
 
 impl std::fmt::Debug for StateMut {
@@ -65,6 +73,7 @@ impl std::fmt::Debug for StateMut {
             .field("term", &self.term)
             .field("cluster_version", &self.cluster_version)
             .field("connects", &self.connects.keys())
+            .field("unhealthy", &self.unhealthy)
             .finish()
     }
 }
@@ -86,11 +95,14 @@ impl State {
                 term,
                 cluster_version,
                 connects,
+                unhealthy: HashSet::new(),
             }),
             immutable: StateStatic {
                 local_server,
                 leader_notifier: Arc::new(Event::new()),
                 tls_config,
+                compression: GrpcCompression::default(),
+                resolve_interval: Duration::ZERO,
                 is_raw_curp: true,
             },
             client_id: Arc::new(AtomicU64::new(0)),
@@ -148,6 +160,33 @@ impl State {
         self.mutable.read().await.leader
     }
 
+    /// Get the cached leader id if it passed the last health check, requests are not
+    /// routed to an unhealthy leader before it's confirmed again
+    pub(super) async fn healthy_leader_id(&self) -> Option<ServerId> {
+        let state = self.mutable.read().await;
+        state.leader.filter(|id| !state.unhealthy.contains(id))
+    }
+
+    /// Update the health of a server, return `true` if its health changed.
+    ///
+    /// The cached leader is cleared when it becomes unhealthy, so that requests fail
+    /// over to the new leader as soon as it is discovered instead of waiting for timeouts.
+    pub(super) async fn update_health(&self, id: ServerId, healthy: bool) -> bool {
+        let mut state = self.mutable.write().await;
+        if healthy {
+            return state.unhealthy.remove(&id);
+        }
+        if !state.connects.contains_key(&id) || !state.unhealthy.insert(id) {
+            return false;
+        }
+        if state.leader == Some(id) {
+            info!("client leader({id}) becomes unhealthy, wait for leadership update");
+            state.leader = None;
+            let _ignore = self.immutable.leader_notifier.notify(usize::MAX);
+        }
+        true
+    }
+
     /// Take an async function and map to the dedicated server, return `Err(CurpError:WrongClusterVersion(()))`
     /// if the server can not found in local state
     pub(super) async fn map_server<R, F: Future<Output = Result<R, CurpError>>>(
@@ -273,11 +312,19 @@ impl State {
                     .remove(&diff)
                     .unwrap_or_else(|| unreachable!("{diff} must in new member addrs"));
                 debug!("client connects to a new server({diff}), address({addrs:?})");
-                let new_conn = rpc::connect(diff, addrs, self.immutable.tls_config.clone()).await?;
+                let new_conn = rpc::connect(
+                    diff,
+                    addrs,
+                    self.immutable.tls_config.clone(),
+                    self.immutable.compression,
+                    self.immutable.resolve_interval,
+                )
+                .await?;
                 let _ig = e.insert(new_conn);
             } else {
                 debug!("client removes old server({diff})");
                 let _ig = state.connects.remove(&diff);
+                let _ignore = state.unhealthy.remove(&diff);
             }
         }
         for same in sames {
@@ -306,6 +353,10 @@ pub(super) struct StateBuilder {
     cluster_version: Option<u64>,
     /// Client Tls config
     tls_config: Option<ClientTlsConfig>,
+    /// Compression of the sent requests
+    compression: GrpcCompression,
+    /// Interval to re-resolve the hostnames in the addresses of the members
+    resolve_interval: Duration,
     /// is current client send request to raw curp server
     is_raw_curp: bool,
 }
@@ -321,6 +372,8 @@ impl StateBuilder {
             leader_state: None,
             cluster_version: None,
             tls_config,
+            compression: GrpcCompression::default(),
+            resolve_interval: Duration::ZERO,
             is_raw_curp: false,
         }
     }
@@ -330,6 +383,16 @@ impl StateBuilder {
         self.is_raw_curp = is_raw_curp;
     }
 
+    /// Set the compression of the sent requests
+    pub(super) fn set_compression(&mut self, compression: GrpcCompression) {
+        self.compression = compression;
+    }
+
+    /// Set the interval to re-resolve the hostnames in the addresses of the members
+    pub(super) fn set_resolve_interval(&mut self, resolve_interval: Duration) {
+        self.resolve_interval = resolve_interval;
+    }
+
     /// Set the leader state (optional)
     pub(super) fn set_leader_state(&mut self, id: ServerId, term: u64) {
         self.leader_state = Some((id, term));
@@ -349,10 +412,14 @@ impl StateBuilder {
         debug!("client bypassed server({local_server_id})");
 
         let _ig = self.all_members.remove(&local_server_id);
-        let mut connects: HashMap<_, _> =
-            rpc::connects(self.all_members.clone(), self.tls_config.as_ref())
-                .await?
-                .collect();
+        let mut connects: HashMap<_, _> = rpc::connects(
+            self.all_members.clone(),
+            self.tls_config.as_ref(),
+            self.compression,
+            self.resolve_interval,
+        )
+        .await?
+        .collect();
         let __ig = connects.insert(
             local_server_id,
             Arc::new(BypassedConnect::new(local_server_id, local_server)),
@@ -364,11 +431,14 @@ impl StateBuilder {
                 term: self.leader_state.map_or(0, |state| state.1),
                 cluster_version: self.cluster_version.unwrap_or_default(),
                 connects,
+                unhealthy: HashSet::new(),
             }),
             immutable: StateStatic {
                 local_server: Some(local_server_id),
                 leader_notifier: Arc::new(Event::new()),
                 tls_config: self.tls_config.take(),
+                compression: self.compression,
+                resolve_interval: self.resolve_interval,
                 is_raw_curp: self.is_raw_curp,
             },
             client_id: Arc::new(AtomicU64::new(0)),
@@ -377,21 +447,28 @@ impl StateBuilder {
 
     /// Build the state
     pub(super) async fn build(self) -> Result<State, tonic::transport::Error> {
-        let connects: HashMap<_, _> =
-            rpc::connects(self.all_members.clone(), self.tls_config.as_ref())
-                .await?
-                .collect();
+        let connects: HashMap<_, _> = rpc::connects(
+            self.all_members.clone(),
+            self.tls_config.as_ref(),
+            self.compression,
+            self.resolve_interval,
+        )
+        .await?
+        .collect();
         Ok(State {
             mutable: RwLock::new(StateMut {
                 leader: self.leader_state.map(|state| state.0),
                 term: self.leader_state.map_or(0, |state| state.1),
                 cluster_version: self.cluster_version.unwrap_or_default(),
                 connects,
+                unhealthy: HashSet::new(),
             }),
             immutable: StateStatic {
                 local_server: None,
                 leader_notifier: Arc::new(Event::new()),
                 tls_config: self.tls_config,
+                compression: self.compression,
+                resolve_interval: self.resolve_interval,
                 is_raw_curp: self.is_raw_curp,
             },
             client_id: Arc::new(AtomicU64::new(0)),
@@ -1,22 +1,27 @@
 use std::{sync::Arc, time::Duration};
 
-use futures::Future;
-use tracing::{debug, warn};
+use futures::{Future, StreamExt};
+use tracing::{debug, info, warn};
 
 use super::state::State;
-use crate::rpc::{connect::ConnectApi, CurpError, Redirect};
+use crate::rpc::{connect::ConnectApi, CurpError, FetchClusterRequest, Redirect};
 
 /// Stream client config
 #[derive(Debug)]
 pub(super) struct StreamingConfig {
     /// Heartbeat interval
     heartbeat_interval: Duration,
+    /// Health check interval, zero means disabled
+    health_check_interval: Duration,
 }
 
 impl StreamingConfig {
     /// Create a stream client config
-    pub(super) fn new(heartbeat_interval: Duration) -> Self {
-        Self { heartbeat_interval }
+    pub(super) fn new(heartbeat_interval: Duration, health_check_interval: Duration) -> Self {
+        Self {
+            heartbeat_interval,
+            health_check_interval,
+        }
     }
 }
 
@@ -106,4 +111,60 @@ impl Streaming {
             }
         }
     }
+
+    /// Keep checking the health of all servers, and discover the leader from their responses.
+    /// Never returns if the health check is disabled.
+    pub(super) async fn keep_checking_health(&self) {
+        let interval = self.config.health_check_interval;
+        if interval.is_zero() {
+            return futures::future::pending().await;
+        }
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            let _instant = ticker.tick().await;
+            let mut responses = self
+                .state
+                .for_each_server(|conn| async move {
+                    (
+                        conn.id(),
+                        conn.fetch_cluster(FetchClusterRequest::default(), interval)
+                            .await,
+                    )
+                })
+                .await;
+            while let Some((id, resp)) = responses.next().await {
+                match resp {
+                    Ok(resp) => {
+                        let resp = resp.into_inner();
+                        // only the leader is trusted here, the members of a stale server may be outdated
+                        if !self
+                            .state
+                            .check_and_update_leader(resp.leader_id, resp.term)
+                            .await
+                        {
+                            // the server may be partitioned from the cluster, its response
+                            // says nothing about whether requests can be served by it
+                            debug!(
+                                "ignore health check response of old term({}) from server({id})",
+                                resp.term
+                            );
+                            continue;
+                        }
+                        if self.state.update_health(id, true).await {
+                            info!("server({id}) becomes healthy");
+                        }
+                    }
+                    Err(CurpError::ShuttingDown(())) => {
+                        debug!("shutting down health check background task");
+                        return;
+                    }
+                    Err(e) => {
+                        if self.state.update_health(id, false).await {
+                            warn!("server({id}) becomes unhealthy, error: {e:?}");
+                        }
+                    }
+                }
+            }
+        }
+    }
 }
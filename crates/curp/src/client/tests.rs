@@ -862,7 +862,10 @@ fn init_stream_client(
     cluster_version: u64,
 ) -> Streaming {
     let state = State::new_arc(connects, local_server, leader, term, cluster_version, None);
-    Streaming::new(state, StreamingConfig::new(Duration::from_secs(1)))
+    Streaming::new(
+        state,
+        StreamingConfig::new(Duration::from_secs(1), Duration::ZERO),
+    )
 }
 
 #[traced_test]
@@ -955,3 +958,81 @@ async fn test_stream_client_keep_alive_resume_on_leadership_changed() {
     }
     assert_eq!(stream.state.client_id(), 10);
 }
+
+#[traced_test]
+#[tokio::test]
+async fn test_stream_client_health_check_will_clear_unhealthy_leader() {
+    let connects = init_mocked_connects(3, |id, conn| {
+        conn.expect_fetch_cluster()
+            .returning(move |_req, _timeout| {
+                if id == 0 {
+                    return Err(CurpError::RpcTransport(()));
+                }
+                Ok(tonic::Response::new(FetchClusterResponse {
+                    leader_id: None,
+                    term: 1,
+                    cluster_id: 123,
+                    members: vec![],
+                    cluster_version: 1,
+                }))
+            });
+    });
+    let state = State::new_arc(connects, None, Some(0), 1, 1, None);
+    let stream = Streaming::new(
+        state,
+        StreamingConfig::new(Duration::from_secs(1), Duration::from_millis(10)),
+    );
+    tokio::time::timeout(Duration::from_millis(100), stream.keep_checking_health())
+        .await
+        .unwrap_err();
+    assert_eq!(stream.state.leader_id().await, None);
+    assert!(stream.state.update_health(0, true).await);
+    assert!(!stream.state.update_health(1, true).await);
+}
+
+#[traced_test]
+#[tokio::test]
+async fn test_stream_client_health_check_will_ignore_old_term() {
+    let connects = init_mocked_connects(3, |id, conn| {
+        conn.expect_fetch_cluster()
+            .returning(move |_req, _timeout| {
+                // server 0 is a stale leader partitioned from the cluster
+                let (leader_id, term) = if id == 0 { (Some(0), 1) } else { (Some(1), 2) };
+                Ok(tonic::Response::new(FetchClusterResponse {
+                    leader_id,
+                    term,
+                    cluster_id: 123,
+                    members: vec![],
+                    cluster_version: 1,
+                }))
+            });
+    });
+    let state = State::new_arc(connects, None, Some(1), 2, 1, None);
+    assert!(state.update_health(0, false).await);
+    let stream = Streaming::new(
+        state,
+        StreamingConfig::new(Duration::from_secs(1), Duration::from_millis(10)),
+    );
+    tokio::time::timeout(Duration::from_millis(100), stream.keep_checking_health())
+        .await
+        .unwrap_err();
+    assert_eq!(stream.state.leader_id().await, Some(1));
+    // the response of the old term doesn't make server 0 healthy
+    assert!(stream.state.update_health(0, true).await);
+}
+
+#[traced_test]
+#[tokio::test]
+async fn test_unhealthy_leader_will_not_be_routed() {
+    let connects = init_mocked_connects(3, |_id, _conn| {});
+    let state = State::new_arc(connects, None, Some(0), 1, 1, None);
+    assert_eq!(state.healthy_leader_id().await, Some(0));
+    assert!(state.update_health(0, false).await);
+    assert_eq!(state.healthy_leader_id().await, None);
+    // a leader learned later is not routed to until it's healthy again
+    assert!(state.check_and_update_leader(Some(0), 2).await);
+    assert_eq!(state.leader_id().await, Some(0));
+    assert_eq!(state.healthy_leader_id().await, None);
+    assert!(state.update_health(0, true).await);
+    assert_eq!(state.healthy_leader_id().await, Some(0));
+}
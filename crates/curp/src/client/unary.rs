@@ -69,7 +69,7 @@ impl<C: Command> Unary<C> {
         &self,
         f: impl FnOnce(Arc<dyn ConnectApi>) -> F,
     ) -> Result<R, CurpError> {
-        let cached_leader = self.state.leader_id().await;
+        let cached_leader = self.state.healthy_leader_id().await;
         let leader_id = match cached_leader {
             Some(id) => id,
             None => <Unary<C> as ClientApi>::fetch_leader_id(self, false).await?,
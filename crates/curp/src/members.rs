@@ -11,11 +11,13 @@ use std::{
 use dashmap::{mapref::one::Ref, DashMap};
 use futures::{stream::FuturesUnordered, StreamExt};
 use itertools::Itertools;
+use tokio::sync::watch;
 #[cfg(not(madsim))]
 use tonic::transport::ClientTlsConfig;
 use tracing::{debug, info};
 #[cfg(madsim)]
 use utils::ClientTlsConfig;
+use utils::{config::GrpcCompression, version::Version};
 
 use crate::rpc::{self, FetchClusterRequest, FetchClusterResponse, Member};
 
@@ -71,6 +73,29 @@ impl Member {
     }
 }
 
+/// Version negotiated by the cluster
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct NegotiatedVersion {
+    /// The min binary version of all members, or the target version of a downgrade,
+    /// `None` if it is not negotiated yet
+    pub version: Option<Version>,
+    /// Target version of the downgrade in progress
+    pub downgrade_target: Option<Version>,
+}
+
+impl NegotiatedVersion {
+    /// Create a new `NegotiatedVersion`
+    #[inline]
+    #[must_use]
+    pub fn new(version: Option<Version>, downgrade_target: Option<Version>) -> Self {
+        Self {
+            version,
+            downgrade_target,
+        }
+    }
+}
+
 /// cluster members information
 #[derive(Debug, Clone)]
 pub struct ClusterInfo {
@@ -82,6 +107,10 @@ pub struct ClusterInfo {
     members: DashMap<ServerId, Member>,
     /// cluster version
     cluster_version: Arc<AtomicU64>,
+    /// binary versions advertised by members
+    member_versions: Arc<DashMap<ServerId, Version>>,
+    /// version negotiated by the cluster, which is the min binary version of all members
+    negotiated_version: Arc<watch::Sender<NegotiatedVersion>>,
 }
 
 impl ClusterInfo {
@@ -94,6 +123,8 @@ impl ClusterInfo {
             member_id,
             members: members.into_iter().map(|m| (m.id, m)).collect(),
             cluster_version: Arc::new(AtomicU64::new(0)),
+            member_versions: Arc::new(DashMap::new()),
+            negotiated_version: Arc::new(watch::channel(NegotiatedVersion::default()).0),
         }
     }
 
@@ -125,6 +156,8 @@ impl ClusterInfo {
             member_id,
             members,
             cluster_version: Arc::new(AtomicU64::new(0)),
+            member_versions: Arc::new(DashMap::new()),
+            negotiated_version: Arc::new(watch::channel(NegotiatedVersion::default()).0),
         };
         cluster_info.gen_cluster_id();
         cluster_info
@@ -166,6 +199,8 @@ impl ClusterInfo {
             member_id,
             members,
             cluster_version: Arc::new(AtomicU64::new(cluster.cluster_version)),
+            member_versions: Arc::new(DashMap::new()),
+            negotiated_version: Arc::new(watch::channel(NegotiatedVersion::default()).0),
         }
     }
 
@@ -349,6 +384,145 @@ impl ClusterInfo {
         self.cluster_version.store(ver, Ordering::Relaxed);
     }
 
+    /// Set the binary version of the current member, which is advertised to the leader
+    #[inline]
+    pub fn set_binary_version(&self, version: Version) {
+        let _prev = self.member_versions.insert(self.member_id, version);
+    }
+
+    /// Get the binary version of the current member
+    #[must_use]
+    #[inline]
+    pub fn binary_version(&self) -> Option<Version> {
+        self.member_versions.get(&self.member_id).map(|v| *v)
+    }
+
+    /// Update the binary version advertised by a member
+    pub(crate) fn update_member_version(&self, id: ServerId, version: Version) {
+        let _prev = self.member_versions.insert(id, version);
+    }
+
+    /// Get the binary versions advertised by members
+    #[must_use]
+    #[inline]
+    pub fn member_versions(&self) -> HashMap<ServerId, Version> {
+        self.members
+            .iter()
+            .filter_map(|t| self.member_versions.get(&t.id).map(|v| (t.id, *v)))
+            .collect()
+    }
+
+    /// Get the version negotiated by the cluster, `None` if it is not negotiated yet
+    #[must_use]
+    #[inline]
+    pub fn negotiated_version(&self) -> Option<Version> {
+        self.negotiated_version.borrow().version
+    }
+
+    /// Get the negotiated version along with the downgrade in progress
+    #[must_use]
+    #[inline]
+    pub fn negotiated(&self) -> NegotiatedVersion {
+        *self.negotiated_version.borrow()
+    }
+
+    /// Get the target version of the downgrade in progress
+    #[must_use]
+    #[inline]
+    pub fn downgrade_target(&self) -> Option<Version> {
+        self.negotiated_version.borrow().downgrade_target
+    }
+
+    /// Get a rx for the changes of the negotiated version
+    #[must_use]
+    #[inline]
+    pub fn negotiated_version_rx(&self) -> watch::Receiver<NegotiatedVersion> {
+        self.negotiated_version.subscribe()
+    }
+
+    /// Raise the negotiated version to `version`, the negotiated version never goes
+    /// down except for a downgrade, so that the features enabled by it are never
+    /// disabled again. It is not raised during a downgrade. Return `true` if it is
+    /// raised.
+    #[inline]
+    pub fn raise_negotiated_version(&self, version: Version) -> bool {
+        self.negotiated_version.send_if_modified(|negotiated| {
+            if negotiated.downgrade_target.is_none()
+                && negotiated.version.map_or(true, |v| v < version)
+            {
+                info!("negotiated version raises to {version}");
+                negotiated.version = Some(version);
+                return true;
+            }
+            false
+        })
+    }
+
+    /// Start a downgrade to `target`, the negotiated version is lowered to the target
+    /// so that the members of the target version are able to join the cluster
+    #[inline]
+    pub fn enable_downgrade(&self, target: Version) {
+        info!("downgrade to {target} is enabled");
+        let _prev = self.negotiated_version.send_replace(NegotiatedVersion {
+            version: Some(target),
+            downgrade_target: Some(target),
+        });
+    }
+
+    /// Stop the downgrade in progress, the negotiated version is kept until it is
+    /// negotiated again. Return `false` if there is no downgrade in progress.
+    #[inline]
+    pub fn cancel_downgrade(&self) -> bool {
+        self.negotiated_version.send_if_modified(|negotiated| {
+            let Some(target) = negotiated.downgrade_target.take() else {
+                return false;
+            };
+            info!("downgrade to {target} is stopped");
+            true
+        })
+    }
+
+    /// Negotiate the version of the cluster, which is the min binary version of all
+    /// members. The version can not be negotiated until every member has advertised
+    /// its binary version, members of older versions advertise none. A downgrade is
+    /// finished once every member runs the target version.
+    pub(crate) fn negotiate_version(&self) -> Option<Version> {
+        let versions = self
+            .members
+            .iter()
+            .map(|t| self.member_versions.get(&t.id).map(|v| *v))
+            .collect::<Option<Vec<_>>>()?;
+        if let Some(target) = self.downgrade_target() {
+            if versions
+                .iter()
+                .all(|v| (v.major, v.minor) <= (target.major, target.minor))
+            {
+                let _stopped = self.cancel_downgrade();
+            }
+            return self.negotiated_version();
+        }
+        if let Some(min) = versions.into_iter().min() {
+            let _raised = self.raise_negotiated_version(min);
+        }
+        self.negotiated_version()
+    }
+
+    /// Follow the negotiated version and the downgrade sent by the leader
+    pub(crate) fn follow_negotiated_version(&self, leader: NegotiatedVersion) {
+        match leader.downgrade_target {
+            Some(target) if self.downgrade_target() != Some(target) => {
+                self.enable_downgrade(target);
+            }
+            Some(_) => {}
+            None => {
+                let _stopped = self.cancel_downgrade();
+                if let Some(version) = leader.version {
+                    let _raised = self.raise_negotiated_version(version);
+                }
+            }
+        }
+    }
+
     /// Get peers
     #[must_use]
     #[inline]
@@ -432,7 +606,8 @@ pub async fn get_cluster_info_from_remote(
 ) -> Option<ClusterInfo> {
     let peers = init_cluster_info.peers_addrs();
     let self_client_urls = init_cluster_info.self_client_urls();
-    let connects = rpc::connects(peers, tls_config)
+    // the connections are only used once, so the hostnames are never re-resolved
+    let connects = rpc::connects(peers, tls_config, GrpcCompression::None, Duration::ZERO)
         .await
         .ok()?
         .map(|pair| pair.1)
@@ -511,4 +686,71 @@ mod tests {
         assert!(peer_urls.iter().find(|url| ***url == node1_url).is_none());
         assert!(peer_ids.iter().find(|id| **id == node1_id).is_none());
     }
+
+    #[test]
+    fn version_should_be_negotiated_after_all_members_advertised() {
+        let all_members = HashMap::from([
+            ("S1".to_owned(), vec!["S1".to_owned()]),
+            ("S2".to_owned(), vec!["S2".to_owned()]),
+        ]);
+        let node1 = ClusterInfo::from_members_map(all_members, [], "S1");
+        let node2_id = node1.peers_ids()[0];
+        node1.set_binary_version(Version::new(0, 7, 0));
+        assert_eq!(node1.binary_version(), Some(Version::new(0, 7, 0)));
+        assert_eq!(node1.negotiate_version(), None);
+
+        node1.update_member_version(node2_id, Version::new(0, 6, 1));
+        assert_eq!(node1.negotiate_version(), Some(Version::new(0, 6, 1)));
+        node1.update_member_version(node2_id, Version::new(0, 7, 1));
+        assert_eq!(node1.negotiate_version(), Some(Version::new(0, 7, 0)));
+
+        // the negotiated version never goes down
+        node1.update_member_version(node2_id, Version::new(0, 6, 1));
+        assert_eq!(node1.negotiate_version(), Some(Version::new(0, 7, 0)));
+        assert!(!node1.raise_negotiated_version(Version::new(0, 6, 1)));
+        assert!(node1.raise_negotiated_version(Version::new(0, 8, 0)));
+        assert_eq!(node1.negotiated_version(), Some(Version::new(0, 8, 0)));
+    }
+
+    #[test]
+    fn downgrade_should_lower_the_negotiated_version_until_finished() {
+        let all_members = HashMap::from([
+            ("S1".to_owned(), vec!["S1".to_owned()]),
+            ("S2".to_owned(), vec!["S2".to_owned()]),
+        ]);
+        let node1 = ClusterInfo::from_members_map(all_members, [], "S1");
+        let node2_id = node1.peers_ids()[0];
+        node1.set_binary_version(Version::new(0, 7, 0));
+        node1.update_member_version(node2_id, Version::new(0, 7, 0));
+        assert_eq!(node1.negotiate_version(), Some(Version::new(0, 7, 0)));
+
+        node1.enable_downgrade(Version::new(0, 6, 0));
+        assert_eq!(node1.negotiate_version(), Some(Version::new(0, 6, 0)));
+        assert!(!node1.raise_negotiated_version(Version::new(0, 7, 0)));
+        node1.update_member_version(node2_id, Version::new(0, 6, 2));
+        assert_eq!(node1.negotiate_version(), Some(Version::new(0, 6, 0)));
+        assert_eq!(node1.downgrade_target(), Some(Version::new(0, 6, 0)));
+
+        // the downgrade is finished after all members are downgraded
+        node1.set_binary_version(Version::new(0, 6, 1));
+        assert_eq!(node1.negotiate_version(), Some(Version::new(0, 6, 0)));
+        assert_eq!(node1.downgrade_target(), None);
+        assert_eq!(node1.negotiate_version(), Some(Version::new(0, 6, 1)));
+        assert!(!node1.cancel_downgrade());
+    }
+
+    #[test]
+    fn follower_should_follow_the_downgrade_of_leader() {
+        let all_members = HashMap::from([("S1".to_owned(), vec!["S1".to_owned()])]);
+        let node = ClusterInfo::from_members_map(all_members, [], "S1");
+        node.follow_negotiated_version(NegotiatedVersion::new(Some(Version::new(0, 7, 0)), None));
+        assert_eq!(node.negotiated_version(), Some(Version::new(0, 7, 0)));
+        let target = Version::new(0, 6, 0);
+        node.follow_negotiated_version(NegotiatedVersion::new(Some(target), Some(target)));
+        assert_eq!(node.negotiated_version(), Some(target));
+        assert_eq!(node.downgrade_target(), Some(target));
+        node.follow_negotiated_version(NegotiatedVersion::new(Some(target), None));
+        assert_eq!(node.downgrade_target(), None);
+        assert_eq!(node.negotiated_version(), Some(target));
+    }
 }
@@ -11,17 +11,18 @@ use async_trait::async_trait;
 use bytes::BytesMut;
 use clippy_utilities::NumericCast;
 use engine::SnapshotApi;
+use fail::fail_point;
 use futures::{stream::FuturesUnordered, Stream};
 #[cfg(test)]
 use mockall::automock;
-use tokio::sync::Mutex;
+use tokio::{sync::Mutex, time::Instant};
 #[cfg(not(madsim))]
 use tonic::transport::ClientTlsConfig;
 use tonic::transport::{Channel, Endpoint};
 use tracing::{debug, error, info, instrument};
 #[cfg(madsim)]
 use utils::ClientTlsConfig;
-use utils::{build_endpoint, tracing::Inject};
+use utils::{build_endpoint, config::GrpcCompression, tracing::Inject};
 
 use crate::{
     members::ServerId,
@@ -32,13 +33,14 @@ use crate::{
         },
         AppendEntriesRequest, AppendEntriesResponse, CurpError, FetchClusterRequest,
         FetchClusterResponse, FetchReadStateRequest, FetchReadStateResponse,
-        InstallSnapshotRequest, InstallSnapshotResponse, LeaseKeepAliveMsg, MoveLeaderRequest,
-        MoveLeaderResponse, ProposeConfChangeRequest, ProposeConfChangeResponse, ProposeRequest,
-        ProposeResponse, Protocol, PublishRequest, PublishResponse, ShutdownRequest,
-        ShutdownResponse, TriggerShutdownRequest, TryBecomeLeaderNowRequest, VoteRequest,
-        VoteResponse, WaitSyncedRequest, WaitSyncedResponse,
+        FetchSnapshotOffsetRequest, FetchSnapshotOffsetResponse, InstallSnapshotRequest,
+        InstallSnapshotResponse, LeaseKeepAliveMsg, MoveLeaderRequest, MoveLeaderResponse,
+        ProposeConfChangeRequest, ProposeConfChangeResponse, ProposeRequest, ProposeResponse,
+        Protocol, PublishRequest, PublishResponse, ShutdownRequest, ShutdownResponse,
+        TriggerShutdownRequest, TryBecomeLeaderNowRequest, VoteRequest, VoteResponse,
+        WaitSyncedRequest, WaitSyncedResponse,
     },
-    snapshot::Snapshot,
+    snapshot::{Snapshot, SnapshotMeta},
 };
 
 /// Install snapshot chunk size: 64KB
@@ -47,29 +49,57 @@ const SNAPSHOT_CHUNK_SIZE: u64 = 64 * 1024;
 /// The default buffer size for rpc connection
 const DEFAULT_BUFFER_SIZE: usize = 1024;
 
+/// Accept compressed responses, and compress the requests with the given compression
+#[cfg(not(madsim))]
+macro_rules! with_compression {
+    ($client:expr, $compression:expr) => {{
+        let client = GrpcCompression::ACCEPTED
+            .into_iter()
+            .fold($client, |client, encoding| {
+                client.accept_compressed(encoding)
+            });
+        match $compression.encoding() {
+            Some(encoding) => client.send_compressed(encoding),
+            None => client,
+        }
+    }};
+}
+
+/// The simulated clients don't support compression
+#[cfg(madsim)]
+macro_rules! with_compression {
+    ($client:expr, $compression:expr) => {{
+        let _ignore = $compression;
+        $client
+    }};
+}
+
 /// For protocol client
-trait FromTonicChannel {
-    /// New from channel
-    fn from_channel(channel: Channel) -> Self;
+pub(crate) trait FromTonicChannel {
+    /// New from channel, sending messages with the given compression
+    fn from_channel(channel: Channel, compression: GrpcCompression) -> Self;
 }
 
 impl FromTonicChannel for ProtocolClient<Channel> {
-    fn from_channel(channel: Channel) -> Self {
-        ProtocolClient::new(channel)
+    fn from_channel(channel: Channel, compression: GrpcCompression) -> Self {
+        with_compression!(ProtocolClient::new(channel), compression)
     }
 }
 
 impl FromTonicChannel for InnerProtocolClient<Channel> {
-    fn from_channel(channel: Channel) -> Self {
-        InnerProtocolClient::new(channel)
+    fn from_channel(channel: Channel, compression: GrpcCompression) -> Self {
+        with_compression!(InnerProtocolClient::new(channel), compression)
     }
 }
 
-/// Connect to a server
+/// Connect to a server, and re-resolve the hostnames in its addresses at the given
+/// interval, `0` disables the re-resolution
 async fn connect_to<Client: FromTonicChannel>(
     id: ServerId,
     addrs: Vec<String>,
     tls_config: Option<ClientTlsConfig>,
+    compression: GrpcCompression,
+    resolve_interval: Duration,
 ) -> Result<Arc<Connect<Client>>, tonic::transport::Error> {
     let (channel, change_tx) = Channel::balance_channel(DEFAULT_BUFFER_SIZE);
     for addr in &addrs {
@@ -78,7 +108,7 @@ async fn connect_to<Client: FromTonicChannel>(
             .send(tower::discover::Change::Insert(addr.clone(), endpoint))
             .await;
     }
-    let client = Client::from_channel(channel);
+    let client = Client::from_channel(channel, compression);
     let connect = Arc::new(Connect {
         id,
         rpc_connect: client,
@@ -87,20 +117,48 @@ async fn connect_to<Client: FromTonicChannel>(
 
         tls_config,
     });
+    #[cfg(not(madsim))]
+    if !resolve_interval.is_zero() {
+        connect.spawn_resolve_task(resolve_interval);
+    }
+    #[cfg(madsim)]
+    let _ignore = resolve_interval;
     Ok(connect)
 }
 
+/// Get the `host:port` of an address whose host is a hostname rather than an ip
+#[cfg(not(madsim))]
+fn hostname_authority(addr: &str) -> Option<&str> {
+    let authority = addr
+        .split_once("://")
+        .map_or(addr, |(_, authority)| authority);
+    let authority = authority.split('/').next()?;
+    let (host, _port) = authority.rsplit_once(':')?;
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    host.parse::<std::net::IpAddr>()
+        .is_err()
+        .then_some(authority)
+}
+
 /// Connect to a map of members
 async fn connect_all<Client: FromTonicChannel>(
     members: HashMap<ServerId, Vec<String>>,
     tls_config: Option<&ClientTlsConfig>,
+    compression: GrpcCompression,
+    resolve_interval: Duration,
 ) -> Result<Vec<(u64, Arc<Connect<Client>>)>, tonic::transport::Error> {
     let conns_to: FuturesUnordered<_> = members
         .into_iter()
         .map(|(id, addrs)| async move {
-            connect_to::<Client>(id, addrs, tls_config.cloned())
-                .await
-                .map(|conn| (id, conn))
+            connect_to::<Client>(
+                id,
+                addrs,
+                tls_config.cloned(),
+                compression,
+                resolve_interval,
+            )
+            .await
+            .map(|conn| (id, conn))
         })
         .collect();
     futures::StreamExt::collect::<Vec<_>>(conns_to)
@@ -114,8 +172,12 @@ pub(crate) async fn connect(
     id: ServerId,
     addrs: Vec<String>,
     tls_config: Option<ClientTlsConfig>,
+    compression: GrpcCompression,
+    resolve_interval: Duration,
 ) -> Result<Arc<dyn ConnectApi>, tonic::transport::Error> {
-    let conn = connect_to::<ProtocolClient<Channel>>(id, addrs, tls_config).await?;
+    let conn =
+        connect_to::<ProtocolClient<Channel>>(id, addrs, tls_config, compression, resolve_interval)
+            .await?;
     Ok(conn)
 }
 
@@ -123,11 +185,13 @@ pub(crate) async fn connect(
 pub(crate) async fn connects(
     members: HashMap<ServerId, Vec<String>>,
     tls_config: Option<&ClientTlsConfig>,
+    compression: GrpcCompression,
+    resolve_interval: Duration,
 ) -> Result<impl Iterator<Item = (ServerId, Arc<dyn ConnectApi>)>, tonic::transport::Error> {
     // It seems that casting high-rank types cannot be inferred, so we allow trivial_casts to cast manually
     #[allow(trivial_casts)]
     #[allow(clippy::as_conversions)]
-    let conns = connect_all(members, tls_config)
+    let conns = connect_all(members, tls_config, compression, resolve_interval)
         .await?
         .into_iter()
         .map(|(id, conn)| (id, conn as Arc<dyn ConnectApi>));
@@ -138,8 +202,10 @@ pub(crate) async fn connects(
 pub(crate) async fn inner_connects(
     members: HashMap<ServerId, Vec<String>>,
     tls_config: Option<&ClientTlsConfig>,
+    compression: GrpcCompression,
+    resolve_interval: Duration,
 ) -> Result<impl Iterator<Item = (ServerId, InnerConnectApiWrapper)>, tonic::transport::Error> {
-    let conns = connect_all(members, tls_config)
+    let conns = connect_all(members, tls_config, compression, resolve_interval)
         .await?
         .into_iter()
         .map(|(id, conn)| (id, InnerConnectApiWrapper::new_from_arc(conn)));
@@ -242,14 +308,27 @@ pub(crate) trait InnerConnectApi: Send + Sync + 'static {
         timeout: Duration,
     ) -> Result<tonic::Response<VoteResponse>, tonic::Status>;
 
-    /// Send a snapshot
+    /// Send a snapshot starting from `offset`, at most `rate_limit` bytes per second
+    /// (`0` means unlimited). Every attempt to send the same snapshot carries the same
+    /// `transfer_id`.
     async fn install_snapshot(
         &self,
         term: u64,
         leader_id: ServerId,
-        snapshot: Snapshot,
+        snapshot: Arc<Mutex<Snapshot>>,
+        transfer_id: u64,
+        offset: u64,
+        rate_limit: u64,
     ) -> Result<tonic::Response<InstallSnapshotResponse>, tonic::Status>;
 
+    /// Fetch how many bytes of the snapshot the server has received in the transfer
+    async fn fetch_snapshot_offset(
+        &self,
+        meta: SnapshotMeta,
+        transfer_id: u64,
+        timeout: Duration,
+    ) -> Result<tonic::Response<FetchSnapshotOffsetResponse>, tonic::Status>;
+
     /// Trigger follower shutdown
     async fn trigger_shutdown(&self) -> Result<(), tonic::Status>;
 
@@ -273,8 +352,17 @@ impl InnerConnectApiWrapper {
         id: ServerId,
         addrs: Vec<String>,
         tls_config: Option<ClientTlsConfig>,
+        compression: GrpcCompression,
+        resolve_interval: Duration,
     ) -> Result<Self, tonic::transport::Error> {
-        let conn = connect_to::<InnerProtocolClient<Channel>>(id, addrs, tls_config).await?;
+        let conn = connect_to::<InnerProtocolClient<Channel>>(
+            id,
+            addrs,
+            tls_config,
+            compression,
+            resolve_interval,
+        )
+        .await?;
         Ok(InnerConnectApiWrapper::new_from_arc(conn))
     }
 }
@@ -330,6 +418,76 @@ impl<C> Connect<C> {
         Ok(())
     }
 
+    /// Re-resolve the hostnames in the addresses at the interval, and reconnect
+    /// when the address behind a hostname changes. The task exits once the
+    /// connection is dropped.
+    #[cfg(not(madsim))]
+    fn spawn_resolve_task(self: &Arc<Self>, interval: Duration)
+    where
+        C: Send + Sync + 'static,
+    {
+        let connect = Arc::downgrade(self);
+        let _ig = tokio::spawn(async move {
+            let mut resolved = HashMap::new();
+            loop {
+                tokio::time::sleep(interval).await;
+                let Some(connect) = connect.upgrade() else {
+                    return;
+                };
+                connect.re_resolve(&mut resolved).await;
+            }
+        });
+    }
+
+    /// Re-resolve the hostnames in the addresses, `resolved` records the
+    /// socket addresses of each hostname in the last resolution
+    #[cfg(not(madsim))]
+    async fn re_resolve(&self, resolved: &mut HashMap<String, HashSet<std::net::SocketAddr>>) {
+        let addrs = self.addrs.lock().await.clone();
+        resolved.retain(|addr, _| addrs.contains(addr));
+        let mut changed = vec![];
+        for addr in addrs {
+            let Some(authority) = hostname_authority(&addr) else {
+                continue;
+            };
+            let socket_addrs: HashSet<std::net::SocketAddr> =
+                match tokio::net::lookup_host(authority).await {
+                    Ok(socket_addrs) => socket_addrs.collect(),
+                    Err(e) => {
+                        debug!("failed to resolve {addr} of server {}: {e}", self.id);
+                        continue;
+                    }
+                };
+            if let Some(prev) = resolved.insert(addr.clone(), socket_addrs.clone()) {
+                if prev != socket_addrs {
+                    changed.push(addr);
+                }
+            }
+        }
+        if changed.is_empty() {
+            return;
+        }
+        // addresses may be updated during the resolution, only reconnect the current ones
+        let current = self.addrs.lock().await;
+        for addr in changed.into_iter().filter(|addr| current.contains(addr)) {
+            let endpoint = match build_endpoint(&addr, self.tls_config.as_ref()) {
+                Ok(endpoint) => endpoint,
+                Err(e) => {
+                    error!("failed to build endpoint for {addr}: {e}");
+                    continue;
+                }
+            };
+            info!(
+                "address behind {addr} of server {} changed, reconnecting",
+                self.id
+            );
+            let _ig = self
+                .change_tx
+                .send(tower::discover::Change::Insert(addr, endpoint))
+                .await;
+        }
+    }
+
     /// Before RPC
     #[cfg(feature = "client-metrics")]
     fn before_rpc<Req>(&self) -> std::time::Instant {
@@ -567,12 +725,17 @@ impl InnerConnectApi for Connect<InnerProtocolClient<Channel>> {
         &self,
         term: u64,
         leader_id: ServerId,
-        snapshot: Snapshot,
+        snapshot: Arc<Mutex<Snapshot>>,
+        transfer_id: u64,
+        offset: u64,
+        rate_limit: u64,
     ) -> Result<tonic::Response<InstallSnapshotResponse>, tonic::Status> {
         #[cfg(feature = "client-metrics")]
-        let start_at = self.before_rpc_with_size(snapshot.inner().size());
+        let start_at =
+            self.before_rpc_with_size(snapshot.lock().await.inner().size().saturating_sub(offset));
 
-        let stream = install_snapshot_stream(term, leader_id, snapshot);
+        let stream =
+            install_snapshot_stream(term, leader_id, snapshot, transfer_id, offset, rate_limit);
         let mut client = self.rpc_connect.clone();
         let result = client.install_snapshot(stream).await;
 
@@ -582,6 +745,26 @@ impl InnerConnectApi for Connect<InnerProtocolClient<Channel>> {
         result
     }
 
+    async fn fetch_snapshot_offset(
+        &self,
+        meta: SnapshotMeta,
+        transfer_id: u64,
+        timeout: Duration,
+    ) -> Result<tonic::Response<FetchSnapshotOffsetResponse>, tonic::Status> {
+        #[cfg(feature = "client-metrics")]
+        let start_at = self.before_rpc::<FetchSnapshotOffsetRequest>();
+
+        let mut client = self.rpc_connect.clone();
+        let mut req = tonic::Request::new(FetchSnapshotOffsetRequest::new(meta, transfer_id));
+        req.set_timeout(timeout);
+        let result = client.fetch_snapshot_offset(req).await;
+
+        #[cfg(feature = "client-metrics")]
+        self.after_rpc(start_at, &result);
+
+        result
+    }
+
     async fn trigger_shutdown(&self) -> Result<(), tonic::Status> {
         #[cfg(feature = "client-metrics")]
         let start_at = self.before_rpc::<TriggerShutdownRequest>();
@@ -799,20 +982,38 @@ fn heartbeat_stream(client_id: u64, interval: Duration) -> impl Stream<Item = Le
     }
 }
 
-/// Generate install snapshot stream
+/// Generate install snapshot stream, the chunks before `offset` are skipped as the
+/// server has received them, and the stream is throttled to `rate_limit` bytes per second
 fn install_snapshot_stream(
     term: u64,
     leader_id: ServerId,
-    snapshot: Snapshot,
+    snapshot: Arc<Mutex<Snapshot>>,
+    transfer_id: u64,
+    offset: u64,
+    rate_limit: u64,
 ) -> impl Stream<Item = InstallSnapshotRequest> {
     stream! {
+        let mut snapshot = snapshot.lock_owned().await;
         let meta = snapshot.meta;
-        let mut snapshot = snapshot.into_inner();
-        let mut offset = 0;
+        let snapshot = snapshot.inner_mut();
         if let Err(e) = snapshot.rewind() {
             error!("snapshot seek failed, {e}");
             return;
         }
+        let mut skipped = 0;
+        #[allow(clippy::arithmetic_side_effects)] // can't overflow
+        while skipped < offset {
+            let len: u64 = std::cmp::min(offset - skipped, SNAPSHOT_CHUNK_SIZE);
+            let mut data = BytesMut::with_capacity(len.numeric_cast());
+            if let Err(e) = snapshot.read_buf_exact(&mut data).await {
+                error!("skip snapshot error, {e}");
+                return;
+            }
+            skipped += len;
+        }
+        let mut offset = offset;
+        let start = Instant::now();
+        let mut sent = 0;
         #[allow(clippy::arithmetic_side_effects)] // can't overflow
         while offset < snapshot.size() {
             let len: u64 =
@@ -830,17 +1031,36 @@ fn install_snapshot_stream(
                 offset,
                 data: data.freeze(),
                 done: (offset + len) == snapshot.size(),
+                transfer_id,
             };
 
             offset += len;
-        }
-        // TODO: Shall we clean snapshot after stream generation complete
-        if let Err(e) = snapshot.clean().await {
-            error!("snapshot clean error, {e}");
+            sent += len;
+            // ends the stream to interrupt the transfer
+            fail_point!("during_snapshot_send", |_| {});
+            if let Some(delay) = throttle_delay(sent, rate_limit, start.elapsed()) {
+                tokio::time::sleep(delay).await;
+            }
         }
     }
 }
 
+/// How long to wait after sending `sent` bytes in `elapsed` to keep the throughput
+/// under `rate_limit` bytes per second, `0` means unlimited
+fn throttle_delay(sent: u64, rate_limit: u64, elapsed: Duration) -> Option<Duration> {
+    if rate_limit == 0 {
+        return None;
+    }
+    let expected = Duration::from_millis(
+        sent.saturating_mul(1000)
+            .checked_div(rate_limit)
+            .unwrap_or_else(|| unreachable!("rate limit is not zero")),
+    );
+    expected
+        .checked_sub(elapsed)
+        .filter(|delay| !delay.is_zero())
+}
+
 #[cfg(test)]
 mod tests {
     use bytes::Bytes;
@@ -865,13 +1085,16 @@ mod tests {
         let stream = install_snapshot_stream(
             0,
             123,
-            Snapshot::new(
+            Arc::new(Mutex::new(Snapshot::new(
                 SnapshotMeta {
                     last_included_index: 1,
                     last_included_term: 1,
                 },
                 snapshot,
-            ),
+            ))),
+            1,
+            0,
+            0,
         );
         pin_mut!(stream);
         let mut sum = 0;
@@ -885,4 +1108,93 @@ mod tests {
         }
         assert_eq!(sum, SNAPSHOT_SIZE);
     }
+
+    #[traced_test]
+    #[tokio::test]
+    #[abort_on_panic]
+    async fn install_snapshot_stream_should_resume_from_offset() {
+        const SNAPSHOT_SIZE: u64 = 200 * 1024;
+        const OFFSET: u64 = 100 * 1024;
+        let mut snapshot = EngineSnapshot::new_for_receiving(EngineType::Memory).unwrap();
+        let data: Vec<u8> = (0..SNAPSHOT_SIZE).map(|i| (i % 251) as u8).collect();
+        snapshot.write_all(Bytes::from(data.clone())).await.unwrap();
+        let snapshot = Arc::new(Mutex::new(Snapshot::new(
+            SnapshotMeta {
+                last_included_index: 1,
+                last_included_term: 1,
+            },
+            snapshot,
+        )));
+        // the snapshot is kept after the stream, so the transfer can be resumed again
+        for _ in 0..2 {
+            let stream = install_snapshot_stream(0, 123, Arc::clone(&snapshot), 1, OFFSET, 0);
+            pin_mut!(stream);
+            let mut received = Vec::new();
+            let mut expected_offset = OFFSET;
+            while let Some(req) = stream.next().await {
+                assert_eq!(req.offset, expected_offset);
+                expected_offset += req.data.len() as u64;
+                received.extend_from_slice(&req.data);
+            }
+            assert_eq!(received, data[OFFSET.numeric_cast::<usize>()..]);
+        }
+    }
+
+    #[cfg(feature = "failpoints")]
+    #[traced_test]
+    #[tokio::test]
+    #[abort_on_panic]
+    async fn install_snapshot_stream_should_be_interrupted_by_failpoint() {
+        const SNAPSHOT_SIZE: u64 = 200 * 1024;
+        let scenario = fail::FailScenario::setup();
+        let mut snapshot = EngineSnapshot::new_for_receiving(EngineType::Memory).unwrap();
+        snapshot
+            .write_all(Bytes::from(vec![0; SNAPSHOT_SIZE.numeric_cast()]))
+            .await
+            .unwrap();
+        let snapshot = Arc::new(Mutex::new(Snapshot::new(
+            SnapshotMeta {
+                last_included_index: 1,
+                last_included_term: 1,
+            },
+            snapshot,
+        )));
+        fail::cfg("during_snapshot_send", "1*return").unwrap();
+        let stream = install_snapshot_stream(0, 123, snapshot, 1, 0, 0);
+        pin_mut!(stream);
+        let mut chunks = 0;
+        while let Some(req) = stream.next().await {
+            assert!(!req.done);
+            chunks += 1;
+        }
+        assert_eq!(chunks, 1);
+        scenario.teardown();
+    }
+
+    #[test]
+    fn throttle_delay_should_keep_the_rate_under_limit() {
+        assert_eq!(throttle_delay(1024, 0, Duration::ZERO), None);
+        assert_eq!(
+            throttle_delay(1024, 1024, Duration::from_millis(200)),
+            Some(Duration::from_millis(800))
+        );
+        assert_eq!(throttle_delay(1024, 1024, Duration::from_secs(2)), None);
+        assert_eq!(
+            throttle_delay(512, 1024, Duration::ZERO),
+            Some(Duration::from_millis(500))
+        );
+    }
+
+    #[cfg(not(madsim))]
+    #[test]
+    fn hostname_authority_should_skip_ip_addresses() {
+        assert_eq!(
+            hostname_authority("http://xline-0.xline:2380"),
+            Some("xline-0.xline:2380")
+        );
+        assert_eq!(hostname_authority("localhost:2380"), Some("localhost:2380"));
+        assert_eq!(hostname_authority("http://127.0.0.1:2380"), None);
+        assert_eq!(hostname_authority("http://[::1]:2380"), None);
+        assert_eq!(hostname_authority("xline-0"), None);
+    }
 }
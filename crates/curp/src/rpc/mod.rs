@@ -6,14 +6,15 @@ use curp_external_api::{
 };
 use prost::Message;
 use serde::{Deserialize, Serialize};
+use utils::version::Version;
 
 pub(crate) use self::proto::{
     commandpb::CurpError as CurpErrorWrapper,
     inner_messagepb::{
         inner_protocol_server::InnerProtocol, AppendEntriesRequest, AppendEntriesResponse,
-        InstallSnapshotRequest, InstallSnapshotResponse, TriggerShutdownRequest,
-        TriggerShutdownResponse, TryBecomeLeaderNowRequest, TryBecomeLeaderNowResponse,
-        VoteRequest, VoteResponse,
+        FetchSnapshotOffsetRequest, FetchSnapshotOffsetResponse, InstallSnapshotRequest,
+        InstallSnapshotResponse, TriggerShutdownRequest, TriggerShutdownResponse,
+        TryBecomeLeaderNowRequest, TryBecomeLeaderNowResponse, VoteRequest, VoteResponse,
     },
 };
 pub use self::proto::{
@@ -48,7 +49,13 @@ pub use self::proto::{
     },
     inner_messagepb::inner_protocol_server::InnerProtocolServer,
 };
-use crate::{cmd::Command, log_entry::LogEntry, members::ServerId, LogIndex};
+use crate::{
+    cmd::Command,
+    log_entry::LogEntry,
+    members::{NegotiatedVersion, ServerId},
+    snapshot::SnapshotMeta,
+    LogIndex,
+};
 
 /// Metrics
 #[cfg(feature = "client-metrics")]
@@ -56,7 +63,7 @@ mod metrics;
 
 /// Rpc connect
 pub(crate) mod connect;
-pub(crate) use connect::{connect, connects, inner_connects};
+pub(crate) use connect::{connect, connects, inner_connects, FromTonicChannel};
 
 // Skip for generated code
 #[allow(
@@ -318,6 +325,7 @@ impl WaitSyncedResponse {
 
 impl AppendEntriesRequest {
     /// Create a new `append_entries` request
+    #[allow(clippy::too_many_arguments)] // the fields of a request
     pub(crate) fn new<C: Command>(
         term: u64,
         leader_id: ServerId,
@@ -325,6 +333,7 @@ impl AppendEntriesRequest {
         prev_log_term: u64,
         entries: Vec<Arc<LogEntry<C>>>,
         leader_commit: LogIndex,
+        negotiated: NegotiatedVersion,
     ) -> bincode::Result<Self> {
         Ok(Self {
             term,
@@ -336,9 +345,25 @@ impl AppendEntriesRequest {
                 .map(|e| bincode::serialize(&e))
                 .collect::<bincode::Result<Vec<Vec<u8>>>>()?,
             leader_commit,
+            negotiated_version: negotiated
+                .version
+                .map(|v| v.to_string())
+                .unwrap_or_default(),
+            downgrade_target: negotiated
+                .downgrade_target
+                .map(|v| v.to_string())
+                .unwrap_or_default(),
         })
     }
 
+    /// Get the version negotiated by the cluster
+    pub(crate) fn negotiated(&self) -> NegotiatedVersion {
+        NegotiatedVersion::new(
+            self.negotiated_version.parse().ok(),
+            self.downgrade_target.parse().ok(),
+        )
+    }
+
     /// Get log entries
     pub(crate) fn entries<C: Command>(&self) -> bincode::Result<Vec<LogEntry<C>>> {
         self.entries
@@ -355,6 +380,7 @@ impl AppendEntriesResponse {
             term,
             success: false,
             hint_index,
+            version: String::new(),
         }
     }
 
@@ -364,8 +390,20 @@ impl AppendEntriesResponse {
             term,
             success: true,
             hint_index: 0,
+            version: String::new(),
         }
     }
+
+    /// Attach the binary version of the follower
+    pub(crate) fn with_version(mut self, version: Option<Version>) -> Self {
+        self.version = version.map(|v| v.to_string()).unwrap_or_default();
+        self
+    }
+
+    /// Get the binary version of the follower, `None` if it is unknown
+    pub(crate) fn version(&self) -> Option<Version> {
+        self.version.parse().ok()
+    }
 }
 
 impl VoteRequest {
@@ -440,6 +478,32 @@ impl InstallSnapshotResponse {
     }
 }
 
+impl FetchSnapshotOffsetRequest {
+    /// Create a new fetch snapshot offset request
+    pub(crate) fn new(meta: SnapshotMeta, transfer_id: u64) -> Self {
+        Self {
+            last_included_index: meta.last_included_index,
+            last_included_term: meta.last_included_term,
+            transfer_id,
+        }
+    }
+
+    /// Get the metadata of the requested snapshot
+    pub(crate) fn meta(&self) -> SnapshotMeta {
+        SnapshotMeta {
+            last_included_index: self.last_included_index,
+            last_included_term: self.last_included_term,
+        }
+    }
+}
+
+impl FetchSnapshotOffsetResponse {
+    /// Create a new fetch snapshot offset response
+    pub(crate) fn new(offset: u64) -> Self {
+        Self { offset }
+    }
+}
+
 impl IdSet {
     /// Create a new `IdSet`
     pub(crate) fn new(inflight_ids: Vec<InflightId>) -> Self {
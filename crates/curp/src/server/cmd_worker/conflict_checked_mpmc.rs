@@ -9,14 +9,15 @@ use std::{
     sync::Arc,
 };
 
+use async_trait::async_trait;
 use tokio::sync::oneshot;
 use tracing::{debug, error};
 use utils::task_manager::{tasks::TaskName, Listener, State, TaskManager};
 
 use self::cart::Cart;
-use super::{CEEvent, CEEventTx};
+use super::{CEEvent, CEEventTx, TaskRxApi};
 use crate::{
-    cmd::{Command, CommandExecutor},
+    cmd::{Command, CommandExecutor, Priority},
     log_entry::{EntryData, LogEntry},
     rpc::ProposeId,
     snapshot::{Snapshot, SnapshotMeta},
@@ -75,6 +76,76 @@ impl<C: Command> Task<C> {
     }
 }
 
+/// Senders of the ready tasks, one queue for each priority
+struct TaskTx<C: Command> {
+    /// Queue of the high priority tasks
+    high: flume::Sender<Task<C>>,
+    /// Queue of the normal priority tasks
+    normal: flume::Sender<Task<C>>,
+    /// Queue of the low priority tasks
+    low: flume::Sender<Task<C>>,
+}
+
+impl<C: Command> TaskTx<C> {
+    /// Send a task to the queue of its priority
+    fn send(&self, task: Task<C>, priority: Priority) -> Result<(), flume::SendError<Task<C>>> {
+        // `Priority` is non-exhaustive, priorities added later are treated as normal
+        #[allow(clippy::wildcard_enum_match_arm)]
+        match priority {
+            Priority::High => self.high.send(task),
+            Priority::Low => self.low.send(task),
+            _ => self.normal.send(task),
+        }
+    }
+}
+
+/// Receiver of the ready tasks, tasks of a higher priority are received first. Ready
+/// tasks never conflict with each other, so reordering them is safe.
+#[derive(Clone)]
+pub(in crate::server) struct TaskRx<C: Command> {
+    /// Queue of the high priority tasks
+    high: flume::Receiver<Task<C>>,
+    /// Queue of the normal priority tasks
+    normal: flume::Receiver<Task<C>>,
+    /// Queue of the low priority tasks
+    low: flume::Receiver<Task<C>>,
+}
+
+#[async_trait]
+impl<C: Command> TaskRxApi<C> for TaskRx<C> {
+    /// Receive the ready task of the highest priority, wait if there is none
+    async fn recv(&self) -> Result<Task<C>, flume::RecvError> {
+        match self.try_recv() {
+            Ok(task) => return Ok(task),
+            Err(flume::TryRecvError::Disconnected) => return Err(flume::RecvError::Disconnected),
+            Err(flume::TryRecvError::Empty) => {}
+        }
+        tokio::select! {
+            biased;
+            task = self.high.recv_async() => task,
+            task = self.normal.recv_async() => task,
+            task = self.low.recv_async() => task,
+        }
+    }
+
+    /// Try to receive the ready task of the highest priority
+    fn try_recv(&self) -> Result<Task<C>, flume::TryRecvError> {
+        self.high
+            .try_recv()
+            .or_else(|_| self.normal.try_recv())
+            .or_else(|_| self.low.try_recv())
+    }
+}
+
+/// Scheduling priority of the tasks of a log entry, entries other than commands are
+/// scheduled first
+fn entry_priority<C: Command>(entry: &LogEntry<C>) -> Priority {
+    match entry.entry_data {
+        EntryData::Command(ref cmd) => cmd.priority(),
+        _ => Priority::High,
+    }
+}
+
 /// Vertex
 #[derive(Debug)]
 struct Vertex<C: Command> {
@@ -198,14 +269,14 @@ struct Filter<C: Command, CE> {
     /// Next vertex id
     next_id: u64,
     /// Send task to users
-    filter_tx: flume::Sender<Task<C>>,
+    filter_tx: TaskTx<C>,
     /// Command Executor
     cmd_executor: Arc<CE>,
 }
 
 impl<C: Command, CE: CommandExecutor<C>> Filter<C, CE> {
     /// Create a new filter that checks conflict in between msgs
-    fn new(filter_tx: flume::Sender<Task<C>>, ce: Arc<CE>) -> Self {
+    fn new(filter_tx: TaskTx<C>, ce: Arc<CE>) -> Self {
         Self {
             cmd_vid: HashMap::new(),
             vs: HashMap::new(),
@@ -363,7 +434,7 @@ impl<C: Command, CE: CommandExecutor<C>> Filter<C, CE> {
                         vid,
                         inner: Cart::new(TaskType::SpecExe(Arc::clone(entry), prepare_err)),
                     };
-                    if let Err(e) = self.filter_tx.send(task) {
+                    if let Err(e) = self.filter_tx.send(task, entry_priority(entry)) {
                         error!("failed to send task through filter, {e}");
                     }
                     false
@@ -374,13 +445,20 @@ impl<C: Command, CE: CommandExecutor<C>> Filter<C, CE> {
                         vid,
                         inner: Cart::new(TaskType::AS(Arc::clone(entry), prepare)),
                     };
-                    if let Err(e) = self.filter_tx.send(task) {
+                    if let Err(e) = self.filter_tx.send(task, entry_priority(entry)) {
                         error!("failed to send task through filter, {e}");
                     }
                     false
                 }
-                (ExeState::Executed(false), AsState::AfterSyncReady(_))
-                | (ExeState::Executed(_), AsState::AfterSynced) => true,
+                (ExeState::Executed(false), AsState::AfterSyncReady(_)) => {
+                    // the command failed to execute so it is not after synced, but its
+                    // index is applied, otherwise the applied index may not advance
+                    if let Err(e) = self.cmd_executor.set_last_applied(entry.index) {
+                        error!("failed to set last_applied, {e}");
+                    }
+                    true
+                }
+                (ExeState::Executed(_), AsState::AfterSynced) => true,
                 (ExeState::Executing | ExeState::Executed(_), AsState::NotSynced(_))
                 | (ExeState::Executing, AsState::AfterSyncReady(_) | AsState::AfterSyncing)
                 | (ExeState::Executed(true), AsState::AfterSyncing) => false,
@@ -399,7 +477,7 @@ impl<C: Command, CE: CommandExecutor<C>> Filter<C, CE> {
                         inner: Cart::new(TaskType::Reset(*snapshot, tx)),
                     };
                     *st = OnceState::Doing;
-                    if let Err(e) = self.filter_tx.send(task) {
+                    if let Err(e) = self.filter_tx.send(task, Priority::High) {
                         error!("failed to send task through filter, {e}");
                     }
                     false
@@ -418,7 +496,7 @@ impl<C: Command, CE: CommandExecutor<C>> Filter<C, CE> {
                         inner: Cart::new(TaskType::Snapshot(meta, tx)),
                     };
                     *st = OnceState::Doing;
-                    if let Err(e) = self.filter_tx.send(task) {
+                    if let Err(e) = self.filter_tx.send(task, Priority::High) {
                         error!("failed to send task through filter, {e}");
                     }
                     false
@@ -536,15 +614,23 @@ impl<C: Command, CE: CommandExecutor<C>> Filter<C, CE> {
 pub(in crate::server) fn channel<C: Command, CE: CommandExecutor<C>>(
     ce: Arc<CE>,
     task_manager: Arc<TaskManager>,
-) -> (
-    CEEventTx<C>,
-    flume::Receiver<Task<C>>,
-    flume::Sender<(Task<C>, bool)>,
-) {
+) -> (CEEventTx<C>, TaskRx<C>, flume::Sender<(Task<C>, bool)>) {
     // recv from user, insert it into filter
     let (send_tx, filter_rx) = flume::unbounded();
-    // recv from filter, pass the msg to user
-    let (filter_tx, recv_rx) = flume::unbounded();
+    // recv from filter, pass the msg to user in the order of priorities
+    let (high_tx, high_rx) = flume::unbounded();
+    let (normal_tx, normal_rx) = flume::unbounded();
+    let (low_tx, low_rx) = flume::unbounded();
+    let filter_tx = TaskTx {
+        high: high_tx,
+        normal: normal_tx,
+        low: low_tx,
+    };
+    let recv_rx = TaskRx {
+        high: high_rx,
+        normal: normal_rx,
+        low: low_rx,
+    };
     // recv from user to mark a msg done
     let (done_tx, done_rx) = flume::unbounded::<(Task<C>, bool)>();
     task_manager.spawn(TaskName::ConflictCheckedMpmc, |n| {
@@ -556,7 +642,7 @@ pub(in crate::server) fn channel<C: Command, CE: CommandExecutor<C>>(
 
 /// Conflict checked mpmc task
 async fn conflict_checked_mpmc_task<C: Command, CE: CommandExecutor<C>>(
-    filter_tx: flume::Sender<Task<C>>,
+    filter_tx: TaskTx<C>,
     filter_rx: flume::Receiver<CEEvent<C>>,
     ce: Arc<CE>,
     done_rx: flume::Receiver<(Task<C>, bool)>,
@@ -594,3 +680,50 @@ async fn conflict_checked_mpmc_task<C: Command, CE: CommandExecutor<C>>(
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use curp_test_utils::test_cmd::TestCommand;
+
+    use super::*;
+
+    fn reset_task(vid: u64) -> Task<TestCommand> {
+        let (tx, _rx) = oneshot::channel();
+        Task {
+            vid,
+            inner: Cart::new(TaskType::Reset(None, tx)),
+        }
+    }
+
+    #[tokio::test]
+    async fn tasks_should_be_received_in_the_order_of_priorities() {
+        let (high_tx, high_rx) = flume::unbounded();
+        let (normal_tx, normal_rx) = flume::unbounded();
+        let (low_tx, low_rx) = flume::unbounded();
+        let task_tx = TaskTx {
+            high: high_tx,
+            normal: normal_tx,
+            low: low_tx,
+        };
+        let task_rx = TaskRx {
+            high: high_rx,
+            normal: normal_rx,
+            low: low_rx,
+        };
+        task_tx.send(reset_task(0), Priority::Low).unwrap();
+        task_tx.send(reset_task(1), Priority::Normal).unwrap();
+        task_tx.send(reset_task(2), Priority::High).unwrap();
+        task_tx.send(reset_task(3), Priority::Normal).unwrap();
+        let mut vids = vec![];
+        for _ in 0..4 {
+            vids.push(task_rx.recv().await.unwrap().vid);
+        }
+        assert_eq!(vids, vec![2, 1, 3, 0]);
+        assert!(matches!(
+            task_rx.try_recv(),
+            Err(flume::TryRecvError::Empty)
+        ));
+        drop(task_tx);
+        assert!(task_rx.recv().await.is_err());
+    }
+}
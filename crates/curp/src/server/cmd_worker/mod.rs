@@ -11,7 +11,7 @@ use tokio::sync::oneshot;
 use tracing::{debug, error, info, warn};
 use utils::task_manager::{tasks::TaskName, Listener, TaskManager};
 
-use self::conflict_checked_mpmc::Task;
+use self::conflict_checked_mpmc::{Task, TaskRx};
 use super::raw_curp::RawCurp;
 use crate::{
     cmd::{Command, CommandExecutor},
@@ -238,7 +238,12 @@ async fn worker_as<C: Command, CE: CommandExecutor<C>, RC: RoleChange>(
                 .set_node_state(node_id, name.clone(), client_urls.clone());
             true
         }
-        EntryData::Empty => true,
+        EntryData::Empty => {
+            if let Err(e) = ce.set_last_applied(entry.index) {
+                error!("failed to set last_applied, {e}");
+            }
+            true
+        }
     };
     ce.trigger(entry.inflight_id(), entry.index);
     success
@@ -312,10 +317,6 @@ async fn worker_snapshot<C: Command, CE: CommandExecutor<C>, RC: RoleChange>(
 #[derive(Debug, Clone)]
 pub(super) struct CEEventTx<C: Command>(flume::Sender<CEEvent<C>>, Arc<TaskManager>);
 
-/// Recv cmds that need to be executed
-#[derive(Clone)]
-struct TaskRx<C: Command>(flume::Receiver<Task<C>>);
-
 /// Send cmd to background execution worker
 #[cfg_attr(test, automock)]
 pub(crate) trait CEEventTxApi<C: Command>: Send + Sync + 'static {
@@ -381,22 +382,11 @@ trait TaskRxApi<C: Command> {
     fn try_recv(&self) -> Result<Task<C>, flume::TryRecvError>;
 }
 
-#[async_trait]
-impl<C: Command> TaskRxApi<C> for TaskRx<C> {
-    async fn recv(&self) -> Result<Task<C>, flume::RecvError> {
-        self.0.recv_async().await
-    }
-
-    fn try_recv(&self) -> Result<Task<C>, flume::TryRecvError> {
-        self.0.try_recv()
-    }
-}
-
 /// Run cmd execute workers. Each cmd execute worker will continually fetch task to perform from `task_rx`.
 pub(super) fn start_cmd_workers<C: Command, CE: CommandExecutor<C>, RC: RoleChange>(
     cmd_executor: Arc<CE>,
     curp: Arc<RawCurp<C, RC>>,
-    task_rx: flume::Receiver<Task<C>>,
+    task_rx: TaskRx<C>,
     done_tx: flume::Sender<(Task<C>, bool)>,
 ) {
     let n_workers: usize = curp.cfg().cmd_workers.numeric_cast();
@@ -406,7 +396,7 @@ pub(super) fn start_cmd_workers<C: Command, CE: CommandExecutor<C>, RC: RoleChan
         .take(n_workers)
         .for_each(|(task_rx, done_tx, curp, ce)| {
             task_manager.spawn(TaskName::CmdWorker, |n| {
-                cmd_worker(TaskRx(task_rx), done_tx, curp, ce, n)
+                cmd_worker(task_rx, done_tx, curp, ce, n)
             });
         });
 }
@@ -1,19 +1,14 @@
-use std::{
-    collections::HashMap,
-    fmt::Debug,
-    sync::Arc,
-    time::{Duration, Instant},
-};
+use std::{collections::HashMap, fmt::Debug, sync::Arc, time::Duration};
 
 use clippy_utilities::{NumericCast, OverflowArithmetic};
-use engine::{SnapshotAllocator, SnapshotApi};
+use engine::{Snapshot as EngineSnapshot, SnapshotAllocator, SnapshotApi};
 use event_listener::Event;
 use futures::{pin_mut, stream::FuturesUnordered, Stream, StreamExt};
 use madsim::rand::{thread_rng, Rng};
 use parking_lot::{Mutex, RwLock};
 use tokio::{
     sync::{broadcast, mpsc},
-    time::MissedTickBehavior,
+    time::{Instant, MissedTickBehavior},
 };
 #[cfg(not(madsim))]
 use tonic::transport::ClientTlsConfig;
@@ -45,12 +40,12 @@ use crate::{
         connect::{InnerConnectApi, InnerConnectApiWrapper},
         AppendEntriesRequest, AppendEntriesResponse, ConfChange, ConfChangeType, CurpError,
         FetchClusterRequest, FetchClusterResponse, FetchReadStateRequest, FetchReadStateResponse,
-        InstallSnapshotRequest, InstallSnapshotResponse, LeaseKeepAliveMsg, MoveLeaderRequest,
-        MoveLeaderResponse, ProposeConfChangeRequest, ProposeConfChangeResponse, ProposeRequest,
-        ProposeResponse, PublishRequest, PublishResponse, ShutdownRequest, ShutdownResponse,
-        TriggerShutdownRequest, TriggerShutdownResponse, TryBecomeLeaderNowRequest,
-        TryBecomeLeaderNowResponse, VoteRequest, VoteResponse, WaitSyncedRequest,
-        WaitSyncedResponse,
+        FetchSnapshotOffsetRequest, FetchSnapshotOffsetResponse, InstallSnapshotRequest,
+        InstallSnapshotResponse, LeaseKeepAliveMsg, MoveLeaderRequest, MoveLeaderResponse,
+        ProposeConfChangeRequest, ProposeConfChangeResponse, ProposeRequest, ProposeResponse,
+        PublishRequest, PublishResponse, ShutdownRequest, ShutdownResponse, TriggerShutdownRequest,
+        TriggerShutdownResponse, TryBecomeLeaderNowRequest, TryBecomeLeaderNowResponse,
+        VoteRequest, VoteResponse, WaitSyncedRequest, WaitSyncedResponse,
     },
     server::{cmd_worker::CEEventTxApi, metrics, raw_curp::SyncAction, storage::db::DB},
     snapshot::{Snapshot, SnapshotMeta},
@@ -68,6 +63,21 @@ pub(super) struct CurpNode<C: Command, RC: RoleChange> {
     storage: Arc<dyn StorageApi<Command = C>>,
     /// Snapshot allocator
     snapshot_allocator: Box<dyn SnapshotAllocator>,
+    /// The partially received snapshot, kept for resuming an interrupted transfer
+    partial_snapshot: Mutex<Option<PartialSnapshot>>,
+}
+
+/// A snapshot whose transfer is not finished yet
+struct PartialSnapshot {
+    /// Metadata of the snapshot
+    meta: SnapshotMeta,
+    /// Id of the transfer, the same meta doesn't mean the same bytes, e.g. a snapshot
+    /// of the same index sent by another leader, so only the same transfer resumes it
+    transfer_id: u64,
+    /// The receiving snapshot
+    snapshot: EngineSnapshot,
+    /// Number of bytes received
+    received: u64,
 }
 
 /// Handlers for clients
@@ -166,11 +176,16 @@ impl<C: Command, RC: RoleChange> CurpNode<C, RC> {
             req.leader_commit,
         );
         let resp = match result {
-            Ok(term) => AppendEntriesResponse::new_accept(term),
+            Ok(term) => {
+                self.curp
+                    .cluster()
+                    .follow_negotiated_version(req.negotiated());
+                AppendEntriesResponse::new_accept(term)
+            }
             Err((term, hint)) => AppendEntriesResponse::new_reject(term, hint),
         };
 
-        Ok(resp)
+        Ok(resp.with_version(self.curp.cluster().binary_version()))
     }
 
     /// Handle `Vote` requests
@@ -260,22 +275,41 @@ impl<C: Command, RC: RoleChange> CurpNode<C, RC> {
     }
 
     /// Handle `InstallSnapshot` stream
-    #[allow(clippy::arithmetic_side_effects)] // can't overflow
     pub(super) async fn install_snapshot<E: std::error::Error + 'static>(
         &self,
         req_stream: impl Stream<Item = Result<InstallSnapshotRequest, E>>,
     ) -> Result<InstallSnapshotResponse, CurpError> {
         metrics::get().apply_snapshot_in_progress.add(1, &[]);
         let start = Instant::now();
+        let mut partial = None;
+        let result = self.receive_snapshot(req_stream, &mut partial).await;
+        // keep the received part so that the leader can resume the transfer from it
+        if let Some(partial) = partial {
+            debug!(
+                "{} keeps a partial snapshot of {} bytes, {:?}",
+                self.curp.id(),
+                partial.received,
+                partial.meta
+            );
+            *self.partial_snapshot.lock() = Some(partial);
+        }
+        if result.is_ok() {
+            metrics::get()
+                .snapshot_install_total_duration_seconds
+                .record(start.elapsed().as_secs(), &[]);
+        }
+        metrics::get().apply_snapshot_in_progress.add(-1, &[]);
+        result
+    }
+
+    /// Receive snapshot chunks into `partial`, a snapshot left in `partial` is incomplete
+    #[allow(clippy::arithmetic_side_effects)] // can't overflow
+    async fn receive_snapshot<E: std::error::Error + 'static>(
+        &self,
+        req_stream: impl Stream<Item = Result<InstallSnapshotRequest, E>>,
+        partial: &mut Option<PartialSnapshot>,
+    ) -> Result<InstallSnapshotResponse, CurpError> {
         pin_mut!(req_stream);
-        let mut snapshot = self
-            .snapshot_allocator
-            .allocate_new_snapshot()
-            .await
-            .map_err(|err| {
-                error!("failed to allocate a new snapshot, error: {err}");
-                CurpError::internal(format!("failed to allocate a new snapshot, error: {err}"))
-            })?;
         while let Some(req) = req_stream.next().await {
             let req = req?;
             if !self.curp.verify_install_snapshot(
@@ -286,21 +320,44 @@ impl<C: Command, RC: RoleChange> CurpNode<C, RC> {
             ) {
                 return Ok(InstallSnapshotResponse::new(self.curp.term()));
             }
+            if partial.is_none() {
+                let meta = SnapshotMeta {
+                    last_included_index: req.last_included_index,
+                    last_included_term: req.last_included_term,
+                };
+                *partial = Some(
+                    self.resume_or_allocate_snapshot(meta, req.transfer_id, req.offset)
+                        .await?,
+                );
+            }
+            let Some(ref mut receiving) = *partial else {
+                unreachable!("the receiving snapshot must exist");
+            };
+            if receiving.received != req.offset {
+                return Err(CurpError::internal(format!(
+                    "snapshot chunk at offset {} doesn't follow the received {} bytes",
+                    req.offset, receiving.received
+                )));
+            }
             let req_data_len = req.data.len().numeric_cast::<u64>();
-            snapshot.write_all(req.data).await.map_err(|err| {
-                error!("can't write snapshot data, {err:?}");
-                err
-            })?;
+            receiving
+                .snapshot
+                .write_all(req.data)
+                .await
+                .map_err(|err| {
+                    error!("can't write snapshot data, {err:?}");
+                    err
+                })?;
+            receiving.received += req_data_len;
             if req.done {
+                let Some(PartialSnapshot { meta, snapshot, .. }) = partial.take() else {
+                    unreachable!("the receiving snapshot must exist");
+                };
                 debug_assert_eq!(
                     snapshot.size(),
                     req.offset + req_data_len,
                     "snapshot corrupted"
                 );
-                let meta = SnapshotMeta {
-                    last_included_index: req.last_included_index,
-                    last_included_term: req.last_included_term,
-                };
                 let snapshot = Snapshot::new(meta, snapshot);
                 info!(
                     "{} successfully received a snapshot, {snapshot:?}",
@@ -315,10 +372,6 @@ impl<C: Command, RC: RoleChange> CurpNode<C, RC> {
                             "failed to reset the command executor by snapshot, {err}"
                         ))
                     })?;
-                metrics::get().apply_snapshot_in_progress.add(-1, &[]);
-                metrics::get()
-                    .snapshot_install_total_duration_seconds
-                    .record(start.elapsed().as_secs(), &[]);
                 return Ok(InstallSnapshotResponse::new(self.curp.term()));
             }
         }
@@ -327,18 +380,108 @@ impl<C: Command, RC: RoleChange> CurpNode<C, RC> {
         ))
     }
 
+    /// Take the kept partial snapshot if the same transfer resumes from its end,
+    /// otherwise drop it and allocate a new one
+    async fn resume_or_allocate_snapshot(
+        &self,
+        meta: SnapshotMeta,
+        transfer_id: u64,
+        offset: u64,
+    ) -> Result<PartialSnapshot, CurpError> {
+        let kept = self.partial_snapshot.lock().take();
+        if let Some(mut kept) = kept {
+            if offset != 0
+                && kept.meta == meta
+                && kept.transfer_id == transfer_id
+                && kept.received == offset
+            {
+                info!(
+                    "{} resumes receiving the snapshot from offset {offset}, {meta:?}",
+                    self.curp.id()
+                );
+                return Ok(kept);
+            }
+            if let Err(err) = kept.snapshot.clean().await {
+                error!("failed to clean the stale partial snapshot, {err}");
+            }
+        }
+        if offset != 0 {
+            return Err(CurpError::internal(format!(
+                "can't resume the snapshot from offset {offset}, no matching partial snapshot"
+            )));
+        }
+        let snapshot = self
+            .snapshot_allocator
+            .allocate_new_snapshot()
+            .await
+            .map_err(|err| {
+                error!("failed to allocate a new snapshot, error: {err}");
+                CurpError::internal(format!("failed to allocate a new snapshot, error: {err}"))
+            })?;
+        Ok(PartialSnapshot {
+            meta,
+            transfer_id,
+            snapshot,
+            received: 0,
+        })
+    }
+
+    /// Handle `FetchSnapshotOffset` requests
+    pub(super) fn fetch_snapshot_offset(
+        &self,
+        req: &FetchSnapshotOffsetRequest,
+    ) -> FetchSnapshotOffsetResponse {
+        let offset = self
+            .partial_snapshot
+            .lock()
+            .as_ref()
+            .filter(|partial| partial.meta == req.meta() && partial.transfer_id == req.transfer_id)
+            .map_or(0, |partial| partial.received);
+        FetchSnapshotOffsetResponse::new(offset)
+    }
+
     /// Handle `FetchReadState` requests
     #[allow(clippy::needless_pass_by_value)] // To keep type consistent with other request handlers
-    pub(super) fn fetch_read_state(
+    pub(super) async fn fetch_read_state(
         &self,
         req: FetchReadStateRequest,
     ) -> Result<FetchReadStateResponse, CurpError> {
         self.check_cluster_version(req.cluster_version)?;
         let cmd = req.cmd()?;
-        let state = self.curp.handle_fetch_read_state(Arc::new(cmd));
+        self.confirm_leadership().await?;
+        let state = self.curp.handle_fetch_read_state(Arc::new(cmd))?;
         Ok(FetchReadStateResponse::new(state))
     }
 
+    /// Confirm that this node is still the leader, so that a read state fetched from it
+    /// is up to date. Like the read index of raft, it sends heartbeats to the followers and
+    /// waits for a quorum of voters to accept them.
+    async fn confirm_leadership(&self) -> Result<(), CurpError> {
+        /// The max number of heartbeat intervals to wait for the confirmation
+        const MAX_WAIT_INTERVALS: u32 = 3;
+        let since = Instant::now();
+        let max_wait = self
+            .curp
+            .cfg()
+            .heartbeat_interval
+            .saturating_mul(MAX_WAIT_INTERVALS);
+        self.curp.trigger_heartbeats();
+        loop {
+            let acked = self.curp.listen_ack();
+            let (leader_id, term, is_leader) = self.curp.leader();
+            if !is_leader {
+                return Err(CurpError::redirect(leader_id, term));
+            }
+            if self.curp.leadership_confirmed_since(since) {
+                return Ok(());
+            }
+            let remaining = max_wait.saturating_sub(since.elapsed());
+            if tokio::time::timeout(remaining, acked).await.is_err() {
+                return Err(CurpError::internal("the leadership can not be confirmed"));
+            }
+        }
+    }
+
     /// Handle `MoveLeader` requests
     pub(super) async fn move_leader(
         &self,
@@ -456,6 +599,8 @@ impl<C: Command, RC: RoleChange> CurpNode<C, RC> {
                         change.node_id,
                         change.address,
                         curp.client_tls_config().cloned(),
+                        curp.cfg().compression,
+                        curp.cfg().resolve_interval,
                     )
                     .await
                     {
@@ -521,6 +666,7 @@ impl<C: Command, RC: RoleChange> CurpNode<C, RC> {
         let connect_id = connect.id();
         let batch_timeout = curp.cfg().batch_timeout;
         let leader_event = curp.leader_event();
+        let heartbeat_event = curp.heartbeat_event();
 
         if !curp.is_leader() {
             tokio::select! {
@@ -544,6 +690,8 @@ impl<C: Command, RC: RoleChange> CurpNode<C, RC> {
                 },
                 _ = remove_event.listen() => return,
                 _now = ticker.tick() => hb_opt = false,
+                // a read asks the followers to confirm the leadership
+                _ = heartbeat_event.listen() => hb_opt = false,
                 res = tokio::time::timeout(batch_timeout, sync_event.listen()) => {
                     if let Err(_e) = res {
                         hb_opt = true;
@@ -623,10 +771,15 @@ impl<C: Command, RC: RoleChange> CurpNode<C, RC> {
             .into_iter()
             .map(|server_id| (server_id, Arc::new(Event::new())))
             .collect();
-        let connects = rpc::inner_connects(cluster_info.peers_addrs(), client_tls_config.as_ref())
-            .await
-            .map_err(|e| CurpError::internal(format!("parse peers addresses failed, err {e:?}")))?
-            .collect();
+        let connects = rpc::inner_connects(
+            cluster_info.peers_addrs(),
+            client_tls_config.as_ref(),
+            curp_cfg.compression,
+            curp_cfg.resolve_interval,
+        )
+        .await
+        .map_err(|e| CurpError::internal(format!("parse peers addresses failed, err {e:?}")))?
+        .collect();
         let (log_tx, log_rx) = mpsc::unbounded_channel();
         let cmd_board = Arc::new(RwLock::new(CommandBoard::new()));
         let lease_manager = Arc::new(RwLock::new(LeaseManager::new()));
@@ -679,6 +832,7 @@ impl<C: Command, RC: RoleChange> CurpNode<C, RC> {
             ce_event_tx,
             storage,
             snapshot_allocator,
+            partial_snapshot: Mutex::new(None),
         })
     }
 
@@ -811,6 +965,7 @@ impl<C: Command, RC: RoleChange> CurpNode<C, RC> {
             ae.prev_log_term,
             ae.entries,
             ae.leader_commit,
+            curp.cluster().negotiated(),
         )?;
 
         if is_heartbeat {
@@ -819,10 +974,18 @@ impl<C: Command, RC: RoleChange> CurpNode<C, RC> {
             debug!("{} send append_entries to {}", curp.id(), connect.id());
         }
 
+        let sent_at = Instant::now();
         let resp = connect
             .append_entries(req, curp.cfg().rpc_timeout)
             .await?
             .into_inner();
+        if resp.term == ae.term {
+            curp.record_append_entries_ack(connect.id(), ae.term, sent_at);
+            if let Some(version) = resp.version() {
+                curp.cluster().update_member_version(connect.id(), version);
+                let _negotiated = curp.cluster().negotiate_version();
+            }
+        }
 
         let Ok(ae_succeed) = curp.handle_append_entries_resp(
             connect.id(),
@@ -837,7 +1000,8 @@ impl<C: Command, RC: RoleChange> CurpNode<C, RC> {
         Ok((false, ae_succeed))
     }
 
-    /// Send snapshot
+    /// Send snapshot, an interrupted transfer is resumed from the part the follower
+    /// has received, at most `snapshot_max_resumes` times
     /// Return `tonic::Error` if meet network issue
     /// Return `leader_retires`
     async fn send_snapshot(
@@ -846,10 +1010,50 @@ impl<C: Command, RC: RoleChange> CurpNode<C, RC> {
         snapshot: Snapshot,
     ) -> Result<bool, CurpError> {
         let meta = snapshot.meta;
-        let resp = connect
-            .install_snapshot(curp.term(), curp.id(), snapshot)
-            .await?
-            .into_inner();
+        let snapshot = Arc::new(tokio::sync::Mutex::new(snapshot));
+        // the follower only resumes the snapshot received in this transfer
+        let transfer_id = thread_rng().gen::<u64>();
+        let mut offset = 0;
+        let mut resumes = 0;
+        let result = loop {
+            let err = match connect
+                .install_snapshot(
+                    curp.term(),
+                    curp.id(),
+                    Arc::clone(&snapshot),
+                    transfer_id,
+                    offset,
+                    curp.cfg().snapshot_rate_limit,
+                )
+                .await
+            {
+                Ok(resp) => break Ok(resp.into_inner()),
+                Err(err) => err,
+            };
+            if resumes >= curp.cfg().snapshot_max_resumes || !curp.is_leader() {
+                break Err(err);
+            }
+            resumes = resumes.overflow_add(1);
+            tokio::time::sleep(curp.cfg().heartbeat_interval).await;
+            offset = match connect
+                .fetch_snapshot_offset(meta, transfer_id, curp.cfg().rpc_timeout)
+                .await
+            {
+                Ok(resp) => resp.into_inner().offset,
+                Err(fetch_err) => {
+                    debug!("failed to fetch snapshot offset, {fetch_err:?}");
+                    0
+                }
+            };
+            warn!(
+                "snapshot to {} interrupted, {err:?}, resume from offset {offset}",
+                connect.id()
+            );
+        };
+        if let Err(err) = snapshot.lock().await.inner_mut().clean().await {
+            error!("snapshot clean error, {err}");
+        }
+        let resp = result?;
         Ok(curp
             .handle_snapshot_resp(connect.id(), meta, resp.term)
             .is_err())
@@ -1021,6 +1225,63 @@ mod tests {
         task_manager.shutdown(true).await;
     }
 
+    #[traced_test]
+    #[tokio::test]
+    async fn send_snapshot_should_resume_interrupted_transfer() {
+        let task_manager = Arc::new(TaskManager::new());
+        let curp = RawCurp::new_test(
+            3,
+            MockCEEventTxApi::<TestCommand>::default(),
+            mock_role_change(),
+            task_manager,
+        );
+        let s1_id = curp.cluster().get_id_by_name("S1").unwrap();
+        let mut mock_connect = MockInnerConnectApi::default();
+        mock_connect.expect_id().return_const(s1_id);
+        let mut seq = mockall::Sequence::new();
+        // every request of the transfer carries the id of the first one
+        let transfer_id = Arc::new(std::sync::atomic::AtomicU64::new(0));
+        let first_id = Arc::clone(&transfer_id);
+        mock_connect
+            .expect_install_snapshot()
+            .times(1)
+            .in_sequence(&mut seq)
+            .withf(move |_, _, _, id, offset, _| {
+                first_id.store(*id, std::sync::atomic::Ordering::SeqCst);
+                *offset == 0
+            })
+            .returning(|_, _, _, _, _, _| Err(tonic::Status::unavailable("disconnected")));
+        let fetch_id = Arc::clone(&transfer_id);
+        mock_connect
+            .expect_fetch_snapshot_offset()
+            .times(1)
+            .in_sequence(&mut seq)
+            .withf(move |_, id, _| *id == fetch_id.load(std::sync::atomic::Ordering::SeqCst))
+            .returning(|_, _, _| Ok(tonic::Response::new(FetchSnapshotOffsetResponse::new(1024))));
+        let resume_id = Arc::clone(&transfer_id);
+        mock_connect
+            .expect_install_snapshot()
+            .times(1)
+            .in_sequence(&mut seq)
+            .withf(move |_, _, _, id, offset, _| {
+                *offset == 1024 && *id == resume_id.load(std::sync::atomic::Ordering::SeqCst)
+            })
+            .returning(|term, _, _, _, _, _| {
+                Ok(tonic::Response::new(InstallSnapshotResponse::new(term)))
+            });
+        let snapshot = Snapshot::new(
+            SnapshotMeta {
+                last_included_index: 1,
+                last_included_term: 0,
+            },
+            EngineSnapshot::new_for_receiving(engine::EngineType::Memory).unwrap(),
+        );
+        let leader_retires = CurpNode::send_snapshot(&mock_connect, &curp, snapshot)
+            .await
+            .unwrap();
+        assert!(!leader_retires);
+    }
+
     #[traced_test]
     #[tokio::test]
     async fn tick_task_will_bcast_votes() {
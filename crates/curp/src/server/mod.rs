@@ -18,10 +18,11 @@ use crate::{
     role_change::RoleChange,
     rpc::{
         AppendEntriesRequest, AppendEntriesResponse, FetchClusterRequest, FetchClusterResponse,
-        FetchReadStateRequest, FetchReadStateResponse, InstallSnapshotRequest,
-        InstallSnapshotResponse, LeaseKeepAliveMsg, MoveLeaderRequest, MoveLeaderResponse,
-        ProposeConfChangeRequest, ProposeConfChangeResponse, ProposeRequest, ProposeResponse,
-        PublishRequest, PublishResponse, ShutdownRequest, ShutdownResponse, TriggerShutdownRequest,
+        FetchReadStateRequest, FetchReadStateResponse, FetchSnapshotOffsetRequest,
+        FetchSnapshotOffsetResponse, InstallSnapshotRequest, InstallSnapshotResponse,
+        LeaseKeepAliveMsg, MoveLeaderRequest, MoveLeaderResponse, ProposeConfChangeRequest,
+        ProposeConfChangeResponse, ProposeRequest, ProposeResponse, PublishRequest,
+        PublishResponse, ShutdownRequest, ShutdownResponse, TriggerShutdownRequest,
         TriggerShutdownResponse, TryBecomeLeaderNowRequest, TryBecomeLeaderNowResponse,
         VoteRequest, VoteResponse, WaitSyncedRequest, WaitSyncedResponse,
     },
@@ -146,7 +147,7 @@ impl<C: Command, RC: RoleChange> crate::rpc::Protocol for Rpc<C, RC> {
         request: tonic::Request<FetchReadStateRequest>,
     ) -> Result<tonic::Response<FetchReadStateResponse>, tonic::Status> {
         Ok(tonic::Response::new(
-            self.inner.fetch_read_state(request.into_inner())?,
+            self.inner.fetch_read_state(request.into_inner()).await?,
         ))
     }
 
@@ -216,6 +217,16 @@ impl<C: Command, RC: RoleChange> crate::rpc::InnerProtocol for Rpc<C, RC> {
         ))
     }
 
+    #[instrument(skip_all, name = "curp_fetch_snapshot_offset")]
+    async fn fetch_snapshot_offset(
+        &self,
+        request: tonic::Request<FetchSnapshotOffsetRequest>,
+    ) -> Result<tonic::Response<FetchSnapshotOffsetResponse>, tonic::Status> {
+        Ok(tonic::Response::new(
+            self.inner.fetch_snapshot_offset(request.get_ref()),
+        ))
+    }
+
     #[instrument(skip_all, name = "curp_try_become_leader_now")]
     async fn try_become_leader_now(
         &self,
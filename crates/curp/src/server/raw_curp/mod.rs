@@ -22,11 +22,15 @@ use std::{
 use clippy_utilities::{NumericCast, OverflowArithmetic};
 use dashmap::DashMap;
 use derive_builder::Builder;
-use event_listener::Event;
+use event_listener::{Event, EventListener};
+use fail::fail_point;
 use itertools::Itertools;
 use opentelemetry::KeyValue;
 use parking_lot::{Mutex, RwLock, RwLockUpgradableReadGuard, RwLockWriteGuard};
-use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio::{
+    sync::{broadcast, mpsc, oneshot},
+    time::Instant,
+};
 #[cfg(not(madsim))]
 use tonic::transport::ClientTlsConfig;
 use tracing::{
@@ -316,6 +320,12 @@ struct Context<C: Command, RC: RoleChange> {
     /// Become leader event
     #[builder(setter(skip))]
     leader_event: Arc<Event>,
+    /// Event to send heartbeats to the followers immediately
+    #[builder(setter(skip))]
+    heartbeat_event: Arc<Event>,
+    /// Event notified when a follower accepts an `append_entries`
+    #[builder(setter(skip))]
+    ack_event: Event,
     /// Leader change callback
     role_change: RC,
     /// Conf change tx, used to update sync tasks
@@ -376,6 +386,8 @@ impl<C: Command, RC: RoleChange> ContextBuilder<C, RC> {
                 None => return Err(ContextBuilderError::UninitializedField("sync_events")),
             },
             leader_event: Arc::new(Event::new()),
+            heartbeat_event: Arc::new(Event::new()),
+            ack_event: Event::new(),
             role_change: match self.role_change.take() {
                 Some(value) => value,
                 None => return Err(ContextBuilderError::UninitializedField("role_change")),
@@ -436,6 +448,11 @@ impl<C: Command, RC: RoleChange> RawCurp<C, RC> {
             return None;
         }
         let mut st_w = RwLockUpgradableReadGuard::upgrade(st_r);
+        // learners never start an election, they only replicate logs from the leader
+        if st_w.role != Role::Leader && self.cluster().self_member().is_learner() {
+            self.reset_election_tick();
+            return None;
+        }
         let mut cst_l = self.cst.lock();
         let log_r = self.log.upgradable_read();
         match st_w.role {
@@ -811,6 +828,15 @@ impl<C: Command, RC: RoleChange> RawCurp<C, RC> {
             return Err(Some(st_w.term));
         }
 
+        // learners are not allowed to be elected
+        if self
+            .cluster()
+            .get(&candidate_id)
+            .is_some_and(|m| m.is_learner)
+        {
+            return Err(Some(st_w.term));
+        }
+
         // check if voted before
         if st_w
             .voted_for
@@ -1051,7 +1077,11 @@ impl<C: Command, RC: RoleChange> RawCurp<C, RC> {
     }
 
     /// Handle `fetch_read_state`
-    pub(super) fn handle_fetch_read_state(&self, cmd: Arc<C>) -> ReadState {
+    pub(super) fn handle_fetch_read_state(&self, cmd: Arc<C>) -> Result<ReadState, CurpError> {
+        let (leader_id, term, is_leader) = self.leader();
+        if !is_leader {
+            return Err(CurpError::redirect(leader_id, term));
+        }
         let ids: Vec<_> = self
             .ctx
             .uncommitted_pool
@@ -1060,14 +1090,57 @@ impl<C: Command, RC: RoleChange> RawCurp<C, RC> {
             .map(|entry| entry.id)
             .collect();
         if ids.is_empty() {
-            ReadState::CommitIndex(self.log.read().commit_index)
+            Ok(ReadState::CommitIndex(self.log.read().commit_index))
         } else {
-            ReadState::Ids(IdSet::new(
+            Ok(ReadState::Ids(IdSet::new(
                 ids.into_iter()
                     .map(crate::log_entry::propose_id_to_inflight_id)
                     .collect(),
-            ))
+            )))
+        }
+    }
+
+    /// Record that a follower accepted the `append_entries` of `term` sent at `sent_at`
+    pub(super) fn record_append_entries_ack(
+        &self,
+        follower_id: ServerId,
+        term: u64,
+        sent_at: Instant,
+    ) {
+        let (cur_term, cur_role) = self.st.map_read(|st_r| (st_r.term, st_r.role));
+        if cur_term != term || cur_role != Role::Leader {
+            return;
+        }
+        self.lst.update_last_ack(follower_id, sent_at);
+        let _ignore = self.ctx.ack_event.notify(usize::MAX);
+    }
+
+    /// Check whether this node is confirmed to be the leader at `since`.
+    ///
+    /// Like the read index of raft, the leadership is confirmed if a quorum of voters
+    /// accepted an `append_entries` of the current term sent after `since`. A newer leader
+    /// must have been voted by a quorum, and any of them would have rejected it. It
+    /// doesn't depend on the clocks of the followers.
+    pub(super) fn leadership_confirmed_since(&self, since: Instant) -> bool {
+        if !self.is_leader() {
+            return false;
         }
+        let acked_cnt = self
+            .lst
+            .iter()
+            .filter(|f| !f.is_learner && f.last_ack.is_some_and(|t| t >= since))
+            .count();
+        acked_cnt + 1 >= quorum(self.ctx.cluster_info.voters_len())
+    }
+
+    /// Send heartbeats to the followers now instead of at the next heartbeat interval
+    pub(super) fn trigger_heartbeats(&self) {
+        let _ignore = self.ctx.heartbeat_event.notify(usize::MAX);
+    }
+
+    /// Listen to the next `append_entries` accepted by a follower
+    pub(super) fn listen_ack(&self) -> EventListener {
+        self.ctx.ack_event.listen()
     }
 
     /// Handle `move_leader`
@@ -1146,6 +1219,25 @@ impl<C: Command, RC: RoleChange> RawCurp<C, RC> {
         self.log.read().commit_index
     }
 
+    /// Get the number of log entries that are proposed but not committed yet
+    #[inline]
+    pub fn in_flight_proposals(&self) -> u64 {
+        let log_r = self.log.read();
+        log_r.last_log_index().saturating_sub(log_r.commit_index)
+    }
+
+    /// Get the voting follower that has replicated the most log entries, which catches
+    /// up with the leader first if the leadership is moved to it
+    #[inline]
+    pub fn most_up_to_date_follower(&self) -> Option<ServerId> {
+        self.lst
+            .get_all_statuses()
+            .into_iter()
+            .filter(|&(_, ref status)| !status.is_learner)
+            .max_by_key(|&(_, ref status)| status.match_index)
+            .map(|(id, _)| id)
+    }
+
     /// Get cluster info
     pub(super) fn cluster(&self) -> &ClusterInfo {
         self.ctx.cluster_info.as_ref()
@@ -1227,6 +1319,11 @@ impl<C: Command, RC: RoleChange> RawCurp<C, RC> {
         Arc::clone(&self.ctx.leader_event)
     }
 
+    /// Get heartbeat event
+    pub(super) fn heartbeat_event(&self) -> Arc<Event> {
+        Arc::clone(&self.ctx.heartbeat_event)
+    }
+
     /// Reset log base
     pub(super) fn reset_by_snapshot(&self, meta: SnapshotMeta) {
         let mut log_w = self.log.write();
@@ -1290,9 +1387,9 @@ impl<C: Command, RC: RoleChange> RawCurp<C, RC> {
 
     /// Check if the new config is valid
     pub(super) fn check_new_config(&self, changes: &[ConfChange]) -> Result<(), CurpError> {
-        assert_eq!(changes.len(), 1, "Joint consensus is not supported yet");
-        let Some(conf_change) = changes.iter().next() else {
-            unreachable!("conf change is empty");
+        // Joint consensus is not supported yet, only single-step changes are accepted
+        let [conf_change] = changes else {
+            return Err(CurpError::invalid_config());
         };
         let mut statuses_ids = self
             .lst
@@ -1621,6 +1718,7 @@ impl<C: Command, RC: RoleChange> RawCurp<C, RC> {
         metrics::get().leader_changes.add(1, &[]);
         st.role = Role::Leader;
         st.leader_id = Some(self.id());
+        self.lst.reset_last_acks();
         let _ig = self.ctx.leader_tx.send(Some(self.id())).ok();
         let _ignore = self.ctx.leader_event.notify(usize::MAX);
         self.ctx.role_change.on_election_win();
@@ -1764,6 +1862,7 @@ impl<C: Command, RC: RoleChange> RawCurp<C, RC> {
 
     /// Apply new logs
     fn apply(&self, log: &mut Log<C>) {
+        fail_point!("after_curp_commit");
         for i in (log.last_as + 1)..=log.commit_index {
             let entry = log.get(i).unwrap_or_else(|| {
                 unreachable!(
@@ -11,6 +11,7 @@ use dashmap::{
     DashMap,
 };
 use madsim::rand::{thread_rng, Rng};
+use tokio::time::Instant;
 use tracing::{debug, warn};
 
 use super::Role;
@@ -62,6 +63,8 @@ pub(super) struct FollowerStatus {
     pub(super) match_index: LogIndex,
     /// This node is a learner or not
     pub(super) is_learner: bool,
+    /// Send time of the latest `append_entries` that the follower accepted in current term
+    pub(super) last_ack: Option<Instant>,
 }
 
 impl Default for FollowerStatus {
@@ -70,6 +73,7 @@ impl Default for FollowerStatus {
             next_index: 1,
             match_index: 0,
             is_learner: false,
+            last_ack: None,
         }
     }
 }
@@ -81,6 +85,7 @@ impl FollowerStatus {
             next_index,
             match_index,
             is_learner,
+            last_ack: None,
         }
     }
 }
@@ -196,6 +201,24 @@ impl LeaderState {
         debug!("follower {id}'s match_index updated to {index}");
     }
 
+    /// Update `last_ack` for server if `sent_at` is later
+    pub(super) fn update_last_ack(&self, id: ServerId, sent_at: Instant) {
+        let Some(mut status) = self.get_status_mut(id) else {
+            warn!("follower {} is not found, it maybe has been removed", id);
+            return;
+        };
+        if status.last_ack.map_or(true, |t| t < sent_at) {
+            status.last_ack = Some(sent_at);
+        }
+    }
+
+    /// Clear `last_ack` of all servers, acks of a previous leadership must not be reused
+    pub(super) fn reset_last_acks(&self) {
+        for mut status in self.statuses.iter_mut() {
+            status.last_ack = None;
+        }
+    }
+
     /// Create a `Iterator` for all statuses
     pub(super) fn iter(&self) -> impl Iterator<Item = RefMulti<'_, ServerId, FollowerStatus>> {
         self.statuses.iter()
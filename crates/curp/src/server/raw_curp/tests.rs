@@ -288,6 +288,33 @@ fn heartbeat_will_calibrate_next_index() {
     assert_eq!(curp.lst.get_next_index(s1_id), Some(1));
 }
 
+#[traced_test]
+#[test]
+fn leadership_is_confirmed_by_acks_from_quorum() {
+    let task_manager = Arc::new(TaskManager::new());
+    let curp = RawCurp::new_test(
+        3,
+        MockCEEventTxApi::<TestCommand>::default(),
+        mock_role_change(),
+        task_manager,
+    );
+    let since = Instant::now();
+    assert!(!curp.leadership_confirmed_since(since));
+
+    let s1_id = curp.cluster().get_id_by_name("S1").unwrap();
+    // acks of another term are ignored
+    curp.record_append_entries_ack(s1_id, 0, Instant::now());
+    assert!(!curp.leadership_confirmed_since(since));
+
+    // an append_entries sent before the read can't confirm it
+    let before = since - curp.cfg().heartbeat_interval;
+    curp.record_append_entries_ack(s1_id, 1, before);
+    assert!(!curp.leadership_confirmed_since(since));
+
+    curp.record_append_entries_ack(s1_id, 1, Instant::now());
+    assert!(curp.leadership_confirmed_since(since));
+}
+
 #[traced_test]
 #[test]
 fn handle_ae_will_calibrate_term() {
@@ -442,6 +469,33 @@ async fn follower_will_not_start_election_when_heartbeats_are_received() {
     handle.abort();
 }
 
+#[traced_test]
+#[tokio::test]
+#[abort_on_panic]
+async fn learner_will_not_start_election_if_timeout() {
+    let task_manager = Arc::new(TaskManager::new());
+    let curp = {
+        let mut exe_tx = MockCEEventTxApi::<TestCommand>::default();
+        exe_tx
+            .expect_send_reset()
+            .returning(|_| oneshot::channel().1);
+        Arc::new(RawCurp::new_test(
+            3,
+            exe_tx,
+            mock_role_change(),
+            task_manager,
+        ))
+    };
+    curp.update_to_term_and_become_follower(&mut *curp.st.write(), 1);
+    curp.cluster().demote(curp.id());
+
+    for _ in 0..default_follower_timeout_ticks() * 3 {
+        let action = curp.tick_election();
+        assert!(matches!(action, None));
+    }
+    assert_eq!(curp.role(), Role::Follower);
+}
+
 #[traced_test]
 #[tokio::test]
 #[abort_on_panic]
@@ -541,6 +595,29 @@ fn handle_vote_will_reject_smaller_term() {
     assert_eq!(result.unwrap_err(), Some(2));
 }
 
+#[traced_test]
+#[test]
+fn handle_vote_will_reject_learner_candidate() {
+    let task_manager = Arc::new(TaskManager::new());
+    let curp = {
+        let mut exe_tx = MockCEEventTxApi::<TestCommand>::default();
+        exe_tx
+            .expect_send_reset()
+            .returning(|_| oneshot::channel().1);
+        Arc::new(RawCurp::new_test(
+            3,
+            exe_tx,
+            mock_role_change(),
+            task_manager,
+        ))
+    };
+    curp.update_to_term_and_become_follower(&mut *curp.st.write(), 1);
+    curp.switch_config(ConfChange::add_learner(1234, vec!["address".to_owned()]));
+
+    let result = curp.handle_vote(2, 1234, 0, 0);
+    assert_eq!(result.unwrap_err(), Some(2));
+}
+
 // #[traced_test]
 #[test]
 fn handle_vote_will_reject_outdated_candidate() {
@@ -872,6 +949,25 @@ fn is_synced_should_return_true_when_followers_caught_up_with_leader() {
     assert!(curp.is_synced(s2_id));
 }
 
+#[traced_test]
+#[test]
+fn most_up_to_date_follower_should_have_the_highest_match_index() {
+    let task_manager = Arc::new(TaskManager::new());
+    let curp = {
+        let exe_tx = MockCEEventTxApi::<TestCommand>::default();
+        RawCurp::new_test(3, exe_tx, mock_role_change(), task_manager)
+    };
+
+    let s1_id = curp.cluster().get_id_by_name("S1").unwrap();
+    let s2_id = curp.cluster().get_id_by_name("S2").unwrap();
+    curp.lst.update_match_index(s1_id, 2);
+    curp.lst.update_match_index(s2_id, 5);
+    assert_eq!(curp.most_up_to_date_follower(), Some(s2_id));
+
+    curp.lst.update_match_index(s1_id, 6);
+    assert_eq!(curp.most_up_to_date_follower(), Some(s1_id));
+}
+
 #[traced_test]
 #[test]
 fn add_node_should_add_new_node_to_curp() {
@@ -1010,6 +1106,28 @@ fn remove_non_exists_node_should_return_node_not_exists_error() {
     assert!(matches!(resp, Err(CurpError::NodeNotExists(()))));
 }
 
+#[traced_test]
+#[test]
+fn multiple_conf_changes_should_return_invalid_config_error() {
+    let task_manager = Arc::new(TaskManager::new());
+    let curp = {
+        let exe_tx = MockCEEventTxApi::<TestCommand>::default();
+        Arc::new(RawCurp::new_test(
+            5,
+            exe_tx,
+            mock_role_change(),
+            task_manager,
+        ))
+    };
+    let s1_id = curp.cluster().get_id_by_name("S1").unwrap();
+    let s2_id = curp.cluster().get_id_by_name("S2").unwrap();
+    let changes = vec![ConfChange::remove(s1_id), ConfChange::remove(s2_id)];
+    let resp = curp.check_new_config(&changes);
+    assert!(matches!(resp, Err(CurpError::InvalidConfig(()))));
+    let resp = curp.check_new_config(&[]);
+    assert!(matches!(resp, Err(CurpError::InvalidConfig(()))));
+}
+
 #[traced_test]
 #[test]
 fn update_node_should_update_the_address_of_node() {
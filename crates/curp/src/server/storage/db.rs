@@ -167,11 +167,7 @@ impl<C> DB<C> {
     /// Will return `StorageError` if failed to open the storage
     #[inline]
     pub fn open(config: &EngineConfig) -> Result<Self, StorageError> {
-        let engine_type = match *config {
-            EngineConfig::Memory => EngineType::Memory,
-            EngineConfig::RocksDB(ref path) => EngineType::Rocks(path.clone()),
-            _ => unreachable!("Not supported storage type"),
-        };
+        let engine_type = EngineType::try_from(config)?;
         let db = Engine::new(engine_type, &[CF, LOGS_CF, MEMBERS_CF])?;
         Ok(Self {
             db,
@@ -23,14 +23,18 @@ impl Snapshot {
     }
 
     /// Get the inner snapshot ref
-    #[cfg(feature = "client-metrics")]
     pub(crate) fn inner(&self) -> &EngineSnapshot {
         &self.inner
     }
+
+    /// Get the mutable inner snapshot ref
+    pub(crate) fn inner_mut(&mut self) -> &mut EngineSnapshot {
+        &mut self.inner
+    }
 }
 
 /// Metadata for snapshot
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub(crate) struct SnapshotMeta {
     /// Last included index
     pub(crate) last_included_index: u64,
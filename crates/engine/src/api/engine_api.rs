@@ -38,6 +38,19 @@ pub trait StorageEngine: Send + Sync + 'static + std::fmt::Debug {
     #[allow(clippy::type_complexity)] // it's clear that (Vec<u8>, Vec<u8>) is a key-value pair
     fn get_all(&self, table: &str) -> Result<Vec<(Vec<u8>, Vec<u8>)>, EngineError>;
 
+    /// Get at most `limit` key-value pairs of the given table whose keys are not less than
+    /// `from`, in the order of the keys
+    /// # Errors
+    /// Return `EngineError::TableNotFound` if the given table does not exist
+    /// Return `EngineError` if met some errors
+    #[allow(clippy::type_complexity)] // it's clear that (Vec<u8>, Vec<u8>) is a key-value pair
+    fn get_from(
+        &self,
+        table: &str,
+        from: &[u8],
+        limit: usize,
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>, EngineError>;
+
     /// Commit a batch of write operations
     /// If sync is true, the write will be flushed from the operating system
     /// buffer cache before the write is considered complete. If this
@@ -76,4 +89,30 @@ pub trait StorageEngine: Send + Sync + 'static + std::fmt::Debug {
     /// # Errors
     /// Return `EngineError` if met some errors when get file size
     fn file_size(&self) -> Result<u64, EngineError>;
+
+    /// Get the estimated size of the live data in the engine (Measured in bytes)
+    ///
+    /// # Errors
+    /// Return `EngineError` if met some errors when get live data size
+    fn live_data_size(&self) -> Result<u64, EngineError>;
+
+    /// Compact all data of the engine to reclaim the space occupied by deleted or
+    /// overwritten data. This is an expensive operation.
+    ///
+    /// # Errors
+    /// Return `EngineError` if met some errors when compacting
+    fn defragment(&self) -> Result<(), EngineError>;
+
+    /// Persist the committed writes that are not synced to disk yet
+    ///
+    /// # Errors
+    /// Return `EngineError` if met some errors when syncing
+    fn sync(&self) -> Result<(), EngineError>;
+
+    /// Get the estimated size of the given table (Measured in bytes)
+    ///
+    /// # Errors
+    /// Return `EngineError::TableNotFound` if the given table does not exist
+    /// Return `EngineError` if met some errors when get table size
+    fn table_size(&self, table: &str) -> Result<u64, EngineError>;
 }
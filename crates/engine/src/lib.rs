@@ -182,12 +182,16 @@ mod metrics;
 mod mock_rocksdb_engine;
 /// Snapshot Trait Definition And Implementation
 mod proxy;
+/// `redb` Storage Engine
+mod redb_engine;
 /// `RocksDB` Storage Engine
 #[cfg(not(madsim))]
 mod rocksdb_engine;
 /// Snapshot Allocator
 mod snapshot_allocator;
 
+#[cfg(not(madsim))]
+pub use crate::rocksdb_engine::ReadOnlyRocksEngine;
 pub use crate::{
     api::{
         engine_api::StorageEngine,
@@ -197,5 +201,5 @@ pub use crate::{
     },
     error::EngineError,
     proxy::{Engine, EngineType, Snapshot},
-    snapshot_allocator::{MemorySnapshotAllocator, RocksSnapshotAllocator},
+    snapshot_allocator::{MemorySnapshotAllocator, RedbSnapshotAllocator, RocksSnapshotAllocator},
 };
@@ -10,7 +10,7 @@ use std::{
 };
 
 use bytes::{Bytes, BytesMut};
-use clippy_utilities::NumericCast;
+use clippy_utilities::{NumericCast, OverflowArithmetic};
 use parking_lot::RwLock;
 use tokio::io::AsyncWriteExt;
 use tokio_util::io::read_buf;
@@ -113,6 +113,27 @@ impl StorageEngine for MemoryEngine {
         Ok(values)
     }
 
+    #[inline]
+    fn get_from(
+        &self,
+        table: &str,
+        from: &[u8],
+        limit: usize,
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>, EngineError> {
+        let inner = self.inner.read();
+        let table = inner
+            .get(table)
+            .ok_or_else(|| EngineError::TableNotFound(table.to_owned()))?;
+        let mut values = table
+            .iter()
+            .filter(|&(key, _)| key.as_slice() >= from)
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect::<Vec<_>>();
+        values.sort_by(|v1, v2| v1.0.cmp(&v2.0));
+        values.truncate(limit);
+        Ok(values)
+    }
+
     #[inline]
     fn write_batch(&self, wr_ops: Vec<WriteOperation<'_>>, _sync: bool) -> Result<(), EngineError> {
         let mut inner = self.inner.write();
@@ -188,6 +209,30 @@ impl StorageEngine for MemoryEngine {
     fn file_size(&self) -> Result<u64, EngineError> {
         Ok(0)
     }
+
+    fn live_data_size(&self) -> Result<u64, EngineError> {
+        Ok(0)
+    }
+
+    fn defragment(&self) -> Result<(), EngineError> {
+        Ok(())
+    }
+
+    fn sync(&self) -> Result<(), EngineError> {
+        Ok(())
+    }
+
+    fn table_size(&self, table: &str) -> Result<u64, EngineError> {
+        let inner = self.inner.read();
+        let table = inner
+            .get(table)
+            .ok_or_else(|| EngineError::TableNotFound(table.to_owned()))?;
+        Ok(table
+            .iter()
+            .map(|(key, value)| key.len().overflow_add(value.len()))
+            .sum::<usize>()
+            .numeric_cast())
+    }
 }
 
 /// A snapshot of the `MemoryEngine`
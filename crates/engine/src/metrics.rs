@@ -98,6 +98,20 @@ where
         self.engine.get_all(table)
     }
 
+    /// Get the key-value pairs of the given table whose keys are not less than `from`
+    /// # Errors
+    /// Return `EngineError::TableNotFound` if the given table does not exist
+    /// Return `EngineError` if met some errors
+    #[allow(clippy::type_complexity)] // it's clear that (Vec<u8>, Vec<u8>) is a key-value pair
+    fn get_from(
+        &self,
+        table: &str,
+        from: &[u8],
+        limit: usize,
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>, EngineError> {
+        self.engine.get_from(table, from, limit)
+    }
+
     /// Commit a batch of write operations
     /// If sync is true, the write will be flushed from the operating system
     /// buffer cache before the write is considered complete. If this
@@ -158,6 +172,26 @@ where
     fn file_size(&self) -> Result<u64, EngineError> {
         self.engine.file_size()
     }
+
+    /// Get the estimated size of the live data in the engine (Measured in bytes)
+    fn live_data_size(&self) -> Result<u64, EngineError> {
+        self.engine.live_data_size()
+    }
+
+    /// Compact all data of the engine
+    fn defragment(&self) -> Result<(), EngineError> {
+        self.engine.defragment()
+    }
+
+    /// Persist the committed writes that are not synced to disk yet
+    fn sync(&self) -> Result<(), EngineError> {
+        self.engine.sync()
+    }
+
+    /// Get the estimated size of the given table (Measured in bytes)
+    fn table_size(&self, table: &str) -> Result<u64, EngineError> {
+        self.engine.table_size(table)
+    }
 }
 
 #[async_trait]
@@ -103,6 +103,16 @@ impl StorageEngine for RocksEngine {
         self.inner.get_all(table)
     }
 
+    #[inline]
+    fn get_from(
+        &self,
+        table: &str,
+        from: &[u8],
+        limit: usize,
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>, EngineError> {
+        self.inner.get_from(table, from, limit)
+    }
+
     #[inline]
     fn write_batch(&self, wr_ops: Vec<WriteOperation<'_>>, sync: bool) -> Result<(), EngineError> {
         self.inner.write_batch(wr_ops, sync)?;
@@ -139,6 +149,26 @@ impl StorageEngine for RocksEngine {
     fn file_size(&self) -> Result<u64, EngineError> {
         Ok(0)
     }
+
+    #[inline]
+    fn live_data_size(&self) -> Result<u64, EngineError> {
+        Ok(0)
+    }
+
+    #[inline]
+    fn defragment(&self) -> Result<(), EngineError> {
+        Ok(())
+    }
+
+    #[inline]
+    fn sync(&self) -> Result<(), EngineError> {
+        Ok(())
+    }
+
+    #[inline]
+    fn table_size(&self, table: &str) -> Result<u64, EngineError> {
+        self.inner.table_size(table)
+    }
 }
 
 /// A mock snapshot of the `RocksEngine`
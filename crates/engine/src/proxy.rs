@@ -1,6 +1,7 @@
 use std::path::{Path, PathBuf};
 
 use bytes::{Bytes, BytesMut};
+use utils::config::EngineConfig;
 
 #[cfg(madsim)]
 use crate::mock_rocksdb_engine::{RocksEngine, RocksSnapshot, RocksTransaction};
@@ -9,7 +10,10 @@ use crate::rocksdb_engine::{RocksEngine, RocksSnapshot, RocksTransaction};
 use crate::{
     error::EngineError,
     memory_engine::{MemoryEngine, MemorySnapshot, MemoryTransaction},
-    metrics, SnapshotApi, StorageEngine, TransactionApi, WriteOperation,
+    metrics,
+    redb_engine::{RedbEngine, RedbSnapshot, RedbTransaction},
+    MemorySnapshotAllocator, RedbSnapshotAllocator, RocksSnapshotAllocator, SnapshotAllocator,
+    SnapshotApi, StorageEngine, TransactionApi, WriteOperation,
 };
 
 #[derive(Debug)]
@@ -20,6 +24,41 @@ pub enum EngineType {
     Memory,
     /// Rocks engine, the inner path is path of `Engine` or `Snapshot`
     Rocks(PathBuf),
+    /// Redb engine, the inner path is path of `Engine` or `Snapshot`
+    Redb(PathBuf),
+}
+
+impl TryFrom<&EngineConfig> for EngineType {
+    type Error = EngineError;
+
+    /// Get the engine type of an engine configuration, this is the only place that maps
+    /// the configured engines to their implementations
+    #[inline]
+    fn try_from(config: &EngineConfig) -> Result<Self, Self::Error> {
+        #[allow(clippy::wildcard_enum_match_arm)]
+        match *config {
+            EngineConfig::Memory => Ok(EngineType::Memory),
+            EngineConfig::RocksDB(ref path) => Ok(EngineType::Rocks(path.clone())),
+            EngineConfig::Redb(ref path) => Ok(EngineType::Redb(path.clone())),
+            _ => Err(EngineError::InvalidArgument(format!(
+                "unsupported storage engine {config:?}"
+            ))),
+        }
+    }
+}
+
+impl EngineType {
+    /// Get the allocator of the snapshots received from other members, each engine
+    /// registers its allocator here
+    #[inline]
+    #[must_use]
+    pub fn snapshot_allocator(&self) -> Box<dyn SnapshotAllocator> {
+        match *self {
+            EngineType::Memory => Box::<MemorySnapshotAllocator>::default(),
+            EngineType::Rocks(_) => Box::<RocksSnapshotAllocator>::default(),
+            EngineType::Redb(_) => Box::<RedbSnapshotAllocator>::default(),
+        }
+    }
 }
 
 /// `Engine` is designed to mask the different type of `MemoryEngine` and `RocksEngine`
@@ -31,6 +70,8 @@ pub enum Engine {
     Memory(MemoryEngine),
     /// Rocks engine
     Rocks(metrics::Layer<RocksEngine>),
+    /// Redb engine
+    Redb(metrics::Layer<RedbEngine>),
 }
 
 impl Engine {
@@ -44,6 +85,9 @@ impl Engine {
             EngineType::Rocks(path) => Ok(Engine::Rocks(metrics::Layer::new(RocksEngine::new(
                 path, tables,
             )?))),
+            EngineType::Redb(path) => Ok(Engine::Redb(metrics::Layer::new(RedbEngine::new(
+                path, tables,
+            )?))),
         }
     }
 
@@ -61,6 +105,9 @@ impl Engine {
             Engine::Memory(ref _e) => {
                 unreachable!("Memory engine does not support apply snapshot from file")
             }
+            Engine::Redb(ref _e) => {
+                unreachable!("Redb engine does not support apply snapshot from file")
+            }
         }
     }
 }
@@ -75,6 +122,7 @@ impl StorageEngine for Engine {
         match *self {
             Engine::Memory(ref e) => Transaction::Memory(e.transaction()),
             Engine::Rocks(ref e) => Transaction::Rocks(e.transaction()),
+            Engine::Redb(ref e) => Transaction::Redb(e.transaction()),
         }
     }
 
@@ -83,6 +131,7 @@ impl StorageEngine for Engine {
         match *self {
             Engine::Memory(ref e) => e.get(table, key),
             Engine::Rocks(ref e) => e.get(table, key),
+            Engine::Redb(ref e) => e.get(table, key),
         }
     }
 
@@ -95,6 +144,7 @@ impl StorageEngine for Engine {
         match *self {
             Engine::Memory(ref e) => e.get_multi(table, keys),
             Engine::Rocks(ref e) => e.get_multi(table, keys),
+            Engine::Redb(ref e) => e.get_multi(table, keys),
         }
     }
 
@@ -103,6 +153,21 @@ impl StorageEngine for Engine {
         match *self {
             Engine::Memory(ref e) => e.get_all(table),
             Engine::Rocks(ref e) => e.get_all(table),
+            Engine::Redb(ref e) => e.get_all(table),
+        }
+    }
+
+    #[inline]
+    fn get_from(
+        &self,
+        table: &str,
+        from: &[u8],
+        limit: usize,
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>, EngineError> {
+        match *self {
+            Engine::Memory(ref e) => e.get_from(table, from, limit),
+            Engine::Rocks(ref e) => e.get_from(table, from, limit),
+            Engine::Redb(ref e) => e.get_from(table, from, limit),
         }
     }
 
@@ -111,6 +176,7 @@ impl StorageEngine for Engine {
         match *self {
             Engine::Memory(ref e) => e.write_batch(wr_ops, sync),
             Engine::Rocks(ref e) => e.write_batch(wr_ops, sync),
+            Engine::Redb(ref e) => e.write_batch(wr_ops, sync),
         }
     }
 
@@ -123,6 +189,7 @@ impl StorageEngine for Engine {
         match *self {
             Engine::Memory(ref e) => e.get_snapshot(path, tables).map(Snapshot::Memory),
             Engine::Rocks(ref e) => e.get_snapshot(path, tables).map(Snapshot::Rocks),
+            Engine::Redb(ref e) => e.get_snapshot(path, tables).map(Snapshot::Redb),
         }
     }
 
@@ -132,15 +199,13 @@ impl StorageEngine for Engine {
         snapshot: Self::Snapshot,
         tables: &[&'static str],
     ) -> Result<(), EngineError> {
-        match *self {
-            Engine::Memory(ref e) => match snapshot {
-                Snapshot::Memory(s) => e.apply_snapshot(s, tables).await,
-                Snapshot::Rocks(_) => Err(EngineError::InvalidSnapshot),
-            },
-            Engine::Rocks(ref e) => match snapshot {
-                Snapshot::Memory(_) => Err(EngineError::InvalidSnapshot),
-                Snapshot::Rocks(s) => e.apply_snapshot(s, tables).await,
-            },
+        match (self, snapshot) {
+            (&Engine::Memory(ref e), Snapshot::Memory(s)) => e.apply_snapshot(s, tables).await,
+            (&Engine::Rocks(ref e), Snapshot::Rocks(s)) => e.apply_snapshot(s, tables).await,
+            (&Engine::Redb(ref e), Snapshot::Redb(s)) => e.apply_snapshot(s, tables).await,
+            (&Engine::Memory(_) | &Engine::Rocks(_) | &Engine::Redb(_), _) => {
+                Err(EngineError::InvalidSnapshot)
+            }
         }
     }
 
@@ -149,6 +214,7 @@ impl StorageEngine for Engine {
         match *self {
             Engine::Memory(ref e) => e.estimated_file_size(),
             Engine::Rocks(ref e) => e.estimated_file_size(),
+            Engine::Redb(ref e) => e.estimated_file_size(),
         }
     }
 
@@ -157,6 +223,43 @@ impl StorageEngine for Engine {
         match *self {
             Engine::Memory(ref e) => e.file_size(),
             Engine::Rocks(ref e) => e.file_size(),
+            Engine::Redb(ref e) => e.file_size(),
+        }
+    }
+
+    #[inline]
+    fn live_data_size(&self) -> Result<u64, EngineError> {
+        match *self {
+            Engine::Memory(ref e) => e.live_data_size(),
+            Engine::Rocks(ref e) => e.live_data_size(),
+            Engine::Redb(ref e) => e.live_data_size(),
+        }
+    }
+
+    #[inline]
+    fn defragment(&self) -> Result<(), EngineError> {
+        match *self {
+            Engine::Memory(ref e) => e.defragment(),
+            Engine::Rocks(ref e) => e.defragment(),
+            Engine::Redb(ref e) => e.defragment(),
+        }
+    }
+
+    #[inline]
+    fn sync(&self) -> Result<(), EngineError> {
+        match *self {
+            Engine::Memory(ref e) => e.sync(),
+            Engine::Rocks(ref e) => e.sync(),
+            Engine::Redb(ref e) => e.sync(),
+        }
+    }
+
+    #[inline]
+    fn table_size(&self, table: &str) -> Result<u64, EngineError> {
+        match *self {
+            Engine::Memory(ref e) => e.table_size(table),
+            Engine::Rocks(ref e) => e.table_size(table),
+            Engine::Redb(ref e) => e.table_size(table),
         }
     }
 }
@@ -171,6 +274,8 @@ pub enum Transaction {
     Memory(MemoryTransaction),
     /// Rocks transaction
     Rocks(metrics::Layer<RocksTransaction>),
+    /// Redb transaction
+    Redb(metrics::Layer<RedbTransaction>),
 }
 
 impl TransactionApi for Transaction {
@@ -179,6 +284,7 @@ impl TransactionApi for Transaction {
         match self {
             Transaction::Memory(t) => t.commit(),
             Transaction::Rocks(t) => t.commit(),
+            Transaction::Redb(t) => t.commit(),
         }
     }
 
@@ -187,6 +293,7 @@ impl TransactionApi for Transaction {
         match *self {
             Transaction::Memory(ref t) => t.rollback(),
             Transaction::Rocks(ref t) => t.rollback(),
+            Transaction::Redb(ref t) => t.rollback(),
         }
     }
 }
@@ -200,6 +307,8 @@ pub enum Snapshot {
     Memory(MemorySnapshot),
     /// Rocks snapshot
     Rocks(metrics::Layer<RocksSnapshot>),
+    /// Redb snapshot
+    Redb(metrics::Layer<RedbSnapshot>),
 }
 
 impl Snapshot {
@@ -213,6 +322,9 @@ impl Snapshot {
             EngineType::Rocks(path) => Ok(Self::Rocks(metrics::Layer::new(
                 RocksSnapshot::new_for_receiving(path)?,
             ))),
+            EngineType::Redb(path) => Ok(Self::Redb(metrics::Layer::new(
+                RedbSnapshot::new_for_receiving(path)?,
+            ))),
         }
     }
 }
@@ -224,6 +336,7 @@ impl SnapshotApi for Snapshot {
         match *self {
             Snapshot::Memory(ref s) => s.size(),
             Snapshot::Rocks(ref s) => s.size(),
+            Snapshot::Redb(ref s) => s.size(),
         }
     }
 
@@ -232,6 +345,7 @@ impl SnapshotApi for Snapshot {
         match *self {
             Snapshot::Memory(ref mut s) => s.rewind(),
             Snapshot::Rocks(ref mut s) => s.rewind(),
+            Snapshot::Redb(ref mut s) => s.rewind(),
         }
     }
 
@@ -240,6 +354,7 @@ impl SnapshotApi for Snapshot {
         match *self {
             Snapshot::Memory(ref mut s) => s.read_buf(buf).await,
             Snapshot::Rocks(ref mut s) => s.read_buf(buf).await,
+            Snapshot::Redb(ref mut s) => s.read_buf(buf).await,
         }
     }
 
@@ -248,6 +363,7 @@ impl SnapshotApi for Snapshot {
         match *self {
             Snapshot::Memory(ref mut s) => s.read_buf_exact(buf).await,
             Snapshot::Rocks(ref mut s) => s.read_buf_exact(buf).await,
+            Snapshot::Redb(ref mut s) => s.read_buf_exact(buf).await,
         }
     }
 
@@ -256,6 +372,7 @@ impl SnapshotApi for Snapshot {
         match *self {
             Snapshot::Memory(ref mut s) => s.write_all(buf).await,
             Snapshot::Rocks(ref mut s) => s.write_all(buf).await,
+            Snapshot::Redb(ref mut s) => s.write_all(buf).await,
         }
     }
 
@@ -264,6 +381,7 @@ impl SnapshotApi for Snapshot {
         match *self {
             Snapshot::Memory(ref mut s) => s.clean().await,
             Snapshot::Rocks(ref mut s) => s.clean().await,
+            Snapshot::Redb(ref mut s) => s.clean().await,
         }
     }
 }
@@ -284,9 +402,11 @@ mod test {
     fn write_batch_into_a_non_existing_table_should_fail() {
         let dir = PathBuf::from("/tmp/write_batch_into_a_non_existing_table_should_fail");
         let rocks_engine_path = dir.join("rocks_engine");
+        let redb_engine_path = dir.join("redb_engine");
         let engines = vec![
             Engine::new(EngineType::Memory, &TESTTABLES).unwrap(),
             Engine::new(EngineType::Rocks(rocks_engine_path), &TESTTABLES).unwrap(),
+            Engine::new(EngineType::Redb(redb_engine_path), &TESTTABLES).unwrap(),
         ];
 
         for engine in engines {
@@ -310,9 +430,11 @@ mod test {
     fn write_batch_should_success() {
         let dir = PathBuf::from("/tmp/write_batch_should_success");
         let rocks_engine_path = dir.join("rocks_engine");
+        let redb_engine_path = dir.join("redb_engine");
         let engines = vec![
             Engine::new(EngineType::Memory, &TESTTABLES).unwrap(),
             Engine::new(EngineType::Rocks(rocks_engine_path), &TESTTABLES).unwrap(),
+            Engine::new(EngineType::Redb(redb_engine_path), &TESTTABLES).unwrap(),
         ];
         for engine in engines {
             let origin_set: Vec<Vec<u8>> = (1u8..=10u8)
@@ -356,9 +478,11 @@ mod test {
     fn get_operation_should_success() {
         let dir = PathBuf::from("/tmp/get_operation_should_success");
         let rocks_engine_path = dir.join("rocks_engine");
+        let redb_engine_path = dir.join("redb_engine");
         let engines = vec![
             Engine::new(EngineType::Memory, &TESTTABLES).unwrap(),
             Engine::new(EngineType::Rocks(rocks_engine_path), &TESTTABLES).unwrap(),
+            Engine::new(EngineType::Redb(redb_engine_path), &TESTTABLES).unwrap(),
         ];
         for engine in engines {
             let test_set = vec![("hello", "hello"), ("world", "world"), ("foo", "foo")];
@@ -386,6 +510,15 @@ mod test {
                 .map(|(key, value)| (key.as_bytes().to_vec(), value.as_bytes().to_vec()))
                 .collect::<Vec<(Vec<u8>, Vec<u8>)>>();
             assert_eq!(res_3.sort(), expected_all_values.sort());
+
+            let res_4 = engine.get_from("kv", b"hello", usize::MAX).unwrap();
+            assert_eq!(
+                res_4,
+                vec![
+                    (b"hello".to_vec(), b"hello".to_vec()),
+                    (b"world".to_vec(), b"world".to_vec())
+                ]
+            );
         }
         std::fs::remove_dir_all(dir).unwrap();
     }
@@ -400,16 +533,29 @@ mod test {
         let snapshot_bak_dir = dir.join("snapshot_bak");
         let engines = vec![
             Engine::new(EngineType::Memory, &TESTTABLES).unwrap(),
-            Engine::new(EngineType::Rocks(origin_data_dir), &TESTTABLES).unwrap(),
+            Engine::new(
+                EngineType::Rocks(origin_data_dir.join("rocks")),
+                &TESTTABLES,
+            )
+            .unwrap(),
+            Engine::new(EngineType::Redb(origin_data_dir.join("redb")), &TESTTABLES).unwrap(),
         ];
         let recover_engines = vec![
             Engine::new(EngineType::Memory, &TESTTABLES).unwrap(),
-            Engine::new(EngineType::Rocks(recover_data_dir), &TESTTABLES).unwrap(),
+            Engine::new(
+                EngineType::Rocks(recover_data_dir.join("rocks")),
+                &TESTTABLES,
+            )
+            .unwrap(),
+            Engine::new(EngineType::Redb(recover_data_dir.join("redb")), &TESTTABLES).unwrap(),
         ];
         let received_snapshots = vec![
             Snapshot::Memory(MemorySnapshot::new(Vec::new())),
             Snapshot::Rocks(metrics::Layer::new(
-                RocksSnapshot::new_for_receiving(snapshot_bak_dir).unwrap(),
+                RocksSnapshot::new_for_receiving(snapshot_bak_dir.join("rocks")).unwrap(),
+            )),
+            Snapshot::Redb(metrics::Layer::new(
+                RedbSnapshot::new_for_receiving(snapshot_bak_dir.join("redb")).unwrap(),
             )),
         ];
 
@@ -455,9 +601,11 @@ mod test {
     fn txn_operations_should_success() {
         let dir = PathBuf::from("/tmp/txn_operations_should_success");
         let rocks_engine_path = dir.join("rocks_engine");
+        let redb_engine_path = dir.join("redb_engine");
         let engines = vec![
             Engine::new(EngineType::Memory, &TESTTABLES).unwrap(),
             Engine::new(EngineType::Rocks(rocks_engine_path), &TESTTABLES).unwrap(),
+            Engine::new(EngineType::Redb(redb_engine_path), &TESTTABLES).unwrap(),
         ];
         for engine in engines {
             let txn = engine.transaction();
@@ -466,4 +614,30 @@ mod test {
         }
         std::fs::remove_dir_all(dir).unwrap();
     }
+
+    #[test]
+    fn batch_across_tables_should_update_table_size() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let dir = tmp_dir.path();
+        let engines = vec![
+            Engine::new(EngineType::Memory, &TESTTABLES).unwrap(),
+            Engine::new(EngineType::Rocks(dir.join("rocks")), &TESTTABLES).unwrap(),
+            Engine::new(EngineType::Redb(dir.join("redb")), &TESTTABLES).unwrap(),
+        ];
+        for engine in engines {
+            let ops = vec![
+                WriteOperation::new_put("kv", "key".into(), "value".into()),
+                WriteOperation::new_put("lease", "lease_id".into(), "lease".into()),
+            ];
+            engine.write_batch(ops, true).unwrap();
+            assert_eq!(engine.get("kv", "key").unwrap(), Some("value".into()));
+            assert_eq!(
+                engine.get("lease", "lease_id").unwrap(),
+                Some("lease".into())
+            );
+            assert!(engine.table_size("kv").unwrap() > 0);
+            assert!(engine.table_size("lease").unwrap() > 0);
+            assert!(engine.table_size("not_exist").is_err());
+        }
+    }
 }
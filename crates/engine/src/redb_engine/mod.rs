@@ -0,0 +1,520 @@
+/// redb transaction implementation
+mod transaction;
+
+use std::{
+    fs::{self, File},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use bytes::{Bytes, BytesMut};
+use clippy_utilities::{NumericCast, OverflowArithmetic};
+use parking_lot::RwLock;
+use redb::{Database, Durability, Error as RedbError, ReadableTable, TableDefinition};
+
+pub(super) use self::transaction::RedbTransaction;
+use crate::{
+    api::{engine_api::StorageEngine, snapshot_api::SnapshotApi},
+    error::EngineError,
+    WriteOperation,
+};
+
+/// File name of the database in the data directory
+const DB_FILE: &str = "xline.redb";
+/// File name of the snapshot in the snapshot directory
+const SNAPSHOT_FILE: &str = "snapshot.redb";
+/// Size of a read from the snapshot when the buffer has no spare capacity: 64KB
+const READ_CHUNK_SIZE: usize = 64 * 1024;
+/// Length of the header of a snapshot
+const HEADER_LEN: usize = 8;
+
+/// Translate a `RedbError` into a `EngineError`
+impl From<RedbError> for EngineError {
+    #[inline]
+    fn from(err: RedbError) -> Self {
+        #[allow(clippy::wildcard_enum_match_arm)]
+        match err {
+            RedbError::TableDoesNotExist(table) => EngineError::TableNotFound(table),
+            RedbError::Io(e) => EngineError::IoError(e),
+            RedbError::Corrupted(msg) => EngineError::Corruption(msg),
+            e => EngineError::UnderlyingError(e.to_string()),
+        }
+    }
+}
+
+/// Definition of a table, every table maps bytes to bytes
+fn table_def(name: &str) -> TableDefinition<'_, &'static [u8], &'static [u8]> {
+    TableDefinition::new(name)
+}
+
+/// `redb` Storage Engine, a pure-Rust embedded database which stores all tables in a
+/// single file, it's suitable for small deployments that don't want to build `RocksDB`
+#[derive(Debug)]
+pub struct RedbEngine {
+    /// The inner database, it's only locked exclusively when defragmenting. Every
+    /// transaction holds the shared guard until it ends, so that defragmenting never
+    /// runs while a transaction is open
+    inner: RwLock<Database>,
+    /// Path of the database file
+    path: PathBuf,
+    /// The tables of current engine
+    tables: Vec<&'static str>,
+    /// The size cache of the engine
+    size: AtomicU64,
+}
+
+impl RedbEngine {
+    /// New `RedbEngine`, the tables are created if they don't exist
+    ///
+    /// # Errors
+    ///
+    /// Return `EngineError` when DB open failed.
+    #[inline]
+    pub fn new(data_dir: impl AsRef<Path>, tables: &[&'static str]) -> Result<Self, EngineError> {
+        fs::create_dir_all(&data_dir)?;
+        let path = data_dir.as_ref().join(DB_FILE);
+        let db = Database::create(&path).map_err(RedbError::from)?;
+        let txn = db.begin_write().map_err(RedbError::from)?;
+        for table in tables {
+            let _ignore = txn.open_table(table_def(table)).map_err(RedbError::from)?;
+        }
+        txn.commit().map_err(RedbError::from)?;
+        let size = fs::metadata(&path)?.len();
+        Ok(Self {
+            inner: RwLock::new(db),
+            path,
+            tables: tables.to_vec(),
+            size: AtomicU64::new(size),
+        })
+    }
+
+    /// Check whether the table belongs to the engine, tables are created implicitly by
+    /// `redb` when they are opened for writing
+    fn check_table(&self, table: &str) -> Result<(), EngineError> {
+        if self.tables.iter().any(|t| *t == table) {
+            Ok(())
+        } else {
+            Err(EngineError::TableNotFound(table.to_owned()))
+        }
+    }
+
+    /// Get the values of the keys in the table
+    fn get_values(
+        &self,
+        table: &str,
+        keys: &[impl AsRef<[u8]>],
+    ) -> Result<Vec<Option<Vec<u8>>>, RedbError> {
+        let db = self.inner.read();
+        let txn = db.begin_read()?;
+        let table = txn.open_table(table_def(table))?;
+        keys.iter()
+            .map(|key| Ok(table.get(key.as_ref())?.map(|v| v.value().to_vec())))
+            .collect()
+    }
+
+    /// Apply the write operations in a single transaction
+    fn apply(&self, wr_ops: Vec<WriteOperation<'_>>, sync: bool) -> Result<(), RedbError> {
+        let db = self.inner.read();
+        let mut txn = db.begin_write()?;
+        if !sync {
+            txn.set_durability(Durability::Eventual);
+        }
+        for op in wr_ops {
+            match op {
+                WriteOperation::Put { table, key, value } => {
+                    let mut table = txn.open_table(table_def(table))?;
+                    let _ignore = table.insert(key.as_slice(), value.as_slice())?;
+                }
+                WriteOperation::Delete { table, key } => {
+                    let mut table = txn.open_table(table_def(table))?;
+                    let _ignore = table.remove(key)?;
+                }
+                WriteOperation::DeleteRange { table, from, to } => {
+                    let mut table = txn.open_table(table_def(table))?;
+                    let keys = table
+                        .range::<&[u8]>(from..to)?
+                        .map(|entry| entry.map(|(k, _v)| k.value().to_vec()))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    for key in keys {
+                        let _ignore = table.remove(key.as_slice())?;
+                    }
+                }
+            }
+        }
+        txn.commit()?;
+        Ok(())
+    }
+
+    /// Copy the tables from the source database to the destination database, the
+    /// tables of the destination are replaced
+    fn copy_tables(src: &Database, dst: &Database, tables: &[&str]) -> Result<(), RedbError> {
+        let read_txn = src.begin_read()?;
+        let write_txn = dst.begin_write()?;
+        for name in tables {
+            let _ignore = write_txn.delete_table(table_def(name))?;
+            let mut dst_table = write_txn.open_table(table_def(name))?;
+            let src_table = match read_txn.open_table(table_def(name)) {
+                Ok(table) => table,
+                Err(redb::TableError::TableDoesNotExist(_)) => continue,
+                Err(e) => return Err(e.into()),
+            };
+            for entry in src_table.iter()? {
+                let (key, value) = entry?;
+                let _ignore = dst_table.insert(key.value(), value.value())?;
+            }
+        }
+        write_txn.commit()?;
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl StorageEngine for RedbEngine {
+    type Snapshot = RedbSnapshot;
+    type Transaction = RedbTransaction;
+
+    #[inline]
+    fn transaction(&self) -> RedbTransaction {
+        RedbTransaction
+    }
+
+    #[inline]
+    fn get(&self, table: &str, key: impl AsRef<[u8]>) -> Result<Option<Vec<u8>>, EngineError> {
+        self.check_table(table)?;
+        Ok(self.get_values(table, &[key])?.pop().flatten())
+    }
+
+    #[inline]
+    fn get_multi(
+        &self,
+        table: &str,
+        keys: &[impl AsRef<[u8]>],
+    ) -> Result<Vec<Option<Vec<u8>>>, EngineError> {
+        self.check_table(table)?;
+        Ok(self.get_values(table, keys)?)
+    }
+
+    #[inline]
+    fn get_all(&self, table: &str) -> Result<Vec<(Vec<u8>, Vec<u8>)>, EngineError> {
+        self.check_table(table)?;
+        let get_all = || -> Result<Vec<(Vec<u8>, Vec<u8>)>, RedbError> {
+            let db = self.inner.read();
+            let txn = db.begin_read()?;
+            let table = txn.open_table(table_def(table))?;
+            let mut values = Vec::new();
+            for entry in table.iter()? {
+                let (key, value) = entry?;
+                values.push((key.value().to_vec(), value.value().to_vec()));
+            }
+            Ok(values)
+        };
+        Ok(get_all()?)
+    }
+
+    #[inline]
+    fn get_from(
+        &self,
+        table: &str,
+        from: &[u8],
+        limit: usize,
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>, EngineError> {
+        self.check_table(table)?;
+        let get_from = || -> Result<Vec<(Vec<u8>, Vec<u8>)>, RedbError> {
+            let db = self.inner.read();
+            let txn = db.begin_read()?;
+            let table = txn.open_table(table_def(table))?;
+            let mut values = Vec::new();
+            for entry in table.range(from..)?.take(limit) {
+                let (key, value) = entry?;
+                values.push((key.value().to_vec(), value.value().to_vec()));
+            }
+            Ok(values)
+        };
+        Ok(get_from()?)
+    }
+
+    #[inline]
+    fn write_batch(&self, wr_ops: Vec<WriteOperation<'_>>, sync: bool) -> Result<(), EngineError> {
+        for op in &wr_ops {
+            match *op {
+                WriteOperation::Put { table, .. }
+                | WriteOperation::Delete { table, .. }
+                | WriteOperation::DeleteRange { table, .. } => self.check_table(table)?,
+            }
+        }
+        Ok(self.apply(wr_ops, sync)?)
+    }
+
+    #[inline]
+    fn get_snapshot(
+        &self,
+        path: impl AsRef<Path>,
+        tables: &[&'static str],
+    ) -> Result<Self::Snapshot, EngineError> {
+        if path.as_ref().exists() {
+            fs::remove_dir_all(&path)?;
+        }
+        fs::create_dir_all(&path)?;
+        let snap_path = path.as_ref().join(SNAPSHOT_FILE);
+        let snap_db = Database::create(&snap_path).map_err(RedbError::from)?;
+        Self::copy_tables(&self.inner.read(), &snap_db, tables)?;
+        drop(snap_db);
+        RedbSnapshot::new_for_sending(snap_path)
+    }
+
+    #[inline]
+    async fn apply_snapshot(
+        &self,
+        mut snapshot: Self::Snapshot,
+        tables: &[&'static str],
+    ) -> Result<(), EngineError> {
+        snapshot.finish_receiving()?;
+        {
+            let snap_db = Database::open(&snapshot.path).map_err(RedbError::from)?;
+            Self::copy_tables(&snap_db, &self.inner.read(), tables)?;
+        }
+        snapshot.clean().await?;
+        Ok(())
+    }
+
+    fn estimated_file_size(&self) -> u64 {
+        self.size.load(Ordering::Relaxed)
+    }
+
+    fn file_size(&self) -> Result<u64, EngineError> {
+        let size = fs::metadata(&self.path)?.len();
+        self.size.store(size, Ordering::Relaxed);
+        Ok(size)
+    }
+
+    fn live_data_size(&self) -> Result<u64, EngineError> {
+        let mut size = 0;
+        for table in &self.tables {
+            size = self.table_size(table)?.overflow_add(size);
+        }
+        Ok(size)
+    }
+
+    fn defragment(&self) -> Result<(), EngineError> {
+        let _compacted = self.inner.write().compact().map_err(RedbError::from)?;
+        let _size = self.file_size()?;
+        Ok(())
+    }
+
+    /// Writes that are not synced are committed with `Durability::Eventual`, they are
+    /// persisted by the next commit with the default `Durability::Immediate`
+    fn sync(&self) -> Result<(), EngineError> {
+        let db = self.inner.read();
+        let txn = db.begin_write().map_err(RedbError::from)?;
+        txn.commit().map_err(RedbError::from)?;
+        Ok(())
+    }
+
+    fn table_size(&self, table: &str) -> Result<u64, EngineError> {
+        self.check_table(table)?;
+        let table_size = || -> Result<u64, RedbError> {
+            let db = self.inner.read();
+            let txn = db.begin_read()?;
+            let table = txn.open_table(table_def(table))?;
+            let mut size = 0;
+            for entry in table.iter()? {
+                let (key, value) = entry?;
+                size = key
+                    .value()
+                    .len()
+                    .overflow_add(value.value().len())
+                    .overflow_add(size);
+            }
+            Ok(size.numeric_cast())
+        };
+        Ok(table_size()?)
+    }
+}
+
+/// Snapshot type for `redb`, it's a `redb` database file that holds a copy of the tables.
+/// The file is sent after a header, which is the size of the file in little endian, so that
+/// the receiver ignores the bytes after the file.
+#[derive(Debug)]
+pub struct RedbSnapshot {
+    /// Path of the snapshot file
+    path: PathBuf,
+    /// Size of the snapshot file, it's known after the header is received
+    file_size: u64,
+    /// The header, it's partial before it's fully received
+    header: Vec<u8>,
+    /// Number of header bytes that have been read
+    header_read: usize,
+    /// Number of file bytes that have been received
+    received: u64,
+    /// The opened snapshot file, it's opened on the first read or write
+    file: Option<File>,
+}
+
+impl RedbSnapshot {
+    /// Create a new snapshot for receiving
+    /// # Errors
+    /// Return `EngineError` when create directory failed.
+    #[inline]
+    pub fn new_for_receiving<P>(dir: P) -> Result<Self, EngineError>
+    where
+        P: Into<PathBuf>,
+    {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            path: dir.join(SNAPSHOT_FILE),
+            file_size: 0,
+            header: Vec::with_capacity(HEADER_LEN),
+            header_read: 0,
+            received: 0,
+            file: None,
+        })
+    }
+
+    /// Create a new snapshot for sending
+    /// # Errors
+    /// Return `EngineError` when read the snapshot file failed.
+    #[inline]
+    pub fn new_for_sending<P>(path: P) -> Result<Self, EngineError>
+    where
+        P: Into<PathBuf>,
+    {
+        let path = path.into();
+        let file_size = fs::metadata(&path)?.len();
+        Ok(Self {
+            path,
+            file_size,
+            header: file_size.to_le_bytes().to_vec(),
+            header_read: 0,
+            received: 0,
+            file: None,
+        })
+    }
+
+    /// Flush and close the received snapshot file
+    fn finish_receiving(&mut self) -> io::Result<()> {
+        if let Some(mut file) = self.file.take() {
+            file.flush()?;
+            file.sync_all()?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl SnapshotApi for RedbSnapshot {
+    #[inline]
+    fn size(&self) -> u64 {
+        self.file_size.overflow_add(HEADER_LEN.numeric_cast())
+    }
+
+    #[inline]
+    fn rewind(&mut self) -> io::Result<()> {
+        self.header_read = 0;
+        self.file = None;
+        Ok(())
+    }
+
+    #[inline]
+    async fn read_buf(&mut self, buf: &mut BytesMut) -> io::Result<()> {
+        // never read more than the spare capacity, so that `read_buf_exact` won't overrun
+        let spare = buf.capacity().overflow_sub(buf.len());
+        let limit = if spare == 0 { READ_CHUNK_SIZE } else { spare };
+        if let Some(header) = self.header.get(self.header_read..) {
+            if !header.is_empty() {
+                let n = header.len().min(limit);
+                buf.extend_from_slice(header.get(..n).unwrap_or_default());
+                self.header_read = self.header_read.overflow_add(n);
+                return Ok(());
+            }
+        }
+        let file = match self.file {
+            Some(ref mut file) => file,
+            None => self.file.insert(File::open(&self.path)?),
+        };
+        let mut chunk = vec![0; limit];
+        let n = file.read(&mut chunk)?;
+        chunk.truncate(n);
+        buf.extend_from_slice(&chunk);
+        Ok(())
+    }
+
+    #[inline]
+    async fn write_all(&mut self, mut buf: Bytes) -> io::Result<()> {
+        if self.header.len() < HEADER_LEN {
+            let n = HEADER_LEN.overflow_sub(self.header.len()).min(buf.len());
+            self.header.extend_from_slice(&buf.split_to(n));
+            let Ok(header) = <[u8; HEADER_LEN]>::try_from(self.header.as_slice()) else {
+                return Ok(());
+            };
+            self.file_size = u64::from_le_bytes(header);
+        }
+        let remain: usize = self.file_size.overflow_sub(self.received).numeric_cast();
+        let n = remain.min(buf.len());
+        if n == 0 {
+            return Ok(());
+        }
+        let file = match self.file {
+            Some(ref mut file) => file,
+            None => self.file.insert(File::create(&self.path)?),
+        };
+        file.write_all(&buf.split_to(n))?;
+        self.received = self.received.overflow_add(n.numeric_cast());
+        Ok(())
+    }
+
+    #[inline]
+    async fn clean(&mut self) -> io::Result<()> {
+        self.file = None;
+        if self.path.exists() {
+            fs::remove_file(&self.path)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    static TEST_TABLES: [&str; 2] = ["t1", "t2"];
+
+    #[test]
+    fn data_should_be_persisted_after_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let engine = RedbEngine::new(dir.path(), &TEST_TABLES).unwrap();
+        engine
+            .write_batch(
+                vec![
+                    WriteOperation::new_put("t1", b"a".to_vec(), b"1".to_vec()),
+                    WriteOperation::new_put("t1", b"b".to_vec(), b"2".to_vec()),
+                    WriteOperation::new_put("t1", b"c".to_vec(), b"3".to_vec()),
+                    WriteOperation::new_delete_range("t1", b"b", b"c"),
+                ],
+                false,
+            )
+            .unwrap();
+        assert!(engine
+            .write_batch(
+                vec![WriteOperation::new_put("t3", b"a".to_vec(), vec![])],
+                false
+            )
+            .is_err());
+        drop(engine);
+
+        let engine = RedbEngine::new(dir.path(), &TEST_TABLES).unwrap();
+        assert_eq!(
+            engine.get_all("t1").unwrap(),
+            vec![
+                (b"a".to_vec(), b"1".to_vec()),
+                (b"c".to_vec(), b"3".to_vec())
+            ]
+        );
+        assert!(engine.get_all("t2").unwrap().is_empty());
+        assert!(matches!(
+            engine.get("t3", b"a"),
+            Err(EngineError::TableNotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,17 @@
+#![allow(clippy::module_name_repetitions)]
+
+use crate::TransactionApi;
+
+/// A transaction of the `RedbEngine`, writes are committed by `write_batch` atomically
+#[derive(Copy, Clone, Debug, Default)]
+pub struct RedbTransaction;
+
+impl TransactionApi for RedbTransaction {
+    fn commit(self) -> Result<(), crate::EngineError> {
+        Ok(())
+    }
+
+    fn rollback(&self) -> Result<(), crate::EngineError> {
+        Ok(())
+    }
+}
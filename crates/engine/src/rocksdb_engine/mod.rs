@@ -1,3 +1,5 @@
+/// Read-only `RocksDB` engine
+mod read_only;
 /// `RocksDB` transaction implementation
 mod transaction;
 
@@ -14,14 +16,16 @@ use std::{
 use bytes::{Buf, Bytes, BytesMut};
 use clippy_utilities::{NumericCast, OverflowArithmetic};
 use rocksdb::{
-    Direction, Error as RocksError, ErrorKind as RocksErrorKind, IteratorMode,
-    OptimisticTransactionDB, Options, SstFileWriter,
+    BottommostLevelCompaction, CompactOptions, Direction, Error as RocksError,
+    ErrorKind as RocksErrorKind, IteratorMode, OptimisticTransactionDB,
+    OptimisticTransactionOptions, Options, SstFileWriter, WriteOptions,
 };
 use serde::{Deserialize, Serialize};
 use tokio::{fs::File, io::AsyncWriteExt};
 use tokio_util::io::read_buf;
 use tracing::warn;
 
+pub use self::read_only::ReadOnlyRocksEngine;
 pub(super) use self::transaction::RocksTransaction;
 use crate::{
     api::{engine_api::StorageEngine, snapshot_api::SnapshotApi},
@@ -200,12 +204,36 @@ impl StorageEngine for RocksEngine {
     }
 
     #[inline]
-    fn write_batch(&self, wr_ops: Vec<WriteOperation<'_>>, _sync: bool) -> Result<(), EngineError> {
+    fn get_from(
+        &self,
+        table: &str,
+        from: &[u8],
+        limit: usize,
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>, EngineError> {
+        if let Some(cf) = self.inner.cf_handle(table) {
+            self.inner
+                .iterator_cf(&cf, IteratorMode::From(from, Direction::Forward))
+                .take(limit)
+                .map(|v| {
+                    v.map(|(key, value)| (key.to_vec(), value.to_vec()))
+                        .map_err(EngineError::from)
+                })
+                .collect()
+        } else {
+            Err(EngineError::TableNotFound(table.to_owned()))
+        }
+    }
+
+    #[inline]
+    fn write_batch(&self, wr_ops: Vec<WriteOperation<'_>>, sync: bool) -> Result<(), EngineError> {
         let mut retry_interval = 10;
         let max_retry_count = 5;
         let mut retry_count = 0;
+        let mut write_opts = WriteOptions::default();
+        write_opts.set_sync(sync);
+        let txn_opts = OptimisticTransactionOptions::default();
         loop {
-            let transaction = self.inner.transaction();
+            let transaction = self.inner.transaction_opt(&write_opts, &txn_opts);
             let mut size = 0;
             #[allow(clippy::pattern_type_mismatch)] // can't be fixed
             for op in &wr_ops {
@@ -339,6 +367,66 @@ impl StorageEngine for RocksEngine {
         self.size.store(size, std::sync::atomic::Ordering::Relaxed);
         Ok(size)
     }
+
+    fn live_data_size(&self) -> Result<u64, EngineError> {
+        let mut size = 0;
+        for table in &self.tables {
+            let cf = self
+                .inner
+                .cf_handle(table)
+                .ok_or_else(|| EngineError::TableNotFound(table.clone()))?;
+            size = self
+                .inner
+                .property_int_value_cf(&cf, rocksdb::properties::ESTIMATE_LIVE_DATA_SIZE)?
+                .ok_or(EngineError::UnderlyingError(
+                    "Got None when read ESTIMATE_LIVE_DATA_SIZE".to_owned(),
+                ))?
+                .overflow_add(size);
+        }
+        Ok(size)
+    }
+
+    fn defragment(&self) -> Result<(), EngineError> {
+        let mut opts = CompactOptions::default();
+        // rewrite the bottommost level as well, otherwise the tombstones in it won't be dropped
+        opts.set_bottommost_level_compaction(BottommostLevelCompaction::Force);
+        for table in &self.tables {
+            let cf = self
+                .inner
+                .cf_handle(table)
+                .ok_or_else(|| EngineError::TableNotFound(table.clone()))?;
+            self.inner
+                .compact_range_cf_opt(&cf, None::<&[u8]>, None::<&[u8]>, &opts);
+        }
+        Ok(())
+    }
+
+    fn sync(&self) -> Result<(), EngineError> {
+        Ok(self.inner.flush_wal(true)?)
+    }
+
+    /// Every table is a column family, its size is the size of its sst files and
+    /// memtables, the memtables are not flushed, so it's cheap to call
+    fn table_size(&self, table: &str) -> Result<u64, EngineError> {
+        let cf = self
+            .inner
+            .cf_handle(table)
+            .ok_or_else(|| EngineError::TableNotFound(table.to_owned()))?;
+        let mut size = 0;
+        for property in [
+            rocksdb::properties::TOTAL_SST_FILES_SIZE,
+            rocksdb::properties::CUR_SIZE_ALL_MEM_TABLES,
+        ] {
+            size = self
+                .inner
+                .property_int_value_cf(&cf, property)?
+                .ok_or_else(|| {
+                    EngineError::UnderlyingError(format!("Got None when read size of {table}"))
+                })?
+                .overflow_add(size);
+        }
+        Ok(size)
+    }
 }
 
 /// Human readable format for `RocksEngine`
@@ -0,0 +1,106 @@
+use std::path::Path;
+
+use rocksdb::{IteratorMode, Options, DB};
+
+use crate::error::EngineError;
+
+/// A `RocksDB` data dir opened read-only, which never modifies the data dir, so it can
+/// be used to inspect the data dir of a stopped or corrupted member
+#[derive(Debug)]
+#[allow(clippy::module_name_repetitions)]
+pub struct ReadOnlyRocksEngine {
+    /// The inner read-only db
+    inner: DB,
+}
+
+impl ReadOnlyRocksEngine {
+    /// Open the data dir read-only, the tables that don't exist in the data dir are
+    /// skipped
+    ///
+    /// # Errors
+    ///
+    /// Return `EngineError` when the data dir does not exist or the db is corrupted
+    #[inline]
+    pub fn open(data_dir: impl AsRef<Path>, tables: &[&'static str]) -> Result<Self, EngineError> {
+        let opts = Options::default();
+        let existing = DB::list_cf(&opts, data_dir.as_ref())?;
+        let tables = tables
+            .iter()
+            .filter(|table| existing.iter().any(|name| name == **table));
+        let inner = DB::open_cf_for_read_only(&opts, data_dir, tables, false)?;
+        Ok(Self { inner })
+    }
+
+    /// Get all key-value pairs of a table
+    ///
+    /// # Errors
+    ///
+    /// Return `EngineError::TableNotFound` if the table does not exist
+    #[inline]
+    pub fn get_all(&self, table: &str) -> Result<Vec<(Vec<u8>, Vec<u8>)>, EngineError> {
+        let cf = self
+            .inner
+            .cf_handle(table)
+            .ok_or_else(|| EngineError::TableNotFound(table.to_owned()))?;
+        self.inner
+            .iterator_cf(&cf, IteratorMode::Start)
+            .map(|v| {
+                v.map(|(key, value)| (key.to_vec(), value.to_vec()))
+                    .map_err(EngineError::from)
+            })
+            .collect()
+    }
+
+    /// Get the total sst file size of a table, the memtables are not flushed because
+    /// the db is read-only
+    ///
+    /// # Errors
+    ///
+    /// Return `EngineError::TableNotFound` if the table does not exist
+    #[inline]
+    pub fn table_size(&self, table: &str) -> Result<u64, EngineError> {
+        let cf = self
+            .inner
+            .cf_handle(table)
+            .ok_or_else(|| EngineError::TableNotFound(table.to_owned()))?;
+        self.inner
+            .property_int_value_cf(&cf, rocksdb::properties::TOTAL_SST_FILES_SIZE)?
+            .ok_or_else(|| {
+                EngineError::UnderlyingError(format!("Got None when read size of {table}"))
+            })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::env::temp_dir;
+
+    use super::*;
+    use crate::{rocksdb_engine::RocksEngine, StorageEngine, WriteOperation};
+
+    #[test]
+    fn read_only_engine_should_read_the_data_dir() {
+        let data_dir = temp_dir().join("read_only_engine_should_read_the_data_dir");
+        let engine = RocksEngine::new(&data_dir, &["kv"]).unwrap();
+        engine
+            .write_batch(
+                vec![WriteOperation::new_put(
+                    "kv",
+                    b"foo".to_vec(),
+                    b"bar".to_vec(),
+                )],
+                true,
+            )
+            .unwrap();
+        // the read-only engine can be opened while the data dir is used
+        let read_only = ReadOnlyRocksEngine::open(&data_dir, &["kv"]).unwrap();
+        assert_eq!(
+            read_only.get_all("kv").unwrap(),
+            vec![(b"foo".to_vec(), b"bar".to_vec())]
+        );
+        assert!(read_only.get_all("lease").is_err());
+        drop(read_only);
+        drop(engine);
+        std::fs::remove_dir_all(data_dir).unwrap();
+    }
+}
@@ -30,3 +30,18 @@ impl SnapshotAllocator for MemorySnapshotAllocator {
         Ok(Snapshot::new_for_receiving(EngineType::Memory)?)
     }
 }
+
+/// Redb snapshot allocator
+#[derive(Debug, Copy, Clone, Default)]
+#[non_exhaustive]
+#[allow(clippy::module_name_repetitions)]
+pub struct RedbSnapshotAllocator;
+
+#[async_trait::async_trait]
+impl SnapshotAllocator for RedbSnapshotAllocator {
+    #[inline]
+    async fn allocate_new_snapshot(&self) -> Result<Snapshot, Box<dyn Error>> {
+        let tmp_path = temp_dir().join(format!("snapshot-{}", uuid::Uuid::new_v4()));
+        Ok(Snapshot::new_for_receiving(EngineType::Redb(tmp_path))?)
+    }
+}
@@ -0,0 +1,501 @@
+//! A Jepsen-style harness: concurrent clients record a history of operations on an
+//! `XlineGroup` while a nemesis injects faults, then the history is checked for the
+//! linearizability of the kv store and the safety of the leases.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use futures::future::join_all;
+use madsim::{
+    rand::{thread_rng, Rng},
+    runtime::Handle,
+    time::{sleep, timeout, Instant},
+};
+use parking_lot::Mutex;
+use tracing::info;
+use xline_client::types::{
+    kv::{PutRequest, RangeRequest},
+    lease::{LeaseGrantRequest, LeaseRevokeRequest},
+};
+
+use crate::xline_group::{SimClient, XlineGroup};
+
+/// Timeout of an operation, an operation timed out may or may not take effect
+const OP_TIMEOUT: Duration = Duration::from_secs(5);
+/// Keys of the leases start from this, so they never collide with the register keys
+const LEASE_KEY_BASE: u64 = 1 << 32;
+
+/// An operation on a register, which is a key of the kv store
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    /// Read the value of the key
+    Read,
+    /// Write a unique value to the key
+    Write(u64),
+}
+
+/// What a client observed of an operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// The operation succeeded, a read returns the value it observed
+    Ok(Option<u64>),
+    /// The operation failed or timed out, it may or may not have taken effect
+    Unknown,
+}
+
+/// An operation in the history
+#[derive(Debug, Clone, Copy)]
+pub struct Record {
+    /// The key
+    pub key: u64,
+    /// The operation
+    pub op: Op,
+    /// When the operation is invoked
+    pub invoke: Duration,
+    /// When the response of the operation is received
+    pub complete: Duration,
+    /// What the client observed
+    pub outcome: Outcome,
+}
+
+/// A lease in the history, a unique key is attached to each lease
+#[derive(Debug, Clone, Copy)]
+pub struct LeaseRecord {
+    /// Id of the lease
+    pub id: i64,
+    /// The key attached to the lease
+    pub key: u64,
+    /// When the grant is invoked, the lease never expires earlier than `ttl` after it
+    pub grant_invoke: Duration,
+    /// Ttl granted by the server
+    pub ttl: Duration,
+    /// When the put of the key with the lease succeeded
+    pub put_complete: Option<Duration>,
+    /// When the revoke is invoked
+    pub revoke_invoke: Option<Duration>,
+    /// When the revoke succeeded
+    pub revoke_complete: Option<Duration>,
+}
+
+/// History of the operations of all clients
+#[derive(Debug)]
+pub struct History {
+    /// When the history starts
+    start: Instant,
+    /// Operations on the registers and the lease keys
+    records: Mutex<Vec<Record>>,
+    /// Leases
+    leases: Mutex<Vec<LeaseRecord>>,
+}
+
+impl History {
+    /// New empty `History`
+    pub fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            records: Mutex::new(Vec::new()),
+            leases: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Time elapsed since the history starts
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    /// Operations recorded
+    pub fn records(&self) -> Vec<Record> {
+        self.records.lock().clone()
+    }
+
+    /// Leases recorded
+    pub fn leases(&self) -> Vec<LeaseRecord> {
+        self.leases.lock().clone()
+    }
+
+    /// Check the history, return the violations found
+    pub fn check(&self) -> Vec<String> {
+        let records = self.records();
+        let mut violations =
+            check_linearizability(records.iter().filter(|record| record.key < LEASE_KEY_BASE));
+        violations.extend(check_leases(&records, &self.leases()));
+        violations
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Parameters of the workload
+#[derive(Debug, Clone, Copy)]
+pub struct Workload {
+    /// Number of concurrent clients
+    pub clients: usize,
+    /// Number of operations of each client
+    pub ops_per_client: usize,
+    /// Number of registers
+    pub keys: u64,
+    /// Probability in percent that an operation works on a lease
+    pub lease_percent: u32,
+    /// Ttl of the leases in seconds
+    pub lease_ttl: i64,
+}
+
+impl Default for Workload {
+    fn default() -> Self {
+        Self {
+            clients: 4,
+            ops_per_client: 100,
+            keys: 3,
+            lease_percent: 10,
+            lease_ttl: 5,
+        }
+    }
+}
+
+/// Run the workload against the group and record the history
+pub async fn run_workload(group: &XlineGroup, workload: Workload, history: &History) {
+    let mut clients = Vec::with_capacity(workload.clients);
+    for _ in 0..workload.clients {
+        clients.push(group.client().await);
+    }
+    let _ignore = join_all(
+        clients
+            .iter()
+            .enumerate()
+            .map(|(idx, client)| run_client(idx, client, workload, history)),
+    )
+    .await;
+}
+
+/// Run the operations of a client
+async fn run_client(idx: usize, client: &SimClient, workload: Workload, history: &History) {
+    for seq in 0..workload.ops_per_client {
+        // the values are unique among all clients
+        let value = u64::try_from(idx * workload.ops_per_client + seq + 1).unwrap();
+        if thread_rng().gen_range(0..100) < workload.lease_percent {
+            run_lease(client, value, workload.lease_ttl, history).await;
+            continue;
+        }
+        let key = thread_rng().gen_range(0..workload.keys);
+        let op = if thread_rng().gen_bool(0.5) {
+            Op::Read
+        } else {
+            Op::Write(value)
+        };
+        let _outcome = run_op(client, key, op, history).await;
+    }
+}
+
+/// Run an operation on a key and record it
+async fn run_op(client: &SimClient, key: u64, op: Op, history: &History) -> Outcome {
+    let invoke = history.now();
+    let outcome = match op {
+        Op::Read => match timeout(OP_TIMEOUT, client.range(RangeRequest::new(key_of(key)))).await {
+            Ok(Ok(resp)) => Outcome::Ok(resp.kvs.first().map(|kv| decode_value(&kv.value))),
+            _ => Outcome::Unknown,
+        },
+        Op::Write(value) => {
+            let req = PutRequest::new(key_of(key), value.to_string());
+            match timeout(OP_TIMEOUT, client.put(req)).await {
+                Ok(Ok(_)) => Outcome::Ok(None),
+                _ => Outcome::Unknown,
+            }
+        }
+    };
+    history.records.lock().push(Record {
+        key,
+        op,
+        invoke,
+        complete: history.now(),
+        outcome,
+    });
+    outcome
+}
+
+/// Grant a lease, attach a key to it, read the key, and revoke the lease by chance
+async fn run_lease(client: &SimClient, value: u64, ttl: i64, history: &History) {
+    let key = LEASE_KEY_BASE + value;
+    let grant_invoke = history.now();
+    let Ok(Ok(resp)) = timeout(OP_TIMEOUT, client.grant(LeaseGrantRequest::new(ttl))).await else {
+        return;
+    };
+    let mut lease = LeaseRecord {
+        id: resp.id,
+        key,
+        grant_invoke,
+        ttl: Duration::from_secs(u64::try_from(resp.ttl).unwrap_or(0)),
+        put_complete: None,
+        revoke_invoke: None,
+        revoke_complete: None,
+    };
+    let req = PutRequest::new(key_of(key), value.to_string()).with_lease(resp.id);
+    if let Ok(Ok(_)) = timeout(OP_TIMEOUT, client.put(req)).await {
+        lease.put_complete = Some(history.now());
+    }
+    let _outcome = run_op(client, key, Op::Read, history).await;
+    if thread_rng().gen_bool(0.5) {
+        lease.revoke_invoke = Some(history.now());
+        let req = LeaseRevokeRequest::new(resp.id);
+        if let Ok(Ok(_)) = timeout(OP_TIMEOUT, client.revoke(req)).await {
+            lease.revoke_complete = Some(history.now());
+        }
+        let _outcome = run_op(client, key, Op::Read, history).await;
+    }
+    history.leases.lock().push(lease);
+}
+
+/// Key in the kv store of a register
+fn key_of(key: u64) -> String {
+    format!("jepsen/{key}")
+}
+
+/// Decode a value written by the workload
+fn decode_value(value: &[u8]) -> u64 {
+    std::str::from_utf8(value)
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or_else(|| panic!("unexpected value {value:?}"))
+}
+
+/// A fault injected by the nemesis
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// Isolate a node from the network
+    Partition,
+    /// Kill a node and restart it. The nodes of `XlineGroup` keep their data in memory,
+    /// so the node rejoins with an empty storage and catches up from the leader.
+    Crash,
+    /// Pause a node and resume it. The time of the simulation is global, so a clock
+    /// skew is simulated by a pause, after which the node sees its clock jump forward.
+    Pause,
+}
+
+/// Inject random faults into the nodes of the group until `stop` is set, every fault
+/// lasts for `interval` and is healed before the next one
+pub async fn run_nemesis(
+    group: &XlineGroup,
+    faults: &[Fault],
+    interval: Duration,
+    stop: &AtomicBool,
+) {
+    let handle = Handle::current();
+    let net = madsim::net::NetSim::current();
+    let names: Vec<_> = group.nodes.keys().cloned().collect();
+    while !stop.load(Ordering::Relaxed) {
+        sleep(interval).await;
+        let fault = faults[thread_rng().gen_range(0..faults.len())];
+        let name = &names[thread_rng().gen_range(0..names.len())];
+        let id = group.get_node(name).handle.id();
+        info!("nemesis injects {fault:?} into {name}");
+        match fault {
+            Fault::Partition => net.clog_node(id),
+            Fault::Crash => handle.kill(name.as_str()),
+            Fault::Pause => handle.pause(id),
+        }
+        sleep(interval).await;
+        info!("nemesis heals {fault:?} of {name}");
+        match fault {
+            Fault::Partition => net.unclog_node(id),
+            Fault::Crash => handle.restart(name.as_str()),
+            Fault::Pause => handle.resume(id),
+        }
+    }
+}
+
+/// Run the workload while the nemesis injects faults, and check the history after the
+/// faults are healed
+pub async fn run_with_faults(
+    group: &XlineGroup,
+    workload: Workload,
+    faults: &[Fault],
+    interval: Duration,
+) -> Vec<String> {
+    let history = History::new();
+    let stop = AtomicBool::new(false);
+    let workload_fut = async {
+        run_workload(group, workload, &history).await;
+        stop.store(true, Ordering::Relaxed);
+    };
+    let _ignore = futures::join!(workload_fut, run_nemesis(group, faults, interval, &stop));
+    info!(
+        "check a history of {} operations and {} leases",
+        history.records().len(),
+        history.leases().len()
+    );
+    history.check()
+}
+
+/// An operation on a register to linearize
+#[derive(Debug, Clone, Copy)]
+struct RegisterOp {
+    /// The operation
+    op: Op,
+    /// The value read by a read
+    read: Option<u64>,
+    /// When the operation is invoked
+    invoke: Duration,
+    /// When the operation completes, `Duration::MAX` if the outcome is unknown
+    complete: Duration,
+    /// Whether the operation must be linearized, a write whose outcome is unknown may
+    /// never take effect
+    required: bool,
+}
+
+/// Check the linearizability of every register, return the violations found
+pub fn check_linearizability<'a>(records: impl IntoIterator<Item = &'a Record>) -> Vec<String> {
+    let mut registers: HashMap<u64, Vec<RegisterOp>> = HashMap::new();
+    for record in records {
+        let op = match (record.op, record.outcome) {
+            // a read whose outcome is unknown observes nothing
+            (Op::Read, Outcome::Unknown) => continue,
+            (Op::Read, Outcome::Ok(read)) => RegisterOp {
+                op: Op::Read,
+                read,
+                invoke: record.invoke,
+                complete: record.complete,
+                required: true,
+            },
+            (Op::Write(_), outcome) => RegisterOp {
+                op: record.op,
+                read: None,
+                invoke: record.invoke,
+                complete: if outcome == Outcome::Unknown {
+                    Duration::MAX
+                } else {
+                    record.complete
+                },
+                required: outcome != Outcome::Unknown,
+            },
+        };
+        registers.entry(record.key).or_default().push(op);
+    }
+    let mut keys: Vec<_> = registers.keys().copied().collect();
+    keys.sort_unstable();
+    keys.into_iter()
+        .filter(|key| !Linearizer::new(&registers[key]).check())
+        .map(|key| format!("the history of register {key} is not linearizable"))
+        .collect()
+}
+
+/// Search for a linearization of the operations on a register, in the way of Wing and
+/// Gong with the memoization of Lowe
+struct Linearizer<'a> {
+    /// Operations on the register
+    ops: &'a [RegisterOp],
+    /// Whether each operation is linearized
+    linearized: Vec<bool>,
+    /// Number of required operations not linearized
+    pending: usize,
+    /// Linearized sets and values known to lead to no linearization
+    dead_ends: HashSet<(Vec<bool>, Option<u64>)>,
+}
+
+impl<'a> Linearizer<'a> {
+    /// New `Linearizer`
+    fn new(ops: &'a [RegisterOp]) -> Self {
+        Self {
+            ops,
+            linearized: vec![false; ops.len()],
+            pending: ops.iter().filter(|op| op.required).count(),
+            dead_ends: HashSet::new(),
+        }
+    }
+
+    /// Whether the operations are linearizable, the register is empty at first
+    fn check(mut self) -> bool {
+        self.search(None)
+    }
+
+    /// Search for a linearization of the rest operations from the given value
+    fn search(&mut self, value: Option<u64>) -> bool {
+        if self.pending == 0 {
+            return true;
+        }
+        if self.dead_ends.contains(&(self.linearized.clone(), value)) {
+            return false;
+        }
+        // an operation can be linearized next only if it is invoked before any other
+        // operation not linearized completes
+        let min_complete = self
+            .ops
+            .iter()
+            .zip(&self.linearized)
+            .filter(|&(_, linearized)| !linearized)
+            .map(|(op, _)| op.complete)
+            .min()
+            .unwrap_or(Duration::MAX);
+        for idx in 0..self.ops.len() {
+            let op = self.ops[idx];
+            if self.linearized[idx] || op.invoke > min_complete {
+                continue;
+            }
+            let next = match op.op {
+                Op::Read if op.read != value => continue,
+                Op::Read => value,
+                Op::Write(written) => Some(written),
+            };
+            self.linearized[idx] = true;
+            if op.required {
+                self.pending -= 1;
+            }
+            let found = self.search(next);
+            self.linearized[idx] = false;
+            if op.required {
+                self.pending += 1;
+            }
+            if found {
+                return true;
+            }
+        }
+        let _new = self.dead_ends.insert((self.linearized.clone(), value));
+        false
+    }
+}
+
+/// Check the safety of the leases, return the violations found: a key attached to a
+/// lease must be readable until the ttl of the lease passes or the lease is revoked,
+/// and must never be read after the lease is revoked
+pub fn check_leases(records: &[Record], leases: &[LeaseRecord]) -> Vec<String> {
+    let mut violations = Vec::new();
+    for lease in leases {
+        let Some(put_complete) = lease.put_complete else {
+            continue;
+        };
+        let reads = records.iter().filter_map(|record| match record.outcome {
+            Outcome::Ok(read) if record.key == lease.key && record.op == Op::Read => {
+                Some((record, read))
+            }
+            Outcome::Ok(_) | Outcome::Unknown => None,
+        });
+        for (read, value) in reads {
+            let revoked = lease
+                .revoke_complete
+                .is_some_and(|revoked| read.invoke > revoked);
+            if revoked && value.is_some() {
+                violations.push(format!(
+                    "key {} is read after lease {} is revoked",
+                    lease.key, lease.id
+                ));
+            }
+            let alive = read.invoke > put_complete
+                && read.complete < lease.grant_invoke + lease.ttl
+                && lease
+                    .revoke_invoke
+                    .map_or(true, |revoke| read.complete < revoke);
+            if alive && value.is_none() {
+                violations.push(format!(
+                    "key {} is lost before lease {} expires",
+                    lease.key, lease.id
+                ));
+            }
+        }
+    }
+    violations
+}
@@ -1,4 +1,5 @@
 #![cfg(madsim)]
 
+pub mod consistency;
 pub mod curp_group;
 pub mod xline_group;
@@ -5,8 +5,9 @@ use madsim::runtime::NodeHandle;
 use tonic::transport::Channel;
 use tracing::debug;
 use utils::config::{
-    AuthConfig, ClientConfig, ClusterConfig, CompactConfig, CurpConfig, InitialClusterState,
-    ServerTimeout, StorageConfig, TlsConfig,
+    default_max_request_bytes, default_max_txn_ops, AuthConfig, ClientConfig, ClusterConfig,
+    CompactConfig, CurpConfig, GrpcCompression, GrpcConfig, InitialClusterState, ServerTimeout,
+    StorageConfig, TlsConfig,
 };
 use xline::server::XlineServer;
 use xline_client::{
@@ -17,6 +18,7 @@ use xline_client::{
             CompactionRequest, CompactionResponse, PutRequest, PutResponse, RangeRequest,
             RangeResponse,
         },
+        lease::{LeaseGrantRequest, LeaseGrantResponse, LeaseRevokeRequest, LeaseRevokeResponse},
         watch::{WatchRequest, WatchStreaming, Watcher},
     },
     Client, ClientOptions,
@@ -60,6 +62,15 @@ impl XlineGroup {
                     ClientConfig::default(),
                     ServerTimeout::default(),
                     InitialClusterState::New,
+                    None,
+                    false,
+                    vec![],
+                    None,
+                    default_max_txn_ops(),
+                    default_max_request_bytes(),
+                    GrpcCompression::default(),
+                    GrpcConfig::default(),
+                    vec![],
                 );
 
                 let handle = handle
@@ -180,6 +191,14 @@ impl SimClient {
     impl_client_method!(compact, kv_client, CompactionRequest, CompactionResponse);
 
     impl_client_method!(watch, watch_client, WatchRequest, (Watcher, WatchStreaming));
+
+    impl_client_method!(grant, lease_client, LeaseGrantRequest, LeaseGrantResponse);
+    impl_client_method!(
+        revoke,
+        lease_client,
+        LeaseRevokeRequest,
+        LeaseRevokeResponse
+    );
 }
 
 impl Drop for XlineGroup {
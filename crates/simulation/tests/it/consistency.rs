@@ -0,0 +1,85 @@
+use std::time::Duration;
+
+use curp_test_utils::init_logger;
+use simulation::{
+    consistency::{
+        check_leases, check_linearizability, run_with_faults, Fault, Op, Outcome, Record, Workload,
+    },
+    xline_group::XlineGroup,
+};
+
+fn record(key: u64, op: Op, invoke: u64, complete: u64, outcome: Outcome) -> Record {
+    Record {
+        key,
+        op,
+        invoke: Duration::from_millis(invoke),
+        complete: Duration::from_millis(complete),
+        outcome,
+    }
+}
+
+#[test]
+fn checker_should_find_stale_reads() {
+    let history = [
+        record(0, Op::Write(1), 0, 10, Outcome::Ok(None)),
+        record(0, Op::Write(2), 20, 30, Outcome::Ok(None)),
+        record(0, Op::Read, 40, 50, Outcome::Ok(Some(1))),
+    ];
+    assert_eq!(check_linearizability(&history).len(), 1);
+
+    // concurrent with the second write, so the read may be linearized before it
+    let history = [
+        record(0, Op::Write(1), 0, 10, Outcome::Ok(None)),
+        record(0, Op::Write(2), 20, 60, Outcome::Ok(None)),
+        record(0, Op::Read, 40, 50, Outcome::Ok(Some(1))),
+        record(0, Op::Read, 70, 80, Outcome::Ok(Some(2))),
+    ];
+    assert!(check_linearizability(&history).is_empty());
+
+    // a write whose outcome is unknown may take effect at any time after it's invoked
+    let history = [
+        record(0, Op::Write(1), 0, 10, Outcome::Unknown),
+        record(0, Op::Read, 20, 30, Outcome::Ok(None)),
+        record(0, Op::Read, 40, 50, Outcome::Ok(Some(1))),
+        record(0, Op::Read, 60, 70, Outcome::Ok(None)),
+    ];
+    assert_eq!(check_linearizability(&history).len(), 1);
+    assert!(check_leases(&history, &[]).is_empty());
+}
+
+// A short run of the harness, which keeps it working in every test run
+#[madsim::test]
+async fn kv_should_be_linearizable_and_leases_safe_under_a_partition() {
+    init_logger();
+    let group = XlineGroup::new(3).await;
+    let workload = Workload {
+        clients: 2,
+        ops_per_client: 20,
+        keys: 2,
+        ..Workload::default()
+    };
+    let violations = run_with_faults(
+        &group,
+        workload,
+        &[Fault::Partition],
+        Duration::from_secs(2),
+    )
+    .await;
+    assert!(violations.is_empty(), "violations: {violations:#?}");
+}
+
+// A long-running test, run it with `--ignored`
+#[madsim::test]
+#[ignore]
+async fn kv_should_be_linearizable_and_leases_safe_under_faults() {
+    init_logger();
+    let group = XlineGroup::new(5).await;
+    let violations = run_with_faults(
+        &group,
+        Workload::default(),
+        &[Fault::Partition, Fault::Crash, Fault::Pause],
+        Duration::from_secs(5),
+    )
+    .await;
+    assert!(violations.is_empty(), "violations: {violations:#?}");
+}
@@ -1,4 +1,5 @@
 #![cfg(madsim)]
 
+mod consistency;
 mod curp;
 mod xline;
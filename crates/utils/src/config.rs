@@ -7,7 +7,7 @@ use tracing_appender::rolling::RollingFileAppender;
 
 /// Xline server configuration object
 #[allow(clippy::module_name_repetitions)]
-#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Getters, Default)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Getters, Default)]
 pub struct XlineServerConfig {
     /// cluster configuration object
     #[getset(get = "pub")]
@@ -34,6 +34,10 @@ pub struct XlineServerConfig {
     #[getset(get = "pub")]
     #[serde(default = "MetricsConfig::default")]
     metrics: MetricsConfig,
+    /// Audit log config
+    #[getset(get = "pub")]
+    #[serde(default = "AuditConfig::default")]
+    audit: AuditConfig,
 }
 
 /// Cluster Range type alias
@@ -78,6 +82,27 @@ pub mod bytes_format {
     }
 }
 
+/// backup retention deserialization formatter
+pub mod backup_retention_format {
+    use serde::{Deserialize, Deserializer};
+
+    /// deserializes the number of backups to keep, the new backup would be removed
+    /// right after it is taken if it is 0
+    #[allow(single_use_lifetimes)] //  the false positive case blocks us
+    pub(crate) fn deserialize<'de, D>(deserializer: D) -> Result<usize, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let retention = usize::deserialize(deserializer)?;
+        if retention == 0 {
+            return Err(serde::de::Error::custom(
+                "the backup retention should be at least 1",
+            ));
+        }
+        Ok(retention)
+    }
+}
+
 /// Cluster configuration object, including cluster relevant configuration fields
 #[allow(clippy::module_name_repetitions)]
 #[derive(Clone, Debug, Deserialize, PartialEq, Eq, Getters)]
@@ -119,6 +144,43 @@ pub struct ClusterConfig {
     #[getset(get = "pub")]
     #[serde(with = "state_format", default = "InitialClusterState::default")]
     initial_cluster_state: InitialClusterState,
+    /// The domain to discover peers from DNS SRV records when `peers` is empty
+    #[getset(get = "pub")]
+    #[serde(default)]
+    discovery_srv: Option<String>,
+    /// Whether to register the gRPC reflection service on the client endpoint
+    #[getset(get = "pub")]
+    #[serde(default)]
+    enable_grpc_reflection: bool,
+    /// Xline server client listen urls of the HTTP/JSON gateway, the gateway is disabled if empty
+    #[getset(get = "pub")]
+    #[serde(default)]
+    client_http_listen_urls: Vec<String>,
+    /// grpc-web settings of the client endpoint, grpc-web is disabled if `None`
+    #[getset(get = "pub")]
+    #[serde(default)]
+    grpc_web: Option<GrpcWebConfig>,
+    /// Max number of operations in a txn request
+    #[getset(get = "pub")]
+    #[serde(default = "default_max_txn_ops")]
+    max_txn_ops: usize,
+    /// Max size of a request that will be proposed, in bytes
+    #[getset(get = "pub")]
+    #[serde(with = "bytes_format", default = "default_max_request_bytes")]
+    max_request_bytes: u64,
+    /// Compression of the gRPC messages sent by the server and its curp client
+    #[getset(get = "pub")]
+    #[serde(default)]
+    grpc_compression: GrpcCompression,
+    /// Limits of the gRPC servers
+    #[getset(get = "pub")]
+    #[serde(default = "GrpcConfig::default")]
+    grpc: GrpcConfig,
+    /// QoS classes of the requests of users, requests of other users are normal. The
+    /// class tagged in the metadata of a request can only lower its class.
+    #[getset(get = "pub")]
+    #[serde(default)]
+    qos_classes: Vec<UserQosClass>,
 }
 
 impl Default for ClusterConfig {
@@ -139,6 +201,15 @@ impl Default for ClusterConfig {
             client_config: ClientConfig::default(),
             server_timeout: ServerTimeout::default(),
             initial_cluster_state: InitialClusterState::default(),
+            discovery_srv: None,
+            enable_grpc_reflection: false,
+            client_http_listen_urls: vec![],
+            grpc_web: None,
+            max_txn_ops: default_max_txn_ops(),
+            max_request_bytes: default_max_request_bytes(),
+            grpc_compression: GrpcCompression::default(),
+            grpc: GrpcConfig::default(),
+            qos_classes: Vec::new(),
         }
     }
 }
@@ -189,6 +260,15 @@ impl ClusterConfig {
         client_config: ClientConfig,
         server_timeout: ServerTimeout,
         initial_cluster_state: InitialClusterState,
+        discovery_srv: Option<String>,
+        enable_grpc_reflection: bool,
+        client_http_listen_urls: Vec<String>,
+        grpc_web: Option<GrpcWebConfig>,
+        max_txn_ops: usize,
+        max_request_bytes: u64,
+        grpc_compression: GrpcCompression,
+        grpc: GrpcConfig,
+        qos_classes: Vec<UserQosClass>,
     ) -> Self {
         Self {
             name,
@@ -202,10 +282,279 @@ impl ClusterConfig {
             client_config,
             server_timeout,
             initial_cluster_state,
+            discovery_srv,
+            enable_grpc_reflection,
+            client_http_listen_urls,
+            grpc_web,
+            max_txn_ops,
+            max_request_bytes,
+            grpc_compression,
+            grpc,
+            qos_classes,
         }
     }
 }
 
+/// default max number of operations in a txn request
+#[must_use]
+#[inline]
+pub const fn default_max_txn_ops() -> usize {
+    128
+}
+
+/// default max size of a request, 1.5MB
+#[must_use]
+#[inline]
+#[allow(clippy::arithmetic_side_effects)]
+pub const fn default_max_request_bytes() -> u64 {
+    3 * 1024 * 1024 / 2
+}
+
+/// grpc-web configuration of the client endpoint, used by browser clients
+#[allow(clippy::module_name_repetitions)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Getters)]
+pub struct GrpcWebConfig {
+    /// Origins allowed by CORS, cross-origin requests are denied if empty
+    #[getset(get = "pub")]
+    #[serde(default)]
+    cors_allowed_origins: Vec<String>,
+    /// How long the results of a CORS preflight request can be cached
+    #[getset(get = "pub")]
+    #[serde(with = "duration_format", default = "default_cors_max_age")]
+    cors_max_age: Duration,
+}
+
+impl GrpcWebConfig {
+    /// Create a new `GrpcWebConfig`
+    #[must_use]
+    #[inline]
+    pub fn new(cors_allowed_origins: Vec<String>, cors_max_age: Duration) -> Self {
+        Self {
+            cors_allowed_origins,
+            cors_max_age,
+        }
+    }
+}
+
+impl Default for GrpcWebConfig {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            cors_allowed_origins: vec![],
+            cors_max_age: default_cors_max_age(),
+        }
+    }
+}
+
+/// default CORS max age
+#[must_use]
+#[inline]
+pub const fn default_cors_max_age() -> Duration {
+    Duration::from_secs(86_400)
+}
+
+/// Limits of the gRPC servers, `0` keeps the default of the transport or means unlimited
+#[allow(clippy::module_name_repetitions)]
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq, Eq, Getters)]
+pub struct GrpcConfig {
+    /// Interval of the HTTP/2 keepalive pings sent to idle connections, `0s` disables them
+    #[getset(get = "pub")]
+    #[serde(with = "duration_format", default = "default_grpc_keepalive_interval")]
+    keepalive_interval: Duration,
+    /// How long to wait for the ack of a keepalive ping before closing the connection
+    #[getset(get = "pub")]
+    #[serde(with = "duration_format", default = "default_grpc_keepalive_timeout")]
+    keepalive_timeout: Duration,
+    /// Max number of concurrent streams of each connection
+    #[getset(get = "pub")]
+    #[serde(default = "default_grpc_max_concurrent_streams")]
+    max_concurrent_streams: u32,
+    /// Initial HTTP/2 flow control window size of each stream, in bytes
+    #[getset(get = "pub")]
+    #[serde(default)]
+    initial_stream_window_size: u32,
+    /// Initial HTTP/2 flow control window size of each connection, in bytes
+    #[getset(get = "pub")]
+    #[serde(default)]
+    initial_connection_window_size: u32,
+    /// Client connections older than this are closed so that the clients reconnect and
+    /// rebalance among the servers, `0s` keeps the connections forever
+    #[getset(get = "pub")]
+    #[serde(with = "duration_format", default = "default_grpc_max_connection_age")]
+    max_connection_age: Duration,
+    /// Max number of client connections, new connections beyond it are closed at once
+    #[getset(get = "pub")]
+    #[serde(default)]
+    max_connections: usize,
+    /// Max number of concurrent watch streams of each client connection
+    #[getset(get = "pub")]
+    #[serde(default)]
+    max_watch_streams_per_connection: usize,
+    /// Max number of requests per second of each client, a client is identified by its
+    /// user if authenticated, otherwise by its ip address
+    #[getset(get = "pub")]
+    #[serde(default)]
+    client_rate_limit: u32,
+    /// Max number of requests a client may send in a burst beyond `client_rate_limit`
+    #[getset(get = "pub")]
+    #[serde(default = "default_grpc_client_rate_burst")]
+    client_rate_burst: u32,
+}
+
+impl GrpcConfig {
+    /// Create a new `GrpcConfig`
+    #[must_use]
+    #[inline]
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        keepalive_interval: Duration,
+        keepalive_timeout: Duration,
+        max_concurrent_streams: u32,
+        initial_stream_window_size: u32,
+        initial_connection_window_size: u32,
+        max_connection_age: Duration,
+        max_connections: usize,
+        max_watch_streams_per_connection: usize,
+        client_rate_limit: u32,
+        client_rate_burst: u32,
+    ) -> Self {
+        Self {
+            keepalive_interval,
+            keepalive_timeout,
+            max_concurrent_streams,
+            initial_stream_window_size,
+            initial_connection_window_size,
+            max_connection_age,
+            max_connections,
+            max_watch_streams_per_connection,
+            client_rate_limit,
+            client_rate_burst,
+        }
+    }
+}
+
+impl Default for GrpcConfig {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            keepalive_interval: default_grpc_keepalive_interval(),
+            keepalive_timeout: default_grpc_keepalive_timeout(),
+            max_concurrent_streams: default_grpc_max_concurrent_streams(),
+            initial_stream_window_size: 0,
+            initial_connection_window_size: 0,
+            max_connection_age: default_grpc_max_connection_age(),
+            max_connections: 0,
+            max_watch_streams_per_connection: 0,
+            client_rate_limit: 0,
+            client_rate_burst: default_grpc_client_rate_burst(),
+        }
+    }
+}
+
+/// default interval of the HTTP/2 keepalive pings
+#[must_use]
+#[inline]
+pub const fn default_grpc_keepalive_interval() -> Duration {
+    Duration::from_secs(2 * 60 * 60)
+}
+
+/// default timeout of the HTTP/2 keepalive pings
+#[must_use]
+#[inline]
+pub const fn default_grpc_keepalive_timeout() -> Duration {
+    Duration::from_secs(20)
+}
+
+/// default max number of concurrent streams of a connection
+#[must_use]
+#[inline]
+pub const fn default_grpc_max_concurrent_streams() -> u32 {
+    u32::MAX
+}
+
+/// default burst of the requests of a client
+#[must_use]
+#[inline]
+pub const fn default_grpc_client_rate_burst() -> u32 {
+    100
+}
+
+/// default max connection age, unlimited
+#[must_use]
+#[inline]
+pub const fn default_grpc_max_connection_age() -> Duration {
+    Duration::ZERO
+}
+
+/// Compression of the gRPC messages. Compressed messages in both encodings are always
+/// accepted, the compression only decides how the sent messages are encoded
+#[non_exhaustive]
+#[derive(Copy, Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all(deserialize = "snake_case"))]
+pub enum GrpcCompression {
+    /// Send the messages uncompressed
+    #[default]
+    None,
+    /// Compress the sent messages with gzip
+    Gzip,
+    /// Compress the sent messages with zstd
+    Zstd,
+}
+
+#[cfg(not(madsim))]
+impl GrpcCompression {
+    /// The encodings accepted from the other side
+    pub const ACCEPTED: [tonic::codec::CompressionEncoding; 2] = [
+        tonic::codec::CompressionEncoding::Gzip,
+        tonic::codec::CompressionEncoding::Zstd,
+    ];
+
+    /// The encoding of the sent messages, `None` if they are sent uncompressed
+    #[must_use]
+    #[inline]
+    pub fn encoding(self) -> Option<tonic::codec::CompressionEncoding> {
+        match self {
+            GrpcCompression::None => None,
+            GrpcCompression::Gzip => Some(tonic::codec::CompressionEncoding::Gzip),
+            GrpcCompression::Zstd => Some(tonic::codec::CompressionEncoding::Zstd),
+        }
+    }
+}
+
+/// QoS class of a request, the ready commands of a higher class are executed before
+/// the others
+#[non_exhaustive]
+#[derive(Copy, Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all(deserialize = "snake_case"))]
+pub enum QosClass {
+    /// System critical requests, e.g. the leases of the control plane
+    Critical,
+    /// Ordinary requests
+    #[default]
+    Normal,
+    /// Bulk requests that can wait, e.g. batch writers
+    Batch,
+}
+
+/// QoS class of the requests of a user
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct UserQosClass {
+    /// Name of the user
+    pub user: String,
+    /// QoS class of the requests of the user
+    pub class: QosClass,
+}
+
+impl UserQosClass {
+    /// Create a new user qos class
+    #[inline]
+    #[must_use]
+    pub fn new(user: String, class: QosClass) -> Self {
+        Self { user, class }
+    }
+}
+
 /// Compaction configuration
 #[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Getters)]
 #[allow(clippy::module_name_repetitions)]
@@ -336,6 +685,30 @@ pub struct CurpConfig {
     #[builder(default = "default_log_entries_cap()")]
     #[serde(default = "default_log_entries_cap")]
     pub log_entries_cap: usize,
+
+    /// The maximum number of bytes per second a leader sends when installing a snapshot
+    /// to a follower, `0` means unlimited
+    #[builder(default = "default_snapshot_rate_limit()")]
+    #[serde(default = "default_snapshot_rate_limit")]
+    pub snapshot_rate_limit: u64,
+
+    /// How many times an interrupted snapshot transfer is resumed before giving up
+    #[builder(default = "default_snapshot_max_resumes()")]
+    #[serde(default = "default_snapshot_max_resumes")]
+    pub snapshot_max_resumes: usize,
+
+    /// Interval to re-resolve the hostnames in the addresses of the peers, the peers
+    /// are reconnected when the addresses behind their hostnames change, `0` disables
+    /// the re-resolution
+    #[builder(default = "default_resolve_interval()")]
+    #[serde(with = "duration_format", default = "default_resolve_interval")]
+    pub resolve_interval: Duration,
+
+    /// Compression of the messages sent to the peers, it follows the `grpc_compression`
+    /// of the cluster config rather than being read from the curp config
+    #[builder(default)]
+    #[serde(skip)]
+    pub compression: GrpcCompression,
 }
 
 /// default heartbeat interval
@@ -438,6 +811,21 @@ pub const fn default_client_id_keep_alive_interval() -> Duration {
     Duration::from_secs(1)
 }
 
+/// default client endpoint health check interval, disabled by default since every
+/// client sends a `FetchCluster` to every member on each check
+#[must_use]
+#[inline]
+pub const fn default_client_health_check_interval() -> Duration {
+    Duration::ZERO
+}
+
+/// default interval to re-resolve the hostnames in the addresses of the members
+#[must_use]
+#[inline]
+pub const fn default_resolve_interval() -> Duration {
+    Duration::from_secs(30)
+}
+
 /// default follower timeout
 #[must_use]
 #[inline]
@@ -487,6 +875,20 @@ pub const fn default_log_entries_cap() -> usize {
     5000
 }
 
+/// default snapshot rate limit, unlimited
+#[must_use]
+#[inline]
+pub const fn default_snapshot_rate_limit() -> u64 {
+    0
+}
+
+/// default max resumes of a snapshot transfer
+#[must_use]
+#[inline]
+pub const fn default_snapshot_max_resumes() -> usize {
+    3
+}
+
 /// default watch progress notify interval
 #[must_use]
 #[inline]
@@ -494,6 +896,82 @@ pub const fn default_watch_progress_notify_interval() -> Duration {
     Duration::from_secs(600)
 }
 
+/// default max number of pending events buffered for a slow watcher
+#[must_use]
+#[inline]
+pub const fn default_watcher_buffer_size() -> usize {
+    4096
+}
+
+/// default size of the channel of the kv updates sent to the watchers
+#[must_use]
+#[inline]
+pub const fn default_kv_update_channel_size() -> usize {
+    1024
+}
+
+/// default lease checkpoint interval
+#[must_use]
+#[inline]
+pub const fn default_lease_checkpoint_interval() -> Duration {
+    Duration::from_secs(300)
+}
+
+/// default threshold above which a request is logged as slow
+#[must_use]
+#[inline]
+pub const fn default_slow_request_threshold() -> Duration {
+    Duration::from_millis(300)
+}
+
+/// default max number of commands waiting to be applied
+#[must_use]
+#[inline]
+pub const fn default_max_apply_queue_depth() -> usize {
+    10_000
+}
+
+/// default memory limit, unlimited
+#[must_use]
+#[inline]
+pub const fn default_memory_limit() -> u64 {
+    0
+}
+
+/// default max time to wait for in-flight client requests on graceful shutdown
+#[must_use]
+#[inline]
+pub const fn default_shutdown_drain_timeout() -> Duration {
+    Duration::from_secs(10)
+}
+
+/// The policy applied to a slow watcher whose pending events exceed the buffer size
+#[non_exhaustive]
+#[derive(Copy, Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all(deserialize = "lowercase"))]
+pub enum SlowWatcherPolicy {
+    /// Drop the pending events, they will be replayed from the storage once the
+    /// watcher catches up
+    #[default]
+    Resync,
+    /// Cancel the watcher, the client needs to create a new one
+    Cancel,
+}
+
+/// The backpressure applied to the write path when the channel of the kv updates sent to
+/// the watchers is full
+#[non_exhaustive]
+#[derive(Copy, Clone, Debug, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all(deserialize = "snake_case"))]
+pub enum KvUpdateBackpressure {
+    /// Block the write path until the watchers catch up
+    #[default]
+    Block,
+    /// Drop the updates and mark all watchers as victims, they catch up with the dropped
+    /// updates from the storage, so heavy watch fan-out never stalls the write path
+    MarkVictims,
+}
+
 impl Default for CurpConfig {
     #[inline]
     fn default() -> Self {
@@ -510,6 +988,10 @@ impl Default for CurpConfig {
             cmd_workers: default_cmd_workers(),
             gc_interval: default_gc_interval(),
             log_entries_cap: default_log_entries_cap(),
+            snapshot_rate_limit: default_snapshot_rate_limit(),
+            snapshot_max_resumes: default_snapshot_max_resumes(),
+            resolve_interval: default_resolve_interval(),
+            compression: GrpcCompression::default(),
         }
     }
 }
@@ -558,6 +1040,20 @@ pub struct ClientConfig {
         default = "default_client_id_keep_alive_interval"
     )]
     keep_alive_interval: Duration,
+
+    /// Curp client endpoint health check interval, health checking is disabled if zero
+    #[getset(get = "pub")]
+    #[serde(
+        with = "duration_format",
+        default = "default_client_health_check_interval"
+    )]
+    health_check_interval: Duration,
+
+    /// Interval to re-resolve the hostnames in the addresses of the members, `0`
+    /// disables the re-resolution
+    #[getset(get = "pub")]
+    #[serde(with = "duration_format", default = "default_resolve_interval")]
+    resolve_interval: Duration,
 }
 
 impl ClientConfig {
@@ -576,6 +1072,8 @@ impl ClientConfig {
         retry_count: usize,
         fixed_backoff: bool,
         keep_alive_interval: Duration,
+        health_check_interval: Duration,
+        resolve_interval: Duration,
     ) -> Self {
         assert!(
             initial_retry_timeout <= max_retry_timeout,
@@ -589,6 +1087,8 @@ impl ClientConfig {
             retry_count,
             fixed_backoff,
             keep_alive_interval,
+            health_check_interval,
+            resolve_interval,
         }
     }
 }
@@ -604,6 +1104,8 @@ impl Default for ClientConfig {
             retry_count: default_retry_count(),
             fixed_backoff: default_fixed_backoff(),
             keep_alive_interval: default_client_id_keep_alive_interval(),
+            health_check_interval: default_client_health_check_interval(),
+            resolve_interval: default_resolve_interval(),
         }
     }
 }
@@ -630,23 +1132,83 @@ pub struct ServerTimeout {
         default = "default_watch_progress_notify_interval"
     )]
     watch_progress_notify_interval: Duration,
+    /// Max number of pending events buffered for a slow watcher
+    #[getset(get = "pub")]
+    #[serde(default = "default_watcher_buffer_size")]
+    watcher_buffer_size: usize,
+    /// Policy applied to a slow watcher whose pending events exceed `watcher_buffer_size`
+    #[getset(get = "pub")]
+    #[serde(default = "SlowWatcherPolicy::default")]
+    slow_watcher_policy: SlowWatcherPolicy,
+    /// Size of the channel of the kv updates sent to the watchers
+    #[getset(get = "pub")]
+    #[serde(default = "default_kv_update_channel_size")]
+    kv_update_channel_size: usize,
+    /// Backpressure applied to the write path when the kv update channel is full
+    #[getset(get = "pub")]
+    #[serde(default = "KvUpdateBackpressure::default")]
+    kv_update_backpressure: KvUpdateBackpressure,
+    /// Interval at which the leader checkpoints the remaining ttl of leases
+    #[getset(get = "pub")]
+    #[serde(
+        with = "duration_format",
+        default = "default_lease_checkpoint_interval"
+    )]
+    lease_checkpoint_interval: Duration,
+    /// Requests that take longer than this threshold are logged as slow requests
+    #[getset(get = "pub")]
+    #[serde(with = "duration_format", default = "default_slow_request_threshold")]
+    slow_request_threshold: Duration,
+    /// Max number of commands waiting to be applied before the server sheds load,
+    /// `0` means unlimited
+    #[getset(get = "pub")]
+    #[serde(default = "default_max_apply_queue_depth")]
+    max_apply_queue_depth: usize,
+    /// Resident memory in bytes of the server above which it sheds load, `0` means
+    /// unlimited
+    #[getset(get = "pub")]
+    #[serde(default = "default_memory_limit")]
+    memory_limit: u64,
+    /// Max time to wait for the in-flight client requests to finish on graceful shutdown
+    #[getset(get = "pub")]
+    #[serde(with = "duration_format", default = "default_shutdown_drain_timeout")]
+    shutdown_drain_timeout: Duration,
 }
 
 impl ServerTimeout {
     /// Create a new server timeout
     #[must_use]
     #[inline]
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         range_retry_timeout: Duration,
         compact_timeout: Duration,
         sync_victims_interval: Duration,
         watch_progress_notify_interval: Duration,
+        watcher_buffer_size: usize,
+        slow_watcher_policy: SlowWatcherPolicy,
+        kv_update_channel_size: usize,
+        kv_update_backpressure: KvUpdateBackpressure,
+        lease_checkpoint_interval: Duration,
+        slow_request_threshold: Duration,
+        max_apply_queue_depth: usize,
+        memory_limit: u64,
+        shutdown_drain_timeout: Duration,
     ) -> Self {
         Self {
             range_retry_timeout,
             compact_timeout,
             sync_victims_interval,
             watch_progress_notify_interval,
+            watcher_buffer_size,
+            slow_watcher_policy,
+            kv_update_channel_size,
+            kv_update_backpressure,
+            lease_checkpoint_interval,
+            slow_request_threshold,
+            max_apply_queue_depth,
+            memory_limit,
+            shutdown_drain_timeout,
         }
     }
 }
@@ -659,6 +1221,15 @@ impl Default for ServerTimeout {
             compact_timeout: default_compact_timeout(),
             sync_victims_interval: default_sync_victims_interval(),
             watch_progress_notify_interval: default_watch_progress_notify_interval(),
+            watcher_buffer_size: default_watcher_buffer_size(),
+            slow_watcher_policy: SlowWatcherPolicy::default(),
+            kv_update_channel_size: default_kv_update_channel_size(),
+            kv_update_backpressure: KvUpdateBackpressure::default(),
+            lease_checkpoint_interval: default_lease_checkpoint_interval(),
+            slow_request_threshold: default_slow_request_threshold(),
+            max_apply_queue_depth: default_max_apply_queue_depth(),
+            memory_limit: default_memory_limit(),
+            shutdown_drain_timeout: default_shutdown_drain_timeout(),
         }
     }
 }
@@ -694,6 +1265,8 @@ pub enum EngineConfig {
     Memory,
     /// RocksDB Storage Engine
     RocksDB(PathBuf),
+    /// Redb Storage Engine, a pure-Rust engine that stores the data in a single file
+    Redb(PathBuf),
 }
 
 impl Default for EngineConfig {
@@ -703,6 +1276,46 @@ impl Default for EngineConfig {
     }
 }
 
+/// Durability policy of the writes to the storage, which trades a bounded durability
+/// window of the storage for write throughput. Committed commands are persisted in the
+/// curp log anyway, so the writes lost in a crash are recovered by replaying the log.
+#[allow(clippy::module_name_repetitions)]
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(
+    tag = "policy",
+    content = "interval",
+    rename_all(deserialize = "lowercase")
+)]
+pub enum DurabilityPolicy {
+    /// Fsync every batch applied to the storage
+    Always,
+    /// Write the batches without fsync, and fsync the storage at the given interval
+    #[serde(with = "duration_format")]
+    Periodic(Duration),
+    /// Write the batches without fsync, and rely on the curp log which is fsynced
+    /// when the commands are committed
+    #[default]
+    Commit,
+}
+
+/// Checksums of the values in the storage, which detect torn writes and bit rot when the
+/// values are read. Once checksums are added to a storage, they are kept even if the
+/// policy is changed to `Off`, but they are not verified.
+#[allow(clippy::module_name_repetitions)]
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all(deserialize = "lowercase"))]
+pub enum ChecksumPolicy {
+    /// Don't add checksums to the values
+    #[default]
+    Off,
+    /// Add checksums to the values, but don't verify them on read
+    Write,
+    /// Add checksums to the values, and verify them on every read
+    Verify,
+}
+
 /// /// Storage Configuration
 #[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
 #[allow(clippy::module_name_repetitions)]
@@ -714,14 +1327,52 @@ pub struct StorageConfig {
     /// Quota
     #[serde(default = "default_quota")]
     pub quota: u64,
+    /// Quotas of the tenants, each tenant owns the keys under its prefix
+    #[serde(default)]
+    pub tenant_quotas: Vec<TenantQuota>,
+    /// At-rest encryption of the values in the storage, disabled if not given
+    #[serde(default)]
+    pub encryption: Option<EncryptionConfig>,
+    /// Durability policy of the writes to the storage
+    #[serde(default)]
+    pub durability: DurabilityPolicy,
+    /// Periodic local backups of the storage, disabled if not given
+    #[serde(default)]
+    pub backup: Option<BackupConfig>,
+    /// Checksums of the values in the storage
+    #[serde(default)]
+    pub checksum: ChecksumPolicy,
+    /// Interval between two snapshots of the in-memory index, which speed up the
+    /// recovery of the index on startup, 0 means the snapshots are not taken
+    #[serde(with = "duration_format", default = "default_index_snapshot_interval")]
+    pub index_snapshot_interval: Duration,
 }
 
 impl StorageConfig {
     /// Create a new storage config
     #[inline]
     #[must_use]
-    pub fn new(engine: EngineConfig, quota: u64) -> Self {
-        Self { engine, quota }
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        engine: EngineConfig,
+        quota: u64,
+        tenant_quotas: Vec<TenantQuota>,
+        encryption: Option<EncryptionConfig>,
+        durability: DurabilityPolicy,
+        backup: Option<BackupConfig>,
+        checksum: ChecksumPolicy,
+        index_snapshot_interval: Duration,
+    ) -> Self {
+        Self {
+            engine,
+            quota,
+            tenant_quotas,
+            encryption,
+            durability,
+            backup,
+            checksum,
+            index_snapshot_interval,
+        }
     }
 }
 
@@ -731,6 +1382,250 @@ impl Default for StorageConfig {
         Self {
             engine: EngineConfig::default(),
             quota: default_quota(),
+            tenant_quotas: Vec::new(),
+            encryption: None,
+            durability: DurabilityPolicy::default(),
+            backup: None,
+            checksum: ChecksumPolicy::default(),
+            index_snapshot_interval: default_index_snapshot_interval(),
+        }
+    }
+}
+
+/// Source of a key encryption key, which is a base64 encoded 256-bit key
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all(deserialize = "lowercase"))]
+pub enum KeySource {
+    /// Read the key from a file
+    File {
+        /// Path of the file
+        path: PathBuf,
+    },
+    /// Read the key from the stdout of a command, which is the hook to fetch the key
+    /// from a KMS
+    Command {
+        /// The program and its arguments
+        command: Vec<String>,
+    },
+}
+
+/// At-rest encryption configuration. Values are encrypted by data keys, which are
+/// encrypted by the key encryption key and stored along with the data. All members
+/// must use the same key encryption key, as snapshots are sent to other members as is.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq, Getters)]
+pub struct EncryptionConfig {
+    /// The key encryption key
+    #[getset(get = "pub")]
+    kek: KeySource,
+    /// Previous key encryption keys, which are only used to decrypt the data keys after
+    /// the key encryption key is rotated
+    #[getset(get = "pub")]
+    #[serde(default)]
+    previous_keks: Vec<KeySource>,
+}
+
+impl EncryptionConfig {
+    /// Create a new `EncryptionConfig`
+    #[must_use]
+    #[inline]
+    pub fn new(kek: KeySource, previous_keks: Vec<KeySource>) -> Self {
+        Self { kek, previous_keks }
+    }
+}
+
+/// Periodic local backups of the storage, which are independent of the snapshots of
+/// curp. A backup is taken when the interval elapses or the given number of log
+/// entries are applied since the last backup. Backups have the same format as the
+/// snapshots of the maintenance service, so they can be restored in the same way.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq, Getters)]
+pub struct BackupConfig {
+    /// Directory of the backups
+    #[getset(get = "pub")]
+    dir: PathBuf,
+    /// Interval between two backups, 0 means backups are not taken by time
+    #[getset(get = "pub")]
+    #[serde(with = "duration_format", default)]
+    interval: Duration,
+    /// Number of applied log entries between two backups, 0 means backups are not
+    /// taken by entries
+    #[getset(get = "pub")]
+    #[serde(default)]
+    entries: u64,
+    /// Number of the most recent backups to keep, at least 1
+    #[getset(get = "pub")]
+    #[serde(with = "backup_retention_format", default = "default_backup_retention")]
+    retention: usize,
+    /// S3-compatible object storage that the backups are uploaded to, backups are only
+    /// kept locally if it is not given
+    #[getset(get = "pub")]
+    #[serde(default)]
+    upload: Option<ObjectStorageConfig>,
+}
+
+impl BackupConfig {
+    /// Create a new `BackupConfig`
+    #[must_use]
+    #[inline]
+    pub fn new(
+        dir: PathBuf,
+        interval: Duration,
+        entries: u64,
+        retention: usize,
+        upload: Option<ObjectStorageConfig>,
+    ) -> Self {
+        Self {
+            dir,
+            interval,
+            entries,
+            retention,
+            upload,
+        }
+    }
+}
+
+/// S3-compatible object storage of the backups. The uploaded backups are not removed
+/// by xline, they are tagged so that they can be expired by the lifecycle rules of the
+/// bucket.
+#[allow(clippy::module_name_repetitions)]
+#[derive(Clone, Deserialize, PartialEq, Eq, Getters)]
+pub struct ObjectStorageConfig {
+    /// Endpoint of the object storage, e.g. `https://s3.us-east-1.amazonaws.com`
+    #[getset(get = "pub")]
+    endpoint: String,
+    /// Region of the bucket
+    #[getset(get = "pub")]
+    #[serde(default = "default_object_storage_region")]
+    region: String,
+    /// Name of the bucket
+    #[getset(get = "pub")]
+    bucket: String,
+    /// Prefix of the object keys of the backups
+    #[getset(get = "pub")]
+    #[serde(default)]
+    prefix: String,
+    /// Access key id, read from the environment or the credential files if not given
+    #[getset(get = "pub")]
+    #[serde(default)]
+    access_key_id: Option<String>,
+    /// Secret access key, read from the environment or the credential files if not given
+    #[getset(get = "pub")]
+    #[serde(default)]
+    secret_access_key: Option<String>,
+    /// Use path-style URLs instead of virtual-hosted-style URLs, which is required by
+    /// most self-hosted object storages
+    #[getset(get = "pub")]
+    #[serde(default)]
+    path_style: bool,
+    /// Tags of the uploaded backups, which are matched by the lifecycle rules
+    #[getset(get = "pub")]
+    #[serde(default)]
+    tags: HashMap<String, String>,
+    /// Restore the latest uploaded backup when a member bootstraps a new cluster with an
+    /// empty data directory. Members joining an existing cluster are not restored, as the
+    /// log of the cluster would be applied again on top of the backup. Every member of the
+    /// new cluster must restore the same backup.
+    #[getset(get = "pub")]
+    #[serde(default)]
+    restore_on_bootstrap: bool,
+}
+
+impl ObjectStorageConfig {
+    /// Create a new `ObjectStorageConfig`
+    #[must_use]
+    #[inline]
+    #[allow(clippy::too_many_arguments)] // it's the config of the object storage
+    pub fn new(
+        endpoint: String,
+        region: String,
+        bucket: String,
+        prefix: String,
+        access_key_id: Option<String>,
+        secret_access_key: Option<String>,
+        path_style: bool,
+        tags: HashMap<String, String>,
+        restore_on_bootstrap: bool,
+    ) -> Self {
+        Self {
+            endpoint,
+            region,
+            bucket,
+            prefix,
+            access_key_id,
+            secret_access_key,
+            path_style,
+            tags,
+            restore_on_bootstrap,
+        }
+    }
+}
+
+impl std::fmt::Debug for ObjectStorageConfig {
+    #[inline]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // the config may be logged, so the secret is never printed
+        f.debug_struct("ObjectStorageConfig")
+            .field("endpoint", &self.endpoint)
+            .field("region", &self.region)
+            .field("bucket", &self.bucket)
+            .field("prefix", &self.prefix)
+            .field("access_key_id", &self.access_key_id)
+            .field(
+                "secret_access_key",
+                &self.secret_access_key.as_ref().map(|_secret| "<redacted>"),
+            )
+            .field("path_style", &self.path_style)
+            .field("tags", &self.tags)
+            .field("restore_on_bootstrap", &self.restore_on_bootstrap)
+            .finish()
+    }
+}
+
+/// Default region of the object storage
+#[must_use]
+#[inline]
+pub fn default_object_storage_region() -> String {
+    "us-east-1".to_owned()
+}
+
+/// Default interval between two index snapshots
+#[must_use]
+#[inline]
+pub const fn default_index_snapshot_interval() -> Duration {
+    Duration::from_secs(5 * 60)
+}
+
+/// Default number of backups to keep
+#[must_use]
+#[inline]
+pub const fn default_backup_retention() -> usize {
+    5
+}
+
+/// Quota of a tenant, a limit of 0 means unlimited
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct TenantQuota {
+    /// The key prefix of the tenant
+    pub prefix: String,
+    /// Max number of keys of the tenant
+    #[serde(default)]
+    pub max_keys: u64,
+    /// Max bytes of the keys and values of the tenant
+    #[serde(default)]
+    pub max_bytes: u64,
+}
+
+impl TenantQuota {
+    /// Create a new tenant quota
+    #[inline]
+    #[must_use]
+    pub fn new(prefix: String, max_keys: u64, max_bytes: u64) -> Self {
+        Self {
+            prefix,
+            max_keys,
+            max_bytes,
         }
     }
 }
@@ -886,7 +1781,7 @@ pub fn file_appender(
 
 /// Xline tracing configuration object
 #[allow(clippy::module_name_repetitions)]
-#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Getters)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Getters)]
 pub struct TraceConfig {
     /// Open jaeger online, sending data to jaeger agent directly
     #[getset(get = "pub")]
@@ -901,6 +1796,15 @@ pub struct TraceConfig {
     #[getset(get = "pub")]
     #[serde(with = "level_format", default = "default_log_level")]
     jaeger_level: LevelConfig,
+    /// The OTLP endpoint that spans are exported to when `jaeger_online` is on,
+    /// the default endpoint of the exporter is used if it is not set
+    #[getset(get = "pub")]
+    #[serde(default)]
+    otlp_endpoint: Option<String>,
+    /// The ratio of traces to be sampled, ranging from 0.0 to 1.0
+    #[getset(get = "pub")]
+    #[serde(default = "default_trace_sampling_ratio")]
+    sampling_ratio: f64,
 }
 
 impl Default for TraceConfig {
@@ -911,10 +1815,19 @@ impl Default for TraceConfig {
             jaeger_offline: false,
             jaeger_output_dir: "".into(),
             jaeger_level: default_log_level(),
+            otlp_endpoint: None,
+            sampling_ratio: default_trace_sampling_ratio(),
         }
     }
 }
 
+/// default trace sampling ratio
+#[must_use]
+#[inline]
+pub const fn default_trace_sampling_ratio() -> f64 {
+    1.0
+}
+
 impl TraceConfig {
     /// Generate a new `TraceConfig` object
     #[must_use]
@@ -924,19 +1837,23 @@ impl TraceConfig {
         jaeger_offline: bool,
         jaeger_output_dir: PathBuf,
         jaeger_level: LevelConfig,
+        otlp_endpoint: Option<String>,
+        sampling_ratio: f64,
     ) -> Self {
         Self {
             jaeger_online,
             jaeger_offline,
             jaeger_output_dir,
             jaeger_level,
+            otlp_endpoint,
+            sampling_ratio,
         }
     }
 }
 
 /// Xline tracing configuration object
 #[allow(clippy::module_name_repetitions)]
-#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Getters, Default)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Getters)]
 pub struct AuthConfig {
     /// The public key file
     #[getset(get = "pub")]
@@ -944,26 +1861,209 @@ pub struct AuthConfig {
     /// The private key file
     #[getset(get = "pub")]
     auth_private_key: Option<PathBuf>,
+    /// Public key files of previous private keys, tokens signed by them are still accepted
+    #[getset(get = "pub")]
+    #[serde(default)]
+    auth_previous_public_keys: Vec<PathBuf>,
+    /// How long an assigned token is valid
+    #[getset(get = "pub")]
+    #[serde(with = "duration_format", default = "default_auth_token_ttl")]
+    auth_token_ttl: Duration,
+    /// Parameters of hashing user passwords
+    #[getset(get = "pub")]
+    #[serde(default)]
+    password_hash: PasswordHashConfig,
+    /// Lockout of users and clients after failed authentications
+    #[getset(get = "pub")]
+    #[serde(default)]
+    lockout: AuthLockoutConfig,
+    /// Whether to authenticate a client by the common name of its verified certificate
+    #[getset(get = "pub")]
+    #[serde(default = "default_client_cert_auth")]
+    client_cert_auth: bool,
+}
+
+impl Default for AuthConfig {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            auth_public_key: None,
+            auth_private_key: None,
+            auth_previous_public_keys: Vec::new(),
+            auth_token_ttl: default_auth_token_ttl(),
+            password_hash: PasswordHashConfig::default(),
+            lockout: AuthLockoutConfig::default(),
+            client_cert_auth: default_client_cert_auth(),
+        }
+    }
+}
+
+/// Default auth token ttl
+#[must_use]
+#[inline]
+pub const fn default_auth_token_ttl() -> Duration {
+    Duration::from_secs(300)
+}
+
+/// Default client cert auth option
+#[must_use]
+#[inline]
+pub const fn default_client_cert_auth() -> bool {
+    true
 }
 
 impl AuthConfig {
     /// Generate a new `AuthConfig` object
     #[must_use]
     #[inline]
-    pub fn new(auth_public_key: Option<PathBuf>, auth_private_key: Option<PathBuf>) -> Self {
+    pub fn new(
+        auth_public_key: Option<PathBuf>,
+        auth_private_key: Option<PathBuf>,
+        auth_previous_public_keys: Vec<PathBuf>,
+        auth_token_ttl: Duration,
+        password_hash: PasswordHashConfig,
+        lockout: AuthLockoutConfig,
+        client_cert_auth: bool,
+    ) -> Self {
         Self {
             auth_public_key,
             auth_private_key,
+            auth_previous_public_keys,
+            auth_token_ttl,
+            password_hash,
+            lockout,
+            client_cert_auth,
         }
     }
 }
 
+/// Lockout of users and client addresses after consecutive failed authentications, failures
+/// are tracked by each member separately. A user is only locked for the client address the
+/// failures come from, so that wrong passwords from one client, e.g. for `root`, don't lock
+/// the user out on other clients.
+#[allow(clippy::module_name_repetitions)]
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Getters)]
+pub struct AuthLockoutConfig {
+    /// Number of consecutive failures that locks a user or client address, 0 disables lockout
+    #[getset(get = "pub")]
+    #[serde(default = "default_auth_max_failures")]
+    max_failures: u32,
+    /// How long a user or client address is locked
+    #[getset(get = "pub")]
+    #[serde(with = "duration_format", default = "default_auth_lockout_duration")]
+    duration: Duration,
+}
+
+impl AuthLockoutConfig {
+    /// Create a new `AuthLockoutConfig`
+    #[must_use]
+    #[inline]
+    pub fn new(max_failures: u32, duration: Duration) -> Self {
+        Self {
+            max_failures,
+            duration,
+        }
+    }
+}
+
+impl Default for AuthLockoutConfig {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            max_failures: default_auth_max_failures(),
+            duration: default_auth_lockout_duration(),
+        }
+    }
+}
+
+/// Default number of consecutive failed authentications before lockout
+#[must_use]
+#[inline]
+pub const fn default_auth_max_failures() -> u32 {
+    5
+}
+
+/// Default lockout duration
+#[must_use]
+#[inline]
+pub const fn default_auth_lockout_duration() -> Duration {
+    Duration::from_secs(60)
+}
+
+/// Cost parameters of the argon2id hashing of user passwords, hashes of other algorithms
+/// or parameters are upgraded when their users authenticate
+#[allow(clippy::module_name_repetitions)]
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Getters)]
+pub struct PasswordHashConfig {
+    /// Memory size in KiB
+    #[getset(get = "pub")]
+    #[serde(default = "default_password_hash_memory_cost")]
+    memory_cost: u32,
+    /// Number of iterations
+    #[getset(get = "pub")]
+    #[serde(default = "default_password_hash_time_cost")]
+    time_cost: u32,
+    /// Degree of parallelism
+    #[getset(get = "pub")]
+    #[serde(default = "default_password_hash_parallelism")]
+    parallelism: u32,
+}
+
+impl PasswordHashConfig {
+    /// Create a new `PasswordHashConfig`
+    #[must_use]
+    #[inline]
+    pub fn new(memory_cost: u32, time_cost: u32, parallelism: u32) -> Self {
+        Self {
+            memory_cost,
+            time_cost,
+            parallelism,
+        }
+    }
+}
+
+impl Default for PasswordHashConfig {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            memory_cost: default_password_hash_memory_cost(),
+            time_cost: default_password_hash_time_cost(),
+            parallelism: default_password_hash_parallelism(),
+        }
+    }
+}
+
+/// Default memory size of password hashing, 19 MiB as recommended by OWASP
+#[must_use]
+#[inline]
+pub const fn default_password_hash_memory_cost() -> u32 {
+    19 * 1024
+}
+
+/// Default number of iterations of password hashing
+#[must_use]
+#[inline]
+pub const fn default_password_hash_time_cost() -> u32 {
+    2
+}
+
+/// Default degree of parallelism of password hashing
+#[must_use]
+#[inline]
+pub const fn default_password_hash_parallelism() -> u32 {
+    1
+}
+
 /// Xline tls configuration object
 #[allow(clippy::module_name_repetitions)]
 #[non_exhaustive]
 #[derive(Clone, Debug, Deserialize, PartialEq, Eq, Getters, Default)]
 pub struct TlsConfig {
-    /// The CA certificate file used by peer to verify client certificates
+    /// The CA certificate file used by peer to verify client certificates, when set
+    /// together with `peer_cert_path` and `peer_key_path`, peers also use it to verify
+    /// each other and present the peer certificate as their client identity
     #[getset(get = "pub")]
     pub peer_ca_cert_path: Option<PathBuf>,
     /// The public key file used by peer
@@ -981,12 +2081,22 @@ pub struct TlsConfig {
     /// The private key file used by client
     #[getset(get = "pub")]
     pub client_key_path: Option<PathBuf>,
+    /// The certificate file used by the client-facing server, fall back to `peer_cert_path` if unset
+    #[getset(get = "pub")]
+    pub server_cert_path: Option<PathBuf>,
+    /// The private key file used by the client-facing server, fall back to `peer_key_path` if unset
+    #[getset(get = "pub")]
+    pub server_key_path: Option<PathBuf>,
+    /// The CA certificate file used by the client-facing server to verify client certificates
+    #[getset(get = "pub")]
+    pub server_ca_cert_path: Option<PathBuf>,
 }
 
 impl TlsConfig {
     /// Create a new `TlsConfig` object
     #[must_use]
     #[inline]
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         peer_ca_cert_path: Option<PathBuf>,
         peer_cert_path: Option<PathBuf>,
@@ -994,6 +2104,9 @@ impl TlsConfig {
         client_ca_cert_path: Option<PathBuf>,
         client_cert_path: Option<PathBuf>,
         client_key_path: Option<PathBuf>,
+        server_cert_path: Option<PathBuf>,
+        server_key_path: Option<PathBuf>,
+        server_ca_cert_path: Option<PathBuf>,
     ) -> Self {
         Self {
             peer_ca_cert_path,
@@ -1002,6 +2115,9 @@ impl TlsConfig {
             client_ca_cert_path,
             client_cert_path,
             client_key_path,
+            server_cert_path,
+            server_key_path,
+            server_ca_cert_path,
         }
     }
 
@@ -1009,6 +2125,14 @@ impl TlsConfig {
     #[must_use]
     #[inline]
     pub fn server_tls_enabled(&self) -> bool {
+        (self.server_cert_path.is_some() && self.server_key_path.is_some())
+            || self.peer_tls_enabled()
+    }
+
+    /// Whether the peer tls is enabled
+    #[must_use]
+    #[inline]
+    pub fn peer_tls_enabled(&self) -> bool {
         self.peer_cert_path.is_some() && self.peer_key_path.is_some()
     }
 }
@@ -1162,6 +2286,48 @@ pub fn default_metrics_push_endpoint() -> String {
     "http://127.0.0.1:4318".to_owned()
 }
 
+/// Xline audit log configuration object
+#[allow(clippy::module_name_repetitions)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Getters)]
+pub struct AuditConfig {
+    /// Enable the audit log or not
+    #[getset(get = "pub")]
+    #[serde(default)]
+    enable: bool,
+    /// Directory of the audit log files
+    #[getset(get = "pub")]
+    #[serde(default)]
+    path: Option<PathBuf>,
+    /// Audit log rotation strategy
+    #[getset(get = "pub")]
+    #[serde(with = "rotation_format", default = "default_rotation")]
+    rotation: RotationConfig,
+}
+
+impl AuditConfig {
+    /// Create a new `AuditConfig`
+    #[must_use]
+    #[inline]
+    pub fn new(enable: bool, path: Option<PathBuf>, rotation: RotationConfig) -> Self {
+        Self {
+            enable,
+            path,
+            rotation,
+        }
+    }
+}
+
+impl Default for AuditConfig {
+    #[inline]
+    fn default() -> Self {
+        Self {
+            enable: false,
+            path: None,
+            rotation: default_rotation(),
+        }
+    }
+}
+
 impl XlineServerConfig {
     /// Generates a new `XlineServerConfig` object
     #[must_use]
@@ -1176,6 +2342,7 @@ impl XlineServerConfig {
         compact: CompactConfig,
         tls: TlsConfig,
         metrics: MetricsConfig,
+        audit: AuditConfig,
     ) -> Self {
         Self {
             cluster,
@@ -1186,6 +2353,7 @@ impl XlineServerConfig {
             compact,
             tls,
             metrics,
+            audit,
         }
     }
 }
@@ -1201,7 +2369,14 @@ mod tests {
             r#"[cluster]
             name = 'node1'
             is_leader = true
+            enable_grpc_reflection = true
+            client_http_listen_urls = ['127.0.0.1:2378']
             initial_cluster_state = 'new'
+            grpc_web = { cors_allowed_origins = ['http://localhost:3000'] }
+            max_txn_ops = 64
+            max_request_bytes = '512kb'
+            grpc_compression = 'zstd'
+            grpc = { keepalive_interval = '30s', max_concurrent_streams = 1000, initial_stream_window_size = 1048576, max_connection_age = '1h', max_connections = 10000, client_rate_limit = 500 }
             peer_listen_urls = ['127.0.0.1:2380']
             peer_advertise_urls = ['127.0.0.1:2380']
             client_listen_urls = ['127.0.0.1:2379']
@@ -1212,6 +2387,19 @@ mod tests {
             compact_timeout = '5s'
             sync_victims_interval = '20ms'
             watch_progress_notify_interval = '1s'
+            watcher_buffer_size = 100
+            slow_watcher_policy = 'cancel'
+            kv_update_channel_size = 256
+            kv_update_backpressure = 'mark_victims'
+            lease_checkpoint_interval = '1m'
+            slow_request_threshold = '500ms'
+            max_apply_queue_depth = 5000
+            memory_limit = 4294967296
+            shutdown_drain_timeout = '30s'
+
+            [[cluster.qos_classes]]
+            user = 'kube-apiserver'
+            class = 'critical'
 
             [cluster.peers]
             node1 = ['127.0.0.1:2378', '127.0.0.1:2379']
@@ -1223,13 +2411,41 @@ mod tests {
             wait_synced_timeout = '100ms'
             rpc_timeout = '100ms'
             retry_timeout = '100ms'
+            snapshot_rate_limit = 1048576
+            resolve_interval = '10s'
 
             [cluster.client_config]
             initial_retry_timeout = '5s'
             max_retry_timeout = '50s'
+            health_check_interval = '3s'
+            resolve_interval = '10s'
 
             [storage]
             engine = { type = 'memory'}
+            durability = { policy = 'periodic', interval = '100ms' }
+            checksum = 'verify'
+            index_snapshot_interval = '1m'
+
+            [storage.backup]
+            dir = '/var/lib/xline/backups'
+            interval = '30m'
+            retention = 3
+
+            [storage.backup.upload]
+            endpoint = 'http://127.0.0.1:9000'
+            bucket = 'xline'
+            prefix = 'node1/'
+            path_style = true
+            tags = { retention = 'weekly' }
+
+            [[storage.tenant_quotas]]
+            prefix = 'tenant-a/'
+            max_keys = 1000
+            max_bytes = 1048576
+
+            [storage.encryption]
+            kek = { type = 'file', path = '/etc/xline/kek' }
+            previous_keks = [{ type = 'command', command = ['kms-client', 'get-key', 'old'] }]
 
             [compact]
             compact_batch_size = 123
@@ -1249,10 +2465,22 @@ mod tests {
             jaeger_offline = false
             jaeger_output_dir = './jaeger_jsons'
             jaeger_level = 'info'
+            otlp_endpoint = 'http://127.0.0.1:4317'
+            sampling_ratio = 0.5
 
             [auth]
             auth_public_key = './public_key.pem'
             auth_private_key = './private_key.pem'
+            auth_previous_public_keys = ['./previous_public_key.pem']
+            auth_token_ttl = '10m'
+
+            [auth.password_hash]
+            memory_cost = 65536
+            time_cost = 3
+
+            [auth.lockout]
+            max_failures = 3
+            duration = '5m'
 
             [tls]
             peer_cert_path = './cert.pem'
@@ -1266,6 +2494,11 @@ mod tests {
             push = true
             push_endpoint = 'http://some-endpoint.com:4396'
             push_protocol = 'http'
+
+            [audit]
+            enable = true
+            path = '/var/log/xline/audit'
+            rotation = 'hourly'
             "#,
         )
         .unwrap();
@@ -1274,6 +2507,8 @@ mod tests {
             .heartbeat_interval(Duration::from_millis(200))
             .wait_synced_timeout(Duration::from_millis(100))
             .rpc_timeout(Duration::from_millis(100))
+            .snapshot_rate_limit(1024 * 1024)
+            .resolve_interval(Duration::from_secs(10))
             .build()
             .unwrap();
 
@@ -1285,6 +2520,8 @@ mod tests {
             default_retry_count(),
             default_fixed_backoff(),
             default_client_id_keep_alive_interval(),
+            Duration::from_secs(3),
+            Duration::from_secs(10),
         );
 
         let server_timeout = ServerTimeout::new(
@@ -1292,6 +2529,15 @@ mod tests {
             Duration::from_secs(5),
             Duration::from_millis(20),
             Duration::from_secs(1),
+            100,
+            SlowWatcherPolicy::Cancel,
+            256,
+            KvUpdateBackpressure::MarkVictims,
+            Duration::from_secs(60),
+            Duration::from_millis(500),
+            5000,
+            4_294_967_296,
+            Duration::from_secs(30),
         );
 
         assert_eq!(
@@ -1314,13 +2560,75 @@ mod tests {
                 curp_config,
                 client_config,
                 server_timeout,
-                InitialClusterState::New
+                InitialClusterState::New,
+                None,
+                true,
+                vec!["127.0.0.1:2378".to_owned()],
+                Some(GrpcWebConfig::new(
+                    vec!["http://localhost:3000".to_owned()],
+                    default_cors_max_age()
+                )),
+                64,
+                512 * 1024,
+                GrpcCompression::Zstd,
+                GrpcConfig::new(
+                    Duration::from_secs(30),
+                    default_grpc_keepalive_timeout(),
+                    1000,
+                    1_048_576,
+                    0,
+                    Duration::from_secs(3600),
+                    10000,
+                    0,
+                    500,
+                    default_grpc_client_rate_burst(),
+                ),
+                vec![UserQosClass::new(
+                    "kube-apiserver".to_owned(),
+                    QosClass::Critical
+                )],
             )
         );
 
         assert_eq!(
             config.storage,
-            StorageConfig::new(EngineConfig::Memory, default_quota())
+            StorageConfig::new(
+                EngineConfig::Memory,
+                default_quota(),
+                vec![TenantQuota::new("tenant-a/".to_owned(), 1000, 1_048_576)],
+                Some(EncryptionConfig::new(
+                    KeySource::File {
+                        path: PathBuf::from("/etc/xline/kek")
+                    },
+                    vec![KeySource::Command {
+                        command: vec![
+                            "kms-client".to_owned(),
+                            "get-key".to_owned(),
+                            "old".to_owned()
+                        ]
+                    }]
+                )),
+                DurabilityPolicy::Periodic(Duration::from_millis(100)),
+                Some(BackupConfig::new(
+                    PathBuf::from("/var/lib/xline/backups"),
+                    Duration::from_secs(30 * 60),
+                    0,
+                    3,
+                    Some(ObjectStorageConfig::new(
+                        "http://127.0.0.1:9000".to_owned(),
+                        default_object_storage_region(),
+                        "xline".to_owned(),
+                        "node1/".to_owned(),
+                        None,
+                        None,
+                        true,
+                        HashMap::from([("retention".to_owned(), "weekly".to_owned())]),
+                        false
+                    ))
+                )),
+                ChecksumPolicy::Verify,
+                Duration::from_secs(60)
+            )
         );
 
         assert_eq!(
@@ -1337,7 +2645,9 @@ mod tests {
                 false,
                 false,
                 PathBuf::from("./jaeger_jsons"),
-                LevelConfig::INFO
+                LevelConfig::INFO,
+                Some("http://127.0.0.1:4317".to_owned()),
+                0.5
             )
         );
 
@@ -1357,6 +2667,11 @@ mod tests {
             AuthConfig {
                 auth_private_key: Some(PathBuf::from("./private_key.pem")),
                 auth_public_key: Some(PathBuf::from("./public_key.pem")),
+                auth_previous_public_keys: vec![PathBuf::from("./previous_public_key.pem")],
+                auth_token_ttl: Duration::from_secs(600),
+                password_hash: PasswordHashConfig::new(65536, 3, 1),
+                lockout: AuthLockoutConfig::new(3, Duration::from_secs(300)),
+                client_cert_auth: true,
             }
         );
 
@@ -1381,6 +2696,15 @@ mod tests {
                 push_protocol: MetricsPushProtocol::HTTP,
             },
         );
+
+        assert_eq!(
+            config.audit,
+            AuditConfig::new(
+                true,
+                Some(PathBuf::from("/var/log/xline/audit")),
+                RotationConfig::Hourly
+            )
+        );
     }
 
     #[test]
@@ -1439,7 +2763,16 @@ mod tests {
                 CurpConfigBuilder::default().build().unwrap(),
                 ClientConfig::default(),
                 ServerTimeout::default(),
-                InitialClusterState::default()
+                InitialClusterState::default(),
+                None,
+                false,
+                vec![],
+                None,
+                default_max_txn_ops(),
+                default_max_request_bytes(),
+                GrpcCompression::default(),
+                GrpcConfig::default(),
+                vec![],
             )
         );
 
@@ -1463,13 +2796,16 @@ mod tests {
                 false,
                 false,
                 PathBuf::from("./jaeger_jsons"),
-                LevelConfig::INFO
+                LevelConfig::INFO,
+                None,
+                default_trace_sampling_ratio()
             )
         );
         assert_eq!(config.compact, CompactConfig::default());
         assert_eq!(config.auth, AuthConfig::default());
         assert_eq!(config.tls, TlsConfig::default());
         assert_eq!(config.metrics, MetricsConfig::default());
+        assert_eq!(config.audit, AuditConfig::default());
     }
 
     #[test]
@@ -1523,4 +2859,23 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn test_object_storage_config_debug_should_redact_secret() {
+        let config = ObjectStorageConfig::new(
+            "http://127.0.0.1:9000".to_owned(),
+            default_object_storage_region(),
+            "xline".to_owned(),
+            String::new(),
+            Some("access".to_owned()),
+            Some("secret".to_owned()),
+            true,
+            HashMap::new(),
+            false,
+        );
+        let debug = format!("{config:?}");
+        assert!(debug.contains("access"));
+        assert!(!debug.contains("secret\""));
+        assert!(debug.contains("<redacted>"));
+    }
 }
@@ -207,13 +207,17 @@ pub mod task_manager;
 pub mod tokio_lock;
 /// utils for pass span context
 pub mod tracing;
+/// version of binaries
+pub mod version;
 
 use ::tracing::debug;
-pub use parser::*;
-use pbkdf2::{
-    password_hash::{rand_core::OsRng, PasswordHasher, SaltString},
-    Params, Pbkdf2,
+use argon2::{
+    password_hash::{self, rand_core::OsRng, PasswordHash, PasswordHasher, SaltString},
+    Argon2,
 };
+use config::PasswordHashConfig;
+pub use parser::*;
+use pbkdf2::Pbkdf2;
 
 /// display all elements for the given vector
 #[macro_export]
@@ -283,21 +287,99 @@ pub fn build_endpoint(
     Ok(endpoint)
 }
 
-/// Hash password
+/// Hash password with the default parameters of argon2id
 ///
 /// # Errors
 ///
 /// return `Error` when hash password failed
 #[inline]
-pub fn hash_password(password: &[u8]) -> Result<String, pbkdf2::password_hash::errors::Error> {
+pub fn hash_password(password: &[u8]) -> Result<String, password_hash::Error> {
+    hash_password_with(password, &PasswordHashConfig::default())
+}
+
+/// Hash password with argon2id of the parameters in `config`
+///
+/// # Errors
+///
+/// return `Error` when the parameters are invalid or hash password failed
+#[inline]
+pub fn hash_password_with(
+    password: &[u8],
+    config: &PasswordHashConfig,
+) -> Result<String, password_hash::Error> {
+    let params = argon2::Params::new(
+        *config.memory_cost(),
+        *config.time_cost(),
+        *config.parallelism(),
+        None,
+    )?;
     let salt = SaltString::generate(&mut OsRng);
-    let simple_para = Params {
-        // The recommended rounds is 600,000 or more
-        // [OWASP cheat sheet]: https://cheatsheetseries.owasp.org/cheatsheets/Password_Storage_Cheat_Sheet.html
-        rounds: 200_000,
-        output_length: 32,
-    };
-    let hashed_password =
-        Pbkdf2.hash_password_customized(password, None, None, simple_para, &salt)?;
+    let hashed_password = Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params)
+        .hash_password(password, &salt)?;
     Ok(hashed_password.to_string())
 }
+
+/// Verify password against a hash of argon2id or pbkdf2, the algorithm used before argon2id
+///
+/// # Errors
+///
+/// return `Error` when the hash can not be parsed
+#[inline]
+pub fn verify_password(password: &[u8], hash: &str) -> Result<bool, password_hash::Error> {
+    let hash = PasswordHash::new(hash)?;
+    Ok(hash
+        .verify_password(&[&Argon2::default(), &Pbkdf2], password)
+        .is_ok())
+}
+
+/// Check if a password hash should be replaced by a hash of the parameters in `config`,
+/// which is true for hashes of other algorithms or other parameters
+#[must_use]
+#[inline]
+pub fn password_needs_rehash(hash: &str, config: &PasswordHashConfig) -> bool {
+    let Ok(hash) = PasswordHash::new(hash) else {
+        return false;
+    };
+    if hash.algorithm != argon2::ARGON2ID_IDENT {
+        return true;
+    }
+    argon2::Params::try_from(&hash).map_or(true, |params| {
+        params.m_cost() != *config.memory_cost()
+            || params.t_cost() != *config.time_cost()
+            || params.p_cost() != *config.parallelism()
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use pbkdf2::Params;
+
+    use super::*;
+
+    #[test]
+    fn legacy_hash_should_be_verified_and_upgraded() {
+        let config = PasswordHashConfig::new(64, 1, 1);
+        let salt = SaltString::generate(&mut OsRng);
+        let params = Params {
+            rounds: 1_000,
+            output_length: 32,
+        };
+        let legacy_hash = Pbkdf2
+            .hash_password_customized(b"pwd", None, None, params, &salt)
+            .unwrap()
+            .to_string();
+        assert!(verify_password(b"pwd", &legacy_hash).unwrap());
+        assert!(!verify_password(b"other", &legacy_hash).unwrap());
+        assert!(password_needs_rehash(&legacy_hash, &config));
+
+        let hash = hash_password_with(b"pwd", &config).unwrap();
+        assert!(verify_password(b"pwd", &hash).unwrap());
+        assert!(!verify_password(b"other", &hash).unwrap());
+        assert!(!password_needs_rehash(&hash, &config));
+        assert!(password_needs_rehash(
+            &hash,
+            &PasswordHashConfig::new(128, 1, 1)
+        ));
+        assert!(verify_password(b"pwd", "invalid hash").is_err());
+    }
+}
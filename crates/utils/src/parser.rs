@@ -4,7 +4,8 @@ use clippy_utilities::OverflowArithmetic;
 use thiserror::Error;
 
 use crate::config::{
-    ClusterRange, InitialClusterState, LevelConfig, MetricsPushProtocol, RotationConfig,
+    ClusterRange, GrpcCompression, InitialClusterState, KvUpdateBackpressure, LevelConfig,
+    MetricsPushProtocol, QosClass, RotationConfig, SlowWatcherPolicy, TenantQuota, UserQosClass,
 };
 
 /// seconds per minute
@@ -162,6 +163,82 @@ pub fn parse_state(s: &str) -> Result<InitialClusterState, ConfigParseError> {
     }
 }
 
+/// Parse `SlowWatcherPolicy` from string
+/// # Errors
+/// Return error when parsing the given string to `SlowWatcherPolicy` failed
+#[inline]
+pub fn parse_slow_watcher_policy(s: &str) -> Result<SlowWatcherPolicy, ConfigParseError> {
+    match s {
+        "resync" => Ok(SlowWatcherPolicy::Resync),
+        "cancel" => Ok(SlowWatcherPolicy::Cancel),
+        _ => Err(ConfigParseError::InvalidValue(format!(
+            "the slow watcher policy should be one of 'resync' or 'cancel' ({s})"
+        ))),
+    }
+}
+
+/// Parse `KvUpdateBackpressure` from string
+/// # Errors
+/// Return error when parsing the given string to `KvUpdateBackpressure` failed
+#[inline]
+pub fn parse_kv_update_backpressure(s: &str) -> Result<KvUpdateBackpressure, ConfigParseError> {
+    match s {
+        "block" => Ok(KvUpdateBackpressure::Block),
+        "mark_victims" => Ok(KvUpdateBackpressure::MarkVictims),
+        _ => Err(ConfigParseError::InvalidValue(format!(
+            "the kv update backpressure should be one of 'block' or 'mark_victims' ({s})"
+        ))),
+    }
+}
+
+/// Parse `GrpcCompression` from string
+/// # Errors
+/// Return error when parsing the given string to `GrpcCompression` failed
+#[inline]
+pub fn parse_grpc_compression(s: &str) -> Result<GrpcCompression, ConfigParseError> {
+    match s {
+        "none" => Ok(GrpcCompression::None),
+        "gzip" => Ok(GrpcCompression::Gzip),
+        "zstd" => Ok(GrpcCompression::Zstd),
+        _ => Err(ConfigParseError::InvalidValue(format!(
+            "the grpc compression should be one of 'none', 'gzip' or 'zstd' ({s})"
+        ))),
+    }
+}
+
+/// Parse `QosClass` from string
+/// # Errors
+/// Return error when parsing the given string to `QosClass` failed
+#[inline]
+pub fn parse_qos_class(s: &str) -> Result<QosClass, ConfigParseError> {
+    match s {
+        "critical" => Ok(QosClass::Critical),
+        "normal" => Ok(QosClass::Normal),
+        "batch" => Ok(QosClass::Batch),
+        _ => Err(ConfigParseError::InvalidValue(format!(
+            "the qos class should be one of 'critical', 'normal' or 'batch' ({s})"
+        ))),
+    }
+}
+
+/// Parse `UserQosClass` from string like "user=class"
+/// # Errors
+/// Return error when parsing the given string to `UserQosClass` failed
+#[inline]
+pub fn parse_user_qos_class(s: &str) -> Result<UserQosClass, ConfigParseError> {
+    let Some((user, class)) = s.rsplit_once('=') else {
+        return Err(ConfigParseError::InvalidValue(format!(
+            "Invalid user qos class: {s}"
+        )));
+    };
+    if user.is_empty() {
+        return Err(ConfigParseError::InvalidValue(format!(
+            "the user of qos class should not be empty ({s})"
+        )));
+    }
+    Ok(UserQosClass::new(user.to_owned(), parse_qos_class(class)?))
+}
+
 /// Parse `LevelConfig` from string
 /// # Errors
 /// Return error when parsing the given string to `LevelConfig` failed
@@ -231,6 +308,33 @@ pub fn parse_batch_bytes(s: &str) -> Result<u64, ConfigParseError> {
     }
 }
 
+/// Parse `TenantQuota` from string like "prefix=max_keys:max_bytes"
+/// # Errors
+/// Return error when parsing the given string to `TenantQuota` failed
+#[inline]
+pub fn parse_tenant_quota(s: &str) -> Result<TenantQuota, ConfigParseError> {
+    let Some((prefix, limits)) = s.rsplit_once('=') else {
+        return Err(ConfigParseError::InvalidValue(format!(
+            "Invalid tenant quota: {s}"
+        )));
+    };
+    let Some((max_keys, max_bytes)) = limits.split_once(':') else {
+        return Err(ConfigParseError::InvalidValue(format!(
+            "Invalid tenant quota: {s}"
+        )));
+    };
+    if prefix.is_empty() {
+        return Err(ConfigParseError::InvalidValue(format!(
+            "the prefix of tenant quota should not be empty ({s})"
+        )));
+    }
+    Ok(TenantQuota::new(
+        prefix.to_owned(),
+        max_keys.parse::<u64>()?,
+        max_bytes.parse::<u64>()?,
+    ))
+}
+
 /// Get the metrics push protocol
 /// # Errors
 /// Return error when parsing the given string to `MetricsPushProtocol` failed
@@ -245,6 +349,21 @@ pub fn parse_metrics_push_protocol(s: &str) -> Result<MetricsPushProtocol, Confi
     }
 }
 
+/// Parse the number of backups to keep, which should be at least 1, or the new backup
+/// would be removed right after it is taken
+/// # Errors
+/// Return error when the given string is not a positive number
+#[inline]
+pub fn parse_backup_retention(s: &str) -> Result<usize, ConfigParseError> {
+    let retention = s.parse::<usize>()?;
+    if retention == 0 {
+        return Err(ConfigParseError::InvalidValue(format!(
+            "the backup retention should be at least 1 ({s})"
+        )));
+    }
+    Ok(retention)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -274,6 +393,64 @@ mod test {
         }
     }
 
+    #[test]
+    fn test_parse_slow_watcher_policy() {
+        assert_eq!(
+            parse_slow_watcher_policy("resync").unwrap(),
+            SlowWatcherPolicy::Resync
+        );
+        assert_eq!(
+            parse_slow_watcher_policy("cancel").unwrap(),
+            SlowWatcherPolicy::Cancel
+        );
+        assert!(parse_slow_watcher_policy("drop").is_err());
+    }
+
+    #[test]
+    fn test_parse_kv_update_backpressure() {
+        assert_eq!(
+            parse_kv_update_backpressure("block").unwrap(),
+            KvUpdateBackpressure::Block
+        );
+        assert_eq!(
+            parse_kv_update_backpressure("mark_victims").unwrap(),
+            KvUpdateBackpressure::MarkVictims
+        );
+        assert!(parse_kv_update_backpressure("drop").is_err());
+    }
+
+    #[test]
+    fn test_parse_grpc_compression() {
+        assert_eq!(
+            parse_grpc_compression("none").unwrap(),
+            GrpcCompression::None
+        );
+        assert_eq!(
+            parse_grpc_compression("gzip").unwrap(),
+            GrpcCompression::Gzip
+        );
+        assert_eq!(
+            parse_grpc_compression("zstd").unwrap(),
+            GrpcCompression::Zstd
+        );
+        assert!(parse_grpc_compression("brotli").is_err());
+    }
+
+    #[test]
+    fn test_parse_user_qos_class() {
+        assert_eq!(
+            parse_user_qos_class("kube-apiserver=critical").unwrap(),
+            UserQosClass::new("kube-apiserver".to_owned(), QosClass::Critical)
+        );
+        assert_eq!(
+            parse_user_qos_class("importer=batch").unwrap(),
+            UserQosClass::new("importer".to_owned(), QosClass::Batch)
+        );
+        assert!(parse_user_qos_class("importer").is_err());
+        assert!(parse_user_qos_class("=normal").is_err());
+        assert!(parse_user_qos_class("importer=urgent").is_err());
+    }
+
     #[test]
     fn test_parse_range() {
         assert_eq!(parse_range("1000..2000").unwrap(), 1000..2000);
@@ -354,6 +531,22 @@ mod test {
         assert!(parse_batch_bytes("MB").is_err());
     }
 
+    #[test]
+    fn test_parse_tenant_quota() {
+        assert_eq!(
+            parse_tenant_quota("tenant-a/=100:4096").unwrap(),
+            TenantQuota::new("tenant-a/".to_owned(), 100, 4096)
+        );
+        assert_eq!(
+            parse_tenant_quota("a=b/=0:10").unwrap(),
+            TenantQuota::new("a=b/".to_owned(), 0, 10)
+        );
+        assert!(parse_tenant_quota("tenant-a/").is_err());
+        assert!(parse_tenant_quota("tenant-a/=100").is_err());
+        assert!(parse_tenant_quota("=100:4096").is_err());
+        assert!(parse_tenant_quota("tenant-a/=x:4096").is_err());
+    }
+
     #[test]
     fn test_parse_metrics_push_protocol() {
         assert_eq!(
@@ -366,4 +559,11 @@ mod test {
         );
         assert!(parse_metrics_push_protocol("thrift").is_err());
     }
+
+    #[test]
+    fn test_parse_backup_retention() {
+        assert_eq!(parse_backup_retention("3").unwrap(), 3);
+        assert!(parse_backup_retention("0").is_err());
+        assert!(parse_backup_retention("-1").is_err());
+    }
 }
@@ -49,14 +49,25 @@ enum_with_iter! {
     GcSpecPool,
     GcCmdBoard,
     RevokeExpiredLeases,
+    CheckpointLeases,
     SyncVictims,
     AutoCompactor,
+    HttpGateway,
+    SyncStorage,
+    Backup,
+    IndexSnapshot,
+    RangeTombstone,
+    PersistCommands,
+    SyncTerm,
+    PersistNegotiatedVersion,
 }
 
 /// All edges of task graph, the first item in each pair must be shut down before the second item
-pub const ALL_EDGES: [(TaskName, TaskName); 9] = [
+pub const ALL_EDGES: [(TaskName, TaskName); 11] = [
     (TaskName::ConflictCheckedMpmc, TaskName::CmdWorker),
     (TaskName::CmdWorker, TaskName::CompactBg),
+    (TaskName::CmdWorker, TaskName::SyncStorage),
+    (TaskName::CmdWorker, TaskName::PersistCommands),
     (TaskName::CmdWorker, TaskName::KvUpdates),
     (TaskName::KvUpdates, TaskName::WatchTask),
     (TaskName::LeaseKeepAlive, TaskName::TonicServer),
@@ -41,6 +41,18 @@ impl Extract for tonic::metadata::MetadataMap {
     }
 }
 
+/// Create a span for an incoming grpc request, whose parent is the W3C trace
+/// context propagated in the request headers
+#[inline]
+#[must_use]
+pub fn grpc_request_span<B>(request: &tonic::codegen::http::Request<B>) -> Span {
+    let span = tracing::info_span!("grpc_request", path = %request.uri().path());
+    let metadata = tonic::metadata::MetadataMap::from_headers(request.headers().clone());
+    let parent_ctx = global::get_text_map_propagator(|prop| prop.extract(&ExtractMap(&metadata)));
+    span.set_parent(parent_ctx);
+    span
+}
+
 /// Struct for inject data to `MetadataMap`
 struct InjectMap<'a>(&'a mut tonic::metadata::MetadataMap);
 
@@ -0,0 +1,74 @@
+use std::{fmt, str::FromStr};
+
+use crate::ConfigParseError;
+
+/// Version of a binary in the `major.minor.patch` form of the crate versions, the
+/// pre-release and build metadata are ignored
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub struct Version {
+    /// Major version
+    pub major: u64,
+    /// Minor version
+    pub minor: u64,
+    /// Patch version
+    pub patch: u64,
+}
+
+impl Version {
+    /// Create a new `Version`
+    #[inline]
+    #[must_use]
+    pub const fn new(major: u64, minor: u64, patch: u64) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+impl FromStr for Version {
+    type Err = ConfigParseError;
+
+    #[inline]
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let core = s.split(['-', '+']).next().unwrap_or_default();
+        let mut parts = core.split('.');
+        let (Some(major), Some(minor), Some(patch), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(ConfigParseError::InvalidValue(format!(
+                "the version should be in the form of 'major.minor.patch' ({s})"
+            )));
+        };
+        Ok(Self::new(major.parse()?, minor.parse()?, patch.parse()?))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn version_should_be_parsed_and_ordered() {
+        assert_eq!("0.6.1".parse::<Version>().unwrap(), Version::new(0, 6, 1));
+        assert_eq!(
+            "0.7.0-alpha.1+build".parse::<Version>().unwrap(),
+            Version::new(0, 7, 0)
+        );
+        assert!("0.7".parse::<Version>().is_err());
+        assert!("0.7.x".parse::<Version>().is_err());
+        assert!("".parse::<Version>().is_err());
+        assert!(Version::new(0, 6, 1) < Version::new(0, 6, 10));
+        assert!(Version::new(0, 10, 0) > Version::new(0, 9, 9));
+        assert_eq!(Version::new(1, 2, 3).to_string(), "1.2.3");
+    }
+}
@@ -202,6 +202,49 @@ impl AuthClient {
             .into_inner())
     }
 
+    /// Get a new token with the token of this client, so that long-lived clients can renew
+    /// their tokens before they expire without storing passwords
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the token of this client is expired, or it is
+    /// issued before the last change of auth
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use xline_client::{Client, ClientOptions};
+    /// use anyhow::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let curp_members = ["10.0.0.1:2379", "10.0.0.2:2379", "10.0.0.3:2379"];
+    ///
+    ///     let options = ClientOptions::default().with_user("root", "root pass word");
+    ///     let mut client = Client::connect(curp_members, options).await?.auth_client();
+    ///
+    ///     let resp = client.refresh_token().await?;
+    ///
+    ///     let options = ClientOptions::default().with_token(resp.token);
+    ///     let _client = Client::connect(curp_members, options).await?;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub async fn refresh_token(&mut self) -> Result<AuthenticateResponse> {
+        if self.token.is_none() {
+            return Err(XlineClientError::InvalidArgs(String::from(
+                "the client has no token to refresh",
+            )));
+        }
+        Ok(self
+            .auth_client
+            .authenticate(xlineapi::AuthenticateRequest::default())
+            .await?
+            .into_inner())
+    }
+
     /// Add an user.
     ///
     /// # Errors
@@ -1,17 +1,31 @@
 use std::{fmt::Debug, sync::Arc};
 
+use clippy_utilities::NumericCast;
+use futures::{stream, Stream, StreamExt};
 use tonic::transport::Channel;
 use xlineapi::{
-    command::Command, CompactionResponse, DeleteRangeResponse, PutResponse, RangeResponse,
-    RequestWrapper, TxnResponse,
+    command::Command, CompactionResponse, DeleteRangeResponse, KeyValue, PutResponse,
+    RangeResponse, RequestWrapper, SortOrder, SortTarget, TxnResponse,
 };
 
 use crate::{
-    error::Result,
+    error::{Result, XlineClientError},
+    namespace::Namespace,
     types::kv::{CompactionRequest, DeleteRangeRequest, PutRequest, RangeRequest, TxnRequest},
     AuthService, CurpClient,
 };
 
+/// State of a paged range
+#[derive(Debug)]
+struct RangePages {
+    /// The request of the next page, `None` if all pages are fetched
+    next: Option<xlineapi::RangeRequest>,
+    /// The max number of keys left to fetch, 0 means no limit
+    remaining: i64,
+    /// The max number of keys in a page
+    page_size: i64,
+}
+
 /// Client for KV operations.
 #[derive(Clone)]
 pub struct KvClient {
@@ -25,6 +39,8 @@ pub struct KvClient {
     kv_client: xlineapi::KvClient<Channel>,
     /// The auth token
     token: Option<String>,
+    /// The namespace of keys
+    namespace: Namespace,
 }
 
 impl Debug for KvClient {
@@ -34,6 +50,7 @@ impl Debug for KvClient {
             .field("kv_client", &self.kv_client)
             .field("kv_client", &self.kv_client)
             .field("token", &self.token)
+            .field("namespace", &self.namespace)
             .finish()
     }
 }
@@ -45,6 +62,7 @@ impl KvClient {
         curp_client: Arc<CurpClient>,
         channel: Channel,
         token: Option<String>,
+        namespace: Namespace,
     ) -> Self {
         Self {
             curp_client,
@@ -53,6 +71,7 @@ impl KvClient {
                 token.as_ref().and_then(|t| t.parse().ok().map(Arc::new)),
             )),
             token,
+            namespace,
         }
     }
 
@@ -83,20 +102,27 @@ impl KvClient {
     /// ```
     #[inline]
     pub async fn put(&self, request: PutRequest) -> Result<PutResponse> {
-        let request = RequestWrapper::from(xlineapi::PutRequest::from(request));
+        let mut request = xlineapi::PutRequest::from(request);
+        self.namespace.prefix_put(&mut request);
+        let request = RequestWrapper::from(request);
         let cmd = Command::new(request.keys(), request);
         let (cmd_res, _sync_res) = self
             .curp_client
             .propose(&cmd, self.token.as_ref(), true)
             .await??;
-        Ok(cmd_res.into_inner().into())
+        let mut resp = cmd_res.into_inner().into();
+        self.namespace.strip_put_response(&mut resp);
+        Ok(resp)
     }
 
-    /// Get a range of keys from the store
+    /// Get a range of keys from the store.
+    /// A serializable range request is sent to a single server and served from its local
+    /// store without going through consensus, so the result may be stale.
     ///
     /// # Errors
     ///
     /// This function will return an error if the inner CURP client encountered a propose failure
+    /// or the serializable range request failed
     ///
     /// # Examples
     ///
@@ -127,13 +153,125 @@ impl KvClient {
     /// ```
     #[inline]
     pub async fn range(&self, request: RangeRequest) -> Result<RangeResponse> {
-        let request = RequestWrapper::from(xlineapi::RangeRequest::from(request));
-        let cmd = Command::new(request.keys(), request);
-        let (cmd_res, _sync_res) = self
-            .curp_client
-            .propose(&cmd, self.token.as_ref(), true)
-            .await??;
-        Ok(cmd_res.into_inner().into())
+        let mut request = xlineapi::RangeRequest::from(request);
+        self.namespace.prefix_range(&mut request);
+        let mut resp = if request.serializable {
+            let mut kv_client = self.kv_client.clone();
+            kv_client.range(request).await?.into_inner()
+        } else {
+            let request = RequestWrapper::from(request);
+            let cmd = Command::new(request.keys(), request);
+            let (cmd_res, _sync_res) = self
+                .curp_client
+                .propose(&cmd, self.token.as_ref(), true)
+                .await??;
+            cmd_res.into_inner().into()
+        };
+        self.namespace.strip_range_response(&mut resp);
+        Ok(resp)
+    }
+
+    /// Get a range of keys from the store page by page, so that a range too large for a single
+    /// response can be iterated without holding all of it in memory. Each page fetches at most
+    /// `page_size` keys, and all pages read the store at the revision of the first page.
+    ///
+    /// Keys are returned in ascending order, the `limit` of the request bounds the total number of keys.
+    ///
+    /// # Errors
+    ///
+    /// The stream yields an error if the request is `count_only` or sorted by other than ascending
+    /// keys, `page_size` is not positive, or a page failed to be fetched.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use futures::StreamExt;
+    /// use xline_client::{types::kv::RangeRequest, Client, ClientOptions};
+    /// use anyhow::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     let curp_members = ["10.0.0.1:2379", "10.0.0.2:2379", "10.0.0.3:2379"];
+    ///
+    ///     let client = Client::connect(curp_members, ClientOptions::default())
+    ///         .await?
+    ///         .kv_client();
+    ///
+    ///     let mut kvs = client.range_stream(RangeRequest::new("key").with_prefix(), 1000);
+    ///     while let Some(kv) = kvs.next().await {
+    ///         println!("got key: {}", String::from_utf8_lossy(&kv?.key));
+    ///     }
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub fn range_stream(
+        &self,
+        request: RangeRequest,
+        page_size: i64,
+    ) -> impl Stream<Item = Result<KeyValue>> + '_ {
+        let request = xlineapi::RangeRequest::from(request);
+        #[allow(clippy::as_conversions)] // this case is always safe
+        let invalid = if page_size <= 0 {
+            Some("page size should be positive")
+        } else if request.count_only {
+            Some("count only range can not be paged")
+        } else if request.sort_target != SortTarget::Key as i32
+            || request.sort_order == SortOrder::Descend as i32
+        {
+            Some("paged range should be sorted by ascending keys")
+        } else {
+            None
+        };
+        let pages = RangePages {
+            remaining: request.limit,
+            next: invalid.is_none().then_some(request),
+            page_size,
+        };
+        let first = invalid.map(|reason| Err(XlineClientError::InvalidArgs(reason.to_owned())));
+        stream::iter(first).chain(
+            stream::unfold(pages, move |pages| self.next_range_page(pages))
+                .map(|page| {
+                    stream::iter(match page {
+                        Ok(kvs) => kvs.into_iter().map(Ok).collect(),
+                        Err(e) => vec![Err(e)],
+                    })
+                })
+                .flatten(),
+        )
+    }
+
+    /// Fetch the next page of a paged range
+    async fn next_range_page(
+        &self,
+        mut pages: RangePages,
+    ) -> Option<(Result<Vec<KeyValue>>, RangePages)> {
+        let mut request = pages.next.take()?;
+        request.limit = if pages.remaining > 0 {
+            pages.remaining.min(pages.page_size)
+        } else {
+            pages.page_size
+        };
+        let resp = match self.range(RangeRequest::from(request.clone())).await {
+            Ok(resp) => resp,
+            Err(e) => return Some((Err(e), pages)),
+        };
+        let mut done = !resp.more;
+        if pages.remaining > 0 {
+            pages.remaining = pages
+                .remaining
+                .saturating_sub(resp.kvs.len().numeric_cast());
+            done |= pages.remaining <= 0;
+        }
+        if let Some(last) = resp.kvs.last().filter(|_| !done) {
+            if request.revision <= 0 {
+                request.revision = resp.header.as_ref().map_or(0, |h| h.revision);
+            }
+            request.key = last.key.iter().copied().chain([0]).collect();
+            pages.next = Some(request);
+        }
+        Some((Ok(resp.kvs), pages))
     }
 
     /// Delete a range of keys from the store
@@ -164,13 +302,17 @@ impl KvClient {
     /// ```
     #[inline]
     pub async fn delete(&self, request: DeleteRangeRequest) -> Result<DeleteRangeResponse> {
-        let request = RequestWrapper::from(xlineapi::DeleteRangeRequest::from(request));
+        let mut request = xlineapi::DeleteRangeRequest::from(request);
+        self.namespace.prefix_delete_range(&mut request);
+        let request = RequestWrapper::from(request);
         let cmd = Command::new(request.keys(), request);
         let (cmd_res, _sync_res) = self
             .curp_client
             .propose(&cmd, self.token.as_ref(), true)
             .await??;
-        Ok(cmd_res.into_inner().into())
+        let mut resp = cmd_res.into_inner().into();
+        self.namespace.strip_delete_range_response(&mut resp);
+        Ok(resp)
     }
 
     /// Creates a transaction, which can provide serializable writes
@@ -212,7 +354,9 @@ impl KvClient {
     /// ```
     #[inline]
     pub async fn txn(&self, request: TxnRequest) -> Result<TxnResponse> {
-        let request = RequestWrapper::from(xlineapi::TxnRequest::from(request));
+        let mut request = xlineapi::TxnRequest::from(request);
+        self.namespace.prefix_txn(&mut request);
+        let request = RequestWrapper::from(request);
         let cmd = Command::new(request.keys(), request);
         let (cmd_res, Some(sync_res)) = self
             .curp_client
@@ -223,7 +367,9 @@ impl KvClient {
         };
         let mut res_wrapper = cmd_res.into_inner();
         res_wrapper.update_revision(sync_res.revision());
-        Ok(res_wrapper.into())
+        let mut resp = res_wrapper.into();
+        self.namespace.strip_txn_response(&mut resp);
+        Ok(resp)
     }
 
     /// Compacts the key-value store up to a given revision.
@@ -5,10 +5,12 @@ use std::{
         atomic::{AtomicBool, Ordering},
         Arc,
     },
+    time::Duration,
 };
 
 use clippy_utilities::OverflowArithmetic;
 use futures::{Future, FutureExt};
+use tokio::task::JoinHandle;
 use tonic::transport::Channel;
 use xlineapi::{
     command::{Command, CommandResponse, KeyRange, SyncResponse},
@@ -23,7 +25,7 @@ use crate::{
     error::{Result, XlineClientError},
     lease_gen::LeaseIdGenerator,
     types::{
-        lease::LeaseGrantRequest,
+        lease::{LeaseGrantRequest, LeaseKeepAliveRequest, LeaseRevokeRequest},
         lock::{LockRequest, UnlockRequest},
         watch::WatchRequest,
     },
@@ -142,6 +144,151 @@ impl LockClient {
         .await
     }
 
+    /// Tries to acquire a distributed shared lock on a given named lock
+    /// without waiting. If the lock is held by another session, the key of
+    /// this attempt is removed, the lease granted for this attempt is revoked,
+    /// and an error is returned immediately.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the inner CURP client encountered a propose failure
+    /// or the lock is held by another session
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use xline_client::{types::lock::LockRequest, Client, ClientOptions};
+    /// use anyhow::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     // the name and address of all curp members
+    ///     let curp_members = ["10.0.0.1:2379", "10.0.0.2:2379", "10.0.0.3:2379"];
+    ///
+    ///     let mut client = Client::connect(curp_members, ClientOptions::default())
+    ///         .await?
+    ///         .lock_client();
+    ///
+    ///     // try to acquire a lock
+    ///     let resp = client
+    ///         .try_lock(LockRequest::new("lock-test"))
+    ///         .await?;
+    ///
+    ///     println!("lock key: {:?}", String::from_utf8_lossy(&resp.key));
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub async fn try_lock(&self, request: LockRequest) -> Result<LockResponse> {
+        let mut lease_id = request.inner.lease;
+        if lease_id == 0 {
+            let resp = self
+                .lease_client
+                .grant(LeaseGrantRequest::new(request.ttl))
+                .await?;
+            lease_id = resp.id;
+        }
+        let prefix = format!(
+            "{}/",
+            String::from_utf8_lossy(&request.inner.name).into_owned()
+        );
+        let key = format!("{prefix}{lease_id:x}");
+        let (my_rev, owner_res) = self.acquire(&prefix, lease_id).await?;
+
+        if !owner_res
+            .kvs
+            .get(0)
+            .map_or(false, |kv| kv.create_revision == my_rev)
+        {
+            // the key may have been recreated by another attempt with the same lease since,
+            // so it's only removed if it's still the key of this attempt
+            let _ignore = self.delete_key_at(key.as_bytes(), my_rev).await;
+            if request.inner.lease == 0 {
+                let _ignore = self
+                    .lease_client
+                    .clone()
+                    .revoke(LeaseRevokeRequest::new(lease_id))
+                    .await;
+            }
+            return Err(XlineClientError::LockError(String::from(
+                "the lock is held by another session",
+            )));
+        }
+
+        Ok(LockResponse {
+            header: owner_res.header,
+            key: key.into_bytes(),
+        })
+    }
+
+    /// Creates a lock session. The session is bound to a new lease with the
+    /// given ttl, which is kept alive automatically until the session is closed.
+    /// Locks acquired with the lease of the session are released when the
+    /// session is closed.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the inner CURP client encountered a propose failure
+    /// or the lease keep alive stream could not be created
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use xline_client::{types::lock::LockRequest, Client, ClientOptions};
+    /// use anyhow::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     // the name and address of all curp members
+    ///     let curp_members = ["10.0.0.1:2379", "10.0.0.2:2379", "10.0.0.3:2379"];
+    ///
+    ///     let client = Client::connect(curp_members, ClientOptions::default())
+    ///         .await?
+    ///         .lock_client();
+    ///
+    ///     let session = client.session(10).await?;
+    ///     let _resp = client
+    ///         .lock(LockRequest::new("lock-test").with_lease(session.lease_id()))
+    ///         .await?;
+    ///
+    ///     // releases all locks held by the session
+    ///     session.close().await?;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub async fn session(&self, ttl: i64) -> Result<Session> {
+        let resp = self.lease_client.grant(LeaseGrantRequest::new(ttl)).await?;
+        let lease_id = resp.id;
+        let mut lease_client = self.lease_client.clone();
+        let (mut keeper, mut stream) = lease_client
+            .keep_alive(LeaseKeepAliveRequest::new(lease_id))
+            .await?;
+        // keep alive at one third of the ttl, so that a short ttl never expires between
+        // two keepalives
+        let interval =
+            Duration::from_millis(resp.ttl.unsigned_abs().saturating_mul(1000).overflow_div(3));
+        let keep_alive = tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                if keeper.keep_alive().is_err() {
+                    return;
+                }
+                match stream.message().await {
+                    Ok(Some(resp)) if resp.ttl > 0 => {}
+                    _ => return,
+                }
+            }
+        });
+        Ok(Session {
+            lease_id,
+            lease_client,
+            keep_alive,
+        })
+    }
+
     /// The inner lock logic
     async fn lock_inner(
         &self,
@@ -150,24 +297,7 @@ impl LockClient {
         lease_id: i64,
         lock_success: &AtomicBool,
     ) -> Result<LockResponse> {
-        let txn = Self::create_acquire_txn(&prefix, lease_id);
-        let (cmd_res, sync_res) = self.propose(txn, false).await?;
-        let mut txn_res = Into::<TxnResponse>::into(cmd_res.into_inner());
-        let my_rev = sync_res
-            .unwrap_or_else(|| unreachable!("sync_res always has value when use slow path"))
-            .revision();
-        let owner_res = txn_res
-            .responses
-            .swap_remove(1)
-            .response
-            .and_then(|r| {
-                if let Response::ResponseRange(res) = r {
-                    Some(res)
-                } else {
-                    None
-                }
-            })
-            .unwrap_or_else(|| unreachable!("owner_resp should be a Get response"));
+        let (my_rev, owner_res) = self.acquire(&prefix, lease_id).await?;
 
         let owner_key = owner_res.kvs;
         let header = if owner_key
@@ -213,6 +343,7 @@ impl LockClient {
     /// # Errors
     ///
     /// This function will return an error if the inner CURP client encountered a propose failure
+    /// or the key is not held by a lock session
     ///
     /// # Examples
     ///
@@ -241,8 +372,160 @@ impl LockClient {
     /// ```
     #[inline]
     pub async fn unlock(&self, request: UnlockRequest) -> Result<UnlockResponse> {
-        let header = self.delete_key(&request.inner.key).await?;
-        Ok(UnlockResponse { header })
+        let key = request.inner.key;
+        let range_req = RangeRequest {
+            key: key.clone(),
+            ..Default::default()
+        };
+        let (cmd_res, _sync_res) = self.propose(range_req, true).await?;
+        let range_res = Into::<RangeResponse>::into(cmd_res.into_inner());
+        let Some(kv) = range_res.kvs.first() else {
+            // the lock has already been released, e.g. the session expired
+            return Ok(UnlockResponse {
+                header: range_res.header,
+            });
+        };
+        if kv.lease == 0 || lock_key_lease(&kv.key) != Some(kv.lease) {
+            return Err(XlineClientError::LockError(String::from(
+                "the key is not held by a lock session",
+            )));
+        }
+
+        #[allow(clippy::as_conversions)] // this cast is always safe
+        let cmp = Compare {
+            result: CompareResult::Equal as i32,
+            target: CompareTarget::Lease as i32,
+            key: key.clone(),
+            range_end: vec![],
+            target_union: Some(TargetUnion::Lease(kv.lease)),
+        };
+        let del = RequestOp {
+            request: Some(Request::RequestDeleteRange(DeleteRangeRequest {
+                key,
+                ..Default::default()
+            })),
+        };
+        let txn = TxnRequest {
+            compare: vec![cmp],
+            success: vec![del],
+            failure: vec![],
+        };
+        let (cmd_res, _sync_res) = self.propose(txn, true).await?;
+        let txn_res = Into::<TxnResponse>::into(cmd_res.into_inner());
+        if !txn_res.succeeded {
+            return Err(XlineClientError::LockError(String::from(
+                "the lock key is held by another session",
+            )));
+        }
+        Ok(UnlockResponse {
+            header: txn_res.header,
+        })
+    }
+
+    /// Gets the fencing token of a key returned by Lock. The token is the create
+    /// revision of the lock key, which increases monotonically as the lock
+    /// changes hands, so downstream systems can use it to reject operations
+    /// from stale lock holders.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the inner CURP client encountered a propose failure
+    /// or the key is not held by a lock session
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use xline_client::{types::lock::LockRequest, Client, ClientOptions};
+    /// use anyhow::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     // the name and address of all curp members
+    ///     let curp_members = ["10.0.0.1:2379", "10.0.0.2:2379", "10.0.0.3:2379"];
+    ///
+    ///     let client = Client::connect(curp_members, ClientOptions::default())
+    ///         .await?
+    ///         .lock_client();
+    ///
+    ///     let resp = client.lock(LockRequest::new("lock-test")).await?;
+    ///     let token = client.fencing_token(resp.key).await?;
+    ///
+    ///     println!("fencing token: {token}");
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub async fn fencing_token(&self, key: impl Into<Vec<u8>>) -> Result<i64> {
+        let range_req = RangeRequest {
+            key: key.into(),
+            ..Default::default()
+        };
+        let (cmd_res, _sync_res) = self.propose(range_req, true).await?;
+        let range_res = Into::<RangeResponse>::into(cmd_res.into_inner());
+        range_res
+            .kvs
+            .first()
+            .filter(|kv| kv.lease != 0 && lock_key_lease(&kv.key) == Some(kv.lease))
+            .map(|kv| kv.create_revision)
+            .ok_or_else(|| {
+                XlineClientError::LockError(String::from("the key is not held by a lock session"))
+            })
+    }
+
+    /// Validates a fencing token of the given named lock. Returns `true` only if
+    /// the token belongs to the current holder of the lock.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the inner CURP client encountered a propose failure
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use xline_client::{types::lock::LockRequest, Client, ClientOptions};
+    /// use anyhow::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     // the name and address of all curp members
+    ///     let curp_members = ["10.0.0.1:2379", "10.0.0.2:2379", "10.0.0.3:2379"];
+    ///
+    ///     let client = Client::connect(curp_members, ClientOptions::default())
+    ///         .await?
+    ///         .lock_client();
+    ///
+    ///     let resp = client.lock(LockRequest::new("lock-test")).await?;
+    ///     let token = client.fencing_token(resp.key).await?;
+    ///     assert!(client.validate_fencing_token("lock-test", token).await?);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub async fn validate_fencing_token(
+        &self,
+        name: impl Into<Vec<u8>>,
+        token: i64,
+    ) -> Result<bool> {
+        let mut prefix = name.into();
+        prefix.push(b'/');
+        let range_end = KeyRange::get_prefix(&prefix);
+        #[allow(clippy::as_conversions)] // this cast is always safe
+        let get_owner = RangeRequest {
+            key: prefix,
+            range_end,
+            sort_order: SortOrder::Ascend as i32,
+            sort_target: SortTarget::Create as i32,
+            limit: 1,
+            ..Default::default()
+        };
+        let (cmd_res, _sync_res) = self.propose(get_owner, true).await?;
+        let owner_res = Into::<RangeResponse>::into(cmd_res.into_inner());
+        Ok(owner_res
+            .kvs
+            .first()
+            .map_or(false, |kv| kv.create_revision == token))
     }
 
     /// Propose request and get result with fast/slow path
@@ -262,6 +545,44 @@ impl LockClient {
             .map_err(Into::into)
     }
 
+    /// Try to acquire the lock, returns the create revision of the lock key
+    /// and the range response of the lock owner
+    async fn acquire(&self, prefix: &str, lease_id: i64) -> Result<(i64, RangeResponse)> {
+        let txn = Self::create_acquire_txn(prefix, lease_id);
+        let (cmd_res, sync_res) = self.propose(txn, false).await?;
+        let mut txn_res = Into::<TxnResponse>::into(cmd_res.into_inner());
+        let owner_res = txn_res
+            .responses
+            .swap_remove(1)
+            .response
+            .and_then(|r| {
+                if let Response::ResponseRange(res) = r {
+                    Some(res)
+                } else {
+                    None
+                }
+            })
+            .unwrap_or_else(|| unreachable!("owner_resp should be a Get response"));
+        let my_rev = if txn_res.succeeded {
+            sync_res
+                .unwrap_or_else(|| unreachable!("sync_res always has value when use slow path"))
+                .revision()
+        } else {
+            // the key has been created by the same session before
+            txn_res
+                .responses
+                .first()
+                .and_then(|r| match r.response {
+                    Some(Response::ResponseRange(ref res)) => {
+                        res.kvs.first().map(|kv| kv.create_revision)
+                    }
+                    _ => None,
+                })
+                .unwrap_or_else(|| unreachable!("the lock key should exist in failure branch"))
+        };
+        Ok((my_rev, owner_res))
+    }
+
     /// Create txn for try acquire lock
     fn create_acquire_txn(prefix: &str, lease_id: i64) -> TxnRequest {
         let key = format!("{prefix}{lease_id:x}");
@@ -353,6 +674,81 @@ impl LockClient {
         let res = Into::<DeleteRangeResponse>::into(cmd_res.into_inner());
         Ok(res.header)
     }
+
+    /// Delete key if it's still created at the given revision
+    async fn delete_key_at(&self, key: &[u8], create_revision: i64) -> Result<bool> {
+        #[allow(clippy::as_conversions)] // this cast is always safe
+        let cmp = Compare {
+            result: CompareResult::Equal as i32,
+            target: CompareTarget::Create as i32,
+            key: key.into(),
+            range_end: vec![],
+            target_union: Some(TargetUnion::CreateRevision(create_revision)),
+        };
+        let del = RequestOp {
+            request: Some(Request::RequestDeleteRange(DeleteRangeRequest {
+                key: key.into(),
+                ..Default::default()
+            })),
+        };
+        let txn = TxnRequest {
+            compare: vec![cmp],
+            success: vec![del],
+            failure: vec![],
+        };
+        let (cmd_res, _sync_res) = self.propose(txn, true).await?;
+        Ok(Into::<TxnResponse>::into(cmd_res.into_inner()).succeeded)
+    }
+}
+
+/// Get the lease id encoded in the last segment of a lock key
+fn lock_key_lease(key: &[u8]) -> Option<i64> {
+    let pos = key.iter().rposition(|b| *b == b'/')?;
+    let suffix = std::str::from_utf8(key.get(pos.overflow_add(1)..)?).ok()?;
+    i64::from_str_radix(suffix, 16).ok()
+}
+
+/// A lock session whose lease is kept alive automatically
+#[derive(Debug)]
+pub struct Session {
+    /// The lease id of the session
+    lease_id: i64,
+    /// The lease client
+    lease_client: LeaseClient,
+    /// The handle of the keep alive task
+    keep_alive: JoinHandle<()>,
+}
+
+impl Session {
+    /// The lease id of the session, use it to acquire locks with the session
+    #[inline]
+    #[must_use]
+    pub const fn lease_id(&self) -> i64 {
+        self.lease_id
+    }
+
+    /// Closes the session. The lease of the session is revoked, so all locks
+    /// acquired with the session are released.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the lease could not be revoked
+    #[inline]
+    pub async fn close(mut self) -> Result<()> {
+        self.keep_alive.abort();
+        let _resp = self
+            .lease_client
+            .revoke(LeaseRevokeRequest::new(self.lease_id))
+            .await?;
+        Ok(())
+    }
+}
+
+impl Drop for Session {
+    #[inline]
+    fn drop(&mut self) {
+        self.keep_alive.abort();
+    }
 }
 
 /// The future that will do the lock operation
@@ -2,7 +2,9 @@ use std::{fmt::Debug, sync::Arc};
 
 use tonic::{transport::Channel, Streaming};
 use xlineapi::{
-    AlarmRequest, AlarmResponse, SnapshotRequest, SnapshotResponse, StatusRequest, StatusResponse,
+    AlarmRequest, AlarmResponse, DefragmentRequest, DefragmentResponse, HashKvRequest,
+    HashKvResponse, MoveLeaderRequest, MoveLeaderResponse, SnapshotRequest, SnapshotResponse,
+    StatusRequest, StatusResponse,
 };
 
 use crate::{error::Result, AuthService};
@@ -139,4 +141,119 @@ impl MaintenanceClient {
             .await?
             .into_inner())
     }
+
+    /// Computes the hash of all MVCC keys up to a given revision
+    ///
+    /// It only iterates "key" bucket in backend storage. The hash can be used to
+    /// detect divergence between members. A revision of 0 means the latest revision.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the inner RPC client encountered a propose failure
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use xline_client::{Client, ClientOptions};
+    /// use anyhow::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     // the name and address of all curp members
+    ///     let curp_members = ["10.0.0.1:2379", "10.0.0.2:2379", "10.0.0.3:2379"];
+    ///
+    ///     let mut client = Client::connect(curp_members, ClientOptions::default())
+    ///         .await?
+    ///         .maintenance_client();
+    ///
+    ///     let resp = client.hash_kv(0).await?;
+    ///     println!("hash: {}, compact revision: {}", resp.hash, resp.compact_revision);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub async fn hash_kv(&mut self, revision: i64) -> Result<HashKvResponse> {
+        Ok(self
+            .inner
+            .hash_kv(HashKvRequest { revision })
+            .await?
+            .into_inner())
+    }
+
+    /// Transfers the leadership of the cluster to the given member
+    ///
+    /// The request should be sent to the current leader, and the target member
+    /// must be a voter. It returns after the target member becomes the leader.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the inner RPC client encountered a propose failure
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use xline_client::{Client, ClientOptions};
+    /// use anyhow::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     // the name and address of all curp members
+    ///     let curp_members = ["10.0.0.1:2379", "10.0.0.2:2379", "10.0.0.3:2379"];
+    ///
+    ///     let mut client = Client::connect(curp_members, ClientOptions::default())
+    ///         .await?
+    ///         .maintenance_client();
+    ///
+    ///     client.move_leader(12345).await?;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub async fn move_leader(&mut self, target_id: u64) -> Result<MoveLeaderResponse> {
+        Ok(self
+            .inner
+            .move_leader(MoveLeaderRequest { target_id })
+            .await?
+            .into_inner())
+    }
+
+    /// Defragments the storage of the connected member to reclaim disk space
+    ///
+    /// It only affects the member this client is connected to, and it may block
+    /// the member for a while, so it should be run on one member at a time.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the inner RPC client encountered a propose failure
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use xline_client::{Client, ClientOptions};
+    /// use anyhow::Result;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> Result<()> {
+    ///     // the name and address of all curp members
+    ///     let curp_members = ["10.0.0.1:2379", "10.0.0.2:2379", "10.0.0.3:2379"];
+    ///
+    ///     let mut client = Client::connect(curp_members, ClientOptions::default())
+    ///         .await?
+    ///         .maintenance_client();
+    ///
+    ///     client.defragment().await?;
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    #[inline]
+    pub async fn defragment(&mut self) -> Result<DefragmentResponse> {
+        Ok(self
+            .inner
+            .defragment(DefragmentRequest {})
+            .await?
+            .into_inner())
+    }
 }
@@ -3,7 +3,7 @@ pub use cluster::ClusterClient;
 pub use election::ElectionClient;
 pub use kv::KvClient;
 pub use lease::LeaseClient;
-pub use lock::LockClient;
+pub use lock::{LockClient, Session};
 pub use maintenance::MaintenanceClient;
 pub use watch::WatchClient;
 
@@ -6,6 +6,7 @@ use xlineapi::{self, RequestUnion};
 
 use crate::{
     error::{Result, XlineClientError},
+    namespace::Namespace,
     types::watch::{WatchRequest, WatchStreaming, Watcher},
     AuthService,
 };
@@ -22,6 +23,8 @@ pub struct WatchClient {
     /// The watch RPC client, only communicate with one server at a time
     #[cfg(madsim)]
     inner: xlineapi::WatchClient<Channel>,
+    /// The namespace of watched keys
+    namespace: Namespace,
 }
 
 impl WatchClient {
@@ -34,9 +37,15 @@ impl WatchClient {
                 channel,
                 token.and_then(|t| t.parse().ok().map(Arc::new)),
             )),
+            namespace: Namespace::default(),
         }
     }
 
+    /// Watch keys in the given namespace
+    pub(crate) fn with_namespace(self, namespace: Namespace) -> Self {
+        Self { namespace, ..self }
+    }
+
     /// Watches for events happening or that have happened. Both input and output
     /// are streams; the input stream is for creating and canceling watcher and the output
     /// stream sends events. The entire event history can be watched starting from the
@@ -90,8 +99,10 @@ impl WatchClient {
         let (mut request_sender, request_receiver) =
             channel::<xlineapi::WatchRequest>(CHANNEL_SIZE);
 
+        let mut create_request = request.into();
+        self.namespace.prefix_watch(&mut create_request);
         let request = xlineapi::WatchRequest {
-            request_union: Some(RequestUnion::CreateRequest(request.into())),
+            request_union: Some(RequestUnion::CreateRequest(create_request)),
         };
 
         request_sender
@@ -113,8 +124,9 @@ impl WatchClient {
         };
 
         Ok((
-            Watcher::new(watch_id, request_sender.clone()),
-            WatchStreaming::new(response_stream, request_sender),
+            Watcher::new(watch_id, request_sender.clone()).with_namespace(self.namespace.clone()),
+            WatchStreaming::new(response_stream, request_sender)
+                .with_namespace(self.namespace.clone()),
         ))
     }
 }
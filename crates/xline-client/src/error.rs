@@ -72,6 +72,9 @@ pub enum XlineClientError<C: CurpCommand> {
     /// Error in lease client
     #[error("Lease client error: {0}")]
     LeaseError(String),
+    /// Error in lock client
+    #[error("Lock client error: {0}")]
+    LockError(String),
     /// Request Timeout
     #[error("Request timeout")]
     Timeout,
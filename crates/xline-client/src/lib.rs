@@ -183,12 +183,15 @@ use crate::{
         MaintenanceClient, WatchClient,
     },
     error::XlineClientBuildError,
+    namespace::Namespace,
 };
 
 /// Sub-clients for each type of API
 pub mod clients;
 /// Lease Id generator
 mod lease_gen;
+/// Key prefix isolation of clients
+mod namespace;
 /// Request type definitions.
 pub mod types;
 
@@ -259,10 +262,16 @@ impl Client {
 
                 Some(resp.token)
             }
-            None => None,
+            None => options.token,
         };
 
-        let kv = KvClient::new(Arc::clone(&curp_client), channel.clone(), token.clone());
+        let namespace = Namespace::new(options.namespace);
+        let kv = KvClient::new(
+            Arc::clone(&curp_client),
+            channel.clone(),
+            token.clone(),
+            namespace.clone(),
+        );
         let lease = LeaseClient::new(
             Arc::clone(&curp_client),
             channel.clone(),
@@ -278,7 +287,7 @@ impl Client {
         let auth = AuthClient::new(curp_client, channel.clone(), token.clone());
         let maintenance = MaintenanceClient::new(channel.clone(), token.clone());
         let cluster = ClusterClient::new(channel.clone(), token.clone());
-        let watch = WatchClient::new(channel, token);
+        let watch = WatchClient::new(channel, token).with_namespace(namespace);
         let election = ElectionClient::new();
 
         Ok(Self {
@@ -372,10 +381,14 @@ impl Client {
 pub struct ClientOptions {
     /// User is a pair values of name and password
     user: Option<(String, String)>,
+    /// Token used when no user is set, e.g. a refreshed token of a previous client
+    token: Option<String>,
     /// Client tls config
     tls_config: Option<ClientTlsConfig>,
     /// config for the curp client
     client_config: ClientConfig,
+    /// Prefix of all keys accessed by the kv and watch clients
+    namespace: Vec<u8>,
 }
 
 impl ClientOptions {
@@ -389,8 +402,10 @@ impl ClientOptions {
     ) -> Self {
         Self {
             user,
+            token: None,
             tls_config,
             client_config,
+            namespace: Vec::new(),
         }
     }
 
@@ -401,6 +416,13 @@ impl ClientOptions {
         self.user.clone()
     }
 
+    /// Get `token`
+    #[inline]
+    #[must_use]
+    pub fn token(&self) -> Option<&str> {
+        self.token.as_deref()
+    }
+
     /// Get `tls_config`
     #[inline]
     #[must_use]
@@ -415,6 +437,13 @@ impl ClientOptions {
         &self.client_config
     }
 
+    /// Get `namespace`
+    #[inline]
+    #[must_use]
+    pub fn namespace(&self) -> &[u8] {
+        &self.namespace
+    }
+
     /// Set `user`
     #[inline]
     #[must_use]
@@ -425,6 +454,17 @@ impl ClientOptions {
         }
     }
 
+    /// Set `token`, it is used to authenticate requests if no user is set, so that a client
+    /// can be built from a token refreshed by `AuthClient::refresh_token` without password
+    #[inline]
+    #[must_use]
+    pub fn with_token(self, token: impl Into<String>) -> Self {
+        Self {
+            token: Some(token.into()),
+            ..self
+        }
+    }
+
     /// Set `client_config`
     #[inline]
     #[must_use]
@@ -444,6 +484,18 @@ impl ClientOptions {
             ..self
         }
     }
+
+    /// Set `namespace`, the prefix transparently added to all keys accessed by the kv and
+    /// watch clients, so that applications in different namespaces never see each other's keys.
+    /// Keys in responses and watch events are returned without the prefix.
+    #[inline]
+    #[must_use]
+    pub fn with_namespace(self, namespace: impl Into<Vec<u8>>) -> Self {
+        Self {
+            namespace: namespace.into(),
+            ..self
+        }
+    }
 }
 
 /// Authentication service.
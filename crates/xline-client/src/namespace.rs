@@ -0,0 +1,227 @@
+use xlineapi::{
+    command::KeyRange, DeleteRangeRequest, DeleteRangeResponse, KeyValue, PutRequest, PutResponse,
+    RangeRequest, RangeResponse, Request, Response, TxnRequest, TxnResponse, WatchCreateRequest,
+    WatchResponse,
+};
+
+/// A key prefix that is added to the keys sent by a client and removed from the keys it
+/// receives, so that applications in different namespaces can share one cluster without
+/// key collisions
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Namespace {
+    /// The prefix of all keys in the namespace, keys are left untouched if it is empty
+    prefix: Vec<u8>,
+}
+
+impl Namespace {
+    /// New `Namespace`
+    pub(crate) fn new(prefix: Vec<u8>) -> Self {
+        Self { prefix }
+    }
+
+    /// Add the prefix to a key
+    fn prefix_key(&self, key: &mut Vec<u8>) {
+        if self.prefix.is_empty() {
+            return;
+        }
+        *key = [self.prefix.as_slice(), key.as_slice()].concat();
+    }
+
+    /// Add the prefix to the range [key, `range_end`), a `range_end` of '\0' is mapped to
+    /// the end of the namespace
+    fn prefix_interval(&self, key: &mut Vec<u8>, range_end: &mut Vec<u8>) {
+        if self.prefix.is_empty() {
+            return;
+        }
+        if range_end.as_slice() == [0] {
+            *range_end = KeyRange::get_prefix(&self.prefix);
+        } else if !range_end.is_empty() {
+            self.prefix_key(range_end);
+        }
+        self.prefix_key(key);
+    }
+
+    /// Remove the prefix from the key of a `KeyValue`
+    fn strip_kv(&self, kv: &mut KeyValue) {
+        if kv.key.starts_with(&self.prefix) {
+            let _ignore = kv.key.drain(..self.prefix.len());
+        }
+    }
+
+    /// Add the prefix to a `RangeRequest`
+    pub(crate) fn prefix_range(&self, req: &mut RangeRequest) {
+        self.prefix_interval(&mut req.key, &mut req.range_end);
+    }
+
+    /// Add the prefix to a `PutRequest`
+    pub(crate) fn prefix_put(&self, req: &mut PutRequest) {
+        self.prefix_key(&mut req.key);
+    }
+
+    /// Add the prefix to a `DeleteRangeRequest`
+    pub(crate) fn prefix_delete_range(&self, req: &mut DeleteRangeRequest) {
+        self.prefix_interval(&mut req.key, &mut req.range_end);
+    }
+
+    /// Add the prefix to the compares and operations of a `TxnRequest`
+    pub(crate) fn prefix_txn(&self, req: &mut TxnRequest) {
+        for cmp in &mut req.compare {
+            self.prefix_interval(&mut cmp.key, &mut cmp.range_end);
+        }
+        for op in req.success.iter_mut().chain(req.failure.iter_mut()) {
+            match op.request {
+                Some(Request::RequestRange(ref mut r)) => self.prefix_range(r),
+                Some(Request::RequestPut(ref mut r)) => self.prefix_put(r),
+                Some(Request::RequestDeleteRange(ref mut r)) => self.prefix_delete_range(r),
+                Some(Request::RequestTxn(ref mut r)) => self.prefix_txn(r),
+                None => {}
+            }
+        }
+    }
+
+    /// Add the prefix to a `WatchCreateRequest`
+    pub(crate) fn prefix_watch(&self, req: &mut WatchCreateRequest) {
+        self.prefix_interval(&mut req.key, &mut req.range_end);
+    }
+
+    /// Remove the prefix from a `RangeResponse`
+    pub(crate) fn strip_range_response(&self, resp: &mut RangeResponse) {
+        resp.kvs.iter_mut().for_each(|kv| self.strip_kv(kv));
+    }
+
+    /// Remove the prefix from a `PutResponse`
+    pub(crate) fn strip_put_response(&self, resp: &mut PutResponse) {
+        if let Some(ref mut kv) = resp.prev_kv {
+            self.strip_kv(kv);
+        }
+    }
+
+    /// Remove the prefix from a `DeleteRangeResponse`
+    pub(crate) fn strip_delete_range_response(&self, resp: &mut DeleteRangeResponse) {
+        resp.prev_kvs.iter_mut().for_each(|kv| self.strip_kv(kv));
+    }
+
+    /// Remove the prefix from the responses of a `TxnResponse`
+    pub(crate) fn strip_txn_response(&self, resp: &mut TxnResponse) {
+        for op in &mut resp.responses {
+            match op.response {
+                Some(Response::ResponseRange(ref mut r)) => self.strip_range_response(r),
+                Some(Response::ResponsePut(ref mut r)) => self.strip_put_response(r),
+                Some(Response::ResponseDeleteRange(ref mut r)) => {
+                    self.strip_delete_range_response(r);
+                }
+                Some(Response::ResponseTxn(ref mut r)) => self.strip_txn_response(r),
+                None => {}
+            }
+        }
+    }
+
+    /// Remove the prefix from the events of a `WatchResponse`
+    pub(crate) fn strip_watch_response(&self, resp: &mut WatchResponse) {
+        for event in &mut resp.events {
+            if let Some(ref mut kv) = event.kv {
+                self.strip_kv(kv);
+            }
+            if let Some(ref mut kv) = event.prev_kv {
+                self.strip_kv(kv);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use xlineapi::{RequestOp, ResponseOp};
+
+    use super::*;
+
+    #[test]
+    fn prefix_interval_should_stay_in_namespace() {
+        let namespace = Namespace::new(b"ns/".to_vec());
+        let mut req = RangeRequest {
+            key: b"foo".to_vec(),
+            ..Default::default()
+        };
+        namespace.prefix_range(&mut req);
+        assert_eq!(req.key, b"ns/foo");
+        assert!(req.range_end.is_empty());
+
+        let mut req = RangeRequest {
+            key: b"foo".to_vec(),
+            range_end: b"fop".to_vec(),
+            ..Default::default()
+        };
+        namespace.prefix_range(&mut req);
+        assert_eq!(req.key, b"ns/foo");
+        assert_eq!(req.range_end, b"ns/fop");
+
+        let mut req = RangeRequest {
+            key: vec![0],
+            range_end: vec![0],
+            ..Default::default()
+        };
+        namespace.prefix_range(&mut req);
+        assert_eq!(req.key, b"ns/\0");
+        assert_eq!(req.range_end, b"ns0");
+    }
+
+    #[test]
+    fn empty_namespace_should_not_change_keys() {
+        let namespace = Namespace::default();
+        let mut req = RangeRequest {
+            key: vec![0],
+            range_end: vec![0],
+            ..Default::default()
+        };
+        namespace.prefix_range(&mut req);
+        assert_eq!(req.key, [0]);
+        assert_eq!(req.range_end, [0]);
+    }
+
+    #[test]
+    fn nested_txn_should_be_prefixed_and_stripped() {
+        let namespace = Namespace::new(b"ns/".to_vec());
+        let mut req = TxnRequest {
+            compare: vec![],
+            success: vec![RequestOp {
+                request: Some(Request::RequestTxn(TxnRequest {
+                    compare: vec![],
+                    success: vec![RequestOp {
+                        request: Some(Request::RequestPut(PutRequest {
+                            key: b"foo".to_vec(),
+                            ..Default::default()
+                        })),
+                    }],
+                    failure: vec![],
+                })),
+            }],
+            failure: vec![],
+        };
+        namespace.prefix_txn(&mut req);
+        let Some(Request::RequestTxn(ref nested)) = req.success[0].request else {
+            unreachable!("the nested txn should be kept");
+        };
+        let Some(Request::RequestPut(ref put)) = nested.success[0].request else {
+            unreachable!("the nested put should be kept");
+        };
+        assert_eq!(put.key, b"ns/foo");
+
+        let mut resp = TxnResponse {
+            responses: vec![ResponseOp {
+                response: Some(Response::ResponseRange(RangeResponse {
+                    kvs: vec![KeyValue {
+                        key: b"ns/foo".to_vec(),
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                })),
+            }],
+            ..Default::default()
+        };
+        namespace.strip_txn_response(&mut resp);
+        let Some(Response::ResponseRange(ref range)) = resp.responses[0].response else {
+            unreachable!("the range response should be kept");
+        };
+        assert_eq!(range.kvs[0].key, b"foo");
+    }
+}
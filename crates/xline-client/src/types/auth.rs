@@ -23,6 +23,7 @@ impl AuthenticateRequest {
             inner: xlineapi::AuthenticateRequest {
                 name: user_name.into(),
                 password: user_password.into(),
+                ..Default::default()
             },
         }
     }
@@ -360,6 +360,13 @@ impl RangeRequest {
     }
 }
 
+impl From<xlineapi::RangeRequest> for RangeRequest {
+    #[inline]
+    fn from(inner: xlineapi::RangeRequest) -> Self {
+        Self { inner }
+    }
+}
+
 impl From<RangeRequest> for xlineapi::RangeRequest {
     #[inline]
     fn from(req: RangeRequest) -> Self {
@@ -1,13 +1,17 @@
 use std::{
     fmt::Debug,
-    ops::{Deref, DerefMut},
+    pin::Pin,
+    task::{Context, Poll},
 };
 
-use futures::channel::mpsc::Sender;
+use futures::{channel::mpsc::Sender, Stream, StreamExt};
 use xlineapi::{command::KeyRange, RequestUnion, WatchCancelRequest, WatchProgressRequest};
 pub use xlineapi::{Event, EventType, KeyValue, WatchResponse};
 
-use crate::error::{Result, XlineClientError};
+use crate::{
+    error::{Result, XlineClientError},
+    namespace::Namespace,
+};
 
 /// The watching handle.
 #[derive(Debug)]
@@ -16,6 +20,8 @@ pub struct Watcher {
     watch_id: i64,
     /// The channel sender
     sender: Sender<xlineapi::WatchRequest>,
+    /// The namespace of watched keys
+    namespace: Namespace,
 }
 
 impl Watcher {
@@ -23,7 +29,16 @@ impl Watcher {
     #[inline]
     #[must_use]
     pub fn new(watch_id: i64, sender: Sender<xlineapi::WatchRequest>) -> Self {
-        Self { watch_id, sender }
+        Self {
+            watch_id,
+            sender,
+            namespace: Namespace::default(),
+        }
+    }
+
+    /// Watch keys in the given namespace
+    pub(crate) fn with_namespace(self, namespace: Namespace) -> Self {
+        Self { namespace, ..self }
     }
 
     /// The ID of the watcher.
@@ -40,8 +55,10 @@ impl Watcher {
     /// If sender fails to send to channel
     #[inline]
     pub fn watch(&mut self, request: WatchRequest) -> Result<()> {
+        let mut create_request = request.into();
+        self.namespace.prefix_watch(&mut create_request);
         let request = xlineapi::WatchRequest {
-            request_union: Some(RequestUnion::CreateRequest(request.into())),
+            request_union: Some(RequestUnion::CreateRequest(create_request)),
         };
 
         self.sender
@@ -246,6 +263,8 @@ pub struct WatchStreaming {
     inner: tonic::Streaming<WatchResponse>,
     /// A sender of WatchResponse, used to keep response stream alive
     _sender: Sender<xlineapi::WatchRequest>,
+    /// The namespace of watched keys
+    namespace: Namespace,
 }
 
 impl WatchStreaming {
@@ -259,22 +278,40 @@ impl WatchStreaming {
         Self {
             inner,
             _sender: sender,
+            namespace: Namespace::default(),
         }
     }
-}
 
-impl Deref for WatchStreaming {
-    type Target = tonic::Streaming<WatchResponse>;
+    /// Watch keys in the given namespace
+    pub(crate) fn with_namespace(self, namespace: Namespace) -> Self {
+        Self { namespace, ..self }
+    }
 
+    /// Fetch the next watch response, keys of the events are returned without the namespace
+    ///
+    /// # Errors
+    ///
+    /// If the response stream returns an error
     #[inline]
-    fn deref(&self) -> &Self::Target {
-        &self.inner
+    pub async fn message(&mut self) -> std::result::Result<Option<WatchResponse>, tonic::Status> {
+        self.next().await.transpose()
     }
 }
 
-impl DerefMut for WatchStreaming {
+impl Stream for WatchStreaming {
+    type Item = std::result::Result<WatchResponse, tonic::Status>;
+
+    /// Poll the next watch response, keys of the events are returned without the namespace
     #[inline]
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.inner
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        Pin::new(&mut this.inner).poll_next(cx).map(|item| {
+            item.map(|res| {
+                res.map(|mut resp| {
+                    this.namespace.strip_watch_response(&mut resp);
+                    resp
+                })
+            })
+        })
     }
 }
@@ -1,4 +1,7 @@
 //! The following tests are originally from `etcd-client`
+use std::time::Duration;
+
+use futures::{StreamExt, TryStreamExt};
 use test_macros::abort_on_panic;
 use xline_client::{
     error::Result,
@@ -6,6 +9,7 @@ use xline_client::{
         CompactionRequest, Compare, CompareResult, DeleteRangeRequest, PutRequest, RangeRequest,
         TxnOp, TxnRequest,
     },
+    Client, ClientOptions,
 };
 
 use super::common::get_cluster_client;
@@ -95,6 +99,64 @@ async fn range_should_fetches_previously_put_keys() -> Result<()> {
     Ok(())
 }
 
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn serializable_range_should_fetch_previously_put_keys() -> Result<()> {
+    let (_cluster, client) = get_cluster_client().await.unwrap();
+    let client = client.kv_client();
+
+    client.put(PutRequest::new("serializable", "1")).await?;
+
+    // the serving member may not have applied the put yet
+    let resp = loop {
+        let resp = client
+            .range(RangeRequest::new("serializable").with_serializable(true))
+            .await?;
+        if resp.count == 1 {
+            break resp;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    };
+    assert_eq!(resp.kvs[0].key, b"serializable");
+    assert_eq!(resp.kvs[0].value, b"1");
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn range_stream_should_fetch_keys_page_by_page() -> Result<()> {
+    let (_cluster, client) = get_cluster_client().await.unwrap();
+    let client = client.kv_client();
+
+    for i in 0..10 {
+        client
+            .put(PutRequest::new(format!("page{i}"), i.to_string()))
+            .await?;
+    }
+
+    let keys: Vec<_> = client
+        .range_stream(RangeRequest::new("page").with_prefix(), 3)
+        .map_ok(|kv| kv.key)
+        .try_collect()
+        .await?;
+    let expected: Vec<_> = (0..10).map(|i| format!("page{i}").into_bytes()).collect();
+    assert_eq!(keys, expected);
+
+    let keys: Vec<_> = client
+        .range_stream(RangeRequest::new("page").with_prefix().with_limit(5), 3)
+        .map_ok(|kv| kv.key)
+        .try_collect()
+        .await?;
+    assert_eq!(keys, expected[..5]);
+
+    let mut invalid = client.range_stream(RangeRequest::new("page").with_prefix(), 0);
+    assert!(invalid.next().await.unwrap().is_err());
+    assert!(invalid.next().await.is_none());
+
+    Ok(())
+}
+
 #[tokio::test(flavor = "multi_thread")]
 #[abort_on_panic]
 async fn delete_should_remove_previously_put_kvs() -> Result<()> {
@@ -270,3 +332,43 @@ async fn compact_should_remove_previous_revision() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn namespaced_clients_should_not_see_keys_of_each_other() -> Result<()> {
+    let (cluster, client) = get_cluster_client().await.unwrap();
+    let client = client.kv_client();
+    let connect = |namespace: &'static str| {
+        Client::connect(
+            cluster.all_client_addrs(),
+            ClientOptions::default().with_namespace(namespace),
+        )
+    };
+    let client_a = connect("a/").await.unwrap().kv_client();
+    let client_b = connect("b/").await.unwrap().kv_client();
+
+    client_a.put(PutRequest::new("foo", "a")).await?;
+    client_b.put(PutRequest::new("foo", "b")).await?;
+
+    let resp = client_a.range(RangeRequest::new("foo")).await?;
+    assert_eq!(resp.kvs[0].key, b"foo");
+    assert_eq!(resp.kvs[0].value, b"a");
+    let resp = client_b
+        .range(RangeRequest::new("").with_from_key())
+        .await?;
+    assert_eq!(resp.kvs.len(), 1);
+    assert_eq!(resp.kvs[0].key, b"foo");
+    assert_eq!(resp.kvs[0].value, b"b");
+
+    let resp = client.range(RangeRequest::new("a/foo")).await?;
+    assert_eq!(resp.kvs[0].value, b"a");
+
+    let resp = client_a
+        .delete(DeleteRangeRequest::new("foo").with_prev_kv(true))
+        .await?;
+    assert_eq!(resp.prev_kvs[0].key, b"foo");
+    let resp = client.range(RangeRequest::new("b/foo")).await?;
+    assert_eq!(resp.kvs[0].value, b"b");
+
+    Ok(())
+}
@@ -2,8 +2,11 @@ use std::time::Duration;
 
 use test_macros::abort_on_panic;
 use xline_client::{
-    error::Result,
-    types::lock::{LockRequest, UnlockRequest},
+    error::{Result, XlineClientError},
+    types::{
+        kv::{PutRequest, RangeRequest},
+        lock::{LockRequest, UnlockRequest},
+    },
 };
 
 use super::common::get_cluster_client;
@@ -100,7 +103,7 @@ async fn lock_should_unlock_after_cancelled() -> Result<()> {
 
     // try lock again, it should success
     let resp = tokio::time::timeout(
-        Duration::from_secs(1),
+        Duration::from_secs(3),
         client.lock(LockRequest::new("lock-test")),
     )
     .await
@@ -110,3 +113,100 @@ async fn lock_should_unlock_after_cancelled() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn try_lock_should_fail_when_lock_is_held() -> Result<()> {
+    let (_cluster, client) = get_cluster_client().await.unwrap();
+    let kv_client = client.kv_client();
+    let lease_client = client.lease_client();
+    let client = client.lock_client();
+
+    let resp = client.try_lock(LockRequest::new("lock-test")).await?;
+    assert!(resp.key.starts_with(b"lock-test/"));
+
+    let res = client.try_lock(LockRequest::new("lock-test")).await;
+    assert!(matches!(res, Err(XlineClientError::LockError(_))));
+    // the failed attempt leaves neither its key nor its lease behind
+    let range_resp = kv_client
+        .range(RangeRequest::new("lock-test/").with_prefix())
+        .await?;
+    assert_eq!(range_resp.kvs.len(), 1);
+    assert_eq!(range_resp.kvs[0].key, resp.key);
+    assert_eq!(lease_client.leases().await?.leases.len(), 1);
+
+    client.unlock(UnlockRequest::new(resp.key)).await?;
+    let resp = client.try_lock(LockRequest::new("lock-test")).await?;
+    assert!(resp.key.starts_with(b"lock-test/"));
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn unlock_should_reject_key_not_held_by_lock() -> Result<()> {
+    let (_cluster, client) = get_cluster_client().await.unwrap();
+    let kv_client = client.kv_client();
+    let lock_client = client.lock_client();
+
+    kv_client
+        .put(PutRequest::new("lock-test/foo", "bar"))
+        .await?;
+    let res = lock_client
+        .unlock(UnlockRequest::new("lock-test/foo"))
+        .await;
+    assert!(matches!(res, Err(XlineClientError::LockError(_))));
+
+    let resp = kv_client.range(RangeRequest::new("lock-test/foo")).await?;
+    assert_eq!(resp.kvs.len(), 1);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn stale_fencing_token_should_be_rejected() -> Result<()> {
+    let (_cluster, client) = get_cluster_client().await.unwrap();
+    let client = client.lock_client();
+
+    let resp = client.lock(LockRequest::new("lock-test")).await?;
+    let token1 = client.fencing_token(resp.key.clone()).await?;
+    assert!(client.validate_fencing_token("lock-test", token1).await?);
+
+    client.unlock(UnlockRequest::new(resp.key)).await?;
+    let resp = client.lock(LockRequest::new("lock-test")).await?;
+    let token2 = client.fencing_token(resp.key).await?;
+    assert!(token2 > token1);
+    assert!(client.validate_fencing_token("lock-test", token2).await?);
+    assert!(!client.validate_fencing_token("lock-test", token1).await?);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn session_should_keep_lock_alive_until_closed() -> Result<()> {
+    let (_cluster, client) = get_cluster_client().await.unwrap();
+    let client = client.lock_client();
+
+    let session = client.session(1).await?;
+    let _resp = client
+        .lock(LockRequest::new("lock-test").with_lease(session.lease_id()))
+        .await?;
+
+    // the lock should outlive the ttl of the session
+    tokio::time::sleep(Duration::from_secs(3)).await;
+    let res = client.try_lock(LockRequest::new("lock-test")).await;
+    assert!(matches!(res, Err(XlineClientError::LockError(_))));
+
+    session.close().await?;
+    let resp = tokio::time::timeout(
+        Duration::from_secs(3),
+        client.lock(LockRequest::new("lock-test")),
+    )
+    .await
+    .expect("timeout when trying to lock")?;
+    assert!(resp.key.starts_with(b"lock-test/"));
+
+    Ok(())
+}
@@ -1,10 +1,12 @@
 //! The following tests are originally from `etcd-client`
+use futures::StreamExt;
 use xline_client::{
     error::Result,
     types::{
         kv::PutRequest,
         watch::{EventType, WatchRequest},
     },
+    Client, ClientOptions,
 };
 
 use super::common::get_cluster_client;
@@ -58,3 +60,28 @@ async fn watch_stream_should_work_after_watcher_dropped() -> Result<()> {
 
     Ok(())
 }
+
+#[tokio::test(flavor = "multi_thread")]
+async fn watch_stream_should_strip_namespace() -> Result<()> {
+    let (cluster, client) = get_cluster_client().await.unwrap();
+    let kv_client = client.kv_client();
+    let mut watch_client = Client::connect(
+        cluster.all_client_addrs(),
+        ClientOptions::default().with_namespace("ns/"),
+    )
+    .await
+    .unwrap()
+    .watch_client();
+
+    let (_, mut stream) = watch_client.watch(WatchRequest::new("watch01")).await?;
+
+    kv_client.put(PutRequest::new("ns/watch01", "01")).await?;
+
+    let resp = stream.next().await.unwrap()?;
+    assert_eq!(resp.events.len(), 1);
+    let kv = resp.events[0].kv.as_ref().unwrap();
+    assert_eq!(kv.key, b"watch01");
+    assert_eq!(kv.value, b"01");
+
+    Ok(())
+}
@@ -4,14 +4,16 @@ use futures::future::join_all;
 use rand::{distributions::Alphanumeric, thread_rng, Rng};
 use tokio::{
     net::TcpListener,
-    runtime::Handle,
+    runtime::{Handle, RuntimeFlavor},
     task::block_in_place,
     time::{self, Duration},
 };
 use tonic::transport::ClientTlsConfig;
 use utils::config::{
-    default_quota, AuthConfig, ClusterConfig, CompactConfig, EngineConfig, InitialClusterState,
-    LogConfig, MetricsConfig, StorageConfig, TlsConfig, TraceConfig, XlineServerConfig,
+    default_index_snapshot_interval, default_quota, AuditConfig, AuthConfig, ChecksumPolicy,
+    ClusterConfig, CompactConfig, DurabilityPolicy, EngineConfig, InitialClusterState, LogConfig,
+    MetricsConfig, RotationConfig, StorageConfig, TenantQuota, TlsConfig, TraceConfig,
+    XlineServerConfig,
 };
 use xline::server::XlineServer;
 use xline_client::types::auth::{
@@ -20,7 +22,15 @@ use xline_client::types::auth::{
 };
 pub use xline_client::{types, Client, ClientOptions};
 
+pub use self::test_cluster::TestCluster;
+
+/// Cluster fixture with node kill and restart helpers
+mod test_cluster;
+
 /// Cluster
+///
+/// It must be used in a multi-thread runtime, e.g. `#[tokio::test(flavor = "multi_thread")]`,
+/// since it blocks on stopping the servers when it is dropped.
 pub struct Cluster {
     /// client and peer listeners of members
     listeners: Vec<(TcpListener, TcpListener)>,
@@ -57,6 +67,11 @@ impl Cluster {
     }
 
     pub async fn new_with_configs(configs: Vec<XlineServerConfig>) -> Self {
+        assert_eq!(
+            Handle::current().runtime_flavor(),
+            RuntimeFlavor::MultiThread,
+            "Cluster must be used in a multi-thread runtime"
+        );
         let size = configs.len();
         let mut listeners = Vec::new();
         for _i in 0..size {
@@ -66,14 +81,16 @@ impl Cluster {
             ));
         }
         let server_tls_enabled = configs.iter().any(|c| c.tls().server_tls_enabled());
-        let scheme = if server_tls_enabled { "https" } else { "http" };
+        let client_scheme = if server_tls_enabled { "https" } else { "http" };
+        let peer_tls_enabled = configs.iter().any(|c| c.tls().peer_tls_enabled());
+        let peer_scheme = if peer_tls_enabled { "https" } else { "http" };
         let all_members_client_urls = listeners
             .iter()
-            .map(|l| format!("{scheme}://{}", l.0.local_addr().unwrap()))
+            .map(|l| format!("{client_scheme}://{}", l.0.local_addr().unwrap()))
             .collect();
         let all_members_peer_urls = listeners
             .iter()
-            .map(|l| format!("{scheme}://{}", l.1.local_addr().unwrap()))
+            .map(|l| format!("{peer_scheme}://{}", l.1.local_addr().unwrap()))
             .collect();
         Self {
             listeners,
@@ -117,6 +134,8 @@ impl Cluster {
                     config.tls().clone(),
                 )
                 .await
+                .unwrap()
+                .audit_log(config.audit())
                 .unwrap(),
             );
             self.servers.push(Arc::clone(&server));
@@ -150,9 +169,14 @@ impl Cluster {
         let idx = self.all_members_peer_urls.len();
         let name = format!("server{}", idx);
         let server_tls_enabled = base_config.tls().server_tls_enabled();
-        let scheme = if server_tls_enabled { "https" } else { "http" };
-        let self_client_url = format!("{scheme}://{}", xline_listener.local_addr().unwrap());
-        let self_peer_url = format!("{scheme}://{}", curp_listener.local_addr().unwrap());
+        let client_scheme = if server_tls_enabled { "https" } else { "http" };
+        let peer_tls_enabled = base_config.tls().peer_tls_enabled();
+        let peer_scheme = if peer_tls_enabled { "https" } else { "http" };
+        let self_client_url = format!(
+            "{client_scheme}://{}",
+            xline_listener.local_addr().unwrap()
+        );
+        let self_peer_url = format!("{peer_scheme}://{}", curp_listener.local_addr().unwrap());
         self.all_members_client_urls.push(self_client_url.clone());
         self.all_members_peer_urls.push(self_peer_url.clone());
 
@@ -184,6 +208,8 @@ impl Cluster {
             config.tls().clone(),
         )
         .await
+        .unwrap()
+        .audit_log(config.audit())
         .unwrap();
         let result = server
             .start_from_listener(xline_listener, curp_listener)
@@ -227,6 +253,61 @@ impl Cluster {
             .collect()
     }
 
+    /// Gracefully shutdown the server at `idx`
+    pub async fn shutdown_node(&self, idx: usize) {
+        self.servers[idx].shutdown().await;
+    }
+
+    /// The server at `idx`
+    pub(crate) fn server(&self, idx: usize) -> &Arc<XlineServer> {
+        &self.servers[idx]
+    }
+
+    /// Stop the server at `idx` without a graceful shutdown
+    pub(crate) async fn stop_node(&self, idx: usize) {
+        self.servers[idx].stop().await;
+    }
+
+    /// Restart the stopped server at `idx` on the same addresses, it rejoins the
+    /// cluster as an existing member
+    pub(crate) async fn restart_node(&mut self, idx: usize) {
+        let xline_listener = TcpListener::bind(url_addr(&self.all_members_client_urls[idx]))
+            .await
+            .unwrap();
+        let curp_listener = TcpListener::bind(url_addr(&self.all_members_peer_urls[idx]))
+            .await
+            .unwrap();
+        let config = Self::merge_config(
+            &self.configs[idx],
+            format!("server{idx}"),
+            self.get_client_url(idx),
+            self.get_peer_url(idx),
+            self.all_members_peer_urls
+                .iter()
+                .enumerate()
+                .map(|(i, addr)| (format!("server{i}"), vec![addr.clone()]))
+                .collect(),
+            false,
+            InitialClusterState::Existing,
+        );
+        let server = XlineServer::new(
+            config.cluster().clone(),
+            config.storage().clone(),
+            *config.compact(),
+            config.auth().clone(),
+            config.tls().clone(),
+        )
+        .await
+        .unwrap()
+        .audit_log(config.audit())
+        .unwrap();
+        server
+            .start_from_listener(xline_listener, curp_listener)
+            .await
+            .unwrap_or_else(|e| panic!("Server start error: {e}"));
+        self.servers[idx] = Arc::new(server);
+    }
+
     pub fn get_client_url(&self, idx: usize) -> String {
         self.all_members_client_urls[idx].clone()
     }
@@ -242,16 +323,37 @@ impl Cluster {
     pub fn default_config_with_quota_and_rocks_path(
         path: PathBuf,
         quota: u64,
+    ) -> XlineServerConfig {
+        Self::default_config_with_storage(StorageConfig::new(
+            EngineConfig::RocksDB(path),
+            quota,
+            vec![],
+            None,
+            DurabilityPolicy::default(),
+            None,
+            ChecksumPolicy::default(),
+            default_index_snapshot_interval(),
+        ))
+    }
+
+    fn default_config_with_storage(storage: StorageConfig) -> XlineServerConfig {
+        Self::default_config_with_storage_and_audit(storage, AuditConfig::default())
+    }
+
+    fn default_config_with_storage_and_audit(
+        storage: StorageConfig,
+        audit: AuditConfig,
     ) -> XlineServerConfig {
         let cluster = ClusterConfig::default();
-        let storage = StorageConfig::new(EngineConfig::RocksDB(path), quota);
         let log = LogConfig::default();
         let trace = TraceConfig::default();
         let auth = AuthConfig::default();
         let compact = CompactConfig::default();
         let tls = TlsConfig::default();
         let metrics = MetricsConfig::default();
-        XlineServerConfig::new(cluster, storage, log, trace, auth, compact, tls, metrics)
+        XlineServerConfig::new(
+            cluster, storage, log, trace, auth, compact, tls, metrics, audit,
+        )
     }
 
     pub fn default_rocks_config_with_path(path: PathBuf) -> XlineServerConfig {
@@ -268,6 +370,37 @@ impl Cluster {
         Self::default_config_with_quota_and_rocks_path(path, quota)
     }
 
+    pub fn default_tenant_quota_config(tenant_quotas: Vec<TenantQuota>) -> XlineServerConfig {
+        let path = temp_dir().join(random_id());
+        Self::default_config_with_storage(StorageConfig::new(
+            EngineConfig::RocksDB(path),
+            default_quota(),
+            tenant_quotas,
+            None,
+            DurabilityPolicy::default(),
+            None,
+            ChecksumPolicy::default(),
+            default_index_snapshot_interval(),
+        ))
+    }
+
+    pub fn default_audit_config(audit_path: PathBuf) -> XlineServerConfig {
+        let path = temp_dir().join(random_id());
+        Self::default_config_with_storage_and_audit(
+            StorageConfig::new(
+                EngineConfig::RocksDB(path),
+                default_quota(),
+                vec![],
+                None,
+                DurabilityPolicy::default(),
+                None,
+                ChecksumPolicy::default(),
+                default_index_snapshot_interval(),
+            ),
+            AuditConfig::new(true, Some(audit_path), RotationConfig::Never),
+        )
+    }
+
     fn merge_config(
         base_config: &XlineServerConfig,
         name: String,
@@ -290,6 +423,15 @@ impl Cluster {
             *old_cluster.client_config(),
             *old_cluster.server_timeout(),
             initial_cluster_state,
+            old_cluster.discovery_srv().clone(),
+            *old_cluster.enable_grpc_reflection(),
+            old_cluster.client_http_listen_urls().clone(),
+            old_cluster.grpc_web().clone(),
+            *old_cluster.max_txn_ops(),
+            *old_cluster.max_request_bytes(),
+            *old_cluster.grpc_compression(),
+            *old_cluster.grpc(),
+            old_cluster.qos_classes().clone(),
         );
         XlineServerConfig::new(
             new_cluster,
@@ -300,6 +442,7 @@ impl Cluster {
             *base_config.compact(),
             base_config.tls().clone(),
             base_config.metrics().clone(),
+            base_config.audit().clone(),
         )
     }
 }
@@ -331,6 +474,11 @@ impl Drop for Cluster {
     }
 }
 
+/// The address of a url without the scheme
+fn url_addr(url: &str) -> &str {
+    url.split_once("://").map_or(url, |(_, addr)| addr)
+}
+
 fn random_id() -> String {
     thread_rng()
         .sample_iter(&Alphanumeric)
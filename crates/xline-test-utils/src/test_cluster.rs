@@ -0,0 +1,124 @@
+use std::{collections::HashMap, sync::Arc};
+
+use tokio::time::{self, Duration};
+use xline::server::XlineServer;
+use xline_client::{Client, ClientOptions};
+
+use crate::Cluster;
+
+/// How long to wait for a leader to be elected
+const LEADER_TIMEOUT: Duration = Duration::from_secs(10);
+/// Interval between checks of the leader
+const LEADER_CHECK_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A `Cluster` of in-process nodes with memory storage on random ports, which waits for
+/// a leader on start and can kill and restart its nodes
+///
+/// Like `Cluster`, it must be used in a multi-thread runtime.
+pub struct TestCluster {
+    /// The underlying cluster
+    cluster: Cluster,
+    /// Whether the node at each index is running
+    running: Vec<bool>,
+}
+
+impl TestCluster {
+    /// Start a cluster of `size` nodes and wait for a leader
+    pub async fn new(size: usize) -> Self {
+        let mut cluster = Cluster::new(size).await;
+        cluster.start().await;
+        let cluster = Self {
+            cluster,
+            running: vec![true; size],
+        };
+        let _leader = cluster.wait_leader().await;
+        cluster
+    }
+
+    /// Number of nodes, including the killed ones
+    pub fn size(&self) -> usize {
+        self.running.len()
+    }
+
+    /// Client urls of all nodes
+    pub fn client_urls(&self) -> Vec<String> {
+        self.cluster.all_client_addrs()
+    }
+
+    /// Client url of the node at `idx`
+    pub fn client_url(&self, idx: usize) -> String {
+        self.cluster.get_client_url(idx)
+    }
+
+    /// Connect a client to all nodes
+    pub async fn client(&self) -> Client {
+        Client::connect(self.client_urls(), ClientOptions::default())
+            .await
+            .unwrap_or_else(|e| panic!("Client connect error: {e:?}"))
+    }
+
+    /// Connect a client to the node at `idx` only
+    pub async fn node_client(&self, idx: usize) -> Client {
+        Client::connect([self.client_url(idx)], ClientOptions::default())
+            .await
+            .unwrap_or_else(|e| panic!("Client connect error: {e:?}"))
+    }
+
+    /// The server of the node at `idx`, `None` if it is killed
+    pub fn server(&self, idx: usize) -> Option<&Arc<XlineServer>> {
+        self.running[idx].then(|| self.cluster.server(idx))
+    }
+
+    /// Wait until all running nodes agree on a leader, return the index of the leader
+    ///
+    /// # Panics
+    ///
+    /// Panic if no leader is elected in `LEADER_TIMEOUT`
+    pub async fn wait_leader(&self) -> usize {
+        time::timeout(LEADER_TIMEOUT, async {
+            loop {
+                if let Some(leader) = self.try_get_leader().await {
+                    return leader;
+                }
+                time::sleep(LEADER_CHECK_INTERVAL).await;
+            }
+        })
+        .await
+        .unwrap_or_else(|_e| panic!("no leader is elected in {LEADER_TIMEOUT:?}"))
+    }
+
+    /// Index of the leader if all running nodes agree on it
+    async fn try_get_leader(&self) -> Option<usize> {
+        let mut member_ids = HashMap::new();
+        let mut leader_id = None;
+        for idx in (0..self.size()).filter(|&idx| self.running[idx]) {
+            let client = Client::connect([self.client_url(idx)], ClientOptions::default())
+                .await
+                .ok()?;
+            let status = client.maintenance_client().status().await.ok()?;
+            let _prev = member_ids.insert(status.header?.member_id, idx);
+            if status.leader == 0 || leader_id.is_some_and(|id| id != status.leader) {
+                return None;
+            }
+            leader_id = Some(status.leader);
+        }
+        member_ids.get(&leader_id?).copied()
+    }
+
+    /// Kill the node at `idx` without a graceful shutdown, its data is lost as the
+    /// storage is in memory
+    pub async fn kill(&mut self, idx: usize) {
+        if self.running[idx] {
+            self.cluster.stop_node(idx).await;
+            self.running[idx] = false;
+        }
+    }
+
+    /// Restart the node at `idx` on the same addresses, it is killed first if it's
+    /// running, and then rejoins the cluster and catches up with the leader
+    pub async fn restart(&mut self, idx: usize) {
+        self.kill(idx).await;
+        self.cluster.restart_node(idx).await;
+        self.running[idx] = true;
+    }
+}
@@ -0,0 +1,26 @@
+use std::{env, process::Command};
+
+/// Output of a command, `None` if it failed
+fn output(program: &str, args: &[&str]) -> Option<String> {
+    let output = Command::new(program).args(args).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let output = String::from_utf8(output.stdout).ok()?;
+    Some(output.trim().to_owned()).filter(|s| !s.is_empty())
+}
+
+fn main() {
+    // the build info can be given by the environment when the source is not a git
+    // repository, like building from a source tarball
+    let git_sha = env::var("XLINE_GIT_SHA")
+        .ok()
+        .or_else(|| output("git", &["rev-parse", "--short=12", "HEAD"]))
+        .unwrap_or_else(|| "unknown".to_owned());
+    let build_date = env::var("XLINE_BUILD_DATE")
+        .ok()
+        .or_else(|| output("date", &["-u", "+%Y-%m-%dT%H:%M:%SZ"]))
+        .unwrap_or_else(|| "unknown".to_owned());
+    println!("cargo:rustc-env=XLINE_GIT_SHA={git_sha}");
+    println!("cargo:rustc-env=XLINE_BUILD_DATE={build_date}");
+}
@@ -58,6 +58,7 @@ fn is_exclusive_cmd(cmd: &Command) -> bool {
             | RequestWrapper::AuthUserGrantRoleRequest(_)
             | RequestWrapper::AuthUserRevokeRoleRequest(_)
             | RequestWrapper::AuthenticateRequest(_)
+            | RequestWrapper::InternalAuthenticateRequest(_)
             | RequestWrapper::AlarmRequest(_)
     )
 }
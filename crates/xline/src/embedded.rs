@@ -0,0 +1,172 @@
+use std::{collections::HashMap, time::Duration};
+
+use anyhow::{anyhow, Result};
+use tokio::net::TcpListener;
+use tracing::info;
+use utils::config::{ClusterConfig, InitialClusterState, XlineServerConfig};
+use xline_client::{Client, ClientOptions};
+
+use crate::server::XlineServer;
+
+/// Name of the only member of an embedded server
+const EMBEDDED_NAME: &str = "embedded";
+/// How long to wait for an embedded server to elect itself as the leader
+const READY_TIMEOUT: Duration = Duration::from_secs(10);
+/// Interval between checks of whether an embedded server is ready
+const READY_CHECK_INTERVAL: Duration = Duration::from_millis(10);
+
+/// A single member Xline cluster running in the current process, for the applications
+/// that need an embedded coordination service and for integration tests. The server
+/// keeps running until it is shut down by `shutdown`.
+#[derive(Debug)]
+pub struct EmbeddedXline {
+    /// The server
+    server: XlineServer,
+    /// Client connected to the server
+    client: Client,
+    /// Url the client-facing endpoint is bound to
+    client_url: String,
+}
+
+impl EmbeddedXline {
+    /// Start an embedded server with in-memory storage on an ephemeral port of the
+    /// loopback interface
+    ///
+    /// # Errors
+    ///
+    /// Return error if the server fails to start or doesn't become ready in time
+    #[inline]
+    pub async fn start() -> Result<Self> {
+        Self::start_with_config(XlineServerConfig::default(), ClientOptions::default()).await
+    }
+
+    /// Start an embedded server with the given config, the membership and listen urls
+    /// in the cluster config are replaced by a single member bound to ephemeral ports of
+    /// the loopback interface, the client is connected with the given options
+    ///
+    /// # Errors
+    ///
+    /// Return error if the server fails to start or doesn't become ready in time
+    #[inline]
+    pub async fn start_with_config(
+        config: XlineServerConfig,
+        client_options: ClientOptions,
+    ) -> Result<Self> {
+        let xline_listener = TcpListener::bind("127.0.0.1:0").await?;
+        let curp_listener = TcpListener::bind("127.0.0.1:0").await?;
+        let client_scheme = if config.tls().server_tls_enabled() {
+            "https"
+        } else {
+            "http"
+        };
+        let peer_scheme = if config.tls().peer_tls_enabled() {
+            "https"
+        } else {
+            "http"
+        };
+        let client_url = format!("{client_scheme}://{}", xline_listener.local_addr()?);
+        let peer_url = format!("{peer_scheme}://{}", curp_listener.local_addr()?);
+        let cluster_config = single_member_cluster(config.cluster(), &client_url, &peer_url);
+        let server = XlineServer::new(
+            cluster_config,
+            config.storage().clone(),
+            *config.compact(),
+            config.auth().clone(),
+            config.tls().clone(),
+        )
+        .await?
+        .audit_log(config.audit())?;
+        server
+            .start_from_listener(xline_listener, curp_listener)
+            .await?;
+        let client = match Client::connect([client_url.as_str()], client_options).await {
+            Ok(client) => client,
+            Err(e) => {
+                server.stop().await;
+                return Err(e.into());
+            }
+        };
+        let embedded = Self {
+            server,
+            client,
+            client_url,
+        };
+        if let Err(e) = embedded.wait_ready().await {
+            embedded.server.stop().await;
+            return Err(e);
+        }
+        info!("embedded xline is ready at {}", embedded.client_url);
+        Ok(embedded)
+    }
+
+    /// Wait for the server to elect itself as the leader
+    async fn wait_ready(&self) -> Result<()> {
+        let mut maintenance_client = self.client.maintenance_client();
+        tokio::time::timeout(READY_TIMEOUT, async {
+            loop {
+                if let Ok(status) = maintenance_client.status().await {
+                    if status.leader != 0 {
+                        return;
+                    }
+                }
+                tokio::time::sleep(READY_CHECK_INTERVAL).await;
+            }
+        })
+        .await
+        .map_err(|_e| anyhow!("embedded xline is not ready in {READY_TIMEOUT:?}"))
+    }
+
+    /// Client connected to the server
+    #[inline]
+    #[must_use]
+    pub fn client(&self) -> &Client {
+        &self.client
+    }
+
+    /// Url of the client-facing endpoint, for the clients in other processes
+    #[inline]
+    #[must_use]
+    pub fn client_url(&self) -> &str {
+        &self.client_url
+    }
+
+    /// The server, for the operations not covered by the client
+    #[inline]
+    #[must_use]
+    pub fn server(&self) -> &XlineServer {
+        &self.server
+    }
+
+    /// Gracefully shutdown the server
+    #[inline]
+    pub async fn shutdown(self) {
+        self.server.shutdown().await;
+    }
+}
+
+/// Replace the membership and the listen urls of a cluster config by a single member
+/// listening on the given urls
+fn single_member_cluster(base: &ClusterConfig, client_url: &str, peer_url: &str) -> ClusterConfig {
+    ClusterConfig::new(
+        EMBEDDED_NAME.to_owned(),
+        vec![peer_url.to_owned()],
+        vec![peer_url.to_owned()],
+        vec![client_url.to_owned()],
+        vec![client_url.to_owned()],
+        HashMap::from([(EMBEDDED_NAME.to_owned(), vec![peer_url.to_owned()])]),
+        true,
+        base.curp_config().clone(),
+        *base.client_config(),
+        *base.server_timeout(),
+        InitialClusterState::New,
+        None,
+        *base.enable_grpc_reflection(),
+        base.client_http_listen_urls().clone(),
+        base.grpc_web().clone(),
+        *base.max_txn_ops(),
+        *base.max_request_bytes(),
+        *base.grpc_compression(),
+        *base.grpc(),
+        base.qos_classes().clone(),
+    )
+}
@@ -2,6 +2,8 @@ use std::sync::Arc;
 
 use curp::members::ServerId;
 use parking_lot::Mutex;
+use tokio::sync::broadcast::{self, error::RecvError};
+use utils::task_manager::Listener;
 
 use crate::{revision_number::RevisionNumberGenerator, rpc::ResponseHeader};
 
@@ -53,7 +55,6 @@ impl HeaderGenerator {
     }
 
     /// Set term
-    #[allow(dead_code)] // Will be used in the future
     pub(crate) fn set_term(&self, term: u64) {
         *self.term.lock() = term;
     }
@@ -78,3 +79,64 @@ impl HeaderGenerator {
         Arc::clone(&self.auth_revision)
     }
 }
+
+/// Keep the term in the headers generated by `header_gen` up to date with the term of
+/// curp, every change of the term is announced by a change of the leader, so that the
+/// clients can detect the leader changes from the response headers
+pub(crate) async fn sync_term_task(
+    header_gen: Arc<HeaderGenerator>,
+    term: impl Fn() -> u64 + Send,
+    mut leader_rx: broadcast::Receiver<Option<ServerId>>,
+    shutdown_listener: Listener,
+) {
+    header_gen.set_term(term());
+    loop {
+        tokio::select! {
+            res = leader_rx.recv() => {
+                // The current term is read after all the lagged changes, nothing is missed
+                if matches!(res, Err(RecvError::Closed)) {
+                    return;
+                }
+            }
+            _ = shutdown_listener.wait() => return,
+        }
+        header_gen.set_term(term());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    use utils::task_manager::{tasks::TaskName, TaskManager};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn header_term_should_follow_leader_changes() {
+        let header_gen = Arc::new(HeaderGenerator::new(1, 2));
+        let term = Arc::new(AtomicU64::new(3));
+        let (leader_tx, leader_rx) = broadcast::channel(1);
+        let task_manager = TaskManager::new();
+        let term_c = Arc::clone(&term);
+        task_manager.spawn(TaskName::SyncTerm, |n| {
+            sync_term_task(
+                Arc::clone(&header_gen),
+                move || term_c.load(Ordering::Relaxed),
+                leader_rx,
+                n,
+            )
+        });
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        assert_eq!(header_gen.gen_header().raft_term, 3);
+
+        term.store(4, Ordering::Relaxed);
+        let _ignore = leader_tx.send(Some(5));
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        let header = header_gen.gen_header();
+        assert_eq!(header.raft_term, 4);
+        assert_eq!(header.cluster_id, 1);
+        assert_eq!(header.member_id, 2);
+        task_manager.shutdown(true).await;
+    }
+}
@@ -176,8 +176,14 @@ mod rpc {
 }
 /// Command conflict implementation
 mod conflict;
+/// Xline server running in the current process, enabled by the `embedded` feature
+#[cfg(all(feature = "embedded", not(madsim)))]
+pub mod embedded;
 /// Xline metrics
 pub mod metrics;
+/// Migration from etcd, enabled by the `migrate` feature
+#[cfg(feature = "migrate")]
+pub mod migrate;
 /// restore module, only for test
 pub mod restore;
 /// Revision check
@@ -145,22 +145,28 @@
 use anyhow::Result;
 use opentelemetry::{global, metrics::noop::NoopMeterProvider};
 use opentelemetry_sdk::propagation::TraceContextPropagator;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
+use utils::config::{EngineConfig, InitialClusterState, StorageConfig};
 use xline::{
+    restore::restore_latest_backup,
     server::XlineServer,
-    utils::{init_metrics, init_subscriber, parse_config},
+    utils::{init_metrics, init_subscriber, parse_config, LogLevelHandle},
 };
 
 #[tokio::main]
 #[allow(clippy::arithmetic_side_effects, clippy::ignored_unit_patterns)] // Introduced by tokio::select!
 async fn main() -> Result<()> {
+    // configure the failpoints from the `FAILPOINTS` environment variable
+    #[cfg(feature = "failpoints")]
+    let _scenario = fail::FailScenario::setup();
     global::set_text_map_propagator(TraceContextPropagator::new());
     let config = parse_config().await?;
 
     let cluster_config = config.cluster();
 
-    let _guard = init_subscriber(cluster_config.name(), config.log(), config.trace())?;
+    let (_guard, log_level) = init_subscriber(cluster_config.name(), config.log(), config.trace())?;
     init_metrics(config.metrics())?;
+    restore_on_bootstrap(config.storage(), *cluster_config.initial_cluster_state()).await?;
 
     let server = XlineServer::new(
         cluster_config.clone(),
@@ -169,17 +175,19 @@ async fn main() -> Result<()> {
         config.auth().clone(),
         config.tls().clone(),
     )
-    .await?;
+    .await?
+    .log_level_handle(log_level.clone())
+    .audit_log(config.audit())?;
     debug!("{:?}", server);
     server.start().await?;
 
-    let _ig = tokio::signal::ctrl_c().await;
+    wait_for_shutdown_signal(&server, &log_level).await?;
 
     tokio::select! {
         _ = tokio::signal::ctrl_c() => {
             info!("received ctrl-c again, force exit");
         }
-        _ = server.stop() => {
+        _ = server.shutdown() => {
             info!("server exited");
         }
     }
@@ -191,3 +199,80 @@ async fn main() -> Result<()> {
     global::set_meter_provider(NoopMeterProvider::new());
     Ok(())
 }
+
+/// Wait for ctrl-c or SIGTERM, the latter is sent by Kubernetes when a pod is terminated.
+/// The config is reloaded on SIGHUP meanwhile.
+#[allow(clippy::arithmetic_side_effects, clippy::ignored_unit_patterns)] // Introduced by tokio::select!
+async fn wait_for_shutdown_signal(server: &XlineServer, log_level: &LogLevelHandle) -> Result<()> {
+    let mut sigterm = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?;
+    let mut sighup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())?;
+    loop {
+        tokio::select! {
+            res = tokio::signal::ctrl_c() => return Ok(res?),
+            _ = sigterm.recv() => {
+                info!("received SIGTERM");
+                return Ok(());
+            }
+            _ = sighup.recv() => {
+                info!("received SIGHUP, reloading config");
+                if let Err(e) = reload_config(server, log_level).await {
+                    warn!("failed to reload config: {e:?}");
+                }
+            }
+        }
+    }
+}
+
+/// Reload the parts of the config that can be changed at runtime: the log level
+/// and the auto-compaction parameters
+async fn reload_config(server: &XlineServer, log_level: &LogLevelHandle) -> Result<()> {
+    let config = parse_config().await?;
+    // the log level and the compaction config are independent, a failure of one
+    // doesn't stop the other from being reloaded
+    match log_level.reload(*config.log().level()) {
+        Ok(()) => info!("log level reloaded: {}", config.log().level()),
+        Err(e) => warn!("failed to reload log level: {e:?}"),
+    }
+    if config.compact().auto_compact_config().is_some() {
+        server.reload_compact_config(config.compact())?;
+    }
+    Ok(())
+}
+
+/// Restore the latest backup uploaded to the object storage if the member bootstraps a new
+/// cluster with an empty data directory and `restore_on_bootstrap` is enabled.
+///
+/// A restored backup doesn't keep the applied index, so it is only consistent with a log
+/// that starts from the beginning. A member joining an existing cluster would apply the
+/// log of the cluster again on top of the backup, so it is never restored, and receives
+/// a snapshot from the leader instead.
+async fn restore_on_bootstrap(
+    config: &StorageConfig,
+    initial_cluster_state: InitialClusterState,
+) -> Result<()> {
+    let Some(upload) = config
+        .backup
+        .as_ref()
+        .and_then(|backup| backup.upload().as_ref())
+        .filter(|upload| *upload.restore_on_bootstrap())
+    else {
+        return Ok(());
+    };
+    let EngineConfig::RocksDB(ref data_dir) = config.engine else {
+        warn!("only the rocksdb engine can be restored from backups, skip restoring");
+        return Ok(());
+    };
+    if data_dir.exists() {
+        return Ok(());
+    }
+    if initial_cluster_state == InitialClusterState::Existing {
+        warn!("a member joining an existing cluster is not restored from backups");
+        return Ok(());
+    }
+    if restore_latest_backup(upload, data_dir).await? {
+        info!("the latest backup is restored to {data_dir:?}");
+    } else {
+        info!("no backup is found in the object storage, start with an empty data dir");
+    }
+    Ok(())
+}
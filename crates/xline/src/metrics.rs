@@ -1,10 +1,14 @@
+use std::sync::Arc;
+
 use clippy_utilities::NumericCast;
 use opentelemetry::{
     metrics::{Counter, MetricsError},
     KeyValue,
 };
 use tracing::error;
-use utils::define_metrics;
+use utils::{define_metrics, table_names::XLINE_TABLES};
+
+use crate::storage::{storage_api::StorageApi, tenants::Tenants};
 
 define_metrics! {
     "xline",
@@ -19,14 +23,33 @@ define_metrics! {
     lease_expired_total: Counter<u64> = meter()
         .u64_counter("lease_expired")
         .with_description("The total number of expired leases.")
+        .init(),
+    recovered_revisions_total: Counter<u64> = meter()
+        .u64_counter("recovered_revisions")
+        .with_description("The total number of kv revisions restored into the index during the recovery.")
+        .init(),
+    shed_requests_total: Counter<u64> = meter()
+        .u64_counter("shed_requests")
+        .with_description("The total number of requests rejected because the server is overloaded.")
         .init()
 }
 
 impl Metrics {
     /// Register metrics
-    pub(super) fn register_callback() -> Result<(), MetricsError> {
+    pub(super) fn register_callback<S: StorageApi>(
+        tenants: Arc<Tenants>,
+        persistent: Arc<S>,
+    ) -> Result<(), MetricsError> {
         let meter = meter();
-        let (fd_used, fd_limit, current_version, current_rust_version) = (
+        let (
+            fd_used,
+            fd_limit,
+            current_version,
+            current_rust_version,
+            tenant_keys,
+            tenant_bytes,
+            table_bytes,
+        ) = (
             meter
                 .u64_observable_gauge("fd_used")
                 .with_description("The number of used file descriptors.")
@@ -43,6 +66,18 @@ impl Metrics {
                 .u64_observable_gauge("current_rust_version")
                 .with_description("Which Rust version server is running with. 1 for 'server_rust_version' label with current version.")
                 .init(),
+            meter
+                .u64_observable_gauge("tenant_keys")
+                .with_description("The number of keys of each tenant.")
+                .init(),
+            meter
+                .u64_observable_gauge("tenant_bytes")
+                .with_description("The bytes of keys and values of each tenant.")
+                .init(),
+            meter
+                .u64_observable_gauge("table_bytes")
+                .with_description("The estimated size of each table in the storage, e.g. kv, lease and auth.")
+                .init(),
         );
 
         _ = meter.register_callback(&[fd_used.as_any(), fd_limit.as_any()], move |observer| {
@@ -90,6 +125,28 @@ impl Metrics {
             },
         )?;
 
+        _ = meter.register_callback(
+            &[tenant_keys.as_any(), tenant_bytes.as_any()],
+            move |observer| {
+                for usage in tenants.usages() {
+                    let labels = [KeyValue::new("tenant", usage.quota.prefix)];
+                    observer.observe_u64(&tenant_keys, usage.keys, &labels);
+                    observer.observe_u64(&tenant_bytes, usage.bytes, &labels);
+                }
+            },
+        )?;
+
+        _ = meter.register_callback(&[table_bytes.as_any()], move |observer| {
+            for table in XLINE_TABLES {
+                match persistent.table_size(table) {
+                    Ok(size) => {
+                        observer.observe_u64(&table_bytes, size, &[KeyValue::new("table", table)]);
+                    }
+                    Err(err) => error!("{err}"),
+                }
+            }
+        })?;
+
         Ok(())
     }
 }
@@ -0,0 +1,249 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+};
+
+use anyhow::{bail, Result};
+use clippy_utilities::OverflowArithmetic;
+use etcd_client::{Client, ConnectOptions, GetOptions};
+use tracing::{info, warn};
+use utils::config::EngineConfig;
+
+use crate::{
+    rpc::{KeyValue, PbLease, Permission, Role, User, UserAddOptions},
+    storage::{
+        db::{WriteOp, DB},
+        schema::CURRENT_SCHEMA_VERSION,
+        storage_api::StorageApi,
+        Revision,
+    },
+};
+
+/// Number of keys fetched from etcd in one range request
+const MIGRATE_PAGE_SIZE: i64 = 1000;
+
+/// Summary of a migration
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct MigrateSummary {
+    /// The etcd revision that the keys are read at
+    pub revision: i64,
+    /// Number of the migrated keys
+    pub keys: usize,
+    /// Number of the migrated leases
+    pub leases: usize,
+    /// Number of the migrated users
+    pub users: usize,
+    /// Number of the migrated roles
+    pub roles: usize,
+    /// Whether the auth is enabled in etcd
+    pub auth_enabled: bool,
+}
+
+/// Migrate the keys, leases, users and roles of a live etcd cluster into a new data dir,
+/// the `user` is the name and password to authenticate to etcd if the auth is enabled.
+///
+/// The latest versions of the keys are read at a single revision and keep their
+/// revisions, so the history before that revision is not available and it is recorded as
+/// the compacted revision. Leases keep their ids and granted TTLs, and restart their TTLs
+/// when the new cluster starts, the keys of the leases that expire during the migration
+/// are not migrated, as etcd deletes them. The password hashes of the users can not be
+/// read from etcd, so the users are migrated as users without passwords, which can't be
+/// authenticated by passwords, and the auth is left disabled. Add the users that need
+/// passwords again with their passwords before enabling the auth.
+///
+/// To migrate a bbolt snapshot, restore it to a temporary etcd member with
+/// `etcdutl snapshot restore` and migrate from that member.
+/// # Errors
+/// return an error if the data dir already exists, etcd can not be read or the data dir
+/// can not be written
+#[inline]
+pub async fn migrate_from_etcd<D: Into<PathBuf>>(
+    endpoints: &[String],
+    user: Option<(String, String)>,
+    data_dir: D,
+) -> Result<MigrateSummary> {
+    let data_dir = data_dir.into();
+    if data_dir.exists() {
+        bail!("data dir {data_dir:?} already exists");
+    }
+    let options = user.map(|(name, password)| ConnectOptions::new().with_user(name, password));
+    let mut client = Client::connect(endpoints, options).await?;
+    let db = DB::open(&EngineConfig::RocksDB(data_dir))?;
+    let mut summary = MigrateSummary::default();
+
+    let leased_keys = migrate_kvs(&mut client, db.as_ref(), &mut summary).await?;
+    let leases = migrate_leases(&mut client, db.as_ref(), &mut summary).await?;
+    drop_expired_keys(db.as_ref(), leased_keys, &leases, &mut summary)?;
+    migrate_auth(&mut client, db.as_ref(), &mut summary).await?;
+    _ = db.write_batch(
+        vec![WriteOp::PutSchemaVersion(CURRENT_SCHEMA_VERSION)],
+        false,
+    )?;
+    if summary.auth_enabled {
+        warn!("auth is enabled in etcd, add the users again with passwords before enabling auth");
+    }
+    db.sync()?;
+    info!("migrated from etcd: {summary:?}");
+    Ok(summary)
+}
+
+/// Migrate the latest versions of all keys at the current revision of etcd, return the
+/// encoded revisions of the keys attached to each lease
+async fn migrate_kvs<S: StorageApi>(
+    client: &mut Client,
+    db: &S,
+    summary: &mut MigrateSummary,
+) -> Result<HashMap<i64, Vec<Vec<u8>>>> {
+    let mut start = vec![0];
+    let mut leased_keys: HashMap<i64, Vec<Vec<u8>>> = HashMap::new();
+    let mut revision = 0;
+    // keys modified in the same revision are distinguished by sub revisions
+    let mut sub_revisions: HashMap<i64, i64> = HashMap::new();
+    loop {
+        let mut options = GetOptions::new()
+            .with_from_key()
+            .with_limit(MIGRATE_PAGE_SIZE);
+        if revision != 0 {
+            options = options.with_revision(revision);
+        }
+        let resp = client.get(start.clone(), Some(options)).await?;
+        if revision == 0 {
+            revision = resp.header().map_or(0, |header| header.revision());
+        }
+        let mut ops = Vec::with_capacity(resp.kvs().len());
+        for kv in resp.kvs() {
+            let next = sub_revisions.entry(kv.mod_revision()).or_insert(0);
+            let sub_revision = *next;
+            *next = next.overflow_add(1);
+            let rev = Revision::new(kv.mod_revision(), sub_revision);
+            if kv.lease() != 0 {
+                leased_keys
+                    .entry(kv.lease())
+                    .or_default()
+                    .push(rev.encode_to_vec());
+            }
+            ops.push(WriteOp::PutKeyValue(
+                rev,
+                KeyValue {
+                    key: kv.key().to_vec(),
+                    value: kv.value().to_vec(),
+                    create_revision: kv.create_revision(),
+                    mod_revision: kv.mod_revision(),
+                    version: kv.version(),
+                    lease: kv.lease(),
+                },
+            ));
+        }
+        summary.keys = summary.keys.overflow_add(ops.len());
+        _ = db.write_batch(ops, false)?;
+        match resp.kvs().last() {
+            Some(last) if resp.more() => {
+                start = last.key().to_vec();
+                start.push(0);
+            }
+            _ => break,
+        }
+    }
+    summary.revision = revision;
+    // the history before the revision is not migrated, including the deletions after the
+    // latest modification of the migrated keys
+    _ = db.write_batch(vec![WriteOp::PutFinishedCompactRevision(revision)], false)?;
+    Ok(leased_keys)
+}
+
+/// Migrate the leases with their granted TTLs, return the ids of the migrated leases
+async fn migrate_leases<S: StorageApi>(
+    client: &mut Client,
+    db: &S,
+    summary: &mut MigrateSummary,
+) -> Result<HashSet<i64>> {
+    let leases = client.leases().await?;
+    let mut ops = Vec::with_capacity(leases.leases().len());
+    let mut ids = HashSet::with_capacity(leases.leases().len());
+    for status in leases.leases() {
+        let resp = client.lease_time_to_live(status.id(), None).await?;
+        // the lease expires during the migration
+        if resp.ttl() < 0 {
+            continue;
+        }
+        let _ignore = ids.insert(status.id());
+        ops.push(WriteOp::PutLease(PbLease {
+            id: status.id(),
+            ttl: resp.granted_ttl(),
+            remaining_ttl: resp.granted_ttl(),
+        }));
+    }
+    summary.leases = ops.len();
+    _ = db.write_batch(ops, false)?;
+    Ok(ids)
+}
+
+/// Drop the keys of the leases that expire or are revoked after the keys are read, etcd
+/// deletes them along with their leases
+fn drop_expired_keys<S: StorageApi>(
+    db: &S,
+    leased_keys: HashMap<i64, Vec<Vec<u8>>>,
+    leases: &HashSet<i64>,
+    summary: &mut MigrateSummary,
+) -> Result<()> {
+    let expired: Vec<_> = leased_keys
+        .into_iter()
+        .filter(|&(id, _)| !leases.contains(&id))
+        .flat_map(|(_, revisions)| revisions)
+        .collect();
+    summary.keys = summary.keys.overflow_sub(expired.len());
+    let ops = expired
+        .iter()
+        .map(|rev| WriteOp::DeleteKeyValue(rev.as_slice()))
+        .collect();
+    _ = db.write_batch(ops, false)?;
+    Ok(())
+}
+
+/// Migrate the users and roles, the auth is left disabled
+async fn migrate_auth<S: StorageApi>(
+    client: &mut Client,
+    db: &S,
+    summary: &mut MigrateSummary,
+) -> Result<()> {
+    let status = client.auth_status().await?;
+    summary.auth_enabled = status.enabled();
+    let mut ops = vec![WriteOp::PutAuthRevision(status.auth_revision().try_into()?)];
+
+    let roles = client.role_list().await?;
+    for name in roles.roles() {
+        let resp = client.role_get(name.as_str()).await?;
+        let key_permission = resp
+            .permissions()
+            .iter()
+            .map(|perm| Permission {
+                perm_type: perm.get_type(),
+                key: perm.key().to_vec(),
+                range_end: perm.range_end().to_vec(),
+            })
+            .collect();
+        ops.push(WriteOp::PutRole(Role {
+            name: name.as_bytes().to_vec(),
+            key_permission,
+        }));
+        summary.roles = summary.roles.overflow_add(1);
+    }
+
+    let users = client.user_list().await?;
+    for name in users.users() {
+        let resp = client.user_get(name.as_str()).await?;
+        let mut roles = resp.roles().to_vec();
+        // roles of a user are searched by binary search
+        roles.sort_unstable();
+        ops.push(WriteOp::PutUser(User {
+            name: name.as_bytes().to_vec(),
+            password: Vec::new(),
+            roles,
+            options: Some(UserAddOptions { no_password: true }),
+        }));
+        summary.users = summary.users.overflow_add(1);
+    }
+    _ = db.write_batch(ops, false)?;
+    Ok(())
+}
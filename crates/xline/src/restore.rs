@@ -1,24 +1,54 @@
-use std::path::{Path, PathBuf};
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+};
 
-use anyhow::Result;
+use anyhow::{anyhow, bail, Result};
 use bytes::BytesMut;
-use clippy_utilities::NumericCast;
-use engine::{Engine, EngineType, Snapshot, SnapshotApi, StorageEngine};
+use clippy_utilities::{NumericCast, OverflowArithmetic};
+use engine::{Engine, EngineType, Snapshot, SnapshotApi, StorageEngine, WriteOperation};
+use prost::Message;
+use sha2::{Digest, Sha256};
+use tokio::io::AsyncReadExt;
 use tokio_util::io::read_buf;
-use utils::table_names::XLINE_TABLES;
+use tracing::info;
+use utils::{
+    config::{EncryptionConfig, EngineConfig, ObjectStorageConfig},
+    table_names::{KV_TABLE, LEASE_TABLE, META_TABLE, XLINE_TABLES},
+};
 
-use crate::server::MAINTENANCE_SNAPSHOT_CHUNK_SIZE;
+use crate::{
+    rpc::{KeyValue, PbLease},
+    server::{
+        backup::BACKUP_PREFIX,
+        command::{APPLIED_INDEX_KEY, APPLIED_REVISION_KEY, OUT_OF_ORDER_APPLIED_KEY},
+        MAINTENANCE_SNAPSHOT_CHUNK_SIZE,
+    },
+    storage::{
+        db::{WriteOp, DB, FINISHED_COMPACT_REVISION, SCHEDULED_COMPACT_REVISION},
+        index_snapshot::INDEX_SNAPSHOT_KEY,
+        object_storage::BackupBucket,
+        range_tombstone::{RangeTombstone, RANGE_TOMBSTONE_PREFIX},
+        storage_api::StorageApi,
+        Revision,
+    },
+};
+
+/// Minimum page size of the snapshot generated by the maintenance snapshot rpc
+const MIN_PAGE_SIZE: u64 = 512;
 
 /// Restore snapshot to data dir
 /// # Errors
 /// return `ClientError::IoError` if meet io errors
 /// return `ClientError::EngineError` if meet engine errors
+/// return an error if the checksum of the snapshot mismatches
 #[inline]
 #[allow(clippy::indexing_slicing)] // safe operation
 pub async fn restore<P: AsRef<Path>, D: Into<PathBuf>>(
     snapshot_path: P,
     data_dir: D,
 ) -> Result<()> {
+    verify_snapshot_hash(snapshot_path.as_ref()).await?;
     let mut snapshot_f = tokio::fs::File::open(snapshot_path).await?;
     let tmp_path = format!("/tmp/snapshot-{}", uuid::Uuid::new_v4());
     let mut rocks_snapshot = Snapshot::new_for_receiving(EngineType::Rocks((&tmp_path).into()))?;
@@ -34,5 +64,229 @@ pub async fn restore<P: AsRef<Path>, D: Into<PathBuf>>(
     restore_rocks_engine
         .apply_snapshot(rocks_snapshot, &XLINE_TABLES)
         .await?;
+    // The restored data will be used by a new cluster whose log starts from the
+    // beginning, so the applied indexes of the old cluster must be dropped. The index
+    // snapshot is dropped as well, since the kv table may be truncated after restoring.
+    restore_rocks_engine.write_batch(
+        vec![
+            WriteOperation::new_delete(META_TABLE, APPLIED_INDEX_KEY.as_bytes()),
+            WriteOperation::new_delete(META_TABLE, OUT_OF_ORDER_APPLIED_KEY.as_bytes()),
+            WriteOperation::new_delete(META_TABLE, APPLIED_REVISION_KEY.as_bytes()),
+            WriteOperation::new_delete(META_TABLE, INDEX_SNAPSHOT_KEY.as_bytes()),
+        ],
+        true,
+    )?;
+    Ok(())
+}
+
+/// Restore snapshot to data dir as of the given revision, the changes after the
+/// revision are dropped from the retained revision history of the snapshot. The
+/// `encryption` must be given if the snapshot is encrypted at rest.
+///
+/// Only the key space is rolled back. Leases, users, roles and permissions have no
+/// revision history, so they are restored as they are in the snapshot. Revisions are
+/// not mapped to wall clock time either, so the store can not be restored as of a
+/// timestamp.
+/// # Errors
+/// return an error if the snapshot can not be restored, or the revision is compacted
+/// or newer than the latest revision of the snapshot
+#[inline]
+pub async fn restore_to_revision<P: AsRef<Path>, D: Into<PathBuf>>(
+    snapshot_path: P,
+    data_dir: D,
+    revision: i64,
+    encryption: Option<&EncryptionConfig>,
+) -> Result<()> {
+    let data_dir = data_dir.into();
+    restore(snapshot_path, data_dir.clone()).await?;
+    let db = DB::open_with_encryption(&EngineConfig::RocksDB(data_dir), encryption)?;
+    truncate_to_revision(db.as_ref(), revision)
+}
+
+/// Drop the changes of the key space after the given revision. Keys attached to leases
+/// that are revoked after the revision are restored without leases, since the revoked
+/// leases are gone. Leases granted after the revision are kept, and they are revoked
+/// once they expire.
+fn truncate_to_revision<S: StorageApi>(db: &S, revision: i64) -> Result<()> {
+    let compacted = get_revision(db, FINISHED_COMPACT_REVISION)?.unwrap_or(-1);
+    if revision < compacted {
+        bail!("revision {revision} has been compacted, the compacted revision is {compacted}");
+    }
+    let kvs = db.get_all(KV_TABLE)?;
+    let tombstones = db
+        .get_from(META_TABLE, RANGE_TOMBSTONE_PREFIX)?
+        .into_iter()
+        .take_while(|&(ref key, _)| key.starts_with(RANGE_TOMBSTONE_PREFIX))
+        .map(|(_, value)| RangeTombstone::decode(&value))
+        .collect::<Result<Vec<_>, _>>()?;
+    let latest = kvs
+        .last()
+        .map_or(1, |pair| Revision::decode(&pair.0).revision())
+        .max(tombstones.last().map_or(1, |tombstone| tombstone.revision));
+    if revision > latest {
+        bail!("revision {revision} is newer than the latest revision {latest}");
+    }
+
+    let leases: HashSet<i64> = db
+        .get_all(LEASE_TABLE)?
+        .into_iter()
+        .map(|(_, v)| PbLease::decode(v.as_slice()).map(|lease| lease.id))
+        .collect::<Result<_, _>>()?;
+    let mut deleted = Vec::new();
+    // latest version of every key as of the revision
+    let mut latest_versions: HashMap<Vec<u8>, (Revision, KeyValue)> = HashMap::new();
+    for (key, value) in &kvs {
+        let rev = Revision::decode(key);
+        if rev.revision() > revision {
+            deleted.push(key.as_slice());
+            continue;
+        }
+        let kv = KeyValue::decode(value.as_slice())?;
+        let _ignore = latest_versions.insert(kv.key.clone(), (rev, kv));
+    }
+    let mut ops: Vec<_> = deleted.into_iter().map(WriteOp::DeleteKeyValue).collect();
+    // the range tombstones after the revision are dropped as well, the deletions of
+    // the others are restored from them on startup
+    ops.extend(
+        tombstones
+            .iter()
+            .filter(|tombstone| tombstone.revision > revision)
+            .map(|tombstone| WriteOp::DeleteRangeTombstone(tombstone.first_revision())),
+    );
+    for (rev, mut kv) in latest_versions.into_values() {
+        // tombstones of deleted keys have no version
+        if kv.version != 0 && kv.lease != 0 && !leases.contains(&kv.lease) {
+            kv.lease = 0;
+            ops.push(WriteOp::PutKeyValue(rev, kv));
+        }
+    }
+    if get_revision(db, SCHEDULED_COMPACT_REVISION)?.is_some_and(|scheduled| scheduled > revision) {
+        ops.push(WriteOp::PutScheduledCompactRevision(revision));
+    }
+    _ = db.write_batch(ops, true)?;
     Ok(())
 }
+
+/// Get a revision from the meta table
+fn get_revision<S: StorageApi>(db: &S, key: &str) -> Result<Option<i64>> {
+    let Some(bytes) = db.get_value(META_TABLE, key)? else {
+        return Ok(None);
+    };
+    let buf: [u8; 8] = bytes
+        .try_into()
+        .map_err(|e| anyhow!("cannot decode revision of {key}, {e:?}"))?;
+    Ok(Some(i64::from_le_bytes(buf)))
+}
+
+/// Restore the latest backup uploaded to the object storage to data dir, the backup
+/// is downloaded next to the data dir, or to the temporary directory if the parent of
+/// the data dir doesn't exist
+/// Returns `false` if there is no backup in the object storage
+/// # Errors
+/// return an error if failed to download or restore the backup
+#[inline]
+pub async fn restore_latest_backup<D: Into<PathBuf>>(
+    config: &ObjectStorageConfig,
+    data_dir: D,
+) -> Result<bool> {
+    let bucket = BackupBucket::new(config)?;
+    let data_dir: PathBuf = data_dir.into();
+    let download_dir = data_dir
+        .parent()
+        .filter(|parent| parent.is_dir())
+        .map_or_else(std::env::temp_dir, Path::to_path_buf);
+    let tmp_path = download_dir.join(format!("backup-{}", uuid::Uuid::new_v4()));
+    let res = match bucket.download_latest(BACKUP_PREFIX, &tmp_path).await {
+        Ok(Some(key)) => {
+            info!("restoring the latest backup {key}");
+            restore(&tmp_path, data_dir).await.map(|()| true)
+        }
+        Ok(None) => return Ok(false),
+        Err(e) => Err(e),
+    };
+    // the partially downloaded file is removed as well
+    let _ignore = tokio::fs::remove_file(&tmp_path).await;
+    res
+}
+
+/// Verify the sha256 checksum appended to the snapshot. Snapshots without a
+/// checksum (whose size % 512 != sha256 size) are not verified.
+async fn verify_snapshot_hash(snapshot_path: &Path) -> Result<()> {
+    let hash_size: u64 = Sha256::output_size().numeric_cast();
+    let size = tokio::fs::metadata(snapshot_path).await?.len();
+    if size.overflow_rem(MIN_PAGE_SIZE) != hash_size {
+        return Ok(());
+    }
+    let mut snapshot_f = tokio::fs::File::open(snapshot_path).await?;
+    let mut hasher = Sha256::new();
+    let mut data = (&mut snapshot_f).take(size.overflow_sub(hash_size));
+    let mut buf = BytesMut::with_capacity(MAINTENANCE_SNAPSHOT_CHUNK_SIZE.numeric_cast());
+    while read_buf(&mut data, &mut buf).await? != 0 {
+        hasher.update(&buf);
+        buf.clear();
+    }
+    let mut expected = Vec::with_capacity(hash_size.numeric_cast());
+    _ = snapshot_f.read_to_end(&mut expected).await?;
+    if hasher.finalize().as_slice() != expected.as_slice() {
+        bail!("snapshot checksum mismatch, the snapshot may be corrupted");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Put a version of the key at the revision
+    fn put(rev: i64, key: &str, version: i64, lease: i64) -> WriteOp<'static> {
+        WriteOp::PutKeyValue(
+            Revision::new(rev, 0),
+            KeyValue {
+                key: key.as_bytes().to_vec(),
+                value: b"value".to_vec(),
+                create_revision: rev,
+                mod_revision: rev,
+                version,
+                lease,
+            },
+        )
+    }
+
+    #[test]
+    fn truncate_should_drop_the_changes_after_the_revision() -> Result<()> {
+        let db = DB::open(&EngineConfig::Memory)?;
+        let _ignore = db.write_batch(
+            vec![
+                WriteOp::PutLease(PbLease {
+                    id: 1,
+                    ttl: 10,
+                    remaining_ttl: 10,
+                }),
+                put(2, "foo", 1, 0),
+                put(3, "bar", 1, 1),
+                // lease 1 is revoked at revision 4, so bar is deleted
+                put(4, "bar", 0, 0),
+                // delete foo by mistake
+                put(5, "foo", 0, 0),
+            ],
+            false,
+        )?;
+        _ = db.write_batch(vec![WriteOp::DeleteLease(1)], false)?;
+
+        assert!(truncate_to_revision(db.as_ref(), 6).is_err());
+        truncate_to_revision(db.as_ref(), 3)?;
+        let kvs: Vec<_> = db
+            .get_all(KV_TABLE)?
+            .into_iter()
+            .map(|(_, v)| KeyValue::decode(v.as_slice()))
+            .collect::<Result<_, _>>()?;
+        assert_eq!(kvs.len(), 2);
+        assert_eq!(kvs[0].key, b"foo");
+        assert_eq!(kvs[1].key, b"bar");
+        assert_eq!(kvs[1].lease, 0);
+
+        _ = db.write_batch(vec![WriteOp::PutFinishedCompactRevision(3)], false)?;
+        assert!(truncate_to_revision(db.as_ref(), 2).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,249 @@
+use std::sync::Arc;
+
+use clippy_utilities::NumericCast;
+use curp::server::RawCurp;
+use tonic::{Request, Response, Status};
+use tracing::{info, warn};
+use utils::{config::AuthConfig, parse_log_level};
+use xlineapi::{
+    command::{Command, CurpClient},
+    Admin, CompactionRequest, DropWatcherRequest, DropWatcherResponse, ForceCompactRequest,
+    ForceCompactResponse, LeaseDumpRequest, LeaseDumpResponse, LeaseInfo, RequestWrapper,
+    RotateAuthKeyRequest, RotateAuthKeyResponse, RuntimeStatusRequest, RuntimeStatusResponse,
+    SetLogLevelRequest, SetLogLevelResponse, TenantInfo, TenantUsageRequest, TenantUsageResponse,
+};
+
+use crate::{
+    header_gen::HeaderGenerator,
+    revision_check::RevisionCheck,
+    server::XlineServer,
+    state::State,
+    storage::{kvwatcher::KvWatcher, storage_api::StorageApi, AuthStore, KvStore, LeaseStore},
+    utils::LogLevelHandle,
+};
+
+/// Admin Server, exposes runtime knobs and dangerous operations of this node.
+/// All requests require the root role when auth is enabled.
+pub(crate) struct AdminServer<S>
+where
+    S: StorageApi,
+{
+    /// Kv Storage
+    kv_store: Arc<KvStore<S>>,
+    /// Lease Storage
+    lease_store: Arc<LeaseStore<S>>,
+    /// Auth Storage
+    auth_store: Arc<AuthStore<S>>,
+    /// Kv watcher
+    kv_watcher: Arc<KvWatcher<S>>,
+    /// Raw curp
+    raw_curp: Arc<RawCurp<Command, State<S, Arc<CurpClient>>>>,
+    /// Consensus client
+    client: Arc<CurpClient>,
+    /// Header generator
+    header_gen: Arc<HeaderGenerator>,
+    /// Auth config, key files are read from it again on rotations
+    auth_config: AuthConfig,
+    /// Handle to change the log level, `None` if the server is not started by the binary
+    log_level: Option<LogLevelHandle>,
+}
+
+impl<S> AdminServer<S>
+where
+    S: StorageApi,
+{
+    /// New `AdminServer`
+    #[allow(clippy::too_many_arguments)] // Consistent with other servers
+    pub(crate) fn new(
+        kv_store: Arc<KvStore<S>>,
+        lease_store: Arc<LeaseStore<S>>,
+        auth_store: Arc<AuthStore<S>>,
+        kv_watcher: Arc<KvWatcher<S>>,
+        raw_curp: Arc<RawCurp<Command, State<S, Arc<CurpClient>>>>,
+        client: Arc<CurpClient>,
+        header_gen: Arc<HeaderGenerator>,
+        auth_config: AuthConfig,
+        log_level: Option<LogLevelHandle>,
+    ) -> Self {
+        Self {
+            kv_store,
+            lease_store,
+            auth_store,
+            kv_watcher,
+            raw_curp,
+            client,
+            header_gen,
+            auth_config,
+            log_level,
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl<S> Admin for AdminServer<S>
+where
+    S: StorageApi,
+{
+    async fn set_log_level(
+        &self,
+        request: Request<SetLogLevelRequest>,
+    ) -> Result<Response<SetLogLevelResponse>, Status> {
+        self.auth_store
+            .check_admin_permission_from_request(&request)?;
+        let Some(ref log_level) = self.log_level else {
+            return Err(Status::unavailable(
+                "log level can not be changed at runtime",
+            ));
+        };
+        let level = parse_log_level(&request.into_inner().level)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        log_level
+            .reload(level)
+            .map_err(|e| Status::internal(e.to_string()))?;
+        info!("log level is changed to {level} by admin");
+        Ok(Response::new(SetLogLevelResponse {
+            header: Some(self.header_gen.gen_header()),
+        }))
+    }
+
+    async fn runtime_status(
+        &self,
+        request: Request<RuntimeStatusRequest>,
+    ) -> Result<Response<RuntimeStatusResponse>, Status> {
+        self.auth_store
+            .check_admin_permission_from_request(&request)?;
+        let (leader, raft_term, is_leader) = self.raw_curp.leader();
+        let (watchers, victim_watchers) = self.kv_watcher.watcher_count();
+        Ok(Response::new(RuntimeStatusResponse {
+            header: Some(self.header_gen.gen_header()),
+            leader: leader.unwrap_or(0),
+            raft_term,
+            is_leader,
+            commit_index: self.raw_curp.commit_index(),
+            in_flight_proposals: self.raw_curp.in_flight_proposals(),
+            compact_revision: self.kv_store.compacted_revision(),
+            watchers: watchers.numeric_cast(),
+            victim_watchers: victim_watchers.numeric_cast(),
+            leases: self.lease_store.leases().len().numeric_cast(),
+        }))
+    }
+
+    async fn lease_dump(
+        &self,
+        request: Request<LeaseDumpRequest>,
+    ) -> Result<Response<LeaseDumpResponse>, Status> {
+        self.auth_store
+            .check_admin_permission_from_request(&request)?;
+        let leases = self
+            .lease_store
+            .leases()
+            .into_iter()
+            .map(|lease| LeaseInfo {
+                id: lease.id(),
+                ttl: lease.ttl().as_secs().numeric_cast(),
+                remaining_ttl: lease.remaining().as_secs().numeric_cast(),
+                keys: lease.keys(),
+            })
+            .collect();
+        Ok(Response::new(LeaseDumpResponse {
+            header: Some(self.header_gen.gen_header()),
+            leases,
+        }))
+    }
+
+    async fn tenant_usage(
+        &self,
+        request: Request<TenantUsageRequest>,
+    ) -> Result<Response<TenantUsageResponse>, Status> {
+        self.auth_store
+            .check_admin_permission_from_request(&request)?;
+        let tenants = self
+            .kv_store
+            .tenants()
+            .usages()
+            .into_iter()
+            .map(|usage| TenantInfo {
+                prefix: usage.quota.prefix.into_bytes(),
+                keys: usage.keys,
+                bytes: usage.bytes,
+                max_keys: usage.quota.max_keys,
+                max_bytes: usage.quota.max_bytes,
+            })
+            .collect();
+        Ok(Response::new(TenantUsageResponse {
+            header: Some(self.header_gen.gen_header()),
+            tenants,
+        }))
+    }
+
+    /// Reload the key files of this node and sign new tokens with the new private key.
+    /// Tokens signed by the replaced key are still accepted by this node until they
+    /// expire, other nodes should list the new public key in `auth_previous_public_keys`
+    /// before the rotation so that they accept the new tokens.
+    async fn rotate_auth_key(
+        &self,
+        request: Request<RotateAuthKeyRequest>,
+    ) -> Result<Response<RotateAuthKeyResponse>, Status> {
+        self.auth_store
+            .check_admin_permission_from_request(&request)?;
+        let Some((encoding_key, decoding_key)) = XlineServer::read_key_pair(&self.auth_config)
+            .await
+            .map_err(|e| Status::failed_precondition(e.to_string()))?
+        else {
+            return Err(Status::failed_precondition("auth keys are not configured"));
+        };
+        let previous_keys = XlineServer::read_previous_public_keys(&self.auth_config)
+            .await
+            .map_err(|e| Status::failed_precondition(e.to_string()))?;
+        self.auth_store
+            .rotate_key_pair(encoding_key, decoding_key, previous_keys)?;
+        warn!("auth key is rotated by admin");
+        Ok(Response::new(RotateAuthKeyResponse {
+            header: Some(self.header_gen.gen_header()),
+        }))
+    }
+
+    async fn force_compact(
+        &self,
+        request: Request<ForceCompactRequest>,
+    ) -> Result<Response<ForceCompactResponse>, Status> {
+        self.auth_store
+            .check_admin_permission_from_request(&request)?;
+        let auth_info = self.auth_store.try_get_auth_info_from_request(&request)?;
+        let current_revision = self.kv_store.revision();
+        let revision = match request.into_inner().revision {
+            0 => current_revision,
+            revision => revision,
+        };
+        let compaction = CompactionRequest {
+            revision,
+            physical: false,
+        };
+        (&compaction).check_revision(self.kv_store.compacted_revision(), current_revision)?;
+        let request = RequestWrapper::from(compaction);
+        let cmd = Command::new_with_auth_info(request.keys(), request, auth_info);
+        let _ignore = self.client.propose(&cmd, None, false).await??;
+        warn!("force compaction to revision {revision} by admin");
+        Ok(Response::new(ForceCompactResponse {
+            header: Some(self.header_gen.gen_header()),
+            revision,
+        }))
+    }
+
+    async fn drop_watcher(
+        &self,
+        request: Request<DropWatcherRequest>,
+    ) -> Result<Response<DropWatcherResponse>, Status> {
+        self.auth_store
+            .check_admin_permission_from_request(&request)?;
+        let watch_id = request.into_inner().watch_id;
+        let dropped = self.kv_watcher.drop_watcher(watch_id);
+        if dropped {
+            warn!("watcher {watch_id} is dropped by admin");
+        }
+        Ok(Response::new(DropWatcherResponse {
+            header: Some(self.header_gen.gen_header()),
+            dropped,
+        }))
+    }
+}
@@ -0,0 +1,228 @@
+#[cfg(not(madsim))]
+use std::time::Duration;
+use std::{net::SocketAddr, sync::Arc};
+
+use clippy_utilities::OverflowArithmetic;
+use dashmap::DashMap;
+#[cfg(not(madsim))]
+use tokio::time::Instant;
+#[cfg(not(madsim))]
+use tonic::service::{interceptor::InterceptedService, Interceptor};
+use tonic::{Request, Status};
+
+#[cfg(not(madsim))]
+use crate::storage::auth_store::Identity;
+
+/// Max number of clients tracked by the rate limiter before the idle ones are evicted
+#[cfg(not(madsim))]
+const MAX_TRACKED_CLIENTS: usize = 4096;
+
+/// Token bucket rate limiter of each client, implemented by the generic cell rate
+/// algorithm: a request is admitted if the theoretical arrival time of the client does
+/// not run ahead of now by more than the burst
+#[cfg(not(madsim))]
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    /// Interval between two requests at the sustained rate
+    interval: Duration,
+    /// How far the theoretical arrival time may run ahead of now
+    tolerance: Duration,
+    /// Theoretical arrival time of the next request of each client
+    clients: DashMap<String, Instant>,
+}
+
+#[cfg(not(madsim))]
+impl RateLimiter {
+    /// New `RateLimiter` admitting `rate` requests per second and bursts of `burst`
+    /// requests of each client, `None` if the rate is unlimited
+    pub(crate) fn new(rate: u32, burst: u32) -> Option<Self> {
+        let interval = Duration::from_secs(1).checked_div(rate)?;
+        Some(Self {
+            interval,
+            tolerance: interval.saturating_mul(burst.saturating_sub(1)),
+            clients: DashMap::new(),
+        })
+    }
+
+    /// Try to admit a request of `client`
+    pub(crate) fn try_acquire(&self, client: &str) -> bool {
+        let now = Instant::now();
+        if self.clients.len() > MAX_TRACKED_CLIENTS {
+            // The bucket of a client whose theoretical arrival time has passed is full,
+            // forgetting the client changes nothing
+            self.clients.retain(|_, tat| *tat > now);
+        }
+        let mut tat = self.clients.entry(client.to_owned()).or_insert(now);
+        let next = (*tat).max(now);
+        if next.saturating_duration_since(now) > self.tolerance {
+            return false;
+        }
+        *tat = next.checked_add(self.interval).unwrap_or(next);
+        true
+    }
+}
+
+/// The client of a request seen by the rate limiter, the authenticated user if any,
+/// otherwise the ip address of the client
+#[cfg(not(madsim))]
+fn client_of<T>(request: &Request<T>) -> Option<String> {
+    request
+        .extensions()
+        .get::<Identity>()
+        .and_then(|identity| identity.auth_info.as_ref())
+        .map(|info| format!("user {}", info.username))
+        .or_else(|| {
+            request
+                .remote_addr()
+                .map(|addr| format!("ip {}", addr.ip()))
+        })
+}
+
+/// Interceptor that rejects the requests of a client exceeding its rate limit with
+/// `RESOURCE_EXHAUSTED`, it must run after the `AuthInterceptor` to see the user
+#[cfg(not(madsim))]
+#[derive(Debug, Clone)]
+pub(crate) struct RateLimitInterceptor {
+    /// Rate limiter, `None` if the rate is unlimited
+    limiter: Option<Arc<RateLimiter>>,
+}
+
+#[cfg(not(madsim))]
+impl RateLimitInterceptor {
+    /// New `RateLimitInterceptor`
+    pub(crate) fn new(limiter: Option<RateLimiter>) -> Self {
+        Self {
+            limiter: limiter.map(Arc::new),
+        }
+    }
+
+    /// Rate limit requests of `service` with this interceptor
+    pub(crate) fn intercept<T>(&self, service: T) -> InterceptedService<T, Self> {
+        InterceptedService::new(service, self.clone())
+    }
+}
+
+#[cfg(not(madsim))]
+impl Interceptor for RateLimitInterceptor {
+    fn call(&mut self, request: Request<()>) -> Result<Request<()>, Status> {
+        let Some(ref limiter) = self.limiter else {
+            return Ok(request);
+        };
+        if let Some(client) = client_of(&request) {
+            if !limiter.try_acquire(&client) {
+                return Err(Status::resource_exhausted("etcdserver: too many requests"));
+            }
+        }
+        Ok(request)
+    }
+}
+
+/// Limit of the concurrent streams of each connection
+#[derive(Debug)]
+pub(crate) struct StreamLimiter {
+    /// Max number of streams of a connection, `0` means unlimited
+    max_streams: usize,
+    /// Number of open streams of each connection
+    streams: Arc<DashMap<SocketAddr, usize>>,
+}
+
+impl StreamLimiter {
+    /// New `StreamLimiter`
+    pub(crate) fn new(max_streams: usize) -> Self {
+        Self {
+            max_streams,
+            streams: Arc::new(DashMap::new()),
+        }
+    }
+
+    /// Open a stream of the connection of `request`, the stream is closed when the
+    /// returned permit is dropped
+    pub(crate) fn acquire<T>(&self, request: &Request<T>) -> Result<StreamPermit, Status> {
+        let Some(addr) = request.remote_addr().filter(|_| self.max_streams != 0) else {
+            return Ok(StreamPermit { opened: None });
+        };
+        let mut count = self.streams.entry(addr).or_insert(0);
+        if *count >= self.max_streams {
+            return Err(Status::resource_exhausted(format!(
+                "too many streams of the connection from {addr}, the limit is {}",
+                self.max_streams
+            )));
+        }
+        *count = count.overflow_add(1);
+        Ok(StreamPermit {
+            opened: Some((addr, Arc::clone(&self.streams))),
+        })
+    }
+}
+
+/// An open stream of a connection
+#[derive(Debug)]
+pub(crate) struct StreamPermit {
+    /// The connection and the stream counts, `None` if the streams are unlimited
+    opened: Option<(SocketAddr, Arc<DashMap<SocketAddr, usize>>)>,
+}
+
+impl Drop for StreamPermit {
+    fn drop(&mut self) {
+        if let Some((addr, ref streams)) = self.opened {
+            let _ignore = streams.remove_if_mut(&addr, |_, count| {
+                *count = count.overflow_sub(1);
+                *count == 0
+            });
+        }
+    }
+}
+
+#[cfg(all(test, not(madsim)))]
+mod test {
+    use tonic::transport::server::TcpConnectInfo;
+
+    use super::*;
+
+    fn request_from(addr: &str) -> Request<()> {
+        let mut request = Request::new(());
+        let _ignore = request.extensions_mut().insert(TcpConnectInfo {
+            local_addr: None,
+            remote_addr: Some(addr.parse().unwrap()),
+        });
+        request
+    }
+
+    #[tokio::test]
+    async fn rate_limiter_should_admit_bursts_and_the_sustained_rate() {
+        let limiter = RateLimiter::new(10, 3).unwrap();
+        for _ in 0..3 {
+            assert!(limiter.try_acquire("a"));
+        }
+        assert!(!limiter.try_acquire("a"));
+        // other clients have their own buckets
+        assert!(limiter.try_acquire("b"));
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert!(limiter.try_acquire("a"));
+    }
+
+    #[test]
+    fn zero_rate_should_be_unlimited() {
+        assert!(RateLimiter::new(0, 10).is_none());
+    }
+
+    #[test]
+    fn client_should_be_identified_by_ip() {
+        let request = request_from("10.0.0.1:1234");
+        assert_eq!(client_of(&request).as_deref(), Some("ip 10.0.0.1"));
+    }
+
+    #[test]
+    fn stream_limiter_should_limit_streams_of_each_connection() {
+        let limiter = StreamLimiter::new(2);
+        let conn1 = request_from("10.0.0.1:1234");
+        let conn2 = request_from("10.0.0.1:1235");
+        let permit1 = limiter.acquire(&conn1).unwrap();
+        let _permit2 = limiter.acquire(&conn1).unwrap();
+        let err = limiter.acquire(&conn1).unwrap_err();
+        assert_eq!(err.code(), tonic::Code::ResourceExhausted);
+        assert!(limiter.acquire(&conn2).is_ok());
+        drop(permit1);
+        assert!(limiter.acquire(&conn1).is_ok());
+    }
+}
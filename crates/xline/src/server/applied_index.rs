@@ -0,0 +1,183 @@
+use std::collections::BTreeMap;
+
+use clippy_utilities::OverflowArithmetic;
+use curp::LogIndex;
+use utils::table_names::META_TABLE;
+use xlineapi::execute_error::ExecuteError;
+
+use super::command::{APPLIED_INDEX_KEY, APPLIED_REVISION_KEY, OUT_OF_ORDER_APPLIED_KEY};
+use crate::storage::{db::WriteOp, storage_api::StorageApi};
+
+/// Applied indexes of the state machine, which are persisted along with the changes of
+/// every command. Commands that don't conflict are applied concurrently, so they may be
+/// applied out of order. The applied index is the largest index that all indexes up to
+/// it are applied, and the indexes applied out of order are persisted as well, so that
+/// they are not applied again when the log is replayed after a crash.
+///
+/// The general revision at the applied index is persisted as well. General revisions
+/// are allocated in the order of the log, so the log replayed from the applied index
+/// gets the same revisions as before the crash, if the revision generator starts from it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct AppliedIndexes {
+    /// All indexes up to it are applied
+    applied: LogIndex,
+    /// The largest general revision of the commands up to `applied`, `None` if it is
+    /// not recorded yet
+    revision: Option<i64>,
+    /// Indexes above `applied` that are applied, and their general revisions
+    out_of_order: BTreeMap<LogIndex, i64>,
+    /// The largest index applied before the restart, the log is replayed until it
+    replay_until: LogIndex,
+}
+
+impl AppliedIndexes {
+    /// Load the applied indexes from the storage
+    pub(crate) fn load<S: StorageApi>(persistent: &S) -> Result<Self, ExecuteError> {
+        let applied = persistent
+            .get_value(META_TABLE, APPLIED_INDEX_KEY)?
+            .map_or(0, |bytes| decode_index(&bytes));
+        let revision = persistent
+            .get_value(META_TABLE, APPLIED_REVISION_KEY)?
+            .map(|bytes| decode_revision(&bytes));
+        let out_of_order: BTreeMap<_, _> = persistent
+            .get_value(META_TABLE, OUT_OF_ORDER_APPLIED_KEY)?
+            .map(|bytes| {
+                bytes
+                    .chunks_exact(16)
+                    .map(|chunk| {
+                        let (index, rev) = chunk.split_at(8);
+                        (decode_index(index), decode_revision(rev))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        let replay_until = out_of_order
+            .last_key_value()
+            .map_or(applied, |(&index, _)| index);
+        Ok(Self {
+            applied,
+            revision,
+            out_of_order,
+            replay_until,
+        })
+    }
+
+    /// All indexes up to it are applied
+    pub(crate) fn applied(&self) -> LogIndex {
+        self.applied
+    }
+
+    /// The largest general revision of the commands up to the applied index, the
+    /// revision generator restarts from it
+    pub(crate) fn revision(&self) -> Option<i64> {
+        self.revision
+    }
+
+    /// Whether the log is still being replayed to catch up with the indexes applied
+    /// before the restart
+    pub(crate) fn is_recovering(&self) -> bool {
+        self.applied < self.replay_until
+    }
+
+    /// The largest index applied before the restart
+    pub(crate) fn replay_until(&self) -> LogIndex {
+        self.replay_until
+    }
+
+    /// Check if the index is applied
+    pub(crate) fn contains(&self, index: LogIndex) -> bool {
+        index <= self.applied || self.out_of_order.contains_key(&index)
+    }
+
+    /// Get the applied indexes after the command at the given index is applied, the
+    /// `revision` is its general revision, or -1 if it has none
+    #[must_use]
+    pub(crate) fn with(&self, index: LogIndex, revision: i64) -> Self {
+        let mut next = self.clone();
+        if index > next.applied {
+            let _ignore = next.out_of_order.insert(index, revision);
+        }
+        while let Some(rev) = next.out_of_order.remove(&next.applied.overflow_add(1)) {
+            next.applied = next.applied.overflow_add(1);
+            if rev > 0 {
+                next.revision = Some(next.revision.map_or(rev, |r| r.max(rev)));
+            }
+        }
+        next
+    }
+
+    /// Operations to persist the applied indexes
+    pub(crate) fn write_ops(&self) -> Vec<WriteOp<'static>> {
+        let mut ops = vec![
+            WriteOp::PutAppliedIndex(self.applied),
+            WriteOp::PutOutOfOrderApplied(
+                self.out_of_order
+                    .iter()
+                    .map(|(&index, &revision)| (index, revision))
+                    .collect(),
+            ),
+        ];
+        if let Some(revision) = self.revision {
+            ops.push(WriteOp::PutAppliedRevision(revision));
+        }
+        ops
+    }
+}
+
+/// Decode an index from its little endian bytes
+fn decode_index(bytes: &[u8]) -> LogIndex {
+    let buf: [u8; 8] = bytes
+        .try_into()
+        .unwrap_or_else(|e| panic!("cannot decode index from backend, {e:?}"));
+    LogIndex::from_le_bytes(buf)
+}
+
+/// Decode a revision from its little endian bytes
+fn decode_revision(bytes: &[u8]) -> i64 {
+    let buf: [u8; 8] = bytes
+        .try_into()
+        .unwrap_or_else(|e| panic!("cannot decode revision from backend, {e:?}"));
+    i64::from_le_bytes(buf)
+}
+
+#[cfg(test)]
+mod test {
+    use utils::config::EngineConfig;
+
+    use super::*;
+    use crate::storage::db::DB;
+
+    #[test]
+    fn applied_index_should_only_advance_when_all_previous_indexes_are_applied() {
+        let indexes = AppliedIndexes::default().with(1, 2).with(3, 4).with(4, -1);
+        assert_eq!(indexes.applied(), 1);
+        assert_eq!(indexes.revision(), Some(2));
+        assert!(indexes.contains(3));
+        assert!(!indexes.contains(2));
+
+        let indexes = indexes.with(2, 3);
+        assert_eq!(indexes.applied(), 4);
+        assert_eq!(indexes.revision(), Some(4));
+        assert!(indexes.out_of_order.is_empty());
+    }
+
+    #[test]
+    fn applied_indexes_should_be_recovered() -> Result<(), ExecuteError> {
+        let db = DB::open(&EngineConfig::Memory)?;
+        let indexes = AppliedIndexes::default().with(1, 2).with(3, 4).with(5, 6);
+        let _ignore = db.write_batch(indexes.write_ops(), false)?;
+
+        let recovered = AppliedIndexes::load(db.as_ref())?;
+        assert_eq!(recovered.applied(), 1);
+        assert_eq!(recovered.revision(), Some(2));
+        assert!(recovered.contains(3) && recovered.contains(5));
+        assert!(recovered.is_recovering());
+        assert_eq!(recovered.replay_until(), 5);
+
+        let recovered = recovered.with(2, 3).with(4, 5);
+        assert_eq!(recovered.applied(), 5);
+        assert_eq!(recovered.revision(), Some(6));
+        assert!(!recovered.is_recovering());
+        Ok(())
+    }
+}
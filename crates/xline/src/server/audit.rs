@@ -0,0 +1,242 @@
+use std::io::Write;
+
+use anyhow::{anyhow, Result};
+use parking_lot::Mutex;
+use serde::Serialize;
+use serde_json::{json, Value};
+use tracing::warn;
+use tracing_appender::non_blocking::{NonBlocking, NonBlockingBuilder, WorkerGuard};
+use utils::{
+    config::{file_appender, AuditConfig},
+    timestamp,
+};
+use xlineapi::command::Command;
+
+use crate::rpc::{DeleteRangeRequest, RequestWrapper, Type};
+
+/// An entry of the audit log
+#[derive(Debug, Serialize)]
+struct AuditEntry<'a> {
+    /// Unix timestamp in seconds
+    time: u64,
+    /// The user who did the action, `None` if auth is disabled
+    user: Option<&'a str>,
+    /// The action
+    action: &'a str,
+    /// Revision of the action, `None` if it is not applied by the state machine
+    revision: Option<i64>,
+    /// Details of the action
+    detail: Value,
+}
+
+/// Writer of the audit log
+#[derive(Debug)]
+struct AuditWriter {
+    /// Non-blocking writer to the log files
+    writer: Mutex<NonBlocking>,
+    /// Flushes the remaining entries when dropped
+    _guard: WorkerGuard,
+}
+
+/// Append-only audit log that records who changed the auth settings, deleted key ranges,
+/// including those deleted inside txns, compacted the kv store or changed the members of
+/// the cluster.
+///
+/// Commands are recorded when they are applied, so every member records the same entries.
+/// Member changes are recorded by the member that serves the request.
+#[derive(Debug, Default)]
+pub(crate) struct AuditLog {
+    /// The writer, `None` if the audit log is disabled
+    inner: Option<AuditWriter>,
+}
+
+impl AuditLog {
+    /// Open the audit log
+    pub(crate) fn open(config: &AuditConfig) -> Result<Self> {
+        if !*config.enable() {
+            return Ok(Self::default());
+        }
+        let Some(ref path) = *config.path() else {
+            return Err(anyhow!("audit log is enabled but its path is not given"));
+        };
+        std::fs::create_dir_all(path)?;
+        let (writer, guard) = NonBlockingBuilder::default()
+            .lossy(false)
+            .finish(file_appender(*config.rotation(), path, "audit"));
+        Ok(Self {
+            inner: Some(AuditWriter {
+                writer: Mutex::new(writer),
+                _guard: guard,
+            }),
+        })
+    }
+
+    /// Record an applied command if it should be audited, along with the delete range
+    /// requests applied inside it if it is a txn
+    pub(crate) fn record_command(
+        &self,
+        cmd: &Command,
+        txn_deletions: &[DeleteRangeRequest],
+        revision: i64,
+    ) {
+        if self.inner.is_none() {
+            return;
+        }
+        let user = cmd.auth_info().map(|info| info.username.as_str());
+        if let Some((action, detail)) = audited_action(cmd.request()) {
+            self.append(user, action, Some(revision), detail);
+        }
+        for (action, detail) in txn_deletions.iter().filter_map(audited_deletion) {
+            self.append(user, action, Some(revision), detail);
+        }
+    }
+
+    /// Record a member change
+    pub(crate) fn record_member_change(&self, user: Option<&str>, action: &str, detail: Value) {
+        self.append(user, action, None, detail);
+    }
+
+    /// Append an entry to the audit log
+    fn append(&self, user: Option<&str>, action: &str, revision: Option<i64>, detail: Value) {
+        let Some(ref inner) = self.inner else {
+            return;
+        };
+        let entry = AuditEntry {
+            time: timestamp(),
+            user,
+            action,
+            revision,
+            detail,
+        };
+        let mut line = match serde_json::to_vec(&entry) {
+            Ok(line) => line,
+            Err(e) => {
+                warn!("failed to serialize audit entry {entry:?}: {e}");
+                return;
+            }
+        };
+        line.push(b'\n');
+        if let Err(e) = inner.writer.lock().write_all(&line) {
+            warn!("failed to write audit entry {entry:?}: {e}");
+        }
+    }
+}
+
+/// Get the action and details of a request if it should be audited, passwords are never
+/// included in the details
+fn audited_action(request: &RequestWrapper) -> Option<(&'static str, Value)> {
+    let lossy = |bytes: &[u8]| String::from_utf8_lossy(bytes).into_owned();
+    #[allow(clippy::wildcard_enum_match_arm)]
+    let action = match *request {
+        RequestWrapper::AuthEnableRequest(_) => ("auth_enable", json!({})),
+        RequestWrapper::AuthDisableRequest(_) => ("auth_disable", json!({})),
+        RequestWrapper::AuthUserAddRequest(ref req) => ("user_add", json!({ "user": req.name })),
+        RequestWrapper::AuthUserDeleteRequest(ref req) => {
+            ("user_delete", json!({ "user": req.name }))
+        }
+        RequestWrapper::AuthUserChangePasswordRequest(ref req) => {
+            ("user_change_password", json!({ "user": req.name }))
+        }
+        RequestWrapper::AuthUserGrantRoleRequest(ref req) => (
+            "user_grant_role",
+            json!({ "user": req.user, "role": req.role }),
+        ),
+        RequestWrapper::AuthUserRevokeRoleRequest(ref req) => (
+            "user_revoke_role",
+            json!({ "user": req.name, "role": req.role }),
+        ),
+        RequestWrapper::AuthRoleAddRequest(ref req) => ("role_add", json!({ "role": req.name })),
+        RequestWrapper::AuthRoleDeleteRequest(ref req) => {
+            ("role_delete", json!({ "role": req.role }))
+        }
+        RequestWrapper::AuthRoleGrantPermissionRequest(ref req) => {
+            let perm = req.perm.as_ref().map(|perm| {
+                json!({
+                    "type": Type::try_from(perm.perm_type).map_or("UNKNOWN", |t| t.as_str_name()),
+                    "key": lossy(&perm.key),
+                    "range_end": lossy(&perm.range_end),
+                })
+            });
+            (
+                "role_grant_permission",
+                json!({ "role": req.name, "perm": perm }),
+            )
+        }
+        RequestWrapper::AuthRoleRevokePermissionRequest(ref req) => (
+            "role_revoke_permission",
+            json!({
+                "role": req.role,
+                "key": lossy(&req.key),
+                "range_end": lossy(&req.range_end),
+            }),
+        ),
+        RequestWrapper::DeleteRangeRequest(ref req) => return audited_deletion(req),
+        RequestWrapper::CompactionRequest(ref req) => (
+            "compaction",
+            json!({ "revision": req.revision, "physical": req.physical }),
+        ),
+        _ => return None,
+    };
+    Some(action)
+}
+
+/// Get the action and details of a delete range request if it deletes a range rather than
+/// a single key
+fn audited_deletion(req: &DeleteRangeRequest) -> Option<(&'static str, Value)> {
+    if req.range_end.is_empty() {
+        return None;
+    }
+    let lossy = |bytes: &[u8]| String::from_utf8_lossy(bytes).into_owned();
+    Some((
+        "delete_range",
+        json!({
+            "key": lossy(&req.key),
+            "range_end": lossy(&req.range_end),
+        }),
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::rpc::{AuthUserAddRequest, AuthUserChangePasswordRequest, PutRequest};
+
+    #[test]
+    fn only_changes_should_be_audited() {
+        let put = RequestWrapper::from(PutRequest::default());
+        assert!(audited_action(&put).is_none());
+        let delete_key = RequestWrapper::from(DeleteRangeRequest {
+            key: b"a".to_vec(),
+            ..Default::default()
+        });
+        assert!(audited_action(&delete_key).is_none());
+
+        let delete_range = RequestWrapper::from(DeleteRangeRequest {
+            key: b"a".to_vec(),
+            range_end: b"b".to_vec(),
+            ..Default::default()
+        });
+        let (action, detail) = audited_action(&delete_range).unwrap();
+        assert_eq!(action, "delete_range");
+        assert_eq!(detail, json!({ "key": "a", "range_end": "b" }));
+    }
+
+    #[test]
+    fn passwords_should_not_be_audited() {
+        let user_add = RequestWrapper::from(AuthUserAddRequest {
+            name: "u".to_owned(),
+            password: "secret".to_owned(),
+            hashed_password: "hash".to_owned(),
+            options: None,
+        });
+        let change_password = RequestWrapper::from(AuthUserChangePasswordRequest {
+            name: "u".to_owned(),
+            password: "secret".to_owned(),
+            hashed_password: "hash".to_owned(),
+        });
+        for req in [user_add, change_password] {
+            let (_, detail) = audited_action(&req).unwrap();
+            assert_eq!(detail, json!({ "user": "u" }));
+        }
+    }
+}
@@ -0,0 +1,62 @@
+use std::sync::Arc;
+
+use tonic::{
+    service::{interceptor::InterceptedService, Interceptor},
+    Request, Status,
+};
+
+use crate::storage::{storage_api::StorageApi, AuthStore};
+
+/// Interceptor that authenticates every request of the client services once, and stores
+/// the resolved `Identity` in the request extensions, so that the servers only need to
+/// authorize the requests.
+///
+/// A request that fails the authentication is not rejected here, because an interceptor
+/// can't tell which method is called, and `Authenticate` must not be blocked by a stale
+/// token. It's passed without an `Identity`, and the server authenticates it again when
+/// it needs the user, which returns the same error.
+pub(crate) struct AuthInterceptor<S>
+where
+    S: StorageApi,
+{
+    /// Auth store
+    auth_store: Arc<AuthStore<S>>,
+}
+
+impl<S> AuthInterceptor<S>
+where
+    S: StorageApi,
+{
+    /// New `AuthInterceptor`
+    pub(crate) fn new(auth_store: Arc<AuthStore<S>>) -> Self {
+        Self { auth_store }
+    }
+
+    /// Authenticate requests of `service` with this interceptor
+    pub(crate) fn intercept<T>(&self, service: T) -> InterceptedService<T, Self> {
+        InterceptedService::new(service, self.clone())
+    }
+}
+
+impl<S> Clone for AuthInterceptor<S>
+where
+    S: StorageApi,
+{
+    fn clone(&self) -> Self {
+        Self {
+            auth_store: Arc::clone(&self.auth_store),
+        }
+    }
+}
+
+impl<S> Interceptor for AuthInterceptor<S>
+where
+    S: StorageApi,
+{
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        if let Ok(identity) = self.auth_store.authenticate(&request) {
+            let _ignore = request.extensions_mut().insert(identity);
+        }
+        Ok(request)
+    }
+}
@@ -0,0 +1,153 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    ops::Add,
+    time::{Duration, Instant},
+};
+
+use clippy_utilities::OverflowArithmetic;
+use parking_lot::Mutex;
+use utils::config::AuthLockoutConfig;
+
+/// Max number of subjects tracked by `AuthLockout`, subjects that are not locked are
+/// dropped when it is exceeded
+const MAX_TRACKED_SUBJECTS: usize = 64 * 1024;
+
+/// Subject of authentications
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum Subject {
+    /// A user authenticated from a client address, `None` if the address is unknown.
+    /// Users are not locked globally, or anyone could lock out a user, e.g. `root`, by
+    /// sending wrong passwords.
+    User(String, Option<IpAddr>),
+    /// A client address
+    Addr(IpAddr),
+}
+
+/// Failed authentications of a subject
+#[derive(Debug, Default)]
+struct Failures {
+    /// Number of consecutive failures
+    count: u32,
+    /// The subject is locked until this instant
+    locked_until: Option<Instant>,
+}
+
+/// Tracks failed authentications of users and client addresses, a subject is locked for
+/// a while after too many consecutive failures, so that passwords can not be brute-forced.
+/// The failures are tracked by each member, so a client may try `max_failures` times on
+/// every member before it is locked out of all of them.
+#[derive(Debug)]
+pub(crate) struct AuthLockout {
+    /// Number of consecutive failures that locks a subject, 0 disables lockout
+    max_failures: u32,
+    /// How long a subject is locked
+    duration: Duration,
+    /// Failures of subjects
+    failures: Mutex<HashMap<Subject, Failures>>,
+}
+
+impl AuthLockout {
+    /// New `AuthLockout`
+    pub(crate) fn new(config: AuthLockoutConfig) -> Self {
+        Self {
+            max_failures: *config.max_failures(),
+            duration: *config.duration(),
+            failures: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Get the remaining lockout duration of the subjects, `None` if none of them is locked
+    pub(crate) fn locked(&self, subjects: &[Subject]) -> Option<Duration> {
+        if self.max_failures == 0 {
+            return None;
+        }
+        let now = Instant::now();
+        let failures = self.failures.lock();
+        subjects
+            .iter()
+            .filter_map(|subject| failures.get(subject)?.locked_until)
+            .filter(|&until| until > now)
+            .max()
+            .map(|until| until.duration_since(now))
+    }
+
+    /// Record a failed authentication of the subjects
+    pub(crate) fn record_failure(&self, subjects: &[Subject]) {
+        if self.max_failures == 0 {
+            return;
+        }
+        let now = Instant::now();
+        let mut failures = self.failures.lock();
+        if failures.len() >= MAX_TRACKED_SUBJECTS {
+            failures.retain(|_, f| f.locked_until.is_some_and(|until| until > now));
+        }
+        for subject in subjects {
+            let entry = failures.entry(subject.clone()).or_default();
+            entry.count = entry.count.overflow_add(1);
+            if entry.count >= self.max_failures {
+                entry.count = 0;
+                entry.locked_until = Some(now.add(self.duration));
+            }
+        }
+    }
+
+    /// Record a successful authentication of a user from a client address, the failures
+    /// of the address are kept, so that a valid account can not be used to reset them
+    pub(crate) fn record_success(&self, user: &Subject) {
+        let _ignore = self.failures.lock().remove(user);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn subjects_should_be_locked_after_max_failures() {
+        let lockout = AuthLockout::new(AuthLockoutConfig::new(2, Duration::from_secs(60)));
+        let ip = IpAddr::from([127, 0, 0, 1]);
+        let user = Subject::User("u".to_owned(), Some(ip));
+        let addr = Subject::Addr(ip);
+        lockout.record_failure(&[user.clone(), addr.clone()]);
+        assert!(lockout.locked(&[user.clone(), addr.clone()]).is_none());
+
+        lockout.record_success(&user);
+        lockout.record_failure(&[user.clone(), addr.clone()]);
+        assert!(lockout.locked(&[user.clone()]).is_none());
+        assert!(lockout.locked(&[addr.clone()]).is_some());
+        assert!(lockout
+            .locked(&[Subject::User("other".to_owned(), Some(ip))])
+            .is_none());
+    }
+
+    #[test]
+    fn users_should_only_be_locked_from_the_failed_address() {
+        let lockout = AuthLockout::new(AuthLockoutConfig::new(1, Duration::from_secs(60)));
+        let attacker = IpAddr::from([10, 0, 0, 1]);
+        lockout.record_failure(&[
+            Subject::User("root".to_owned(), Some(attacker)),
+            Subject::Addr(attacker),
+        ]);
+        assert!(lockout
+            .locked(&[Subject::User("root".to_owned(), Some(attacker))])
+            .is_some());
+        let admin = IpAddr::from([10, 0, 0, 2]);
+        assert!(lockout
+            .locked(&[
+                Subject::User("root".to_owned(), Some(admin)),
+                Subject::Addr(admin)
+            ])
+            .is_none());
+    }
+
+    #[test]
+    fn zero_max_failures_should_disable_lockout() {
+        let lockout = AuthLockout::new(AuthLockoutConfig::new(0, Duration::from_secs(60)));
+        let user = Subject::User("u".to_owned(), None);
+        for _ in 0..10 {
+            lockout.record_failure(&[user.clone()]);
+        }
+        assert!(lockout.locked(&[user]).is_none());
+    }
+}
@@ -2,12 +2,18 @@ use std::sync::Arc;
 
 use tonic::metadata::MetadataMap;
 use tracing::debug;
-use utils::hash_password;
+use utils::{
+    config::{AuthLockoutConfig, PasswordHashConfig},
+    hash_password_with,
+};
 use xlineapi::{
     command::{Command, CommandResponse, CurpClient, SyncResponse},
+    execute_error::ExecuteError,
     request_validation::RequestValidator,
 };
 
+use super::auth_lockout::{AuthLockout, Subject};
+
 use crate::{
     rpc::{
         Auth, AuthDisableRequest, AuthDisableResponse, AuthEnableRequest, AuthEnableResponse,
@@ -20,7 +26,7 @@ use crate::{
         AuthUserGetRequest, AuthUserGetResponse, AuthUserGrantRoleRequest,
         AuthUserGrantRoleResponse, AuthUserListRequest, AuthUserListResponse,
         AuthUserRevokeRoleRequest, AuthUserRevokeRoleResponse, AuthenticateRequest,
-        AuthenticateResponse, RequestWrapper, ResponseWrapper,
+        AuthenticateResponse, InternalAuthenticateRequest, RequestWrapper, ResponseWrapper,
     },
     storage::{storage_api::StorageApi, AuthStore},
 };
@@ -34,6 +40,10 @@ where
     client: Arc<CurpClient>,
     /// Auth Store
     auth_store: Arc<AuthStore<S>>,
+    /// Parameters of hashing user passwords
+    password_hash: PasswordHashConfig,
+    /// Lockout after failed authentications
+    lockout: AuthLockout,
 }
 
 /// Get token from metadata
@@ -49,26 +59,67 @@ where
     S: StorageApi,
 {
     /// New `AuthServer`
-    pub(crate) fn new(client: Arc<CurpClient>, auth_store: Arc<AuthStore<S>>) -> Self {
-        Self { client, auth_store }
+    pub(crate) fn new(
+        client: Arc<CurpClient>,
+        auth_store: Arc<AuthStore<S>>,
+        password_hash: PasswordHashConfig,
+        lockout: AuthLockoutConfig,
+    ) -> Self {
+        Self {
+            client,
+            auth_store,
+            password_hash,
+            lockout: AuthLockout::new(lockout),
+        }
     }
 
-    /// Propose request and get result with fast/slow path
-    async fn propose<T>(
+    /// Hash a password with the configured parameters
+    fn hash_password(&self, password: &str) -> Result<String, tonic::Status> {
+        hash_password_with(password.as_bytes(), &self.password_hash)
+            .map_err(|err| tonic::Status::internal(format!("Failed to hash password: {err}")))
+    }
+
+    /// Propose request and get result with fast/slow path, errors of the execution are
+    /// returned in the inner result
+    async fn propose_cmd<T>(
         &self,
         request: tonic::Request<T>,
         use_fast_path: bool,
-    ) -> Result<(CommandResponse, Option<SyncResponse>), tonic::Status>
+    ) -> Result<Result<(CommandResponse, Option<SyncResponse>), ExecuteError>, tonic::Status>
     where
         T: Into<RequestWrapper>,
     {
         let auth_info = self.auth_store.try_get_auth_info_from_request(&request)?;
         let request = request.into_inner().into();
         let cmd = Command::new_with_auth_info(request.keys(), request, auth_info);
-        let res = self.client.propose(&cmd, None, use_fast_path).await??;
+        let res = self.client.propose(&cmd, None, use_fast_path).await?;
         Ok(res)
     }
 
+    /// Propose request and get result with fast/slow path
+    async fn propose<T>(
+        &self,
+        request: tonic::Request<T>,
+        use_fast_path: bool,
+    ) -> Result<(CommandResponse, Option<SyncResponse>), tonic::Status>
+    where
+        T: Into<RequestWrapper>,
+    {
+        Ok(self.propose_cmd(request, use_fast_path).await??)
+    }
+
+    /// Make a response from the results of a command
+    fn make_response<Res>(cmd_res: CommandResponse, sync_res: Option<SyncResponse>) -> Res
+    where
+        Res: From<ResponseWrapper>,
+    {
+        let mut res_wrapper = cmd_res.into_inner();
+        if let Some(sync_res) = sync_res {
+            res_wrapper.update_revision(sync_res.revision());
+        }
+        res_wrapper.into()
+    }
+
     /// Propose request and make a response
     async fn handle_req<Req, Res>(
         &self,
@@ -80,11 +131,7 @@ where
         Res: From<ResponseWrapper>,
     {
         let (cmd_res, sync_res) = self.propose(request, use_fast_path).await?;
-        let mut res_wrapper = cmd_res.into_inner();
-        if let Some(sync_res) = sync_res {
-            res_wrapper.update_revision(sync_res.revision());
-        }
-        Ok(tonic::Response::new(res_wrapper.into()))
+        Ok(tonic::Response::new(Self::make_response(cmd_res, sync_res)))
     }
 }
 
@@ -123,7 +170,59 @@ where
         request: tonic::Request<AuthenticateRequest>,
     ) -> Result<tonic::Response<AuthenticateResponse>, tonic::Status> {
         debug!("Receive AuthenticateRequest {:?}", request);
-        self.handle_req(request, false).await
+        // an empty password with a valid token refreshes the token
+        if request.get_ref().password.is_empty() {
+            if let Some(auth_info) = self.auth_store.try_get_auth_info_from_request(&request)? {
+                let res = self
+                    .auth_store
+                    .refresh_token(&request.get_ref().name, &auth_info)?;
+                return Ok(tonic::Response::new(res));
+            }
+        }
+        // upgrade the password hash of the user if it is outdated, the new hash is stored
+        // only if the password is correct
+        let name = request.get_ref().name.clone();
+        let addr = request.remote_addr().map(|addr| addr.ip());
+        let user = Subject::User(name, addr);
+        let mut subjects = vec![user.clone()];
+        if let Some(addr) = addr {
+            subjects.push(Subject::Addr(addr));
+        }
+        if let Some(remaining) = self.lockout.locked(&subjects) {
+            return Err(tonic::Status::resource_exhausted(format!(
+                "too many failed authentications, retry in {}s",
+                remaining.as_secs().max(1)
+            )));
+        }
+        let hashed_password = if self
+            .auth_store
+            .password_needs_rehash(&request.get_ref().name, &self.password_hash)
+        {
+            self.hash_password(&request.get_ref().password)?
+        } else {
+            String::new()
+        };
+        // the hash is proposed in an internal request, so clients can never set it
+        let request = request.map(|req| InternalAuthenticateRequest {
+            name: req.name,
+            password: req.password,
+            hashed_password,
+        });
+        match self.propose_cmd(request, false).await? {
+            Ok((cmd_res, sync_res)) => {
+                self.lockout.record_success(&user);
+                Ok(tonic::Response::new(Self::make_response(cmd_res, sync_res)))
+            }
+            Err(err) => {
+                if matches!(
+                    err,
+                    ExecuteError::AuthFailed | ExecuteError::UserNotFound(_)
+                ) {
+                    self.lockout.record_failure(&subjects);
+                }
+                Err(err.into())
+            }
+        }
     }
 
     async fn user_add(
@@ -133,9 +232,7 @@ where
         let user_add_req = request.get_mut();
         debug!("Receive AuthUserAddRequest {}", user_add_req);
         user_add_req.validation()?;
-        let hashed_password = hash_password(user_add_req.password.as_bytes())
-            .map_err(|err| tonic::Status::internal(format!("Failed to hash password: {err}")))?;
-        user_add_req.hashed_password = hashed_password;
+        user_add_req.hashed_password = self.hash_password(&user_add_req.password)?;
         user_add_req.password = String::new();
         self.handle_req(request, false).await
     }
@@ -172,9 +269,8 @@ where
     ) -> Result<tonic::Response<AuthUserChangePasswordResponse>, tonic::Status> {
         debug!("Receive AuthUserChangePasswordRequest {:?}", request);
         let user_change_password_req = request.get_mut();
-        let hashed_password = hash_password(user_change_password_req.password.as_bytes())
-            .map_err(|err| tonic::Status::internal(format!("Failed to hash password: {err}")))?;
-        user_change_password_req.hashed_password = hashed_password;
+        user_change_password_req.hashed_password =
+            self.hash_password(&user_change_password_req.password)?;
         user_change_password_req.password = String::new();
         self.handle_req(request, false).await
     }
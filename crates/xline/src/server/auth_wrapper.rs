@@ -37,6 +37,12 @@ where
             auth_store,
         }
     }
+
+    /// Get the auth store
+    #[cfg(not(madsim))]
+    pub(crate) fn auth_store(&self) -> Arc<AuthStore<S>> {
+        Arc::clone(&self.auth_store)
+    }
 }
 
 #[tonic::async_trait]
@@ -0,0 +1,189 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use tokio::{fs, io::AsyncWriteExt};
+use tokio_stream::StreamExt;
+use tracing::{info, warn};
+use utils::{config::BackupConfig, task_manager::Listener};
+
+use super::{command::CommandExecutor, maintenance::snapshot_stream};
+use crate::{
+    header_gen::HeaderGenerator,
+    storage::{object_storage::BackupBucket, storage_api::StorageApi},
+};
+
+/// Prefix of the file names of the backups
+pub(crate) const BACKUP_PREFIX: &str = "xline-backup-";
+/// Suffix of the file names of the backups
+const BACKUP_SUFFIX: &str = ".db";
+/// Interval between two checks of whether a backup should be taken
+const BACKUP_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Background task that takes local backups of the storage when the interval elapses
+/// or enough log entries are applied since the last backup, and removes the old ones.
+/// The backups are uploaded to the object storage as well if it is configured.
+#[allow(clippy::arithmetic_side_effects, clippy::ignored_unit_patterns)] // introduced by tokio::select! macro
+pub(super) async fn backup_task<S: StorageApi>(
+    config: BackupConfig,
+    header_gen: Arc<HeaderGenerator>,
+    persistent: Arc<S>,
+    ce: Arc<CommandExecutor<S>>,
+    shutdown_listener: Listener,
+) {
+    if config.interval().is_zero() && *config.entries() == 0 {
+        warn!("neither the interval nor the entries of backups is given, no backup will be taken");
+        return;
+    }
+    let bucket = match config.upload().as_ref().map(BackupBucket::new).transpose() {
+        Ok(bucket) => bucket,
+        Err(e) => {
+            warn!("failed to connect to the object storage, backups will not be uploaded, {e}");
+            None
+        }
+    };
+    let mut last_time = Instant::now();
+    let mut last_index = ce.applied_indexes().applied();
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep(BACKUP_CHECK_INTERVAL) => {}
+            _ = shutdown_listener.wait() => return,
+        }
+        let applied = ce.applied_indexes().applied();
+        let by_time = !config.interval().is_zero() && last_time.elapsed() >= *config.interval();
+        // the applied index may go back after the storage is reset by a snapshot
+        let by_entries =
+            *config.entries() != 0 && applied.saturating_sub(last_index) >= *config.entries();
+        if !by_time && !by_entries {
+            continue;
+        }
+        match save_backup(&config, &header_gen, persistent.as_ref()).await {
+            Ok(path) => {
+                info!("backup is saved to {path:?} at applied index {applied}");
+                if let Some(ref bucket) = bucket {
+                    match bucket.upload(&path).await {
+                        Ok(key) => info!("backup {path:?} is uploaded to {key}"),
+                        Err(e) => warn!("failed to upload backup {path:?}, {e}"),
+                    }
+                }
+            }
+            Err(e) => warn!("failed to save backup, {e}"),
+        }
+        last_time = Instant::now();
+        last_index = applied;
+    }
+}
+
+/// Save a backup into the backup directory, and remove the backups beyond the retention
+async fn save_backup<S: StorageApi>(
+    config: &BackupConfig,
+    header_gen: &HeaderGenerator,
+    persistent: &S,
+) -> Result<PathBuf> {
+    fs::create_dir_all(config.dir()).await?;
+    let millis = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+    // the names are zero padded, so they are sorted by time
+    let name = format!("{BACKUP_PREFIX}{millis:020}{BACKUP_SUFFIX}");
+    let path = config.dir().join(name);
+    write_snapshot(header_gen, persistent, &path).await?;
+    remove_old_backups(config.dir(), *config.retention()).await?;
+    Ok(path)
+}
+
+/// Write a snapshot of the storage to the path, which can be restored like the snapshots
+/// of the maintenance service
+pub(super) async fn write_snapshot<S: StorageApi>(
+    header_gen: &HeaderGenerator,
+    persistent: &S,
+    path: &Path,
+) -> Result<()> {
+    let mut tmp_name = path.as_os_str().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_name);
+
+    let mut stream = Box::pin(snapshot_stream(header_gen, persistent)?);
+    let mut file = fs::File::create(&tmp_path).await?;
+    while let Some(resp) = stream.next().await {
+        file.write_all(&resp?.blob).await?;
+    }
+    file.sync_all().await?;
+    // a snapshot is visible only after it is completely written
+    fs::rename(&tmp_path, path).await?;
+    Ok(())
+}
+
+/// Remove the oldest backups, only the most recent `retention` backups are kept, and
+/// the new backup is always kept
+async fn remove_old_backups(dir: &Path, retention: usize) -> Result<()> {
+    let mut backups = Vec::new();
+    let mut entries = fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with(BACKUP_PREFIX) && name.ends_with(BACKUP_SUFFIX) {
+            backups.push(entry.path());
+        }
+    }
+    backups.sort_unstable();
+    let removed = backups.len().saturating_sub(retention.max(1));
+    for path in backups.into_iter().take(removed) {
+        fs::remove_file(&path).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use test_macros::abort_on_panic;
+    use utils::{config::EngineConfig, table_names::KV_TABLE};
+
+    use super::*;
+    use crate::{
+        rpc::KeyValue,
+        storage::{
+            db::{WriteOp, DB},
+            Revision,
+        },
+    };
+
+    #[tokio::test]
+    #[abort_on_panic]
+    async fn backups_should_be_rotated_and_restorable() -> Result<()> {
+        let tmp_dir = tempfile::tempdir()?;
+        let dir = tmp_dir.path();
+        let persistent = DB::open(&EngineConfig::RocksDB(dir.join("data")))?;
+        let kv = KeyValue {
+            key: b"foo".to_vec(),
+            value: b"bar".to_vec(),
+            ..Default::default()
+        };
+        let _ignore =
+            persistent.write_batch(vec![WriteOp::PutKeyValue(Revision::new(1, 0), kv)], false)?;
+        let header_gen = HeaderGenerator::new(0, 0);
+        let config = BackupConfig::new(dir.join("backups"), Duration::ZERO, 1, 2, None);
+
+        let mut paths = Vec::new();
+        for _ in 0..3 {
+            paths.push(save_backup(&config, &header_gen, persistent.as_ref()).await?);
+            // backups are named by milliseconds
+            tokio::time::sleep(Duration::from_millis(2)).await;
+        }
+        assert!(!paths[0].exists());
+        assert!(paths[1].exists() && paths[2].exists());
+
+        crate::restore::restore(&paths[2], dir.join("restored")).await?;
+        let restored = DB::open(&EngineConfig::RocksDB(dir.join("restored")))?;
+        let values = restored.get_all(KV_TABLE)?;
+        assert_eq!(values.len(), 1);
+
+        // the new backup is kept even if the retention is 0
+        let config = BackupConfig::new(dir.join("backups"), Duration::ZERO, 1, 0, None);
+        let path = save_backup(&config, &header_gen, persistent.as_ref()).await?;
+        assert!(path.exists());
+        assert!(!paths[2].exists());
+
+        Ok(())
+    }
+}
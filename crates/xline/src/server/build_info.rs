@@ -0,0 +1,92 @@
+use curp::members::ClusterInfo;
+use serde::Serialize;
+
+use super::feature_gate::binary_version;
+
+/// Commit of the source that the binary is built from
+const GIT_SHA: &str = env!("XLINE_GIT_SHA");
+/// When the binary is built, in RFC 3339
+const BUILD_DATE: &str = env!("XLINE_BUILD_DATE");
+/// Cluster version reported before it is negotiated, the same as etcd
+const NOT_DECIDED: &str = "not_decided";
+/// Metadata key of the cluster version in the status response
+const CLUSTER_VERSION_KEY: &str = "xline-cluster-version";
+/// Metadata key of the git sha in the status response
+const GIT_SHA_KEY: &str = "xline-git-sha";
+/// Metadata key of the build date in the status response
+const BUILD_DATE_KEY: &str = "xline-build-date";
+
+/// Versions and build info of a member, served by the `/version` endpoint in the layout
+/// of the one of etcd
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub(crate) struct VersionInfo {
+    /// Binary version of the member
+    pub(crate) xlineserver: String,
+    /// Version negotiated by the cluster
+    pub(crate) xlinecluster: String,
+    /// Commit of the source that the binary is built from
+    pub(crate) git_sha: &'static str,
+    /// When the binary is built
+    pub(crate) build_date: &'static str,
+}
+
+impl VersionInfo {
+    /// Versions and build info of the current member
+    pub(crate) fn new(cluster_info: &ClusterInfo) -> Self {
+        Self {
+            xlineserver: binary_version().to_string(),
+            xlinecluster: cluster_info
+                .negotiated_version()
+                .map_or_else(|| NOT_DECIDED.to_owned(), |v| v.to_string()),
+            git_sha: GIT_SHA,
+            build_date: BUILD_DATE,
+        }
+    }
+
+    /// Attach the info which has no field in the status response of etcd to the metadata
+    /// of the response
+    pub(crate) fn attach_to<T>(&self, response: &mut tonic::Response<T>) {
+        for (key, value) in [
+            (CLUSTER_VERSION_KEY, self.xlinecluster.as_str()),
+            (GIT_SHA_KEY, self.git_sha),
+            (BUILD_DATE_KEY, self.build_date),
+        ] {
+            if let Ok(value) = value.parse() {
+                let _ignore = response.metadata_mut().insert(key, value);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use utils::version::Version;
+
+    use super::*;
+
+    #[test]
+    fn version_info_should_report_the_negotiated_version() {
+        let cluster_info = ClusterInfo::from_members_map(
+            HashMap::from([("S1".to_owned(), vec!["S1".to_owned()])]),
+            [],
+            "S1",
+        );
+        let info = VersionInfo::new(&cluster_info);
+        assert_eq!(info.xlineserver, env!("CARGO_PKG_VERSION"));
+        assert_eq!(info.xlinecluster, "not_decided");
+        let _raised = cluster_info.raise_negotiated_version(Version::new(0, 6, 1));
+        let info = VersionInfo::new(&cluster_info);
+        assert_eq!(info.xlinecluster, "0.6.1");
+        assert!(!info.git_sha.is_empty());
+
+        let mut response = tonic::Response::new(());
+        info.attach_to(&mut response);
+        assert_eq!(
+            response.metadata().get(CLUSTER_VERSION_KEY).unwrap(),
+            "0.6.1"
+        );
+        assert_eq!(response.metadata().get(GIT_SHA_KEY).unwrap(), GIT_SHA);
+    }
+}
@@ -0,0 +1,131 @@
+use std::{
+    future::Future,
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use tokio::{
+    io::{AsyncRead, AsyncWrite, ReadBuf},
+    sync::OwnedSemaphorePermit,
+    time::Sleep,
+};
+use tonic::transport::server::Connected;
+
+/// A client connection that reaches the end of its input once it is older than the max
+/// age, so that the server closes it and the client reconnects, possibly to another server
+#[derive(Debug)]
+pub(super) struct ClientConnection<IO> {
+    /// The inner connection
+    io: IO,
+    /// Fires when the connection reaches the max age, `None` if the age is unlimited
+    expiry: Option<Pin<Box<Sleep>>>,
+    /// Admission of the connection, released when the connection is closed
+    _permit: Option<OwnedSemaphorePermit>,
+}
+
+impl<IO> ClientConnection<IO> {
+    /// Wrap a connection, `max_age` of zero keeps the connection forever
+    pub(super) fn new(io: IO, max_age: Duration, permit: Option<OwnedSemaphorePermit>) -> Self {
+        let expiry = (!max_age.is_zero()).then(|| Box::pin(tokio::time::sleep(max_age)));
+        Self {
+            io,
+            expiry,
+            _permit: permit,
+        }
+    }
+
+    /// Whether the connection has reached the max age, the task is woken up when it does
+    fn is_expired(&mut self, cx: &mut Context<'_>) -> bool {
+        self.expiry
+            .as_mut()
+            .is_some_and(|expiry| expiry.as_mut().poll(cx).is_ready())
+    }
+}
+
+impl<IO: AsyncRead + Unpin> AsyncRead for ClientConnection<IO> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        if self.is_expired(cx) {
+            // An empty read means the connection is closed by the client
+            return Poll::Ready(Ok(()));
+        }
+        Pin::new(&mut self.io).poll_read(cx, buf)
+    }
+}
+
+impl<IO: AsyncWrite + Unpin> AsyncWrite for ClientConnection<IO> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.io).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.io).poll_shutdown(cx)
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.io).poll_write_vectored(cx, bufs)
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.io.is_write_vectored()
+    }
+}
+
+impl<IO: Connected> Connected for ClientConnection<IO> {
+    type ConnectInfo = IO::ConnectInfo;
+
+    fn connect_info(&self) -> Self::ConnectInfo {
+        self.io.connect_info()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn connection_should_be_closed_after_max_age() {
+        let (mut client, server) = tokio::io::duplex(64);
+        let mut conn = ClientConnection::new(server, Duration::from_millis(100), None);
+        client.write_all(b"ping").await.unwrap();
+        let mut buf = [0; 4];
+        conn.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"ping");
+        // the read is woken up by the expiry even if the client sends nothing
+        let n = tokio::time::timeout(Duration::from_secs(1), conn.read(&mut buf))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(n, 0);
+    }
+
+    #[tokio::test]
+    async fn zero_max_age_should_keep_the_connection() {
+        let (mut client, server) = tokio::io::duplex(64);
+        let mut conn = ClientConnection::new(server, Duration::ZERO, None);
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        client.write_all(b"ping").await.unwrap();
+        let mut buf = [0; 4];
+        conn.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"ping");
+    }
+}
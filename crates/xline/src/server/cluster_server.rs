@@ -8,6 +8,7 @@ use curp::{
     },
 };
 use itertools::Itertools;
+use serde_json::json;
 use tonic::{Request, Response, Status};
 use utils::timestamp;
 use xlineapi::{
@@ -16,20 +17,53 @@ use xlineapi::{
     MemberRemoveResponse, MemberUpdateRequest, MemberUpdateResponse,
 };
 
-use crate::header_gen::HeaderGenerator;
+use super::audit::AuditLog;
+use crate::{
+    header_gen::HeaderGenerator,
+    storage::{storage_api::StorageApi, AuthStore},
+};
 
 /// Cluster Server
-pub(crate) struct ClusterServer {
+pub(crate) struct ClusterServer<S>
+where
+    S: StorageApi,
+{
     /// Consensus client
     client: Arc<CurpClient>,
     /// Header generator
     header_gen: Arc<HeaderGenerator>,
+    /// Auth storage
+    auth_store: Arc<AuthStore<S>>,
+    /// Audit log of member changes
+    audit_log: Arc<AuditLog>,
 }
 
-impl ClusterServer {
+impl<S> ClusterServer<S>
+where
+    S: StorageApi,
+{
     /// New `ClusterServer`
-    pub(crate) fn new(client: Arc<CurpClient>, header_gen: Arc<HeaderGenerator>) -> Self {
-        Self { client, header_gen }
+    pub(crate) fn new(
+        client: Arc<CurpClient>,
+        header_gen: Arc<HeaderGenerator>,
+        auth_store: Arc<AuthStore<S>>,
+        audit_log: Arc<AuditLog>,
+    ) -> Self {
+        Self {
+            client,
+            header_gen,
+            auth_store,
+            audit_log,
+        }
+    }
+
+    /// Get the name of the user who sent the request, `None` if auth is disabled
+    fn user_of<T>(&self, request: &Request<T>) -> Option<String> {
+        self.auth_store
+            .try_get_auth_info_from_request(request)
+            .ok()
+            .flatten()
+            .map(|auth_info| auth_info.username)
     }
 
     /// Send propose conf change request
@@ -51,11 +85,17 @@ impl ClusterServer {
 }
 
 #[tonic::async_trait]
-impl Cluster for ClusterServer {
+impl<S> Cluster for ClusterServer<S>
+where
+    S: StorageApi,
+{
     async fn member_add(
         &self,
         request: Request<MemberAddRequest>,
     ) -> Result<Response<MemberAddResponse>, Status> {
+        self.auth_store
+            .check_admin_permission_from_request(&request)?;
+        let user = self.user_of(&request);
         let req = request.into_inner();
         let change_type = if req.is_learner {
             i32::from(AddLearner)
@@ -69,9 +109,14 @@ impl Cluster for ClusterServer {
             .propose_conf_change(vec![ConfChange {
                 change_type,
                 node_id,
-                address: peer_url_ls,
+                address: peer_url_ls.clone(),
             }])
             .await?;
+        self.audit_log.record_member_change(
+            user.as_deref(),
+            "member_add",
+            json!({ "id": node_id, "peer_urls": peer_url_ls, "is_learner": req.is_learner }),
+        );
         let resp = MemberAddResponse {
             header: Some(self.header_gen.gen_header()),
             member: members.iter().find(|m| m.id == node_id).cloned(),
@@ -84,6 +129,9 @@ impl Cluster for ClusterServer {
         &self,
         request: Request<MemberRemoveRequest>,
     ) -> Result<Response<MemberRemoveResponse>, Status> {
+        self.auth_store
+            .check_admin_permission_from_request(&request)?;
+        let user = self.user_of(&request);
         let req = request.into_inner();
         let members = self
             .propose_conf_change(vec![ConfChange {
@@ -92,6 +140,11 @@ impl Cluster for ClusterServer {
                 address: vec![],
             }])
             .await?;
+        self.audit_log.record_member_change(
+            user.as_deref(),
+            "member_remove",
+            json!({ "id": req.id }),
+        );
         let resp = MemberRemoveResponse {
             header: Some(self.header_gen.gen_header()),
             members,
@@ -103,14 +156,22 @@ impl Cluster for ClusterServer {
         &self,
         request: Request<MemberUpdateRequest>,
     ) -> Result<Response<MemberUpdateResponse>, Status> {
+        self.auth_store
+            .check_admin_permission_from_request(&request)?;
+        let user = self.user_of(&request);
         let req = request.into_inner();
         let members = self
             .propose_conf_change(vec![ConfChange {
                 change_type: i32::from(Update),
                 node_id: req.id,
-                address: req.peer_ur_ls,
+                address: req.peer_ur_ls.clone(),
             }])
             .await?;
+        self.audit_log.record_member_change(
+            user.as_deref(),
+            "member_update",
+            json!({ "id": req.id, "peer_urls": req.peer_ur_ls }),
+        );
         let resp = MemberUpdateResponse {
             header: Some(self.header_gen.gen_header()),
             members,
@@ -145,6 +206,9 @@ impl Cluster for ClusterServer {
         &self,
         request: Request<MemberPromoteRequest>,
     ) -> Result<Response<MemberPromoteResponse>, Status> {
+        self.auth_store
+            .check_admin_permission_from_request(&request)?;
+        let user = self.user_of(&request);
         let req = request.into_inner();
         let members = self
             .propose_conf_change(vec![ConfChange {
@@ -153,6 +217,11 @@ impl Cluster for ClusterServer {
                 address: vec![],
             }])
             .await?;
+        self.audit_log.record_member_change(
+            user.as_deref(),
+            "member_promote",
+            json!({ "id": req.id }),
+        );
         let resp = MemberPromoteResponse {
             header: Some(self.header_gen.gen_header()),
             members,
@@ -1,4 +1,11 @@
-use std::{fmt::Debug, sync::Arc};
+use std::{
+    collections::BTreeSet,
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
 
 use clippy_utilities::OverflowArithmetic;
 use curp::{
@@ -9,24 +16,47 @@ use curp::{
 use dashmap::DashMap;
 use engine::Snapshot;
 use event_listener::Event;
-use parking_lot::RwLock;
-use tracing::warn;
-use utils::table_names::META_TABLE;
+use parking_lot::{Mutex, RwLock};
+use tokio::sync::{
+    mpsc::{self, error::SendError},
+    oneshot,
+};
+use tracing::{debug, instrument, warn};
+use utils::{table_names::META_TABLE, task_manager::Listener};
 use xlineapi::{
-    command::{Command, CurpClient},
+    command::{Command, CurpClient, SyncResponse},
     execute_error::ExecuteError,
-    AlarmAction, AlarmRequest, AlarmType,
+    AlarmAction, AlarmRequest, AlarmType, Request, TxnRequest,
 };
 
-use super::barriers::{IdBarrier, IndexBarrier};
+use super::{
+    applied_index::AppliedIndexes,
+    audit::AuditLog,
+    barriers::{IdBarrier, IndexBarrier},
+    overload::OverloadMonitor,
+};
 use crate::{
     revision_number::RevisionNumberGenerator,
     rpc::{RequestBackend, RequestWrapper},
-    storage::{db::WriteOp, storage_api::StorageApi, AlarmStore, AuthStore, KvStore, LeaseStore},
+    storage::{
+        db::{EncodedBatch, WriteOp},
+        revision::KeyRevision,
+        storage_api::StorageApi,
+        AlarmStore, AuthStore, KvStore, LeaseStore,
+    },
 };
 
 /// Key of applied index
 pub(crate) const APPLIED_INDEX_KEY: &str = "applied_index";
+/// Key of the indexes applied out of order
+pub(crate) const OUT_OF_ORDER_APPLIED_KEY: &str = "out_of_order_applied";
+/// Key of the general revision at the applied index
+pub(crate) const APPLIED_REVISION_KEY: &str = "applied_revision";
+
+/// Size of the queue of the commands sent to the persist stage
+pub(crate) const PERSIST_CHANNEL_SIZE: usize = 1024;
+/// Maximum number of the commands written in one batch by the persist stage
+const MAX_PERSIST_GROUP: usize = 128;
 
 /// Range start and end to get all keys
 const UNBOUNDED: &[u8] = &[0_u8];
@@ -87,6 +117,33 @@ where
     quota_checker: Arc<dyn QuotaChecker>,
     /// Alarmer
     alarmer: RwLock<Option<Alarmer>>,
+    /// Audit log of the applied commands
+    audit_log: Arc<AuditLog>,
+    /// Applied indexes of the state machine
+    applied_indexes: Mutex<AppliedIndexes>,
+    /// General revisions of the commands that are prepared but not synced yet, all
+    /// revisions below the smallest one are persisted
+    pending_revisions: Mutex<BTreeSet<i64>>,
+    /// Overload monitor that tracks the number of the pending revisions
+    overload_monitor: Arc<OverloadMonitor>,
+    /// Whether index snapshots can be persisted, it is turned off after the storage is
+    /// reset because the in-memory index is not rebuilt from the new storage
+    index_snapshots: AtomicBool,
+    /// Queue of the commands sent to the persist stage
+    persist_tx: mpsc::Sender<PersistTask>,
+}
+
+/// The encoded changes of a command sent to the persist stage
+#[derive(Debug)]
+pub(crate) struct PersistTask {
+    /// Log index of the command
+    index: LogIndex,
+    /// General revision of the command, -1 if it has none
+    revision: i64,
+    /// Encoded changes of the command
+    batch: EncodedBatch,
+    /// Notified once the changes are written
+    done: oneshot::Sender<Result<(), ExecuteError>>,
 }
 
 /// Quota checker
@@ -243,10 +300,21 @@ where
         auth_rev: Arc<RevisionNumberGenerator>,
         compact_events: Arc<DashMap<u64, Arc<Event>>>,
         quota: u64,
-    ) -> Self {
+        audit_log: Arc<AuditLog>,
+        persist_tx: mpsc::Sender<PersistTask>,
+        overload_monitor: Arc<OverloadMonitor>,
+    ) -> Result<Self, ExecuteError> {
         let alarmer = RwLock::new(None);
         let quota_checker = Arc::new(CommandQuotaChecker::new(quota, Arc::clone(&persistent)));
-        Self {
+        let applied_indexes = AppliedIndexes::load(persistent.as_ref())?;
+        // the commands above the applied index are replayed, and get the same revisions
+        // as before the restart only if the generator starts from the applied index, even
+        // though some of them are applied out of order and have larger revisions stored
+        if let Some(revision) = applied_indexes.revision() {
+            general_rev.set(revision);
+        }
+        let applied_indexes = Mutex::new(applied_indexes);
+        Ok(Self {
             kv_storage,
             auth_storage,
             lease_storage,
@@ -259,7 +327,211 @@ where
             compact_events,
             quota_checker,
             alarmer,
+            audit_log,
+            applied_indexes,
+            pending_revisions: Mutex::new(BTreeSet::new()),
+            overload_monitor,
+            index_snapshots: AtomicBool::new(true),
+            persist_tx,
+        })
+    }
+
+    /// Write the changes of the command at the given index along with the applied
+    /// indexes. All changes of the command, e.g. every put of a txn, and the applied
+    /// indexes are written in one batch, so a command is never applied twice when the
+    /// log is replayed after a crash. The command is already persisted in the curp log,
+    /// so the batch is not fsynced.
+    fn write_applied(
+        &self,
+        index: LogIndex,
+        mut ops: Vec<WriteOp>,
+    ) -> Result<Vec<(Vec<u8>, KeyRevision)>, ExecuteError> {
+        let mut applied_indexes = self.applied_indexes.lock();
+        let next = applied_indexes.with(index, -1);
+        ops.extend(next.write_ops());
+        let key_revisions = self.persistent.write_batch(ops, false)?;
+        *applied_indexes = next;
+        Ok(key_revisions)
+    }
+
+    /// Encode the changes of the command at the given index and send them to the persist
+    /// stage, which writes them along with the applied indexes. The changes are encoded
+    /// here, so the encoding of concurrent commands runs in parallel and only the write
+    /// itself is serialized. The revisions of the written keys are returned once the
+    /// changes are written.
+    async fn persist(
+        &self,
+        index: LogIndex,
+        revision: i64,
+        ops: Vec<WriteOp<'_>>,
+    ) -> Result<Vec<(Vec<u8>, KeyRevision)>, ExecuteError> {
+        let mut batch = self.persistent.encode_batch(ops)?;
+        let key_revisions = batch.take_revisions();
+        let (done, written) = oneshot::channel();
+        let task = PersistTask {
+            index,
+            revision,
+            batch,
+            done,
+        };
+        if let Err(SendError(task)) = self.persist_tx.send(task).await {
+            // the persist stage is stopped, e.g. when shutting down, write it here
+            self.write_group(vec![task]);
         }
+        written.await.map_err(|e| {
+            ExecuteError::DbError(format!("the persist stage exits unexpectedly: {e}"))
+        })??;
+        Ok(key_revisions)
+    }
+
+    /// Write the changes of a group of commands along with the applied indexes in one
+    /// batch, and notify the commands
+    fn write_group(&self, tasks: Vec<PersistTask>) {
+        let mut applied_indexes = self.applied_indexes.lock();
+        let next = tasks.iter().fold(applied_indexes.clone(), |indexes, task| {
+            indexes.with(task.index, task.revision)
+        });
+        let (mut batches, dones): (Vec<_>, Vec<_>) = tasks
+            .into_iter()
+            .map(|task| (task.batch, task.done))
+            .unzip();
+        let res = self
+            .persistent
+            .encode_batch(next.write_ops())
+            .and_then(|batch| {
+                batches.push(batch);
+                self.persistent.write_encoded(batches, false)
+            });
+        if res.is_ok() {
+            *applied_indexes = next;
+        }
+        drop(applied_indexes);
+        for done in dones {
+            let _ignore = done.send(res.clone());
+        }
+    }
+
+    /// Persist a snapshot of the index at the highest revision that all revisions up to
+    /// it are persisted, if the revision is newer than `last_revision`. The revision of
+    /// the persisted snapshot is returned.
+    ///
+    /// # Errors
+    ///
+    /// Return `ExecuteError::DbError` if the snapshot can not be written
+    pub(crate) fn persist_index_snapshot(
+        &self,
+        last_revision: i64,
+    ) -> Result<Option<i64>, ExecuteError> {
+        let revision = {
+            let pending = self.pending_revisions.lock();
+            pending
+                .first()
+                .map_or_else(|| self.general_rev.get(), |first| first.overflow_sub(1))
+        };
+        if revision <= last_revision || !self.index_snapshots.load(Ordering::Acquire) {
+            return Ok(None);
+        }
+        let snapshot = self.kv_storage.index_snapshot(revision).encode();
+        // hold the lock so that the snapshot is never written after a reset
+        let _applied_indexes = self.applied_indexes.lock();
+        if !self.index_snapshots.load(Ordering::Acquire) {
+            return Ok(None);
+        }
+        _ = self
+            .persistent
+            .write_batch(vec![WriteOp::PutIndexSnapshot(snapshot)], false)?;
+        Ok(Some(revision))
+    }
+
+    /// Sync the command at the given index, see `after_sync`
+    async fn sync_cmd(
+        &self,
+        cmd: &Command,
+        index: LogIndex,
+        revision: i64,
+    ) -> Result<SyncResponse, ExecuteError> {
+        // the command was applied out of order before the restart, and its changes are
+        // already persisted at the same revision, which is allocated again by `prepare`
+        if self.applied_indexes.lock().contains(index) {
+            debug!("skip command at index {index}, which is applied before the restart");
+            return Ok(SyncResponse::new(revision));
+        }
+        let quota_enough = self.quota_checker.check(cmd);
+        let wrapper = cmd.request();
+        let general_revision = match wrapper.backend() {
+            RequestBackend::Kv | RequestBackend::Lease => revision,
+            RequestBackend::Auth | RequestBackend::Alarm => -1,
+        };
+        let synced = match wrapper.backend() {
+            RequestBackend::Kv => self.kv_storage.after_sync(wrapper, revision).await,
+            RequestBackend::Auth => self
+                .auth_storage
+                .after_sync(wrapper, revision)
+                .map(|(res, ops)| (res, ops, Vec::new(), Vec::new())),
+            RequestBackend::Lease => self
+                .lease_storage
+                .after_sync(wrapper, revision)
+                .map(|(res, ops, events)| (res, ops, events, Vec::new())),
+            RequestBackend::Alarm => {
+                let (res, ops) = self.alarm_storage.after_sync(wrapper, revision);
+                Ok((res, ops, Vec::new(), Vec::new()))
+            }
+        };
+        let (res, ops, events, txn_deletions) = match synced {
+            Ok(synced) => synced,
+            Err(e) => {
+                // the command changes nothing, but it is applied
+                let _ignore = self.persist(index, general_revision, Vec::new()).await?;
+                return Err(e);
+            }
+        };
+        if let RequestWrapper::CompactionRequest(ref compact_req) = *wrapper {
+            if compact_req.physical {
+                if let Some(n) = self.compact_events.get(&cmd.compact_id()) {
+                    let _ignore = n.notify(usize::MAX);
+                }
+            }
+        };
+        let key_revisions = self.persist(index, general_revision, ops).await?;
+        if !key_revisions.is_empty() {
+            self.kv_storage.insert_index(key_revisions);
+        }
+        // watchers replay the revisions they missed from the storage, so the events
+        // are only notified once they are persisted and visible in the index
+        if !events.is_empty() {
+            let floor = self
+                .pending_revisions
+                .lock()
+                .first()
+                .copied()
+                .unwrap_or(revision);
+            self.kv_storage
+                .notify_updates(revision, events, floor)
+                .await;
+        }
+        if wrapper.backend() == RequestBackend::Auth {
+            self.auth_storage.invalidate_permission_checks();
+        }
+        self.audit_log.record_command(cmd, &txn_deletions, revision);
+        self.lease_storage.mark_lease_synced(wrapper);
+        if !quota_enough {
+            if let Some(alarmer) = self.alarmer.read().clone() {
+                let _ig = tokio::spawn(async move {
+                    if let Err(e) = alarmer
+                        .alarm(AlarmAction::Activate, AlarmType::Nospace)
+                        .await
+                    {
+                        warn!("{} propose alarm failed: {:?}", alarmer.id, e);
+                    }
+                });
+            }
+        }
+        Ok(res)
+    }
+
+    /// Get the applied indexes of the state machine
+    pub(crate) fn applied_indexes(&self) -> AppliedIndexes {
+        self.applied_indexes.lock().clone()
     }
 
     /// Set alarmer
@@ -267,16 +539,28 @@ where
         *self.alarmer.write() = Some(alarmer);
     }
 
+    /// Get the quota checker
+    pub(crate) fn quota_checker(&self) -> Arc<dyn QuotaChecker> {
+        Arc::clone(&self.quota_checker)
+    }
+
     /// Check if the alarm is activated
     fn check_alarm(&self, cmd: &Command) -> Result<(), ExecuteError> {
         #[allow(clippy::wildcard_enum_match_arm)]
         match *cmd.request() {
-            RequestWrapper::PutRequest(_)
-            | RequestWrapper::TxnRequest(_)
-            | RequestWrapper::LeaseGrantRequest(_) => match self.alarm_storage.current_alarm() {
+            RequestWrapper::PutRequest(_) | RequestWrapper::LeaseGrantRequest(_) => {
+                match self.alarm_storage.current_alarm() {
+                    AlarmType::Corrupt => Err(ExecuteError::DbError("Corrupt".to_owned())),
+                    AlarmType::Nospace => Err(ExecuteError::Nospace),
+                    AlarmType::None => Ok(()),
+                }
+            }
+
+            // txns that only read or delete keys are still allowed when space is exhausted
+            RequestWrapper::TxnRequest(ref txn_req) => match self.alarm_storage.current_alarm() {
                 AlarmType::Corrupt => Err(ExecuteError::DbError("Corrupt".to_owned())),
-                AlarmType::Nospace => Err(ExecuteError::Nospace),
-                AlarmType::None => Ok(()),
+                AlarmType::Nospace if txn_has_put(txn_req) => Err(ExecuteError::Nospace),
+                AlarmType::Nospace | AlarmType::None => Ok(()),
             },
 
             RequestWrapper::RangeRequest(_)
@@ -292,11 +576,60 @@ where
     }
 }
 
+/// Persist stage of the command executor. The changes of the commands are executed and
+/// encoded concurrently in `after_sync`, and this stage writes the changes that are
+/// queued in the meantime in one batch, so a write to the storage is shared by many
+/// commands. The watchers are notified by the `KvWatcher` through another queue.
+#[allow(clippy::arithmetic_side_effects, clippy::ignored_unit_patterns)] // introduced by tokio::select! macro
+pub(crate) async fn persist_task<S: StorageApi>(
+    ce: Arc<CommandExecutor<S>>,
+    mut persist_rx: mpsc::Receiver<PersistTask>,
+    shutdown_listener: Listener,
+) {
+    loop {
+        let task = tokio::select! {
+            task = persist_rx.recv() => task,
+            _ = shutdown_listener.wait() => None,
+        };
+        let Some(task) = task else {
+            break;
+        };
+        let mut tasks = vec![task];
+        while tasks.len() < MAX_PERSIST_GROUP {
+            let Ok(task) = persist_rx.try_recv() else {
+                break;
+            };
+            tasks.push(task);
+        }
+        ce.write_group(tasks);
+    }
+    // write the commands that are already queued, the later ones are written by
+    // `after_sync` itself
+    persist_rx.close();
+    while let Ok(task) = persist_rx.try_recv() {
+        ce.write_group(vec![task]);
+    }
+}
+
+/// Check if the txn request contains any put operation
+fn txn_has_put(txn_req: &TxnRequest) -> bool {
+    txn_req
+        .success
+        .iter()
+        .chain(txn_req.failure.iter())
+        .any(|op| match op.request {
+            Some(Request::RequestPut(_)) => true,
+            Some(Request::RequestTxn(ref req)) => txn_has_put(req),
+            Some(Request::RequestRange(_) | Request::RequestDeleteRange(_)) | None => false,
+        })
+}
+
 #[async_trait::async_trait]
 impl<S> CurpCommandExecutor<Command> for CommandExecutor<S>
 where
     S: StorageApi,
 {
+    #[instrument(skip_all, name = "xline_prepare")]
     fn prepare(
         &self,
         cmd: &Command,
@@ -317,7 +650,14 @@ where
                 if wrapper.skip_general_revision() {
                     -1
                 } else {
-                    self.general_rev.next()
+                    // the log replayed after a restart is prepared in the same order from
+                    // the revision at the applied index, so a command that was applied out
+                    // of order before the restart gets its stored revision again
+                    let mut pending = self.pending_revisions.lock();
+                    let revision = self.general_rev.next();
+                    let _ignore = pending.insert(revision);
+                    self.overload_monitor.set_apply_queue_depth(pending.len());
+                    revision
                 }
             }
             RequestBackend::Alarm => -1,
@@ -325,6 +665,7 @@ where
         Ok(revision)
     }
 
+    #[instrument(skip_all, name = "xline_execute")]
     async fn execute(
         &self,
         cmd: &Command,
@@ -338,54 +679,24 @@ where
         }
     }
 
+    #[instrument(skip_all, name = "xline_after_sync")]
     async fn after_sync(
         &self,
         cmd: &Command,
         index: LogIndex,
         revision: i64,
     ) -> Result<<Command as CurpCommand>::ASR, <Command as CurpCommand>::Error> {
-        let quota_enough = self.quota_checker.check(cmd);
-        let mut ops = vec![WriteOp::PutAppliedIndex(index)];
-        let wrapper = cmd.request();
-        let (res, mut wr_ops) = match wrapper.backend() {
-            RequestBackend::Kv => self.kv_storage.after_sync(wrapper, revision).await?,
-            RequestBackend::Auth => self.auth_storage.after_sync(wrapper, revision)?,
-            RequestBackend::Lease => self.lease_storage.after_sync(wrapper, revision).await?,
-            RequestBackend::Alarm => self.alarm_storage.after_sync(wrapper, revision),
-        };
-        if let RequestWrapper::CompactionRequest(ref compact_req) = *wrapper {
-            if compact_req.physical {
-                if let Some(n) = self.compact_events.get(&cmd.compact_id()) {
-                    let _ignore = n.notify(usize::MAX);
-                }
-            }
-        };
-        if let RequestWrapper::CompactionRequest(ref compact_req) = *wrapper {
-            if compact_req.physical {
-                if let Some(n) = self.compact_events.get(&cmd.compact_id()) {
-                    let _ignore = n.notify(usize::MAX);
-                }
-            }
-        };
-        ops.append(&mut wr_ops);
-        let key_revisions = self.persistent.flush_ops(ops)?;
-        if !key_revisions.is_empty() {
-            self.kv_storage.insert_index(key_revisions);
-        }
-        self.lease_storage.mark_lease_synced(wrapper);
-        if !quota_enough {
-            if let Some(alarmer) = self.alarmer.read().clone() {
-                let _ig = tokio::spawn(async move {
-                    if let Err(e) = alarmer
-                        .alarm(AlarmAction::Activate, AlarmType::Nospace)
-                        .await
-                    {
-                        warn!("{} propose alarm failed: {:?}", alarmer.id, e);
-                    }
-                });
-            }
+        let res = self.sync_cmd(cmd, index, revision).await;
+        if matches!(
+            cmd.request().backend(),
+            RequestBackend::Kv | RequestBackend::Lease
+        ) {
+            // the revision is persisted and inserted into the index, or never used
+            let mut pending = self.pending_revisions.lock();
+            let _ignore = pending.remove(&revision);
+            self.overload_monitor.set_apply_queue_depth(pending.len());
         }
-        Ok(res)
+        res
     }
 
     async fn reset(
@@ -393,14 +704,27 @@ where
         snapshot: Option<(Snapshot, LogIndex)>,
     ) -> Result<(), <Command as CurpCommand>::Error> {
         let s = if let Some((snapshot, index)) = snapshot {
-            _ = self
-                .persistent
-                .flush_ops(vec![WriteOp::PutAppliedIndex(index)])?;
+            _ = self.persistent.write_batch(
+                vec![
+                    WriteOp::PutAppliedIndex(index),
+                    WriteOp::PutOutOfOrderApplied(Vec::new()),
+                ],
+                false,
+            )?;
             Some(snapshot)
         } else {
             None
         };
-        self.persistent.reset(s).await
+        {
+            // the in-memory index is not rebuilt, so it can not be snapshotted any more
+            let _applied_indexes = self.applied_indexes.lock();
+            self.index_snapshots.store(false, Ordering::Release);
+        }
+        // the pending range tombstones belong to the storage before the reset
+        self.kv_storage.clear_range_tombstones();
+        self.persistent.reset(s).await?;
+        *self.applied_indexes.lock() = AppliedIndexes::load(self.persistent.as_ref())?;
+        Ok(())
     }
 
     async fn snapshot(&self) -> Result<Snapshot, <Command as CurpCommand>::Error> {
@@ -409,9 +733,7 @@ where
     }
 
     fn set_last_applied(&self, index: LogIndex) -> Result<(), <Command as CurpCommand>::Error> {
-        _ = self
-            .persistent
-            .flush_ops(vec![WriteOp::PutAppliedIndex(index)])?;
+        _ = self.write_applied(index, Vec::new())?;
         Ok(())
     }
 
@@ -433,7 +755,7 @@ where
 
 #[cfg(test)]
 mod test {
-    use xlineapi::{LeaseGrantRequest, PutRequest, Request, RequestOp, TxnRequest};
+    use xlineapi::{DeleteRangeRequest, LeaseGrantRequest, PutRequest, RequestOp};
 
     use super::*;
     #[test]
@@ -473,4 +795,39 @@ mod test {
             assert_eq!(size_estimate::cmd_size(req), size);
         }
     }
+
+    #[test]
+    fn txn_has_put_should_detect_nested_put() {
+        let delete_op = RequestOp {
+            request: Some(Request::RequestDeleteRange(DeleteRangeRequest {
+                key: b"key".to_vec(),
+                ..Default::default()
+            })),
+        };
+        let put_op = RequestOp {
+            request: Some(Request::RequestPut(PutRequest {
+                key: b"key".to_vec(),
+                value: b"value".to_vec(),
+                ..Default::default()
+            })),
+        };
+        let delete_only = TxnRequest {
+            compare: vec![],
+            success: vec![delete_op.clone()],
+            failure: vec![],
+        };
+        assert!(!txn_has_put(&delete_only));
+        let nested_put = TxnRequest {
+            compare: vec![],
+            success: vec![delete_op],
+            failure: vec![RequestOp {
+                request: Some(Request::RequestTxn(TxnRequest {
+                    compare: vec![],
+                    success: vec![put_op],
+                    failure: vec![],
+                })),
+            }],
+        };
+        assert!(txn_has_put(&nested_put));
+    }
 }
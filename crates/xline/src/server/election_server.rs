@@ -0,0 +1,482 @@
+use std::{pin::Pin, sync::Arc};
+
+use async_stream::{stream, try_stream};
+use clippy_utilities::OverflowArithmetic;
+use futures::stream::Stream;
+#[cfg(not(madsim))]
+use tonic::transport::ClientTlsConfig;
+use tonic::transport::{Channel, Endpoint};
+use tracing::debug;
+use utils::build_endpoint;
+#[cfg(madsim)]
+use utils::ClientTlsConfig;
+use xlineapi::{
+    command::{Command, CommandResponse, CurpClient, KeyRange, SyncResponse},
+    execute_error::ExecuteError,
+    AuthInfo, EventType,
+};
+
+use super::stream_shutdown::StreamShutdown;
+use crate::{
+    id_gen::IdGenerator,
+    rpc::{
+        CampaignRequest, CampaignResponse, Compare, CompareResult, CompareTarget,
+        DeleteRangeRequest, DeleteRangeResponse, Election, KeyValue, LeaderKey, LeaderRequest,
+        LeaderResponse, LeaseGrantRequest, LeaseGrantResponse, ProclaimRequest, ProclaimResponse,
+        PutRequest, RangeRequest, RangeResponse, Request, RequestOp, RequestUnion, RequestWrapper,
+        ResignRequest, ResignResponse, Response, ResponseHeader, SortOrder, SortTarget,
+        TargetUnion, TxnRequest, TxnResponse, WatchClient, WatchCreateRequest, WatchRequest,
+        WatchResponse,
+    },
+    storage::{storage_api::StorageApi, AuthStore},
+};
+
+/// Default session ttl
+const DEFAULT_SESSION_TTL: i64 = 60;
+
+/// Election Server
+pub(super) struct ElectionServer<S>
+where
+    S: StorageApi,
+{
+    /// Consensus client
+    client: Arc<CurpClient>,
+    /// Auth store
+    auth_store: Arc<AuthStore<S>>,
+    /// Id Generator
+    id_gen: Arc<IdGenerator>,
+    /// Server addresses
+    addrs: Vec<Endpoint>,
+    /// Ends the observe streams on graceful shutdown
+    stream_shutdown: StreamShutdown,
+}
+
+impl<S> ElectionServer<S>
+where
+    S: StorageApi,
+{
+    /// New `ElectionServer`
+    pub(super) fn new(
+        client: Arc<CurpClient>,
+        auth_store: Arc<AuthStore<S>>,
+        id_gen: Arc<IdGenerator>,
+        addrs: &[String],
+        client_tls_config: Option<&ClientTlsConfig>,
+        stream_shutdown: StreamShutdown,
+    ) -> Self {
+        let addrs = addrs
+            .iter()
+            .map(|addr| {
+                build_endpoint(addr, client_tls_config)
+                    .unwrap_or_else(|_e| panic!("invalid address: {addr}"))
+            })
+            .collect();
+        Self {
+            client,
+            auth_store,
+            id_gen,
+            addrs,
+            stream_shutdown,
+        }
+    }
+
+    /// Wait until all keys created before `my_rev` under the election prefix are deleted
+    async fn wait_delete(
+        &self,
+        prefix: &[u8],
+        my_rev: i64,
+        auth_info: Option<&AuthInfo>,
+    ) -> Result<(), tonic::Status> {
+        let rev = my_rev.overflow_sub(1);
+        let mut watch_client =
+            WatchClient::new(Channel::balance_list(self.addrs.clone().into_iter()));
+        loop {
+            #[allow(clippy::as_conversions)] // this cast is always safe
+            let get_req = RangeRequest {
+                key: prefix.to_vec(),
+                range_end: KeyRange::get_prefix(prefix),
+                limit: 1,
+                sort_order: SortOrder::Descend as i32,
+                sort_target: SortTarget::Create as i32,
+                max_create_revision: rev,
+                ..Default::default()
+            };
+            let (cmd_res, _sync_res) =
+                propose(&self.client, get_req, auth_info.cloned(), false).await?;
+            let response = Into::<RangeResponse>::into(cmd_res.into_inner());
+            let Some(last_kv) = response.kvs.first() else {
+                return Ok(());
+            };
+            let mut response_stream = watch(
+                &mut watch_client,
+                last_kv.key.clone(),
+                vec![],
+                last_kv.mod_revision.overflow_add(1),
+            )
+            .await?;
+            while let Some(watch_res) = response_stream.message().await? {
+                if watch_res
+                    .events
+                    .iter()
+                    .any(|e| e.r#type == i32::from(EventType::Delete))
+                {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Delete key
+    async fn delete_key(
+        &self,
+        key: &[u8],
+        auth_info: Option<AuthInfo>,
+    ) -> Result<Option<ResponseHeader>, tonic::Status> {
+        let del_req = DeleteRangeRequest {
+            key: key.into(),
+            ..Default::default()
+        };
+        let (cmd_res, _) = propose(&self.client, del_req, auth_info, true).await?;
+        let res = Into::<DeleteRangeResponse>::into(cmd_res.into_inner());
+        Ok(res.header)
+    }
+
+    /// Lease grant
+    async fn lease_grant(&self, auth_info: Option<AuthInfo>) -> Result<i64, tonic::Status> {
+        let lease_id = self.id_gen.next();
+        let lease_grant_req = LeaseGrantRequest {
+            ttl: DEFAULT_SESSION_TTL,
+            id: lease_id,
+        };
+        let (cmd_res, _) = propose(&self.client, lease_grant_req, auth_info, true).await?;
+        let res = Into::<LeaseGrantResponse>::into(cmd_res.into_inner());
+        Ok(res.id)
+    }
+}
+
+/// Propose request and get result with fast/slow path
+async fn propose<T>(
+    client: &CurpClient,
+    request: T,
+    auth_info: Option<AuthInfo>,
+    use_fast_path: bool,
+) -> Result<(CommandResponse, Option<SyncResponse>), tonic::Status>
+where
+    T: Into<RequestWrapper>,
+{
+    let request = request.into();
+    let cmd = Command::new_with_auth_info(request.keys(), request, auth_info);
+    let res = client.propose(&cmd, None, use_fast_path).await??;
+    Ok(res)
+}
+
+/// Watch a key or a range from the given revision
+async fn watch(
+    watch_client: &mut WatchClient<Channel>,
+    key: Vec<u8>,
+    range_end: Vec<u8>,
+    start_revision: i64,
+) -> Result<tonic::Streaming<WatchResponse>, tonic::Status> {
+    let request_stream = stream! {
+        yield WatchRequest {
+            request_union: Some(RequestUnion::CreateRequest(WatchCreateRequest {
+                key,
+                range_end,
+                start_revision,
+                ..Default::default()
+            })),
+        };
+        // keep the request stream open, otherwise the watch will be closed by the server
+        futures::future::pending::<()>().await;
+    };
+    Ok(watch_client.watch(request_stream).await?.into_inner())
+}
+
+/// Get the prefix of the keys of an election
+fn election_prefix(name: &[u8]) -> Vec<u8> {
+    let mut prefix = name.to_vec();
+    prefix.push(b'/');
+    prefix
+}
+
+/// Build a `Compare` that checks the create revision of a key
+fn create_revision_cmp(key: &[u8], revision: i64) -> Compare {
+    #[allow(clippy::as_conversions)] // this cast is always safe
+    Compare {
+        result: CompareResult::Equal as i32,
+        target: CompareTarget::Create as i32,
+        key: key.to_vec(),
+        range_end: vec![],
+        target_union: Some(TargetUnion::CreateRevision(revision)),
+    }
+}
+
+/// Build a `RequestOp` that puts a key with a lease
+fn put_op(key: Vec<u8>, value: Vec<u8>, lease: i64) -> RequestOp {
+    RequestOp {
+        request: Some(Request::RequestPut(PutRequest {
+            key,
+            value,
+            lease,
+            ..Default::default()
+        })),
+    }
+}
+
+/// Get the current leader of an election, which is the key with the smallest create
+/// revision under the election prefix
+async fn leader_kv(
+    client: &CurpClient,
+    prefix: &[u8],
+    auth_info: Option<AuthInfo>,
+) -> Result<(Option<ResponseHeader>, Option<KeyValue>), tonic::Status> {
+    #[allow(clippy::as_conversions)] // this cast is always safe
+    let get_req = RangeRequest {
+        key: prefix.to_vec(),
+        range_end: KeyRange::get_prefix(prefix),
+        sort_order: SortOrder::Ascend as i32,
+        sort_target: SortTarget::Create as i32,
+        limit: 1,
+        ..Default::default()
+    };
+    let (cmd_res, _) = propose(client, get_req, auth_info, true).await?;
+    let mut res = Into::<RangeResponse>::into(cmd_res.into_inner());
+    Ok((res.header, res.kvs.pop()))
+}
+
+#[tonic::async_trait]
+impl<S> Election for ElectionServer<S>
+where
+    S: StorageApi,
+{
+    /// Campaign waits to acquire leadership in an election, returning a LeaderKey
+    /// representing the leadership if successful. The LeaderKey can then be used
+    /// to issue new values on the election, transactionally guard API requests on
+    /// leadership still being held, and resign from the election.
+    async fn campaign(
+        &self,
+        request: tonic::Request<CampaignRequest>,
+    ) -> Result<tonic::Response<CampaignResponse>, tonic::Status> {
+        debug!("Receive CampaignRequest {:?}", request);
+        let auth_info = self.auth_store.try_get_auth_info_from_request(&request)?;
+        let campaign_req = request.into_inner();
+        let lease_id = if campaign_req.lease == 0 {
+            self.lease_grant(auth_info.clone()).await?
+        } else {
+            campaign_req.lease
+        };
+
+        let prefix = election_prefix(&campaign_req.name);
+        let mut key = prefix.clone();
+        key.extend_from_slice(format!("{lease_id:x}").as_bytes());
+
+        let txn = TxnRequest {
+            compare: vec![create_revision_cmp(&key, 0)],
+            success: vec![put_op(key.clone(), campaign_req.value.clone(), lease_id)],
+            failure: vec![RequestOp {
+                request: Some(Request::RequestRange(RangeRequest {
+                    key: key.clone(),
+                    ..Default::default()
+                })),
+            }],
+        };
+        let (cmd_res, sync_res) = propose(&self.client, txn, auth_info.clone(), false).await?;
+        let mut txn_res = Into::<TxnResponse>::into(cmd_res.into_inner());
+        let rev = if txn_res.succeeded {
+            #[allow(clippy::unwrap_used)] // sync_res always has value when use slow path
+            sync_res.unwrap().revision()
+        } else {
+            // campaign again with the key created before, and update its value if needed
+            let kv = txn_res
+                .responses
+                .pop()
+                .and_then(|r| {
+                    if let Some(Response::ResponseRange(res)) = r.response {
+                        res.kvs.into_iter().next()
+                    } else {
+                        None
+                    }
+                })
+                .ok_or(ExecuteError::LeaseExpired(lease_id))?;
+            if kv.value != campaign_req.value {
+                let put_req = PutRequest {
+                    key: key.clone(),
+                    value: campaign_req.value,
+                    lease: lease_id,
+                    ..Default::default()
+                };
+                let _ignore = propose(&self.client, put_req, auth_info.clone(), false).await?;
+            }
+            kv.create_revision
+        };
+
+        if let Err(e) = self.wait_delete(&prefix, rev, auth_info.as_ref()).await {
+            let _ignore = self.delete_key(&key, auth_info).await;
+            return Err(e);
+        }
+        let range_req = RangeRequest {
+            key: key.clone(),
+            ..Default::default()
+        };
+        let header = match propose(&self.client, range_req, auth_info.clone(), true).await {
+            Ok((cmd_res, _)) => {
+                let res = Into::<RangeResponse>::into(cmd_res.into_inner());
+                if res.kvs.is_empty() {
+                    return Err(ExecuteError::LeaseExpired(lease_id).into());
+                }
+                res.header
+            }
+            Err(e) => {
+                let _ignore = self.delete_key(&key, auth_info).await;
+                return Err(e);
+            }
+        };
+        let res = CampaignResponse {
+            header,
+            leader: Some(LeaderKey {
+                name: campaign_req.name,
+                key,
+                rev,
+                lease: lease_id,
+            }),
+        };
+        Ok(tonic::Response::new(res))
+    }
+
+    /// Proclaim updates the leader's posted value with a new value.
+    async fn proclaim(
+        &self,
+        request: tonic::Request<ProclaimRequest>,
+    ) -> Result<tonic::Response<ProclaimResponse>, tonic::Status> {
+        debug!("Receive ProclaimRequest {:?}", request);
+        let auth_info = self.auth_store.try_get_auth_info_from_request(&request)?;
+        let proclaim_req = request.into_inner();
+        let leader = proclaim_req
+            .leader
+            .ok_or_else(|| tonic::Status::invalid_argument(r#""leader" field must be provided"#))?;
+        let txn = TxnRequest {
+            compare: vec![create_revision_cmp(&leader.key, leader.rev)],
+            success: vec![put_op(leader.key, proclaim_req.value, leader.lease)],
+            failure: vec![],
+        };
+        let (cmd_res, _) = propose(&self.client, txn, auth_info, false).await?;
+        let txn_res = Into::<TxnResponse>::into(cmd_res.into_inner());
+        if !txn_res.succeeded {
+            return Err(tonic::Status::failed_precondition("election: not leader"));
+        }
+        Ok(tonic::Response::new(ProclaimResponse {
+            header: txn_res.header,
+        }))
+    }
+
+    /// Leader returns the current election proclamation, if any.
+    async fn leader(
+        &self,
+        request: tonic::Request<LeaderRequest>,
+    ) -> Result<tonic::Response<LeaderResponse>, tonic::Status> {
+        debug!("Receive LeaderRequest {:?}", request);
+        let auth_info = self.auth_store.try_get_auth_info_from_request(&request)?;
+        let prefix = election_prefix(&request.into_inner().name);
+        let (header, kv) = leader_kv(&self.client, &prefix, auth_info).await?;
+        if kv.is_none() {
+            return Err(tonic::Status::not_found("election: no leader"));
+        }
+        Ok(tonic::Response::new(LeaderResponse { header, kv }))
+    }
+
+    /// Server streaming response type for the Observe method.
+    type ObserveStream = Pin<Box<dyn Stream<Item = Result<LeaderResponse, tonic::Status>> + Send>>;
+
+    /// Observe streams election proclamations in-order as made by the election's
+    /// elected leaders.
+    async fn observe(
+        &self,
+        request: tonic::Request<LeaderRequest>,
+    ) -> Result<tonic::Response<Self::ObserveStream>, tonic::Status> {
+        debug!("Receive ObserveRequest {:?}", request);
+        let auth_info = self.auth_store.try_get_auth_info_from_request(&request)?;
+        let prefix = election_prefix(&request.into_inner().name);
+        let client = Arc::clone(&self.client);
+        let mut watch_client =
+            WatchClient::new(Channel::balance_list(self.addrs.clone().into_iter()));
+        let stream = try_stream! {
+            loop {
+                let (header, leader) = leader_kv(&client, &prefix, auth_info.clone()).await?;
+                let Some(leader) = leader else {
+                    // no leader yet, wait for a candidate to show up
+                    let revision = header.map_or(0, |h| h.revision);
+                    let mut response_stream = watch(
+                        &mut watch_client,
+                        prefix.clone(),
+                        KeyRange::get_prefix(&prefix),
+                        revision.overflow_add(1),
+                    )
+                    .await?;
+                    while let Some(watch_res) = response_stream.message().await? {
+                        if watch_res
+                            .events
+                            .iter()
+                            .any(|e| e.r#type == i32::from(EventType::Put))
+                        {
+                            break;
+                        }
+                    }
+                    continue;
+                };
+                let mut response_stream = watch(
+                    &mut watch_client,
+                    leader.key.clone(),
+                    vec![],
+                    leader.mod_revision.overflow_add(1),
+                )
+                .await?;
+                yield LeaderResponse {
+                    header,
+                    kv: Some(leader),
+                };
+                // stream the proclamations of the current leader until it resigns or expires
+                'observe: while let Some(watch_res) = response_stream.message().await? {
+                    for event in watch_res.events {
+                        if event.r#type == i32::from(EventType::Delete) {
+                            break 'observe;
+                        }
+                        yield LeaderResponse {
+                            header: watch_res.header.clone(),
+                            kv: event.kv,
+                        };
+                    }
+                }
+            }
+        };
+        Ok(tonic::Response::new(self.stream_shutdown.guard(stream)))
+    }
+
+    /// Resign releases election leadership so other campaigners may acquire
+    /// leadership on the election.
+    async fn resign(
+        &self,
+        request: tonic::Request<ResignRequest>,
+    ) -> Result<tonic::Response<ResignResponse>, tonic::Status> {
+        debug!("Receive ResignRequest {:?}", request);
+        let auth_info = self.auth_store.try_get_auth_info_from_request(&request)?;
+        let leader = request
+            .into_inner()
+            .leader
+            .ok_or_else(|| tonic::Status::invalid_argument(r#""leader" field must be provided"#))?;
+        let txn = TxnRequest {
+            compare: vec![create_revision_cmp(&leader.key, leader.rev)],
+            success: vec![RequestOp {
+                request: Some(Request::RequestDeleteRange(DeleteRangeRequest {
+                    key: leader.key,
+                    ..Default::default()
+                })),
+            }],
+            failure: vec![],
+        };
+        let (cmd_res, _) = propose(&self.client, txn, auth_info, false).await?;
+        let txn_res = Into::<TxnResponse>::into(cmd_res.into_inner());
+        Ok(tonic::Response::new(ResignResponse {
+            header: txn_res.header,
+        }))
+    }
+}
@@ -0,0 +1,281 @@
+use std::sync::Arc;
+
+use curp::members::{ClusterInfo, NegotiatedVersion};
+use tonic::Status;
+use tracing::{info, warn};
+use utils::{table_names::META_TABLE, task_manager::Listener, version::Version};
+use xlineapi::{execute_error::ExecuteError, DowngradeAction};
+
+use crate::storage::{db::WriteOp, storage_api::StorageApi};
+
+/// Key of the negotiated version of the cluster in the meta table
+pub(crate) const NEGOTIATED_VERSION_KEY: &str = "negotiated_version";
+/// Key of the target version of the downgrade in progress in the meta table
+pub(crate) const DOWNGRADE_TARGET_KEY: &str = "downgrade_target";
+
+/// Features whose wire format is not understood by older versions of xline, they are
+/// enabled only after every member of the cluster is upgraded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub(crate) enum Feature {
+    /// Propagate the qos classes of the requests along with the proposed commands
+    QosClass,
+}
+
+impl Feature {
+    /// The min negotiated version to enable the feature
+    fn min_version(self) -> Version {
+        match self {
+            Feature::QosClass => Version::new(0, 6, 1),
+        }
+    }
+}
+
+/// Binary version of this member
+pub(crate) fn binary_version() -> Version {
+    env!("CARGO_PKG_VERSION")
+        .parse()
+        .unwrap_or_else(|e| unreachable!("the crate version should be valid, {e}"))
+}
+
+/// Gate of the features by the version negotiated by the cluster, the min binary version
+/// of all members, so that a rolling upgrade never sends a member what it can't read
+#[derive(Debug)]
+pub(crate) struct FeatureGate {
+    /// Cluster info, where the negotiated version is kept
+    cluster_info: Arc<ClusterInfo>,
+}
+
+impl FeatureGate {
+    /// New `FeatureGate`
+    pub(crate) fn new(cluster_info: Arc<ClusterInfo>) -> Self {
+        Self { cluster_info }
+    }
+
+    /// Check if a feature is enabled by the negotiated version, no feature is enabled
+    /// before the version is negotiated
+    pub(crate) fn is_enabled(&self, feature: Feature) -> bool {
+        self.cluster_info
+            .negotiated_version()
+            .map_or(false, |version| version >= feature.min_version())
+    }
+}
+
+/// Get a version persisted in the meta table
+fn get_version<S: StorageApi>(db: &S, key: &str) -> Result<Option<Version>, ExecuteError> {
+    db.get_value(META_TABLE, key)?
+        .map(|bytes| {
+            String::from_utf8(bytes)
+                .ok()
+                .and_then(|s| s.parse().ok())
+                .ok_or_else(|| ExecuteError::DbError(format!("cannot decode the {key}")))
+        })
+        .transpose()
+}
+
+/// Load the negotiated version persisted by the last run and advertise the binary version
+/// of this member
+///
+/// # Errors
+///
+/// Return `ExecuteError::DbError` if the persisted version can't be decoded, or the
+/// binary is older than the negotiated version, which means the storage is written by
+/// a newer version and the cluster is not downgraded to this version
+pub(crate) fn recover_negotiated_version<S: StorageApi>(
+    db: &S,
+    cluster_info: &ClusterInfo,
+) -> Result<(), ExecuteError> {
+    let binary_version = binary_version();
+    let version = get_version(db, NEGOTIATED_VERSION_KEY)?;
+    if let Some(version) = version.filter(|version| *version > binary_version) {
+        return Err(ExecuteError::DbError(format!(
+            "the cluster has negotiated version {version}, which is newer than the version \
+             {binary_version} of this binary, enable a downgrade of the cluster first"
+        )));
+    }
+    if let Some(target) = get_version(db, DOWNGRADE_TARGET_KEY)? {
+        cluster_info.enable_downgrade(target);
+    } else if let Some(version) = version {
+        let _raised = cluster_info.raise_negotiated_version(version);
+    }
+    cluster_info.set_binary_version(binary_version);
+    info!("binary version of this member is {binary_version}");
+    Ok(())
+}
+
+/// Persist the negotiated version and the downgrade whenever they change
+pub(crate) async fn persist_negotiated_version_task<S: StorageApi>(
+    cluster_info: Arc<ClusterInfo>,
+    db: Arc<S>,
+    shutdown_listener: Listener,
+) {
+    let mut version_rx = cluster_info.negotiated_version_rx();
+    loop {
+        tokio::select! {
+            res = version_rx.changed() => {
+                if res.is_err() {
+                    return;
+                }
+            }
+            _ = shutdown_listener.wait() => return,
+        }
+        let negotiated = *version_rx.borrow_and_update();
+        let Some(version) = negotiated.version else {
+            continue;
+        };
+        let ops = vec![
+            WriteOp::PutNegotiatedVersion(version),
+            WriteOp::PutDowngradeTarget(negotiated.downgrade_target),
+        ];
+        if let Err(e) = db.write_batch(ops, true) {
+            warn!("failed to persist the negotiated version {version}, {e}");
+        }
+    }
+}
+
+/// Parse the target version of a downgrade, which is in the form of `major.minor` or
+/// `major.minor.patch`, the patch is ignored
+fn parse_downgrade_target(target: &str) -> Result<Version, Status> {
+    let version: Version = if target.matches('.').count() == 1 {
+        format!("{target}.0").parse()
+    } else {
+        target.parse()
+    }
+    .map_err(|_e| Status::invalid_argument("etcdserver: wrong downgrade target version format"))?;
+    Ok(Version::new(version.major, version.minor, 0))
+}
+
+/// Handle a downgrade action at the leader like etcd: a downgrade can only target the
+/// previous minor version of the negotiated version, and only one downgrade is in
+/// progress at a time. Return the negotiated version before the action.
+pub(crate) fn downgrade(
+    cluster_info: &ClusterInfo,
+    action: DowngradeAction,
+    target: &str,
+) -> Result<Version, Status> {
+    let NegotiatedVersion {
+        version,
+        downgrade_target,
+        ..
+    } = cluster_info.negotiated();
+    let version = version.ok_or_else(|| {
+        Status::unavailable("etcdserver: cluster version not found during downgrade")
+    })?;
+    if action == DowngradeAction::Cancel {
+        if !cluster_info.cancel_downgrade() {
+            return Err(Status::failed_precondition(
+                "etcdserver: no inflight downgrade job",
+            ));
+        }
+        return Ok(version);
+    }
+    let target = parse_downgrade_target(target)?;
+    if downgrade_target.is_some() {
+        return Err(Status::failed_precondition(
+            "etcdserver: cluster has a downgrade job in progress",
+        ));
+    }
+    if target.major != version.major || target.minor.saturating_add(1) != version.minor {
+        return Err(Status::invalid_argument(
+            "etcdserver: invalid downgrade target version",
+        ));
+    }
+    if action == DowngradeAction::Enable {
+        cluster_info.enable_downgrade(target);
+    }
+    Ok(version)
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use utils::config::EngineConfig;
+
+    use super::*;
+    use crate::storage::db::DB;
+
+    fn single_member_cluster() -> ClusterInfo {
+        ClusterInfo::from_members_map(
+            HashMap::from([("S1".to_owned(), vec!["S1".to_owned()])]),
+            [],
+            "S1",
+        )
+    }
+
+    #[test]
+    fn features_should_be_gated_by_the_negotiated_version() {
+        let cluster_info = Arc::new(single_member_cluster());
+        let gate = FeatureGate::new(Arc::clone(&cluster_info));
+        assert!(!gate.is_enabled(Feature::QosClass));
+        let _raised = cluster_info.raise_negotiated_version(Version::new(0, 6, 0));
+        assert!(!gate.is_enabled(Feature::QosClass));
+        let _raised = cluster_info.raise_negotiated_version(Feature::QosClass.min_version());
+        assert!(gate.is_enabled(Feature::QosClass));
+    }
+
+    #[test]
+    fn negotiated_version_should_be_recovered() -> Result<(), ExecuteError> {
+        let db = DB::open(&EngineConfig::Memory)?;
+        let cluster_info = single_member_cluster();
+        recover_negotiated_version(db.as_ref(), &cluster_info)?;
+        assert_eq!(cluster_info.binary_version(), Some(binary_version()));
+        assert_eq!(cluster_info.negotiated_version(), None);
+
+        let _ignore = db.write_batch(
+            vec![WriteOp::PutNegotiatedVersion(Version::new(0, 1, 0))],
+            true,
+        )?;
+        let cluster_info = single_member_cluster();
+        recover_negotiated_version(db.as_ref(), &cluster_info)?;
+        assert_eq!(
+            cluster_info.negotiated_version(),
+            Some(Version::new(0, 1, 0))
+        );
+
+        let target = Version::new(0, 0, 0);
+        let _ignore = db.write_batch(vec![WriteOp::PutDowngradeTarget(Some(target))], true)?;
+        let cluster_info = single_member_cluster();
+        recover_negotiated_version(db.as_ref(), &cluster_info)?;
+        assert_eq!(cluster_info.negotiated_version(), Some(target));
+        assert_eq!(cluster_info.downgrade_target(), Some(target));
+
+        let _ignore = db.write_batch(
+            vec![WriteOp::PutNegotiatedVersion(Version::new(u64::MAX, 0, 0))],
+            true,
+        )?;
+        assert!(recover_negotiated_version(db.as_ref(), &single_member_cluster()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn downgrade_should_target_the_previous_minor_version() {
+        let cluster_info = single_member_cluster();
+        let err = downgrade(&cluster_info, DowngradeAction::Validate, "0.5").unwrap_err();
+        assert_eq!(err.code(), tonic::Code::Unavailable);
+
+        let _raised = cluster_info.raise_negotiated_version(Version::new(0, 6, 1));
+        let err = downgrade(&cluster_info, DowngradeAction::Validate, "0.4").unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+        let err = downgrade(&cluster_info, DowngradeAction::Validate, "v0.5").unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+        let err = downgrade(&cluster_info, DowngradeAction::Cancel, "").unwrap_err();
+        assert_eq!(err.code(), tonic::Code::FailedPrecondition);
+
+        let version = downgrade(&cluster_info, DowngradeAction::Validate, "0.5").unwrap();
+        assert_eq!(version, Version::new(0, 6, 1));
+        assert_eq!(cluster_info.downgrade_target(), None);
+        let _version = downgrade(&cluster_info, DowngradeAction::Enable, "0.5.3").unwrap();
+        assert_eq!(cluster_info.downgrade_target(), Some(Version::new(0, 5, 0)));
+        assert_eq!(
+            cluster_info.negotiated_version(),
+            Some(Version::new(0, 5, 0))
+        );
+        let err = downgrade(&cluster_info, DowngradeAction::Enable, "0.5").unwrap_err();
+        assert_eq!(err.code(), tonic::Code::FailedPrecondition);
+
+        let version = downgrade(&cluster_info, DowngradeAction::Cancel, "").unwrap();
+        assert_eq!(version, Version::new(0, 5, 0));
+        assert_eq!(cluster_info.downgrade_target(), None);
+    }
+}
@@ -0,0 +1,929 @@
+use std::{collections::HashMap, future::Future, mem, sync::Arc};
+
+use anyhow::Result;
+use axum::{
+    extract::State,
+    http::{header::AUTHORIZATION, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use bytes::Bytes;
+use curp::members::ClusterInfo;
+use prost::Message;
+use prost_types::{
+    field_descriptor_proto::{Label, Type},
+    DescriptorProto, EnumDescriptorProto, FieldDescriptorProto, FileDescriptorSet,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Map, Value};
+use tonic::{
+    transport::{Channel, ClientTlsConfig},
+    Code, Status,
+};
+use utils::build_endpoint;
+use xlineapi::{
+    AuthClient, KvClient, LeaseClient, LeaseKeepAliveRequest, MaintenanceClient,
+    FILE_DESCRIPTOR_SET,
+};
+
+use super::build_info::VersionInfo;
+
+/// Protobuf schema of the messages, which translates between the JSON of the grpc-gateway
+/// and the serde layout of the generated types, so that the generated types don't have to
+/// follow the JSON of the grpc-gateway.
+#[derive(Debug, Default)]
+struct Schema {
+    /// Messages by their full names, like `.etcdserverpb.PutRequest`
+    messages: HashMap<String, DescriptorProto>,
+    /// Enums by their full names, like `.etcdserverpb.RangeRequest.SortOrder`
+    enums: HashMap<String, EnumDescriptorProto>,
+}
+
+impl Schema {
+    /// Build the schema from an encoded file descriptor set
+    fn new(encoded: &[u8]) -> Result<Self, prost::DecodeError> {
+        let mut schema = Self::default();
+        for file in FileDescriptorSet::decode(encoded)?.file {
+            let package = format!(".{}", file.package());
+            schema.add_messages(&package, file.message_type);
+            schema.add_enums(&package, file.enum_type);
+        }
+        Ok(schema)
+    }
+
+    /// Add messages and their nested types in the scope of `prefix`
+    fn add_messages(&mut self, prefix: &str, messages: Vec<DescriptorProto>) {
+        for mut message in messages {
+            let name = format!("{prefix}.{}", message.name());
+            self.add_messages(&name, mem::take(&mut message.nested_type));
+            self.add_enums(&name, mem::take(&mut message.enum_type));
+            let _prev = self.messages.insert(name, message);
+        }
+    }
+
+    /// Add enums in the scope of `prefix`
+    fn add_enums(&mut self, prefix: &str, enums: Vec<EnumDescriptorProto>) {
+        for enum_type in enums {
+            let _prev = self
+                .enums
+                .insert(format!("{prefix}.{}", enum_type.name()), enum_type);
+        }
+    }
+
+    /// Get a message by its full name
+    fn message(&self, type_name: &str) -> Result<&DescriptorProto, Status> {
+        self.messages
+            .get(type_name)
+            .ok_or_else(|| Status::internal(format!("unknown message {type_name}")))
+    }
+
+    /// Translate a message in the JSON of the grpc-gateway to the serde layout of the
+    /// generated type. Fields are looked up by their proto names, their JSON names and
+    /// the names of the rust fields. Missing fields take their default values.
+    fn decode_message(&self, type_name: &str, value: Value) -> Result<Value, Status> {
+        let message = self.message(type_name)?;
+        let mut fields = match value {
+            Value::Object(fields) => fields,
+            Value::Null => Map::new(),
+            Value::Bool(_) | Value::Number(_) | Value::String(_) | Value::Array(_) => {
+                return Err(Status::invalid_argument(format!(
+                    "{type_name} should be an object"
+                )))
+            }
+        };
+        let mut decoded = Map::new();
+        for field in &message.field {
+            let rust_name = snake_case(field.name());
+            let value = [field.name(), field.json_name(), rust_name.as_str()]
+                .into_iter()
+                .find_map(|name| fields.remove(name));
+            if let Some(oneof) = oneof_name(message, field) {
+                let variant = upper_camel_case(field.name());
+                // the oneof may be in the layout of the generated types as well
+                let value = value.or_else(|| take_variant(&mut fields, &oneof, &variant));
+                if let Some(value) = value {
+                    let value = self.decode_field(field, value)?;
+                    let _prev = decoded.insert(oneof, json!({ variant: value }));
+                }
+                continue;
+            }
+            let value = match value {
+                Some(value) => self.decode_field(field, value)?,
+                None => default_value(field),
+            };
+            let _prev = decoded.insert(rust_name, value);
+        }
+        Ok(Value::Object(decoded))
+    }
+
+    /// Translate a field in the JSON of the grpc-gateway to the serde layout
+    fn decode_field(&self, field: &FieldDescriptorProto, value: Value) -> Result<Value, Status> {
+        if field.label() != Label::Repeated {
+            return self.decode_value(field, value);
+        }
+        match value {
+            Value::Array(items) => items
+                .into_iter()
+                .map(|item| self.decode_value(field, item))
+                .collect(),
+            Value::Null => Ok(default_value(field)),
+            Value::Bool(_) | Value::Number(_) | Value::String(_) | Value::Object(_) => Err(
+                Status::invalid_argument(format!("{} should be an array", field.name())),
+            ),
+        }
+    }
+
+    /// Translate a value of a field in the JSON of the grpc-gateway to the serde layout,
+    /// bytes are base64 decoded, enums are numbers and 64-bit integers may be strings
+    fn decode_value(&self, field: &FieldDescriptorProto, value: Value) -> Result<Value, Status> {
+        if value.is_null() {
+            return Ok(default_value(field));
+        }
+        match field.r#type() {
+            Type::Message | Type::Group => self.decode_message(field.type_name(), value),
+            Type::Bytes => match value {
+                Value::String(ref encoded) => STANDARD
+                    .decode(encoded)
+                    .map(|bytes| bytes.into_iter().map(Value::from).collect())
+                    .map_err(|e| {
+                        Status::invalid_argument(format!(
+                            "invalid base64 field {}: {e}",
+                            field.name()
+                        ))
+                    }),
+                Value::Null
+                | Value::Bool(_)
+                | Value::Number(_)
+                | Value::Array(_)
+                | Value::Object(_) => Ok(value),
+            },
+            Type::Enum => match value {
+                Value::String(ref name) => self
+                    .enums
+                    .get(field.type_name())
+                    .and_then(|e| e.value.iter().find(|v| v.name() == name.as_str()))
+                    .map(|v| Value::from(v.number()))
+                    .ok_or_else(|| {
+                        Status::invalid_argument(format!(
+                            "invalid value of {}: {name}",
+                            field.name()
+                        ))
+                    }),
+                Value::Null
+                | Value::Bool(_)
+                | Value::Number(_)
+                | Value::Array(_)
+                | Value::Object(_) => Ok(value),
+            },
+            Type::Int64 | Type::Sint64 | Type::Sfixed64 => match value {
+                Value::String(ref number) => number.parse::<i64>().map(Value::from).map_err(|e| {
+                    Status::invalid_argument(format!("invalid {}: {e}", field.name()))
+                }),
+                Value::Null
+                | Value::Bool(_)
+                | Value::Number(_)
+                | Value::Array(_)
+                | Value::Object(_) => Ok(value),
+            },
+            Type::Uint64 | Type::Fixed64 => match value {
+                Value::String(ref number) => number.parse::<u64>().map(Value::from).map_err(|e| {
+                    Status::invalid_argument(format!("invalid {}: {e}", field.name()))
+                }),
+                Value::Null
+                | Value::Bool(_)
+                | Value::Number(_)
+                | Value::Array(_)
+                | Value::Object(_) => Ok(value),
+            },
+            Type::Double
+            | Type::Float
+            | Type::Int32
+            | Type::Fixed32
+            | Type::Bool
+            | Type::String
+            | Type::Uint32
+            | Type::Sfixed32
+            | Type::Sint32 => Ok(value),
+        }
+    }
+
+    /// Translate a message in the serde layout of the generated type to the JSON of the
+    /// grpc-gateway, the fields are named by their proto names
+    fn encode_message(&self, type_name: &str, value: Value) -> Value {
+        let (Ok(message), Value::Object(mut fields)) = (self.message(type_name), value) else {
+            return Value::Null;
+        };
+        let mut encoded = Map::new();
+        for field in &message.field {
+            let value = match oneof_name(message, field) {
+                Some(oneof) => take_variant(&mut fields, &oneof, &upper_camel_case(field.name())),
+                None => fields.remove(&snake_case(field.name())),
+            };
+            if let Some(value) = value.filter(|value| !value.is_null()) {
+                let _prev =
+                    encoded.insert(field.name().to_owned(), self.encode_field(field, value));
+            }
+        }
+        Value::Object(encoded)
+    }
+
+    /// Translate a field in the serde layout to the JSON of the grpc-gateway
+    fn encode_field(&self, field: &FieldDescriptorProto, value: Value) -> Value {
+        match value {
+            Value::Array(items) if field.label() == Label::Repeated => items
+                .into_iter()
+                .map(|item| self.encode_value(field, item))
+                .collect(),
+            Value::Null
+            | Value::Bool(_)
+            | Value::Number(_)
+            | Value::String(_)
+            | Value::Array(_)
+            | Value::Object(_) => self.encode_value(field, value),
+        }
+    }
+
+    /// Translate a value of a field in the serde layout to the JSON of the grpc-gateway,
+    /// bytes are base64 encoded and enums are the names of the values
+    fn encode_value(&self, field: &FieldDescriptorProto, value: Value) -> Value {
+        match field.r#type() {
+            Type::Message | Type::Group => self.encode_message(field.type_name(), value),
+            Type::Bytes => {
+                let bytes: Option<Vec<u8>> = value.as_array().and_then(|items| {
+                    items
+                        .iter()
+                        .map(|item| item.as_u64().and_then(|n| u8::try_from(n).ok()))
+                        .collect()
+                });
+                bytes.map_or(value, |bytes| Value::String(STANDARD.encode(bytes)))
+            }
+            Type::Enum => {
+                let name = value.as_i64().and_then(|number| {
+                    self.enums
+                        .get(field.type_name())
+                        .and_then(|e| e.value.iter().find(|v| i64::from(v.number()) == number))
+                });
+                name.map_or(value, |v| Value::from(v.name()))
+            }
+            Type::Double
+            | Type::Float
+            | Type::Int64
+            | Type::Uint64
+            | Type::Int32
+            | Type::Fixed64
+            | Type::Fixed32
+            | Type::Bool
+            | Type::String
+            | Type::Uint32
+            | Type::Sfixed32
+            | Type::Sfixed64
+            | Type::Sint32
+            | Type::Sint64 => value,
+        }
+    }
+}
+
+/// The name of the rust field of the oneof that the field belongs to
+fn oneof_name(message: &DescriptorProto, field: &FieldDescriptorProto) -> Option<String> {
+    if field.proto3_optional() {
+        return None;
+    }
+    let index = usize::try_from(field.oneof_index?).ok()?;
+    message
+        .oneof_decl
+        .get(index)
+        .map(|oneof| snake_case(oneof.name()))
+}
+
+/// Take the value of a variant of a oneof in the serde layout, like
+/// `{"target_union": {"Value": ...}}`
+fn take_variant(fields: &mut Map<String, Value>, oneof: &str, variant: &str) -> Option<Value> {
+    fields
+        .get_mut(oneof)
+        .and_then(Value::as_object_mut)
+        .and_then(|variants| variants.remove(variant))
+}
+
+/// The default value of a field in the serde layout
+fn default_value(field: &FieldDescriptorProto) -> Value {
+    if field.label() == Label::Repeated {
+        return Value::Array(Vec::new());
+    }
+    match field.r#type() {
+        Type::Message | Type::Group => Value::Null,
+        Type::Bytes => Value::Array(Vec::new()),
+        Type::String => Value::from(""),
+        Type::Bool => Value::from(false),
+        Type::Double | Type::Float => Value::from(0.0),
+        Type::Int64
+        | Type::Uint64
+        | Type::Int32
+        | Type::Fixed64
+        | Type::Fixed32
+        | Type::Uint32
+        | Type::Enum
+        | Type::Sfixed32
+        | Type::Sfixed64
+        | Type::Sint32
+        | Type::Sint64 => Value::from(0),
+    }
+}
+
+/// Split a proto name into words like prost does, `peerURLs` is `peer`, `UR` and `Ls`
+fn words(name: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    for part in name.split('_').filter(|part| !part.is_empty()) {
+        let chars: Vec<char> = part.chars().collect();
+        let mut word = String::new();
+        for (i, &c) in chars.iter().enumerate() {
+            word.push(c);
+            let boundary = match (chars.get(i.wrapping_add(1)), chars.get(i.wrapping_add(2))) {
+                (Some(n1), _) if !c.is_uppercase() && n1.is_uppercase() => true,
+                (Some(n1), Some(n2)) => c.is_uppercase() && n1.is_uppercase() && n2.is_lowercase(),
+                (Some(_) | None, _) => false,
+            };
+            if boundary {
+                words.push(mem::take(&mut word));
+            }
+        }
+        words.push(word);
+    }
+    words
+}
+
+/// The name of the rust field generated for a proto name, like `member_id` for `memberID`
+fn snake_case(name: &str) -> String {
+    words(name)
+        .iter()
+        .map(|word| word.to_lowercase())
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+/// The name of the rust variant generated for a proto name, like `RequestPut` for
+/// `request_put`
+fn upper_camel_case(name: &str) -> String {
+    words(name)
+        .iter()
+        .map(|word| {
+            let mut chars = word.chars();
+            chars.next().map_or_else(String::new, |first| {
+                first
+                    .to_uppercase()
+                    .chain(chars.flat_map(char::to_lowercase))
+                    .collect()
+            })
+        })
+        .collect()
+}
+
+/// The gRPC clients that the gateway translates requests to
+#[derive(Clone, Debug)]
+struct Gateway {
+    /// Kv client
+    kv: KvClient<Channel>,
+    /// Lease client
+    lease: LeaseClient<Channel>,
+    /// Auth client
+    auth: AuthClient<Channel>,
+    /// Maintenance client
+    maintenance: MaintenanceClient<Channel>,
+    /// Schema of the messages
+    schema: Arc<Schema>,
+}
+
+/// Add a route that translates a JSON request to an unary gRPC call, `$message` is the
+/// name of the messages of the call without the `Request` and `Response` suffixes
+macro_rules! unary_route {
+    ($router:expr, $path:literal, $client:ident, $method:ident, $message:literal) => {
+        $router.route(
+            $path,
+            post(
+                |State(gateway): State<Gateway>, headers: HeaderMap, body: Bytes| async move {
+                    let mut client = gateway.$client;
+                    let types = (
+                        concat!(".etcdserverpb.", $message, "Request"),
+                        concat!(".etcdserverpb.", $message, "Response"),
+                    );
+                    unary(
+                        &gateway.schema,
+                        types,
+                        &headers,
+                        &body,
+                        |request| async move { client.$method(request).await },
+                    )
+                    .await
+                },
+            ),
+        )
+    };
+}
+
+/// Build the router of the HTTP/JSON gateway, which serves the `/v3/*` endpoints
+/// like the grpc-gateway of etcd by calling the gRPC services at `addrs`.
+///
+/// Messages are translated by the protobuf schema: fields are named by their proto names,
+/// like `ID` and `TTL`, bytes fields are base64 encoded, enums are the names of the values
+/// and oneofs are flattened to the fields of the variants. 64-bit integers are JSON
+/// numbers, the grpc-gateway accepts them as well, and strings are accepted in requests.
+/// Streaming RPCs are not served except a single lease keepalive.
+///
+/// The `/version` endpoint reports the versions and build info of this member.
+pub(crate) fn router(
+    addrs: &[String],
+    tls_config: Option<&ClientTlsConfig>,
+    cluster_info: Arc<ClusterInfo>,
+) -> Result<Router> {
+    let endpoints = addrs
+        .iter()
+        .map(|addr| build_endpoint(addr, tls_config))
+        .collect::<Result<Vec<_>, _>>()?;
+    let channel = Channel::balance_list(endpoints.into_iter());
+    let gateway = Gateway {
+        kv: KvClient::new(channel.clone()),
+        lease: LeaseClient::new(channel.clone()),
+        auth: AuthClient::new(channel.clone()),
+        maintenance: MaintenanceClient::new(channel),
+        schema: Arc::new(Schema::new(FILE_DESCRIPTOR_SET)?),
+    };
+
+    let router = Router::new();
+    let router = unary_route!(router, "/v3/kv/range", kv, range, "Range");
+    let router = unary_route!(router, "/v3/kv/put", kv, put, "Put");
+    let router = unary_route!(
+        router,
+        "/v3/kv/deleterange",
+        kv,
+        delete_range,
+        "DeleteRange"
+    );
+    let router = unary_route!(router, "/v3/kv/txn", kv, txn, "Txn");
+    let router = unary_route!(router, "/v3/kv/compaction", kv, compact, "Compaction");
+
+    let router = unary_route!(router, "/v3/lease/grant", lease, lease_grant, "LeaseGrant");
+    let router = unary_route!(
+        router,
+        "/v3/lease/revoke",
+        lease,
+        lease_revoke,
+        "LeaseRevoke"
+    );
+    let router = unary_route!(
+        router,
+        "/v3/kv/lease/revoke",
+        lease,
+        lease_revoke,
+        "LeaseRevoke"
+    );
+    let router = unary_route!(
+        router,
+        "/v3/lease/timetolive",
+        lease,
+        lease_time_to_live,
+        "LeaseTimeToLive"
+    );
+    let router = unary_route!(
+        router,
+        "/v3/kv/lease/timetolive",
+        lease,
+        lease_time_to_live,
+        "LeaseTimeToLive"
+    );
+    let router = unary_route!(
+        router,
+        "/v3/lease/leases",
+        lease,
+        lease_leases,
+        "LeaseLeases"
+    );
+    let router = unary_route!(
+        router,
+        "/v3/kv/lease/leases",
+        lease,
+        lease_leases,
+        "LeaseLeases"
+    );
+    let router = router.route("/v3/lease/keepalive", post(lease_keep_alive));
+
+    let router = unary_route!(router, "/v3/auth/enable", auth, auth_enable, "AuthEnable");
+    let router = unary_route!(
+        router,
+        "/v3/auth/disable",
+        auth,
+        auth_disable,
+        "AuthDisable"
+    );
+    let router = unary_route!(router, "/v3/auth/status", auth, auth_status, "AuthStatus");
+    let router = unary_route!(
+        router,
+        "/v3/auth/authenticate",
+        auth,
+        authenticate,
+        "Authenticate"
+    );
+    let router = unary_route!(router, "/v3/auth/user/add", auth, user_add, "AuthUserAdd");
+    let router = unary_route!(router, "/v3/auth/user/get", auth, user_get, "AuthUserGet");
+    let router = unary_route!(
+        router,
+        "/v3/auth/user/list",
+        auth,
+        user_list,
+        "AuthUserList"
+    );
+    let router = unary_route!(
+        router,
+        "/v3/auth/user/delete",
+        auth,
+        user_delete,
+        "AuthUserDelete"
+    );
+    let router = unary_route!(
+        router,
+        "/v3/auth/user/changepw",
+        auth,
+        user_change_password,
+        "AuthUserChangePassword"
+    );
+    let router = unary_route!(
+        router,
+        "/v3/auth/user/grant",
+        auth,
+        user_grant_role,
+        "AuthUserGrantRole"
+    );
+    let router = unary_route!(
+        router,
+        "/v3/auth/user/revoke",
+        auth,
+        user_revoke_role,
+        "AuthUserRevokeRole"
+    );
+    let router = unary_route!(router, "/v3/auth/role/add", auth, role_add, "AuthRoleAdd");
+    let router = unary_route!(router, "/v3/auth/role/get", auth, role_get, "AuthRoleGet");
+    let router = unary_route!(
+        router,
+        "/v3/auth/role/list",
+        auth,
+        role_list,
+        "AuthRoleList"
+    );
+    let router = unary_route!(
+        router,
+        "/v3/auth/role/delete",
+        auth,
+        role_delete,
+        "AuthRoleDelete"
+    );
+    let router = unary_route!(
+        router,
+        "/v3/auth/role/grant",
+        auth,
+        role_grant_permission,
+        "AuthRoleGrantPermission"
+    );
+    let router = unary_route!(
+        router,
+        "/v3/auth/role/revoke",
+        auth,
+        role_revoke_permission,
+        "AuthRoleRevokePermission"
+    );
+
+    let router = unary_route!(router, "/v3/maintenance/alarm", maintenance, alarm, "Alarm");
+    let router = unary_route!(
+        router,
+        "/v3/maintenance/status",
+        maintenance,
+        status,
+        "Status"
+    );
+    let router = unary_route!(
+        router,
+        "/v3/maintenance/defragment",
+        maintenance,
+        defragment,
+        "Defragment"
+    );
+    let router = unary_route!(router, "/v3/maintenance/hash", maintenance, hash, "Hash");
+    let router = unary_route!(
+        router,
+        "/v3/maintenance/hashkv",
+        maintenance,
+        hash_kv,
+        "HashKV"
+    );
+    let router = unary_route!(
+        router,
+        "/v3/maintenance/transfer-leadership",
+        maintenance,
+        move_leader,
+        "MoveLeader"
+    );
+
+    let router = router.route(
+        "/version",
+        get(move || {
+            let info = VersionInfo::new(&cluster_info);
+            async move { Json(info) }
+        }),
+    );
+
+    Ok(router.with_state(gateway))
+}
+
+/// Send a single keepalive request and return the first response
+async fn lease_keep_alive(
+    State(gateway): State<Gateway>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    let mut client = gateway.lease;
+    let types = (
+        ".etcdserverpb.LeaseKeepAliveRequest",
+        ".etcdserverpb.LeaseKeepAliveResponse",
+    );
+    unary(
+        &gateway.schema,
+        types,
+        &headers,
+        &body,
+        |request: tonic::Request<LeaseKeepAliveRequest>| async move {
+            let (metadata, extensions, message) = request.into_parts();
+            let request =
+                tonic::Request::from_parts(metadata, extensions, futures::stream::iter([message]));
+            client
+                .lease_keep_alive(request)
+                .await?
+                .into_inner()
+                .message()
+                .await?
+                .map(tonic::Response::new)
+                .ok_or_else(|| Status::unavailable("lease keepalive stream is closed"))
+        },
+    )
+    .await
+}
+
+/// Translate a JSON request to a gRPC call, and the gRPC response back to JSON, `types`
+/// are the full names of the request and response messages
+async fn unary<Req, Resp, F, Fut>(
+    schema: &Schema,
+    (request_type, response_type): (&str, &str),
+    headers: &HeaderMap,
+    body: &[u8],
+    call: F,
+) -> Response
+where
+    Req: DeserializeOwned,
+    Resp: Serialize,
+    F: FnOnce(tonic::Request<Req>) -> Fut,
+    Fut: Future<Output = Result<tonic::Response<Resp>, Status>>,
+{
+    let request = match decode_request(schema, request_type, headers, body) {
+        Ok(request) => request,
+        Err(status) => return error_response(&status),
+    };
+    let response = match call(request).await {
+        Ok(response) => response.into_inner(),
+        Err(status) => return error_response(&status),
+    };
+    match serde_json::to_value(response) {
+        Ok(value) => Json(schema.encode_message(response_type, value)).into_response(),
+        Err(e) => error_response(&Status::internal(e.to_string())),
+    }
+}
+
+/// Decode a JSON body to a gRPC request, the token in the `Authorization` header is kept
+fn decode_request<Req: DeserializeOwned>(
+    schema: &Schema,
+    request_type: &str,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<tonic::Request<Req>, Status> {
+    let value = if body.is_empty() {
+        Value::Object(Map::new())
+    } else {
+        serde_json::from_slice(body).map_err(|e| Status::invalid_argument(e.to_string()))?
+    };
+    let value = schema.decode_message(request_type, value)?;
+    let message =
+        serde_json::from_value(value).map_err(|e| Status::invalid_argument(e.to_string()))?;
+    let mut request = tonic::Request::new(message);
+    if let Some(token) = headers
+        .get(AUTHORIZATION)
+        .and_then(|token| token.to_str().ok())
+    {
+        let token = token
+            .parse()
+            .map_err(|_e| Status::invalid_argument("invalid authorization header"))?;
+        let _ignore = request.metadata_mut().insert("authorization", token);
+    }
+    Ok(request)
+}
+
+/// Build an error response in the same format as the grpc-gateway
+fn error_response(status: &Status) -> Response {
+    let http_status = match status.code() {
+        Code::InvalidArgument | Code::FailedPrecondition | Code::OutOfRange => {
+            StatusCode::BAD_REQUEST
+        }
+        Code::Unauthenticated => StatusCode::UNAUTHORIZED,
+        Code::PermissionDenied => StatusCode::FORBIDDEN,
+        Code::NotFound => StatusCode::NOT_FOUND,
+        Code::AlreadyExists | Code::Aborted => StatusCode::CONFLICT,
+        Code::ResourceExhausted => StatusCode::TOO_MANY_REQUESTS,
+        Code::Unimplemented => StatusCode::NOT_IMPLEMENTED,
+        Code::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+        Code::DeadlineExceeded => StatusCode::GATEWAY_TIMEOUT,
+        Code::Ok | Code::Cancelled | Code::Unknown | Code::Internal | Code::DataLoss => {
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    };
+    let body = json!({
+        "error": status.message(),
+        "code": i32::from(status.code()),
+        "message": status.message(),
+    });
+    (http_status, Json(body)).into_response()
+}
+
+#[cfg(test)]
+mod test {
+    use xlineapi::{
+        AlarmMember, AlarmResponse, AlarmType, AuthRoleGrantPermissionRequest, CompareResult,
+        CompareTarget, LeaseGrantRequest, LeaseGrantResponse, PutRequest, RangeResponse, Request,
+        ResponseOp, SortOrder, TargetUnion, TxnRequest, TxnResponse, Type as PermType,
+    };
+
+    use super::*;
+
+    fn schema() -> Schema {
+        Schema::new(FILE_DESCRIPTOR_SET).unwrap()
+    }
+
+    #[test]
+    fn request_bytes_fields_should_be_decoded_from_base64() {
+        let schema = schema();
+        let body = br#"{"key": "Zm9v", "value": "YmFy", "lease": 1}"#;
+        let request: tonic::Request<PutRequest> =
+            decode_request(&schema, ".etcdserverpb.PutRequest", &HeaderMap::new(), body).unwrap();
+        let request = request.into_inner();
+        assert_eq!(request.key, b"foo");
+        assert_eq!(request.value, b"bar");
+        assert_eq!(request.lease, 1);
+        assert!(!request.prev_kv);
+
+        let body = br#"{"key": "not base64!"}"#;
+        let status = decode_request::<PutRequest>(
+            &schema,
+            ".etcdserverpb.PutRequest",
+            &HeaderMap::new(),
+            body,
+        )
+        .unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+    }
+
+    #[test]
+    fn txn_request_should_be_decoded_from_gateway_json() {
+        let schema = schema();
+        let body = br#"{
+            "compare": [{"key": "Zm9v", "result": "EQUAL", "target": "VALUE", "value": "YmFy"}],
+            "success": [{"request_put": {"key": "Zm9v", "value": "YmF6"}}],
+            "failure": [{"request_range": {"key": "Zm9v", "sort_order": "DESCEND"}}]
+        }"#;
+        let request: tonic::Request<TxnRequest> =
+            decode_request(&schema, ".etcdserverpb.TxnRequest", &HeaderMap::new(), body).unwrap();
+        let request = request.into_inner();
+        assert_eq!(request.compare.len(), 1);
+        assert_eq!(request.compare[0].key, b"foo");
+        assert_eq!(request.compare[0].result(), CompareResult::Equal);
+        assert_eq!(request.compare[0].target(), CompareTarget::Value);
+        assert_eq!(
+            request.compare[0].target_union,
+            Some(TargetUnion::Value(b"bar".to_vec()))
+        );
+        let Some(Request::RequestPut(ref put)) = request.success[0].request else {
+            panic!("unexpected success request: {:?}", request.success);
+        };
+        assert_eq!(put.key, b"foo");
+        assert_eq!(put.value, b"baz");
+        let Some(Request::RequestRange(ref range)) = request.failure[0].request else {
+            panic!("unexpected failure request: {:?}", request.failure);
+        };
+        assert_eq!(range.key, b"foo");
+        assert_eq!(range.sort_order(), SortOrder::Descend);
+
+        let body = br#"{"compare": [{"key": "Zm9v", "target": "UNKNOWN"}]}"#;
+        let status = decode_request::<TxnRequest>(
+            &schema,
+            ".etcdserverpb.TxnRequest",
+            &HeaderMap::new(),
+            body,
+        )
+        .unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+    }
+
+    #[test]
+    fn txn_response_should_be_encoded_to_gateway_json() {
+        let response = TxnResponse {
+            succeeded: true,
+            responses: vec![ResponseOp {
+                response: Some(xlineapi::Response::ResponseRange(RangeResponse {
+                    kvs: vec![xlineapi::KeyValue {
+                        key: b"foo".to_vec(),
+                        value: b"bar".to_vec(),
+                        ..Default::default()
+                    }],
+                    count: 1,
+                    ..Default::default()
+                })),
+            }],
+            ..Default::default()
+        };
+        let value = schema().encode_message(
+            ".etcdserverpb.TxnResponse",
+            serde_json::to_value(response).unwrap(),
+        );
+        assert_eq!(value["succeeded"], true);
+        let range = &value["responses"][0]["response_range"];
+        assert_eq!(range["kvs"][0]["key"], "Zm9v");
+        assert_eq!(range["kvs"][0]["value"], "YmFy");
+        assert_eq!(range["count"], 1);
+        assert!(value["responses"][0].get("response").is_none());
+    }
+
+    #[test]
+    fn lease_fields_should_keep_proto_names() {
+        let schema = schema();
+        let body = br#"{"TTL": "10", "ID": 1}"#;
+        let request: tonic::Request<LeaseGrantRequest> = decode_request(
+            &schema,
+            ".etcdserverpb.LeaseGrantRequest",
+            &HeaderMap::new(),
+            body,
+        )
+        .unwrap();
+        assert_eq!(request.get_ref().ttl, 10);
+        assert_eq!(request.get_ref().id, 1);
+
+        let response = LeaseGrantResponse {
+            id: 1,
+            ttl: 10,
+            ..Default::default()
+        };
+        let value = schema.encode_message(
+            ".etcdserverpb.LeaseGrantResponse",
+            serde_json::to_value(response).unwrap(),
+        );
+        assert_eq!(value["ID"], 1);
+        assert_eq!(value["TTL"], 10);
+    }
+
+    #[test]
+    fn fields_should_be_transcoded_by_their_types() {
+        let schema = schema();
+        let response = AlarmResponse {
+            alarms: vec![AlarmMember::new(1, AlarmType::Nospace)],
+            ..Default::default()
+        };
+        let value = schema.encode_message(
+            ".etcdserverpb.AlarmResponse",
+            serde_json::to_value(response).unwrap(),
+        );
+        assert_eq!(value["alarms"][0]["alarm"], "NOSPACE");
+        assert_eq!(value["alarms"][0]["memberID"], 1);
+
+        // `name` is a string and `key` is bytes, `permType` is an enum of another package
+        let body = br#"{"name": "Zm9v", "perm": {"permType": "READWRITE", "key": "Zm9v"}}"#;
+        let request: tonic::Request<AuthRoleGrantPermissionRequest> = decode_request(
+            &schema,
+            ".etcdserverpb.AuthRoleGrantPermissionRequest",
+            &HeaderMap::new(),
+            body,
+        )
+        .unwrap();
+        let request = request.into_inner();
+        assert_eq!(request.name, "Zm9v");
+        let perm = request.perm.unwrap();
+        assert_eq!(perm.perm_type(), PermType::Readwrite);
+        assert_eq!(perm.key, b"foo");
+        assert!(perm.range_end.is_empty());
+    }
+
+    #[test]
+    fn proto_names_should_be_converted_like_prost() {
+        assert_eq!(snake_case("peerURLs"), "peer_ur_ls");
+        assert_eq!(snake_case("memberID"), "member_id");
+        assert_eq!(snake_case("remaining_TTL"), "remaining_ttl");
+        assert_eq!(snake_case("dbSizeInUse"), "db_size_in_use");
+        assert_eq!(
+            upper_camel_case("request_delete_range"),
+            "RequestDeleteRange"
+        );
+        assert_eq!(upper_camel_case("create_revision"), "CreateRevision");
+    }
+}
@@ -0,0 +1,149 @@
+use std::{
+    convert::Infallible,
+    task::{Context, Poll},
+};
+
+use anyhow::Result;
+use futures::future::BoxFuture;
+use hyper::{
+    header::{HeaderName, HeaderValue},
+    Body, Method, Request, Response,
+};
+use tonic::{body::BoxBody, server::NamedService};
+use tonic_web::{GrpcWebLayer, GrpcWebService};
+use tower::{Layer, Service};
+use tower_http::cors::{AllowOrigin, Cors, CorsLayer};
+use utils::config::GrpcWebConfig;
+
+/// Headers that browser clients are allowed to send
+const ALLOWED_HEADERS: [&str; 5] = [
+    "x-grpc-web",
+    "x-user-agent",
+    "grpc-timeout",
+    "content-type",
+    "authorization",
+];
+
+/// Headers that browser clients are allowed to read
+const EXPOSED_HEADERS: [&str; 3] = ["grpc-status", "grpc-message", "grpc-status-details-bin"];
+
+/// Build the CORS layer of grpc-web requests. Only the listed origins are allowed, so
+/// cross-origin requests are denied by default and other sites can't call the client
+/// services with the credentials of a browser.
+pub(super) fn cors_layer(config: &GrpcWebConfig) -> Result<CorsLayer> {
+    let allowed_origins = config
+        .cors_allowed_origins()
+        .iter()
+        .map(|origin| HeaderValue::from_str(origin))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(CorsLayer::new()
+        .allow_origin(AllowOrigin::list(allowed_origins))
+        .allow_methods([Method::POST])
+        .allow_headers(ALLOWED_HEADERS.map(HeaderName::from_static))
+        .expose_headers(EXPOSED_HEADERS.map(HeaderName::from_static))
+        .max_age(*config.cors_max_age()))
+}
+
+/// Accept grpc-web requests on `service` if `cors` is set
+pub(super) fn enable<S>(cors: Option<&CorsLayer>, service: S) -> WebService<S> {
+    match cors {
+        Some(cors) => WebService::Web(cors.layer(GrpcWebLayer::new().layer(service))),
+        None => WebService::Grpc(service),
+    }
+}
+
+/// A client service that may accept grpc-web requests besides grpc requests
+#[derive(Clone, Debug)]
+pub(super) enum WebService<S> {
+    /// Accept both grpc and grpc-web requests
+    Web(Cors<GrpcWebService<S>>),
+    /// Accept grpc requests only
+    Grpc(S),
+}
+
+impl<S: NamedService> NamedService for WebService<S> {
+    const NAME: &'static str = S::NAME;
+}
+
+impl<S> Service<Request<Body>> for WebService<S>
+where
+    S: Service<Request<Body>, Response = Response<BoxBody>, Error = Infallible> + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response<BoxBody>;
+    type Error = Infallible;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    #[inline]
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        match *self {
+            WebService::Web(ref mut service) => service.poll_ready(cx),
+            WebService::Grpc(ref mut service) => service.poll_ready(cx),
+        }
+    }
+
+    #[inline]
+    fn call(&mut self, req: Request<Body>) -> Self::Future {
+        match *self {
+            WebService::Web(ref mut service) => Box::pin(service.call(req)),
+            WebService::Grpc(ref mut service) => Box::pin(service.call(req)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn invalid_origin_should_be_rejected() {
+        let config = GrpcWebConfig::new(vec!["http://a\nb".to_owned()], Duration::from_secs(1));
+        assert!(cors_layer(&config).is_err());
+        let config = GrpcWebConfig::new(
+            vec!["http://localhost:3000".to_owned()],
+            Duration::from_secs(1),
+        );
+        assert!(cors_layer(&config).is_ok());
+    }
+
+    /// Send a CORS preflight request from `origin`, return the allowed origin
+    async fn preflight(config: &GrpcWebConfig, origin: &str) -> Option<HeaderValue> {
+        let mut service = cors_layer(config)
+            .unwrap()
+            .layer(hyper::service::service_fn(|_req: Request<Body>| async {
+                Ok::<_, Infallible>(Response::new(Body::empty()))
+            }));
+        futures::future::poll_fn(|cx| service.poll_ready(cx))
+            .await
+            .unwrap();
+        let request = Request::builder()
+            .method(Method::OPTIONS)
+            .header("origin", origin)
+            .header("access-control-request-method", "POST")
+            .body(Body::empty())
+            .unwrap();
+        let response = service.call(request).await.unwrap();
+        response
+            .headers()
+            .get("access-control-allow-origin")
+            .cloned()
+    }
+
+    #[tokio::test]
+    async fn cross_origin_requests_should_be_denied_unless_allowed() {
+        let config = GrpcWebConfig::default();
+        assert!(preflight(&config, "http://localhost:3000").await.is_none());
+
+        let config = GrpcWebConfig::new(
+            vec!["http://localhost:3000".to_owned()],
+            Duration::from_secs(1),
+        );
+        assert_eq!(
+            preflight(&config, "http://localhost:3000").await.unwrap(),
+            "http://localhost:3000"
+        );
+        assert!(preflight(&config, "http://example.com").await.is_none());
+    }
+}
@@ -6,20 +6,28 @@ use std::{
     time::Duration,
 };
 
+use clippy_utilities::NumericCast;
 use curp::rpc::ReadState;
 use dashmap::DashMap;
 use event_listener::Event;
 use futures::future::{join_all, Either};
+use prost::Message;
 use tokio::time::timeout;
 use tracing::{debug, instrument};
 use xlineapi::{
     command::{Command, CommandResponse, CurpClient, SyncResponse},
     execute_error::ExecuteError,
-    request_validation::RequestValidator,
+    request_validation::{RequestValidator, ValidationError},
     AuthInfo, ResponseWrapper,
 };
 
-use super::barriers::{IdBarrier, IndexBarrier};
+use super::{
+    barriers::{IdBarrier, IndexBarrier},
+    command::QuotaChecker,
+    overload::{OverloadMonitor, Priority},
+    qos::QosClassifier,
+    request_log::RequestLogger,
+};
 use crate::{
     metrics,
     revision_check::RevisionCheck,
@@ -54,6 +62,18 @@ where
     compact_events: Arc<DashMap<u64, Arc<Event>>>,
     /// Next compact_id
     next_compact_id: AtomicU64,
+    /// Quota checker
+    quota_checker: Arc<dyn QuotaChecker>,
+    /// Max number of operations in a txn request
+    max_txn_ops: usize,
+    /// Max size of a request that will be proposed
+    max_request_bytes: u64,
+    /// Logger of the requests
+    request_logger: RequestLogger,
+    /// Overload monitor that sheds requests when the server is saturated
+    overload_monitor: Arc<OverloadMonitor>,
+    /// Classifier of the qos classes of the proposed requests
+    qos_classifier: Arc<QosClassifier>,
 }
 
 impl<S> KvServer<S>
@@ -71,6 +91,12 @@ where
         compact_timeout: Duration,
         client: Arc<CurpClient>,
         compact_events: Arc<DashMap<u64, Arc<Event>>>,
+        quota_checker: Arc<dyn QuotaChecker>,
+        max_txn_ops: usize,
+        max_request_bytes: u64,
+        request_logger: RequestLogger,
+        overload_monitor: Arc<OverloadMonitor>,
+        qos_classifier: Arc<QosClassifier>,
     ) -> Self {
         Self {
             kv_storage,
@@ -82,9 +108,23 @@ where
             client,
             compact_events,
             next_compact_id: AtomicU64::new(0),
+            quota_checker,
+            max_txn_ops,
+            max_request_bytes,
+            request_logger,
+            overload_monitor,
+            qos_classifier,
         }
     }
 
+    /// Reject the request if its encoded size exceeds the max request size
+    fn check_request_size(&self, request: &impl Message) -> Result<(), ValidationError> {
+        if request.encoded_len().numeric_cast::<u64>() > self.max_request_bytes {
+            return Err(ValidationError::RequestTooLarge);
+        }
+        Ok(())
+    }
+
     /// Parse `ResponseOp`
     pub(crate) fn parse_response_op(response_op: ResponseOp) -> Response {
         if let Some(response) = response_op.response {
@@ -105,15 +145,22 @@ where
     /// Propose request and get result with fast/slow path
     async fn propose<T>(
         &self,
-        request: T,
+        request: tonic::Request<T>,
         auth_info: Option<AuthInfo>,
         use_fast_path: bool,
     ) -> Result<(CommandResponse, Option<SyncResponse>), tonic::Status>
     where
         T: Into<RequestWrapper>,
     {
-        let request = request.into();
-        let cmd = Command::new_with_auth_info(request.keys(), request, auth_info);
+        self.overload_monitor.admit(Priority::Write)?;
+        let qos_class = self.qos_classifier.classify(&request, auth_info.as_ref())?;
+        let request = request.into_inner().into();
+        let cmd = Command::new_with_auth_info(request.keys(), request, auth_info)
+            .with_qos_class(qos_class);
+        // reject the request early if the backend of current node runs out of quota
+        if !self.quota_checker.check(&cmd) {
+            return Err(ExecuteError::Nospace.into());
+        }
         let res = self.client.propose(&cmd, None, use_fast_path).await??;
         Ok(res)
     }
@@ -159,6 +206,15 @@ where
             .ok_or(ExecuteError::RevisionCompacted(range_revision, compacted_revision).into())
     }
 
+    /// Priority of a read request, serializable reads are shed first
+    fn read_priority(is_serializable: bool) -> Priority {
+        if is_serializable {
+            Priority::SerializableRead
+        } else {
+            Priority::Write
+        }
+    }
+
     /// Wait current node's state machine apply the conflict commands
     async fn wait_read_state(&self, cmd: &Command) -> Result<(), tonic::Status> {
         loop {
@@ -190,16 +246,9 @@ where
         }
         Ok(())
     }
-}
 
-#[tonic::async_trait]
-impl<S> Kv for KvServer<S>
-where
-    S: StorageApi,
-{
-    /// Range gets the keys in the range from the key-value store.
-    #[instrument(skip_all)]
-    async fn range(
+    /// Handle `RangeRequest`
+    async fn handle_range(
         &self,
         request: tonic::Request<RangeRequest>,
     ) -> Result<tonic::Response<RangeResponse>, tonic::Status> {
@@ -213,6 +262,8 @@ where
         let auth_info = self.auth_storage.try_get_auth_info_from_request(&request)?;
         let range_required_revision = range_req.revision;
         let is_serializable = range_req.serializable;
+        self.overload_monitor
+            .admit(Self::read_priority(is_serializable))?;
         let request = RequestWrapper::from(request.into_inner());
         let cmd = Command::new_with_auth_info(request.keys(), request, auth_info);
         if !is_serializable {
@@ -234,22 +285,18 @@ where
         }
     }
 
-    /// Put puts the given key into the key-value store.
-    /// A put request increments the revision of the key-value store
-    /// and generates one event in the event history.
-    #[instrument(skip_all)]
-    async fn put(
+    /// Handle `PutRequest`
+    async fn handle_put(
         &self,
         request: tonic::Request<PutRequest>,
     ) -> Result<tonic::Response<PutResponse>, tonic::Status> {
         let put_req: &PutRequest = request.get_ref();
+        self.check_request_size(put_req)?;
         put_req.validation()?;
         debug!("Receive grpc request: {}", put_req);
         let auth_info = self.auth_storage.try_get_auth_info_from_request(&request)?;
         let is_fast_path = true;
-        let (cmd_res, sync_res) = self
-            .propose(request.into_inner(), auth_info, is_fast_path)
-            .await?;
+        let (cmd_res, sync_res) = self.propose(request, auth_info, is_fast_path).await?;
         let mut res = Self::parse_response_op(cmd_res.into_inner().into());
         if let Some(sync_res) = sync_res {
             let revision = sync_res.revision();
@@ -263,22 +310,18 @@ where
         }
     }
 
-    /// DeleteRange deletes the given range from the key-value store.
-    /// A delete request increments the revision of the key-value store
-    /// and generates a delete event in the event history for every deleted key.
-    #[instrument(skip_all)]
-    async fn delete_range(
+    /// Handle `DeleteRangeRequest`
+    async fn handle_delete_range(
         &self,
         request: tonic::Request<DeleteRangeRequest>,
     ) -> Result<tonic::Response<DeleteRangeResponse>, tonic::Status> {
         let delete_range_req = request.get_ref();
+        self.check_request_size(delete_range_req)?;
         delete_range_req.validation()?;
         debug!("Receive grpc request: {}", delete_range_req);
         let auth_info = self.auth_storage.try_get_auth_info_from_request(&request)?;
         let is_fast_path = true;
-        let (cmd_res, sync_res) = self
-            .propose(request.into_inner(), auth_info, is_fast_path)
-            .await?;
+        let (cmd_res, sync_res) = self.propose(request, auth_info, is_fast_path).await?;
         let mut res = Self::parse_response_op(cmd_res.into_inner().into());
         if let Some(sync_res) = sync_res {
             let revision = sync_res.revision();
@@ -292,17 +335,14 @@ where
         }
     }
 
-    /// Txn processes multiple requests in a single transaction.
-    /// A txn request increments the revision of the key-value store
-    /// and generates events with the same revision for every completed request.
-    /// It is not allowed to modify the same key several times within one txn.
-    #[instrument(skip_all)]
-    async fn txn(
+    /// Handle `TxnRequest`
+    async fn handle_txn(
         &self,
         request: tonic::Request<TxnRequest>,
     ) -> Result<tonic::Response<TxnResponse>, tonic::Status> {
         let txn_req = request.get_ref();
-        txn_req.validation()?;
+        self.check_request_size(txn_req)?;
+        txn_req.validation_with_max_ops(self.max_txn_ops)?;
         debug!("Receive grpc request: {}", txn_req);
         txn_req.check_revision(
             self.kv_storage.compacted_revision(),
@@ -312,17 +352,24 @@ where
         let res = if txn_req.is_read_only() {
             debug!("TxnRequest is read only");
             let is_serializable = txn_req.is_serializable();
+            self.overload_monitor
+                .admit(Self::read_priority(is_serializable))?;
             let request = RequestWrapper::from(request.into_inner());
             let cmd = Command::new_with_auth_info(request.keys(), request, auth_info);
             if !is_serializable {
                 self.wait_read_state(&cmd).await?;
+                // Double check whether the txn request is compacted or not, same as `range`
+                if let RequestWrapper::TxnRequest(ref req) = *cmd.request() {
+                    req.check_revision(
+                        self.kv_storage.compacted_revision(),
+                        self.kv_storage.revision(),
+                    )?;
+                }
             }
             self.do_serializable(&cmd)?
         } else {
             let is_fast_path = true;
-            let (cmd_res, sync_res) = self
-                .propose(request.into_inner(), auth_info, is_fast_path)
-                .await?;
+            let (cmd_res, sync_res) = self.propose(request, auth_info, is_fast_path).await?;
             let mut res = Self::parse_response_op(cmd_res.into_inner().into());
             if let Some(sync_res) = sync_res {
                 let revision = sync_res.revision();
@@ -338,11 +385,8 @@ where
         }
     }
 
-    /// Compact compacts the event history in the etcd key-value store. The key-value
-    /// store should be periodically compacted or the event history will continue to grow
-    /// indefinitely.
-    #[instrument(skip_all)]
-    async fn compact(
+    /// Handle `CompactionRequest`
+    async fn handle_compact(
         &self,
         request: tonic::Request<CompactionRequest>,
     ) -> Result<tonic::Response<CompactionResponse>, tonic::Status> {
@@ -354,8 +398,9 @@ where
         let auth_info = self.auth_storage.try_get_auth_info_from_request(&request)?;
         let physical = req.physical;
         let request = RequestWrapper::from(request.into_inner());
-        let cmd = Command::new_with_auth_info(request.keys(), request, auth_info);
         let compact_id = self.next_compact_id.fetch_add(1, Ordering::Relaxed);
+        let cmd = Command::new_with_auth_info(request.keys(), request, auth_info)
+            .with_compact_id(compact_id);
         let compact_physical_fut = if physical {
             let event = Arc::new(Event::new());
             _ = self.compact_events.insert(compact_id, Arc::clone(&event));
@@ -364,14 +409,22 @@ where
         } else {
             Either::Right(async {})
         };
-        let (cmd_res, _sync_res) = self.client.propose(&cmd, None, !physical).await??;
-        let resp = cmd_res.into_inner();
-        if timeout(self.compact_timeout, compact_physical_fut)
-            .await
-            .is_err()
-        {
-            return Err(tonic::Status::deadline_exceeded("Compact timeout"));
+        let res = async {
+            let (cmd_res, _sync_res) = self.client.propose(&cmd, None, !physical).await??;
+            if timeout(self.compact_timeout, compact_physical_fut)
+                .await
+                .is_err()
+            {
+                return Err(tonic::Status::unavailable("etcdserver: request timed out"));
+            }
+            Ok(cmd_res.into_inner())
+        }
+        .await;
+        // the event is no longer needed whether the compaction succeeds or not
+        if physical {
+            _ = self.compact_events.remove(&compact_id);
         }
+        let resp = res?;
 
         if let ResponseWrapper::CompactionResponse(response) = resp {
             Ok(tonic::Response::new(response))
@@ -381,6 +434,90 @@ where
     }
 }
 
+#[tonic::async_trait]
+impl<S> Kv for KvServer<S>
+where
+    S: StorageApi,
+{
+    /// Range gets the keys in the range from the key-value store.
+    #[instrument(skip_all)]
+    async fn range(
+        &self,
+        request: tonic::Request<RangeRequest>,
+    ) -> Result<tonic::Response<RangeResponse>, tonic::Status> {
+        let req = request.get_ref();
+        let log = self
+            .request_logger
+            .start("Range", &req.key, &req.range_end, false);
+        let res = self.handle_range(request).await;
+        log.finish(&res);
+        res
+    }
+
+    /// Put puts the given key into the key-value store.
+    /// A put request increments the revision of the key-value store
+    /// and generates one event in the event history.
+    #[instrument(skip_all)]
+    async fn put(
+        &self,
+        request: tonic::Request<PutRequest>,
+    ) -> Result<tonic::Response<PutResponse>, tonic::Status> {
+        let log = self
+            .request_logger
+            .start("Put", &request.get_ref().key, &[], true);
+        let res = self.handle_put(request).await;
+        log.finish(&res);
+        res
+    }
+
+    /// DeleteRange deletes the given range from the key-value store.
+    /// A delete request increments the revision of the key-value store
+    /// and generates a delete event in the event history for every deleted key.
+    #[instrument(skip_all)]
+    async fn delete_range(
+        &self,
+        request: tonic::Request<DeleteRangeRequest>,
+    ) -> Result<tonic::Response<DeleteRangeResponse>, tonic::Status> {
+        let req = request.get_ref();
+        let log = self
+            .request_logger
+            .start("DeleteRange", &req.key, &req.range_end, true);
+        let res = self.handle_delete_range(request).await;
+        log.finish(&res);
+        res
+    }
+
+    /// Txn processes multiple requests in a single transaction.
+    /// A txn request increments the revision of the key-value store
+    /// and generates events with the same revision for every completed request.
+    /// It is not allowed to modify the same key several times within one txn.
+    #[instrument(skip_all)]
+    async fn txn(
+        &self,
+        request: tonic::Request<TxnRequest>,
+    ) -> Result<tonic::Response<TxnResponse>, tonic::Status> {
+        let proposed = !request.get_ref().is_read_only();
+        let log = self.request_logger.start("Txn", &[], &[], proposed);
+        let res = self.handle_txn(request).await;
+        log.finish(&res);
+        res
+    }
+
+    /// Compact compacts the event history in the etcd key-value store. The key-value
+    /// store should be periodically compacted or the event history will continue to grow
+    /// indefinitely.
+    #[instrument(skip_all)]
+    async fn compact(
+        &self,
+        request: tonic::Request<CompactionRequest>,
+    ) -> Result<tonic::Response<CompactionResponse>, tonic::Status> {
+        let log = self.request_logger.start("Compact", &[], &[], true);
+        let res = self.handle_compact(request).await;
+        log.finish(&res);
+        res
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
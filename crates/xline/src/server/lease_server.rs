@@ -20,13 +20,18 @@ use xlineapi::{
     execute_error::ExecuteError,
 };
 
+use super::{
+    qos::{QosClassifier, QOS_CLASS_KEY},
+    stream_shutdown::StreamShutdown,
+};
 use crate::{
     id_gen::IdGenerator,
     metrics,
     rpc::{
-        Lease, LeaseClient, LeaseGrantRequest, LeaseGrantResponse, LeaseKeepAliveRequest,
-        LeaseKeepAliveResponse, LeaseLeasesRequest, LeaseLeasesResponse, LeaseRevokeRequest,
-        LeaseRevokeResponse, LeaseTimeToLiveRequest, LeaseTimeToLiveResponse, RequestWrapper,
+        Lease, LeaseCheckpointRequest, LeaseClient, LeaseGrantRequest, LeaseGrantResponse,
+        LeaseKeepAliveRequest, LeaseKeepAliveResponse, LeaseLeasesRequest, LeaseLeasesResponse,
+        LeaseRevokeRequest, LeaseRevokeResponse, LeaseTimeToLiveRequest, LeaseTimeToLiveResponse,
+        RequestWrapper,
     },
     storage::{storage_api::StorageApi, AuthStore, LeaseStore},
 };
@@ -51,8 +56,12 @@ where
     cluster_info: Arc<ClusterInfo>,
     /// Client tls config
     client_tls_config: Option<ClientTlsConfig>,
+    /// Classifier of the qos classes of the proposed requests
+    qos_classifier: Arc<QosClassifier>,
     /// Task manager
     task_manager: Arc<TaskManager>,
+    /// Ends the keep alive streams on graceful shutdown
+    stream_shutdown: StreamShutdown,
 }
 
 impl<S> LeaseServer<S>
@@ -60,6 +69,7 @@ where
     S: StorageApi,
 {
     /// New `LeaseServer`
+    #[allow(clippy::too_many_arguments)] // Consistent with other servers
     pub(crate) fn new(
         lease_storage: Arc<LeaseStore<S>>,
         auth_storage: Arc<AuthStore<S>>,
@@ -67,7 +77,10 @@ where
         id_gen: Arc<IdGenerator>,
         cluster_info: Arc<ClusterInfo>,
         client_tls_config: Option<ClientTlsConfig>,
+        lease_checkpoint_interval: Duration,
+        qos_classifier: Arc<QosClassifier>,
         task_manager: &Arc<TaskManager>,
+        stream_shutdown: StreamShutdown,
     ) -> Arc<Self> {
         let lease_server = Arc::new(Self {
             lease_storage,
@@ -76,14 +89,38 @@ where
             id_gen,
             cluster_info,
             client_tls_config,
+            qos_classifier,
             task_manager: Arc::clone(task_manager),
+            stream_shutdown,
         });
         task_manager.spawn(TaskName::RevokeExpiredLeases, |n| {
             Self::revoke_expired_leases_task(Arc::clone(&lease_server), n)
         });
+        task_manager.spawn(TaskName::CheckpointLeases, |n| {
+            Self::checkpoint_leases_task(Arc::clone(&lease_server), lease_checkpoint_interval, n)
+        });
         lease_server
     }
 
+    /// Build a request carrying the root token, used by the requests proposed by the server
+    /// itself, which are critical to keep the leases alive
+    fn request_with_root_token<T>(&self, message: T) -> tonic::Request<T> {
+        let mut request = tonic::Request::new(message);
+        let _ignore = request.metadata_mut().insert(
+            QOS_CLASS_KEY,
+            tonic::metadata::MetadataValue::from_static("critical"),
+        );
+        if let Ok(token) = self.auth_storage.root_token() {
+            let _ignore = request.metadata_mut().insert(
+                "token",
+                token
+                    .parse()
+                    .unwrap_or_else(|e| panic!("metadata value parse error: {e}")),
+            );
+        }
+        request
+    }
+
     /// Task of revoke expired leases
     #[allow(clippy::arithmetic_side_effects, clippy::ignored_unit_patterns)] // Introduced by tokio::select!
     async fn revoke_expired_leases_task(
@@ -100,17 +137,9 @@ where
                 for id in lease_server.lease_storage.find_expired_leases() {
                     let _handle = tokio::spawn({
                         let s = Arc::clone(&lease_server);
-                        let token_option = lease_server.auth_storage.root_token();
+                        let request =
+                            lease_server.request_with_root_token(LeaseRevokeRequest { id });
                         async move {
-                            let mut request = tonic::Request::new(LeaseRevokeRequest { id });
-                            if let Ok(token) = token_option {
-                                let _ignore = request.metadata_mut().insert(
-                                    "token",
-                                    token.parse().unwrap_or_else(|e| {
-                                        panic!("metadata value parse error: {e}")
-                                    }),
-                                );
-                            }
                             if let Err(e) = s.lease_revoke(request).await {
                                 warn!("Failed to revoke expired leases: {}", e);
                             }
@@ -121,6 +150,35 @@ where
         }
     }
 
+    /// Task of checkpoint the remaining ttl of leases, so that a new leader can resume the
+    /// countdown of leases instead of granting them a full ttl
+    #[allow(clippy::arithmetic_side_effects, clippy::ignored_unit_patterns)] // Introduced by tokio::select!
+    async fn checkpoint_leases_task(
+        lease_server: Arc<LeaseServer<S>>,
+        interval: Duration,
+        shutdown_listener: Listener,
+    ) {
+        loop {
+            tokio::select! {
+                _ = shutdown_listener.wait() => return,
+                _ = time::sleep(interval) => {}
+            }
+            // only leader will checkpoint leases
+            if !lease_server.lease_storage.is_primary() {
+                continue;
+            }
+            let checkpoints = lease_server.lease_storage.checkpoints();
+            if checkpoints.is_empty() {
+                continue;
+            }
+            let request =
+                lease_server.request_with_root_token(LeaseCheckpointRequest { checkpoints });
+            if let Err(e) = lease_server.propose(request, true).await {
+                warn!("Failed to checkpoint leases: {}", e);
+            }
+        }
+    }
+
     /// Propose request and get result with fast/slow path
     async fn propose<T>(
         &self,
@@ -131,6 +189,7 @@ where
         T: Into<RequestWrapper>,
     {
         let auth_info = self.auth_storage.try_get_auth_info_from_request(&request)?;
+        let qos_class = self.qos_classifier.classify(&request, auth_info.as_ref())?;
         let request = request.into_inner().into();
         let keys = {
             if let RequestWrapper::LeaseRevokeRequest(ref req) = request {
@@ -143,7 +202,7 @@ where
                 vec![]
             }
         };
-        let cmd = Command::new_with_auth_info(keys, request, auth_info);
+        let cmd = Command::new_with_auth_info(keys, request, auth_info).with_qos_class(qos_class);
         let res = self.client.propose(&cmd, None, use_fast_path).await??;
         Ok(res)
     }
@@ -185,7 +244,7 @@ where
                     };
                     lease_storage.keep_alive(keep_alive_req.id).map_err(Into::into)
                 } else {
-                    Err(tonic::Status::failed_precondition("current node is not a leader"))
+                    Err(tonic::Status::failed_precondition("etcdserver: not leader"))
                 }?;
                 yield LeaseKeepAliveResponse {
                     id: keep_alive_req.id,
@@ -243,7 +302,7 @@ where
 }
 
 /// Build endpoints from addresses
-fn build_endpoints(
+pub(super) fn build_endpoints(
     addrs: &[String],
     tls_config: Option<&ClientTlsConfig>,
 ) -> Result<Vec<Endpoint>, tonic::Status> {
@@ -343,7 +402,7 @@ where
                     .await?;
             }
         };
-        Ok(tonic::Response::new(stream))
+        Ok(tonic::Response::new(self.stream_shutdown.guard(stream)))
     }
 
     /// LeaseTimeToLive retrieves lease information.
@@ -5,7 +5,7 @@ use clippy_utilities::OverflowArithmetic;
 #[cfg(not(madsim))]
 use tonic::transport::ClientTlsConfig;
 use tonic::transport::{Channel, Endpoint};
-use tracing::debug;
+use tracing::{debug, warn};
 use utils::build_endpoint;
 #[cfg(madsim)]
 use utils::ClientTlsConfig;
@@ -19,10 +19,10 @@ use crate::{
     id_gen::IdGenerator,
     rpc::{
         Compare, CompareResult, CompareTarget, DeleteRangeRequest, DeleteRangeResponse,
-        LeaseGrantRequest, LeaseGrantResponse, Lock, LockRequest, LockResponse, PutRequest,
-        RangeRequest, RangeResponse, Request, RequestOp, RequestUnion, RequestWrapper, Response,
-        ResponseHeader, SortOrder, SortTarget, TargetUnion, TxnRequest, TxnResponse, UnlockRequest,
-        UnlockResponse, WatchClient, WatchCreateRequest, WatchRequest,
+        LeaseGrantRequest, LeaseGrantResponse, LeaseRevokeRequest, Lock, LockRequest, LockResponse,
+        PutRequest, RangeRequest, RangeResponse, Request, RequestOp, RequestUnion, RequestWrapper,
+        Response, ResponseHeader, SortOrder, SortTarget, TargetUnion, TxnRequest, TxnResponse,
+        UnlockRequest, UnlockResponse, WatchClient, WatchCreateRequest, WatchRequest,
     },
     storage::{storage_api::StorageApi, AuthStore},
 };
@@ -30,6 +30,14 @@ use crate::{
 /// Default session ttl
 const DEFAULT_SESSION_TTL: i64 = 60;
 
+/// The request metadata key that switches `Lock` to the non-blocking mode,
+/// in which the request fails immediately if the lock is held by another session
+const TRY_LOCK_METADATA_KEY: &str = "try-lock";
+
+/// The response metadata key that carries the fencing token of an acquired lock,
+/// which is the create revision of the lock key and increases monotonically
+const FENCING_TOKEN_METADATA_KEY: &str = "fencing-token";
+
 /// Lock Server
 pub(super) struct LockServer<S>
 where
@@ -88,8 +96,8 @@ where
         Ok(res)
     }
 
-    /// Crate txn for try acquire lock
-    fn create_acquire_txn(prefix: &str, lease_id: i64) -> TxnRequest {
+    /// Crate txn for try acquire lock, the value of the lock key is its owner
+    fn create_acquire_txn(prefix: &str, lease_id: i64, owner: Vec<u8>) -> TxnRequest {
         let key = format!("{prefix}{lease_id:x}");
         #[allow(clippy::as_conversions)] // this cast is always safe
         let cmp = Compare {
@@ -102,7 +110,7 @@ where
         let put = RequestOp {
             request: Some(Request::RequestPut(PutRequest {
                 key: key.as_bytes().to_vec(),
-                value: vec![],
+                value: owner,
                 lease: lease_id,
                 ..Default::default()
             })),
@@ -197,6 +205,52 @@ where
         Ok(res.header)
     }
 
+    /// Delete the lock key only if it is still attached to the given lease and owned by the
+    /// given owner
+    async fn delete_lock_key(
+        &self,
+        key: Vec<u8>,
+        lease_id: i64,
+        owner: Vec<u8>,
+        auth_info: Option<AuthInfo>,
+    ) -> Result<Option<ResponseHeader>, tonic::Status> {
+        #[allow(clippy::as_conversions)] // this cast is always safe
+        let cmp_lease = Compare {
+            result: CompareResult::Equal as i32,
+            target: CompareTarget::Lease as i32,
+            key: key.clone(),
+            range_end: vec![],
+            target_union: Some(TargetUnion::Lease(lease_id)),
+        };
+        #[allow(clippy::as_conversions)] // this cast is always safe
+        let cmp_owner = Compare {
+            result: CompareResult::Equal as i32,
+            target: CompareTarget::Value as i32,
+            key: key.clone(),
+            range_end: vec![],
+            target_union: Some(TargetUnion::Value(owner)),
+        };
+        let del = RequestOp {
+            request: Some(Request::RequestDeleteRange(DeleteRangeRequest {
+                key,
+                ..Default::default()
+            })),
+        };
+        let txn = TxnRequest {
+            compare: vec![cmp_lease, cmp_owner],
+            success: vec![del],
+            failure: vec![],
+        };
+        let (cmd_res, _) = self.propose(txn, auth_info, true).await?;
+        let res = Into::<TxnResponse>::into(cmd_res.into_inner());
+        if !res.succeeded {
+            return Err(tonic::Status::failed_precondition(
+                "lock: the lock key is held by another session or user",
+            ));
+        }
+        Ok(res.header)
+    }
+
     /// Lease grant
     async fn lease_grant(&self, auth_info: Option<AuthInfo>) -> Result<i64, tonic::Status> {
         let lease_id = self.id_gen.next();
@@ -208,6 +262,57 @@ where
         let res = Into::<LeaseGrantResponse>::into(cmd_res.into_inner());
         Ok(res.id)
     }
+
+    /// Revoke a lease granted by `lease_grant`, the lock key is the only key attached to it
+    async fn lease_revoke(
+        &self,
+        lease_id: i64,
+        key: &[u8],
+        auth_info: Option<AuthInfo>,
+    ) -> Result<(), tonic::Status> {
+        let request = RequestWrapper::from(LeaseRevokeRequest { id: lease_id });
+        let cmd = Command::new_with_auth_info(vec![KeyRange::new(key, "")], request, auth_info);
+        let _res = self.client.propose(&cmd, None, true).await??;
+        Ok(())
+    }
+
+    /// Clean up after a failed lock: revoke the lease if it's granted by the request, which
+    /// also deletes the lock key, or delete the lock key if it's created by the request.
+    /// A lock key created before by the same session is left alone.
+    async fn abort_lock(
+        &self,
+        key: &[u8],
+        created: bool,
+        granted_lease: Option<i64>,
+        auth_info: Option<AuthInfo>,
+    ) {
+        match granted_lease {
+            Some(lease_id) => {
+                if let Err(e) = self.lease_revoke(lease_id, key, auth_info).await {
+                    warn!("failed to revoke lease {lease_id} of a failed lock: {e}");
+                }
+            }
+            None if created => {
+                if let Err(e) = self.delete_key(key, auth_info).await {
+                    warn!("failed to delete the key of a failed lock: {e}");
+                }
+            }
+            None => {}
+        }
+    }
+}
+
+/// The owner of the lock keys created by a request, which is the user name, or empty if
+/// the auth is disabled
+fn lock_owner(auth_info: Option<&AuthInfo>) -> Vec<u8> {
+    auth_info.map_or_else(Vec::new, |info| info.username.as_bytes().to_vec())
+}
+
+/// Get the lease id encoded in the last segment of a lock key
+fn lock_key_lease(key: &[u8]) -> Option<i64> {
+    let pos = key.iter().rposition(|b| *b == b'/')?;
+    let suffix = std::str::from_utf8(key.get(pos.overflow_add(1)..)?).ok()?;
+    i64::from_str_radix(suffix, 16).ok()
 }
 
 #[tonic::async_trait]
@@ -220,28 +325,32 @@ where
     /// lock is held by the caller. This key can be used in conjunction with
     /// transactions to safely ensure updates to etcd only occur while holding
     /// lock ownership. The lock is held until Unlock is called on the key or the
-    /// lease associate with the owner expires.
+    /// lease associate with the owner expires. If the request carries the
+    /// `try-lock` metadata, it fails instead of waiting when the lock is held
+    /// by another session. The fencing token of the lock is returned in the
+    /// `fencing-token` metadata of the response.
     async fn lock(
         &self,
         request: tonic::Request<LockRequest>,
     ) -> Result<tonic::Response<LockResponse>, tonic::Status> {
         debug!("Receive LockRequest {:?}", request);
         let auth_info = self.auth_store.try_get_auth_info_from_request(&request)?;
+        let try_lock = request.metadata().contains_key(TRY_LOCK_METADATA_KEY);
         let lock_req = request.into_inner();
-        let lease_id = if lock_req.lease == 0 {
-            self.lease_grant(auth_info.clone()).await?
+        let granted_lease = if lock_req.lease == 0 {
+            Some(self.lease_grant(auth_info.clone()).await?)
         } else {
-            lock_req.lease
+            None
         };
+        let lease_id = granted_lease.unwrap_or(lock_req.lease);
 
         let prefix = format!("{}/", String::from_utf8_lossy(&lock_req.name).into_owned());
         let key = format!("{prefix}{lease_id:x}");
 
-        let txn = Self::create_acquire_txn(&prefix, lease_id);
+        let owner = lock_owner(auth_info.as_ref());
+        let txn = Self::create_acquire_txn(&prefix, lease_id, owner);
         let (cmd_res, sync_res) = self.propose(txn, auth_info.clone(), false).await?;
         let mut txn_res = Into::<TxnResponse>::into(cmd_res.into_inner());
-        #[allow(clippy::unwrap_used)] // sync_res always has value when use slow path
-        let my_rev = sync_res.unwrap().revision();
         let owner_res = txn_res
             .responses
             .swap_remove(1)
@@ -254,6 +363,23 @@ where
                 }
             })
             .unwrap_or_else(|| unreachable!("owner_resp should be a Get response"));
+        // The create revision of the lock key, which is also used as the fencing token
+        let my_rev = if txn_res.succeeded {
+            #[allow(clippy::unwrap_used)] // sync_res always has value when use slow path
+            sync_res.unwrap().revision()
+        } else {
+            // the key has been created by the same session before
+            txn_res
+                .responses
+                .first()
+                .and_then(|r| match r.response {
+                    Some(Response::ResponseRange(ref res)) => {
+                        res.kvs.first().map(|kv| kv.create_revision)
+                    }
+                    _ => None,
+                })
+                .unwrap_or_else(|| unreachable!("the lock key should exist in failure branch"))
+        };
 
         let owner_key = owner_res.kvs;
         let header = if owner_key
@@ -261,9 +387,16 @@ where
             .map_or(false, |kv| kv.create_revision == my_rev)
         {
             owner_res.header
+        } else if try_lock {
+            self.abort_lock(key.as_bytes(), txn_res.succeeded, granted_lease, auth_info)
+                .await;
+            return Err(tonic::Status::failed_precondition(
+                "lock: the lock is held by another session",
+            ));
         } else {
             if let Err(e) = self.wait_delete(prefix, my_rev, auth_info.as_ref()).await {
-                let _ignore = self.delete_key(key.as_bytes(), auth_info).await;
+                self.abort_lock(key.as_bytes(), txn_res.succeeded, granted_lease, auth_info)
+                    .await;
                 return Err(e);
             }
             let range_req = RangeRequest {
@@ -280,7 +413,8 @@ where
                     res.header
                 }
                 Err(e) => {
-                    let _ignore = self.delete_key(key.as_bytes(), auth_info).await;
+                    self.abort_lock(key.as_bytes(), txn_res.succeeded, granted_lease, auth_info)
+                        .await;
                     return Err(e);
                 }
             }
@@ -289,19 +423,50 @@ where
             header,
             key: key.into_bytes(),
         };
-        Ok(tonic::Response::new(res))
+        let mut response = tonic::Response::new(res);
+        let _prev = response
+            .metadata_mut()
+            .insert(FENCING_TOKEN_METADATA_KEY, my_rev.into());
+        Ok(response)
     }
 
     /// Unlock takes a key returned by Lock and releases the hold on lock. The
     /// next Lock caller waiting for the lock will then be woken up and given
-    /// ownership of the lock.
+    /// ownership of the lock. Keys that are not held by a lock session, or that
+    /// are held by another user, are rejected.
     async fn unlock(
         &self,
         request: tonic::Request<UnlockRequest>,
     ) -> Result<tonic::Response<UnlockResponse>, tonic::Status> {
         debug!("Receive UnlockRequest {:?}", request);
         let auth_info = self.auth_store.try_get_auth_info_from_request(&request)?;
-        let header = self.delete_key(&request.get_ref().key, auth_info).await?;
+        let key = request.into_inner().key;
+        let range_req = RangeRequest {
+            key: key.clone(),
+            ..Default::default()
+        };
+        let (cmd_res, _) = self.propose(range_req, auth_info.clone(), true).await?;
+        let range_res = Into::<RangeResponse>::into(cmd_res.into_inner());
+        let Some(kv) = range_res.kvs.first() else {
+            // the lock has already been released, e.g. the session expired
+            return Ok(tonic::Response::new(UnlockResponse {
+                header: range_res.header,
+            }));
+        };
+        if kv.lease == 0 || lock_key_lease(&kv.key) != Some(kv.lease) {
+            return Err(tonic::Status::failed_precondition(
+                "lock: the key is not held by a lock session",
+            ));
+        }
+        let owner = lock_owner(auth_info.as_ref());
+        if kv.value != owner {
+            return Err(tonic::Status::permission_denied(
+                "lock: the lock is held by another user",
+            ));
+        }
+        let header = self
+            .delete_lock_key(key, kv.lease, owner, auth_info)
+            .await?;
         Ok(tonic::Response::new(UnlockResponse { header }))
     }
 }
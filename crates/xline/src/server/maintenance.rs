@@ -3,24 +3,31 @@ use std::{fmt::Debug, pin::Pin, sync::Arc};
 use async_stream::try_stream;
 use bytes::BytesMut;
 use clippy_utilities::{NumericCast, OverflowArithmetic};
-use curp::{cmd::CommandExecutor as _, members::ClusterInfo, server::RawCurp};
+use curp::{members::ClusterInfo, server::RawCurp};
 use engine::SnapshotApi;
 use futures::stream::Stream;
 use sha2::{Digest, Sha256};
-use tracing::{debug, error};
+#[cfg(not(madsim))]
+use tonic::transport::ClientTlsConfig;
+use tracing::{debug, error, info};
+#[cfg(madsim)]
+use utils::ClientTlsConfig;
 use xlineapi::{
     command::{Command, CommandResponse, CurpClient, SyncResponse},
+    execute_error::ExecuteError,
     RequestWrapper,
 };
 
-use super::command::CommandExecutor;
+use super::{
+    build_info::VersionInfo, command::CommandExecutor, feature_gate, lease_server::build_endpoints,
+};
 use crate::{
     header_gen::HeaderGenerator,
     rpc::{
         AlarmRequest, AlarmResponse, DefragmentRequest, DefragmentResponse, DowngradeRequest,
         DowngradeResponse, HashKvRequest, HashKvResponse, HashRequest, HashResponse, Maintenance,
-        MoveLeaderRequest, MoveLeaderResponse, SnapshotRequest, SnapshotResponse, StatusRequest,
-        StatusResponse,
+        MaintenanceClient, MoveLeaderRequest, MoveLeaderResponse, SnapshotRequest,
+        SnapshotResponse, StatusRequest, StatusResponse,
     },
     state::State,
     storage::{storage_api::StorageApi, AlarmStore, AuthStore, KvStore},
@@ -54,6 +61,8 @@ where
     ce: Arc<CommandExecutor<S>>,
     /// Alarm store
     alarm_store: Arc<AlarmStore<S>>,
+    /// Client tls config
+    client_tls_config: Option<ClientTlsConfig>,
 }
 
 impl<S> MaintenanceServer<S>
@@ -72,6 +81,7 @@ where
         raw_curp: Arc<RawCurp<Command, State<S, Arc<CurpClient>>>>,
         ce: Arc<CommandExecutor<S>>,
         alarm_store: Arc<AlarmStore<S>>,
+        client_tls_config: Option<ClientTlsConfig>,
     ) -> Self {
         Self {
             kv_store,
@@ -83,6 +93,7 @@ where
             raw_curp,
             ce,
             alarm_store,
+            client_tls_config,
         }
     }
 
@@ -136,39 +147,68 @@ where
             error!("get file size failed, {e}");
             tonic::Status::internal("get file size failed")
         })?;
-        let last_applied = self.ce.last_applied().map_err(|e| {
-            error!("get last applied failed, {e}");
-            tonic::Status::internal("get last applied failed")
+        let size_in_use = self.persistent.live_data_size().map_err(|e| {
+            error!("get live data size failed, {e}");
+            tonic::Status::internal("get live data size failed")
         })?;
+        let applied_indexes = self.ce.applied_indexes();
         let mut errors = vec![];
         if leader.is_none() {
             errors.push("etcdserver: no leader".to_owned());
         }
+        if applied_indexes.is_recovering() {
+            errors.push(format!(
+                "xline: recovering, replaying the log from applied index {} to {}",
+                applied_indexes.applied(),
+                applied_indexes.replay_until()
+            ));
+        }
         for a in self.alarm_store.get_all_alarms() {
             errors.push(a.to_string());
         }
+        let version_info = VersionInfo::new(&self.cluster_info);
         let response = StatusResponse {
             header: Some(self.header_gen.gen_header()),
-            version: env!("CARGO_PKG_VERSION").to_owned(),
+            version: version_info.xlineserver.clone(),
             db_size: size.numeric_cast(),
             leader: leader.unwrap_or(0), // None means this member believes there is no leader
             raft_index: commit_index,
             raft_term: term,
-            raft_applied_index: last_applied,
+            raft_applied_index: applied_indexes.applied(),
             errors,
-            db_size_in_use: size.numeric_cast(),
+            db_size_in_use: size_in_use.numeric_cast(),
             is_learner,
         };
-        Ok(tonic::Response::new(response))
+        let mut response = tonic::Response::new(response);
+        version_info.attach_to(&mut response);
+        Ok(response)
     }
 
     async fn defragment(
         &self,
-        _request: tonic::Request<DefragmentRequest>,
+        request: tonic::Request<DefragmentRequest>,
     ) -> Result<tonic::Response<DefragmentResponse>, tonic::Status> {
-        Err(tonic::Status::unimplemented(
-            "defragment is unimplemented".to_owned(),
-        ))
+        self.auth_store
+            .check_admin_permission_from_request(&request)?;
+        let persistent = Arc::clone(&self.persistent);
+        let (size_before, size_after) = tokio::task::spawn_blocking(move || {
+            let size_before = persistent.file_size()?;
+            persistent.defragment()?;
+            let size_after = persistent.file_size()?;
+            Ok::<_, ExecuteError>((size_before, size_after))
+        })
+        .await
+        .map_err(|e| {
+            error!("defragment task failed, {e}");
+            tonic::Status::internal("defragment task failed")
+        })??;
+        info!(
+            "defragment finished, db size: {size_before} -> {size_after}, reclaimed {} bytes",
+            size_before.saturating_sub(size_after)
+        );
+        Ok(tonic::Response::new(DefragmentResponse {
+            header: Some(self.header_gen.gen_header()),
+        }))
     }
 
     async fn hash(
@@ -177,7 +217,7 @@ where
     ) -> Result<tonic::Response<HashResponse>, tonic::Status> {
         Ok(tonic::Response::new(HashResponse {
             header: Some(self.header_gen.gen_header()),
-            hash: self.persistent.hash()?,
+            hash: self.kv_store.hash()?,
         }))
     }
 
@@ -211,6 +251,8 @@ where
         &self,
         request: tonic::Request<MoveLeaderRequest>,
     ) -> Result<tonic::Response<MoveLeaderResponse>, tonic::Status> {
+        self.auth_store
+            .check_admin_permission_from_request(&request)?;
         let node_id = request.into_inner().target_id;
         self.client.move_leader(node_id).await?;
         Ok(tonic::Response::new(MoveLeaderResponse {
@@ -220,16 +262,39 @@ where
 
     async fn downgrade(
         &self,
-        _request: tonic::Request<DowngradeRequest>,
+        request: tonic::Request<DowngradeRequest>,
     ) -> Result<tonic::Response<DowngradeResponse>, tonic::Status> {
-        Err(tonic::Status::unimplemented(
-            "downgrade is unimplemented".to_owned(),
-        ))
+        self.auth_store
+            .check_admin_permission_from_request(&request)?;
+        let (leader_id, _, is_leader) = self.raw_curp.leader();
+        if !is_leader {
+            let leader_addrs = leader_id
+                .and_then(|id| self.cluster_info.client_urls(id))
+                .ok_or_else(|| tonic::Status::unavailable("etcdserver: no leader"))?;
+            let endpoints = build_endpoints(&leader_addrs, self.client_tls_config.as_ref())?;
+            let channel = tonic::transport::Channel::balance_list(endpoints.into_iter());
+            return MaintenanceClient::new(channel).downgrade(request).await;
+        }
+        let downgrade_req = request.into_inner();
+        let version = feature_gate::downgrade(
+            &self.cluster_info,
+            downgrade_req.action(),
+            &downgrade_req.version,
+        )?;
+        info!(
+            "downgrade action {:?} to {} is done at the leader",
+            downgrade_req.action(),
+            downgrade_req.version
+        );
+        Ok(tonic::Response::new(DowngradeResponse {
+            header: Some(self.header_gen.gen_header()),
+            version: version.to_string(),
+        }))
     }
 }
 
 /// Generate snapshot stream
-fn snapshot_stream<S: StorageApi>(
+pub(super) fn snapshot_stream<S: StorageApi>(
     header_gen: &HeaderGenerator,
     persistent: &S,
 ) -> Result<impl Stream<Item = Result<SnapshotResponse, tonic::Status>>, tonic::Status> {
@@ -242,12 +307,13 @@ fn snapshot_stream<S: StorageApi>(
     let header = header_gen.gen_header();
 
     let stream = try_stream! {
-        if let Err(e) = snapshot.rewind() {
+        snapshot.rewind().map_err(|e| {
             error!("snapshot rewind failed, {e}");
-            return;
-        }
+            tonic::Status::internal("snapshot rewind failed")
+        })?;
 
-        let mut remain_size = snapshot.size();
+        let snapshot_size = snapshot.size();
+        let mut remain_size = snapshot_size;
         let mut checksum_gen = Sha256::new();
         while remain_size > 0 {
             let buf_size = std::cmp::min(MAINTENANCE_SNAPSHOT_CHUNK_SIZE, remain_size);
@@ -255,10 +321,11 @@ fn snapshot_stream<S: StorageApi>(
             remain_size = remain_size.overflow_sub(buf_size);
             snapshot.read_buf_exact(&mut buf).await.map_err(|_e| {tonic::Status::internal("snapshot read failed")})?;
             // etcd client will use the size of the snapshot to determine whether checksum is included,
-            // and the check method size % 512 == sha256.size, So we need to pad snapshots to multiples
-            // of 512 bytes
-            let padding = MIN_PAGE_SIZE.overflow_sub(buf_size.overflow_rem(MIN_PAGE_SIZE));
-            if padding != 0 {
+            // and the check method size % 512 == sha256.size, So we need to pad the tail of snapshots
+            // to multiples of 512 bytes
+            let tail_size = snapshot_size.overflow_rem(MIN_PAGE_SIZE);
+            if remain_size == 0 && tail_size != 0 {
+                let padding = MIN_PAGE_SIZE.overflow_sub(tail_size);
                 buf.extend_from_slice(&vec![0; padding.numeric_cast()]);
             }
             checksum_gen.update(&buf);
@@ -291,7 +358,13 @@ mod test {
     use utils::config::EngineConfig;
 
     use super::*;
-    use crate::storage::db::DB;
+    use crate::{
+        rpc::KeyValue,
+        storage::{
+            db::{WriteOp, DB},
+            Revision,
+        },
+    };
 
     #[tokio::test]
     #[abort_on_panic]
@@ -301,6 +374,23 @@ mod test {
         let snapshot_path = dir.join("snapshot");
 
         let persistent = DB::open(&EngineConfig::RocksDB(db_path.clone()))?;
+        // write enough data to make the snapshot span multiple chunks
+        let ops = (1..=64)
+            .map(|rev| {
+                let kv = KeyValue {
+                    key: format!("key{rev}").into_bytes(),
+                    value: (0..4096)
+                        .map(|i| ((i * rev) % 251).numeric_cast())
+                        .collect(),
+                    create_revision: rev,
+                    mod_revision: rev,
+                    version: 1,
+                    lease: 0,
+                };
+                WriteOp::PutKeyValue(Revision::new(rev, 0), kv)
+            })
+            .collect();
+        _ = persistent.write_batch(ops, false)?;
         let header_gen = HeaderGenerator::new(0, 0);
         let snap1_stream = snapshot_stream(&header_gen, persistent.as_ref())?;
         tokio::pin!(snap1_stream);
@@ -317,8 +407,12 @@ mod test {
         let size = snap2.size().numeric_cast();
         let mut snap2_data = BytesMut::with_capacity(size);
         snap2.read_buf_exact(&mut snap2_data).await.unwrap();
+        assert!(size > MAINTENANCE_SNAPSHOT_CHUNK_SIZE.numeric_cast());
         let snap1_data = recv_data[..size].to_vec();
         assert_eq!(snap1_data, snap2_data);
+        let padding = &recv_data[size..recv_data.len() - Sha256::output_size()];
+        assert!(padding.len() < MIN_PAGE_SIZE.numeric_cast());
+        assert!(padding.iter().all(|b| *b == 0));
 
         snap2.clean().await.unwrap();
         std::fs::remove_dir_all(dir).unwrap();
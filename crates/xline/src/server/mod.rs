@@ -1,13 +1,43 @@
+/// Xline admin server
+mod admin_server;
+/// Admission control of the client requests
+mod admission;
+/// Applied indexes of the state machine
+mod applied_index;
+/// Audit log
+mod audit;
+/// Authentication of the requests of client services
+#[cfg(not(madsim))]
+mod auth_interceptor;
+/// Lockout after failed authentications
+mod auth_lockout;
 /// Xline auth server
 mod auth_server;
 /// Auth Wrapper
 mod auth_wrapper;
+/// Periodic local backups
+pub(crate) mod backup;
 /// Barriers for range requests
 mod barriers;
+/// Versions and build info of the server
+mod build_info;
+/// Client connections with a max age and a limited number
+#[cfg(not(madsim))]
+mod client_connection;
 /// Cluster server
 mod cluster_server;
 /// Command to be executed
 pub(crate) mod command;
+/// Xline election server
+mod election_server;
+/// Gate of the features by the version negotiated by the cluster
+pub(crate) mod feature_gate;
+/// HTTP/JSON gateway of the gRPC services
+#[cfg(not(madsim))]
+mod gateway;
+/// grpc-web support of the client services
+#[cfg(not(madsim))]
+mod grpc_web;
 /// Xline kv server
 mod kv_server;
 /// Xline lease server
@@ -16,6 +46,14 @@ mod lease_server;
 mod lock_server;
 /// Xline maintenance client
 mod maintenance;
+/// Overload protection of the server
+mod overload;
+/// QoS classes of the client requests
+mod qos;
+/// Structured logs of requests
+mod request_log;
+/// Ending the client streams on graceful shutdown
+mod stream_shutdown;
 /// Xline watch server
 mod watch_server;
 /// Xline server
@@ -0,0 +1,202 @@
+use std::{
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use clippy_utilities::OverflowArithmetic;
+use opentelemetry::KeyValue;
+use parking_lot::Mutex;
+use tokio::time::Instant;
+use tonic::Status;
+use tracing::warn;
+
+use crate::metrics;
+
+/// Load in percent above which the serializable reads are shed
+const SHED_READS_PERCENT: u64 = 80;
+/// Load in percent above which the writes and linearizable reads are shed
+const SHED_WRITES_PERCENT: u64 = 100;
+/// How long a sample of the resident memory is reused
+const MEMORY_SAMPLE_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Priority of a request, requests of a lower priority are shed first when the server
+/// is overloaded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Priority {
+    /// Serializable reads, which can be served by any other member
+    SerializableRead,
+    /// Writes and linearizable reads
+    Write,
+}
+
+impl Priority {
+    /// Load in percent above which requests of this priority are shed
+    fn shed_percent(self) -> u64 {
+        match self {
+            Priority::SerializableRead => SHED_READS_PERCENT,
+            Priority::Write => SHED_WRITES_PERCENT,
+        }
+    }
+
+    /// Name of the priority used in the metrics
+    fn as_str(self) -> &'static str {
+        match self {
+            Priority::SerializableRead => "serializable_read",
+            Priority::Write => "write",
+        }
+    }
+}
+
+/// Monitor of the apply queue depth and the memory pressure of the server, which
+/// rejects requests instead of queueing them when the server is saturated
+#[derive(Debug)]
+pub(crate) struct OverloadMonitor {
+    /// Max number of commands waiting to be applied, `0` means unlimited
+    max_apply_queue: usize,
+    /// Max resident memory in bytes, `0` means unlimited
+    memory_limit: u64,
+    /// Number of commands waiting to be applied
+    apply_queue: AtomicUsize,
+    /// Resident memory in bytes of the last sample
+    memory: AtomicU64,
+    /// When the resident memory is sampled last time
+    sampled_at: Mutex<Option<Instant>>,
+}
+
+impl OverloadMonitor {
+    /// New `OverloadMonitor`
+    pub(crate) fn new(max_apply_queue: usize, memory_limit: u64) -> Self {
+        Self {
+            max_apply_queue,
+            memory_limit,
+            apply_queue: AtomicUsize::new(0),
+            memory: AtomicU64::new(0),
+            sampled_at: Mutex::new(None),
+        }
+    }
+
+    /// Update the number of commands waiting to be applied
+    pub(crate) fn set_apply_queue_depth(&self, depth: usize) {
+        self.apply_queue.store(depth, Ordering::Relaxed);
+    }
+
+    /// Admit a request of the given priority, or reject it with `RESOURCE_EXHAUSTED`
+    /// if the server is too busy to serve it
+    pub(crate) fn admit(&self, priority: Priority) -> Result<(), Status> {
+        let load = self.load_percent();
+        if load < priority.shed_percent() {
+            return Ok(());
+        }
+        metrics::get()
+            .shed_requests_total
+            .add(1, &[KeyValue::new("priority", priority.as_str())]);
+        warn!(
+            "shed a {} request, the server load is {load}%",
+            priority.as_str()
+        );
+        Err(Status::resource_exhausted("etcdserver: too many requests"))
+    }
+
+    /// Load of the server in percent of its most saturated limit
+    fn load_percent(&self) -> u64 {
+        let queue_load = u64::try_from(self.max_apply_queue)
+            .ok()
+            .filter(|max| *max != 0)
+            .map_or(0, |max| {
+                let depth =
+                    u64::try_from(self.apply_queue.load(Ordering::Relaxed)).unwrap_or(u64::MAX);
+                depth.saturating_mul(100).overflow_div(max)
+            });
+        let memory_load = if self.memory_limit == 0 {
+            0
+        } else {
+            self.resident_memory()
+                .saturating_mul(100)
+                .overflow_div(self.memory_limit)
+        };
+        queue_load.max(memory_load)
+    }
+
+    /// Resident memory of the server, sampled at most once per `MEMORY_SAMPLE_INTERVAL`
+    fn resident_memory(&self) -> u64 {
+        // another request is sampling, the last sample is good enough
+        if let Some(mut sampled_at) = self.sampled_at.try_lock() {
+            let now = Instant::now();
+            let expired = sampled_at.map_or(true, |at| {
+                now.saturating_duration_since(at) >= MEMORY_SAMPLE_INTERVAL
+            });
+            if expired {
+                *sampled_at = Some(now);
+                self.memory
+                    .store(sample_resident_memory().unwrap_or(0), Ordering::Relaxed);
+            }
+        }
+        self.memory.load(Ordering::Relaxed)
+    }
+}
+
+/// Read the resident memory of the current process, `None` if it is unavailable
+#[cfg(not(madsim))]
+fn sample_resident_memory() -> Option<u64> {
+    std::fs::read_to_string("/proc/self/status")
+        .ok()
+        .and_then(|status| parse_resident_memory(&status))
+}
+
+/// The memory of the host process is not simulated, reading it in the madsim simulation
+/// would make the shedding nondeterministic
+#[cfg(madsim)]
+fn sample_resident_memory() -> Option<u64> {
+    None
+}
+
+/// Parse the resident memory in bytes from the content of `/proc/self/status`
+#[cfg_attr(madsim, allow(dead_code))]
+fn parse_resident_memory(status: &str) -> Option<u64> {
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kb = line
+        .trim_start_matches("VmRSS:")
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+    kb.checked_mul(1024)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn serializable_reads_should_be_shed_before_writes() {
+        let monitor = OverloadMonitor::new(10, 0);
+        monitor.set_apply_queue_depth(5);
+        assert!(monitor.admit(Priority::SerializableRead).is_ok());
+        assert!(monitor.admit(Priority::Write).is_ok());
+        monitor.set_apply_queue_depth(8);
+        let err = monitor.admit(Priority::SerializableRead).unwrap_err();
+        assert_eq!(err.code(), tonic::Code::ResourceExhausted);
+        assert!(monitor.admit(Priority::Write).is_ok());
+        monitor.set_apply_queue_depth(10);
+        assert!(monitor.admit(Priority::SerializableRead).is_err());
+        assert!(monitor.admit(Priority::Write).is_err());
+        monitor.set_apply_queue_depth(0);
+        assert!(monitor.admit(Priority::SerializableRead).is_ok());
+    }
+
+    #[test]
+    fn unlimited_monitor_should_admit_everything() {
+        let monitor = OverloadMonitor::new(0, 0);
+        monitor.set_apply_queue_depth(usize::MAX);
+        assert!(monitor.admit(Priority::SerializableRead).is_ok());
+        assert!(monitor.admit(Priority::Write).is_ok());
+    }
+
+    #[test]
+    fn resident_memory_should_be_parsed() {
+        let status = "Name:\txline\nVmPeak:\t  204800 kB\nVmRSS:\t   10240 kB\nThreads:\t8\n";
+        assert_eq!(parse_resident_memory(status), Some(10 * 1024 * 1024));
+        assert_eq!(parse_resident_memory("Name:\txline\n"), None);
+    }
+}
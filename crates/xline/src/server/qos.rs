@@ -0,0 +1,171 @@
+use std::{collections::HashMap, sync::Arc};
+
+use tonic::{Request, Status};
+use utils::{
+    config::{QosClass, UserQosClass},
+    parse_qos_class,
+};
+use xlineapi::AuthInfo;
+
+use super::feature_gate::{Feature, FeatureGate};
+
+/// Metadata key of the qos class of a request
+pub(crate) const QOS_CLASS_KEY: &str = "qos-class";
+
+/// Classifier of the qos classes of the client requests
+#[derive(Debug)]
+pub(crate) struct QosClassifier {
+    /// QoS classes of the requests of users
+    user_classes: HashMap<String, QosClass>,
+    /// Feature gate, the classes are not propagated until all members understand them
+    feature_gate: Arc<FeatureGate>,
+}
+
+impl QosClassifier {
+    /// New `QosClassifier`
+    pub(crate) fn new(user_classes: &[UserQosClass], feature_gate: Arc<FeatureGate>) -> Self {
+        Self {
+            user_classes: user_classes
+                .iter()
+                .map(|user_class| (user_class.user.clone(), user_class.class))
+                .collect(),
+            feature_gate,
+        }
+    }
+
+    /// QoS class of a request, the class of its user, the users are only known when the
+    /// auth is enabled. The class tagged in its metadata is honored only if it is not
+    /// higher than the class of its user, so clients can only lower their own class.
+    /// Every request is `Normal` before the feature is enabled by the cluster.
+    pub(crate) fn classify<T>(
+        &self,
+        request: &Request<T>,
+        auth_info: Option<&AuthInfo>,
+    ) -> Result<QosClass, Status> {
+        if !self.feature_gate.is_enabled(Feature::QosClass) {
+            return Ok(QosClass::Normal);
+        }
+        let user_class = auth_info
+            .and_then(|info| self.user_classes.get(&info.username))
+            .copied()
+            .unwrap_or_default();
+        let Some(value) = request.metadata().get(QOS_CLASS_KEY) else {
+            return Ok(user_class);
+        };
+        let tagged_class = value
+            .to_str()
+            .ok()
+            .and_then(|class| parse_qos_class(class).ok())
+            .ok_or_else(|| Status::invalid_argument(format!("invalid {QOS_CLASS_KEY} metadata")))?;
+        if rank(tagged_class) > rank(user_class) {
+            return Ok(user_class);
+        }
+        Ok(tagged_class)
+    }
+}
+
+/// Rank of a qos class, a higher class has a higher rank
+#[allow(clippy::wildcard_enum_match_arm)] // new classes rank as normal
+fn rank(class: QosClass) -> u8 {
+    match class {
+        QosClass::Critical => 2,
+        QosClass::Batch => 0,
+        _ => 1,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use curp::members::ClusterInfo;
+    use utils::version::Version;
+
+    use super::*;
+
+    fn auth_info(username: &str) -> AuthInfo {
+        AuthInfo {
+            username: username.to_owned(),
+            auth_revision: 1,
+        }
+    }
+
+    #[test]
+    fn requests_should_be_classified_by_user_and_lowered_by_metadata() {
+        let cluster_info = Arc::new(ClusterInfo::from_members_map(
+            HashMap::from([("S1".to_owned(), vec!["S1".to_owned()])]),
+            [],
+            "S1",
+        ));
+        let classifier = QosClassifier::new(
+            &[UserQosClass::new(
+                "kube-apiserver".to_owned(),
+                QosClass::Critical,
+            )],
+            Arc::new(FeatureGate::new(Arc::clone(&cluster_info))),
+        );
+        let request = Request::new(());
+        // the classes are not propagated before the feature is enabled
+        assert_eq!(
+            classifier
+                .classify(&request, Some(&auth_info("kube-apiserver")))
+                .unwrap(),
+            QosClass::Normal
+        );
+        let _raised = cluster_info.raise_negotiated_version(Version::new(0, 6, 1));
+        assert_eq!(
+            classifier
+                .classify(&request, Some(&auth_info("kube-apiserver")))
+                .unwrap(),
+            QosClass::Critical
+        );
+        assert_eq!(
+            classifier
+                .classify(&request, Some(&auth_info("alice")))
+                .unwrap(),
+            QosClass::Normal
+        );
+        assert_eq!(
+            classifier.classify(&request, None).unwrap(),
+            QosClass::Normal
+        );
+
+        let mut request = Request::new(());
+        let _ignore = request
+            .metadata_mut()
+            .insert(QOS_CLASS_KEY, "batch".parse().unwrap());
+        assert_eq!(
+            classifier
+                .classify(&request, Some(&auth_info("kube-apiserver")))
+                .unwrap(),
+            QosClass::Batch
+        );
+
+        // the metadata can not raise the class above the class of the user
+        let mut request = Request::new(());
+        let _ignore = request
+            .metadata_mut()
+            .insert(QOS_CLASS_KEY, "critical".parse().unwrap());
+        assert_eq!(
+            classifier
+                .classify(&request, Some(&auth_info("alice")))
+                .unwrap(),
+            QosClass::Normal
+        );
+        assert_eq!(
+            classifier.classify(&request, None).unwrap(),
+            QosClass::Normal
+        );
+        assert_eq!(
+            classifier
+                .classify(&request, Some(&auth_info("kube-apiserver")))
+                .unwrap(),
+            QosClass::Critical
+        );
+
+        let mut request = Request::new(());
+        let _ignore = request
+            .metadata_mut()
+            .insert(QOS_CLASS_KEY, "urgent".parse().unwrap());
+        let err = classifier.classify(&request, None).unwrap_err();
+        assert_eq!(err.code(), tonic::Code::InvalidArgument);
+    }
+}
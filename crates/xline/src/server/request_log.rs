@@ -0,0 +1,139 @@
+use std::time::{Duration, Instant};
+
+use tracing::{debug, warn};
+
+use crate::rpc::{
+    CompactionResponse, DeleteRangeResponse, PutResponse, RangeResponse, TxnResponse,
+};
+
+/// Summary of a kv response that is logged when the request finishes
+pub(crate) trait ResponseSummary {
+    /// Number of keys returned or changed by the request
+    fn keys(&self) -> i64;
+
+    /// Revision in the header of the response
+    fn revision(&self) -> Option<i64>;
+}
+
+/// Implement `ResponseSummary` for responses with a header
+macro_rules! impl_response_summary {
+    ($($response:ty => |$resp:ident| $keys:expr),* $(,)?) => {
+        $(
+            impl ResponseSummary for $response {
+                fn keys(&self) -> i64 {
+                    let $resp = self;
+                    $keys
+                }
+
+                fn revision(&self) -> Option<i64> {
+                    self.header.as_ref().map(|header| header.revision)
+                }
+            }
+        )*
+    };
+}
+
+impl_response_summary!(
+    RangeResponse => |resp| resp.count,
+    PutResponse => |_resp| 1,
+    DeleteRangeResponse => |resp| resp.deleted,
+    TxnResponse => |resp| resp.responses.len().try_into().unwrap_or(i64::MAX),
+    CompactionResponse => |_resp| 0,
+);
+
+/// Logs a structured summary of every request, requests that take longer than the
+/// threshold are logged as slow requests at warn level, the others at debug level
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct RequestLogger {
+    /// Id of the current node, which proposes the requests it serves
+    node_id: u64,
+    /// Requests that take longer than this threshold are slow requests
+    slow_threshold: Duration,
+}
+
+impl RequestLogger {
+    /// New `RequestLogger`
+    pub(crate) fn new(node_id: u64, slow_threshold: Duration) -> Self {
+        Self {
+            node_id,
+            slow_threshold,
+        }
+    }
+
+    /// Start logging a request on the key range, `proposed` is whether the request is
+    /// proposed to the cluster or served by the current node only
+    pub(crate) fn start(
+        &self,
+        method: &'static str,
+        key: &[u8],
+        range_end: &[u8],
+        proposed: bool,
+    ) -> RequestLog {
+        RequestLog {
+            logger: *self,
+            method,
+            key: String::from_utf8_lossy(key).into_owned(),
+            range_end: String::from_utf8_lossy(range_end).into_owned(),
+            proposed,
+            start: Instant::now(),
+        }
+    }
+}
+
+/// An ongoing request
+#[derive(Debug)]
+pub(crate) struct RequestLog {
+    /// The logger
+    logger: RequestLogger,
+    /// The rpc method
+    method: &'static str,
+    /// Key of the request
+    key: String,
+    /// Range end of the request
+    range_end: String,
+    /// Whether the request is proposed to the cluster
+    proposed: bool,
+    /// When the request started
+    start: Instant,
+}
+
+impl RequestLog {
+    /// Log the result of the request
+    pub(crate) fn finish<T: ResponseSummary>(
+        self,
+        result: &Result<tonic::Response<T>, tonic::Status>,
+    ) {
+        let latency = self.start.elapsed();
+        let (keys, revision, error) = match *result {
+            Ok(ref resp) => (Some(resp.get_ref().keys()), resp.get_ref().revision(), None),
+            Err(ref status) => (None, None, Some(status.code())),
+        };
+        let proposer = self.proposed.then_some(self.logger.node_id);
+        let latency_ms = u64::try_from(latency.as_millis()).unwrap_or(u64::MAX);
+        if latency >= self.logger.slow_threshold {
+            warn!(
+                method = self.method,
+                key = %self.key,
+                range_end = %self.range_end,
+                ?keys,
+                ?revision,
+                latency_ms,
+                ?proposer,
+                ?error,
+                "slow request"
+            );
+        } else {
+            debug!(
+                method = self.method,
+                key = %self.key,
+                range_end = %self.range_end,
+                ?keys,
+                ?revision,
+                latency_ms,
+                ?proposer,
+                ?error,
+                "request finished"
+            );
+        }
+    }
+}
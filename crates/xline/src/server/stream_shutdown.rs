@@ -0,0 +1,52 @@
+use std::pin::Pin;
+
+use async_stream::stream;
+use futures::{stream::Stream, StreamExt};
+use tokio::sync::watch;
+
+/// Ends the long-lived server streams of the client services when the client-facing
+/// server starts a graceful shutdown. The server waits for all open streams before it
+/// stops, so a watch or a keep alive stream would otherwise hold the shutdown until
+/// the drain times out.
+#[derive(Debug, Clone)]
+pub(crate) struct StreamShutdown {
+    /// Set once the client-facing server starts shutting down
+    client_shutdown: watch::Receiver<bool>,
+}
+
+impl StreamShutdown {
+    /// New `StreamShutdown`
+    pub(crate) fn new(client_shutdown: watch::Receiver<bool>) -> Self {
+        Self { client_shutdown }
+    }
+
+    /// Forward the items of `stream` until the shutdown starts, then end it with an
+    /// `Unavailable` status so that the client retries on another member
+    #[allow(clippy::arithmetic_side_effects, clippy::ignored_unit_patterns)] // Introduced by tokio::select!
+    pub(crate) fn guard<S, T>(
+        &self,
+        stream: S,
+    ) -> Pin<Box<dyn Stream<Item = Result<T, tonic::Status>> + Send>>
+    where
+        S: Stream<Item = Result<T, tonic::Status>> + Send + 'static,
+        T: Send + 'static,
+    {
+        let mut client_shutdown = self.client_shutdown.clone();
+        Box::pin(stream! {
+            tokio::pin!(stream);
+            loop {
+                tokio::select! {
+                    item = stream.next() => match item {
+                        Some(item) => yield item,
+                        None => break,
+                    },
+                    // a dropped sender means the server is gone as well
+                    _ = client_shutdown.wait_for(|stop| *stop) => {
+                        yield Err(tonic::Status::unavailable("etcdserver: server stopped"));
+                        break;
+                    }
+                }
+            }
+        })
+    }
+}
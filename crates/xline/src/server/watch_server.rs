@@ -1,21 +1,25 @@
 use std::{
     collections::{HashMap, HashSet},
+    pin::Pin,
     sync::Arc,
     time::Duration,
 };
 
+use clippy_utilities::OverflowArithmetic;
 use event_listener::Event;
+use prost::Message;
 use tokio::sync::mpsc;
 use tokio_stream::{wrappers::ReceiverStream, Stream, StreamExt};
 use tracing::{debug, warn};
 use utils::task_manager::{tasks::TaskName, Listener, TaskManager};
 use xlineapi::command::KeyRange;
 
+use super::{admission::StreamLimiter, stream_shutdown::StreamShutdown};
 use crate::{
     header_gen::HeaderGenerator,
     rpc::{
-        RequestUnion, ResponseHeader, Watch, WatchCancelRequest, WatchCreateRequest,
-        WatchProgressRequest, WatchRequest, WatchResponse,
+        Event as PbEvent, RequestUnion, ResponseHeader, Watch, WatchCancelRequest,
+        WatchCreateRequest, WatchProgressRequest, WatchRequest, WatchResponse,
     },
     storage::{
         kvwatcher::{KvWatcher, KvWatcherOps, WatchEvent, WatchId, WatchIdGenerator},
@@ -26,6 +30,10 @@ use crate::{
 /// Default channel size
 pub(crate) const CHANNEL_SIZE: usize = 1024;
 
+/// Max size of events in one `WatchResponse`, a larger response will be split into
+/// fragments if the watcher enables `fragment`
+const MAX_FRAGMENT_SIZE: usize = 1536 * 1024;
+
 /// Watch Server
 #[derive(Debug)]
 pub(crate) struct WatchServer<S>
@@ -40,8 +48,12 @@ where
     header_gen: Arc<HeaderGenerator>,
     /// Watch progress notify interval
     watch_progress_notify_interval: Duration,
+    /// Limit of the watch streams of each connection
+    stream_limiter: StreamLimiter,
     /// Task manager
     task_manager: Arc<TaskManager>,
+    /// Ends the watch streams on graceful shutdown
+    stream_shutdown: StreamShutdown,
 }
 
 impl<S> WatchServer<S>
@@ -53,14 +65,18 @@ where
         watcher: Arc<KvWatcher<S>>,
         header_gen: Arc<HeaderGenerator>,
         watch_progress_notify_interval: Duration,
+        max_streams_per_connection: usize,
         task_manager: Arc<TaskManager>,
+        stream_shutdown: StreamShutdown,
     ) -> Self {
         Self {
             watcher,
             next_id_gen: Arc::new(WatchIdGenerator::new(1)), // watch_id starts from 1, 0 means auto-generating
             header_gen,
             watch_progress_notify_interval,
+            stream_limiter: StreamLimiter::new(max_streams_per_connection),
             task_manager,
+            stream_shutdown,
         }
     }
 
@@ -142,8 +158,13 @@ where
     response_tx: mpsc::Sender<Result<WatchResponse, tonic::Status>>,
     /// Event sender
     event_tx: mpsc::Sender<WatchEvent>,
-    /// Watch ID to watcher map
-    active_watch_ids: HashSet<WatchId>,
+    /// Map from the watch id seen by the client to the id registered in the kv watcher
+    ///
+    /// Clients may choose their own watch ids, which are only unique in one stream, so
+    /// the kv watcher shared by all streams uses ids generated by `next_id_gen` instead
+    active_watch_ids: HashMap<WatchId, WatchId>,
+    /// Map from the id registered in the kv watcher to the watch id seen by the client
+    client_watch_ids: HashMap<WatchId, WatchId>,
     /// Next available `WatchId`
     next_id_gen: Arc<WatchIdGenerator>,
     /// Stop Event
@@ -158,6 +179,8 @@ where
     ///
     /// `false` means the next tick should be skipped
     progress: HashMap<WatchId, bool>,
+    /// Watchers that allow large responses to be split into fragments
+    fragment: HashSet<WatchId>,
 }
 
 impl<W> WatchHandle<W>
@@ -177,12 +200,14 @@ where
             kv_watcher,
             response_tx,
             event_tx,
-            active_watch_ids: HashSet::new(),
+            active_watch_ids: HashMap::new(),
+            client_watch_ids: HashMap::new(),
             next_id_gen,
             stop_notify,
             header_gen,
             prev_kv: HashSet::new(),
             progress: HashMap::new(),
+            fragment: HashSet::new(),
         }
     }
 
@@ -192,11 +217,11 @@ where
         if watch_id == 0 {
             loop {
                 let next = self.next_id_gen.next();
-                if !self.active_watch_ids.contains(&next) {
+                if !self.active_watch_ids.contains_key(&next) {
                     break Some(next);
                 }
             }
-        } else if self.active_watch_ids.contains(&watch_id) {
+        } else if self.active_watch_ids.contains_key(&watch_id) {
             None
         } else {
             Some(watch_id)
@@ -206,19 +231,31 @@ where
     /// Handle `WatchCreateRequest`
     async fn handle_watch_create(&mut self, req: WatchCreateRequest) {
         let Some(watch_id) = self.validate_watch_id(req.watch_id) else {
-            let result = Err(tonic::Status::already_exists(format!(
-                "Watch ID {} has already been used",
-                req.watch_id
-            )));
-            if self.response_tx.send(result).await.is_err() {
+            // Reject the duplicated watch id in band, other watchers on this stream
+            // should not be affected
+            let response = WatchResponse {
+                header: Some(self.header_gen.gen_header()),
+                watch_id: req.watch_id,
+                created: true,
+                canceled: true,
+                cancel_reason: "mvcc: duplicate watch ID provided on the WatchStream".to_owned(),
+                ..WatchResponse::default()
+            };
+            if self.response_tx.send(Ok(response)).await.is_err() {
                 let _ignore = self.stop_notify.notify(1);
             }
             return;
         };
+        // auto-generated ids are unique among all streams, so they can be used directly
+        let inner_id = if req.watch_id == 0 {
+            watch_id
+        } else {
+            self.next_id_gen.next()
+        };
 
         let key_range = KeyRange::new(req.key, req.range_end);
         self.kv_watcher.watch(
-            watch_id,
+            inner_id,
             key_range,
             req.start_revision,
             req.filters,
@@ -231,6 +268,12 @@ where
                 "WatchId {watch_id} already exists in prev_kv",
             );
         }
+        if req.fragment {
+            assert!(
+                self.fragment.insert(watch_id),
+                "WatchId {watch_id} already exists in fragment",
+            );
+        }
         if req.progress_notify {
             assert!(
                 self.progress.insert(watch_id, true).is_none(),
@@ -238,9 +281,13 @@ where
             );
         }
         assert!(
-            self.active_watch_ids.insert(watch_id),
+            self.active_watch_ids.insert(watch_id, inner_id).is_none(),
             "WatchId {watch_id} already exists in active_watch_ids",
         );
+        assert!(
+            self.client_watch_ids.insert(inner_id, watch_id).is_none(),
+            "WatchId {inner_id} already exists in client_watch_ids",
+        );
 
         let response = WatchResponse {
             header: Some(self.header_gen.gen_header()),
@@ -256,27 +303,34 @@ where
     /// Handle `WatchCancelRequest`
     async fn handle_watch_cancel(&mut self, req: WatchCancelRequest) {
         let watch_id = req.watch_id;
-        let result = if self.active_watch_ids.remove(&watch_id) {
-            self.kv_watcher.cancel(watch_id);
-            let _prev = self.active_watch_ids.remove(&watch_id);
-            let response = WatchResponse {
-                header: Some(self.header_gen.gen_header()),
-                watch_id,
-                canceled: true,
-                ..WatchResponse::default()
-            };
-            Ok(response)
-        } else {
-            Err(tonic::Status::not_found(format!(
-                "Watch ID {} doesn't exist",
-                req.watch_id
-            )))
+        let Some(inner_id) = self.active_watch_ids.get(&watch_id).copied() else {
+            // Same as etcd, canceling an unknown watcher is a no-op
+            debug!("Watch ID {watch_id} doesn't exist, ignore the cancel request");
+            return;
+        };
+        self.kv_watcher.cancel(inner_id);
+        self.remove_watch_state(watch_id);
+        let response = WatchResponse {
+            header: Some(self.header_gen.gen_header()),
+            watch_id,
+            canceled: true,
+            ..WatchResponse::default()
         };
-        if self.response_tx.send(result).await.is_err() {
+        if self.response_tx.send(Ok(response)).await.is_err() {
             let _ignore = self.stop_notify.notify(1);
         }
     }
 
+    /// Remove the per-watch state kept by this handle, so that the `watch_id` can be reused
+    fn remove_watch_state(&mut self, watch_id: WatchId) {
+        if let Some(inner_id) = self.active_watch_ids.remove(&watch_id) {
+            let _client_id = self.client_watch_ids.remove(&inner_id);
+        }
+        let _prev_kv = self.prev_kv.remove(&watch_id);
+        let _progress = self.progress.remove(&watch_id);
+        let _fragment = self.fragment.remove(&watch_id);
+    }
+
     /// Handle `WatchRequest`
     async fn handle_watch_request(&mut self, req: WatchRequest) {
         if let Some(req) = req.request_union {
@@ -296,7 +350,10 @@ where
 
     /// Handle watch event
     async fn handle_watch_event(&mut self, mut watch_event: WatchEvent) {
-        let watch_id = watch_event.watch_id();
+        let Some(watch_id) = self.client_watch_ids.get(&watch_event.watch_id()).copied() else {
+            // the watcher has been canceled
+            return;
+        };
         let mut response = WatchResponse {
             header: Some(ResponseHeader {
                 revision: watch_event.revision(),
@@ -308,6 +365,12 @@ where
         if watch_event.compacted() {
             response.compact_revision = self.kv_watcher.compacted_revision();
             response.canceled = true;
+            // a canceled watcher should not receive progress notifications any more
+            self.remove_watch_state(watch_id);
+        } else if let Some(reason) = watch_event.canceled() {
+            response.canceled = true;
+            response.cancel_reason = reason.to_string();
+            self.remove_watch_state(watch_id);
         } else {
             let mut events = watch_event.take_events();
             if events.is_empty() {
@@ -328,8 +391,17 @@ where
             response.events = events;
         };
 
-        if self.response_tx.send(Ok(response)).await.is_err() {
-            let _ignore = self.stop_notify.notify(1);
+        let responses =
+            if self.fragment.contains(&watch_id) && response.encoded_len() > MAX_FRAGMENT_SIZE {
+                fragment_response(response, MAX_FRAGMENT_SIZE)
+            } else {
+                vec![response]
+            };
+        for response in responses {
+            if self.response_tx.send(Ok(response)).await.is_err() {
+                let _ignore = self.stop_notify.notify(1);
+                break;
+            }
         }
         if let Some(progress) = self.progress.get_mut(&watch_id) {
             *progress = false;
@@ -375,13 +447,44 @@ where
     }
 }
 
+/// Split a `WatchResponse` into fragments whose events are no larger than `max_size`,
+/// all fragments except the last one are marked with `fragment = true`
+fn fragment_response(response: WatchResponse, max_size: usize) -> Vec<WatchResponse> {
+    let mut fragments: Vec<Vec<PbEvent>> = vec![];
+    let mut cur = vec![];
+    let mut cur_size = 0;
+    for event in response.events {
+        let size = event.encoded_len();
+        // every fragment contains at least one event
+        if !cur.is_empty() && cur_size.overflow_add(size) > max_size {
+            fragments.push(std::mem::take(&mut cur));
+            cur_size = 0;
+        }
+        cur_size = cur_size.overflow_add(size);
+        cur.push(event);
+    }
+    fragments.push(cur);
+    let last = fragments.len().overflow_sub(1);
+    fragments
+        .into_iter()
+        .enumerate()
+        .map(|(i, events)| WatchResponse {
+            header: response.header.clone(),
+            watch_id: response.watch_id,
+            events,
+            fragment: i != last,
+            ..WatchResponse::default()
+        })
+        .collect()
+}
+
 impl<W> Drop for WatchHandle<W>
 where
     W: KvWatcherOps,
 {
     fn drop(&mut self) {
-        for watch_id in &self.active_watch_ids {
-            self.kv_watcher.cancel(*watch_id);
+        for inner_id in self.active_watch_ids.values() {
+            self.kv_watcher.cancel(*inner_id);
         }
     }
 }
@@ -392,7 +495,7 @@ where
     S: StorageApi,
 {
     ///Server streaming response type for the Watch method.
-    type WatchStream = ReceiverStream<Result<WatchResponse, tonic::Status>>;
+    type WatchStream = Pin<Box<dyn Stream<Item = Result<WatchResponse, tonic::Status>> + Send>>;
 
     /// Watch watches for events happening or that have happened. Both input and output
     /// are streams; the input stream is for creating and canceling watchers and the output
@@ -404,10 +507,11 @@ where
         request: tonic::Request<tonic::Streaming<WatchRequest>>,
     ) -> Result<tonic::Response<Self::WatchStream>, tonic::Status> {
         debug!("Receive Watch Connection {:?}", request);
+        let stream_permit = self.stream_limiter.acquire(&request)?;
         let req_stream = request.into_inner();
         let (tx, rx) = mpsc::channel(CHANNEL_SIZE);
         self.task_manager.spawn(TaskName::WatchTask, |n| {
-            Self::task(
+            let task = Self::task(
                 Arc::clone(&self.next_id_gen),
                 Arc::clone(&self.watcher),
                 tx,
@@ -415,9 +519,16 @@ where
                 Arc::clone(&self.header_gen),
                 self.watch_progress_notify_interval,
                 n,
-            )
+            );
+            async move {
+                task.await;
+                // the stream of the connection is closed with the task
+                drop(stream_permit);
+            }
         });
-        Ok(tonic::Response::new(ReceiverStream::new(rx)))
+        Ok(tonic::Response::new(
+            self.stream_shutdown.guard(ReceiverStream::new(rx)),
+        ))
     }
 }
 
@@ -435,15 +546,23 @@ mod test {
         sync::mpsc,
         time::{sleep, timeout},
     };
-    use utils::config::{default_watch_progress_notify_interval, EngineConfig};
+    use utils::config::{
+        default_watch_progress_notify_interval, default_watcher_buffer_size, EngineConfig,
+        KvUpdateBackpressure, SlowWatcherPolicy,
+    };
     use xlineapi::RequestWrapper;
 
     use super::*;
     use crate::{
-        rpc::{PutRequest, WatchProgressRequest},
+        rpc::{KeyValue, PutRequest, WatchProgressRequest},
         storage::{
-            compact::COMPACT_CHANNEL_SIZE, db::DB, index::Index, kv_store::KvStoreInner,
-            kvwatcher::MockKvWatcherOps, lease_store::LeaseCollection, KvStore,
+            compact::COMPACT_CHANNEL_SIZE,
+            db::DB,
+            index::Index,
+            kv_store::KvStoreInner,
+            kvwatcher::{kv_update_channel, MockKvWatcherOps},
+            lease_store::LeaseCollection,
+            KvStore,
         },
     };
 
@@ -467,9 +586,10 @@ mod test {
             value: value.into(),
             ..Default::default()
         });
-        let (_sync_res, ops) = store.after_sync(&req, revision).await.unwrap();
-        let key_revisions = db.flush_ops(ops).unwrap();
+        let (_sync_res, ops, events, _deletions) = store.after_sync(&req, revision).await.unwrap();
+        let key_revisions = db.write_batch(ops, false).unwrap();
         store.insert_index(key_revisions);
+        store.notify_updates(revision, events, revision).await;
     }
 
     #[tokio::test]
@@ -599,7 +719,8 @@ mod test {
         let header_gen = Arc::new(HeaderGenerator::new(0, 0));
         let lease_collection = Arc::new(LeaseCollection::new(0));
         let next_id_gen = Arc::new(WatchIdGenerator::new(1));
-        let (kv_update_tx, kv_update_rx) = mpsc::channel(CHANNEL_SIZE);
+        let (kv_update_tx, kv_update_rx) =
+            kv_update_channel(CHANNEL_SIZE, KvUpdateBackpressure::default());
         let kv_store_inner = Arc::new(KvStoreInner::new(index, Arc::clone(&db)));
         let kv_store = Arc::new(KvStore::new(
             Arc::clone(&kv_store_inner),
@@ -607,11 +728,14 @@ mod test {
             kv_update_tx,
             compact_tx,
             lease_collection,
+            Arc::default(),
         ));
         let kv_watcher = KvWatcher::new_arc(
             kv_store_inner,
             kv_update_rx,
             Duration::from_millis(10),
+            default_watcher_buffer_size(),
+            SlowWatcherPolicy::default(),
             &task_manager,
         );
         put(&kv_store, &db, "foo", "old_bar", 2).await;
@@ -661,6 +785,94 @@ mod test {
         task_manager.shutdown(true).await;
     }
 
+    #[tokio::test]
+    #[abort_on_panic]
+    async fn client_assigned_watch_ids_should_be_scoped_to_stream() {
+        let task_manager = Arc::new(TaskManager::new());
+        let (compact_tx, _compact_rx) = mpsc::channel(COMPACT_CHANNEL_SIZE);
+        let index = Arc::new(Index::new());
+        let db = DB::open(&EngineConfig::Memory).unwrap();
+        let header_gen = Arc::new(HeaderGenerator::new(0, 0));
+        let lease_collection = Arc::new(LeaseCollection::new(0));
+        let next_id_gen = Arc::new(WatchIdGenerator::new(1));
+        let (kv_update_tx, kv_update_rx) =
+            kv_update_channel(CHANNEL_SIZE, KvUpdateBackpressure::default());
+        let kv_store_inner = Arc::new(KvStoreInner::new(index, Arc::clone(&db)));
+        let kv_store = Arc::new(KvStore::new(
+            Arc::clone(&kv_store_inner),
+            Arc::clone(&header_gen),
+            kv_update_tx,
+            compact_tx,
+            lease_collection,
+            Arc::default(),
+        ));
+        let kv_watcher = KvWatcher::new_arc(
+            kv_store_inner,
+            kv_update_rx,
+            Duration::from_millis(10),
+            default_watcher_buffer_size(),
+            SlowWatcherPolicy::default(),
+            &task_manager,
+        );
+        let create_watch_req = WatchRequest {
+            request_union: Some(RequestUnion::CreateRequest(WatchCreateRequest {
+                watch_id: 5,
+                key: "foo".into(),
+                ..Default::default()
+            })),
+        };
+
+        let mut streams = vec![];
+        for _ in 0..2 {
+            let (req_tx, req_rx) = mpsc::channel(CHANNEL_SIZE);
+            let (res_tx, mut res_rx) = mpsc::channel(CHANNEL_SIZE);
+            task_manager.spawn(TaskName::WatchTask, |n| {
+                WatchServer::<DB>::task(
+                    Arc::clone(&next_id_gen),
+                    Arc::clone(&kv_watcher),
+                    res_tx,
+                    ReceiverStream::new(req_rx),
+                    Arc::clone(&header_gen),
+                    default_watch_progress_notify_interval(),
+                    n,
+                )
+            });
+            req_tx.send(Ok(create_watch_req.clone())).await.unwrap();
+            let res = res_rx.recv().await.unwrap().unwrap();
+            assert!(res.created && !res.canceled);
+            assert_eq!(res.watch_id, 5);
+            streams.push((req_tx, res_rx));
+        }
+
+        // duplicated ids are rejected in band and canceling an unknown id is ignored
+        let (req_tx, res_rx) = &mut streams[0];
+        req_tx.send(Ok(create_watch_req)).await.unwrap();
+        let res = res_rx.recv().await.unwrap().unwrap();
+        assert!(res.created && res.canceled);
+        assert_eq!(res.watch_id, 5);
+        req_tx
+            .send(Ok(WatchRequest {
+                request_union: Some(RequestUnion::CancelRequest(WatchCancelRequest {
+                    watch_id: 9,
+                })),
+            }))
+            .await
+            .unwrap();
+
+        put(&kv_store, &db, "foo", "bar", 2).await;
+        for (_req_tx, res_rx) in &mut streams {
+            let res = timeout(Duration::from_secs(1), res_rx.recv())
+                .await
+                .unwrap()
+                .unwrap()
+                .unwrap();
+            assert_eq!(res.watch_id, 5);
+            assert_eq!(res.events.len(), 1);
+        }
+        drop(kv_store);
+        task_manager.shutdown(true).await;
+    }
+
     #[tokio::test]
     #[abort_on_panic]
     async fn test_watch_progress() -> Result<(), Box<dyn std::error::Error>> {
@@ -724,6 +936,106 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    #[abort_on_panic]
+    async fn watch_id_with_progress_notify_can_be_reused_after_cancel(
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let task_manager = Arc::new(TaskManager::new());
+        let (req_tx, req_rx) = mpsc::channel(CHANNEL_SIZE);
+        let (res_tx, mut res_rx) = mpsc::channel(CHANNEL_SIZE);
+        let req_stream: ReceiverStream<Result<WatchRequest, tonic::Status>> =
+            ReceiverStream::new(req_rx);
+        let header_gen = Arc::new(HeaderGenerator::new(0, 0));
+        let mut mock_watcher = MockKvWatcherOps::new();
+        let _ = mock_watcher.expect_watch().times(2).return_const(());
+        let _ = mock_watcher.expect_cancel().times(2).return_const(());
+        let watcher = Arc::new(mock_watcher);
+        let next_id = Arc::new(WatchIdGenerator::new(1));
+        task_manager.spawn(TaskName::WatchTask, |n| {
+            WatchServer::<DB>::task(
+                next_id,
+                Arc::clone(&watcher),
+                res_tx,
+                req_stream,
+                header_gen,
+                Duration::from_secs(10),
+                n,
+            )
+        });
+        let create_req = WatchRequest {
+            request_union: Some(RequestUnion::CreateRequest(WatchCreateRequest {
+                key: "foo".into(),
+                progress_notify: true,
+                prev_kv: true,
+                watch_id: 1,
+                ..Default::default()
+            })),
+        };
+        req_tx.send(Ok(create_req.clone())).await?;
+        req_tx
+            .send(Ok(WatchRequest {
+                request_union: Some(RequestUnion::CancelRequest(WatchCancelRequest {
+                    watch_id: 1,
+                })),
+            }))
+            .await?;
+        req_tx.send(Ok(create_req)).await?;
+
+        let mut responses = vec![];
+        while responses.len() < 3 {
+            let res = timeout(Duration::from_secs(1), res_rx.recv())
+                .await?
+                .unwrap()?;
+            if !is_progress_notify(&res) {
+                responses.push(res);
+            }
+        }
+        assert!(responses[0].created);
+        assert!(responses[1].canceled);
+        assert!(responses[2].created);
+        assert_eq!(responses[2].watch_id, 1);
+        drop(req_tx);
+        task_manager.shutdown(true).await;
+        Ok(())
+    }
+
+    #[test]
+    fn large_watch_response_should_be_fragmented() {
+        let events: Vec<PbEvent> = (0..10_u8)
+            .map(|i| PbEvent {
+                kv: Some(KeyValue {
+                    key: vec![i],
+                    value: vec![i; 100],
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })
+            .collect();
+        let event_size = events[0].encoded_len();
+        let response = WatchResponse {
+            header: Some(ResponseHeader {
+                revision: 5,
+                ..ResponseHeader::default()
+            }),
+            watch_id: 1,
+            events: events.clone(),
+            ..WatchResponse::default()
+        };
+
+        let fragments = fragment_response(response.clone(), event_size * 3);
+        assert_eq!(fragments.len(), 4);
+        for (i, fragment) in fragments.iter().enumerate() {
+            assert_eq!(fragment.watch_id, 1);
+            assert_eq!(fragment.header, response.header);
+            assert_eq!(fragment.fragment, i != 3);
+        }
+        let merged: Vec<PbEvent> = fragments.into_iter().flat_map(|f| f.events).collect();
+        assert_eq!(merged, events);
+
+        let fragments = fragment_response(response, 1);
+        assert_eq!(fragments.len(), 10, "each fragment has at least one event");
+    }
+
     #[tokio::test]
     async fn watch_task_should_terminate_when_response_tx_closed(
     ) -> Result<(), Box<dyn std::error::Error>> {
@@ -785,7 +1097,8 @@ mod test {
         let header_gen = Arc::new(HeaderGenerator::new(0, 0));
         let lease_collection = Arc::new(LeaseCollection::new(0));
         let next_id_gen = Arc::new(WatchIdGenerator::new(1));
-        let (kv_update_tx, kv_update_rx) = mpsc::channel(CHANNEL_SIZE);
+        let (kv_update_tx, kv_update_rx) =
+            kv_update_channel(CHANNEL_SIZE, KvUpdateBackpressure::default());
         let kv_store_inner = Arc::new(KvStoreInner::new(index, Arc::clone(&db)));
         let kv_store = Arc::new(KvStore::new(
             Arc::clone(&kv_store_inner),
@@ -793,11 +1106,14 @@ mod test {
             kv_update_tx,
             compact_tx,
             lease_collection,
+            Arc::default(),
         ));
         let kv_watcher = KvWatcher::new_arc(
             kv_store_inner,
             kv_update_rx,
             Duration::from_millis(10),
+            default_watcher_buffer_size(),
+            SlowWatcherPolicy::default(),
             &task_manager,
         );
         put(&kv_store, &db, "foo", "old_bar", 2).await;
@@ -1,4 +1,7 @@
-use std::{sync::Arc, time::Duration};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use anyhow::{anyhow, Result};
 use clippy_utilities::{NumericCast, OverflowArithmetic};
@@ -9,23 +12,32 @@ use curp::{
     server::{Rpc, StorageApi as _, DB as CurpDB},
 };
 use dashmap::DashMap;
-use engine::{MemorySnapshotAllocator, RocksSnapshotAllocator, SnapshotAllocator};
+use engine::EngineType;
 #[cfg(not(madsim))]
-use futures::Stream;
+use futures::{Stream, TryStreamExt};
 use jsonwebtoken::{DecodingKey, EncodingKey};
+use parking_lot::Mutex;
+use tokio::{
+    fs,
+    sync::{mpsc::channel, watch},
+};
 #[cfg(not(madsim))]
-use tokio::io::{AsyncRead, AsyncWrite};
-use tokio::{fs, sync::mpsc::channel};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    sync::Semaphore,
+};
 #[cfg(not(madsim))]
 use tonic::transport::{
     server::Connected, Certificate, ClientTlsConfig, Identity, ServerTlsConfig,
 };
 use tonic::transport::{server::Router, Server};
-use tracing::{info, warn};
+use tracing::{error, info, warn};
+#[cfg(not(madsim))]
+use utils::{config::GrpcConfig, tracing::grpc_request_span};
 use utils::{
     config::{
-        AuthConfig, ClusterConfig, CompactConfig, EngineConfig, InitialClusterState, StorageConfig,
-        TlsConfig,
+        AuditConfig, AuthConfig, ClusterConfig, CompactConfig, DurabilityPolicy,
+        InitialClusterState, StorageConfig, TlsConfig,
     },
     task_manager::{tasks::TaskName, TaskManager},
 };
@@ -34,43 +46,97 @@ use utils::{ClientTlsConfig, ServerTlsConfig};
 use xlineapi::command::{Command, CurpClient};
 
 use super::{
+    admin_server::AdminServer,
+    audit::AuditLog,
     auth_server::AuthServer,
     auth_wrapper::AuthWrapper,
+    backup::{backup_task, write_snapshot},
     barriers::{IdBarrier, IndexBarrier},
     cluster_server::ClusterServer,
-    command::{Alarmer, CommandExecutor},
+    command::{persist_task, Alarmer, CommandExecutor, PERSIST_CHANNEL_SIZE},
+    election_server::ElectionServer,
+    feature_gate::{persist_negotiated_version_task, recover_negotiated_version, FeatureGate},
     kv_server::KvServer,
     lease_server::LeaseServer,
     lock_server::LockServer,
     maintenance::MaintenanceServer,
-    watch_server::{WatchServer, CHANNEL_SIZE},
+    overload::OverloadMonitor,
+    qos::QosClassifier,
+    request_log::RequestLogger,
+    stream_shutdown::StreamShutdown,
+    watch_server::WatchServer,
+};
+#[cfg(not(madsim))]
+use super::{
+    admission::{RateLimitInterceptor, RateLimiter},
+    auth_interceptor::AuthInterceptor,
+    client_connection::ClientConnection,
+    gateway, grpc_web,
 };
 use crate::{
     conflict::{XlineSpeculativePools, XlineUncommittedPools},
-    header_gen::HeaderGenerator,
+    header_gen::{sync_term_task, HeaderGenerator},
     id_gen::IdGenerator,
     metrics::Metrics,
     rpc::{
-        AuthServer as RpcAuthServer, ClusterServer as RpcClusterServer, KvServer as RpcKvServer,
-        LeaseServer as RpcLeaseServer, LockServer as RpcLockServer,
+        AdminServer as RpcAdminServer, AuthServer as RpcAuthServer,
+        ClusterServer as RpcClusterServer, ElectionServer as RpcElectionServer,
+        KvServer as RpcKvServer, LeaseServer as RpcLeaseServer, LockServer as RpcLockServer,
         MaintenanceServer as RpcMaintenanceServer, WatchServer as RpcWatchServer,
     },
     state::State,
     storage::{
-        compact::{auto_compactor, compact_bg_task, COMPACT_CHANNEL_SIZE},
-        db::DB,
+        compact::{auto_compactor, compact_bg_task, Compactor, COMPACT_CHANNEL_SIZE},
+        data_dir_lock::DataDirLock,
+        db::{sync_bg_task, DB},
         index::Index,
+        index_snapshot::index_snapshot_task,
         kv_store::KvStoreInner,
-        kvwatcher::KvWatcher,
+        kvwatcher::{kv_update_channel, KvWatcher},
         lease_store::LeaseCollection,
+        range_tombstone::range_tombstone_bg_task,
+        schema::{check_schema, pre_upgrade_snapshot_path, upgrade_schema},
         storage_api::StorageApi,
+        tenants::Tenants,
         AlarmStore, AuthStore, KvStore, LeaseStore,
     },
+    utils::{Discovery, DnsSrvDiscovery, LogLevelHandle, StaticDiscovery},
 };
 
 /// Rpc Server of curp protocol
 pub(crate) type CurpServer<S> = Rpc<Command, State<S, Arc<CurpClient>>>;
 
+/// A component that is only available after the server started
+struct StartedSlot<T: ?Sized>(Mutex<Option<Arc<T>>>);
+
+impl<T: ?Sized> Default for StartedSlot<T> {
+    #[inline]
+    fn default() -> Self {
+        Self(Mutex::new(None))
+    }
+}
+
+impl<T: ?Sized> StartedSlot<T> {
+    /// Set the component
+    fn set(&self, component: Arc<T>) {
+        *self.0.lock() = Some(component);
+    }
+
+    /// Get the component if the server has started
+    fn get(&self) -> Option<Arc<T>> {
+        self.0.lock().clone()
+    }
+}
+
+impl<T: ?Sized> std::fmt::Debug for StartedSlot<T> {
+    #[inline]
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("StartedSlot")
+            .field(&self.0.lock().is_some())
+            .finish()
+    }
+}
+
 /// Xline server
 #[derive(Debug)]
 pub struct XlineServer {
@@ -84,15 +150,38 @@ pub struct XlineServer {
     compact_config: CompactConfig,
     /// Auth config
     auth_config: AuthConfig,
-    /// Client tls config
+    /// Client tls config, used when connecting to the client-facing endpoint of other members
     client_tls_config: Option<ClientTlsConfig>,
-    /// Server tls config
+    /// Client tls config, used when connecting to the peer endpoint of other members
+    peer_client_tls_config: Option<ClientTlsConfig>,
+    /// Server tls config of the client-facing endpoint
     #[cfg_attr(madsim, allow(unused))]
     server_tls_config: Option<ServerTlsConfig>,
+    /// Server tls config of the peer endpoint
+    #[cfg_attr(madsim, allow(unused))]
+    peer_tls_config: Option<ServerTlsConfig>,
     /// Task Manager
     task_manager: Arc<TaskManager>,
     /// Curp storage
     curp_storage: Arc<CurpDB<Command>>,
+    /// Notifies the client-facing server to stop accepting requests
+    client_shutdown: watch::Sender<bool>,
+    /// Set once the client-facing server has stopped
+    client_stopped: Arc<watch::Sender<bool>>,
+    /// The curp client, available after the server started
+    curp_client: StartedSlot<CurpClient>,
+    /// The auto compactor, available after the server started if auto compaction is enabled
+    auto_compactor: StartedSlot<dyn Compactor<Arc<CurpClient>>>,
+    /// Picks the member to move the leadership to on graceful shutdown, available after
+    /// the server started
+    transfer_target: StartedSlot<dyn Fn() -> Option<u64> + Send + Sync>,
+    /// Handle to change the log level through the admin service
+    log_level: Option<LogLevelHandle>,
+    /// Audit log, disabled unless it is opened by `audit_log`
+    audit_log: Arc<AuditLog>,
+    /// Locks on the data dirs of the storage and the curp storage, held as long as the
+    /// server is alive
+    _data_dir_locks: Vec<DataDirLock>,
 }
 
 impl XlineServer {
@@ -108,15 +197,31 @@ impl XlineServer {
         #[cfg_attr(madsim, allow(unused_variables))] tls_config: TlsConfig,
     ) -> Result<Self> {
         #[cfg(not(madsim))]
-        let (client_tls_config, server_tls_config) = Self::read_tls_config(&tls_config).await?;
+        let (client_tls_config, server_tls_config, peer_tls_config) =
+            Self::read_tls_config(&tls_config).await?;
+        #[cfg(not(madsim))]
+        let peer_client_tls_config = Self::read_peer_client_tls_config(&tls_config)
+            .await?
+            .or_else(|| client_tls_config.clone());
+        #[cfg(madsim)]
+        let (client_tls_config, server_tls_config, peer_tls_config) = (None, None, None);
         #[cfg(madsim)]
-        let (client_tls_config, server_tls_config) = (None, None);
+        let peer_client_tls_config = None;
+        // fail fast before any storage is opened if the data dirs are used by another
+        // process, opening a data dir twice may corrupt it
+        let curp_engine = &cluster_config.curp_config().engine_cfg;
+        let mut data_dir_locks: Vec<_> = DataDirLock::acquire(&storage_config.engine)?
+            .into_iter()
+            .collect();
+        if *curp_engine != storage_config.engine {
+            data_dir_locks.extend(DataDirLock::acquire(curp_engine)?);
+        }
         let curp_storage = Arc::new(CurpDB::open(&cluster_config.curp_config().engine_cfg)?);
         let cluster_info = Arc::new(
             Self::init_cluster_info(
                 &cluster_config,
                 curp_storage.as_ref(),
-                client_tls_config.as_ref(),
+                peer_client_tls_config.as_ref(),
             )
             .await?,
         );
@@ -127,12 +232,39 @@ impl XlineServer {
             compact_config,
             auth_config,
             client_tls_config,
+            peer_client_tls_config,
             server_tls_config,
+            peer_tls_config,
             task_manager: Arc::new(TaskManager::new()),
             curp_storage,
+            client_shutdown: watch::Sender::new(false),
+            client_stopped: Arc::new(watch::Sender::new(false)),
+            curp_client: StartedSlot::default(),
+            auto_compactor: StartedSlot::default(),
+            transfer_target: StartedSlot::default(),
+            log_level: None,
+            audit_log: Arc::default(),
+            _data_dir_locks: data_dir_locks,
         })
     }
 
+    /// Allow the admin service to change the log level through the given handle
+    #[inline]
+    #[must_use]
+    pub fn log_level_handle(mut self, handle: LogLevelHandle) -> Self {
+        self.log_level = Some(handle);
+        self
+    }
+
+    /// Record the changes applied by this server to the audit log if it is enabled
+    /// # Errors
+    /// Return error if the audit log can not be opened
+    #[inline]
+    pub fn audit_log(mut self, audit_config: &AuditConfig) -> Result<Self> {
+        self.audit_log = Arc::new(AuditLog::open(audit_config)?);
+        Ok(self)
+    }
+
     /// Init cluster info from cluster config
     async fn init_cluster_info(
         cluster_config: &ClusterConfig,
@@ -143,25 +275,36 @@ impl XlineServer {
         info!("cluster_peers = {:?}", cluster_config.peers());
 
         let name = cluster_config.name().clone();
-        let all_members = cluster_config.peers().clone();
+        // the persisted cluster info takes precedence, so the peers are only discovered
+        // when the member is bootstrapped
+        if let Some(cluster_info) = curp_storage.recover_cluster_info()? {
+            info!("get cluster_info from local");
+            return Ok(cluster_info);
+        }
+        let all_members = match cluster_config.discovery_srv().as_ref() {
+            Some(domain) if cluster_config.peers().is_empty() => {
+                info!("discover cluster peers from DNS SRV records of {domain}");
+                DnsSrvDiscovery::new(domain.as_str(), tls_config.is_some())
+                    .discover()
+                    .await?
+            }
+            _ => {
+                StaticDiscovery::new(cluster_config.peers().clone())
+                    .discover()
+                    .await?
+            }
+        };
         let self_client_urls = cluster_config.client_advertise_urls().clone();
         let self_peer_urls = cluster_config.peer_advertise_urls().clone();
-        match (
-            curp_storage.recover_cluster_info()?,
-            *cluster_config.initial_cluster_state(),
-        ) {
-            (Some(cluster_info), _) => {
-                info!("get cluster_info from local");
-                Ok(cluster_info)
-            }
-            (None, InitialClusterState::New) => {
+        match *cluster_config.initial_cluster_state() {
+            InitialClusterState::New => {
                 info!("get cluster_info by args");
                 let cluster_info =
                     ClusterInfo::from_members_map(all_members, self_client_urls, &name);
                 curp_storage.put_cluster_info(&cluster_info)?;
                 Ok(cluster_info)
             }
-            (None, InitialClusterState::Existing) => {
+            InitialClusterState::Existing => {
                 info!("get cluster_info from remote");
                 let cluster_info = get_cluster_info_from_remote(
                     &ClusterInfo::from_members_map(all_members, self_client_urls, &name),
@@ -175,7 +318,7 @@ impl XlineServer {
                 curp_storage.put_cluster_info(&cluster_info)?;
                 Ok(cluster_info)
             }
-            (None, _) => {
+            _ => {
                 unreachable!("xline only supports two initial cluster states: new, existing")
             }
         }
@@ -214,17 +357,28 @@ impl XlineServer {
     )> {
         let (compact_task_tx, compact_task_rx) = channel(COMPACT_CHANNEL_SIZE);
         let index = Arc::new(Index::new());
-        let (kv_update_tx, kv_update_rx) = channel(CHANNEL_SIZE);
+        let (kv_update_tx, kv_update_rx) = kv_update_channel(
+            *self
+                .cluster_config
+                .server_timeout()
+                .kv_update_channel_size(),
+            *self
+                .cluster_config
+                .server_timeout()
+                .kv_update_backpressure(),
+        );
         let kv_store_inner = Arc::new(KvStoreInner::new(
             Arc::clone(&index),
             Arc::clone(&persistent),
         ));
+        let tenants = Arc::new(Tenants::new(self.storage_config.tenant_quotas.clone()));
         let kv_storage = Arc::new(KvStore::new(
             Arc::clone(&kv_store_inner),
             Arc::clone(&header_gen),
-            kv_update_tx.clone(),
+            kv_update_tx,
             compact_task_tx,
             Arc::clone(&lease_collection),
+            Arc::clone(&tenants),
         ));
         self.task_manager.spawn(TaskName::CompactBg, |n| {
             compact_bg_task(
@@ -236,19 +390,36 @@ impl XlineServer {
                 n,
             )
         });
+        self.task_manager.spawn(TaskName::RangeTombstone, |n| {
+            range_tombstone_bg_task(
+                Arc::clone(&kv_storage),
+                *self.compact_config.compact_batch_size(),
+                *self.compact_config.compact_sleep_interval(),
+                n,
+            )
+        });
+        if let DurabilityPolicy::Periodic(interval) = self.storage_config.durability {
+            self.task_manager.spawn(TaskName::SyncStorage, |n| {
+                sync_bg_task(Arc::clone(&persistent), interval, n)
+            });
+        }
         let lease_storage = Arc::new(LeaseStore::new(
             Arc::clone(&lease_collection),
             Arc::clone(&header_gen),
             Arc::clone(&persistent),
             index,
-            kv_update_tx,
+            tenants,
             *self.cluster_config.is_leader(),
         ));
+        let previous_keys = Self::read_previous_public_keys(&self.auth_config).await?;
         let auth_storage = Arc::new(AuthStore::new(
             lease_collection,
             key_pair,
+            previous_keys,
+            *self.auth_config.auth_token_ttl(),
             Arc::clone(&header_gen),
             Arc::clone(&persistent),
+            *self.auth_config.client_cert_auth(),
         ));
         let alarm_storage = Arc::new(AlarmStore::new(header_gen, persistent));
 
@@ -256,13 +427,32 @@ impl XlineServer {
             kv_store_inner,
             kv_update_rx,
             *self.cluster_config.server_timeout().sync_victims_interval(),
+            *self.cluster_config.server_timeout().watcher_buffer_size(),
+            *self.cluster_config.server_timeout().slow_watcher_policy(),
             &self.task_manager,
         );
+        let started = Instant::now();
+        // auth and alarm storages are independent of the others, so they are recovered
+        // in parallel with the lease and kv storages
+        let recover_auth_and_alarm = {
+            let auth_storage = Arc::clone(&auth_storage);
+            let alarm_storage = Arc::clone(&alarm_storage);
+            tokio::task::spawn_blocking(move || {
+                auth_storage.recover()?;
+                alarm_storage.recover()
+            })
+        };
         // lease storage must recover before kv storage
         lease_storage.recover()?;
+        info!("lease storage is recovered in {:?}", started.elapsed());
         kv_storage.recover().await?;
-        auth_storage.recover()?;
-        alarm_storage.recover()?;
+        info!(
+            "kv storage is recovered at revision {} in {:?}",
+            kv_storage.revision(),
+            started.elapsed()
+        );
+        recover_auth_and_alarm.await??;
+        info!("storages are recovered in {:?}", started.elapsed());
         Ok((
             kv_storage,
             lease_storage,
@@ -272,6 +462,24 @@ impl XlineServer {
         ))
     }
 
+    /// Upgrade the schema of the storage if it is written by an older version of xline,
+    /// a snapshot of the storage is taken before the upgrade
+    async fn upgrade_storage_schema<S: StorageApi>(
+        &self,
+        header_gen: &HeaderGenerator,
+        persistent: &S,
+    ) -> Result<()> {
+        let Some(from) = check_schema(persistent)? else {
+            return Ok(());
+        };
+        if let Some(path) = pre_upgrade_snapshot_path(&self.storage_config.engine, from) {
+            write_snapshot(header_gen, persistent, &path).await?;
+            info!("snapshot before upgrading the storage schema is saved to {path:?}");
+        }
+        upgrade_schema(persistent, from)?;
+        Ok(())
+    }
+
     /// Construct a header generator
     #[inline]
     fn construct_generator(cluster_info: &ClusterInfo) -> (Arc<HeaderGenerator>, Arc<IdGenerator>) {
@@ -297,11 +505,13 @@ impl XlineServer {
         let (
             kv_server,
             lock_server,
+            election_server,
             lease_server,
             auth_server,
             watch_server,
             maintenance_server,
             cluster_server,
+            admin_server,
             curp_server,
             auth_wrapper,
             curp_client,
@@ -311,26 +521,118 @@ impl XlineServer {
         if let Some(ref cfg) = self.server_tls_config {
             builder = builder.tls_config(cfg.clone())?;
         }
+        #[cfg(not(madsim))]
+        let cors = self
+            .cluster_config
+            .grpc_web()
+            .as_ref()
+            .map(grpc_web::cors_layer)
+            .transpose()?;
+        #[cfg(not(madsim))]
+        let builder = with_grpc_limits(builder, self.cluster_config.grpc())
+            .accept_http1(cors.is_some())
+            .trace_fn(grpc_request_span);
+        // Compressed requests are always accepted, the responses are compressed as configured
+        #[cfg(not(madsim))]
+        let compression = self.cluster_config.grpc_compression().encoding();
+        #[cfg(not(madsim))]
+        macro_rules! compressed {
+            ($service:expr) => {{
+                let service = utils::config::GrpcCompression::ACCEPTED
+                    .into_iter()
+                    .fold($service, |service, encoding| {
+                        service.accept_compressed(encoding)
+                    });
+                match compression {
+                    Some(encoding) => service.send_compressed(encoding),
+                    None => service,
+                }
+            }};
+        }
+        #[cfg(madsim)]
+        macro_rules! compressed {
+            ($service:expr) => {
+                $service
+            };
+        }
+        // Client services authenticate every request once before it reaches the servers
+        #[cfg(not(madsim))]
+        let auth_interceptor = AuthInterceptor::new(auth_wrapper.auth_store());
+        #[cfg(not(madsim))]
+        macro_rules! authenticated {
+            ($service:expr) => {
+                auth_interceptor.intercept(compressed!($service))
+            };
+        }
+        #[cfg(madsim)]
+        macro_rules! authenticated {
+            ($service:expr) => {
+                $service
+            };
+        }
+        // Client services are rate limited per client after the authentication, and
+        // browsers may call them through grpc-web
+        #[cfg(not(madsim))]
+        let rate_limit_interceptor = RateLimitInterceptor::new(RateLimiter::new(
+            *self.cluster_config.grpc().client_rate_limit(),
+            *self.cluster_config.grpc().client_rate_burst(),
+        ));
+        #[cfg(not(madsim))]
+        macro_rules! client_service {
+            ($service:expr) => {
+                grpc_web::enable(
+                    cors.as_ref(),
+                    auth_interceptor
+                        .intercept(rate_limit_interceptor.intercept(compressed!($service))),
+                )
+            };
+        }
+        #[cfg(madsim)]
+        macro_rules! client_service {
+            ($service:expr) => {
+                $service
+            };
+        }
+        let mut peer_builder = Server::builder();
+        #[cfg(not(madsim))]
+        if let Some(ref cfg) = self.peer_tls_config {
+            peer_builder = peer_builder.tls_config(cfg.clone())?;
+        }
+        #[cfg(not(madsim))]
+        let peer_builder =
+            with_grpc_limits(peer_builder, self.cluster_config.grpc()).trace_fn(grpc_request_span);
         let xline_router = builder
-            .clone()
-            .add_service(RpcLockServer::new(lock_server))
-            .add_service(RpcKvServer::new(kv_server))
-            .add_service(RpcLeaseServer::from_arc(lease_server))
-            .add_service(RpcAuthServer::new(auth_server))
-            .add_service(RpcWatchServer::new(watch_server))
-            .add_service(RpcMaintenanceServer::new(maintenance_server))
-            .add_service(RpcClusterServer::new(cluster_server))
-            .add_service(ProtocolServer::new(auth_wrapper));
-        let curp_router = builder
-            .add_service(ProtocolServer::new(curp_server.clone()))
-            .add_service(InnerProtocolServer::new(curp_server));
+            .add_service(client_service!(RpcLockServer::new(lock_server)))
+            .add_service(client_service!(RpcElectionServer::new(election_server)))
+            .add_service(client_service!(RpcKvServer::new(kv_server)))
+            .add_service(client_service!(RpcLeaseServer::from_arc(lease_server)))
+            .add_service(client_service!(RpcAuthServer::new(auth_server)))
+            .add_service(client_service!(RpcWatchServer::new(watch_server)))
+            .add_service(client_service!(RpcMaintenanceServer::new(
+                maintenance_server
+            )))
+            .add_service(client_service!(RpcClusterServer::new(cluster_server)))
+            .add_service(authenticated!(RpcAdminServer::new(admin_server)))
+            .add_service(authenticated!(ProtocolServer::new(auth_wrapper)));
+        let curp_router = peer_builder
+            .add_service(compressed!(ProtocolServer::new(curp_server.clone())))
+            .add_service(compressed!(InnerProtocolServer::new(curp_server)));
         #[cfg(not(madsim))]
         let xline_router = {
             let (mut reporter, health_server) = tonic_health::server::health_reporter();
             reporter
                 .set_service_status("", tonic_health::ServingStatus::Serving)
                 .await;
-            xline_router.add_service(health_server)
+            let xline_router = xline_router.add_service(health_server);
+            if *self.cluster_config.enable_grpc_reflection() {
+                let reflection_server = tonic_reflection::server::Builder::configure()
+                    .register_encoded_file_descriptor_set(xlineapi::FILE_DESCRIPTOR_SET)
+                    .register_encoded_file_descriptor_set(tonic_health::pb::FILE_DESCRIPTOR_SET)
+                    .build()?;
+                xline_router.add_service(reflection_server)
+            } else {
+                xline_router
+            }
         };
         Ok((xline_router, curp_router, curp_client))
     }
@@ -351,16 +653,41 @@ impl XlineServer {
             .task_manager
             .get_shutdown_listener(TaskName::TonicServer);
         let n2 = n1.clone();
-        let persistent = DB::open(&self.storage_config.engine)?;
+        let persistent = DB::open_with_storage_config(&self.storage_config)?;
         let key_pair = Self::read_key_pair(&self.auth_config).await?;
         let (xline_router, curp_router, curp_client) =
             self.init_router(persistent, key_pair).await?;
+        self.curp_client.set(Arc::clone(&curp_client));
+        let mut client_shutdown = self.client_shutdown.subscribe();
+        let client_stopped = Arc::clone(&self.client_stopped);
         let handle = tokio::spawn(async move {
+            let xline_shutdown = async move {
+                tokio::select! {
+                    _ = n1.wait() => {},
+                    _ = client_shutdown.wait_for(|stop| *stop) => {},
+                }
+            };
+            let client_stopped_c = Arc::clone(&client_stopped);
+            let xline_server = async move {
+                let res = xline_router
+                    .serve_with_shutdown(xline_addr, xline_shutdown)
+                    .await;
+                let _prev = client_stopped_c.send_replace(true);
+                res
+            };
+            let curp_server = curp_router.serve_with_shutdown(curp_addr, n2.wait());
+            tokio::pin!(curp_server);
+            // The servers fail together, see `start_inner`
             tokio::select! {
-                _ = xline_router.serve_with_shutdown(xline_addr, n1.wait()) => {},
-                _ = curp_router.serve_with_shutdown(curp_addr, n2.wait()) => {},
+                res = xline_server => match res {
+                    Ok(()) => curp_server.await,
+                    Err(e) => Err(e),
+                },
+                res = &mut curp_server => {
+                    let _prev = client_stopped.send_replace(true);
+                    res
+                }
             }
-            Ok(())
         });
         if let Err(e) = self.publish(curp_client).await {
             warn!("publish name to cluster failed: {:?}", e);
@@ -378,16 +705,73 @@ impl XlineServer {
         IO::ConnectInfo: Clone + Send + Sync + 'static,
         IE: Into<Box<dyn std::error::Error + Send + Sync>> + Send,
     {
-        let persistent = DB::open(&self.storage_config.engine)?;
+        let persistent = DB::open_with_storage_config(&self.storage_config)?;
         let key_pair = Self::read_key_pair(&self.auth_config).await?;
         let (xline_router, curp_router, curp_client) =
             self.init_router(persistent, key_pair).await?;
+        self.curp_client.set(Arc::clone(&curp_client));
+        let grpc_config = *self.cluster_config.grpc();
+        let connections = (*grpc_config.max_connections() != 0)
+            .then(|| Arc::new(Semaphore::new(*grpc_config.max_connections())));
+        let xline_incoming = xline_incoming.try_filter_map(move |io| {
+            let permit = match connections {
+                Some(ref connections) => match Arc::clone(connections).try_acquire_owned() {
+                    Ok(permit) => Some(permit),
+                    Err(_e) => {
+                        warn!(
+                            "close a new client connection, the limit is {}",
+                            grpc_config.max_connections()
+                        );
+                        return futures::future::ready(Ok(None));
+                    }
+                },
+                None => None,
+            };
+            let conn = ClientConnection::new(io, *grpc_config.max_connection_age(), permit);
+            futures::future::ready(Ok(Some(conn)))
+        });
+        let mut client_shutdown = self.client_shutdown.subscribe();
+        let client_stopped = Arc::clone(&self.client_stopped);
         self.task_manager
             .spawn(TaskName::TonicServer, |n1| async move {
                 let n2 = n1.clone();
+                // The client-facing server may stop before the peer one on graceful shutdown
+                let xline_shutdown = async move {
+                    tokio::select! {
+                        _ = n1.wait() => {},
+                        _ = client_shutdown.wait_for(|stop| *stop) => {},
+                    }
+                };
+                let client_stopped_c = Arc::clone(&client_stopped);
+                let xline_server = async move {
+                    let res = xline_router
+                        .serve_with_incoming_shutdown(xline_incoming, xline_shutdown)
+                        .await;
+                    let _prev = client_stopped_c.send_replace(true);
+                    res
+                };
+                let curp_server =
+                    curp_router.serve_with_incoming_shutdown(curp_incoming, n2.wait());
+                tokio::pin!(curp_server);
+                // The servers fail together. Only a client-facing server that stopped on
+                // shutdown leaves the peer one running, which keeps the member in the
+                // cluster until the leadership is transferred.
                 tokio::select! {
-                    _ = xline_router.serve_with_incoming_shutdown(xline_incoming, n1.wait()) => {},
-                    _ = curp_router.serve_with_incoming_shutdown(curp_incoming, n2.wait()) => {},
+                    res = xline_server => match res {
+                        Ok(()) => {
+                            if let Err(e) = curp_server.await {
+                                error!("curp server exited with error: {e}");
+                            }
+                        }
+                        Err(e) => error!("xline server exited with error: {e}"),
+                    },
+                    res = &mut curp_server => {
+                        if let Err(e) = res {
+                            error!("curp server exited with error: {e}");
+                        }
+                        // the client-facing server is dropped with the peer one
+                        let _prev = client_stopped.send_replace(true);
+                    }
                 }
             });
         if let Err(e) = self.publish(curp_client).await {
@@ -410,7 +794,35 @@ impl XlineServer {
         let curp_incoming = bind_addrs(peer_listen_urls)?;
         info!("start xline server on {:?}", client_listen_urls);
         info!("start curp server on {:?}", peer_listen_urls);
-        self.start_inner(xline_incoming, curp_incoming).await
+        self.start_inner(xline_incoming, curp_incoming).await?;
+        self.start_gateway()
+    }
+
+    /// Start the HTTP/JSON gateway if its listen urls are configured
+    #[cfg(not(madsim))]
+    fn start_gateway(&self) -> Result<()> {
+        let listen_urls = self.cluster_config.client_http_listen_urls();
+        if listen_urls.is_empty() {
+            return Ok(());
+        }
+        let router = gateway::router(
+            &self.cluster_info.self_client_urls(),
+            self.client_tls_config.as_ref(),
+            Arc::clone(&self.cluster_info),
+        )?;
+        let incoming = bind_addrs(listen_urls)?;
+        info!("start http gateway on {:?}", listen_urls);
+        self.task_manager
+            .spawn(TaskName::HttpGateway, |n| async move {
+                if let Err(e) = axum::Server::builder(hyper::server::accept::from_stream(incoming))
+                    .serve(router.into_make_service())
+                    .with_graceful_shutdown(n.wait())
+                    .await
+                {
+                    error!("http gateway exited with error: {e}");
+                }
+            });
+        Ok(())
     }
 
     /// Start `XlineServer` from listeners
@@ -430,7 +842,7 @@ impl XlineServer {
         self.start_inner(xline_incoming, curp_incoming).await
     }
 
-    /// Init `KvServer`, `LockServer`, `LeaseServer`, `WatchServer` and `CurpServer`
+    /// Init `KvServer`, `LockServer`, `ElectionServer`, `LeaseServer`, `WatchServer` and `CurpServer`
     /// for the Xline Server.
     #[allow(clippy::type_complexity, clippy::too_many_lines)] // it is easy to read
     #[allow(clippy::as_conversions)] // cast to dyn
@@ -442,16 +854,29 @@ impl XlineServer {
     ) -> Result<(
         KvServer<S>,
         LockServer<S>,
+        ElectionServer<S>,
         Arc<LeaseServer<S>>,
         AuthServer<S>,
         WatchServer<S>,
         MaintenanceServer<S>,
-        ClusterServer,
+        ClusterServer<S>,
+        AdminServer<S>,
         CurpServer<S>,
         AuthWrapper<S>,
         Arc<CurpClient>,
     )> {
         let (header_gen, id_gen) = Self::construct_generator(&self.cluster_info);
+        self.upgrade_storage_schema(&header_gen, persistent.as_ref())
+            .await?;
+        recover_negotiated_version(persistent.as_ref(), &self.cluster_info)?;
+        self.task_manager
+            .spawn(TaskName::PersistNegotiatedVersion, |n| {
+                persist_negotiated_version_task(
+                    Arc::clone(&self.cluster_info),
+                    Arc::clone(&persistent),
+                    n,
+                )
+            });
         let lease_collection = Self::construct_lease_collection(
             self.cluster_config.curp_config().heartbeat_interval,
             self.cluster_config.curp_config().candidate_timeout_ticks,
@@ -469,6 +894,11 @@ impl XlineServer {
         let index_barrier = Arc::new(IndexBarrier::new());
         let id_barrier = Arc::new(IdBarrier::new());
         let compact_events = Arc::new(DashMap::new());
+        let (persist_tx, persist_rx) = channel(PERSIST_CHANNEL_SIZE);
+        let overload_monitor = Arc::new(OverloadMonitor::new(
+            *self.cluster_config.server_timeout().max_apply_queue_depth(),
+            *self.cluster_config.server_timeout().memory_limit(),
+        ));
         let ce = Arc::new(CommandExecutor::new(
             Arc::clone(&kv_storage),
             Arc::clone(&auth_storage),
@@ -481,13 +911,32 @@ impl XlineServer {
             header_gen.auth_revision_arc(),
             Arc::clone(&compact_events),
             self.storage_config.quota,
-        ));
-        let snapshot_allocator: Box<dyn SnapshotAllocator> = match self.storage_config.engine {
-            EngineConfig::Memory => Box::<MemorySnapshotAllocator>::default(),
-            EngineConfig::RocksDB(_) => Box::<RocksSnapshotAllocator>::default(),
-            #[allow(clippy::unimplemented)]
-            _ => unimplemented!(),
-        };
+            Arc::clone(&self.audit_log),
+            persist_tx,
+            Arc::clone(&overload_monitor),
+        )?);
+        self.task_manager.spawn(TaskName::PersistCommands, |n| {
+            persist_task(Arc::clone(&ce), persist_rx, n)
+        });
+        if let Some(ref backup_config) = self.storage_config.backup {
+            self.task_manager.spawn(TaskName::Backup, |n| {
+                backup_task(
+                    backup_config.clone(),
+                    Arc::clone(&header_gen),
+                    Arc::clone(&persistent),
+                    Arc::clone(&ce),
+                    n,
+                )
+            });
+        }
+        let index_snapshot_interval = self.storage_config.index_snapshot_interval;
+        if !index_snapshot_interval.is_zero() {
+            self.task_manager.spawn(TaskName::IndexSnapshot, |n| {
+                index_snapshot_task(index_snapshot_interval, Arc::clone(&ce), n)
+            });
+        }
+        let snapshot_allocator =
+            EngineType::try_from(&self.storage_config.engine)?.snapshot_allocator();
 
         let auto_compactor =
             if let Some(auto_config_cfg) = *self.compact_config.auto_compact_config() {
@@ -508,7 +957,9 @@ impl XlineServer {
 
         let state = State::new(Arc::clone(&lease_storage), auto_compactor);
 
-        let curp_config = Arc::new(self.cluster_config.curp_config().clone());
+        let mut curp_config = self.cluster_config.curp_config().clone();
+        curp_config.compression = *self.cluster_config.grpc_compression();
+        let curp_config = Arc::new(curp_config);
 
         let curp_server = CurpServer::new(
             Arc::clone(&self.cluster_info),
@@ -519,7 +970,7 @@ impl XlineServer {
             Arc::clone(&curp_config),
             Arc::clone(&self.curp_storage),
             Arc::clone(&self.task_manager),
-            self.client_tls_config.clone(),
+            self.peer_client_tls_config.clone(),
             XlineSpeculativePools::default().into_inner(),
             XlineUncommittedPools::default().into_inner(),
         )
@@ -527,7 +978,8 @@ impl XlineServer {
 
         let client = Arc::new(
             CurpClientBuilder::new(*self.cluster_config.client_config(), false)
-                .tls_config(self.client_tls_config.clone())
+                .tls_config(self.peer_client_tls_config.clone())
+                .compression(*self.cluster_config.grpc_compression())
                 .cluster_version(self.cluster_info.cluster_version())
                 .all_members(self.cluster_info.all_members_peer_urls())
                 .bypass(self.cluster_info.self_id(), curp_server.clone())
@@ -537,16 +989,34 @@ impl XlineServer {
 
         if let Some(compactor) = auto_compactor_c {
             compactor.set_compactable(Arc::clone(&client)).await;
+            self.auto_compactor.set(compactor);
         }
         ce.set_alarmer(Alarmer::new(
             self.cluster_info.self_id(),
             Arc::clone(&client),
         ));
         let raw_curp = curp_server.raw_curp();
+        let raw_curp_t = Arc::clone(&raw_curp);
+        self.transfer_target
+            .set(Arc::new(move || raw_curp_t.most_up_to_date_follower()));
+        let raw_curp_c = Arc::clone(&raw_curp);
+        self.task_manager.spawn(TaskName::SyncTerm, |n| {
+            sync_term_task(
+                Arc::clone(&header_gen),
+                move || raw_curp_c.leader().1,
+                curp_server.leader_rx(),
+                n,
+            )
+        });
 
-        Metrics::register_callback()?;
+        Metrics::register_callback(kv_storage.tenants(), Arc::clone(&persistent))?;
 
         let server_timeout = self.cluster_config.server_timeout();
+        let qos_classifier = Arc::new(QosClassifier::new(
+            self.cluster_config.qos_classes(),
+            Arc::new(FeatureGate::new(Arc::clone(&self.cluster_info))),
+        ));
+        let stream_shutdown = StreamShutdown::new(self.client_shutdown.subscribe());
         Ok((
             KvServer::new(
                 Arc::clone(&kv_storage),
@@ -557,42 +1027,89 @@ impl XlineServer {
                 *server_timeout.compact_timeout(),
                 Arc::clone(&client),
                 compact_events,
+                ce.quota_checker(),
+                *self.cluster_config.max_txn_ops(),
+                *self.cluster_config.max_request_bytes(),
+                RequestLogger::new(
+                    self.cluster_info.self_id(),
+                    *server_timeout.slow_request_threshold(),
+                ),
+                overload_monitor,
+                Arc::clone(&qos_classifier),
             ),
             LockServer::new(
                 Arc::clone(&client),
                 Arc::clone(&auth_storage),
                 Arc::clone(&id_gen),
-                &self.cluster_info.self_peer_urls(),
+                &self.cluster_info.self_client_urls(),
                 self.client_tls_config.as_ref(),
             ),
+            ElectionServer::new(
+                Arc::clone(&client),
+                Arc::clone(&auth_storage),
+                Arc::clone(&id_gen),
+                &self.cluster_info.self_client_urls(),
+                self.client_tls_config.as_ref(),
+                stream_shutdown.clone(),
+            ),
             LeaseServer::new(
-                lease_storage,
+                Arc::clone(&lease_storage),
                 Arc::clone(&auth_storage),
                 Arc::clone(&client),
                 id_gen,
                 Arc::clone(&self.cluster_info),
                 self.client_tls_config.clone(),
+                *server_timeout.lease_checkpoint_interval(),
+                qos_classifier,
                 &self.task_manager,
+                stream_shutdown.clone(),
+            ),
+            AuthServer::new(
+                Arc::clone(&client),
+                Arc::clone(&auth_storage),
+                *self.auth_config.password_hash(),
+                *self.auth_config.lockout(),
             ),
-            AuthServer::new(Arc::clone(&client), Arc::clone(&auth_storage)),
             WatchServer::new(
-                watcher,
+                Arc::clone(&watcher),
                 Arc::clone(&header_gen),
                 *server_timeout.watch_progress_notify_interval(),
+                *self
+                    .cluster_config
+                    .grpc()
+                    .max_watch_streams_per_connection(),
                 Arc::clone(&self.task_manager),
+                stream_shutdown,
             ),
             MaintenanceServer::new(
-                kv_storage,
+                Arc::clone(&kv_storage),
                 Arc::clone(&auth_storage),
                 Arc::clone(&client),
                 persistent,
                 Arc::clone(&header_gen),
                 Arc::clone(&self.cluster_info),
-                raw_curp,
+                Arc::clone(&raw_curp),
                 ce,
                 alarm_storage,
+                self.client_tls_config.clone(),
+            ),
+            ClusterServer::new(
+                Arc::clone(&client),
+                Arc::clone(&header_gen),
+                Arc::clone(&auth_storage),
+                Arc::clone(&self.audit_log),
+            ),
+            AdminServer::new(
+                kv_storage,
+                lease_storage,
+                Arc::clone(&auth_storage),
+                watcher,
+                raw_curp,
+                Arc::clone(&client),
+                header_gen,
+                self.auth_config.clone(),
+                self.log_level.clone(),
             ),
-            ClusterServer::new(Arc::clone(&client), header_gen),
             curp_server.clone(),
             AuthWrapper::new(curp_server, auth_storage),
             client,
@@ -616,8 +1133,84 @@ impl XlineServer {
         self.task_manager.shutdown(true).await;
     }
 
+    /// Gracefully shutdown `XlineServer`. It stops accepting new client
+    /// requests, waits up to the `shutdown_drain_timeout` of the server
+    /// timeout for the in-flight ones to finish, transfers the
+    /// leadership to another member if this node is the leader, and then
+    /// stops all tasks.
+    #[inline]
+    pub async fn shutdown(&self) {
+        info!("graceful shutdown start");
+        let _prev = self.client_shutdown.send_replace(true);
+        let mut client_stopped = self.client_stopped.subscribe();
+        if tokio::time::timeout(
+            *self
+                .cluster_config
+                .server_timeout()
+                .shutdown_drain_timeout(),
+            client_stopped.wait_for(|stopped| *stopped),
+        )
+        .await
+        .is_err()
+        {
+            warn!("timeout waiting for in-flight client requests, continue shutting down");
+        }
+        if let Some(curp_client) = self.curp_client.get() {
+            self.transfer_leadership(curp_client.as_ref()).await;
+        }
+        self.task_manager.shutdown(true).await;
+        info!("graceful shutdown finished");
+    }
+
+    /// Apply a new compact config at runtime. Only the auto-compaction
+    /// parameters can be changed, and its mode must stay the same as the one
+    /// the server started with.
+    ///
+    /// # Errors
+    ///
+    /// Return error if auto compaction is not running or the mode changes
+    #[inline]
+    pub fn reload_compact_config(&self, compact_config: &CompactConfig) -> Result<()> {
+        let Some(auto_compact_cfg) = *compact_config.auto_compact_config() else {
+            return Err(anyhow!("auto compaction can not be disabled at runtime"));
+        };
+        let Some(compactor) = self.auto_compactor.get() else {
+            return Err(anyhow!("auto compaction is not running"));
+        };
+        if !compactor.update_config(auto_compact_cfg) {
+            return Err(anyhow!(
+                "auto compaction mode can not be changed at runtime"
+            ));
+        }
+        info!("auto compaction config reloaded: {auto_compact_cfg:?}");
+        Ok(())
+    }
+
+    /// Move the leadership to the most up-to-date follower if this node is the leader, so
+    /// that the new leader needs the fewest log entries to catch up
+    async fn transfer_leadership(&self, curp_client: &CurpClient) {
+        let self_id = self.cluster_info.self_id();
+        match curp_client.fetch_leader_id(false).await {
+            Ok(leader_id) if leader_id == self_id => {}
+            Ok(_) => return,
+            Err(e) => {
+                warn!("failed to fetch leader before shutdown: {e:?}");
+                return;
+            }
+        }
+        let Some(target) = self.transfer_target.get().and_then(|pick| pick()) else {
+            return;
+        };
+        info!("transfer leadership to {target} before shutdown");
+        if let Err(e) = curp_client.move_leader(target).await {
+            warn!("failed to transfer leadership to {target}: {e:?}");
+        }
+    }
+
     /// Read key pair from file
-    async fn read_key_pair(auth_config: &AuthConfig) -> Result<Option<(EncodingKey, DecodingKey)>> {
+    pub(crate) async fn read_key_pair(
+        auth_config: &AuthConfig,
+    ) -> Result<Option<(EncodingKey, DecodingKey)>> {
         match (
             auth_config.auth_private_key().as_ref(),
             auth_config.auth_public_key().as_ref(),
@@ -634,11 +1227,26 @@ impl XlineServer {
         }
     }
 
+    /// Read public keys of previous private keys from file
+    pub(crate) async fn read_previous_public_keys(
+        auth_config: &AuthConfig,
+    ) -> Result<Vec<DecodingKey>> {
+        let mut keys = Vec::with_capacity(auth_config.auth_previous_public_keys().len());
+        for path in auth_config.auth_previous_public_keys() {
+            keys.push(DecodingKey::from_rsa_pem(&fs::read(path).await?)?);
+        }
+        Ok(keys)
+    }
+
     /// Read tls cert and key from file
     #[cfg(not(madsim))]
     async fn read_tls_config(
         tls_config: &TlsConfig,
-    ) -> Result<(Option<ClientTlsConfig>, Option<ServerTlsConfig>)> {
+    ) -> Result<(
+        Option<ClientTlsConfig>,
+        Option<ServerTlsConfig>,
+        Option<ServerTlsConfig>,
+    )> {
         let client_tls_config = match (
             tls_config.client_ca_cert_path().as_ref(),
             tls_config.client_cert_path().as_ref(),
@@ -665,7 +1273,7 @@ impl XlineServer {
             }
             _ => None,
         };
-        let server_tls_config = match (
+        let peer_tls_config = match (
             tls_config.peer_ca_cert_path().as_ref(),
             tls_config.peer_cert_path().as_ref(),
             tls_config.peer_key_path().as_ref(),
@@ -690,8 +1298,76 @@ impl XlineServer {
             }
             _ => None,
         };
-        Ok((client_tls_config, server_tls_config))
+        let server_tls_config = match (
+            tls_config.server_ca_cert_path().as_ref(),
+            tls_config.server_cert_path().as_ref(),
+            tls_config.server_key_path().as_ref(),
+        ) {
+            (Some(ca_path), Some(cert_path), Some(key_path)) => {
+                let ca = fs::read(ca_path).await?;
+                let cert = fs::read_to_string(cert_path).await?;
+                let key = fs::read_to_string(key_path).await?;
+                Some(
+                    ServerTlsConfig::new()
+                        .client_ca_root(Certificate::from_pem(ca))
+                        .identity(Identity::from_pem(cert, key)),
+                )
+            }
+            (None, Some(cert_path), Some(key_path)) => {
+                let cert = fs::read_to_string(cert_path).await?;
+                let key = fs::read_to_string(key_path).await?;
+                Some(ServerTlsConfig::new().identity(Identity::from_pem(cert, key)))
+            }
+            (_, Some(_), None) | (_, None, Some(_)) => {
+                return Err(anyhow!(
+                    "server_cert_path and server_key_path must be both set"
+                ))
+            }
+            _ => peer_tls_config.clone(),
+        };
+        Ok((client_tls_config, server_tls_config, peer_tls_config))
     }
+
+    /// Read the client tls config used on the peer endpoint, the peer certificate
+    /// is used as the client identity when the peer ca certificate is set
+    #[cfg(not(madsim))]
+    async fn read_peer_client_tls_config(
+        tls_config: &TlsConfig,
+    ) -> Result<Option<ClientTlsConfig>> {
+        let (Some(ca_path), Some(cert_path), Some(key_path)) = (
+            tls_config.peer_ca_cert_path().as_ref(),
+            tls_config.peer_cert_path().as_ref(),
+            tls_config.peer_key_path().as_ref(),
+        ) else {
+            return Ok(None);
+        };
+        let ca = fs::read(ca_path).await?;
+        let cert = fs::read(cert_path).await?;
+        let key = fs::read(key_path).await?;
+        Ok(Some(
+            ClientTlsConfig::new()
+                .ca_certificate(Certificate::from_pem(ca))
+                .identity(Identity::from_pem(cert, key)),
+        ))
+    }
+}
+
+/// Apply the limits of the gRPC config to a server builder, zero values keep the
+/// defaults of the transport
+#[cfg(not(madsim))]
+fn with_grpc_limits<L>(builder: Server<L>, config: &GrpcConfig) -> Server<L> {
+    builder
+        .http2_keepalive_interval(non_zero(*config.keepalive_interval()))
+        .http2_keepalive_timeout(non_zero(*config.keepalive_timeout()))
+        .max_concurrent_streams(non_zero(*config.max_concurrent_streams()))
+        .initial_stream_window_size(non_zero(*config.initial_stream_window_size()))
+        .initial_connection_window_size(non_zero(*config.initial_connection_window_size()))
+}
+
+/// `None` if the value is zero
+#[cfg(not(madsim))]
+fn non_zero<T: Default + PartialEq>(value: T) -> Option<T> {
+    (value != T::default()).then_some(value)
 }
 
 /// Bind multiple addresses
@@ -94,6 +94,7 @@ where
                 .or_default()
                 .insert(alarm.member_id, alarm);
         }
+        self.refresh_current_alarm(&types_w);
         Ok(())
     }
 }
@@ -221,3 +222,33 @@ where
         ops
     }
 }
+
+#[cfg(test)]
+mod test {
+    use utils::config::EngineConfig;
+    use xlineapi::AlarmRequest;
+
+    use super::*;
+    use crate::storage::db::DB;
+
+    #[test]
+    fn recover_should_restore_current_alarm() -> Result<(), Box<dyn std::error::Error>> {
+        let db = DB::open(&EngineConfig::Memory)?;
+        let header_gen = Arc::new(HeaderGenerator::new(0, 0));
+        let store = AlarmStore::new(Arc::clone(&header_gen), Arc::clone(&db));
+        let req = RequestWrapper::from(AlarmRequest::new(
+            AlarmAction::Activate,
+            1,
+            AlarmType::Nospace,
+        ));
+        let (_sync_res, ops) = store.after_sync(&req, 1);
+        _ = db.write_batch(ops, false)?;
+        assert_eq!(store.current_alarm(), AlarmType::Nospace);
+
+        let new_store = AlarmStore::new(header_gen, db);
+        assert_eq!(new_store.current_alarm(), AlarmType::None);
+        new_store.recover()?;
+        assert_eq!(new_store.current_alarm(), AlarmType::Nospace);
+        Ok(())
+    }
+}
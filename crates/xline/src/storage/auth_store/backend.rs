@@ -126,7 +126,7 @@ where
         &self,
         ops: Vec<crate::storage::db::WriteOp>,
     ) -> Result<(), ExecuteError> {
-        _ = self.db.flush_ops(ops)?;
+        _ = self.db.write_batch(ops, false)?;
         Ok(())
     }
 }
@@ -6,4 +6,4 @@ mod perms;
 mod store;
 
 pub(crate) use backend::{AUTH_ENABLE_KEY, AUTH_REVISION_KEY};
-pub(crate) use store::AuthStore;
+pub(crate) use store::{AuthStore, Identity};
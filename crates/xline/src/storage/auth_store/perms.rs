@@ -1,17 +1,24 @@
-use std::{collections::HashMap, fmt::Debug};
+use std::{
+    collections::HashMap,
+    fmt::Debug,
+    ops::Add,
+    time::{Duration, Instant},
+};
 
 use jsonwebtoken::{
-    errors::Error as JwtError, Algorithm, DecodingKey, EncodingKey, Header, Validation,
+    errors::{Error as JwtError, ErrorKind},
+    Algorithm, DecodingKey, EncodingKey, Header, Validation,
 };
 use merged_range::MergedRange;
+use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
 use utils::timestamp;
 use xlineapi::{command::KeyRange, AuthInfo};
 
 use crate::rpc::{Permission, Type};
 
-/// default token ttl
-const DEFAULT_TOKEN_TTL: u64 = 300;
+/// Max number of results kept by `PermissionCheckCache`
+const PERMISSION_CHECK_CACHE_CAPACITY: usize = 16 * 1024;
 
 /// Claims of Token
 #[derive(Debug, Serialize, Deserialize)]
@@ -51,29 +58,73 @@ pub(super) trait TokenOperate {
 
 /// `TokenManager` of Json Web Token.
 pub(super) struct JwtTokenManager {
+    /// Keys used to sign and verify the token
+    keys: RwLock<JwtKeys>,
+    /// How long an assigned token is valid
+    token_ttl: Duration,
+}
+
+/// Keys of `JwtTokenManager`
+struct JwtKeys {
     /// The key used to sign the token.
     encoding_key: EncodingKey,
     /// The key used to verify the token.
     decoding_key: DecodingKey,
+    /// Configured keys of previous signing keys, tokens signed by them are still accepted
+    previous_keys: Vec<DecodingKey>,
+    /// Keys replaced by rotations, each of them is accepted until its deadline, so that
+    /// tokens signed before a rotation stay valid until they expire
+    retired_keys: Vec<(DecodingKey, Instant)>,
 }
 
 impl Debug for JwtTokenManager {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let keys = self.keys.read();
         f.debug_struct("JwtTokenManager")
             .field("encoding_key", &"EncodingKey")
             .field("decoding_key", &"DecodingKey")
+            .field("previous_keys", &keys.previous_keys.len())
+            .field("retired_keys", &keys.retired_keys.len())
+            .field("token_ttl", &self.token_ttl)
             .finish()
     }
 }
 
 impl JwtTokenManager {
     /// New `JwtTokenManager`
-    pub(crate) fn new(encoding_key: EncodingKey, decoding_key: DecodingKey) -> Self {
+    pub(crate) fn new(
+        encoding_key: EncodingKey,
+        decoding_key: DecodingKey,
+        previous_keys: Vec<DecodingKey>,
+        token_ttl: Duration,
+    ) -> Self {
         Self {
-            encoding_key,
-            decoding_key,
+            keys: RwLock::new(JwtKeys {
+                encoding_key,
+                decoding_key,
+                previous_keys,
+                retired_keys: Vec::new(),
+            }),
+            token_ttl,
         }
     }
+
+    /// Replace the signing key, the replaced decoding key is accepted for another token
+    /// ttl, which is the longest lifetime of the tokens it signed
+    pub(crate) fn rotate(
+        &self,
+        encoding_key: EncodingKey,
+        decoding_key: DecodingKey,
+        previous_keys: Vec<DecodingKey>,
+    ) {
+        let now = Instant::now();
+        let mut keys = self.keys.write();
+        keys.retired_keys.retain(|&(_, deadline)| deadline > now);
+        let retired = std::mem::replace(&mut keys.decoding_key, decoding_key);
+        keys.retired_keys.push((retired, now.add(self.token_ttl)));
+        keys.encoding_key = encoding_key;
+        keys.previous_keys = previous_keys;
+    }
 }
 
 impl TokenOperate for JwtTokenManager {
@@ -86,20 +137,34 @@ impl TokenOperate for JwtTokenManager {
         let claims = TokenClaims {
             username: username.to_owned(),
             revision,
-            exp: now.wrapping_add(DEFAULT_TOKEN_TTL),
+            exp: now.wrapping_add(self.token_ttl.as_secs()),
         };
-        let token =
-            jsonwebtoken::encode(&Header::new(Algorithm::RS256), &claims, &self.encoding_key)?;
+        let token = jsonwebtoken::encode(
+            &Header::new(Algorithm::RS256),
+            &claims,
+            &self.keys.read().encoding_key,
+        )?;
         Ok(token)
     }
 
     fn verify(&self, token: &str) -> Result<Self::Claims, Self::Error> {
-        jsonwebtoken::decode::<TokenClaims>(
-            token,
-            &self.decoding_key,
-            &Validation::new(Algorithm::RS256),
-        )
-        .map(|d| d.claims)
+        let validation = Validation::new(Algorithm::RS256);
+        let keys = self.keys.read();
+        let result = jsonwebtoken::decode::<TokenClaims>(token, &keys.decoding_key, &validation);
+        if !matches!(result, Err(ref e) if matches!(*e.kind(), ErrorKind::InvalidSignature)) {
+            return result.map(|d| d.claims);
+        }
+        let now = Instant::now();
+        keys.previous_keys
+            .iter()
+            .chain(
+                keys.retired_keys
+                    .iter()
+                    .filter(|&&(_, deadline)| deadline > now)
+                    .map(|&(ref key, _)| key),
+            )
+            .find_map(|key| jsonwebtoken::decode::<TokenClaims>(token, key, &validation).ok())
+            .map_or(result.map(|d| d.claims), Ok)
     }
 }
 
@@ -147,6 +212,8 @@ pub(super) struct PermissionCache {
     pub(super) user_permissions: HashMap<String, UserPermissions>,
     /// Role to users map
     pub(super) role_to_users_map: HashMap<String, Vec<String>>,
+    /// User to roles map, for resolving the roles of a user without a scan
+    pub(super) user_to_roles_map: HashMap<String, Vec<String>>,
 }
 
 impl PermissionCache {
@@ -155,6 +222,55 @@ impl PermissionCache {
         Self {
             user_permissions: HashMap::new(),
             role_to_users_map: HashMap::new(),
+            user_to_roles_map: HashMap::new(),
         }
     }
+
+    /// Set the roles of a user, a user without roles is removed from the map
+    pub(super) fn set_user_roles(&mut self, username: &str, roles: &[String]) {
+        if roles.is_empty() {
+            let _ignore = self.user_to_roles_map.remove(username);
+        } else {
+            let _old = self
+                .user_to_roles_map
+                .insert(username.to_owned(), roles.to_vec());
+        }
+    }
+}
+
+/// Key of a permission check, made of the user, the key range and the permission type
+pub(super) type PermissionCheck = (String, KeyRange, Type);
+
+/// Cache of the results of permission checks
+#[derive(Debug, Default)]
+pub(super) struct PermissionCheckCache {
+    /// Generation of the results, bumped on every invalidation
+    generation: u64,
+    /// Whether the checks are permitted
+    results: HashMap<PermissionCheck, bool>,
+}
+
+impl PermissionCheckCache {
+    /// Get the result of a check, or the current generation if it is not cached
+    pub(super) fn get(&self, check: &PermissionCheck) -> Result<bool, u64> {
+        self.results.get(check).copied().ok_or(self.generation)
+    }
+
+    /// Cache the result of a check computed in `generation`, the result is dropped if
+    /// the cache has been invalidated since then
+    pub(super) fn insert(&mut self, generation: u64, check: PermissionCheck, permitted: bool) {
+        if generation != self.generation {
+            return;
+        }
+        if self.results.len() >= PERMISSION_CHECK_CACHE_CAPACITY {
+            self.results.clear();
+        }
+        let _ignore = self.results.insert(check, permitted);
+    }
+
+    /// Drop all cached results
+    pub(super) fn invalidate(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+        self.results.clear();
+    }
 }
@@ -5,18 +5,17 @@ use std::{
         atomic::{AtomicBool, Ordering as AtomicOrdering},
         Arc,
     },
+    time::Duration,
 };
 
 use clippy_utilities::NumericCast;
 use itertools::Itertools;
 use jsonwebtoken::{DecodingKey, EncodingKey};
-use log::debug;
+use log::{debug, warn};
 use parking_lot::RwLock;
-use pbkdf2::{
-    password_hash::{PasswordHash, PasswordVerifier},
-    Pbkdf2,
+use utils::{
+    config::PasswordHashConfig, parking_lot_lock::RwLockMap, password_needs_rehash, verify_password,
 };
-use utils::parking_lot_lock::RwLockMap;
 use xlineapi::{
     command::{CommandResponse, KeyRange, SyncResponse},
     execute_error::ExecuteError,
@@ -25,7 +24,10 @@ use xlineapi::{
 
 use super::{
     backend::{ROOT_ROLE, ROOT_USER},
-    perms::{JwtTokenManager, PermissionCache, TokenOperate, UserPermissions},
+    perms::{
+        JwtTokenManager, PermissionCache, PermissionCheck, PermissionCheckCache, TokenOperate,
+        UserPermissions,
+    },
 };
 use crate::{
     header_gen::HeaderGenerator,
@@ -40,9 +42,9 @@ use crate::{
         AuthUserChangePasswordResponse, AuthUserDeleteRequest, AuthUserDeleteResponse,
         AuthUserGetRequest, AuthUserGetResponse, AuthUserGrantRoleRequest,
         AuthUserGrantRoleResponse, AuthUserListRequest, AuthUserListResponse,
-        AuthUserRevokeRoleRequest, AuthUserRevokeRoleResponse, AuthenticateRequest,
-        AuthenticateResponse, DeleteRangeRequest, LeaseRevokeRequest, Permission, PutRequest,
-        RangeRequest, Request, RequestOp, RequestWrapper, Role, TxnRequest, Type, User,
+        AuthUserRevokeRoleRequest, AuthUserRevokeRoleResponse, AuthenticateResponse,
+        DeleteRangeRequest, InternalAuthenticateRequest, LeaseRevokeRequest, Permission,
+        PutRequest, RangeRequest, Request, RequestOp, RequestWrapper, Role, TxnRequest, Type, User,
     },
     server::get_token,
     storage::{
@@ -53,6 +55,15 @@ use crate::{
     },
 };
 
+/// Identity of the user of a request
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Identity {
+    /// Auth info of the user, `None` if auth is disabled or the request carries no credential
+    pub(crate) auth_info: Option<AuthInfo>,
+    /// Roles of the user
+    pub(crate) roles: Vec<String>,
+}
+
 /// Auth store
 #[derive(Debug)]
 pub(crate) struct AuthStore<S>
@@ -71,8 +82,12 @@ where
     header_gen: Arc<HeaderGenerator>,
     /// Permission cache
     permission_cache: RwLock<PermissionCache>,
+    /// Results of permission checks of kv operations
+    permission_check_cache: RwLock<PermissionCheckCache>,
     /// The manager of token
     token_manager: Option<JwtTokenManager>,
+    /// Whether to authenticate a client by the common name of its certificate
+    client_cert_auth: bool,
 }
 
 impl<S> AuthStore<S>
@@ -84,8 +99,11 @@ where
     pub(crate) fn new(
         lease_collection: Arc<LeaseCollection>,
         key_pair: Option<(EncodingKey, DecodingKey)>,
+        previous_keys: Vec<DecodingKey>,
+        token_ttl: Duration,
         header_gen: Arc<HeaderGenerator>,
         storage: Arc<S>,
+        client_cert_auth: bool,
     ) -> Self {
         let backend = Arc::new(AuthStoreBackend::new(storage));
         Self {
@@ -95,9 +113,11 @@ where
             lease_collection,
             header_gen,
             permission_cache: RwLock::new(PermissionCache::new()),
+            permission_check_cache: RwLock::new(PermissionCheckCache::default()),
             token_manager: key_pair.map(|(encoding_key, decoding_key)| {
-                JwtTokenManager::new(encoding_key, decoding_key)
+                JwtTokenManager::new(encoding_key, decoding_key, previous_keys, token_ttl)
             }),
+            client_cert_auth,
         }
     }
 
@@ -121,6 +141,52 @@ where
         }
     }
 
+    /// Assign a new token to the user of `auth_info`, which is resolved from a still-valid
+    /// token or client certificate, so that clients can renew tokens without passwords.
+    /// Tokens of old auth revisions can not be refreshed.
+    pub(crate) fn refresh_token(
+        &self,
+        name: &str,
+        auth_info: &AuthInfo,
+    ) -> Result<AuthenticateResponse, ExecuteError> {
+        if !self.is_enabled() {
+            return Err(ExecuteError::AuthNotEnabled);
+        }
+        if !name.is_empty() && name != auth_info.username {
+            return Err(ExecuteError::AuthFailed);
+        }
+        let cur_rev = self.revision();
+        if auth_info.auth_revision < cur_rev {
+            return Err(ExecuteError::TokenOldRevision(
+                auth_info.auth_revision,
+                cur_rev,
+            ));
+        }
+        let _user = self.backend.get_user(&auth_info.username)?;
+        let token = self.assign(&auth_info.username)?;
+        Ok(AuthenticateResponse {
+            header: Some(self.header_gen.gen_auth_header()),
+            token,
+        })
+    }
+
+    /// Rotate the key pair used to sign and verify tokens, tokens signed by the replaced
+    /// key are still accepted until they expire
+    pub(crate) fn rotate_key_pair(
+        &self,
+        encoding_key: EncodingKey,
+        decoding_key: DecodingKey,
+        previous_keys: Vec<DecodingKey>,
+    ) -> Result<(), ExecuteError> {
+        match self.token_manager {
+            Some(ref token_manager) => {
+                token_manager.rotate(encoding_key, decoding_key, previous_keys);
+                Ok(())
+            }
+            None => Err(ExecuteError::TokenManagerNotInit),
+        }
+    }
+
     /// verify token
     pub(crate) fn verify(&self, token: &str) -> Result<AuthInfo, ExecuteError> {
         match self.token_manager {
@@ -132,11 +198,25 @@ where
         }
     }
 
-    /// Try get auth info from tonic request
-    pub(crate) fn try_get_auth_info_from_request<T>(
+    /// Authenticate a tonic request by its token or the common name of its client
+    /// certificate, and resolve the roles of its user
+    pub(crate) fn authenticate<T>(
         &self,
         request: &tonic::Request<T>,
-    ) -> Result<Option<AuthInfo>, tonic::Status> {
+    ) -> Result<Identity, tonic::Status> {
+        let auth_info = self.auth_info_of(request)?;
+        let roles = auth_info
+            .as_ref()
+            .map(|info| self.user_roles(&info.username))
+            .unwrap_or_default();
+        Ok(Identity { auth_info, roles })
+    }
+
+    /// Get auth info of a tonic request by its token or the common name of its client certificate
+    fn auth_info_of<T>(
+        &self,
+        request: &tonic::Request<T>,
+    ) -> Result<Option<AuthInfo>, ExecuteError> {
         if !self.is_enabled() {
             return Ok(None);
         }
@@ -144,6 +224,9 @@ where
             let auth_info = self.verify(&token)?;
             return Ok(Some(auth_info));
         }
+        if !self.client_cert_auth {
+            return Ok(None);
+        }
         if let Some(cn) = get_cn(request) {
             let auth_info = AuthInfo {
                 username: cn,
@@ -154,12 +237,66 @@ where
         Ok(None)
     }
 
+    /// Get roles of a user from the permission cache
+    fn user_roles(&self, username: &str) -> Vec<String> {
+        self.permission_cache.map_read(|cache| {
+            cache
+                .user_to_roles_map
+                .get(username)
+                .cloned()
+                .unwrap_or_default()
+        })
+    }
+
+    /// Get the identity of a tonic request, which is resolved by `AuthInterceptor` if
+    /// the request has passed it, or authenticated here otherwise
+    fn identity_of<T>(&self, request: &tonic::Request<T>) -> Result<Identity, tonic::Status> {
+        match request.extensions().get::<Identity>() {
+            Some(identity) => Ok(identity.clone()),
+            None => self.authenticate(request),
+        }
+    }
+
+    /// Try get auth info from tonic request
+    pub(crate) fn try_get_auth_info_from_request<T>(
+        &self,
+        request: &tonic::Request<T>,
+    ) -> Result<Option<AuthInfo>, tonic::Status> {
+        if !self.is_enabled() {
+            return Ok(None);
+        }
+        self.identity_of(request).map(|identity| identity.auth_info)
+    }
+
+    /// Check if the user of a tonic request has admin permission
+    pub(crate) fn check_admin_permission_from_request<T>(
+        &self,
+        request: &tonic::Request<T>,
+    ) -> Result<(), tonic::Status> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+        let Identity { auth_info, roles } = self.identity_of(request)?;
+        let Some(auth_info) = auth_info else {
+            return Err(ExecuteError::TokenNotProvided.into());
+        };
+        let cur_rev = self.revision();
+        if auth_info.auth_revision < cur_rev {
+            return Err(ExecuteError::TokenOldRevision(auth_info.auth_revision, cur_rev).into());
+        }
+        if roles.iter().any(|role| role == ROOT_ROLE) {
+            return Ok(());
+        }
+        Err(ExecuteError::PermissionDenied.into())
+    }
+
     /// create permission cache
     fn create_permission_cache(&self) -> Result<(), ExecuteError> {
         let mut permission_cache = PermissionCache::new();
         for user in self.backend.get_all_users()? {
             let user_permission = self.get_user_permissions(&user, None);
             let username = String::from_utf8_lossy(&user.name).to_string();
+            permission_cache.set_user_roles(&username, &user.roles);
             for role in user.roles {
                 permission_cache
                     .role_to_users_map
@@ -173,9 +310,17 @@ where
         }
         self.permission_cache
             .map_write(|mut cache| *cache = permission_cache);
+        self.invalidate_permission_checks();
         Ok(())
     }
 
+    /// Drop the cached results of permission checks, it should be called after the
+    /// changes of an auth request are flushed to the backend
+    pub(crate) fn invalidate_permission_checks(&self) {
+        self.permission_check_cache
+            .map_write(|mut cache| cache.invalidate());
+    }
+
     /// get user permissions
     fn get_user_permissions(&self, user: &User, skip_role: Option<&str>) -> UserPermissions {
         let mut user_permission = UserPermissions::new();
@@ -248,9 +393,12 @@ where
             RequestWrapper::AuthRoleListRequest(ref req) => {
                 self.handle_role_list_request(req).map(Into::into)
             }
-            RequestWrapper::AuthenticateRequest(ref req) => {
-                self.handle_authenticate_request(req).map(Into::into)
-            }
+            RequestWrapper::AuthenticateRequest(ref req) => self
+                .handle_authenticate_request(&req.name, &req.password)
+                .map(Into::into),
+            RequestWrapper::InternalAuthenticateRequest(ref req) => self
+                .handle_authenticate_request(&req.name, &req.password)
+                .map(Into::into),
             _ => {
                 unreachable!("Other request should not be sent to this store");
             }
@@ -299,17 +447,18 @@ where
         }
     }
 
-    /// Handle `AuthenticateRequest`
+    /// Handle `AuthenticateRequest` and `InternalAuthenticateRequest`
     fn handle_authenticate_request(
         &self,
-        req: &AuthenticateRequest,
+        name: &str,
+        password: &str,
     ) -> Result<AuthenticateResponse, ExecuteError> {
         debug!("handle_authenticate_request");
         if !self.is_enabled() {
             return Err(ExecuteError::AuthNotEnabled);
         }
-        self.check_password(&req.name, &req.password)?;
-        let token = self.assign(&req.name)?;
+        self.check_password(name, password)?;
+        let token = self.assign(name)?;
         Ok(AuthenticateResponse {
             header: Some(self.header_gen.gen_auth_header()),
             token,
@@ -606,6 +755,10 @@ where
                 debug!("Sync AuthenticateRequest {:?}", req);
                 Vec::new()
             }
+            RequestWrapper::InternalAuthenticateRequest(ref req) => {
+                debug!("Sync InternalAuthenticateRequest of {}", req.name);
+                self.sync_authenticate_request(req)?
+            }
             _ => {
                 unreachable!("Other request should not be sent to this store");
             }
@@ -665,6 +818,7 @@ where
         let mut ops = Vec::new();
         self.permission_cache.map_write(|mut cache| {
             let _ignore = cache.user_permissions.remove(&req.name);
+            let _old_roles = cache.user_to_roles_map.remove(&req.name);
             cache.role_to_users_map.iter_mut().for_each(|(_, users)| {
                 if let Some((idx, _)) = users.iter().find_position(|uname| uname == &&req.name) {
                     let _old = users.swap_remove(idx);
@@ -690,6 +844,23 @@ where
         Ok(ops)
     }
 
+    /// Sync `InternalAuthenticateRequest`, the password hash of the user is upgraded if the
+    /// request carries a new hash of the password. The password is verified again because it may
+    /// have been changed after the request is executed. The auth revision is not changed,
+    /// so that the tokens of the user stay valid.
+    fn sync_authenticate_request<'a>(
+        &self,
+        req: &'a InternalAuthenticateRequest,
+    ) -> Result<Vec<WriteOp<'a>>, ExecuteError> {
+        if req.hashed_password.is_empty() || self.check_password(&req.name, &req.password).is_err()
+        {
+            return Ok(Vec::new());
+        }
+        let mut user = self.backend.get_user(&req.name)?;
+        user.password = req.hashed_password.as_str().into();
+        Ok(vec![WriteOp::PutUser(user)])
+    }
+
     /// Sync `AuthUserGrantRoleRequest` and return whether authstore is changed.
     fn sync_user_grant_role_request<'a>(
         &self,
@@ -709,6 +880,8 @@ where
             ));
         };
         user.roles.insert(idx, req.role.clone());
+        self.permission_cache
+            .map_write(|mut cache| cache.set_user_roles(&req.user, &user.roles));
         if let Ok(role) = role {
             let perms = role.key_permission;
             self.permission_cache.map_write(|mut cache| {
@@ -754,6 +927,7 @@ where
             let _old = cache
                 .user_permissions
                 .insert(req.name.clone(), user_permissions);
+            cache.set_user_roles(&req.name, &user.roles);
         });
         ops.push(WriteOp::PutAuthRevision(revision));
         ops.push(WriteOp::PutUser(user));
@@ -781,18 +955,24 @@ where
         let mut ops = Vec::new();
         let users = self.backend.get_all_users()?;
         let mut new_perms = HashMap::new();
+        let mut new_roles = Vec::new();
         ops.push(WriteOp::PutAuthRevision(revision));
         ops.push(WriteOp::DeleteRole(req.role.as_str()));
         for mut user in users {
             if let Ok(idx) = user.roles.binary_search(&req.role) {
                 let _ignore = user.roles.remove(idx);
                 let perms = self.get_user_permissions(&user, None);
-                let _old = new_perms.insert(String::from_utf8_lossy(&user.name).to_string(), perms);
+                let username = String::from_utf8_lossy(&user.name).to_string();
+                new_roles.push((username.clone(), user.roles.clone()));
+                let _old = new_perms.insert(username, perms);
                 ops.push(WriteOp::PutUser(user));
             }
         }
         self.permission_cache.map_write(|mut cache| {
             cache.user_permissions.extend(new_perms);
+            for (username, roles) in new_roles {
+                cache.set_user_roles(&username, &roles);
+            }
             let _ignore = cache.role_to_users_map.remove(&req.role);
         });
         Ok(ops)
@@ -818,22 +998,46 @@ where
             }) {
             Ok(idx) => {
                 role.key_permission[idx].perm_type = permission.perm_type;
+                // The type of an existing permission is changed, and merged ranges can not
+                // shrink, so permissions of the users of this role are rebuilt
+                let role_permissions = role.key_permission.clone();
+                self.permission_cache.map_write(|mut cache| {
+                    let users =
+                        cache
+                            .role_to_users_map
+                            .get(&req.name)
+                            .map_or_else(Vec::new, |users| {
+                                users
+                                    .iter()
+                                    .filter_map(|user| self.backend.get_user(user).ok())
+                                    .collect::<Vec<_>>()
+                            });
+                    for user in users {
+                        let mut perms = self.get_user_permissions(&user, Some(&req.name));
+                        for perm in role_permissions.iter().cloned() {
+                            perms.insert(perm);
+                        }
+                        let _old = cache
+                            .user_permissions
+                            .insert(String::from_utf8_lossy(&user.name).to_string(), perms);
+                    }
+                });
             }
             Err(idx) => {
                 role.key_permission.insert(idx, permission.clone());
+                self.permission_cache.map_write(move |mut cache| {
+                    let users = cache
+                        .role_to_users_map
+                        .get(&req.name)
+                        .cloned()
+                        .unwrap_or_default();
+                    for user in users {
+                        let entry = cache.user_permissions.entry(user).or_default();
+                        entry.insert(permission.clone());
+                    }
+                });
             }
         };
-        self.permission_cache.map_write(move |mut cache| {
-            let users = cache
-                .role_to_users_map
-                .get(&req.name)
-                .cloned()
-                .unwrap_or_default();
-            for user in users {
-                let entry = cache.user_permissions.entry(user).or_default();
-                entry.insert(permission.clone());
-            }
-        });
         ops.push(WriteOp::PutAuthRevision(revision));
         ops.push(WriteOp::PutRole(role));
         Ok(ops)
@@ -898,16 +1102,32 @@ where
             return Err(ExecuteError::NoPasswordUser);
         }
 
+        // a user without a valid hash, e.g. one migrated without its password, can't be
+        // authenticated by any password
         let hash = String::from_utf8_lossy(&user.password);
-        let hash = PasswordHash::new(&hash)
-            .unwrap_or_else(|e| panic!("Failed to parse password hash, error: {e}"));
-        Pbkdf2
-            .verify_password(password.as_bytes(), &hash)
-            .map_err(|_ignore| ExecuteError::AuthFailed)?;
+        let verified = verify_password(password.as_bytes(), &hash).unwrap_or_else(|e| {
+            warn!("failed to parse the password hash of user {username}: {e}");
+            false
+        });
+        if !verified {
+            return Err(ExecuteError::AuthFailed);
+        }
 
         Ok(())
     }
 
+    /// Check if the password hash of a user should be upgraded to the parameters in `config`
+    pub(crate) fn password_needs_rehash(
+        &self,
+        username: &str,
+        config: &PasswordHashConfig,
+    ) -> bool {
+        self.backend.get_user(username).is_ok_and(|user| {
+            let need_password = user.options.as_ref().map_or(true, |o| !o.no_password);
+            need_password && password_needs_rehash(&String::from_utf8_lossy(&user.password), config)
+        })
+    }
+
     /// Check if the request need admin permission
     fn need_admin_permission(wrapper: &RequestWrapper) -> bool {
         matches!(
@@ -926,6 +1146,7 @@ where
                 | RequestWrapper::AuthRoleDeleteRequest(_)
                 | RequestWrapper::AuthUserListRequest(_)
                 | RequestWrapper::AuthRoleListRequest(_)
+                | RequestWrapper::LeaseCheckpointRequest(_)
         )
     }
 
@@ -943,7 +1164,10 @@ where
         if !self.is_enabled() {
             return Ok(());
         }
-        if let RequestWrapper::AuthenticateRequest(_) = *wrapper {
+        if matches!(
+            *wrapper,
+            RequestWrapper::AuthenticateRequest(_) | RequestWrapper::InternalAuthenticateRequest(_)
+        ) {
             return Ok(());
         }
         let Some(auth_info) = auth_info else {
@@ -1109,7 +1333,8 @@ where
         Err(ExecuteError::PermissionDenied)
     }
 
-    /// check permission for a kv operation
+    /// check permission for a kv operation, the results are cached until the next auth
+    /// request is applied
     fn check_op_permission(
         &self,
         username: &str,
@@ -1117,29 +1342,46 @@ where
         range_end: &[u8],
         perm_type: Type,
     ) -> Result<(), ExecuteError> {
+        let check = (
+            username.to_owned(),
+            KeyRange::new(key, range_end),
+            perm_type,
+        );
+        let permitted = match self.permission_check_cache.read().get(&check) {
+            Ok(permitted) => permitted,
+            Err(generation) => {
+                let permitted = self.is_op_permitted(&check)?;
+                self.permission_check_cache
+                    .write()
+                    .insert(generation, check, permitted);
+                permitted
+            }
+        };
+        if permitted {
+            Ok(())
+        } else {
+            Err(ExecuteError::PermissionDenied)
+        }
+    }
+
+    /// Check if the user is permitted to do the operation on the key range
+    fn is_op_permitted(&self, check: &PermissionCheck) -> Result<bool, ExecuteError> {
+        let (ref username, ref key_range, perm_type) = *check;
         let user = self.backend.get_user(username)?;
         if user.has_role(ROOT_ROLE) {
-            return Ok(());
+            return Ok(true);
         }
-        let key_range = KeyRange::new(key, range_end);
-        if let Some(permissions) = self.permission_cache.read().user_permissions.get(username) {
-            match perm_type {
-                Type::Read => {
-                    if permissions.read.contains_range(&key_range) {
-                        return Ok(());
-                    }
-                }
-                Type::Write => {
-                    if permissions.write.contains_range(&key_range) {
-                        return Ok(());
-                    }
-                }
-                Type::Readwrite => {
-                    unreachable!("Readwrite is unreachable");
-                }
+        let permission_cache = self.permission_cache.read();
+        let Some(permissions) = permission_cache.user_permissions.get(username) else {
+            return Ok(false);
+        };
+        match perm_type {
+            Type::Read => Ok(permissions.read.contains_range(key_range)),
+            Type::Write => Ok(permissions.write.contains_range(key_range)),
+            Type::Readwrite => {
+                unreachable!("Readwrite is unreachable");
             }
         }
-        Err(ExecuteError::PermissionDenied)
     }
 
     /// Assign root token
@@ -1173,7 +1415,10 @@ mod test {
     use std::collections::HashMap;
 
     use merged_range::MergedRange;
-    use utils::config::EngineConfig;
+    use utils::{
+        config::{default_auth_token_ttl, EngineConfig},
+        hash_password_with,
+    };
 
     use super::*;
     use crate::{
@@ -1199,6 +1444,62 @@ mod test {
         assert_eq!(auth_info.username, "xline");
     }
 
+    #[test]
+    fn test_identity_of_request() {
+        let db = DB::open(&EngineConfig::Memory).unwrap();
+        let store = init_auth_store(db);
+        store.enabled.store(true, AtomicOrdering::Relaxed);
+        let token = store.assign("u").unwrap();
+
+        let mut request = tonic::Request::new(());
+        let _ignore = request
+            .metadata_mut()
+            .insert("token", token.parse().unwrap());
+        let identity = store.authenticate(&request).unwrap();
+        assert_eq!(identity.auth_info.as_ref().unwrap().username, "u");
+        assert_eq!(identity.roles, vec!["r".to_owned()]);
+        assert_eq!(
+            store
+                .check_admin_permission_from_request(&request)
+                .unwrap_err()
+                .code(),
+            tonic::Code::PermissionDenied
+        );
+
+        let mut invalid = tonic::Request::new(());
+        let _ignore = invalid
+            .metadata_mut()
+            .insert("token", "invalid".parse().unwrap());
+        assert!(store.authenticate(&invalid).is_err());
+        // a request failing the interceptor carries no identity and is rejected here
+        assert!(store.try_get_auth_info_from_request(&invalid).is_err());
+
+        // the identity resolved by the interceptor is used without verifying the token again
+        let mut intercepted = tonic::Request::new(());
+        let _ignore = intercepted.extensions_mut().insert(identity);
+        let auth_info = store
+            .try_get_auth_info_from_request(&intercepted)
+            .unwrap()
+            .unwrap();
+        assert_eq!(auth_info.username, "u");
+
+        let req = RequestWrapper::from(AuthUserGrantRoleRequest {
+            user: "u".to_owned(),
+            role: ROOT_ROLE.to_owned(),
+        });
+        assert!(exe_and_sync(&store, &req, store.revision.next()).is_ok());
+        let token = store.assign("u").unwrap();
+        let mut request = tonic::Request::new(());
+        let _ignore = request
+            .metadata_mut()
+            .insert("token", token.parse().unwrap());
+        assert_eq!(
+            store.authenticate(&request).unwrap().roles,
+            vec!["r".to_owned(), ROOT_ROLE.to_owned()]
+        );
+        assert!(store.check_admin_permission_from_request(&request).is_ok());
+    }
+
     #[test]
     fn test_role_grant_permission() -> Result<(), ExecuteError> {
         let db = DB::open(&EngineConfig::Memory)?;
@@ -1227,11 +1528,115 @@ mod test {
                     },
                 )]),
                 role_to_users_map: HashMap::from([("r".to_owned(), vec!["u".to_owned()])]),
+                user_to_roles_map: HashMap::from([("u".to_owned(), vec!["r".to_owned()])]),
             },
         );
         Ok(())
     }
 
+    #[test]
+    fn test_permission_check_cache() -> Result<(), ExecuteError> {
+        let db = DB::open(&EngineConfig::Memory)?;
+        let store = init_auth_store(db);
+        assert!(store
+            .check_op_permission("u", b"foo", &[], Type::Read)
+            .is_ok());
+        assert!(matches!(
+            store.check_op_permission("u", b"bar", &[], Type::Write),
+            Err(ExecuteError::PermissionDenied)
+        ));
+        let check = ("u".to_owned(), KeyRange::new("bar", ""), Type::Write);
+        assert_eq!(store.permission_check_cache.read().get(&check), Ok(false));
+
+        let req = RequestWrapper::from(AuthRoleGrantPermissionRequest {
+            name: "r".to_owned(),
+            perm: Some(Permission {
+                #[allow(clippy::as_conversions)] // This cast is always valid
+                perm_type: Type::Write as i32,
+                key: b"bar".to_vec(),
+                range_end: vec![],
+            }),
+        });
+        assert!(exe_and_sync(&store, &req, store.revision.next()).is_ok());
+        assert!(store.permission_check_cache.read().get(&check).is_err());
+        assert!(store
+            .check_op_permission("u", b"bar", &[], Type::Write)
+            .is_ok());
+        assert!(matches!(
+            store.check_op_permission("nobody", b"bar", &[], Type::Write),
+            Err(ExecuteError::UserNotFound(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_prefix_and_open_ended_permissions() -> Result<(), ExecuteError> {
+        let db = DB::open(&EngineConfig::Memory)?;
+        let store = init_auth_store(db);
+        let grant = |perm_type: Type, key: &[u8], range_end: Vec<u8>| {
+            RequestWrapper::from(AuthRoleGrantPermissionRequest {
+                name: "r".to_owned(),
+                perm: Some(Permission {
+                    #[allow(clippy::as_conversions)] // This cast is always valid
+                    perm_type: perm_type as i32,
+                    key: key.to_vec(),
+                    range_end,
+                }),
+            })
+        };
+        let req = grant(Type::Read, b"m", vec![0]);
+        assert!(exe_and_sync(&store, &req, store.revision.next()).is_ok());
+        let req = grant(Type::Readwrite, b"p/", KeyRange::get_prefix(b"p/"));
+        assert!(exe_and_sync(&store, &req, store.revision.next()).is_ok());
+
+        assert!(store
+            .check_op_permission("u", b"m", &[], Type::Read)
+            .is_ok());
+        assert!(store
+            .check_op_permission("u", b"zzz", &[], Type::Read)
+            .is_ok());
+        assert!(store
+            .check_op_permission("u", b"n", &[0], Type::Read)
+            .is_ok());
+        assert!(store
+            .check_op_permission("u", b"l", &[], Type::Read)
+            .is_err());
+        assert!(store
+            .check_op_permission("u", b"l", &[0], Type::Read)
+            .is_err());
+        assert!(store
+            .check_op_permission("u", b"m", &[], Type::Write)
+            .is_err());
+        assert!(store
+            .check_op_permission("u", b"p/", &KeyRange::get_prefix(b"p/"), Type::Write)
+            .is_ok());
+        assert!(store
+            .check_op_permission("u", b"p/a/b", &[], Type::Write)
+            .is_ok());
+        assert!(store
+            .check_op_permission("u", b"p0", &[], Type::Write)
+            .is_err());
+        assert!(store
+            .check_op_permission("u", b"p", &[], Type::Write)
+            .is_err());
+
+        // downgrading the type of a granted permission revokes the write permission
+        let req = grant(Type::Read, b"p/", KeyRange::get_prefix(b"p/"));
+        assert!(exe_and_sync(&store, &req, store.revision.next()).is_ok());
+        assert!(store
+            .check_op_permission("u", b"p/a", &[], Type::Read)
+            .is_ok());
+        assert!(store
+            .check_op_permission("u", b"p/a", &[], Type::Write)
+            .is_err());
+        assert!(store
+            .check_op_permission("u", b"foo", &[], Type::Write)
+            .is_ok());
+
+        Ok(())
+    }
+
     #[test]
     fn test_role_revoke_permission() -> Result<(), ExecuteError> {
         let db = DB::open(&EngineConfig::Memory)?;
@@ -1247,6 +1652,7 @@ mod test {
             PermissionCache {
                 user_permissions: HashMap::from([("u".to_owned(), UserPermissions::new())]),
                 role_to_users_map: HashMap::from([("r".to_owned(), vec!["u".to_owned()])]),
+                user_to_roles_map: HashMap::from([("u".to_owned(), vec!["r".to_owned()])]),
             },
         );
         Ok(())
@@ -1265,6 +1671,7 @@ mod test {
             PermissionCache {
                 user_permissions: HashMap::from([("u".to_owned(), UserPermissions::new(),)]),
                 role_to_users_map: HashMap::new(),
+                user_to_roles_map: HashMap::new(),
             },
         );
         Ok(())
@@ -1283,6 +1690,7 @@ mod test {
             PermissionCache {
                 user_permissions: HashMap::new(),
                 role_to_users_map: HashMap::from([("r".to_owned(), vec![])]),
+                user_to_roles_map: HashMap::new(),
             },
         );
         Ok(())
@@ -1322,6 +1730,11 @@ mod test {
         assert!(exe_and_sync(&store, &enable_req, -1).is_ok());
         assert_eq!(store.revision(), 8);
         assert!(store.is_enabled());
+        // "123" is not a valid password hash
+        assert!(matches!(
+            store.check_password("root", "123"),
+            Err(ExecuteError::AuthFailed)
+        ));
 
         let disable_req = RequestWrapper::from(AuthDisableRequest {});
 
@@ -1330,6 +1743,83 @@ mod test {
         assert!(!store.is_enabled());
     }
 
+    #[test]
+    fn test_rotate_key_pair() -> Result<(), ExecuteError> {
+        let db = DB::open(&EngineConfig::Memory).unwrap();
+        let store = init_empty_store(db);
+        let old_token = store.assign("u")?;
+
+        let (encoding_key, decoding_key) = rotated_key_pair().unwrap();
+        store.rotate_key_pair(encoding_key, decoding_key, vec![])?;
+        let new_token = store.assign("u")?;
+        assert_ne!(old_token, new_token);
+        assert_eq!(store.verify(&old_token)?.username, "u");
+        assert_eq!(store.verify(&new_token)?.username, "u");
+        assert!(store.verify("invalid token").is_err());
+
+        let db = DB::open(&EngineConfig::Memory).unwrap();
+        let other_store = init_empty_store(db);
+        assert!(other_store.verify(&new_token).is_err());
+        let (encoding_key, decoding_key) = test_key_pair().unwrap();
+        let (_, rotated_decoding_key) = rotated_key_pair().unwrap();
+        other_store.rotate_key_pair(encoding_key, decoding_key, vec![rotated_decoding_key])?;
+        assert_eq!(other_store.verify(&new_token)?.username, "u");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_password_rehash_on_authenticate() -> Result<(), ExecuteError> {
+        let db = DB::open(&EngineConfig::Memory).unwrap();
+        let store = init_empty_store(db);
+        let rev_gen = Arc::clone(&store.revision);
+        let legacy_config = PasswordHashConfig::new(64, 1, 1);
+        let config = PasswordHashConfig::default();
+        let requests = [
+            RequestWrapper::from(AuthUserAddRequest {
+                name: "root".to_owned(),
+                password: String::new(),
+                hashed_password: hash_password_with(b"pwd", &legacy_config).unwrap(),
+                options: None,
+            }),
+            RequestWrapper::from(AuthRoleAddRequest {
+                name: "root".to_owned(),
+            }),
+            RequestWrapper::from(AuthUserGrantRoleRequest {
+                user: "root".to_owned(),
+                role: "root".to_owned(),
+            }),
+        ];
+        for req in &requests {
+            let _ignore = exe_and_sync(&store, req, rev_gen.next())?;
+        }
+        let _ignore = exe_and_sync(&store, &RequestWrapper::from(AuthEnableRequest {}), -1)?;
+        assert!(store.password_needs_rehash("root", &config));
+        let revision = store.revision();
+
+        let wrong_password_req = RequestWrapper::from(InternalAuthenticateRequest {
+            name: "root".to_owned(),
+            password: "wrong".to_owned(),
+            hashed_password: hash_password_with(b"wrong", &config).unwrap(),
+        });
+        assert!(exe_and_sync(&store, &wrong_password_req, revision).is_err());
+        let (_, ops) = store.after_sync(&wrong_password_req, revision)?;
+        assert!(ops.is_empty());
+        assert!(store.password_needs_rehash("root", &config));
+
+        let req = RequestWrapper::from(InternalAuthenticateRequest {
+            name: "root".to_owned(),
+            password: "pwd".to_owned(),
+            hashed_password: hash_password_with(b"pwd", &config).unwrap(),
+        });
+        let _ignore = exe_and_sync(&store, &req, revision)?;
+        assert!(!store.password_needs_rehash("root", &config));
+        assert!(store.check_password("root", "pwd").is_ok());
+        assert_eq!(store.revision(), revision);
+
+        Ok(())
+    }
+
     #[test]
     fn test_recover() -> Result<(), ExecuteError> {
         let db = DB::open(&EngineConfig::Memory).unwrap();
@@ -1384,6 +1874,7 @@ mod test {
                     },
                 )]),
                 role_to_users_map: HashMap::from([("r".to_owned(), vec!["u".to_owned()])]),
+                user_to_roles_map: HashMap::from([("u".to_owned(), vec!["r".to_owned()])]),
             },
         );
         store
@@ -1393,7 +1884,15 @@ mod test {
         let key_pair = test_key_pair();
         let header_gen = Arc::new(HeaderGenerator::new(0, 0));
         let lease_collection = Arc::new(LeaseCollection::new(0));
-        AuthStore::new(lease_collection, key_pair, header_gen, db)
+        AuthStore::new(
+            lease_collection,
+            key_pair,
+            vec![],
+            default_auth_token_ttl(),
+            header_gen,
+            db,
+            true,
+        )
     }
 
     fn exe_and_sync(
@@ -1404,6 +1903,7 @@ mod test {
         let cmd_res = store.execute(req)?;
         let (sync_res, ops) = store.after_sync(req, revision)?;
         store.backend.flush_ops(ops)?;
+        store.invalidate_permission_checks();
         Ok((cmd_res, sync_res))
     }
 
@@ -1414,4 +1914,12 @@ mod test {
         let decoding_key = DecodingKey::from_rsa_pem(public_key).ok()?;
         Some((encoding_key, decoding_key))
     }
+
+    fn rotated_key_pair() -> Option<(EncodingKey, DecodingKey)> {
+        let private_key = include_bytes!("../../../../../fixtures/private_rotated.pem");
+        let public_key = include_bytes!("../../../../../fixtures/public_rotated.pem");
+        let encoding_key = EncodingKey::from_rsa_pem(private_key).ok()?;
+        let decoding_key = DecodingKey::from_rsa_pem(public_key).ok()?;
+        Some((encoding_key, decoding_key))
+    }
 }
@@ -0,0 +1,75 @@
+use xlineapi::execute_error::ExecuteError;
+
+/// Key of the marker in the meta table, which is present if the values carry checksums
+pub(crate) const CHECKSUMS_KEY: &[u8] = b"checksums";
+/// Length of a checksum in bytes
+const CHECKSUM_LEN: usize = 4;
+
+/// Append the crc32 checksum of the key and the value to the value
+pub(crate) fn seal(key: &[u8], mut value: Vec<u8>) -> Vec<u8> {
+    let checksum = checksum(key, &value);
+    value.extend_from_slice(&checksum.to_le_bytes());
+    value
+}
+
+/// Remove the checksum from the value, and verify it if `verify` is true
+///
+/// # Errors
+///
+/// Return `ExecuteError::DbError` if the value is too short to carry a checksum, or the
+/// checksum mismatches
+pub(crate) fn unseal(
+    table: &str,
+    key: &[u8],
+    mut value: Vec<u8>,
+    verify: bool,
+) -> Result<Vec<u8>, ExecuteError> {
+    let Some(len) = value.len().checked_sub(CHECKSUM_LEN) else {
+        return Err(corrupted(table, key));
+    };
+    let stored = value.split_off(len);
+    if verify {
+        let mut buf = [0; CHECKSUM_LEN];
+        buf.copy_from_slice(&stored);
+        if u32::from_le_bytes(buf) != checksum(key, &value) {
+            return Err(corrupted(table, key));
+        }
+    }
+    Ok(value)
+}
+
+/// Checksum of a key-value pair, the key is covered so that a value written to a wrong
+/// key is detected as well
+fn checksum(key: &[u8], value: &[u8]) -> u32 {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(key);
+    hasher.update(value);
+    hasher.finalize()
+}
+
+/// Error of a corrupted value
+fn corrupted(table: &str, key: &[u8]) -> ExecuteError {
+    ExecuteError::DbError(format!(
+        "checksum mismatch of key {key:?} in table {table}, the storage may be corrupted"
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn sealed_value_should_be_verified() {
+        let sealed = seal(b"foo", b"bar".to_vec());
+        assert_eq!(unseal("kv", b"foo", sealed.clone(), true).unwrap(), b"bar");
+
+        let mut flipped = sealed.clone();
+        flipped[0] ^= 1;
+        assert!(unseal("kv", b"foo", flipped.clone(), true).is_err());
+        // the checksum is only removed if it is not verified
+        assert_eq!(unseal("kv", b"foo", flipped, false).unwrap(), b"car");
+
+        assert!(unseal("kv", b"baz", sealed, true).is_err());
+        assert!(unseal("kv", b"foo", vec![0; 3], false).is_err());
+    }
+}
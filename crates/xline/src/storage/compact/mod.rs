@@ -6,6 +6,7 @@ use event_listener::Event;
 use periodic_compactor::PeriodicCompactor;
 use revision_compactor::RevisionCompactor;
 use tokio::{sync::mpsc::Receiver, time::sleep};
+use tracing::warn;
 use utils::{
     config::AutoCompactConfig,
     task_manager::{tasks::TaskName, Listener, TaskManager},
@@ -39,6 +40,9 @@ pub(crate) trait Compactor<C: Compactable>: Send + Sync {
     fn resume(&self);
     /// Set compactable
     async fn set_compactable(&self, c: C);
+    /// Update the config of an auto-compactor at runtime, return `false` if the
+    /// new config is of a different mode
+    fn update_config(&self, cfg: AutoCompactConfig) -> bool;
 }
 
 /// `Compactable` trait indicates a method that receives a given revision and proposes a compact proposal
@@ -118,6 +122,10 @@ pub(crate) async fn compact_bg_task<DB>(
             _ = shutdown_listener.wait() => break,
         };
 
+        // the deletions of the range tombstones must be written before they are compacted
+        if let Err(e) = kv_store.write_all_range_tombstones(revision) {
+            panic!("failed to write the range tombstones before compacting {revision} due to {e}");
+        }
         let target_revisions = index
             .compact(revision)
             .into_iter()
@@ -136,5 +144,16 @@ pub(crate) async fn compact_bg_task<DB>(
         if let Some(notifier) = listener {
             let _ignore = notifier.notify(usize::MAX);
         }
+        // values encrypted by retired data keys are re-encrypted progressively as well
+        loop {
+            match kv_store.reencrypt(batch_limit) {
+                Ok(0) => break,
+                Ok(_) => sleep(interval).await,
+                Err(e) => {
+                    warn!("failed to re-encrypt values due to {e}");
+                    break;
+                }
+            }
+        }
     }
 }
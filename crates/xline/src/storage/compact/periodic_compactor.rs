@@ -8,9 +8,10 @@ use std::{
 };
 
 use clippy_utilities::OverflowArithmetic;
+use parking_lot::Mutex;
 use tokio::sync::RwLock;
 use tracing::{info, warn};
-use utils::task_manager::Listener;
+use utils::{config::AutoCompactConfig, task_manager::Listener};
 
 use super::{Compactable, Compactor};
 use crate::revision_number::RevisionNumberGenerator;
@@ -64,7 +65,7 @@ pub(crate) struct PeriodicCompactor<C: Compactable> {
     /// revision getter
     revision_getter: Arc<RevisionNumberGenerator>,
     /// compaction period
-    period: Duration,
+    period: Mutex<Duration>,
 }
 
 impl<C: Compactable> PeriodicCompactor<C> {
@@ -78,7 +79,7 @@ impl<C: Compactable> PeriodicCompactor<C> {
             is_leader: AtomicBool::new(is_leader),
             compactable: RwLock::new(None),
             revision_getter,
-            period,
+            period: Mutex::new(period),
         })
     }
 
@@ -91,16 +92,15 @@ impl<C: Compactable> PeriodicCompactor<C> {
         if !self.is_leader.load(Relaxed) {
             return None;
         }
-        let target_revision = revision_window.expired_revision();
-        if target_revision == last_revision {
-            return None;
-        }
-        let revision =
-            target_revision.unwrap_or_else(|| unreachable!("target revision shouldn't be None"));
+        // skip if the window is not filled yet or the target revision has been compacted
+        let revision = revision_window
+            .expired_revision()
+            .filter(|rev| last_revision.map_or(true, |last| *rev > last))?;
+        let period = *self.period.lock();
         let now = Instant::now();
         info!(
             "starting auto periodic compaction, revision = {}, period = {:?}",
-            revision, self.period
+            revision, period
         );
 
         let Some(ref compactable) = *self.compactable.read().await else {
@@ -110,18 +110,18 @@ impl<C: Compactable> PeriodicCompactor<C> {
         match compactable.compact(revision).await {
             Ok(rev) => {
                 info!(
-                    "completed auto revision compaction, request revision = {}, target revision = {}, period = {:?}, took {:?}",
+                    "completed auto periodic compaction, request revision = {}, target revision = {}, period = {:?}, took {:?}",
                     revision,
                     rev,
-                    self.period,
+                    period,
                     now.elapsed().as_secs()
                 );
                 Some(rev)
             }
             Err(err) => {
                 warn!(
-                    "failed auto revision compaction, revision = {}, period = {:?}, err: {}",
-                    revision, self.period, err
+                    "failed auto periodic compaction, revision = {}, period = {:?}, err: {}",
+                    revision, period, err
                 );
                 None
             }
@@ -156,10 +156,20 @@ impl<C: Compactable> Compactor<C> for PeriodicCompactor<C> {
     #[allow(clippy::arithmetic_side_effects, clippy::ignored_unit_patterns)]
     async fn run(&self, shutdown_listener: Listener) {
         let mut last_revision: Option<i64> = None;
-        let (sample_frequency, sample_total) = sample_config(self.period);
+        let mut period = *self.period.lock();
+        let (sample_frequency, sample_total) = sample_config(period);
         let mut ticker = tokio::time::interval(sample_frequency);
         let mut revision_window = RevisionWindow::new(sample_total);
         loop {
+            let new_period = *self.period.lock();
+            if new_period != period {
+                // restart sampling since the old samples are taken at a different frequency
+                info!("auto periodic compaction period changed from {period:?} to {new_period:?}");
+                period = new_period;
+                let (sample_frequency, sample_total) = sample_config(period);
+                ticker = tokio::time::interval(sample_frequency);
+                revision_window = RevisionWindow::new(sample_total);
+            }
             revision_window.sample(self.revision_getter.get());
             tokio::select! {
                 _ = ticker.tick() => {
@@ -183,6 +193,14 @@ impl<C: Compactable> Compactor<C> for PeriodicCompactor<C> {
     fn resume(&self) {
         self.is_leader.store(true, Relaxed);
     }
+
+    fn update_config(&self, cfg: AutoCompactConfig) -> bool {
+        let AutoCompactConfig::Periodic(period) = cfg else {
+            return false;
+        };
+        *self.period.lock() = period;
+        true
+    }
 }
 
 #[cfg(test)]
@@ -276,5 +294,10 @@ mod test {
             .do_compact(Some(4), &revision_window)
             .await
             .is_none());
+        // auto compactor should skip revisions older than the last compacted one.
+        assert!(periodic_compactor
+            .do_compact(Some(10), &revision_window)
+            .await
+            .is_none());
     }
 }
@@ -1,6 +1,6 @@
 use std::{
     sync::{
-        atomic::{AtomicBool, Ordering::Relaxed},
+        atomic::{AtomicBool, AtomicI64, Ordering::Relaxed},
         Arc,
     },
     time::{Duration, Instant},
@@ -9,7 +9,7 @@ use std::{
 use clippy_utilities::OverflowArithmetic;
 use tokio::sync::RwLock;
 use tracing::{info, warn};
-use utils::task_manager::Listener;
+use utils::{config::AutoCompactConfig, task_manager::Listener};
 
 use super::{Compactable, Compactor};
 use crate::revision_number::RevisionNumberGenerator;
@@ -27,7 +27,7 @@ pub(crate) struct RevisionCompactor<C: Compactable> {
     /// revision getter
     revision_getter: Arc<RevisionNumberGenerator>,
     /// revision retention
-    retention: i64,
+    retention: AtomicI64,
 }
 
 impl<C: Compactable> RevisionCompactor<C> {
@@ -41,7 +41,7 @@ impl<C: Compactable> RevisionCompactor<C> {
             is_leader: AtomicBool::new(is_leader),
             compactable: RwLock::new(None),
             revision_getter,
-            retention,
+            retention: AtomicI64::new(retention),
         })
     }
 
@@ -51,7 +51,8 @@ impl<C: Compactable> RevisionCompactor<C> {
             return None;
         }
 
-        let target_revision = self.revision_getter.get().overflow_sub(self.retention);
+        let retention = self.retention.load(Relaxed);
+        let target_revision = self.revision_getter.get().overflow_sub(retention);
         if target_revision <= 0 || Some(target_revision) <= last_revision {
             return None;
         }
@@ -59,7 +60,7 @@ impl<C: Compactable> RevisionCompactor<C> {
         let now = Instant::now();
         info!(
             "starting auto revision compaction, revision = {}, retention = {}",
-            target_revision, self.retention
+            target_revision, retention
         );
 
         let Some(ref compactable) = *self.compactable.read().await else {
@@ -72,7 +73,7 @@ impl<C: Compactable> RevisionCompactor<C> {
                     "completed auto revision compaction, request revision = {}, target revision = {}, retention = {}, took {:?}",
                     target_revision,
                     rev,
-                    self.retention,
+                    retention,
                     now.elapsed().as_secs()
                 );
                 Some(rev)
@@ -80,7 +81,7 @@ impl<C: Compactable> RevisionCompactor<C> {
             Err(err) => {
                 warn!(
                     "failed auto revision compaction, revision = {}, retention = {}, result: {}",
-                    target_revision, self.retention, err
+                    target_revision, retention, err
                 );
                 None
             }
@@ -117,6 +118,14 @@ impl<C: Compactable> Compactor<C> for RevisionCompactor<C> {
     async fn set_compactable(&self, compactable: C) {
         *self.compactable.write().await = Some(compactable);
     }
+
+    fn update_config(&self, cfg: AutoCompactConfig) -> bool {
+        let AutoCompactConfig::Revision(retention) = cfg else {
+            return false;
+        };
+        self.retention.store(retention, Relaxed);
+        true
+    }
 }
 
 #[cfg(test)]
@@ -145,4 +154,18 @@ mod test {
         // auto compactor should skip those revisions which have been auto compacted.
         assert!(revision_compactor.do_compact(Some(13)).await.is_none());
     }
+
+    #[tokio::test]
+    async fn revision_compactor_should_apply_updated_retention() {
+        let mut compactable = MockCompactable::new();
+        compactable.expect_compact().times(1).returning(Ok);
+        let revision_gen = Arc::new(RevisionNumberGenerator::new(110));
+        let revision_compactor = RevisionCompactor::new_arc(true, Arc::clone(&revision_gen), 100);
+        revision_compactor.set_compactable(compactable).await;
+        assert!(
+            !revision_compactor.update_config(AutoCompactConfig::Periodic(Duration::from_secs(10)))
+        );
+        assert!(revision_compactor.update_config(AutoCompactConfig::Revision(50)));
+        assert_eq!(revision_compactor.do_compact(None).await, Some(60));
+    }
 }
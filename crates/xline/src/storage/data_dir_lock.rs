@@ -0,0 +1,92 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use anyhow::{anyhow, Result};
+use fs2::FileExt;
+use utils::config::EngineConfig;
+
+/// Name of the lock file in the data dir
+const LOCK_FILE: &str = "xline.lock";
+
+/// An exclusive lock on a data dir, which prevents another xline process from opening
+/// the same data dir. The lock is released when it is dropped or the process exits.
+#[derive(Debug)]
+pub(crate) struct DataDirLock {
+    /// The locked file, which holds the pid of the owner
+    _file: File,
+}
+
+impl DataDirLock {
+    /// Lock the data dir of the engine, `None` is returned if the engine is not persistent
+    ///
+    /// # Errors
+    ///
+    /// Return an error if the data dir is locked by another process, or the lock file can
+    /// not be written
+    pub(crate) fn acquire(engine: &EngineConfig) -> Result<Option<Self>> {
+        #[allow(clippy::wildcard_enum_match_arm)]
+        match *engine {
+            EngineConfig::RocksDB(ref path) | EngineConfig::Redb(ref path) => {
+                Self::lock(path).map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Lock the data dir, the data dir is created if it does not exist
+    fn lock(data_dir: &Path) -> Result<Self> {
+        std::fs::create_dir_all(data_dir)?;
+        let path = data_dir.join(LOCK_FILE);
+        let mut file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .read(true)
+            .write(true)
+            .open(&path)?;
+        if file.try_lock_exclusive().is_err() {
+            let mut owner = String::new();
+            let _ignore = file.read_to_string(&mut owner);
+            return Err(locked(data_dir, owner.trim()));
+        }
+        file.set_len(0)?;
+        let _pos = file.seek(SeekFrom::Start(0))?;
+        write!(file, "{}", std::process::id())?;
+        file.sync_all()?;
+        Ok(Self { _file: file })
+    }
+}
+
+/// Error of a data dir locked by another process
+fn locked(data_dir: &Path, owner: &str) -> anyhow::Error {
+    if owner.is_empty() {
+        anyhow!("data dir {data_dir:?} is used by another xline process")
+    } else {
+        anyhow!("data dir {data_dir:?} is used by another xline process (pid {owner})")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn data_dir_should_be_locked_once() {
+        let dir = std::env::temp_dir().join("data_dir_should_be_locked_once");
+        let engine = EngineConfig::RocksDB(dir.clone());
+        let lock = DataDirLock::acquire(&engine).unwrap();
+        assert!(lock.is_some());
+        let err = DataDirLock::acquire(&engine).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains(&format!("pid {}", std::process::id())));
+        drop(lock);
+        assert!(DataDirLock::acquire(&engine).unwrap().is_some());
+        assert!(DataDirLock::acquire(&EngineConfig::Memory)
+            .unwrap()
+            .is_none());
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -1,24 +1,44 @@
-use std::{collections::HashMap, path::Path, sync::Arc};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 
-use engine::{Engine, EngineType, Snapshot, StorageEngine, WriteOperation};
+use engine::{Engine, EngineError, EngineType, Snapshot, StorageEngine, WriteOperation};
+use fail::fail_point;
 use prost::Message;
+use tracing::warn;
 use utils::{
-    config::EngineConfig,
+    config::{ChecksumPolicy, DurabilityPolicy, EncryptionConfig, EngineConfig, StorageConfig},
     table_names::{
         ALARM_TABLE, AUTH_TABLE, KV_TABLE, LEASE_TABLE, META_TABLE, ROLE_TABLE, USER_TABLE,
         XLINE_TABLES,
     },
+    task_manager::Listener,
+    version::Version,
 };
 use xlineapi::{execute_error::ExecuteError, AlarmMember};
 
 use super::{
     auth_store::{AUTH_ENABLE_KEY, AUTH_REVISION_KEY},
+    checksum::{seal, unseal, CHECKSUMS_KEY},
+    encryption::{Encryptor, DATA_KEYS_KEY, REENCRYPT_CURSOR_KEY},
+    index_snapshot::INDEX_SNAPSHOT_KEY,
+    range_tombstone::{RangeTombstone, RANGE_TOMBSTONE_PREFIX},
     revision::KeyRevision,
+    schema::SCHEMA_VERSION_KEY,
     storage_api::StorageApi,
 };
 use crate::{
     rpc::{KeyValue, PbLease, Role, User},
-    server::command::APPLIED_INDEX_KEY,
+    server::{
+        command::{APPLIED_INDEX_KEY, APPLIED_REVISION_KEY, OUT_OF_ORDER_APPLIED_KEY},
+        feature_gate::{DOWNGRADE_TARGET_KEY, NEGOTIATED_VERSION_KEY},
+    },
     storage::Revision,
 };
 
@@ -27,11 +47,31 @@ pub(crate) const FINISHED_COMPACT_REVISION: &str = "finished_compact_revision";
 /// Key of scheduled compact revision
 pub(crate) const SCHEDULED_COMPACT_REVISION: &str = "scheduled_compact_revision";
 
+/// Tables whose values are encrypted when encryption is enabled, the meta table only
+/// holds indexes and the data keys, so it is not encrypted
+const ENCRYPTED_TABLES: [&str; 6] = [
+    KV_TABLE,
+    LEASE_TABLE,
+    AUTH_TABLE,
+    USER_TABLE,
+    ROLE_TABLE,
+    ALARM_TABLE,
+];
+
 /// Database to store revision to kv mapping
 #[derive(Debug)]
 pub struct DB {
     /// internal storage of `DB`
     engine: Arc<Engine>,
+    /// Encrypts the values at rest, `None` if encryption is disabled
+    encryptor: Option<Encryptor>,
+    /// Whether every batch is fsynced, see `DurabilityPolicy::Always`
+    sync_writes: bool,
+    /// Checksum policy of the values
+    checksum_policy: ChecksumPolicy,
+    /// Whether the values carry checksums, which is decided by the storage, see
+    /// `init_checksums`
+    checksums: AtomicBool,
 }
 
 impl DB {
@@ -41,16 +81,260 @@ impl DB {
     /// Return `ExecuteError::DbError` when open db failed
     #[inline]
     pub fn open(config: &EngineConfig) -> Result<Arc<Self>, ExecuteError> {
-        let engine_type = match *config {
-            EngineConfig::Memory => EngineType::Memory,
-            EngineConfig::RocksDB(ref path) => EngineType::Rocks(path.clone()),
-            _ => unreachable!("Not supported storage type"),
-        };
-        let engine = Engine::new(engine_type, &XLINE_TABLES)
+        Self::open_with_encryption(config, None)
+    }
+
+    /// Create a new `DB` whose values are encrypted at rest if `encryption` is given.
+    /// Values written before encryption is enabled are encrypted when the `DB` is opened.
+    ///
+    /// # Errors
+    /// Return `ExecuteError::DbError` when open db failed, the key encryption keys
+    /// can not be loaded, or the db is encrypted but `encryption` is not given
+    #[inline]
+    pub fn open_with_encryption(
+        config: &EngineConfig,
+        encryption: Option<&EncryptionConfig>,
+    ) -> Result<Arc<Self>, ExecuteError> {
+        Self::open_inner(
+            config,
+            encryption,
+            DurabilityPolicy::default(),
+            ChecksumPolicy::default(),
+        )
+    }
+
+    /// Create a new `DB` with the engine, encryption, durability policy and checksum
+    /// policy of the storage config
+    ///
+    /// # Errors
+    /// Return `ExecuteError::DbError` when open db failed, see `open_with_encryption`
+    #[inline]
+    pub fn open_with_storage_config(config: &StorageConfig) -> Result<Arc<Self>, ExecuteError> {
+        Self::open_inner(
+            &config.engine,
+            config.encryption.as_ref(),
+            config.durability,
+            config.checksum,
+        )
+    }
+
+    /// Open the engine, add the checksums and load the data keys
+    fn open_inner(
+        config: &EngineConfig,
+        encryption: Option<&EncryptionConfig>,
+        durability: DurabilityPolicy,
+        checksum_policy: ChecksumPolicy,
+    ) -> Result<Arc<Self>, ExecuteError> {
+        let engine = EngineType::try_from(config)
+            .and_then(|engine_type| Engine::new(engine_type, &XLINE_TABLES))
             .map_err(|e| ExecuteError::DbError(format!("Cannot open database: {e}")))?;
-        Ok(Arc::new(Self {
+        let db = Self {
             engine: Arc::new(engine),
-        }))
+            encryptor: encryption.map(Encryptor::new).transpose()?,
+            sync_writes: matches!(durability, DurabilityPolicy::Always),
+            checksum_policy,
+            checksums: AtomicBool::new(false),
+        };
+        db.init_checksums()?;
+        db.init_encryption()?;
+        Ok(Arc::new(db))
+    }
+
+    /// Check if the values of the storage carry checksums, or add checksums to the
+    /// existing values if the storage has no checksums yet and the checksum policy is
+    /// not `Off`. The checksums are not removed once they are added.
+    fn init_checksums(&self) -> Result<(), ExecuteError> {
+        let stored = self
+            .engine
+            .get(META_TABLE, CHECKSUMS_KEY)
+            .map_err(|e| ExecuteError::DbError(format!("Failed to get checksums marker: {e}")))?
+            .is_some();
+        if stored || matches!(self.checksum_policy, ChecksumPolicy::Off) {
+            self.checksums.store(stored, Ordering::Relaxed);
+            return Ok(());
+        }
+        let mut ops = Vec::new();
+        for table in XLINE_TABLES.into_iter().filter(|&t| t != META_TABLE) {
+            for (key, value) in self.engine.get_all(table).map_err(|e| {
+                ExecuteError::DbError(format!("Failed to get all keys from {table:?}: {e}"))
+            })? {
+                let value = seal(&key, value);
+                ops.push(WriteOperation::new_put(table, key, value));
+            }
+        }
+        ops.push(WriteOperation::new_put(
+            META_TABLE,
+            CHECKSUMS_KEY.to_vec(),
+            vec![1],
+        ));
+        self.engine
+            .write_batch(ops, true)
+            .map_err(|e| ExecuteError::DbError(format!("Failed to add checksums: {e}")))?;
+        self.checksums.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Load the stored data keys, or encrypt the existing values and store the data keys
+    /// if the db is not encrypted yet. Values of small tables that are encrypted by
+    /// retired data keys are re-encrypted at once, values of the kv table are
+    /// re-encrypted lazily by `reencrypt`, which restarts from the first key once a new
+    /// data key is generated.
+    fn init_encryption(&self) -> Result<(), ExecuteError> {
+        let stored = self
+            .engine
+            .get(META_TABLE, DATA_KEYS_KEY)
+            .map_err(|e| ExecuteError::DbError(format!("Failed to get data keys: {e}")))?;
+        let Some(ref encryptor) = self.encryptor else {
+            if stored.is_some() {
+                return Err(ExecuteError::DbError(
+                    "Database is encrypted, but encryption is not configured".to_owned(),
+                ));
+            }
+            return Ok(());
+        };
+        let mut ops = Vec::new();
+        if let Some(stored) = stored {
+            let rotated = encryptor.load_data_keys(&stored)?;
+            if encryptor.has_retired_keys() {
+                for table in ENCRYPTED_TABLES.into_iter().filter(|&t| t != KV_TABLE) {
+                    let kvs = self.engine.get_all(table).map_err(|e| {
+                        ExecuteError::DbError(format!("Failed to get all keys from {table:?}: {e}"))
+                    })?;
+                    ops.extend(self.reencrypt_ops(encryptor, table, kvs)?);
+                }
+            }
+            if !rotated && ops.is_empty() {
+                return Ok(());
+            }
+            if rotated {
+                ops.push(WriteOperation::new_delete(META_TABLE, REENCRYPT_CURSOR_KEY));
+            }
+        } else {
+            for table in ENCRYPTED_TABLES {
+                for (key, value) in self.raw_get_all(table)? {
+                    let value = encryptor.encrypt(table, &key, &value)?;
+                    ops.push(WriteOperation::new_put(table, key, value));
+                }
+            }
+        }
+        ops.push(WriteOperation::new_put(
+            META_TABLE,
+            DATA_KEYS_KEY.to_vec(),
+            encryptor.encode_data_keys()?,
+        ));
+        self.write_engine(ops, true)
+            .map_err(|e| ExecuteError::DbError(format!("Failed to encrypt database: {e}")))
+    }
+
+    /// Get the operations that re-encrypt the values of the table that are encrypted by
+    /// retired data keys among the given key-value pairs
+    fn reencrypt_ops(
+        &self,
+        encryptor: &Encryptor,
+        table: &'static str,
+        kvs: Vec<(Vec<u8>, Vec<u8>)>,
+    ) -> Result<Vec<WriteOperation<'static>>, ExecuteError> {
+        kvs.into_iter()
+            .map(|(key, value)| {
+                let value = self.unseal(table, &key, value)?;
+                Ok((key, value))
+            })
+            .collect::<Result<Vec<_>, ExecuteError>>()?
+            .into_iter()
+            .filter(|&(_, ref value)| encryptor.is_stale(value))
+            .map(|(key, value)| {
+                let value = encryptor.decrypt(table, &key, &value)?;
+                let value = encryptor.encrypt(table, &key, &value)?;
+                Ok(WriteOperation::new_put(table, key, value))
+            })
+            .collect()
+    }
+
+    /// Get all key-value pairs of the table without decrypting them, the checksums are
+    /// removed
+    fn raw_get_all(&self, table: &'static str) -> Result<Vec<(Vec<u8>, Vec<u8>)>, ExecuteError> {
+        self.engine
+            .get_all(table)
+            .map_err(|e| {
+                ExecuteError::DbError(format!("Failed to get all keys from {table:?}: {e}"))
+            })?
+            .into_iter()
+            .map(|(key, value)| {
+                let value = self.unseal(table, &key, value)?;
+                Ok((key, value))
+            })
+            .collect()
+    }
+
+    /// Remove the checksum from a value of the key in the table if the values carry
+    /// checksums, the checksum is verified if the checksum policy is `Verify`
+    fn unseal(&self, table: &str, key: &[u8], value: Vec<u8>) -> Result<Vec<u8>, ExecuteError> {
+        if table == META_TABLE || !self.checksums.load(Ordering::Relaxed) {
+            return Ok(value);
+        }
+        let verify = matches!(self.checksum_policy, ChecksumPolicy::Verify);
+        unseal(table, key, value, verify)
+    }
+
+    /// Write the operations to the engine, checksums are added to the values of the put
+    /// operations if the values carry checksums
+    fn write_engine(&self, ops: Vec<WriteOperation<'_>>, sync: bool) -> Result<(), EngineError> {
+        self.engine.write_batch(self.seal_ops(ops), sync)
+    }
+
+    /// Add checksums to the values of the put operations if the values carry checksums
+    fn seal_ops<'a>(&self, ops: Vec<WriteOperation<'a>>) -> Vec<WriteOperation<'a>> {
+        if !self.checksums.load(Ordering::Relaxed) {
+            return ops;
+        }
+        ops.into_iter()
+            .map(|op| {
+                #[allow(clippy::wildcard_enum_match_arm)]
+                match op {
+                    WriteOperation::Put { table, key, value } if table != META_TABLE => {
+                        let value = seal(&key, value);
+                        WriteOperation::new_put(table, key, value)
+                    }
+                    op => op,
+                }
+            })
+            .collect()
+    }
+
+    /// Remove the checksum and decrypt a value of the key in the table
+    fn open_value(&self, table: &str, key: &[u8], value: Vec<u8>) -> Result<Vec<u8>, ExecuteError> {
+        let value = self.unseal(table, key, value)?;
+        self.decrypt(table, key, value)
+    }
+
+    /// Decrypt a value of the key in the table if it is encrypted
+    fn decrypt(&self, table: &str, key: &[u8], value: Vec<u8>) -> Result<Vec<u8>, ExecuteError> {
+        match self.encryptor {
+            Some(ref encryptor) if table != META_TABLE => encryptor.decrypt(table, key, &value),
+            _ => Ok(value),
+        }
+    }
+
+    /// Encrypt the values of put operations if encryption is enabled
+    fn encrypt_ops<'a>(
+        &self,
+        ops: Vec<WriteOperation<'a>>,
+    ) -> Result<Vec<WriteOperation<'a>>, ExecuteError> {
+        let Some(ref encryptor) = self.encryptor else {
+            return Ok(ops);
+        };
+        ops.into_iter()
+            .map(|op| {
+                #[allow(clippy::wildcard_enum_match_arm)]
+                match op {
+                    WriteOperation::Put { table, key, value } if table != META_TABLE => {
+                        let value = encryptor.encrypt(table, &key, &value)?;
+                        Ok(WriteOperation::new_put(table, key, value))
+                    }
+                    op => Ok(op),
+                }
+            })
+            .collect()
     }
 
     /// Get del lease key buffer
@@ -67,6 +351,20 @@ impl DB {
             .collect::<HashMap<_, _>>()
     }
 
+    /// get del range tombstone key buffer
+    #[inline]
+    fn get_del_range_tombstone_buffer(ops: &[WriteOp]) -> HashMap<Revision, Vec<u8>> {
+        ops.iter()
+            .filter_map(|op| {
+                if let WriteOp::DeleteRangeTombstone(revision) = *op {
+                    Some((revision, RangeTombstone::meta_key(revision)))
+                } else {
+                    None
+                }
+            })
+            .collect::<HashMap<_, _>>()
+    }
+
     /// get del alarm buffer
     #[inline]
     fn get_del_alarm_buffer(ops: &[WriteOp]) -> Vec<u8> {
@@ -100,7 +398,15 @@ impl StorageApi for DB {
 
         assert_eq!(values.len(), keys.len(), "Index doesn't match with DB");
 
-        Ok(values)
+        values
+            .into_iter()
+            .zip(keys)
+            .map(|(value, key)| {
+                value
+                    .map(|v| self.open_value(table, key.as_ref(), v))
+                    .transpose()
+            })
+            .collect()
     }
 
     fn get_value<K>(&self, table: &'static str, key: K) -> Result<Option<Vec<u8>>, ExecuteError>
@@ -109,13 +415,37 @@ impl StorageApi for DB {
     {
         self.engine
             .get(table, key.as_ref())
-            .map_err(|e| ExecuteError::DbError(format!("Failed to get key {key:?}: {e}")))
+            .map_err(|e| ExecuteError::DbError(format!("Failed to get key {key:?}: {e}")))?
+            .map(|value| self.open_value(table, key.as_ref(), value))
+            .transpose()
     }
 
     fn get_all(&self, table: &'static str) -> Result<Vec<(Vec<u8>, Vec<u8>)>, ExecuteError> {
-        self.engine.get_all(table).map_err(|e| {
-            ExecuteError::DbError(format!("Failed to get all keys from {table:?}: {e}"))
-        })
+        self.raw_get_all(table)?
+            .into_iter()
+            .filter(|&(ref key, _)| !is_local_meta(table, key))
+            .map(|(key, value)| {
+                let value = self.decrypt(table, &key, value)?;
+                Ok((key, value))
+            })
+            .collect()
+    }
+
+    fn get_from(
+        &self,
+        table: &'static str,
+        from: &[u8],
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>, ExecuteError> {
+        self.engine
+            .get_from(table, from, usize::MAX)
+            .map_err(|e| ExecuteError::DbError(format!("Failed to get keys from {table:?}: {e}")))?
+            .into_iter()
+            .filter(|&(ref key, _)| !is_local_meta(table, key))
+            .map(|(key, value)| {
+                let value = self.open_value(table, &key, value)?;
+                Ok((key, value))
+            })
+            .collect()
     }
 
     fn get_snapshot(&self, snap_path: impl AsRef<Path>) -> Result<Snapshot, ExecuteError> {
@@ -129,7 +459,9 @@ impl StorageApi for DB {
             self.engine
                 .apply_snapshot(snap, &XLINE_TABLES)
                 .await
-                .map_err(|e| ExecuteError::DbError(format!("Failed to reset database, error: {e}")))
+                .map_err(|e| {
+                    ExecuteError::DbError(format!("Failed to reset database, error: {e}"))
+                })?;
         } else {
             let start = vec![];
             let end = vec![0xff];
@@ -139,17 +471,39 @@ impl StorageApi for DB {
                     WriteOperation::new_delete_range(table, start.as_slice(), end.as_slice())
                 })
                 .collect();
-            self.engine
-                .write_batch(ops, true)
-                .map_err(|e| ExecuteError::DbError(format!("Failed to reset database, error: {e}")))
+            self.engine.write_batch(ops, true).map_err(|e| {
+                ExecuteError::DbError(format!("Failed to reset database, error: {e}"))
+            })?;
         }
+        // the index snapshot does not match the in-memory index any more, the index is
+        // rebuilt from the kv table on the next start
+        self.engine
+            .write_batch(
+                vec![WriteOperation::new_delete(
+                    META_TABLE,
+                    INDEX_SNAPSHOT_KEY.as_bytes(),
+                )],
+                true,
+            )
+            .map_err(|e| ExecuteError::DbError(format!("Failed to reset database, error: {e}")))?;
+        // the checksums marker and the data keys are replaced by the ones in the
+        // snapshot, or removed. The data keys of a snapshot are wrapped by the key
+        // encryption key of the member that took it, they can't be re-encrypted here, so
+        // all members must share the key encryption key, the current or a previous one.
+        self.init_checksums()?;
+        self.init_encryption().map_err(|e| {
+            ExecuteError::DbError(format!(
+                "Failed to reset encryption, members must share the key encryption key: {e}"
+            ))
+        })
     }
 
-    fn flush_ops(&self, ops: Vec<WriteOp>) -> Result<Vec<(Vec<u8>, KeyRevision)>, ExecuteError> {
+    fn encode_batch(&self, ops: Vec<WriteOp>) -> Result<EncodedBatch, ExecuteError> {
         let mut wr_ops = Vec::new();
         let mut revs = Vec::new();
         let del_lease_key_buffer = Self::get_del_lease_key_buffer(&ops);
         let del_alarm_buffer = Self::get_del_alarm_buffer(&ops);
+        let del_range_tombstone_buffer = Self::get_del_range_tombstone_buffer(&ops);
         for op in ops {
             let wop = match op {
                 WriteOp::PutKeyValue(rev, value) => {
@@ -170,6 +524,60 @@ impl StorageApi for DB {
                     APPLIED_INDEX_KEY.as_bytes().to_vec(),
                     index.to_le_bytes().to_vec(),
                 ),
+                WriteOp::PutOutOfOrderApplied(indexes) => WriteOperation::new_put(
+                    META_TABLE,
+                    OUT_OF_ORDER_APPLIED_KEY.as_bytes().to_vec(),
+                    indexes
+                        .iter()
+                        .flat_map(|&(index, revision)| {
+                            index
+                                .to_le_bytes()
+                                .into_iter()
+                                .chain(revision.to_le_bytes())
+                        })
+                        .collect(),
+                ),
+                WriteOp::PutAppliedRevision(revision) => WriteOperation::new_put(
+                    META_TABLE,
+                    APPLIED_REVISION_KEY.as_bytes().to_vec(),
+                    revision.to_le_bytes().to_vec(),
+                ),
+                WriteOp::PutSchemaVersion(version) => WriteOperation::new_put(
+                    META_TABLE,
+                    SCHEMA_VERSION_KEY.as_bytes().to_vec(),
+                    version.to_le_bytes().to_vec(),
+                ),
+                WriteOp::PutNegotiatedVersion(version) => WriteOperation::new_put(
+                    META_TABLE,
+                    NEGOTIATED_VERSION_KEY.as_bytes().to_vec(),
+                    version.to_string().into_bytes(),
+                ),
+                WriteOp::PutDowngradeTarget(Some(target)) => WriteOperation::new_put(
+                    META_TABLE,
+                    DOWNGRADE_TARGET_KEY.as_bytes().to_vec(),
+                    target.to_string().into_bytes(),
+                ),
+                WriteOp::PutDowngradeTarget(None) => {
+                    WriteOperation::new_delete(META_TABLE, DOWNGRADE_TARGET_KEY.as_bytes())
+                }
+                WriteOp::PutIndexSnapshot(snapshot) => WriteOperation::new_put(
+                    META_TABLE,
+                    INDEX_SNAPSHOT_KEY.as_bytes().to_vec(),
+                    snapshot,
+                ),
+                WriteOp::PutRangeTombstone(revision, tombstone) => WriteOperation::new_put(
+                    META_TABLE,
+                    RangeTombstone::meta_key(revision),
+                    tombstone,
+                ),
+                WriteOp::DeleteRangeTombstone(revision) => {
+                    let key = del_range_tombstone_buffer
+                        .get(&revision)
+                        .unwrap_or_else(|| {
+                            panic!("revision({revision:?}) is not in del_range_tombstone_buffer")
+                        });
+                    WriteOperation::new_delete(META_TABLE, key)
+                }
                 WriteOp::PutLease(lease) => WriteOperation::new_put(
                     LEASE_TABLE,
                     lease.id.encode_to_vec(),
@@ -226,20 +634,70 @@ impl StorageApi for DB {
             };
             wr_ops.push(wop);
         }
+        let ops = self
+            .seal_ops(self.encrypt_ops(wr_ops)?)
+            .into_iter()
+            .map(|op| {
+                #[allow(clippy::wildcard_enum_match_arm)]
+                match op {
+                    WriteOperation::Put { table, key, value } => {
+                        EncodedOp::Put(static_table(table), key, value)
+                    }
+                    WriteOperation::Delete { table, key } => {
+                        EncodedOp::Delete(static_table(table), key.to_vec())
+                    }
+                    _ => unreachable!("only puts and deletes are encoded"),
+                }
+            })
+            .collect();
+        Ok(EncodedBatch {
+            ops,
+            revisions: revs,
+        })
+    }
+
+    fn write_encoded(
+        &self,
+        mut batches: Vec<EncodedBatch>,
+        sync: bool,
+    ) -> Result<(), ExecuteError> {
+        let mut ops = Vec::new();
+        for op in batches.iter_mut().flat_map(|batch| batch.ops.iter_mut()) {
+            ops.push(match *op {
+                EncodedOp::Put(table, ref mut key, ref mut value) => {
+                    WriteOperation::new_put(table, std::mem::take(key), std::mem::take(value))
+                }
+                EncodedOp::Delete(table, ref key) => WriteOperation::new_delete(table, key),
+            });
+        }
+        fail_point!("before_backend_write", |_| Err(ExecuteError::DbError(
+            "failpoint before_backend_write is triggered".to_owned()
+        )));
         self.engine
-            .write_batch(wr_ops, false)
-            .map_err(|e| ExecuteError::DbError(format!("Failed to flush ops, error: {e}")))?;
-        Ok(revs)
+            .write_batch(ops, sync || self.sync_writes)
+            .map_err(|e| ExecuteError::DbError(format!("Failed to flush ops, error: {e}")))
     }
 
-    fn hash(&self) -> Result<u32, ExecuteError> {
+    fn hash(&self, pending_kvs: Vec<(Vec<u8>, Vec<u8>)>) -> Result<u32, ExecuteError> {
         let mut hasher = crc32fast::Hasher::new();
+        let mut pending_kvs = Some(pending_kvs);
         for table in XLINE_TABLES {
             hasher.update(table.as_bytes());
-            let kv_pairs = self.engine.get_all(table).map_err(|e| {
-                ExecuteError::DbError(format!("Failed to get all keys from {table:?}: {e}"))
-            })?;
-            for (k, v) in kv_pairs {
+            // values are hashed in plaintext, so the hash doesn't depend on encryption
+            let kvs: BTreeMap<_, _> = if table == KV_TABLE {
+                self.get_all(table)?
+                    .into_iter()
+                    .chain(pending_kvs.take().unwrap_or_default())
+                    .collect()
+            } else {
+                self.get_all(table)?
+                    .into_iter()
+                    .filter(|&(ref key, _)| {
+                        table != META_TABLE || !key.starts_with(RANGE_TOMBSTONE_PREFIX)
+                    })
+                    .collect()
+            };
+            for (k, v) in kvs {
                 hasher.update(&k);
                 hasher.update(&v);
             }
@@ -247,6 +705,53 @@ impl StorageApi for DB {
         Ok(hasher.finalize())
     }
 
+    fn reencrypt(&self, limit: usize) -> Result<usize, ExecuteError> {
+        let Some(ref encryptor) = self.encryptor else {
+            return Ok(0);
+        };
+        if !encryptor.has_retired_keys() {
+            return Ok(0);
+        }
+        // keys before the cursor are re-encrypted already, and new values are always
+        // encrypted by the active data key, so every batch resumes from the cursor
+        let from = self
+            .engine
+            .get(META_TABLE, REENCRYPT_CURSOR_KEY)
+            .map_err(|e| ExecuteError::DbError(format!("Failed to get re-encrypt cursor: {e}")))?
+            .unwrap_or_default();
+        let kvs = self
+            .engine
+            .get_from(KV_TABLE, &from, limit)
+            .map_err(|e| ExecuteError::DbError(format!("Failed to get keys from kv: {e}")))?;
+        let checked = kvs.len();
+        let Some(mut cursor) = kvs.last().map(|&(ref key, _)| key.clone()) else {
+            // values of the other tables were re-encrypted when the data keys are loaded
+            encryptor.drop_retired_keys();
+            let ops = vec![
+                WriteOperation::new_put(
+                    META_TABLE,
+                    DATA_KEYS_KEY.to_vec(),
+                    encryptor.encode_data_keys()?,
+                ),
+                WriteOperation::new_delete(META_TABLE, REENCRYPT_CURSOR_KEY),
+            ];
+            self.write_engine(ops, true)
+                .map_err(|e| ExecuteError::DbError(format!("Failed to re-encrypt values: {e}")))?;
+            return Ok(0);
+        };
+        let mut ops = self.reencrypt_ops(encryptor, KV_TABLE, kvs)?;
+        // the cursor is the successor of the last checked key
+        cursor.push(0);
+        ops.push(WriteOperation::new_put(
+            META_TABLE,
+            REENCRYPT_CURSOR_KEY.to_vec(),
+            cursor,
+        ));
+        self.write_engine(ops, false)
+            .map_err(|e| ExecuteError::DbError(format!("Failed to re-encrypt values: {e}")))?;
+        Ok(checked)
+    }
+
     fn estimated_file_size(&self) -> u64 {
         self.engine.estimated_file_size()
     }
@@ -256,6 +761,30 @@ impl StorageApi for DB {
             .file_size()
             .map_err(|e| ExecuteError::DbError(format!("Failed to get file size, error: {e}")))
     }
+
+    fn live_data_size(&self) -> Result<u64, ExecuteError> {
+        self.engine
+            .live_data_size()
+            .map_err(|e| ExecuteError::DbError(format!("Failed to get live data size, error: {e}")))
+    }
+
+    fn defragment(&self) -> Result<(), ExecuteError> {
+        self.engine
+            .defragment()
+            .map_err(|e| ExecuteError::DbError(format!("Failed to defragment, error: {e}")))
+    }
+
+    fn sync(&self) -> Result<(), ExecuteError> {
+        self.engine
+            .sync()
+            .map_err(|e| ExecuteError::DbError(format!("Failed to sync database, error: {e}")))
+    }
+
+    fn table_size(&self, table: &'static str) -> Result<u64, ExecuteError> {
+        self.engine.table_size(table).map_err(|e| {
+            ExecuteError::DbError(format!("Failed to get size of {table:?}, error: {e}"))
+        })
+    }
 }
 
 /// Buffered Write Operation
@@ -266,6 +795,24 @@ pub enum WriteOp<'a> {
     PutKeyValue(Revision, KeyValue),
     /// Put the applied index to meta table
     PutAppliedIndex(u64),
+    /// Put the indexes applied out of order, which are above the applied index, and
+    /// their general revisions to meta table
+    PutOutOfOrderApplied(Vec<(u64, i64)>),
+    /// Put the general revision at the applied index to meta table
+    PutAppliedRevision(i64),
+    /// Put the schema version of the storage to meta table
+    PutSchemaVersion(u32),
+    /// Put the version negotiated by the cluster to meta table
+    PutNegotiatedVersion(Version),
+    /// Put the target version of the downgrade in progress to meta table, or delete it
+    /// if there is none
+    PutDowngradeTarget(Option<Version>),
+    /// Put an encoded index snapshot to meta table
+    PutIndexSnapshot(Vec<u8>),
+    /// Put an encoded range tombstone of the given revision to meta table
+    PutRangeTombstone(Revision, Vec<u8>),
+    /// Delete the range tombstone of the given revision from meta table
+    DeleteRangeTombstone(Revision),
     /// Put a lease to lease table
     PutLease(PbLease),
     /// Put a finished compact revision into meta table
@@ -294,6 +841,74 @@ pub enum WriteOp<'a> {
     DeleteAlarm(AlarmMember),
 }
 
+/// A batch of write operations that is encoded, encrypted and sealed ahead of the write
+/// by `StorageApi::encode_batch`, and written by `StorageApi::write_encoded`
+#[derive(Debug, Default)]
+pub struct EncodedBatch {
+    /// The encoded operations
+    ops: Vec<EncodedOp>,
+    /// Revisions of the keys written by the batch
+    revisions: Vec<(Vec<u8>, KeyRevision)>,
+}
+
+impl EncodedBatch {
+    /// Take the revisions of the keys written by the batch
+    #[inline]
+    pub fn take_revisions(&mut self) -> Vec<(Vec<u8>, KeyRevision)> {
+        std::mem::take(&mut self.revisions)
+    }
+}
+
+/// An encoded write operation
+#[derive(Debug)]
+enum EncodedOp {
+    /// Put the value to the key of the table
+    Put(&'static str, Vec<u8>, Vec<u8>),
+    /// Delete the key from the table
+    Delete(&'static str, Vec<u8>),
+}
+
+/// Get the static name of a table
+fn static_table(table: &str) -> &'static str {
+    XLINE_TABLES
+        .into_iter()
+        .find(|&name| name == table)
+        .unwrap_or_else(|| unreachable!("unknown table {table}"))
+}
+
+/// Check if the key of the table is a meta key that depends on the local member, such as
+/// the data keys, the checksums marker and the index snapshot, which are not returned by
+/// `get_all` so that the hash of the storage is the same on all members
+fn is_local_meta(table: &str, key: &[u8]) -> bool {
+    table == META_TABLE
+        && (key == DATA_KEYS_KEY
+            || key == REENCRYPT_CURSOR_KEY
+            || key == CHECKSUMS_KEY
+            || key == INDEX_SNAPSHOT_KEY.as_bytes())
+}
+
+/// Background task that fsyncs the storage at the given interval, which is used by
+/// `DurabilityPolicy::Periodic`. The storage is fsynced once more when shutting down.
+#[allow(clippy::arithmetic_side_effects, clippy::ignored_unit_patterns)] // introduced by tokio::select! macro
+pub(crate) async fn sync_bg_task<S: StorageApi>(
+    persistent: Arc<S>,
+    interval: Duration,
+    shutdown_listener: Listener,
+) {
+    loop {
+        let shutdown = tokio::select! {
+            _ = tokio::time::sleep(interval) => false,
+            _ = shutdown_listener.wait() => true,
+        };
+        if let Err(e) = persistent.sync() {
+            warn!("failed to sync the storage due to {e}");
+        }
+        if shutdown {
+            break;
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use std::path::PathBuf;
@@ -316,7 +931,7 @@ mod test {
             ..Default::default()
         };
         let ops = vec![WriteOp::PutKeyValue(revision, kv.clone())];
-        _ = db.flush_ops(ops)?;
+        _ = db.write_batch(ops, false)?;
         let res = db.get_value(KV_TABLE, &key)?;
         assert_eq!(res, Some(kv.encode_to_vec()));
 
@@ -347,7 +962,7 @@ mod test {
             ..Default::default()
         };
         let ops = vec![WriteOp::PutKeyValue(revision, kv.clone())];
-        _ = origin_db.flush_ops(ops)?;
+        _ = origin_db.write_batch(ops, false)?;
 
         let snapshot = origin_db.get_snapshot(snapshot_path)?;
 
@@ -428,7 +1043,7 @@ mod test {
             WriteOp::PutUser(user),
             WriteOp::PutRole(role),
         ];
-        _ = db.flush_ops(write_ops).unwrap();
+        _ = db.write_batch(write_ops, false).unwrap();
         assert_eq!(
             db.get_value(KV_TABLE, Revision::new(1, 2).encode_to_vec())
                 .unwrap(),
@@ -458,7 +1073,7 @@ mod test {
             WriteOp::DeleteUser("user"),
             WriteOp::DeleteRole("role"),
         ];
-        _ = db.flush_ops(del_ops).unwrap();
+        _ = db.write_batch(del_ops, false).unwrap();
         assert_eq!(
             db.get_value(LEASE_TABLE, 1i64.encode_to_vec()).unwrap(),
             None
@@ -466,4 +1081,211 @@ mod test {
         assert_eq!(db.get_value(USER_TABLE, b"user").unwrap(), None);
         assert_eq!(db.get_value(ROLE_TABLE, b"role").unwrap(), None);
     }
+
+    #[test]
+    fn test_durability_policy() -> Result<(), ExecuteError> {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let dir = tmp_dir.path();
+        for (i, durability) in [
+            DurabilityPolicy::Always,
+            DurabilityPolicy::Periodic(Duration::from_millis(10)),
+            DurabilityPolicy::Commit,
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let engine = EngineConfig::RocksDB(dir.join(i.to_string()));
+            let mut config = StorageConfig::default();
+            config.engine = engine.clone();
+            config.durability = durability;
+            let db = DB::open_with_storage_config(&config)?;
+            _ = db.write_batch(vec![WriteOp::PutAppliedIndex(1)], false)?;
+            db.sync()?;
+            drop(db);
+            let db = DB::open(&engine)?;
+            assert_eq!(
+                db.get_value(META_TABLE, b"applied_index")?,
+                Some(1u64.to_le_bytes().to_vec())
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_batch_returns_key_revisions() -> Result<(), ExecuteError> {
+        let db = DB::open(&EngineConfig::Memory)?;
+        // the puts of a txn share the revision and have increasing sub revisions
+        let ops = (0..3)
+            .map(|i| {
+                let kv = KeyValue {
+                    key: format!("key{i}").into_bytes(),
+                    value: b"value".to_vec(),
+                    create_revision: 2,
+                    mod_revision: 2,
+                    version: 1,
+                    lease: 0,
+                };
+                WriteOp::PutKeyValue(Revision::new(2, i), kv)
+            })
+            .chain([WriteOp::PutAppliedIndex(3)])
+            .collect();
+        let key_revisions = db.write_batch(ops, true)?;
+        assert_eq!(
+            key_revisions,
+            (0..3)
+                .map(|i| (format!("key{i}").into_bytes(), KeyRevision::new(2, 1, 2, i)))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(db.get_all(KV_TABLE)?.len(), 3);
+        assert_eq!(
+            db.get_value(META_TABLE, b"applied_index")?,
+            Some(3u64.to_le_bytes().to_vec())
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_encoded_batches_in_order() -> Result<(), ExecuteError> {
+        let db = DB::open(&EngineConfig::Memory)?;
+        let put = |value: &str| {
+            let kv = KeyValue {
+                key: b"key".to_vec(),
+                value: value.as_bytes().to_vec(),
+                ..KeyValue::default()
+            };
+            vec![WriteOp::PutKeyValue(Revision::new(1, 0), kv)]
+        };
+        let mut first = db.encode_batch(put("first"))?;
+        let mut second = db.encode_batch(put("second"))?;
+        assert_eq!(first.take_revisions().len(), 1);
+        assert_eq!(second.take_revisions().len(), 1);
+        assert!(db.get_all(KV_TABLE)?.is_empty());
+
+        db.write_encoded(vec![first, second], false)?;
+        let kvs = db.get_all(KV_TABLE)?;
+        assert_eq!(kvs.len(), 1);
+        assert_eq!(
+            KeyValue::decode(kvs[0].1.as_slice()).unwrap().value,
+            b"second"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_encryption_at_rest() -> Result<(), ExecuteError> {
+        use base64::{engine::general_purpose::STANDARD, Engine as _};
+        use utils::config::KeySource;
+
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let dir = tmp_dir.path();
+        let db_path = EngineConfig::RocksDB(dir.join("db"));
+        let kek_file = |name: &str, byte: u8| {
+            let path = dir.join(name);
+            std::fs::write(&path, STANDARD.encode([byte; 32])).unwrap();
+            KeySource::File { path }
+        };
+        let old_kek = kek_file("old", 1);
+        let new_kek = kek_file("new", 2);
+        let put = |db: &DB, rev: i64| -> Result<Vec<u8>, ExecuteError> {
+            let revision = Revision::new(rev, 0);
+            let kv = KeyValue {
+                key: format!("key{rev}").into_bytes(),
+                value: b"secret".to_vec(),
+                ..Default::default()
+            };
+            _ = db.write_batch(vec![WriteOp::PutKeyValue(revision, kv.clone())], false)?;
+            assert_eq!(
+                db.get_value(KV_TABLE, revision.encode_to_vec())?,
+                Some(kv.encode_to_vec())
+            );
+            Ok(revision.encode_to_vec())
+        };
+
+        // values written before encryption is enabled are encrypted when opened
+        let db = DB::open(&db_path)?;
+        let rev1 = put(&db, 1)?;
+        let hash = db.hash(vec![])?;
+        drop(db);
+        let config = EncryptionConfig::new(old_kek.clone(), vec![]);
+        let db = DB::open_with_encryption(&db_path, Some(&config))?;
+        let raw = db.engine.get(KV_TABLE, &rev1).unwrap().unwrap();
+        assert!(!raw.windows(6).any(|w| w == b"secret"));
+        assert_eq!(db.hash(vec![])?, hash);
+        let rev3 = put(&db, 3)?;
+        drop(db);
+        assert!(DB::open(&db_path).is_err());
+
+        // values are re-encrypted by the new data key after the kek is rotated
+        let config = EncryptionConfig::new(new_kek.clone(), vec![old_kek]);
+        let db = DB::open_with_encryption(&db_path, Some(&config))?;
+        let rev2 = put(&db, 2)?;
+        // every batch checks at most `limit` values and resumes after the last checked key
+        assert_eq!(db.reencrypt(1)?, 1);
+        let mut cursor = rev1.clone();
+        cursor.push(0);
+        assert_eq!(
+            db.engine.get(META_TABLE, REENCRYPT_CURSOR_KEY).unwrap(),
+            Some(cursor)
+        );
+        assert_eq!(db.reencrypt(2)?, 2);
+        assert_eq!(db.reencrypt(2)?, 0);
+        assert!(db
+            .engine
+            .get(META_TABLE, REENCRYPT_CURSOR_KEY)
+            .unwrap()
+            .is_none());
+        drop(db);
+        let config = EncryptionConfig::new(new_kek, vec![]);
+        let db = DB::open_with_encryption(&db_path, Some(&config))?;
+        assert!(db.get_value(KV_TABLE, &rev1)?.is_some());
+        assert!(db.get_value(KV_TABLE, &rev2)?.is_some());
+        assert!(db.get_value(KV_TABLE, &rev3)?.is_some());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_checksums() -> Result<(), ExecuteError> {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let dir = tmp_dir.path();
+        let engine = EngineConfig::RocksDB(dir.to_path_buf());
+        let revision = Revision::new(1, 0);
+        let key = revision.encode_to_vec();
+        let kv = KeyValue {
+            key: b"foo".to_vec(),
+            value: b"bar".to_vec(),
+            ..Default::default()
+        };
+
+        // checksums are added to the values written before the policy is enabled
+        let db = DB::open(&engine)?;
+        _ = db.write_batch(vec![WriteOp::PutKeyValue(revision, kv.clone())], false)?;
+        let hash = db.hash(vec![])?;
+        drop(db);
+        let mut config = StorageConfig::default();
+        config.engine = engine.clone();
+        config.checksum = ChecksumPolicy::Verify;
+        let db = DB::open_with_storage_config(&config)?;
+        assert_eq!(db.get_value(KV_TABLE, &key)?, Some(kv.encode_to_vec()));
+        assert_eq!(db.hash(vec![])?, hash);
+
+        // a corrupted value is detected
+        let mut raw = db.engine.get(KV_TABLE, &key).unwrap().unwrap();
+        raw[0] ^= 1;
+        db.engine
+            .write_batch(
+                vec![WriteOperation::new_put(KV_TABLE, key.clone(), raw)],
+                true,
+            )
+            .unwrap();
+        assert!(db.get_value(KV_TABLE, &key).is_err());
+        drop(db);
+
+        // the checksums are kept when the policy is disabled, but not verified
+        let db = DB::open(&engine)?;
+        assert!(db.get_value(KV_TABLE, &key)?.is_some());
+        drop(db);
+
+        Ok(())
+    }
 }
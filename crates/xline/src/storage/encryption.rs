@@ -0,0 +1,414 @@
+use std::{collections::BTreeMap, fmt, process::Command as ProcessCommand};
+
+use aes_gcm::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    Aes256Gcm, Key, Nonce,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use clippy_utilities::OverflowArithmetic;
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use utils::config::{EncryptionConfig, KeySource};
+use xlineapi::execute_error::ExecuteError;
+
+/// Key of the data keys in the meta table
+pub(crate) const DATA_KEYS_KEY: &[u8] = b"encryption_data_keys";
+/// Key of the kv table key where the progressive re-encryption resumes, in the meta table
+pub(crate) const REENCRYPT_CURSOR_KEY: &[u8] = b"encryption_reencrypt_cursor";
+/// Version of the format of encrypted values
+const FORMAT_VERSION: u8 = 1;
+/// Length of a key in bytes
+const KEY_LEN: usize = 32;
+/// Length of a nonce in bytes
+const NONCE_LEN: usize = 12;
+/// Length of the header of an encrypted value: version, data key id and nonce
+const HEADER_LEN: usize = 1 + 4 + NONCE_LEN;
+/// Associated data of wrapped data keys
+const DATA_KEY_AAD: &[u8] = b"xline data key";
+
+/// Data keys as they are stored in the meta table, every key is wrapped by the key
+/// encryption key and base64 encoded
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredDataKeys {
+    /// Id of the active data key
+    active: u32,
+    /// Ids and wrapped data keys
+    keys: Vec<(u32, String)>,
+}
+
+/// A data key
+struct DataKey {
+    /// The raw key, which is wrapped when the data keys are stored
+    raw: Key<Aes256Gcm>,
+    /// Cipher of the key
+    cipher: Aes256Gcm,
+}
+
+impl DataKey {
+    /// Generate a new data key
+    fn generate() -> Self {
+        Self::new(Aes256Gcm::generate_key(OsRng))
+    }
+
+    /// New `DataKey`
+    fn new(raw: Key<Aes256Gcm>) -> Self {
+        Self {
+            cipher: Aes256Gcm::new(&raw),
+            raw,
+        }
+    }
+}
+
+/// Data keys in use
+struct DataKeys {
+    /// Id of the active data key, which encrypts new values
+    active: u32,
+    /// All data keys by id, keys other than the active one are retired and only used
+    /// to decrypt values that are not re-encrypted yet
+    keys: BTreeMap<u32, DataKey>,
+}
+
+/// Envelope encryption of values at rest.
+///
+/// Values are encrypted by AES-256-GCM data keys, which are encrypted by the key
+/// encryption key and stored along with the data. The key and table of a value are
+/// authenticated, so encrypted values can not be moved around. Keys of the tables are
+/// not encrypted.
+///
+/// Encrypted values are laid out as `[version][data key id][nonce][ciphertext]`.
+pub(crate) struct Encryptor {
+    /// The key encryption key
+    kek: Aes256Gcm,
+    /// Previous key encryption keys
+    previous_keks: Vec<Aes256Gcm>,
+    /// The data keys
+    data_keys: RwLock<DataKeys>,
+}
+
+impl fmt::Debug for Encryptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let data_keys = self.data_keys.read();
+        f.debug_struct("Encryptor")
+            .field("active", &data_keys.active)
+            .field("data_keys", &data_keys.keys.keys().collect::<Vec<_>>())
+            .finish_non_exhaustive()
+    }
+}
+
+impl Encryptor {
+    /// New `Encryptor` with a freshly generated data key
+    pub(crate) fn new(config: &EncryptionConfig) -> Result<Self, ExecuteError> {
+        let kek = load_kek(config.kek())?;
+        let previous_keks = config
+            .previous_keks()
+            .iter()
+            .map(load_kek)
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            kek,
+            previous_keks,
+            data_keys: RwLock::new(DataKeys {
+                active: 1,
+                keys: BTreeMap::from([(1, DataKey::generate())]),
+            }),
+        })
+    }
+
+    /// Replace the data keys by the stored ones. If any of them is wrapped by a previous
+    /// key encryption key, a new active data key is generated and `true` is returned, the
+    /// data keys should be stored again then.
+    pub(crate) fn load_data_keys(&self, stored: &[u8]) -> Result<bool, ExecuteError> {
+        let stored: StoredDataKeys = serde_json::from_slice(stored)
+            .map_err(|e| ExecuteError::DbError(format!("Failed to decode data keys: {e}")))?;
+        let mut rewrap = false;
+        let mut keys = BTreeMap::new();
+        for (id, wrapped) in stored.keys {
+            let wrapped = STANDARD
+                .decode(wrapped)
+                .map_err(|e| ExecuteError::DbError(format!("Failed to decode data keys: {e}")))?;
+            let raw = if let Some(raw) = unwrap_key(&self.kek, id, &wrapped) {
+                raw
+            } else {
+                rewrap = true;
+                self.previous_keks
+                    .iter()
+                    .find_map(|kek| unwrap_key(kek, id, &wrapped))
+                    .ok_or_else(|| {
+                        ExecuteError::DbError(format!(
+                            "Data key {id} can not be decrypted by any key encryption key"
+                        ))
+                    })?
+            };
+            let _ignore = keys.insert(id, DataKey::new(raw));
+        }
+        if !keys.contains_key(&stored.active) {
+            return Err(ExecuteError::DbError(format!(
+                "Active data key {} is missing",
+                stored.active
+            )));
+        }
+        let mut data_keys = DataKeys {
+            active: stored.active,
+            keys,
+        };
+        if rewrap {
+            // the key encryption key is rotated, rotate the data key as well
+            let id = data_keys
+                .keys
+                .keys()
+                .max()
+                .map_or(1, |id| id.overflow_add(1));
+            let _ignore = data_keys.keys.insert(id, DataKey::generate());
+            data_keys.active = id;
+        }
+        *self.data_keys.write() = data_keys;
+        Ok(rewrap)
+    }
+
+    /// Encode the data keys to be stored, they are wrapped by the key encryption key
+    pub(crate) fn encode_data_keys(&self) -> Result<Vec<u8>, ExecuteError> {
+        let data_keys = self.data_keys.read();
+        let keys = data_keys
+            .keys
+            .iter()
+            .map(|(&id, key)| {
+                let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+                let payload = Payload {
+                    msg: key.raw.as_slice(),
+                    aad: &data_key_aad(id),
+                };
+                let ciphertext = self.kek.encrypt(&nonce, payload).map_err(|e| {
+                    ExecuteError::DbError(format!("Failed to encrypt data key: {e}"))
+                })?;
+                let mut wrapped = nonce.to_vec();
+                wrapped.extend(ciphertext);
+                Ok((id, STANDARD.encode(wrapped)))
+            })
+            .collect::<Result<_, ExecuteError>>()?;
+        let stored = StoredDataKeys {
+            active: data_keys.active,
+            keys,
+        };
+        serde_json::to_vec(&stored)
+            .map_err(|e| ExecuteError::DbError(format!("Failed to encode data keys: {e}")))
+    }
+
+    /// Encrypt a value of the key in the table by the active data key
+    pub(crate) fn encrypt(
+        &self,
+        table: &str,
+        key: &[u8],
+        value: &[u8],
+    ) -> Result<Vec<u8>, ExecuteError> {
+        let data_keys = self.data_keys.read();
+        let Some(data_key) = data_keys.keys.get(&data_keys.active) else {
+            unreachable!("the active data key always exists");
+        };
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let payload = Payload {
+            msg: value,
+            aad: &value_aad(table, key),
+        };
+        let ciphertext = data_key
+            .cipher
+            .encrypt(&nonce, payload)
+            .map_err(|e| ExecuteError::DbError(format!("Failed to encrypt value: {e}")))?;
+        let mut encrypted = Vec::with_capacity(HEADER_LEN.overflow_add(ciphertext.len()));
+        encrypted.push(FORMAT_VERSION);
+        encrypted.extend_from_slice(&data_keys.active.to_le_bytes());
+        encrypted.extend_from_slice(&nonce);
+        encrypted.extend(ciphertext);
+        Ok(encrypted)
+    }
+
+    /// Decrypt a value of the key in the table
+    pub(crate) fn decrypt(
+        &self,
+        table: &str,
+        key: &[u8],
+        value: &[u8],
+    ) -> Result<Vec<u8>, ExecuteError> {
+        let Some((id, nonce, ciphertext)) = split_value(value) else {
+            return Err(ExecuteError::DbError(format!(
+                "Value of key {key:?} in {table} is not encrypted"
+            )));
+        };
+        let data_keys = self.data_keys.read();
+        let Some(data_key) = data_keys.keys.get(&id) else {
+            return Err(ExecuteError::DbError(format!(
+                "Data key {id} of key {key:?} in {table} is missing"
+            )));
+        };
+        let payload = Payload {
+            msg: ciphertext,
+            aad: &value_aad(table, key),
+        };
+        data_key
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), payload)
+            .map_err(|e| {
+                ExecuteError::DbError(format!("Failed to decrypt key {key:?} in {table}: {e}"))
+            })
+    }
+
+    /// Whether the value is encrypted by a retired data key
+    pub(crate) fn is_stale(&self, value: &[u8]) -> bool {
+        split_value(value).is_some_and(|(id, _, _)| id != self.data_keys.read().active)
+    }
+
+    /// Whether there are retired data keys
+    pub(crate) fn has_retired_keys(&self) -> bool {
+        self.data_keys.read().keys.len() > 1
+    }
+
+    /// Drop the retired data keys, should only be called after all values are
+    /// re-encrypted by the active data key
+    pub(crate) fn drop_retired_keys(&self) {
+        let mut data_keys = self.data_keys.write();
+        let active = data_keys.active;
+        data_keys.keys.retain(|&id, _| id == active);
+    }
+}
+
+/// Load a key encryption key from its source
+fn load_kek(source: &KeySource) -> Result<Aes256Gcm, ExecuteError> {
+    let encoded = match *source {
+        KeySource::File { ref path } => std::fs::read_to_string(path).map_err(|e| {
+            ExecuteError::DbError(format!("Failed to read key encryption key {path:?}: {e}"))
+        })?,
+        KeySource::Command { ref command } => {
+            let Some((program, args)) = command.split_first() else {
+                return Err(ExecuteError::DbError(
+                    "Key encryption key command is empty".to_owned(),
+                ));
+            };
+            let output = ProcessCommand::new(program)
+                .args(args)
+                .output()
+                .map_err(|e| {
+                    ExecuteError::DbError(format!("Failed to run key encryption key command: {e}"))
+                })?;
+            if !output.status.success() {
+                return Err(ExecuteError::DbError(format!(
+                    "Key encryption key command exited with {}",
+                    output.status
+                )));
+            }
+            String::from_utf8(output.stdout).map_err(|e| {
+                ExecuteError::DbError(format!("Key encryption key is not valid utf-8: {e}"))
+            })?
+        }
+    };
+    let key = STANDARD.decode(encoded.trim()).map_err(|e| {
+        ExecuteError::DbError(format!("Key encryption key is not valid base64: {e}"))
+    })?;
+    if key.len() != KEY_LEN {
+        return Err(ExecuteError::DbError(format!(
+            "Key encryption key should be {KEY_LEN} bytes, got {}",
+            key.len()
+        )));
+    }
+    Ok(Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)))
+}
+
+/// Unwrap a data key by the key encryption key, `None` if it is not wrapped by it
+fn unwrap_key(kek: &Aes256Gcm, id: u32, wrapped: &[u8]) -> Option<Key<Aes256Gcm>> {
+    if wrapped.len() < NONCE_LEN {
+        return None;
+    }
+    let (nonce, ciphertext) = wrapped.split_at(NONCE_LEN);
+    let payload = Payload {
+        msg: ciphertext,
+        aad: &data_key_aad(id),
+    };
+    let raw = kek.decrypt(Nonce::from_slice(nonce), payload).ok()?;
+    (raw.len() == KEY_LEN).then(|| *Key::<Aes256Gcm>::from_slice(&raw))
+}
+
+/// Associated data of a data key
+fn data_key_aad(id: u32) -> Vec<u8> {
+    let mut aad = DATA_KEY_AAD.to_vec();
+    aad.extend_from_slice(&id.to_le_bytes());
+    aad
+}
+
+/// Associated data of a value
+fn value_aad(table: &str, key: &[u8]) -> Vec<u8> {
+    let mut aad = table.as_bytes().to_vec();
+    aad.push(0);
+    aad.extend_from_slice(key);
+    aad
+}
+
+/// Split an encrypted value to the data key id, the nonce and the ciphertext
+fn split_value(value: &[u8]) -> Option<(u32, &[u8], &[u8])> {
+    if value.len() < HEADER_LEN || value.first() != Some(&FORMAT_VERSION) {
+        return None;
+    }
+    let (header, ciphertext) = value.split_at(HEADER_LEN);
+    let (id, nonce) = header.get(1..)?.split_at(4);
+    let id = u32::from_le_bytes(id.try_into().ok()?);
+    Some((id, nonce, ciphertext))
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::Path;
+
+    use super::*;
+
+    fn kek_file(dir: &Path, name: &str) -> KeySource {
+        let path = dir.join(name);
+        let key = Aes256Gcm::generate_key(OsRng);
+        std::fs::write(&path, STANDARD.encode(key)).unwrap();
+        KeySource::File { path }
+    }
+
+    #[test]
+    fn values_should_be_bound_to_their_keys() -> Result<(), ExecuteError> {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let dir = tmp_dir.path();
+        let config = EncryptionConfig::new(kek_file(dir, "kek"), vec![]);
+        let encryptor = Encryptor::new(&config)?;
+
+        let encrypted = encryptor.encrypt("kv", b"a", b"value")?;
+        assert_ne!(encrypted, b"value");
+        assert_eq!(encryptor.decrypt("kv", b"a", &encrypted)?, b"value");
+        assert!(encryptor.decrypt("kv", b"b", &encrypted).is_err());
+        assert!(encryptor.decrypt("lease", b"a", &encrypted).is_err());
+        assert!(encryptor.decrypt("kv", b"a", b"value").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn kek_rotation_should_rotate_data_key() -> Result<(), ExecuteError> {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let dir = tmp_dir.path();
+        let old_kek = kek_file(dir, "old");
+        let new_kek = kek_file(dir, "new");
+        let old = Encryptor::new(&EncryptionConfig::new(old_kek.clone(), vec![]))?;
+        let encrypted = old.encrypt("kv", b"a", b"value")?;
+        let stored = old.encode_data_keys()?;
+
+        let without_old = Encryptor::new(&EncryptionConfig::new(new_kek.clone(), vec![]))?;
+        assert!(without_old.load_data_keys(&stored).is_err());
+
+        let new = Encryptor::new(&EncryptionConfig::new(new_kek, vec![old_kek]))?;
+        assert!(new.load_data_keys(&stored)?);
+        assert!(new.has_retired_keys());
+        assert!(new.is_stale(&encrypted));
+        assert_eq!(new.decrypt("kv", b"a", &encrypted)?, b"value");
+        let reencrypted = new.encrypt("kv", b"a", b"value")?;
+        assert!(!new.is_stale(&reencrypted));
+
+        // the rotated data keys are wrapped by the new key encryption key only
+        assert!(!new.load_data_keys(&new.encode_data_keys()?)?);
+        new.drop_retired_keys();
+        assert!(!new.has_retired_keys());
+        assert!(new.decrypt("kv", b"a", &encrypted).is_err());
+        assert_eq!(new.decrypt("kv", b"a", &reencrypted)?, b"value");
+
+        Ok(())
+    }
+}
@@ -1,7 +1,7 @@
 use std::collections::HashSet;
 
 use clippy_utilities::OverflowArithmetic;
-use crossbeam_skiplist::SkipMap;
+use crossbeam_skiplist::{map::Entry, SkipMap};
 use itertools::Itertools;
 use parking_lot::RwLock;
 use utils::parking_lot_lock::RwLockMap;
@@ -10,7 +10,9 @@ use xlineapi::command::KeyRange;
 use super::revision::{KeyRevision, Revision};
 use crate::server::command::RangeType;
 
-/// Keys to revisions mapping
+/// Keys to revisions mapping. The keys are kept in a concurrent ordered map and every
+/// key has its own lock, so commands on different keys read and write the index in
+/// parallel, and a range only locks one key at a time.
 #[derive(Debug)]
 pub(crate) struct Index {
     /// Inner struct of `Index`
@@ -35,6 +37,11 @@ impl Index {
 
     /// Get specified or last `KeyRevision` if the key is not deleted, and convert to `Revision`
     fn get_revision(revs: &[KeyRevision], revision: i64) -> Option<Revision> {
+        Self::get_key_revision(revs, revision).map(|kr| kr.as_revision())
+    }
+
+    /// Get specified or last `KeyRevision` if the key is not deleted
+    fn get_key_revision(revs: &[KeyRevision], revision: i64) -> Option<KeyRevision> {
         let rev = if revision <= 0 {
             revs.last()
         } else {
@@ -49,8 +56,18 @@ impl Index {
             };
             revs.get(idx)
         };
-        rev.filter(|kr| !kr.is_deleted())
-            .map(KeyRevision::as_revision)
+        rev.filter(|kr| !kr.is_deleted()).copied()
+    }
+
+    /// Get the key and its specified or last `KeyRevision` of an entry if the key is not deleted
+    fn entry_key_revision(
+        entry: &Entry<'_, Vec<u8>, RwLock<Vec<KeyRevision>>>,
+        revision: i64,
+    ) -> Option<(Vec<u8>, KeyRevision)> {
+        entry
+            .value()
+            .map_read(|revs| Self::get_key_revision(revs.as_ref(), revision))
+            .map(|rev| (entry.key().clone(), rev))
     }
 
     /// Insert `KeyRevision` of deleted and generate `Revision` pair of deleted
@@ -65,6 +82,87 @@ impl Index {
         Some((last_available_rev, del_rev.as_revision()))
     }
 
+    /// Mark the key of an entry as deleted if it is not deleted yet. The deleted keys
+    /// take consecutive sub revisions from `next_sub_revision`, so the sub revisions of
+    /// a deletion only depend on the keys that are alive before it.
+    fn delete_entry(
+        entry: &Entry<'_, Vec<u8>, RwLock<Vec<KeyRevision>>>,
+        revision: i64,
+        next_sub_revision: &mut i64,
+    ) -> Option<((Revision, Revision), Vec<u8>)> {
+        let pair = entry.value().map_write(|mut revs| {
+            Self::gen_del_revision(revs.as_mut(), revision, *next_sub_revision)
+        })?;
+        *next_sub_revision = next_sub_revision.overflow_add(1);
+        Some((pair, entry.key().clone()))
+    }
+
+    /// Get the keys in the range that are alive right before the given revision, in the
+    /// key order
+    pub(crate) fn alive_keys_before(
+        &self,
+        key: &[u8],
+        range_end: &[u8],
+        revision: Revision,
+    ) -> Vec<Vec<u8>> {
+        match RangeType::get_range_type(key, range_end) {
+            RangeType::OneKey => self
+                .inner
+                .get(key)
+                .filter(|entry| Self::is_alive_before(entry, revision))
+                .map(|entry| vec![entry.key().clone()])
+                .unwrap_or_default(),
+            RangeType::AllKeys => self
+                .inner
+                .iter()
+                .filter(|entry| Self::is_alive_before(entry, revision))
+                .map(|entry| entry.key().clone())
+                .collect(),
+            RangeType::Range => self
+                .inner
+                .range(KeyRange::new(key, range_end))
+                .filter(|entry| Self::is_alive_before(entry, revision))
+                .map(|entry| entry.key().clone())
+                .collect(),
+        }
+    }
+
+    /// Check if the key of an entry is alive right before the given revision
+    fn is_alive_before(
+        entry: &Entry<'_, Vec<u8>, RwLock<Vec<KeyRevision>>>,
+        revision: Revision,
+    ) -> bool {
+        entry.value().map_read(|revs| {
+            revs.iter()
+                .rev()
+                .find(|rev| rev.as_revision() < revision)
+                .is_some_and(|rev| !rev.is_deleted())
+        })
+    }
+
+    /// Restore the deletion of a key at the given revision in the order of revisions, it
+    /// is skipped if the key already has the revision. Return `true` if the deletion is
+    /// restored as the latest revision of the key.
+    pub(crate) fn restore_deletion(&self, key: &[u8], revision: Revision) -> bool {
+        let Some(entry) = self.inner.get(key) else {
+            return false;
+        };
+        entry.value().map_write(|mut revs| {
+            let pos = revs.partition_point(|rev| rev.as_revision() < revision);
+            if revs
+                .get(pos)
+                .is_some_and(|rev| rev.as_revision() == revision)
+            {
+                return false;
+            }
+            revs.insert(
+                pos,
+                KeyRevision::new_deletion(revision.revision(), revision.sub_revision()),
+            );
+            pos.overflow_add(1) == revs.len()
+        })
+    }
+
     /// Get all revisions that need to be kept after compact at the given revision
     pub(crate) fn keep(&self, at_rev: i64) -> HashSet<Revision> {
         let mut revs = HashSet::new();
@@ -88,6 +186,31 @@ impl Index {
         });
         revs
     }
+
+    /// Get the revisions of all keys up to the given revision, keys without such
+    /// revisions are skipped
+    pub(crate) fn snapshot(&self, revision: i64) -> Vec<(Vec<u8>, Vec<KeyRevision>)> {
+        self.inner
+            .iter()
+            .filter_map(|entry| {
+                let revisions: Vec<_> = entry.value().map_read(|revisions| {
+                    revisions
+                        .iter()
+                        .filter(|rev| rev.mod_revision <= revision)
+                        .copied()
+                        .collect()
+                });
+                (!revisions.is_empty()).then(|| (entry.key().clone(), revisions))
+            })
+            .collect()
+    }
+
+    /// Restore the revisions of the keys from a snapshot
+    pub(crate) fn restore_snapshot(&self, keys: Vec<(Vec<u8>, Vec<KeyRevision>)>) {
+        for (key, revisions) in keys {
+            let _ignore = self.inner.insert(key, RwLock::new(revisions));
+        }
+    }
 }
 
 /// Operations of Index
@@ -95,6 +218,14 @@ pub(super) trait IndexOperate {
     /// Get `Revision` of keys, get the latest `Revision` when revision <= 0
     fn get(&self, key: &[u8], range_end: &[u8], revision: i64) -> Vec<Revision>;
 
+    /// Get keys and their `KeyRevision`, get the latest `KeyRevision` when revision <= 0
+    fn get_key_revisions(
+        &self,
+        key: &[u8],
+        range_end: &[u8],
+        revision: i64,
+    ) -> Vec<(Vec<u8>, KeyRevision)>;
+
     /// Get `Revision` of keys from one revision
     fn get_from_rev(&self, key: &[u8], range_end: &[u8], revision: i64) -> Vec<Revision>;
 
@@ -164,6 +295,32 @@ impl IndexOperate for Index {
         }
     }
 
+    fn get_key_revisions(
+        &self,
+        key: &[u8],
+        range_end: &[u8],
+        revision: i64,
+    ) -> Vec<(Vec<u8>, KeyRevision)> {
+        match RangeType::get_range_type(key, range_end) {
+            RangeType::OneKey => self
+                .inner
+                .get(key)
+                .and_then(|entry| Self::entry_key_revision(&entry, revision))
+                .into_iter()
+                .collect(),
+            RangeType::AllKeys => self
+                .inner
+                .iter()
+                .filter_map(|entry| Self::entry_key_revision(&entry, revision))
+                .collect(),
+            RangeType::Range => self
+                .inner
+                .range(KeyRange::new(key, range_end))
+                .filter_map(|entry| Self::entry_key_revision(&entry, revision))
+                .collect(),
+        }
+    }
+
     fn get_from_rev(&self, key: &[u8], range_end: &[u8], revision: i64) -> Vec<Revision> {
         match RangeType::get_range_type(key, range_end) {
             RangeType::OneKey => self
@@ -224,47 +381,36 @@ impl IndexOperate for Index {
                 };
                 (pairs, keys)
             }
-            RangeType::AllKeys => self
-                .inner
-                .iter()
-                .zip(0..)
-                .filter_map(|(entry, i)| {
-                    entry.value().map_write(|mut revs| {
-                        Self::gen_del_revision(
-                            revs.as_mut(),
-                            revision,
-                            sub_revision.overflow_add(i),
-                        )
-                        .map(|pair| (pair, entry.key().clone()))
+            RangeType::AllKeys => {
+                let mut next_sub_revision = sub_revision;
+                self.inner
+                    .iter()
+                    .filter_map(|entry| {
+                        Self::delete_entry(&entry, revision, &mut next_sub_revision)
                     })
-                })
-                .unzip(),
-            RangeType::Range => self
-                .inner
-                .range(KeyRange::new(key, range_end))
-                .zip(0..)
-                .filter_map(|(entry, i)| {
-                    entry.value().map_write(|mut revs| {
-                        Self::gen_del_revision(
-                            revs.as_mut(),
-                            revision,
-                            sub_revision.overflow_add(i),
-                        )
-                        .map(|pair| (pair, entry.key().clone()))
+                    .unzip()
+            }
+            RangeType::Range => {
+                let mut next_sub_revision = sub_revision;
+                self.inner
+                    .range(KeyRange::new(key, range_end))
+                    .filter_map(|entry| {
+                        Self::delete_entry(&entry, revision, &mut next_sub_revision)
                     })
-                })
-                .unzip(),
+                    .unzip()
+            }
         };
         (pairs, keys)
     }
 
     fn insert(&self, key_revisions: Vec<(Vec<u8>, KeyRevision)>) {
         for (key, revision) in key_revisions {
-            if let Some(entry) = self.inner.get::<[u8]>(key.as_ref()) {
-                entry.value().map_write(|mut revs| revs.push(revision));
-            } else {
-                _ = self.inner.insert(key, RwLock::new(vec![revision]));
-            }
+            // a new key is inserted atomically, so a concurrent insert of the same key is
+            // never overwritten
+            self.inner
+                .get_or_insert(key, RwLock::new(Vec::new()))
+                .value()
+                .map_write(|mut revs| revs.push(revision));
         }
     }
 
@@ -363,6 +509,27 @@ mod test {
             .map_read(|revs| assert_eq!(*revs, expected_values));
     }
 
+    #[test]
+    fn test_concurrent_insert() {
+        let index = Index::new();
+        std::thread::scope(|s| {
+            for t in 0..8_i64 {
+                let index = &index;
+                let _handle = s.spawn(move || {
+                    for i in 0..100_i64 {
+                        let key = format!("key{}", i % 10).into_bytes();
+                        let revision = t * 100 + i + 1;
+                        index.insert(vec![(key, KeyRevision::new(revision, 1, revision, 0))]);
+                    }
+                });
+            }
+        });
+        assert_eq!(index.inner.len(), 10);
+        for entry in &index.inner {
+            assert_eq!(entry.value().read().len(), 80);
+        }
+    }
+
     fn init_and_test_insert() -> Index {
         let index = Index::new();
 
@@ -450,6 +617,24 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_get_key_revisions() {
+        let index = init_and_test_insert();
+        let revisions = index.get(b"\0", b"\0", 0).into_iter().collect::<Vec<_>>();
+        let key_revisions = index.get_key_revisions(b"\0", b"\0", 0);
+        assert_eq!(
+            key_revisions
+                .iter()
+                .map(|(_key, rev)| rev.as_revision())
+                .collect::<Vec<_>>(),
+            revisions
+        );
+        assert_eq!(
+            index.get_key_revisions(b"key", b"", 1),
+            vec![(b"key".to_vec(), KeyRevision::new(1, 1, 1, 3))]
+        );
+    }
+
     #[test]
     fn test_delete() {
         let index = init_and_test_insert();
@@ -0,0 +1,215 @@
+use std::{sync::Arc, time::Duration};
+
+use clippy_utilities::NumericCast;
+use prost::bytes::{Buf, BufMut};
+use tracing::{debug, info, warn};
+use utils::task_manager::Listener;
+use xlineapi::execute_error::ExecuteError;
+
+use super::{revision::KeyRevision, storage_api::StorageApi};
+use crate::server::command::CommandExecutor;
+
+/// Key of the index snapshot in the meta table
+pub(crate) const INDEX_SNAPSHOT_KEY: &str = "index_snapshot";
+/// Version of the encoding of the index snapshot, snapshots of other versions are
+/// ignored and the index is rebuilt from the kv table
+const INDEX_SNAPSHOT_FORMAT: u8 = 1;
+
+/// A snapshot of the in-memory state that is rebuilt from the kv table on startup: the
+/// revisions of the keys in the index, the leases attached to the keys and the sizes of
+/// the keys tracked by the tenants.
+///
+/// The revisions up to `revision` are all included, the revisions above it are recovered
+/// by scanning the tail of the kv table. The leases and the sizes may be newer than
+/// `revision`, which is fine as the tail is replayed on top of them.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct IndexSnapshot {
+    /// All revisions up to it are included
+    pub(crate) revision: i64,
+    /// Revisions of the keys
+    pub(crate) keys: Vec<(Vec<u8>, Vec<KeyRevision>)>,
+    /// Leases attached to the keys
+    pub(crate) leases: Vec<(Vec<u8>, i64)>,
+    /// Prefixes of the tenants, the sizes are only valid for the same tenants
+    pub(crate) tenant_prefixes: Vec<String>,
+    /// Sizes of the keys tracked by the tenants
+    pub(crate) tenant_sizes: Vec<(Vec<u8>, u64)>,
+}
+
+impl IndexSnapshot {
+    /// Encode the snapshot
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.put_u8(INDEX_SNAPSHOT_FORMAT);
+        buf.put_i64(self.revision);
+        buf.put_u64(self.keys.len().numeric_cast());
+        for &(ref key, ref revisions) in &self.keys {
+            put_bytes(&mut buf, key);
+            buf.put_u64(revisions.len().numeric_cast());
+            for rev in revisions {
+                buf.put_i64(rev.create_revision);
+                buf.put_i64(rev.version);
+                buf.put_i64(rev.mod_revision);
+                buf.put_i64(rev.sub_revision);
+            }
+        }
+        buf.put_u64(self.leases.len().numeric_cast());
+        for &(ref key, lease) in &self.leases {
+            put_bytes(&mut buf, key);
+            buf.put_i64(lease);
+        }
+        buf.put_u64(self.tenant_prefixes.len().numeric_cast());
+        for prefix in &self.tenant_prefixes {
+            put_bytes(&mut buf, prefix.as_bytes());
+        }
+        buf.put_u64(self.tenant_sizes.len().numeric_cast());
+        for &(ref key, size) in &self.tenant_sizes {
+            put_bytes(&mut buf, key);
+            buf.put_u64(size);
+        }
+        buf
+    }
+
+    /// Decode a snapshot
+    ///
+    /// # Errors
+    ///
+    /// Return `ExecuteError::DbError` if the snapshot is truncated or of another format
+    pub(crate) fn decode(mut buf: &[u8]) -> Result<Self, ExecuteError> {
+        let Some((&format, rest)) = buf.split_first() else {
+            return Err(truncated());
+        };
+        buf = rest;
+        if format != INDEX_SNAPSHOT_FORMAT {
+            return Err(ExecuteError::DbError(format!(
+                "unknown index snapshot format {format}"
+            )));
+        }
+        let revision = get_i64(&mut buf)?;
+        let mut keys = Vec::new();
+        for _ in 0..get_u64(&mut buf)? {
+            let key = get_bytes(&mut buf)?;
+            let mut revisions = Vec::new();
+            for _ in 0..get_u64(&mut buf)? {
+                revisions.push(KeyRevision::new(
+                    get_i64(&mut buf)?,
+                    get_i64(&mut buf)?,
+                    get_i64(&mut buf)?,
+                    get_i64(&mut buf)?,
+                ));
+            }
+            keys.push((key, revisions));
+        }
+        let mut leases = Vec::new();
+        for _ in 0..get_u64(&mut buf)? {
+            leases.push((get_bytes(&mut buf)?, get_i64(&mut buf)?));
+        }
+        let mut tenant_prefixes = Vec::new();
+        for _ in 0..get_u64(&mut buf)? {
+            let prefix = String::from_utf8(get_bytes(&mut buf)?)
+                .map_err(|e| ExecuteError::DbError(format!("invalid tenant prefix: {e}")))?;
+            tenant_prefixes.push(prefix);
+        }
+        let mut tenant_sizes = Vec::new();
+        for _ in 0..get_u64(&mut buf)? {
+            tenant_sizes.push((get_bytes(&mut buf)?, get_u64(&mut buf)?));
+        }
+        Ok(Self {
+            revision,
+            keys,
+            leases,
+            tenant_prefixes,
+            tenant_sizes,
+        })
+    }
+}
+
+/// Put a length-prefixed byte string
+fn put_bytes(buf: &mut Vec<u8>, bytes: &[u8]) {
+    buf.put_u64(bytes.len().numeric_cast());
+    buf.put_slice(bytes);
+}
+
+/// Get a length-prefixed byte string
+fn get_bytes(buf: &mut &[u8]) -> Result<Vec<u8>, ExecuteError> {
+    let len: usize = get_u64(buf)?.numeric_cast();
+    let bytes = buf.get(..len).ok_or_else(truncated)?.to_vec();
+    buf.advance(len);
+    Ok(bytes)
+}
+
+/// Get an unsigned integer
+fn get_u64(buf: &mut &[u8]) -> Result<u64, ExecuteError> {
+    if buf.remaining() < 8 {
+        return Err(truncated());
+    }
+    Ok(buf.get_u64())
+}
+
+/// Get a signed integer
+fn get_i64(buf: &mut &[u8]) -> Result<i64, ExecuteError> {
+    if buf.remaining() < 8 {
+        return Err(truncated());
+    }
+    Ok(buf.get_i64())
+}
+
+/// Error of a truncated snapshot
+fn truncated() -> ExecuteError {
+    ExecuteError::DbError("the index snapshot is truncated".to_owned())
+}
+
+/// Background task that persists an index snapshot at the given interval, so that the
+/// index is recovered from the snapshot and the tail of the kv table on startup instead
+/// of the whole kv table
+#[allow(clippy::arithmetic_side_effects, clippy::ignored_unit_patterns)] // introduced by tokio::select! macro
+pub(crate) async fn index_snapshot_task<S: StorageApi>(
+    interval: Duration,
+    ce: Arc<CommandExecutor<S>>,
+    shutdown_listener: Listener,
+) {
+    let mut last_revision = 0;
+    loop {
+        tokio::select! {
+            _ = tokio::time::sleep(interval) => {}
+            _ = shutdown_listener.wait() => return,
+        }
+        match ce.persist_index_snapshot(last_revision) {
+            Ok(Some(revision)) => {
+                info!("index snapshot is persisted at revision {revision}");
+                last_revision = revision;
+            }
+            Ok(None) => debug!("index snapshot is up to date, skip persisting"),
+            Err(e) => warn!("failed to persist index snapshot, {e}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn index_snapshot_should_be_decoded() {
+        let snapshot = IndexSnapshot {
+            revision: 3,
+            keys: vec![
+                (b"a".to_vec(), vec![KeyRevision::new(1, 1, 1, 0)]),
+                (
+                    b"b".to_vec(),
+                    vec![
+                        KeyRevision::new(2, 1, 2, 0),
+                        KeyRevision::new_deletion(3, 0),
+                    ],
+                ),
+            ],
+            leases: vec![(b"a".to_vec(), 7)],
+            tenant_prefixes: vec!["a".to_owned()],
+            tenant_sizes: vec![(b"a".to_vec(), 4)],
+        };
+        let encoded = snapshot.encode();
+        assert_eq!(IndexSnapshot::decode(&encoded).unwrap(), snapshot);
+        assert!(IndexSnapshot::decode(&encoded[..encoded.len() - 1]).is_err());
+        assert!(IndexSnapshot::decode(&[]).is_err());
+    }
+}
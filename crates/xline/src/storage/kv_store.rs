@@ -1,6 +1,8 @@
 use std::{
     cmp::Ordering,
-    collections::{HashMap, VecDeque},
+    collections::{hash_map::DefaultHasher, BTreeMap, HashMap, VecDeque},
+    hash::{Hash, Hasher},
+    num::NonZeroUsize,
     sync::{
         atomic::{AtomicI64, Ordering::Relaxed},
         Arc,
@@ -8,9 +10,10 @@ use std::{
 };
 
 use clippy_utilities::{NumericCast, OverflowArithmetic};
+use parking_lot::{Mutex, RwLock};
 use prost::Message;
 use tokio::sync::mpsc;
-use tracing::{debug, warn};
+use tracing::{debug, info, warn};
 use utils::table_names::{KV_TABLE, META_TABLE};
 use xlineapi::{
     command::{CommandResponse, KeyRange, SyncResponse},
@@ -20,12 +23,19 @@ use xlineapi::{
 use super::{
     db::SCHEDULED_COMPACT_REVISION,
     index::{Index, IndexOperate},
+    index_snapshot::{IndexSnapshot, INDEX_SNAPSHOT_KEY},
+    kvwatcher::KvUpdateTx,
     lease_store::LeaseCollection,
+    range_tombstone::{
+        PendingTombstones, RangeTombstone, RANGE_TOMBSTONE_MIN_KEYS, RANGE_TOMBSTONE_PREFIX,
+    },
     revision::{KeyRevision, Revision},
     storage_api::StorageApi,
+    tenants::Tenants,
 };
 use crate::{
     header_gen::HeaderGenerator,
+    metrics,
     revision_check::RevisionCheck,
     revision_number::RevisionNumberGenerator,
     rpc::{
@@ -37,6 +47,10 @@ use crate::{
     storage::db::{WriteOp, FINISHED_COMPACT_REVISION},
 };
 
+/// Minimum number of revisions restored by a shard in the recovery, it is not worth a
+/// thread to restore fewer revisions
+const MIN_REVISIONS_PER_SHARD: usize = 10_000;
+
 /// KV store
 #[derive(Debug)]
 pub(crate) struct KvStore<DB>
@@ -50,11 +64,17 @@ where
     /// Header generator
     header_gen: Arc<HeaderGenerator>,
     /// KV update sender
-    kv_update_tx: mpsc::Sender<(i64, Vec<Event>)>,
+    kv_update_tx: KvUpdateTx,
     /// Compact task submit sender
     compact_task_tx: mpsc::Sender<(i64, Option<Arc<event_listener::Event>>)>,
     /// Lease collection
     lease_collection: Arc<LeaseCollection>,
+    /// Per-tenant accounting and quotas
+    tenants: Arc<Tenants>,
+    /// Notified when a range tombstone is added
+    range_tombstone_added: event_listener::Event,
+    /// Lock that serializes the writers of the deletions of the range tombstones
+    range_tombstone_writer: Mutex<()>,
 }
 
 /// KV store inner, shared by `KvStore` and `KvWatcher`
@@ -69,6 +89,8 @@ where
     db: Arc<DB>,
     /// Compacted Revision
     compacted_rev: AtomicI64,
+    /// Range tombstones whose deletions are not written to the kv table yet
+    range_tombstones: RwLock<PendingTombstones>,
 }
 
 impl<DB> KvStoreInner<DB>
@@ -81,20 +103,33 @@ where
             index,
             db,
             compacted_rev: AtomicI64::new(-1),
+            range_tombstones: RwLock::new(PendingTombstones::default()),
         }
     }
 
     /// Get `KeyValue` from the `KvStoreInner`
     fn get_values(&self, revisions: &[Revision]) -> Result<Vec<KeyValue>, ExecuteError> {
-        let revisions = revisions
+        let keys = revisions
             .iter()
             .map(Revision::encode_to_vec)
             .collect::<Vec<Vec<u8>>>();
-        let values = self.db.get_values(KV_TABLE, &revisions)?;
+        // the deletions of the range tombstones are written in the background, the lock
+        // is held while reading so that a deletion is either written or still pending
+        let range_tombstones = self.range_tombstones.read();
+        let values = self.db.get_values(KV_TABLE, &keys)?;
         let kvs: Vec<KeyValue> = values
             .into_iter()
-            .flatten()
-            .map(|v| KeyValue::decode(v.as_slice()))
+            .zip(revisions)
+            .filter_map(|(value, rev)| match value {
+                Some(v) => Some(KeyValue::decode(v.as_slice())),
+                None => range_tombstones.get_key(rev).map(|key| {
+                    Ok(KeyValue {
+                        key: key.clone(),
+                        mod_revision: rev.revision(),
+                        ..KeyValue::default()
+                    })
+                }),
+            })
             .collect::<Result<_, _>>()
             .map_err(|e| {
                 ExecuteError::DbError(format!("Failed to decode key-value from DB, error: {e}"))
@@ -160,7 +195,9 @@ where
         self.compacted_rev.load(Relaxed)
     }
 
-    /// Get `KeyValue` of a range with limit and count only, return kvs and total count
+    /// Get `KeyValue` of a range with limit and count only, return kvs and total count.
+    /// Keys whose `KeyRevision` doesn't pass `filter` are skipped before reading values,
+    /// but they are still counted in the total count.
     fn get_range_with_opts(
         &self,
         key: &[u8],
@@ -168,15 +205,21 @@ where
         revision: i64,
         limit: usize,
         count_only: bool,
+        filter: impl Fn(&KeyRevision) -> bool,
     ) -> Result<(Vec<KeyValue>, usize), ExecuteError> {
-        let mut revisions = self.index.get(key, range_end, revision);
-        let total = revisions.len();
+        let mut key_revisions = self.index.get_key_revisions(key, range_end, revision);
+        let total = key_revisions.len();
         if count_only || total == 0 {
             return Ok((vec![], total));
         }
+        key_revisions.retain(|&(_, ref rev)| filter(rev));
         if limit != 0 {
-            revisions.truncate(limit);
+            key_revisions.truncate(limit);
         }
+        let revisions = key_revisions
+            .iter()
+            .map(|&(_, ref rev)| rev.as_revision())
+            .collect::<Vec<_>>();
         let kvs = self.get_values(&revisions)?;
         Ok((kvs, total))
     }
@@ -194,56 +237,86 @@ where
         self.handle_kv_requests(request).map(CommandResponse::new)
     }
 
-    /// sync a kv request
+    /// sync a kv request, the events are notified by `notify_updates` once the changes
+    /// are persisted. The delete range requests applied inside a txn are returned as well,
+    /// the branches of a txn are only decided here.
     pub(crate) async fn after_sync(
         &self,
         request: &RequestWrapper,
         revision: i64,
-    ) -> Result<(SyncResponse, Vec<WriteOp>), ExecuteError> {
+    ) -> Result<
+        (
+            SyncResponse,
+            Vec<WriteOp>,
+            Vec<Event>,
+            Vec<DeleteRangeRequest>,
+        ),
+        ExecuteError,
+    > {
         self.sync_request(request, revision)
             .await
-            .map(|(rev, ops)| (SyncResponse::new(rev), ops))
+            .map(|(rev, ops, events, deletions)| (SyncResponse::new(rev), ops, events, deletions))
     }
 
-    /// Recover data from persistent storage
+    /// Recover data from persistent storage. If an index snapshot is persisted, the
+    /// index is recovered from it and the tail of the kv table above its revision.
     pub(crate) async fn recover(&self) -> Result<(), ExecuteError> {
         let mut key_to_lease: HashMap<Vec<u8>, i64> = HashMap::new();
-        let kvs = self.inner.db.get_all(KV_TABLE)?;
-
-        let current_rev = kvs
-            .last()
-            .map_or(1, |pair| Revision::decode(&pair.0).revision());
-        self.revision.set(current_rev);
-
-        for (key, value) in kvs {
-            let rev = Revision::decode(key.as_slice());
-            let kv = KeyValue::decode(value.as_slice())
-                .unwrap_or_else(|e| panic!("decode kv error: {e:?}"));
-
-            if kv.lease == 0 {
-                let _ignore = key_to_lease.remove(&kv.key);
-            } else {
-                let _ignore = key_to_lease.insert(kv.key.clone(), kv.lease);
-            }
+        let mut current_rev = 1;
+        let snapshot = self.load_index_snapshot()?;
+        let from_snapshot = snapshot.is_some();
+        let kvs = if let Some(snapshot) = snapshot {
+            let from = Revision::new(snapshot.revision.overflow_add(1), 0).encode_to_vec();
+            let kvs = self.inner.db.get_from(KV_TABLE, &from)?;
+            info!(
+                "recover the index from the snapshot at revision {}, {} revisions after it",
+                snapshot.revision,
+                kvs.len()
+            );
+            current_rev = snapshot
+                .keys
+                .iter()
+                .flat_map(|&(_, ref revisions)| revisions.iter().map(|rev| rev.mod_revision))
+                .max()
+                .unwrap_or(1);
+            key_to_lease.extend(snapshot.leases);
+            self.tenants.restore(snapshot.tenant_sizes);
+            self.inner.index.restore_snapshot(snapshot.keys);
+            kvs
+        } else {
+            self.inner.db.get_all(KV_TABLE)?
+        };
 
-            self.inner.index.restore(
-                kv.key,
-                rev.revision(),
-                rev.sub_revision(),
-                kv.create_revision,
-                kv.version,
+        if let Some(pair) = kvs.last() {
+            current_rev = Revision::decode(&pair.0).revision();
+        }
+        self.restore_revisions(&kvs, &mut key_to_lease);
+        // the latest revision may only be recorded by a range tombstone
+        if let Some(revision) = self.recover_range_tombstones(&mut key_to_lease)? {
+            current_rev = current_rev.max(revision);
+        }
+        let finished_rev = self.get_compact_revision(FINISHED_COMPACT_REVISION)?;
+        if let Some(finished_rev) = finished_rev {
+            assert!(
+                finished_rev >= -1,
+                "compacted revision corruption, which ({finished_rev}) must not be less than -1"
             );
+            // the latest revisions may be compacted away, e.g. a data dir migrated from
+            // etcd only keeps the latest versions of the keys
+            current_rev = current_rev.max(finished_rev);
         }
+        self.revision.set(current_rev);
 
         for (key, lease_id) in key_to_lease {
             self.attach(lease_id, key)?;
         }
-        if let Some(finished_rev) = self.get_compact_revision(FINISHED_COMPACT_REVISION)? {
-            assert!(
-                finished_rev >= -1 && finished_rev <= current_rev,
-                "compacted revision corruption, which ({finished_rev}) must belong to the range [-1, {current_rev}]"
-            );
+        if let Some(finished_rev) = finished_rev {
             self.update_compacted_revision(finished_rev);
+            // the snapshot may be taken before the compaction, the compacted revisions
+            // are already removed from the kv table
+            if from_snapshot {
+                let _ignore = self.inner.index.compact(finished_rev);
+            }
         }
         if let Some(scheduled_rev) = self.get_compact_revision(SCHEDULED_COMPACT_REVISION)? {
             if scheduled_rev > self.compacted_revision() {
@@ -262,6 +335,118 @@ where
         Ok(())
     }
 
+    /// Restore the revisions of the kv table into the index and the tenants, and update
+    /// the leases attached to the keys. The rows are decoded in parallel chunks, and then
+    /// restored in parallel shards partitioned by the keys, so that the revisions of a
+    /// key are still restored in order.
+    fn restore_revisions(
+        &self,
+        kvs: &[(Vec<u8>, Vec<u8>)],
+        key_to_lease: &mut HashMap<Vec<u8>, i64>,
+    ) {
+        let shards = recovery_shards(kvs.len());
+        let chunk_size = kvs.len().div_ceil(shards).max(1);
+        debug!("restore {} revisions with {shards} shards", kvs.len());
+        let chunks: Vec<Vec<Vec<(Revision, KeyValue)>>> =
+            run_recovery_threads(kvs.chunks(chunk_size), |chunk| decode_chunk(chunk, shards));
+        // the chunks are in the order of revisions, so are the revisions of each shard
+        let mut shard_revisions: Vec<Vec<(Revision, KeyValue)>> =
+            (0..shards).map(|_| Vec::new()).collect();
+        for buckets in chunks {
+            for (revisions, bucket) in shard_revisions.iter_mut().zip(buckets) {
+                revisions.extend(bucket);
+            }
+        }
+        let index = self.inner.index.as_ref();
+        let tenants = self.tenants.as_ref();
+        let shard_leases: Vec<HashMap<Vec<u8>, i64>> =
+            run_recovery_threads(shard_revisions, |revisions| {
+                restore_shard(index, tenants, revisions)
+            });
+        // the keys of the shards are disjoint
+        for (key, lease) in shard_leases.into_iter().flatten() {
+            if lease == 0 {
+                let _ignore = key_to_lease.remove(&key);
+            } else {
+                let _ignore = key_to_lease.insert(key, lease);
+            }
+        }
+    }
+
+    /// Recover the range tombstones whose deletions are not all written to the kv table.
+    /// The deleted keys are the keys alive right before the range tombstone, and the
+    /// missing deletions are restored into the index in the order of revisions. Return
+    /// the revision of the latest range tombstone.
+    fn recover_range_tombstones(
+        &self,
+        key_to_lease: &mut HashMap<Vec<u8>, i64>,
+    ) -> Result<Option<i64>, ExecuteError> {
+        let mut latest = None;
+        let records = self.inner.db.get_from(META_TABLE, RANGE_TOMBSTONE_PREFIX)?;
+        for (_, value) in records
+            .into_iter()
+            .take_while(|&(ref key, _)| key.starts_with(RANGE_TOMBSTONE_PREFIX))
+        {
+            let tombstone = RangeTombstone::decode(&value)?;
+            let keys = self.inner.index.alive_keys_before(
+                &tombstone.key,
+                &tombstone.range_end,
+                tombstone.first_revision(),
+            );
+            let mut deletions = Vec::with_capacity(keys.len());
+            for (key, sub_revision) in keys.into_iter().zip(tombstone.sub_revision..) {
+                let revision = Revision::new(tombstone.revision, sub_revision);
+                // the deletions that are already written are restored from the kv table
+                if self.inner.index.restore_deletion(&key, revision) {
+                    self.tenants.delete(&key);
+                    let _ignore = key_to_lease.remove(&key);
+                }
+                deletions.push((revision, key));
+            }
+            info!(
+                "recover the range tombstone at revision {}, {} keys are deleted",
+                tombstone.revision,
+                deletions.len()
+            );
+            latest = Some(tombstone.revision);
+            self.add_range_tombstone(tombstone, deletions);
+        }
+        Ok(latest)
+    }
+
+    /// Load the index snapshot, `None` if it is not persisted or can not be used
+    fn load_index_snapshot(&self) -> Result<Option<IndexSnapshot>, ExecuteError> {
+        let Some(bytes) = self.inner.db.get_value(META_TABLE, INDEX_SNAPSHOT_KEY)? else {
+            return Ok(None);
+        };
+        let snapshot = match IndexSnapshot::decode(&bytes) {
+            Ok(snapshot) => snapshot,
+            Err(e) => {
+                warn!(
+                    "failed to decode the index snapshot, rebuild the index from the kv table, {e}"
+                );
+                return Ok(None);
+            }
+        };
+        if snapshot.tenant_prefixes != self.tenants.prefixes() {
+            info!("the tenants are changed since the index snapshot, rebuild the index");
+            return Ok(None);
+        }
+        Ok(Some(snapshot))
+    }
+
+    /// Take a snapshot of the index up to the given revision, all revisions up to it
+    /// must be inserted into the index
+    pub(crate) fn index_snapshot(&self, revision: i64) -> IndexSnapshot {
+        IndexSnapshot {
+            revision,
+            keys: self.inner.index.snapshot(revision),
+            leases: self.lease_collection.attached_keys(),
+            tenant_prefixes: self.tenants.prefixes(),
+            tenant_sizes: self.tenants.sizes(),
+        }
+    }
+
     /// Get compact revision from db
     fn get_compact_revision(&self, revision_key: &str) -> Result<Option<i64>, ExecuteError> {
         let Some(revision_bytes) = self.inner.db.get_value(META_TABLE, revision_key)? else {
@@ -276,6 +461,105 @@ where
     }
 }
 
+/// The tombstone of a key deleted by a range tombstone at the given revision, as it's
+/// written to the kv table
+fn deletion_kv(revision: Revision, key: Vec<u8>) -> KeyValue {
+    KeyValue {
+        key,
+        mod_revision: revision.revision(),
+        ..KeyValue::default()
+    }
+}
+
+/// Number of the shards that restore the given number of revisions in the recovery
+fn recovery_shards(revisions: usize) -> usize {
+    std::thread::available_parallelism()
+        .map_or(1, NonZeroUsize::get)
+        .min(revisions.overflow_div(MIN_REVISIONS_PER_SHARD))
+        .max(1)
+}
+
+/// Decode a chunk of the kv table, and bucket the revisions by the shards of their keys
+fn decode_chunk(chunk: &[(Vec<u8>, Vec<u8>)], shards: usize) -> Vec<Vec<(Revision, KeyValue)>> {
+    let mut buckets: Vec<Vec<(Revision, KeyValue)>> = (0..shards).map(|_| Vec::new()).collect();
+    for &(ref key, ref value) in chunk {
+        let rev = Revision::decode(key.as_slice());
+        let kv =
+            KeyValue::decode(value.as_slice()).unwrap_or_else(|e| panic!("decode kv error: {e:?}"));
+        let mut hasher = DefaultHasher::new();
+        kv.key.hash(&mut hasher);
+        let shard: usize = hasher
+            .finish()
+            .overflow_rem(shards.numeric_cast())
+            .numeric_cast();
+        if let Some(bucket) = buckets.get_mut(shard) {
+            bucket.push((rev, kv));
+        }
+    }
+    buckets
+}
+
+/// Restore the revisions of a shard into the index and the tenants, the last leases of
+/// the keys in the shard are returned, 0 means the lease is detached
+fn restore_shard(
+    index: &Index,
+    tenants: &Tenants,
+    revisions: Vec<(Revision, KeyValue)>,
+) -> HashMap<Vec<u8>, i64> {
+    let mut leases = HashMap::new();
+    let total = revisions.len();
+    for (rev, kv) in revisions {
+        let _ignore = leases.insert(kv.key.clone(), kv.lease);
+        // tombstones of deleted keys have no version
+        if kv.version == 0 {
+            tenants.delete(&kv.key);
+        } else {
+            tenants.put(&kv.key, kv.value.len());
+        }
+        index.restore(
+            kv.key,
+            rev.revision(),
+            rev.sub_revision(),
+            kv.create_revision,
+            kv.version,
+        );
+    }
+    metrics::get()
+        .recovered_revisions_total
+        .add(total.numeric_cast(), &[]);
+    leases
+}
+
+/// Run a task of the recovery for each input on its own thread, and collect the outputs
+/// in the order of the inputs. The tasks run one by one on the current thread in the
+/// madsim simulation, where os threads are not simulated and would break determinism.
+fn run_recovery_threads<I, T, R, F>(inputs: I, task: F) -> Vec<R>
+where
+    I: IntoIterator<Item = T>,
+    T: Send,
+    R: Send,
+    F: Fn(T) -> R + Sync,
+{
+    if cfg!(madsim) {
+        return inputs.into_iter().map(task).collect();
+    }
+    std::thread::scope(|s| {
+        let task = &task;
+        let handles: Vec<_> = inputs
+            .into_iter()
+            .map(|input| s.spawn(move || task(input)))
+            .collect();
+        handles.into_iter().map(join_recovery_thread).collect()
+    })
+}
+
+/// Join a thread of the recovery, the panic of the thread is propagated
+fn join_recovery_thread<T>(handle: std::thread::ScopedJoinHandle<'_, T>) -> T {
+    handle
+        .join()
+        .unwrap_or_else(|e| std::panic::resume_unwind(e))
+}
+
 impl<DB> KvStore<DB>
 where
     DB: StorageApi,
@@ -284,9 +568,10 @@ where
     pub(crate) fn new(
         inner: Arc<KvStoreInner<DB>>,
         header_gen: Arc<HeaderGenerator>,
-        kv_update_tx: mpsc::Sender<(i64, Vec<Event>)>,
+        kv_update_tx: KvUpdateTx,
         compact_task_tx: mpsc::Sender<(i64, Option<Arc<event_listener::Event>>)>,
         lease_collection: Arc<LeaseCollection>,
+        tenants: Arc<Tenants>,
     ) -> Self {
         Self {
             inner,
@@ -295,9 +580,17 @@ where
             kv_update_tx,
             compact_task_tx,
             lease_collection,
+            tenants,
+            range_tombstone_added: event_listener::Event::new(),
+            range_tombstone_writer: Mutex::new(()),
         }
     }
 
+    /// Get the per-tenant accounting of KV store
+    pub(crate) fn tenants(&self) -> Arc<Tenants> {
+        Arc::clone(&self.tenants)
+    }
+
     /// Get revision of KV store
     pub(crate) fn revision(&self) -> i64 {
         self.revision.get()
@@ -313,12 +606,39 @@ where
         self.inner.compacted_rev.store(revision, Relaxed);
     }
 
-    /// Notify KV changes to KV watcher
-    async fn notify_updates(&self, revision: i64, updates: Vec<Event>) {
-        assert!(
-            self.kv_update_tx.send((revision, updates)).await.is_ok(),
-            "Failed to send updates to KV watcher"
-        );
+    /// Get keys of a range from the index without reading values, return kvs and total count.
+    /// Keys whose `KeyRevision` doesn't pass `filter` are skipped but still counted.
+    fn get_keys_from_index(
+        &self,
+        key: &[u8],
+        range_end: &[u8],
+        limit: usize,
+        filter: impl Fn(&KeyRevision) -> bool,
+    ) -> (Vec<KeyValue>, usize) {
+        let mut key_revisions = self.inner.index.get_key_revisions(key, range_end, 0);
+        let total = key_revisions.len();
+        key_revisions.retain(|&(_, ref rev)| filter(rev));
+        if limit != 0 {
+            key_revisions.truncate(limit);
+        }
+        let kvs = key_revisions
+            .into_iter()
+            .map(|(key, rev)| KeyValue {
+                lease: self.lease_collection.get_lease(&key),
+                key,
+                create_revision: rev.create_revision,
+                mod_revision: rev.mod_revision,
+                version: rev.version,
+                ..KeyValue::default()
+            })
+            .collect();
+        (kvs, total)
+    }
+
+    /// Notify KV changes to KV watcher, the updates of all revisions below `floor` must
+    /// be notified already, see `KvUpdateTx::send`
+    pub(crate) async fn notify_updates(&self, revision: i64, updates: Vec<Event>, floor: i64) {
+        self.kv_update_tx.send(revision, updates, floor).await;
     }
 
     /// Sort kvs by sort target and order
@@ -358,26 +678,12 @@ where
         };
     }
 
-    /// filter kvs by `{max,min}_{mod,create}_revision`
-    fn filter_kvs(
-        kvs: &mut Vec<KeyValue>,
-        max_mod_revision: i64,
-        min_mod_revision: i64,
-        max_create_revision: i64,
-        min_create_revision: i64,
-    ) {
-        if max_mod_revision > 0 {
-            kvs.retain(|kv| kv.mod_revision <= max_mod_revision);
-        }
-        if min_mod_revision > 0 {
-            kvs.retain(|kv| kv.mod_revision >= min_mod_revision);
-        }
-        if max_create_revision > 0 {
-            kvs.retain(|kv| kv.create_revision <= max_create_revision);
-        }
-        if min_create_revision > 0 {
-            kvs.retain(|kv| kv.create_revision >= min_create_revision);
-        }
+    /// Check if a `KeyRevision` matches `{max,min}_{mod,create}_revision` of a `RangeRequest`
+    fn match_revision_filters(req: &RangeRequest, rev: &KeyRevision) -> bool {
+        (req.max_mod_revision <= 0 || rev.mod_revision <= req.max_mod_revision)
+            && (req.min_mod_revision <= 0 || rev.mod_revision >= req.min_mod_revision)
+            && (req.max_create_revision <= 0 || rev.create_revision <= req.max_create_revision)
+            && (req.min_create_revision <= 0 || rev.create_revision >= req.min_create_revision)
     }
 
     /// Compare i64
@@ -440,7 +746,7 @@ where
                 } else {
                     0
                 };
-                Self::compare_i64(kv.mod_revision, les)
+                Self::compare_i64(kv.lease, les)
             }
         };
 
@@ -490,18 +796,108 @@ where
         revisions
             .iter()
             .for_each(|rev| ops.push(WriteOp::DeleteKeyValue(rev.as_ref())));
-        _ = self.inner.db.flush_ops(ops)?;
+        _ = self.inner.db.write_batch(ops, false)?;
         Ok(())
     }
 
     /// Compact kv storage
     pub(crate) fn compact_finished(&self, revision: i64) -> Result<(), ExecuteError> {
         let ops = vec![WriteOp::PutFinishedCompactRevision(revision)];
-        _ = self.inner.db.flush_ops(ops)?;
+        _ = self.inner.db.write_batch(ops, false)?;
         self.update_compacted_revision(revision);
         Ok(())
     }
 
+    /// Add a range tombstone whose deletions are written in the background
+    fn add_range_tombstone(&self, tombstone: RangeTombstone, deletions: Vec<(Revision, Vec<u8>)>) {
+        self.inner
+            .range_tombstones
+            .write()
+            .add(tombstone, deletions);
+        let _ignore = self.range_tombstone_added.notify(usize::MAX);
+    }
+
+    /// Listen to the range tombstones added after it
+    pub(crate) fn range_tombstone_listener(&self) -> event_listener::EventListener {
+        self.range_tombstone_added.listen()
+    }
+
+    /// Write at most `limit` deletions of the oldest range tombstone at or below `until`
+    /// to the kv table, and remove the range tombstone once all of its deletions are
+    /// written. Return `false` if there is no such range tombstone.
+    pub(crate) fn write_range_tombstones(
+        &self,
+        until: i64,
+        limit: usize,
+    ) -> Result<bool, ExecuteError> {
+        let _writer = self.range_tombstone_writer.lock();
+        let Some((revision, deletions, last)) =
+            self.inner.range_tombstones.read().next_batch(until, limit)
+        else {
+            return Ok(false);
+        };
+        let count = deletions.len();
+        let mut ops: Vec<_> = deletions
+            .into_iter()
+            .map(|(rev, key)| WriteOp::PutKeyValue(rev, deletion_kv(rev, key)))
+            .collect();
+        if last {
+            ops.push(WriteOp::DeleteRangeTombstone(revision));
+        }
+        // the deletions are already in the index
+        _ = self.inner.db.write_batch(ops, false)?;
+        self.inner.range_tombstones.write().written(revision, count);
+        Ok(true)
+    }
+
+    /// Drop the range tombstones that are not written, which is used when the storage is
+    /// reset
+    pub(crate) fn clear_range_tombstones(&self) {
+        let _writer = self.range_tombstone_writer.lock();
+        *self.inner.range_tombstones.write() = PendingTombstones::default();
+    }
+
+    /// Write all deletions of the range tombstones at or below `until` to the kv table
+    pub(crate) fn write_all_range_tombstones(&self, until: i64) -> Result<(), ExecuteError> {
+        while self.write_range_tombstones(until, usize::MAX)? {}
+        Ok(())
+    }
+
+    /// Read the storage along with the deletions of the range tombstones that are not
+    /// written yet, encoded as they will be written to the kv table. The deletions are
+    /// written at different times on the members, so they are read this way wherever the
+    /// members must agree on the storage. No deletion is written during the read.
+    fn read_with_pending_deletions<R>(
+        &self,
+        read: impl FnOnce(&DB, Vec<(Vec<u8>, Vec<u8>)>) -> Result<R, ExecuteError>,
+    ) -> Result<R, ExecuteError> {
+        let _writer = self.range_tombstone_writer.lock();
+        let pending = self
+            .inner
+            .range_tombstones
+            .read()
+            .deletions()
+            .map(|(&rev, key)| {
+                (
+                    rev.encode_to_vec(),
+                    deletion_kv(rev, key.clone()).encode_to_vec(),
+                )
+            })
+            .collect();
+        read(&self.inner.db, pending)
+    }
+
+    /// Calculate the hash of the storage, see `StorageApi::hash`
+    pub(crate) fn hash(&self) -> Result<u32, ExecuteError> {
+        self.read_with_pending_deletions(|db, pending| db.hash(pending))
+    }
+
+    /// Check at most `limit` values and re-encrypt those encrypted by retired data keys,
+    /// return the number of checked values
+    pub(crate) fn reencrypt(&self, limit: usize) -> Result<usize, ExecuteError> {
+        self.inner.db.reencrypt(limit)
+    }
+
     /// Calculate hash of kv storage
     pub(crate) fn hash_kv(&self, mut rev: i64) -> Result<(u32, i64, i64), ExecuteError> {
         let (compact_rev, current_rev) = (self.compacted_revision(), self.revision());
@@ -514,12 +910,20 @@ where
         if rev <= 0 {
             rev = current_rev;
         }
-        let keep = self.inner.index.keep(rev);
+        // revisions below the compacted revision are only hashed if they survive the
+        // compaction, so the hash won't change before and after the physical compaction
+        let keep = self.inner.index.keep(compact_rev);
         let upper = Revision::new(rev.overflow_add(1), 0);
         let lower = Revision::new(compact_rev, 0);
         let mut hasher = crc32fast::Hasher::new();
         hasher.update(KV_TABLE.as_bytes());
-        let kv_pairs = self.inner.db.get_all(KV_TABLE)?;
+        let kv_pairs = self.read_with_pending_deletions(|db, pending| {
+            Ok(db
+                .get_all(KV_TABLE)?
+                .into_iter()
+                .chain(pending)
+                .collect::<BTreeMap<_, _>>())
+        })?;
         for (k, v) in kv_pairs {
             let kr = Revision::decode(&k);
             if upper <= kr {
@@ -567,24 +971,35 @@ where
     fn handle_range_request(&self, req: &RangeRequest) -> Result<RangeResponse, ExecuteError> {
         req.check_revision(self.compacted_revision(), self.revision())?;
 
-        let storage_fetch_limit = if (req.sort_order() != SortOrder::None)
-            || (req.max_mod_revision != 0)
-            || (req.min_mod_revision != 0)
-            || (req.max_create_revision != 0)
-            || (req.min_create_revision != 0)
-            || (req.limit == 0)
-        {
-            0 // get all from storage then sort and filter
+        // revision filters are applied on the index, so only sorting needs all kvs
+        let storage_fetch_limit = if (req.sort_order() != SortOrder::None) || (req.limit == 0) {
+            0 // get all from storage then sort
         } else {
             req.limit.overflow_add(1) // get one extra for "more" flag
         };
-        let (mut kvs, total) = self.inner.get_range_with_opts(
-            &req.key,
-            &req.range_end,
-            req.revision,
-            storage_fetch_limit.numeric_cast(),
-            req.count_only,
-        )?;
+        // Leases are only tracked for the latest revision, so only keys-only ranges
+        // on the latest revision could be served by the index alone
+        let (mut kvs, total) = if req.keys_only
+            && !req.count_only
+            && req.sort_target() != SortTarget::Value
+            && (req.revision <= 0 || req.revision == self.revision())
+        {
+            self.get_keys_from_index(
+                &req.key,
+                &req.range_end,
+                storage_fetch_limit.numeric_cast(),
+                |rev| Self::match_revision_filters(req, rev),
+            )
+        } else {
+            self.inner.get_range_with_opts(
+                &req.key,
+                &req.range_end,
+                req.revision,
+                storage_fetch_limit.numeric_cast(),
+                req.count_only,
+                |rev| Self::match_revision_filters(req, rev),
+            )?
+        };
         let mut response = RangeResponse {
             header: Some(self.header_gen.gen_header()),
             count: total.numeric_cast(),
@@ -594,13 +1009,6 @@ where
             return Ok(response);
         }
 
-        Self::filter_kvs(
-            &mut kvs,
-            req.max_mod_revision,
-            req.min_mod_revision,
-            req.max_create_revision,
-            req.min_create_revision,
-        );
         Self::sort_kvs(&mut kvs, req.sort_order(), req.sort_target());
 
         if (req.limit > 0) && (kvs.len() > req.limit.numeric_cast()) {
@@ -623,15 +1031,20 @@ where
         if req.lease != 0 && self.lease_collection.look_up(req.lease).is_none() {
             return Err(ExecuteError::LeaseNotFound(req.lease));
         };
+        let mut value_len = req.value.len();
         if req.prev_kv || req.ignore_lease || req.ignore_value {
             let prev_kv = self.inner.get_range(&req.key, &[], 0)?.pop();
             if prev_kv.is_none() && (req.ignore_lease || req.ignore_value) {
                 return Err(ExecuteError::KeyNotFound);
             }
+            if req.ignore_value {
+                value_len = prev_kv.as_ref().map_or(0, |kv| kv.value.len());
+            }
             if req.prev_kv {
                 response.prev_kv = prev_kv;
             }
         };
+        self.tenants.check_put(&req.key, value_len)?;
         Ok(response)
     }
 
@@ -640,14 +1053,22 @@ where
         &self,
         req: &DeleteRangeRequest,
     ) -> Result<DeleteRangeResponse, ExecuteError> {
-        let prev_kvs = self.inner.get_range(&req.key, &req.range_end, 0)?;
         let mut response = DeleteRangeResponse {
             header: Some(self.header_gen.gen_header()),
             ..DeleteRangeResponse::default()
         };
-        response.deleted = prev_kvs.len().numeric_cast();
+        // the values are only read if they are returned
         if req.prev_kv {
+            let prev_kvs = self.inner.get_range(&req.key, &req.range_end, 0)?;
+            response.deleted = prev_kvs.len().numeric_cast();
             response.prev_kvs = prev_kvs;
+        } else {
+            response.deleted = self
+                .inner
+                .index
+                .get(&req.key, &req.range_end, 0)
+                .len()
+                .numeric_cast();
         }
         Ok(response)
     }
@@ -700,8 +1121,9 @@ where
         &self,
         wrapper: &RequestWrapper,
         revision: i64,
-    ) -> Result<(i64, Vec<WriteOp>), ExecuteError> {
+    ) -> Result<(i64, Vec<WriteOp>, Vec<Event>, Vec<DeleteRangeRequest>), ExecuteError> {
         debug!("After Sync {:?} with revision {}", wrapper, revision);
+        let mut deletions = Vec::new();
         #[allow(clippy::wildcard_enum_match_arm)] // only kv requests can be sent to kv store
         let (ops, events) = match *wrapper {
             RequestWrapper::RangeRequest(_) => (Vec::new(), Vec::new()),
@@ -709,7 +1131,9 @@ where
             RequestWrapper::DeleteRangeRequest(ref req) => {
                 self.sync_delete_range_request(req, revision, 0)
             }
-            RequestWrapper::TxnRequest(ref req) => self.sync_txn_request(req, revision)?,
+            RequestWrapper::TxnRequest(ref req) => {
+                self.sync_txn_request(req, revision, &mut deletions)?
+            }
             RequestWrapper::CompactionRequest(ref req) => {
                 self.sync_compaction_request(req, revision).await?
             }
@@ -717,8 +1141,7 @@ where
                 unreachable!("only kv requests can be sent to kv store");
             }
         };
-        self.notify_updates(revision, events).await;
-        Ok((revision, ops))
+        Ok((revision, ops, events, deletions))
     }
 
     /// Sync `CompactionRequest` and return if kvstore is changed
@@ -746,11 +1169,13 @@ where
         Ok((ops, Vec::new()))
     }
 
-    /// Sync `TxnRequest` and return if kvstore is changed
+    /// Sync `TxnRequest` and return if kvstore is changed, the applied delete range
+    /// requests are appended to `deletions`
     fn sync_txn_request(
         &self,
         req: &TxnRequest,
         revision: i64,
+        deletions: &mut Vec<DeleteRangeRequest>,
     ) -> Result<(Vec<WriteOp>, Vec<Event>), ExecuteError> {
         let mut sub_revision = 0;
         let mut origin_reqs = VecDeque::from([Request::RequestTxn(req.clone())]);
@@ -763,7 +1188,9 @@ where
                     self.sync_put_request(put_req, revision, sub_revision)?
                 }
                 Request::RequestDeleteRange(del_req) => {
-                    self.sync_delete_range_request(&del_req, revision, sub_revision)
+                    let synced = self.sync_delete_range_request(&del_req, revision, sub_revision);
+                    deletions.push(del_req);
+                    synced
                 }
                 Request::RequestTxn(txn_req) => {
                     let success = txn_req
@@ -775,7 +1202,10 @@ where
                     } else {
                         txn_req.failure.into_iter()
                     };
-                    origin_reqs.extend(reqs_iter.filter_map(|req_op| req_op.request));
+                    // requests of a nested txn are applied before the requests after it
+                    for req in reqs_iter.filter_map(|req_op| req_op.request).rev() {
+                        origin_reqs.push_front(req);
+                    }
                     continue;
                 }
             };
@@ -794,38 +1224,47 @@ where
         sub_revision: i64,
     ) -> Result<(Vec<WriteOp>, Vec<Event>), ExecuteError> {
         let mut ops = Vec::new();
+        let (value, lease) = if req.ignore_lease || req.ignore_value {
+            let prev_kv = self.inner.get_range(&req.key, &[], 0)?.pop();
+            let prev = prev_kv.ok_or(ExecuteError::KeyNotFound)?;
+            (
+                if req.ignore_value {
+                    prev.value
+                } else {
+                    req.value.clone()
+                },
+                if req.ignore_lease {
+                    prev.lease
+                } else {
+                    req.lease
+                },
+            )
+        } else {
+            (req.value.clone(), req.lease)
+        };
         let new_rev = self
             .inner
             .index
             .register_revision(&req.key, revision, sub_revision);
-        let mut kv = KeyValue {
+        let kv = KeyValue {
             key: req.key.clone(),
-            value: req.value.clone(),
+            value,
             create_revision: new_rev.create_revision,
             mod_revision: new_rev.mod_revision,
             version: new_rev.version,
-            lease: req.lease,
+            lease,
         };
-        if req.ignore_lease || req.ignore_value {
-            let prev_kv = self.inner.get_range(&req.key, &[], 0)?.pop();
-            let prev = prev_kv.as_ref().ok_or(ExecuteError::KeyNotFound)?;
-            if req.ignore_lease {
-                kv.lease = prev.lease;
-            }
-            if req.ignore_value {
-                kv.value = prev.value.clone();
-            }
-        }
 
         let old_lease = self.get_lease(&kv.key);
         if old_lease != 0 {
             self.detach(old_lease, kv.key.as_slice())
                 .unwrap_or_else(|e| warn!("Failed to detach lease from a key, error: {:?}", e));
         }
-        if req.lease != 0 {
-            self.attach(req.lease, kv.key.as_slice())
+        if kv.lease != 0 {
+            self.attach(kv.lease, kv.key.as_slice())
                 .unwrap_or_else(|e| panic!("unexpected error from lease Attach: {e}"));
         }
+        self.tenants.put(&kv.key, kv.value.len());
         ops.push(WriteOp::PutKeyValue(new_rev.as_revision(), kv.clone()));
         let event = Event {
             #[allow(clippy::as_conversions)] // This cast is always valid
@@ -874,44 +1313,92 @@ where
             .collect()
     }
 
-    /// Sync `DeleteRangeRequest` and return if kvstore is changed
+    /// Sync `DeleteRangeRequest` and return if kvstore is changed. A range tombstone is
+    /// written instead of a tombstone per key if many keys are deleted, and the
+    /// tombstones of the keys are written in the background.
     fn sync_delete_range_request(
         &self,
         req: &DeleteRangeRequest,
         revision: i64,
         sub_revision: i64,
     ) -> (Vec<WriteOp>, Vec<Event>) {
-        Self::delete_keys(
+        let (revisions, keys) = Self::delete_index_keys(
             &self.inner.index,
             &self.lease_collection,
+            &self.tenants,
             &req.key,
             &req.range_end,
             revision,
             sub_revision,
-        )
+        );
+        if keys.len() < RANGE_TOMBSTONE_MIN_KEYS {
+            let ops = Self::mark_deletions(&revisions, &keys);
+            return (ops, Self::new_deletion_events(revision, keys));
+        }
+        let tombstone = RangeTombstone::new(
+            req.key.clone(),
+            req.range_end.clone(),
+            revision,
+            sub_revision,
+        );
+        let ops = vec![WriteOp::PutRangeTombstone(
+            tombstone.first_revision(),
+            tombstone.encode(),
+        )];
+        let deletions = revisions
+            .into_iter()
+            .map(|(_, del_rev)| del_rev)
+            .zip(keys.iter().cloned())
+            .collect();
+        self.add_range_tombstone(tombstone, deletions);
+        (ops, Self::new_deletion_events(revision, keys))
     }
 
-    /// Delete keys from index and detach them in lease collection, return all the write operations and events
+    /// Delete keys from index, detach them in lease collection and remove them from the
+    /// usages of tenants, return all the write operations and events
     pub(crate) fn delete_keys<'a>(
         index: &Index,
         lease_collection: &LeaseCollection,
+        tenants: &Tenants,
         key: &[u8],
         range_end: &[u8],
         revision: i64,
         sub_revision: i64,
     ) -> (Vec<WriteOp<'a>>, Vec<Event>) {
-        let mut ops = Vec::new();
+        let (revisions, keys) = Self::delete_index_keys(
+            index,
+            lease_collection,
+            tenants,
+            key,
+            range_end,
+            revision,
+            sub_revision,
+        );
+        let ops = Self::mark_deletions(&revisions, &keys);
+        let events = Self::new_deletion_events(revision, keys);
+        (ops, events)
+    }
+
+    /// Delete keys from index, detach them in lease collection and remove them from the
+    /// usages of tenants, return the revision pairs and the deleted keys
+    fn delete_index_keys(
+        index: &Index,
+        lease_collection: &LeaseCollection,
+        tenants: &Tenants,
+        key: &[u8],
+        range_end: &[u8],
+        revision: i64,
+        sub_revision: i64,
+    ) -> (Vec<(Revision, Revision)>, Vec<Vec<u8>>) {
         let (revisions, keys) = index.delete(key, range_end, revision, sub_revision);
-        let mut del_ops = Self::mark_deletions(&revisions, &keys);
-        ops.append(&mut del_ops);
         for k in &keys {
             let lease_id = lease_collection.get_lease(k);
             lease_collection
                 .detach(lease_id, k)
                 .unwrap_or_else(|e| warn!("Failed to detach lease from a key, error: {:?}", e));
+            tenants.delete(k);
         }
-        let events = Self::new_deletion_events(revision, keys);
-        (ops, events)
+        (revisions, keys)
     }
 
     /// Insert the given pairs (key, `KeyRevision`) into the index
@@ -928,7 +1415,10 @@ mod test {
     use test_macros::abort_on_panic;
     use tokio::{runtime::Handle, task::block_in_place};
     use utils::{
-        config::EngineConfig,
+        config::{
+            default_watcher_buffer_size, EngineConfig, KvUpdateBackpressure, SlowWatcherPolicy,
+            TenantQuota,
+        },
         task_manager::{tasks::TaskName, TaskManager},
     };
 
@@ -939,7 +1429,7 @@ mod test {
         storage::{
             compact::{compact_bg_task, COMPACT_CHANNEL_SIZE},
             db::DB,
-            kvwatcher::KvWatcher,
+            kvwatcher::{kv_update_channel, KvWatcher},
         },
     };
 
@@ -1001,9 +1491,14 @@ mod test {
     }
 
     fn init_empty_store(db: Arc<DB>) -> StoreWrapper {
+        init_empty_store_with_tenants(db, Arc::default())
+    }
+
+    fn init_empty_store_with_tenants(db: Arc<DB>, tenants: Arc<Tenants>) -> StoreWrapper {
         let task_manager = Arc::new(TaskManager::new());
         let (compact_tx, compact_rx) = mpsc::channel(COMPACT_CHANNEL_SIZE);
-        let (kv_update_tx, kv_update_rx) = mpsc::channel(CHANNEL_SIZE);
+        let (kv_update_tx, kv_update_rx) =
+            kv_update_channel(CHANNEL_SIZE, KvUpdateBackpressure::default());
         let lease_collection = Arc::new(LeaseCollection::new(0));
         let header_gen = Arc::new(HeaderGenerator::new(0, 0));
         let index = Arc::new(Index::new());
@@ -1014,11 +1509,14 @@ mod test {
             kv_update_tx,
             compact_tx,
             lease_collection,
+            tenants,
         ));
         let _watcher = KvWatcher::new_arc(
             kv_store_inner,
             kv_update_rx,
             Duration::from_millis(10),
+            default_watcher_buffer_size(),
+            SlowWatcherPolicy::default(),
             &task_manager,
         );
         task_manager.spawn(TaskName::CompactBg, |n| {
@@ -1039,9 +1537,10 @@ mod test {
         request: &RequestWrapper,
         revision: i64,
     ) -> Result<(), ExecuteError> {
-        let (_sync_res, ops) = store.after_sync(request, revision).await?;
-        let key_revs = store.inner.db.flush_ops(ops)?;
+        let (_sync_res, ops, events, _deletions) = store.after_sync(request, revision).await?;
+        let key_revs = store.inner.db.write_batch(ops, false)?;
         store.insert_index(key_revs);
+        store.notify_updates(revision, events, revision).await;
         Ok(())
     }
 
@@ -1074,6 +1573,76 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    #[abort_on_panic]
+    async fn test_keys_only_should_match_full_range() -> Result<(), ExecuteError> {
+        let db = DB::open(&EngineConfig::Memory)?;
+        let (store, _rev) = init_store(db).await?;
+        let request = RangeRequest {
+            key: vec![0],
+            range_end: vec![0],
+            limit: 4,
+            ..Default::default()
+        };
+        let mut expected = store.handle_range_request(&request)?;
+        expected.kvs.iter_mut().for_each(|kv| kv.value.clear());
+        let response = store.handle_range_request(&RangeRequest {
+            keys_only: true,
+            ..request
+        })?;
+        assert_eq!(response.kvs, expected.kvs);
+        assert_eq!(response.count, expected.count);
+        assert_eq!(response.more, expected.more);
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    #[abort_on_panic]
+    async fn test_put_ignore_value_and_lease() -> Result<(), ExecuteError> {
+        let db = DB::open(&EngineConfig::Memory)?;
+        let store = init_empty_store(db);
+        let revision = RevisionNumberGenerator::default();
+        let _lease = store.lease_collection.grant(1, 60, false);
+
+        let ignore_value = PutRequest {
+            key: "foo".into(),
+            lease: 1,
+            ignore_value: true,
+            ..Default::default()
+        };
+        assert!(matches!(
+            store.handle_put_request(&ignore_value),
+            Err(ExecuteError::KeyNotFound)
+        ));
+
+        let put = RequestWrapper::from(PutRequest {
+            key: "foo".into(),
+            value: "bar".into(),
+            lease: 1,
+            ..Default::default()
+        });
+        exe_as_and_flush(&store, &put, revision.next()).await?;
+        let req = RequestWrapper::from(ignore_value);
+        exe_as_and_flush(&store, &req, revision.next()).await?;
+        let kv = store.inner.get_range(b"foo", &[], 0)?.pop().unwrap();
+        assert_eq!(kv.value, b"bar");
+
+        let req = RequestWrapper::from(PutRequest {
+            key: "foo".into(),
+            value: "baz".into(),
+            ignore_lease: true,
+            ..Default::default()
+        });
+        exe_as_and_flush(&store, &req, revision.next()).await?;
+
+        let kv = store.inner.get_range(b"foo", &[], 0)?.pop().unwrap();
+        assert_eq!(kv.value, b"baz");
+        assert_eq!(kv.lease, 1);
+        assert_eq!(kv.version, 3);
+        assert_eq!(store.lease_collection.get_lease(b"foo"), 1);
+        Ok(())
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     #[abort_on_panic]
     async fn test_range_empty() -> Result<(), ExecuteError> {
@@ -1115,6 +1684,35 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    #[abort_on_panic]
+    async fn test_range_filter_with_limit() -> Result<(), ExecuteError> {
+        let db = DB::open(&EngineConfig::Memory)?;
+        let (store, _rev) = init_store(db).await?;
+
+        let request = RangeRequest {
+            key: vec![0],
+            range_end: vec![0],
+            min_mod_revision: 3,
+            limit: 2,
+            ..Default::default()
+        };
+        let response = store.handle_range_request(&request)?;
+        assert_eq!(response.count, 6);
+        assert_eq!(response.kvs.len(), 2);
+        assert!(response.more);
+        assert!(response.kvs.iter().all(|kv| kv.mod_revision >= 3));
+
+        let response = store.handle_range_request(&RangeRequest {
+            keys_only: true,
+            ..request
+        })?;
+        assert_eq!(response.kvs.len(), 2);
+        assert!(response.more);
+        assert!(response.kvs.iter().all(|kv| kv.mod_revision >= 3));
+        Ok(())
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     #[abort_on_panic]
     async fn test_range_sort() -> Result<(), ExecuteError> {
@@ -1178,7 +1776,7 @@ mod test {
     async fn test_recover() -> Result<(), ExecuteError> {
         let db = DB::open(&EngineConfig::Memory)?;
         let ops = vec![WriteOp::PutScheduledCompactRevision(8)];
-        db.flush_ops(ops)?;
+        db.write_batch(ops, false)?;
         let (store, _rev_gen) = init_store(Arc::clone(&db)).await?;
         assert_eq!(store.inner.index.get_from_rev(b"z", b"", 5).len(), 3);
 
@@ -1205,6 +1803,213 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    #[abort_on_panic]
+    async fn revisions_should_be_restored_in_shards() -> Result<(), ExecuteError> {
+        let db = DB::open(&EngineConfig::Memory)?;
+        let store = init_empty_store(db);
+        let kvs: Vec<_> = (1..=3 * MIN_REVISIONS_PER_SHARD)
+            .map(|i| {
+                let revision: i64 = i.numeric_cast();
+                let kv = KeyValue {
+                    key: format!("key{}", i % 100).into_bytes(),
+                    value: b"value".to_vec(),
+                    create_revision: revision,
+                    mod_revision: revision,
+                    version: 1,
+                    lease: revision % 3,
+                };
+                (
+                    Revision::new(revision, 0).encode_to_vec(),
+                    kv.encode_to_vec(),
+                )
+            })
+            .collect();
+        let mut key_to_lease = HashMap::new();
+        store.restore_revisions(&kvs, &mut key_to_lease);
+
+        let revisions = store.inner.index.snapshot(i64::MAX);
+        assert_eq!(revisions.len(), 100);
+        for (_, key_revisions) in revisions {
+            assert_eq!(key_revisions.len(), 3 * MIN_REVISIONS_PER_SHARD / 100);
+            assert!(key_revisions
+                .windows(2)
+                .all(|pair| pair[0].mod_revision < pair[1].mod_revision));
+        }
+        // the last revision of each key decides its lease
+        let last = (3 * MIN_REVISIONS_PER_SHARD - 99..=3 * MIN_REVISIONS_PER_SHARD)
+            .filter(|i| i % 3 != 0)
+            .count();
+        assert_eq!(key_to_lease.len(), last);
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    #[abort_on_panic]
+    async fn test_range_tombstone() -> Result<(), ExecuteError> {
+        let db = DB::open(&EngineConfig::Memory)?;
+        let store = init_empty_store(Arc::clone(&db));
+        let revision = RevisionNumberGenerator::default();
+        for i in 0..RANGE_TOMBSTONE_MIN_KEYS {
+            let req = RequestWrapper::from(PutRequest {
+                key: format!("key{i:04}").into_bytes(),
+                value: b"value".to_vec(),
+                ..Default::default()
+            });
+            exe_as_and_flush(&store, &req, revision.next()).await?;
+        }
+        let del_req = RequestWrapper::from(DeleteRangeRequest {
+            key: b"key".to_vec(),
+            range_end: b"kez".to_vec(),
+            ..Default::default()
+        });
+        let del_revision = revision.next();
+        exe_as_and_flush(&store, &del_req, del_revision).await?;
+        // only the range tombstone is written
+        assert_eq!(db.get_all(KV_TABLE)?.len(), RANGE_TOMBSTONE_MIN_KEYS);
+        let range_req = RangeRequest {
+            key: b"key".to_vec(),
+            range_end: b"kez".to_vec(),
+            ..Default::default()
+        };
+        assert!(store.handle_range_request(&range_req)?.kvs.is_empty());
+        let events = store
+            .inner
+            .get_event_from_revision(KeyRange::new("key", "kez"), del_revision)?;
+        assert_eq!(events.len(), RANGE_TOMBSTONE_MIN_KEYS);
+        assert!(events
+            .iter()
+            .all(|event| event.r#type() == EventType::Delete));
+
+        // the deletions are restored from the range tombstone
+        let new_store = init_empty_store(Arc::clone(&db));
+        new_store.recover().await?;
+        assert_eq!(new_store.revision(), del_revision);
+        assert!(new_store.handle_range_request(&range_req)?.kvs.is_empty());
+        assert_eq!(
+            new_store.inner.index.snapshot(i64::MAX),
+            store.inner.index.snapshot(i64::MAX)
+        );
+
+        // hashing hashes the pending deletions without writing them
+        let kv_hash = store.hash_kv(0)?;
+        let hash = store.hash()?;
+        assert_eq!(db.get_all(KV_TABLE)?.len(), RANGE_TOMBSTONE_MIN_KEYS);
+        assert!(!db.get_from(META_TABLE, RANGE_TOMBSTONE_PREFIX)?.is_empty());
+
+        store.write_all_range_tombstones(i64::MAX)?;
+        assert_eq!(store.hash_kv(0)?, kv_hash);
+        assert_eq!(store.hash()?, hash);
+        assert_eq!(db.get_all(KV_TABLE)?.len(), 2 * RANGE_TOMBSTONE_MIN_KEYS);
+        assert!(db
+            .get_from(META_TABLE, RANGE_TOMBSTONE_PREFIX)?
+            .iter()
+            .all(|&(ref key, _)| !key.starts_with(RANGE_TOMBSTONE_PREFIX)));
+        let events = store
+            .inner
+            .get_event_from_revision(KeyRange::new("key", "kez"), del_revision)?;
+        assert_eq!(events.len(), RANGE_TOMBSTONE_MIN_KEYS);
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    #[abort_on_panic]
+    async fn test_recover_from_index_snapshot() -> Result<(), ExecuteError> {
+        let db = DB::open(&EngineConfig::Memory)?;
+        let (store, rev_gen) = init_store(Arc::clone(&db)).await?;
+        let snapshot = store.index_snapshot(rev_gen.get());
+        let _ignore = db.write_batch(vec![WriteOp::PutIndexSnapshot(snapshot.encode())], false)?;
+        let put_req = RequestWrapper::from(PutRequest {
+            key: b"a".to_vec(),
+            value: b"a1".to_vec(),
+            ..Default::default()
+        });
+        exe_as_and_flush(&store, &put_req, rev_gen.next()).await?;
+        let del_req = RequestWrapper::from(DeleteRangeRequest {
+            key: b"b".to_vec(),
+            ..Default::default()
+        });
+        exe_as_and_flush(&store, &del_req, rev_gen.next()).await?;
+
+        let new_store = init_empty_store(db);
+        new_store.recover().await?;
+        assert_eq!(new_store.revision(), rev_gen.get());
+        assert_eq!(
+            new_store.inner.index.snapshot(i64::MAX),
+            store.inner.index.snapshot(i64::MAX)
+        );
+        let range_req = RangeRequest {
+            key: vec![0],
+            range_end: vec![0],
+            ..Default::default()
+        };
+        let res = new_store.handle_range_request(&range_req)?;
+        assert_eq!(res.kvs.len(), 5);
+        assert_eq!(res.kvs[0].value, b"a1");
+
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    #[abort_on_panic]
+    async fn test_tenant_quota() -> Result<(), ExecuteError> {
+        let quotas = vec![TenantQuota::new("t/".to_owned(), 2, 0)];
+        let db = DB::open(&EngineConfig::Memory)?;
+        let store =
+            init_empty_store_with_tenants(Arc::clone(&db), Arc::new(Tenants::new(quotas.clone())));
+        let revision = RevisionNumberGenerator::default();
+        for key in ["t/a", "t/b", "other"] {
+            let req = RequestWrapper::from(PutRequest {
+                key: key.into(),
+                value: "value".into(),
+                ..Default::default()
+            });
+            store.execute(&req)?;
+            exe_as_and_flush(&store, &req, revision.next()).await?;
+        }
+        let put_c = RequestWrapper::from(PutRequest {
+            key: "t/c".into(),
+            value: "value".into(),
+            ..Default::default()
+        });
+        assert!(matches!(
+            store.execute(&put_c),
+            Err(ExecuteError::TenantQuotaExceeded(_))
+        ));
+        let txn = RequestWrapper::from(TxnRequest {
+            compare: vec![],
+            success: vec![RequestOp {
+                request: Some(UniRequest::RequestPut(PutRequest {
+                    key: "t/c".into(),
+                    ..Default::default()
+                })),
+            }],
+            failure: vec![],
+        });
+        assert!(matches!(
+            store.execute(&txn),
+            Err(ExecuteError::TenantQuotaExceeded(_))
+        ));
+
+        let delete_a = RequestWrapper::from(DeleteRangeRequest {
+            key: "t/a".into(),
+            ..Default::default()
+        });
+        exe_as_and_flush(&store, &delete_a, revision.next()).await?;
+        store.execute(&put_c)?;
+        exe_as_and_flush(&store, &put_c, revision.next()).await?;
+        let usage = &store.tenants().usages()[0];
+        assert_eq!((usage.keys, usage.bytes), (2, 16));
+
+        let new_store = init_empty_store_with_tenants(db, Arc::new(Tenants::new(quotas)));
+        new_store.recover().await?;
+        assert_eq!(new_store.tenants().usages(), store.tenants().usages());
+
+        Ok(())
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     #[abort_on_panic]
     async fn test_txn() -> Result<(), ExecuteError> {
@@ -1259,6 +2064,78 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    #[abort_on_panic]
+    async fn test_nested_txn_with_lease_and_range_compares() -> Result<(), ExecuteError> {
+        let db = DB::open(&EngineConfig::Memory)?;
+        let (store, rev) = init_store(db).await?;
+        let _lease = store.lease_collection.grant(1, 60, false);
+        let put_with_lease = RequestWrapper::from(PutRequest {
+            key: "l".into(),
+            value: "l".into(),
+            lease: 1,
+            ..Default::default()
+        });
+        exe_as_and_flush(&store, &put_with_lease, rev.next()).await?;
+
+        let put_op = |key: &str| RequestOp {
+            request: Some(Request::RequestPut(PutRequest {
+                key: key.into(),
+                value: "1".into(),
+                ..Default::default()
+            })),
+        };
+        let txn_req = RequestWrapper::from(TxnRequest {
+            compare: vec![
+                Compare {
+                    result: CompareResult::Equal as i32,
+                    target: CompareTarget::Lease as i32,
+                    key: "l".into(),
+                    range_end: vec![],
+                    target_union: Some(TargetUnion::Lease(1)),
+                },
+                Compare {
+                    result: CompareResult::Equal as i32,
+                    target: CompareTarget::Version as i32,
+                    key: "a".into(),
+                    range_end: "f".into(),
+                    target_union: Some(TargetUnion::Version(1)),
+                },
+            ],
+            success: vec![
+                RequestOp {
+                    request: Some(Request::RequestTxn(TxnRequest {
+                        compare: vec![Compare {
+                            result: CompareResult::Less as i32,
+                            target: CompareTarget::Mod as i32,
+                            key: "a".into(),
+                            range_end: vec![0],
+                            target_union: Some(TargetUnion::ModRevision(2)),
+                        }],
+                        success: vec![put_op("nested_success")],
+                        failure: vec![put_op("nested_failure")],
+                    })),
+                },
+                put_op("outer"),
+            ],
+            failure: vec![put_op("failure")],
+        });
+        let (_sync_res, ops, _events, _deletions) = store.after_sync(&txn_req, rev.next()).await?;
+        let keys: Vec<_> = ops
+            .iter()
+            .filter_map(|op| {
+                if let WriteOp::PutKeyValue(_, ref kv) = *op {
+                    Some(kv.key.as_slice())
+                } else {
+                    None
+                }
+            })
+            .collect();
+        assert_eq!(keys, [b"nested_failure".as_slice(), b"outer".as_slice()]);
+
+        Ok(())
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     #[abort_on_panic]
     async fn test_kv_store_index_available() {
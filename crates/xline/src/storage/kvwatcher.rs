@@ -8,19 +8,25 @@ use std::{
     time::Duration,
 };
 
+use fail::fail_point;
 use itertools::Itertools;
 use parking_lot::RwLock;
 use tokio::{
-    sync::mpsc::{self, error::TrySendError},
+    sync::{
+        mpsc::{self, error::TrySendError},
+        Notify,
+    },
     time::sleep,
 };
 use tracing::{debug, warn};
 use utils::{
+    config::{KvUpdateBackpressure, SlowWatcherPolicy},
+    interval_map::{Interval, IntervalMap},
     parking_lot_lock::RwLockMap,
     task_manager::{tasks::TaskName, Listener, TaskManager},
     write_vec,
 };
-use xlineapi::command::KeyRange;
+use xlineapi::{command::KeyRange, interval::BytesAffine};
 
 use super::{kv_store::KvStoreInner, storage_api::StorageApi};
 use crate::rpc::{Event, KeyValue};
@@ -44,6 +50,87 @@ impl WatchIdGenerator {
     }
 }
 
+/// Sender of the kv updates to the `KvWatcher`
+#[derive(Debug, Clone)]
+pub(crate) struct KvUpdateTx {
+    /// Sender of the updates
+    tx: mpsc::Sender<(i64, Vec<Event>)>,
+    /// Backpressure applied when the channel is full
+    backpressure: KvUpdateBackpressure,
+    /// Updates dropped because the channel is full
+    overflow: Arc<KvUpdateOverflow>,
+}
+
+/// Receiver of the kv updates, see `KvUpdateTx`
+#[derive(Debug)]
+pub(crate) struct KvUpdateRx {
+    /// Receiver of the updates
+    rx: mpsc::Receiver<(i64, Vec<Event>)>,
+    /// Updates dropped because the channel is full
+    overflow: Arc<KvUpdateOverflow>,
+}
+
+/// Kv updates dropped by `KvUpdateBackpressure::MarkVictims`
+#[derive(Debug)]
+struct KvUpdateOverflow {
+    /// The watchers need to catch up with the events from this revision, `i64::MAX` if
+    /// no update is dropped
+    resync_from: AtomicI64,
+    /// Notified when updates are dropped
+    dropped: Notify,
+}
+
+/// Create the channel of the kv updates sent to the `KvWatcher`
+pub(crate) fn kv_update_channel(
+    size: usize,
+    backpressure: KvUpdateBackpressure,
+) -> (KvUpdateTx, KvUpdateRx) {
+    let (tx, rx) = mpsc::channel(size);
+    let overflow = Arc::new(KvUpdateOverflow {
+        resync_from: AtomicI64::new(i64::MAX),
+        dropped: Notify::new(),
+    });
+    (
+        KvUpdateTx {
+            tx,
+            backpressure,
+            overflow: Arc::clone(&overflow),
+        },
+        KvUpdateRx { rx, overflow },
+    )
+}
+
+impl KvUpdateTx {
+    /// Send the updates of a revision to the `KvWatcher`. If the channel is full, the
+    /// sender waits until there is room, or drops the updates and lets the watchers catch
+    /// up from the storage, depending on the backpressure. The updates of all revisions
+    /// below `floor` must be sent already, and all updates must be persisted before they
+    /// are sent, so that they can be read by the watchers that catch up.
+    pub(crate) async fn send(&self, revision: i64, updates: Vec<Event>, floor: i64) {
+        let sent = match self.backpressure {
+            KvUpdateBackpressure::Block => self.tx.send((revision, updates)).await.is_ok(),
+            KvUpdateBackpressure::MarkVictims => match self.tx.try_send((revision, updates)) {
+                Ok(()) => true,
+                Err(TrySendError::Full(_)) => {
+                    warn!(
+                        revision,
+                        "kv update channel is full, watchers will catch up from the storage"
+                    );
+                    let _prev = self
+                        .overflow
+                        .resync_from
+                        .fetch_min(floor.min(revision), Ordering::AcqRel);
+                    self.overflow.dropped.notify_one();
+                    true
+                }
+                Err(TrySendError::Closed(_)) => false,
+            },
+            _ => unreachable!("unknown kv update backpressure"),
+        };
+        assert!(sent, "Failed to send updates to KV watcher");
+    }
+}
+
 /// Watcher
 #[derive(Debug)]
 struct Watcher {
@@ -61,6 +148,8 @@ struct Watcher {
     event_tx: mpsc::Sender<WatchEvent>,
     /// Compacted flag
     compacted: bool,
+    /// Set if the watcher has been canceled by the server
+    canceled: Option<CancelReason>,
     /// TODO: remove it when https://github.com/xline-kv/Xline/issues/491 has been closed
     /// Store the revision that has been notified
     notified_set: HashSet<i64>,
@@ -99,6 +188,7 @@ impl Watcher {
             stop_notify,
             event_tx,
             compacted,
+            canceled: None,
             notified_set: HashSet::new(),
         }
     }
@@ -133,13 +223,21 @@ impl Watcher {
         let watch_id = self.watch_id();
         let events = self.filter_events(events);
         let events_len = events.len();
+        // initial events replayed from history may span several revisions, all of them
+        // should be recorded to avoid notifying them again when they are dispatched later
+        let event_revisions: Vec<i64> = events
+            .iter()
+            .filter_map(|event| event.kv.as_ref().map(|kv| kv.mod_revision))
+            .collect();
         let watch_event = WatchEvent {
             id: watch_id,
             events,
             revision,
             compacted: self.compacted,
+            canceled: self.canceled,
         };
         if !self.compacted
+            && self.canceled.is_none()
             && (revision < self.start_rev
                 || self.notified_set.contains(&revision)
                 || 0 == events_len)
@@ -150,6 +248,7 @@ impl Watcher {
         match self.event_tx.try_send(watch_event) {
             Ok(()) => {
                 let _ignore = self.notified_set.insert(revision);
+                self.notified_set.extend(event_revisions);
                 Ok(())
             }
             Err(TrySendError::Closed(_)) => {
@@ -168,6 +267,56 @@ impl Watcher {
     }
 }
 
+/// The reason why the server cancels a watcher
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CancelReason {
+    /// The watcher is too slow to consume its events
+    TooSlow,
+    /// The watcher is dropped by an administrator
+    Dropped,
+}
+
+impl std::fmt::Display for CancelReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            CancelReason::TooSlow => write!(f, "watcher is too slow to keep up with the events"),
+            CancelReason::Dropped => write!(f, "watcher is dropped by the administrator"),
+        }
+    }
+}
+
+/// Limit on the pending events of a slow watcher
+#[derive(Debug, Clone, Copy)]
+struct SlowWatcherLimit {
+    /// Max number of pending events buffered for a watcher
+    buffer_size: usize,
+    /// Policy applied to a watcher whose pending events exceed `buffer_size`
+    policy: SlowWatcherPolicy,
+}
+
+impl SlowWatcherLimit {
+    /// Apply the limit to the pending events of a victim watcher, returns the events
+    /// that should be kept in memory
+    fn apply(self, watcher: &mut Watcher, events: Vec<Event>) -> Vec<Event> {
+        if events.len() <= self.buffer_size {
+            return events;
+        }
+        let watch_id = watcher.watch_id();
+        match self.policy {
+            SlowWatcherPolicy::Resync => {
+                // the dropped events will be replayed from storage once the watcher catches up
+                warn!(watch_id, "watcher is too slow, drop its pending events");
+            }
+            SlowWatcherPolicy::Cancel => {
+                warn!(watch_id, "watcher is too slow, cancel it");
+                watcher.canceled = Some(CancelReason::TooSlow);
+            }
+            _ => unreachable!("unknown slow watcher policy"),
+        }
+        vec![]
+    }
+}
+
 /// KV watcher
 #[derive(Debug)]
 pub(crate) struct KvWatcher<S>
@@ -178,13 +327,15 @@ where
     kv_store_inner: Arc<KvStoreInner<S>>,
     /// Watch indexes
     watcher_map: Arc<RwLock<WatcherMap>>,
+    /// Limit on the pending events of slow watchers
+    slow_watcher_limit: SlowWatcherLimit,
 }
 
 /// Store all watchers
 #[derive(Debug)]
 struct WatcherMap {
-    /// Index for watchers
-    index: HashMap<KeyRange, HashSet<WatchId>>,
+    /// Index for watchers, watchers with an empty key range are not indexed
+    index: IntervalMap<BytesAffine, HashSet<WatchId>>,
     /// All watchers
     watchers: HashMap<WatchId, Watcher>,
     /// Victims
@@ -195,7 +346,7 @@ impl WatcherMap {
     /// Create a new `WatcherMap`
     fn new() -> Self {
         Self {
-            index: HashMap::new(),
+            index: IntervalMap::new(),
             watchers: HashMap::new(),
             victims: HashMap::new(),
         }
@@ -203,71 +354,106 @@ impl WatcherMap {
 
     /// Insert a new watcher to the map and create. Internally, it will create a index for this watcher.
     fn register(&mut self, watcher: Watcher) {
-        let key_range = watcher.key_range().clone();
         let watch_id = watcher.watch_id();
+        if let Some(interval) = key_range_interval(watcher.key_range()) {
+            assert!(
+                self.index
+                    .entry(interval)
+                    .or_insert(HashSet::new())
+                    .insert(watch_id),
+                "can't insert a watcher to index twice"
+            );
+        }
         assert!(
             self.watchers.insert(watch_id, watcher).is_none(),
             "can't insert a watcher to watchers twice"
         );
-        assert!(
-            self.index.entry(key_range).or_default().insert(watch_id),
-            "can't insert a watcher to index twice"
-        );
     }
 
-    /// Move a watcher to victims, the `watch_id` must be valid.
-    fn move_to_victim(&mut self, watch_id: WatchId, updates: (i64, Vec<Event>)) {
-        debug!(watch_id, "move watcher to victim");
-        let Some(watcher) = self.watchers.remove(&watch_id) else {
-            unreachable!("watcher should exist")
+    /// Get ids of all watchers that watch the given key
+    fn watch_ids_of(&self, key: &[u8]) -> impl Iterator<Item = WatchId> + '_ {
+        let mut key_end = key.to_vec();
+        key_end.push(0);
+        let point = Interval::new(BytesAffine::new_key(key), BytesAffine::new_key(key_end));
+        self.index
+            .find_all_overlap(&point)
+            .into_iter()
+            .flat_map(|(_, watch_ids)| watch_ids.iter().copied())
+    }
+
+    /// Remove the index of a watcher
+    fn remove_from_index(&mut self, watcher: &Watcher) {
+        let Some(interval) = key_range_interval(watcher.key_range()) else {
+            return;
         };
-        let Some(watch_ids) = self.index.get_mut(watcher.key_range()) else {
-            unreachable!("watch_ids should exist")
+        let is_empty = {
+            let Some(watch_ids) = self.index.get_mut(&interval) else {
+                unreachable!("watch_ids should exist")
+            };
+            assert!(
+                watch_ids.remove(&watcher.watch_id()),
+                "no such watcher in index"
+            );
+            watch_ids.is_empty()
         };
-        assert!(
-            watch_ids.remove(&watcher.watch_id()),
-            "no such watcher in index"
-        );
-        if watch_ids.is_empty() {
+        if is_empty {
             assert!(
-                self.index.remove(&watcher.key_range).is_some(),
+                self.index.remove(&interval).is_some(),
                 "watch_ids should exist"
             );
         }
-        let watch_event = WatchEvent {
-            id: watch_id,
-            revision: updates.0,
-            events: updates.1,
-            compacted: false,
+    }
+
+    /// Move a watcher to victims, the `watch_id` must be valid.
+    fn move_to_victim(
+        &mut self,
+        watch_id: WatchId,
+        (revision, events): (i64, Vec<Event>),
+        limit: SlowWatcherLimit,
+    ) {
+        debug!(watch_id, "move watcher to victim");
+        let Some(mut watcher) = self.watchers.remove(&watch_id) else {
+            unreachable!("watcher should exist")
         };
+        self.remove_from_index(&watcher);
+        let events = limit.apply(&mut watcher, events);
         assert!(
-            self.victims
-                .insert(watcher, (watch_event.revision, watch_event.events))
-                .is_none(),
+            self.victims.insert(watcher, (revision, events)).is_none(),
             "can't insert a watcher to victims twice"
         );
     }
 
+    /// Move all synced watchers to victims, they catch up with the events from the given
+    /// revision from the storage
+    fn resync_all(&mut self, revision: i64, limit: SlowWatcherLimit) {
+        let watch_ids: Vec<_> = self.watchers.keys().copied().collect();
+        for watch_id in watch_ids {
+            if let Some(watcher) = self.watchers.get_mut(&watch_id) {
+                // the events below the revision are already notified
+                watcher.start_rev = watcher.start_rev.max(revision);
+            }
+            self.move_to_victim(watch_id, (revision, Vec::new()), limit);
+        }
+    }
+
+    /// Take a watcher out of the map, no matter it is synced or a victim
+    fn take(&mut self, watch_id: WatchId) -> Option<Watcher> {
+        if let Some(watcher) = self.watchers.remove(&watch_id) {
+            self.remove_from_index(&watcher);
+            return Some(watcher);
+        }
+        let (mut taken, rest): (Vec<_>, Vec<_>) = self
+            .victims
+            .drain()
+            .partition(|pair| pair.0.watch_id() == watch_id);
+        self.victims = rest.into_iter().collect();
+        taken.pop().map(|(watcher, _)| watcher)
+    }
+
     /// Remove a watcher
     fn remove(&mut self, watch_id: WatchId) {
         if let Some(watcher) = self.watchers.remove(&watch_id) {
-            let key_range = watcher.key_range();
-            let is_empty = {
-                let Some(watch_ids) = self.index.get_mut(watcher.key_range()) else {
-                    unreachable!("watch_ids should exist")
-                };
-                assert!(
-                    watch_ids.remove(&watcher.watch_id()),
-                    "no such watcher in index"
-                );
-                watch_ids.is_empty()
-            };
-            if is_empty {
-                assert!(
-                    self.index.remove(key_range).is_some(),
-                    "watch_ids should exist"
-                );
-            }
+            self.remove_from_index(&watcher);
         } else {
             self.victims = self
                 .victims
@@ -278,6 +464,23 @@ impl WatcherMap {
     }
 }
 
+/// Convert a `KeyRange` to the `Interval` used by the watcher index, return `None`
+/// if the range contains no key
+fn key_range_interval(key_range: &KeyRange) -> Option<Interval<BytesAffine>> {
+    let start = key_range.range_start();
+    let low = BytesAffine::new_key(start);
+    let high = match key_range.range_end() {
+        [] => {
+            let mut end = start.to_vec();
+            end.push(0);
+            BytesAffine::new_key(end)
+        }
+        [0] => BytesAffine::new_unbounded(),
+        end => BytesAffine::new_key(end),
+    };
+    (low < high).then(|| Interval::new(low, high))
+}
+
 /// Operations of KV watcher
 #[allow(clippy::arithmetic_side_effects, clippy::indexing_slicing)] // Introduced by mockall::automock
 #[cfg_attr(test, mockall::automock)]
@@ -358,10 +561,13 @@ where
             if let Err(TrySendError::Full(watch_event)) =
                 watcher.notify((last_revision, initial_events))
             {
+                let events = self
+                    .slow_watcher_limit
+                    .apply(&mut watcher, watch_event.events);
                 assert!(
                     watcher_map_w
                         .victims
-                        .insert(watcher, (watch_event.revision, watch_event.events))
+                        .insert(watcher, (watch_event.revision, events))
                         .is_none(),
                     "can't insert a watcher to victims twice"
                 );
@@ -389,17 +595,42 @@ impl<S> KvWatcher<S>
 where
     S: StorageApi,
 {
+    /// Get the number of synced watchers and victim watchers
+    pub(crate) fn watcher_count(&self) -> (usize, usize) {
+        self.watcher_map
+            .map_read(|m| (m.watchers.len(), m.victims.len()))
+    }
+
+    /// Cancel a watcher on behalf of an administrator, its client will receive a
+    /// canceled response. Return `false` if there is no such watcher.
+    pub(crate) fn drop_watcher(&self, watch_id: WatchId) -> bool {
+        let Some(mut watcher) = self.watcher_map.write().take(watch_id) else {
+            return false;
+        };
+        watcher.canceled = Some(CancelReason::Dropped);
+        if watcher.notify((0, vec![])).is_err() {
+            warn!(watch_id, "failed to notify the dropped watcher");
+        }
+        true
+    }
+
     /// Create a new `Arc<KvWatcher>`
     pub(crate) fn new_arc(
         kv_store_inner: Arc<KvStoreInner<S>>,
-        kv_update_rx: mpsc::Receiver<(i64, Vec<Event>)>,
+        kv_update_rx: KvUpdateRx,
         sync_victims_interval: Duration,
+        watcher_buffer_size: usize,
+        slow_watcher_policy: SlowWatcherPolicy,
         task_manager: &TaskManager,
     ) -> Arc<Self> {
         let watcher_map = Arc::new(RwLock::new(WatcherMap::new()));
         let kv_watcher = Arc::new(Self {
             kv_store_inner,
             watcher_map,
+            slow_watcher_limit: SlowWatcherLimit {
+                buffer_size: watcher_buffer_size,
+                policy: slow_watcher_policy,
+            },
         });
         task_manager.spawn(TaskName::SyncVictims, |n| {
             Self::sync_victims_task(Arc::clone(&kv_watcher), sync_victims_interval, n)
@@ -414,9 +645,13 @@ where
     #[allow(clippy::arithmetic_side_effects, clippy::ignored_unit_patterns)] // Introduced by tokio::select!
     async fn kv_updates_task(
         kv_watcher: Arc<KvWatcher<S>>,
-        mut kv_update_rx: mpsc::Receiver<(i64, Vec<Event>)>,
+        kv_update_rx: KvUpdateRx,
         shutdown_listener: Listener,
     ) {
+        let KvUpdateRx {
+            rx: mut kv_update_rx,
+            overflow,
+        } = kv_update_rx;
         loop {
             tokio::select! {
                 updates = kv_update_rx.recv() => {
@@ -425,6 +660,19 @@ where
                     };
                     kv_watcher.handle_kv_updates(updates);
                 },
+                _ = overflow.dropped.notified() => {
+                    // the updates queued before the dropped ones are notified first
+                    while let Ok(updates) = kv_update_rx.try_recv() {
+                        kv_watcher.handle_kv_updates(updates);
+                    }
+                    let revision = overflow.resync_from.swap(i64::MAX, Ordering::AcqRel);
+                    if revision != i64::MAX {
+                        kv_watcher
+                            .watcher_map
+                            .write()
+                            .resync_all(revision, kv_watcher.slow_watcher_limit);
+                    }
+                },
                 _ = shutdown_listener.wait() => break,
             }
         }
@@ -459,6 +707,11 @@ where
                             .is_none(),
                         "can't insert a watcher to new_victims twice"
                     );
+                } else if watcher.canceled.is_some() {
+                    debug!(
+                        watch_id = watcher.watch_id(),
+                        "slow watcher canceled by sync_victims_task"
+                    );
                 } else {
                     let mut watcher_map_w = kv_watcher.watcher_map.write();
                     let initial_events = kv_watcher
@@ -473,13 +726,16 @@ where
                         if let Err(TrySendError::Full(watch_event)) =
                             watcher.notify((last_revision, initial_events))
                         {
+                            let events = kv_watcher
+                                .slow_watcher_limit
+                                .apply(&mut watcher, watch_event.events);
                             assert!(
                                 new_victims
-                                    .insert(watcher, (watch_event.revision, watch_event.events))
+                                    .insert(watcher, (watch_event.revision, events))
                                     .is_none(),
                                 "can't insert a watcher to new_victims twice"
                             );
-                            break;
+                            continue;
                         };
                     }
                     debug!(
@@ -499,25 +755,16 @@ where
 
     /// Handle KV store updates
     fn handle_kv_updates(&self, (revision, all_events): (i64, Vec<Event>)) {
+        fail_point!("watcher_dispatch");
         self.watcher_map.map_write(|mut watcher_map_w| {
             let mut watcher_events: HashMap<WatchId, Vec<Event>> = HashMap::new();
             for event in all_events {
-                let watch_ids = watcher_map_w
-                    .index
-                    .iter()
-                    .filter_map(|(k, v)| {
-                        k.contains_key(
-                            &event
-                                .kv
-                                .as_ref()
-                                .unwrap_or_else(|| panic!("Receive Event with empty kv"))
-                                .key,
-                        )
-                        .then_some(v)
-                    })
-                    .flatten()
-                    .copied()
-                    .collect_vec();
+                let key = &event
+                    .kv
+                    .as_ref()
+                    .unwrap_or_else(|| panic!("Receive Event with empty kv"))
+                    .key;
+                let watch_ids = watcher_map_w.watch_ids_of(key).collect_vec();
                 for watch_id in watch_ids {
                     watcher_events
                         .entry(watch_id)
@@ -531,8 +778,11 @@ where
                     .get_mut(&watch_id)
                     .unwrap_or_else(|| panic!("watcher index and watchers doesn't match"));
                 if let Err(TrySendError::Full(watch_event)) = watcher.notify((revision, events)) {
-                    watcher_map_w
-                        .move_to_victim(watch_id, (watch_event.revision, watch_event.events));
+                    watcher_map_w.move_to_victim(
+                        watch_id,
+                        (watch_event.revision, watch_event.events),
+                        self.slow_watcher_limit,
+                    );
                 }
             }
         });
@@ -549,14 +799,16 @@ pub(crate) struct WatchEvent {
     revision: i64,
     /// Compacted WatchEvent
     compacted: bool,
+    /// Set if the watcher of this `WatchEvent` has been canceled by the server
+    canceled: Option<CancelReason>,
 }
 
 impl std::fmt::Debug for WatchEvent {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "WatchEvent {{ id: {}, revision: {}, compacted: {}, ",
-            self.id, self.revision, self.compacted,
+            "WatchEvent {{ id: {}, revision: {}, compacted: {}, canceled: {:?}, ",
+            self.id, self.revision, self.compacted, self.canceled,
         )?;
         write_vec!(f, "events", self.events);
         write!(f, " }}")
@@ -583,6 +835,11 @@ impl WatchEvent {
     pub(crate) fn compacted(&self) -> bool {
         self.compacted
     }
+
+    /// Get the reason if the watcher of this `WatchEvent` has been canceled by the server
+    pub(crate) fn canceled(&self) -> Option<CancelReason> {
+        self.canceled
+    }
 }
 
 /// Get the last revision of a event slice
@@ -604,7 +861,7 @@ mod test {
     use clippy_utilities::{NumericCast, OverflowArithmetic};
     use test_macros::abort_on_panic;
     use tokio::time::{sleep, timeout};
-    use utils::config::EngineConfig;
+    use utils::config::{default_watcher_buffer_size, EngineConfig};
     use xlineapi::RequestWrapper;
 
     use super::*;
@@ -619,13 +876,22 @@ mod test {
 
     fn init_empty_store(
         task_manager: &TaskManager,
+    ) -> (Arc<KvStore<DB>>, Arc<DB>, Arc<KvWatcher<DB>>) {
+        init_store_with_kv_update_channel(task_manager, 128, KvUpdateBackpressure::default())
+    }
+
+    fn init_store_with_kv_update_channel(
+        task_manager: &TaskManager,
+        kv_update_channel_size: usize,
+        kv_update_backpressure: KvUpdateBackpressure,
     ) -> (Arc<KvStore<DB>>, Arc<DB>, Arc<KvWatcher<DB>>) {
         let (compact_tx, _compact_rx) = mpsc::channel(COMPACT_CHANNEL_SIZE);
         let db = DB::open(&EngineConfig::Memory).unwrap();
         let header_gen = Arc::new(HeaderGenerator::new(0, 0));
         let index = Arc::new(Index::new());
         let lease_collection = Arc::new(LeaseCollection::new(0));
-        let (kv_update_tx, kv_update_rx) = mpsc::channel(128);
+        let (kv_update_tx, kv_update_rx) =
+            kv_update_channel(kv_update_channel_size, kv_update_backpressure);
         let kv_store_inner = Arc::new(KvStoreInner::new(index, Arc::clone(&db)));
         let store = Arc::new(KvStore::new(
             Arc::clone(&kv_store_inner),
@@ -633,12 +899,15 @@ mod test {
             kv_update_tx,
             compact_tx,
             lease_collection,
+            Arc::default(),
         ));
         let sync_victims_interval = Duration::from_millis(10);
         let kv_watcher = KvWatcher::new_arc(
             kv_store_inner,
             kv_update_rx,
             sync_victims_interval,
+            default_watcher_buffer_size(),
+            SlowWatcherPolicy::default(),
             task_manager,
         );
         (store, db, kv_watcher)
@@ -701,6 +970,53 @@ mod test {
         task_manager.shutdown(true).await;
     }
 
+    #[tokio::test(flavor = "multi_thread")]
+    #[abort_on_panic]
+    async fn dropped_kv_updates_should_be_resynced() {
+        let task_manager = Arc::new(TaskManager::new());
+        let (store, db, kv_watcher) =
+            init_store_with_kv_update_channel(&task_manager, 1, KvUpdateBackpressure::MarkVictims);
+        let (event_tx, mut event_rx) = mpsc::channel(128);
+        let stop_notify = Arc::new(event_listener::Event::new());
+        kv_watcher.watch(
+            123,
+            KeyRange::new_one_key("foo"),
+            0,
+            vec![],
+            stop_notify,
+            event_tx,
+        );
+        sleep(Duration::from_micros(50)).await;
+        for i in 0..100_u8 {
+            put(
+                store.as_ref(),
+                db.as_ref(),
+                "foo",
+                vec![i],
+                i.overflow_add(1).numeric_cast(),
+            )
+            .await;
+        }
+
+        // the dropped events are replayed from the storage, so they may arrive out of order
+        let mut map = BTreeMap::new();
+        while map.len() < 100 {
+            let watch_events = timeout(Duration::from_secs(3), event_rx.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            for event in watch_events.events {
+                let val = event.kv.as_ref().unwrap().value[0];
+                *map.entry(val).or_insert(0) += 1;
+            }
+        }
+        for (k, count) in map {
+            assert_eq!(count, 1, "key {k} should be notified once");
+        }
+        drop(store);
+        task_manager.shutdown(true).await;
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     #[abort_on_panic]
     async fn test_victim() {
@@ -772,6 +1088,160 @@ mod test {
         task_manager.shutdown(true).await;
     }
 
+    #[tokio::test]
+    #[abort_on_panic]
+    async fn dropped_watcher_should_be_notified() {
+        let task_manager = Arc::new(TaskManager::new());
+        let (store, _db, kv_watcher) = init_empty_store(&task_manager);
+        let (event_tx, mut event_rx) = mpsc::channel(1);
+        let stop_notify = Arc::new(event_listener::Event::new());
+        kv_watcher.watch(
+            1,
+            KeyRange::new_one_key("foo"),
+            0,
+            vec![],
+            stop_notify,
+            event_tx,
+        );
+        assert_eq!(kv_watcher.watcher_count(), (1, 0));
+        assert!(!kv_watcher.drop_watcher(2));
+        assert!(kv_watcher.drop_watcher(1));
+        assert_eq!(kv_watcher.watcher_count(), (0, 0));
+        let watch_event = event_rx.recv().await.unwrap();
+        assert_eq!(watch_event.watch_id(), 1);
+        assert_eq!(watch_event.canceled(), Some(CancelReason::Dropped));
+        drop(store);
+        task_manager.shutdown(true).await;
+    }
+
+    #[test]
+    fn watcher_map_should_find_watchers_by_key() {
+        let (event_tx, _event_rx) = mpsc::channel(1);
+        let watcher = |id: WatchId, key_range: KeyRange| {
+            Watcher::new(
+                key_range,
+                id,
+                0,
+                vec![],
+                Arc::new(event_listener::Event::new()),
+                event_tx.clone(),
+                false,
+            )
+        };
+        let mut map = WatcherMap::new();
+        map.register(watcher(1, KeyRange::new_one_key("foo")));
+        map.register(watcher(2, KeyRange::new_one_key("foo")));
+        map.register(watcher(3, KeyRange::new("fo", "fp")));
+        map.register(watcher(4, KeyRange::new(vec![0], vec![0])));
+        map.register(watcher(5, KeyRange::new("b", "a")));
+        map.register(watcher(6, KeyRange::new("g", vec![0])));
+        let watch_ids = |map: &WatcherMap, key: &str| {
+            map.watch_ids_of(key.as_bytes())
+                .sorted()
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(watch_ids(&map, "foo"), vec![1, 2, 3, 4]);
+        assert_eq!(watch_ids(&map, "fo"), vec![3, 4]);
+        assert_eq!(watch_ids(&map, "foo1"), vec![3, 4]);
+        assert_eq!(watch_ids(&map, "b"), vec![4]);
+        assert_eq!(watch_ids(&map, "z"), vec![4, 6]);
+
+        map.remove(1);
+        map.remove(5);
+        map.move_to_victim(
+            3,
+            (0, vec![]),
+            SlowWatcherLimit {
+                buffer_size: default_watcher_buffer_size(),
+                policy: SlowWatcherPolicy::default(),
+            },
+        );
+        assert_eq!(watch_ids(&map, "foo"), vec![2, 4]);
+        map.remove(2);
+        map.remove(4);
+        map.remove(6);
+        assert!(map.index.is_empty());
+    }
+
+    #[test]
+    fn replayed_events_should_not_be_notified_again() {
+        let event = |revision: i64| Event {
+            kv: Some(KeyValue {
+                key: "foo".into(),
+                mod_revision: revision,
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let (event_tx, mut event_rx) = mpsc::channel(8);
+        let mut watcher = Watcher::new(
+            KeyRange::new_one_key("foo"),
+            1,
+            2,
+            vec![],
+            Arc::new(event_listener::Event::new()),
+            event_tx,
+            false,
+        );
+        watcher
+            .notify((4, vec![event(2), event(3), event(4)]))
+            .unwrap();
+        watcher.notify((3, vec![event(3)])).unwrap();
+        watcher.notify((5, vec![event(5)])).unwrap();
+
+        let replayed = event_rx.try_recv().unwrap();
+        assert_eq!(replayed.events.len(), 3);
+        let next = event_rx.try_recv().unwrap();
+        assert_eq!(next.revision(), 5);
+        assert!(
+            event_rx.try_recv().is_err(),
+            "revision 3 should not be notified twice"
+        );
+    }
+
+    #[test]
+    fn slow_watcher_should_be_handled_by_policy() {
+        let events = (1..=3)
+            .map(|revision| Event {
+                kv: Some(KeyValue {
+                    key: "foo".into(),
+                    mod_revision: revision,
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+        let (event_tx, mut event_rx) = mpsc::channel(8);
+        let mut watcher = Watcher::new(
+            KeyRange::new_one_key("foo"),
+            1,
+            1,
+            vec![],
+            Arc::new(event_listener::Event::new()),
+            event_tx,
+            false,
+        );
+        let limit = |policy| SlowWatcherLimit {
+            buffer_size: 2,
+            policy,
+        };
+
+        let kept = limit(SlowWatcherPolicy::Cancel).apply(&mut watcher, events[..2].to_vec());
+        assert_eq!(kept.len(), 2);
+        assert!(watcher.canceled.is_none());
+        let kept = limit(SlowWatcherPolicy::Resync).apply(&mut watcher, events.clone());
+        assert!(kept.is_empty());
+        assert!(watcher.canceled.is_none());
+        let kept = limit(SlowWatcherPolicy::Cancel).apply(&mut watcher, events);
+        assert!(kept.is_empty());
+        assert_eq!(watcher.canceled, Some(CancelReason::TooSlow));
+
+        watcher.notify((3, kept)).unwrap();
+        let watch_event = event_rx.try_recv().unwrap();
+        assert_eq!(watch_event.canceled(), Some(CancelReason::TooSlow));
+    }
+
     async fn put(
         store: &KvStore<DB>,
         db: &DB,
@@ -784,8 +1254,9 @@ mod test {
             value: value.into(),
             ..Default::default()
         });
-        let (_sync_res, ops) = store.after_sync(&req, revision).await.unwrap();
-        let key_revisions = db.flush_ops(ops).unwrap();
+        let (_sync_res, ops, events, _deletions) = store.after_sync(&req, revision).await.unwrap();
+        let key_revisions = db.write_batch(ops, false).unwrap();
         store.insert_index(key_revisions);
+        store.notify_updates(revision, events, revision).await;
     }
 }
@@ -69,6 +69,11 @@ impl Lease {
         }
     }
 
+    /// Set the remaining ttl checkpointed by the leader, a zero value clears it
+    pub(crate) fn set_remaining_ttl(&mut self, remaining_ttl: Duration) {
+        self.remaining_ttl = remaining_ttl;
+    }
+
     /// Refresh expiry and return new expiry
     pub(crate) fn refresh(&mut self, extend: Duration) -> Instant {
         let new_expiry = Instant::now().add(extend).add(self.remaining_ttl());
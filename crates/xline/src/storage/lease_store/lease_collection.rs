@@ -10,7 +10,7 @@ use utils::parking_lot_lock::RwLockMap;
 use xlineapi::execute_error::ExecuteError;
 
 use super::{lease_queue::LeaseQueue, Lease};
-use crate::rpc::PbLease;
+use crate::rpc::{LeaseCheckpoint, PbLease};
 
 /// Collection of lease related data
 #[derive(Debug)]
@@ -73,6 +73,8 @@ impl LeaseCollection {
             if lease.expired() {
                 return Err(ExecuteError::LeaseExpired(lease_id));
             }
+            // a renewed lease starts over from its full ttl
+            lease.set_remaining_ttl(Duration::ZERO);
             let expiry = lease.refresh(Duration::default());
             let ttl = lease.ttl().as_secs().numeric_cast();
             (expiry, ttl)
@@ -108,6 +110,16 @@ impl LeaseCollection {
         self.inner.read().item_map.get(key).copied().unwrap_or(0)
     }
 
+    /// Get the keys that are attached to leases, along with their lease ids
+    pub(crate) fn attached_keys(&self) -> Vec<(Vec<u8>, i64)> {
+        self.inner
+            .read()
+            .item_map
+            .iter()
+            .map(|(key, &lease_id)| (key.clone(), lease_id))
+            .collect()
+    }
+
     /// Get Lease by lease id
     pub(crate) fn look_up(&self, lease_id: i64) -> Option<Lease> {
         self.inner.read().lease_map.get(&lease_id).cloned()
@@ -150,6 +162,32 @@ impl LeaseCollection {
         }
     }
 
+    /// Record the remaining ttl of a lease, return the lease to persist or `None` if the
+    /// lease doesn't exist
+    pub(crate) fn checkpoint(&self, lease_id: i64, remaining_ttl: i64) -> Option<PbLease> {
+        let mut inner = self.inner.write();
+        let lease = inner.lease_map.get_mut(&lease_id)?;
+        lease.set_remaining_ttl(Duration::from_secs(remaining_ttl.max(0).numeric_cast()));
+        Some(PbLease {
+            id: lease.id(),
+            ttl: lease.ttl().as_secs().numeric_cast(),
+            remaining_ttl: lease.remaining_ttl().as_secs().numeric_cast(),
+        })
+    }
+
+    /// Get the remaining ttl of all leases, should only be called by the leader
+    pub(crate) fn checkpoints(&self) -> Vec<LeaseCheckpoint> {
+        self.inner
+            .read()
+            .lease_map
+            .values()
+            .map(|lease| LeaseCheckpoint {
+                id: lease.id(),
+                remaining_ttl: lease.remaining().as_secs().numeric_cast(),
+            })
+            .collect()
+    }
+
     /// Revokes a lease
     pub(crate) fn revoke(&self, lease_id: i64) -> Option<Lease> {
         self.inner.write().lease_map.remove(&lease_id)
@@ -187,4 +225,21 @@ mod test {
         assert!(l.is_some());
         assert_eq!(l.unwrap().ttl(), Duration::from_secs(3));
     }
+
+    #[test]
+    fn test_checkpoint_should_be_used_after_promote() {
+        let c = LeaseCollection::new(0);
+        c.grant(1, 100, false);
+        let pb_lease = c.checkpoint(1, 10).unwrap();
+        assert_eq!(pb_lease.remaining_ttl, 10);
+        assert!(c.checkpoint(2, 10).is_none());
+
+        c.promote(Duration::ZERO);
+        let remaining = c.look_up(1).unwrap().remaining();
+        assert!(remaining <= Duration::from_secs(10));
+        assert!(remaining > Duration::from_secs(9));
+
+        c.renew(1).unwrap();
+        assert!(c.look_up(1).unwrap().remaining() > Duration::from_secs(99));
+    }
 }
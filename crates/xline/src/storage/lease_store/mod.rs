@@ -17,7 +17,6 @@ use std::{
 use log::debug;
 use parking_lot::RwLock;
 use prost::Message;
-use tokio::sync::mpsc;
 use utils::table_names::LEASE_TABLE;
 use xlineapi::{
     command::{CommandResponse, SyncResponse},
@@ -25,13 +24,13 @@ use xlineapi::{
 };
 
 pub(crate) use self::{lease::Lease, lease_collection::LeaseCollection};
-use super::{db::WriteOp, index::Index, storage_api::StorageApi};
+use super::{db::WriteOp, index::Index, storage_api::StorageApi, tenants::Tenants};
 use crate::{
     header_gen::HeaderGenerator,
     rpc::{
-        Event, LeaseGrantRequest, LeaseGrantResponse, LeaseLeasesRequest, LeaseLeasesResponse,
-        LeaseRevokeRequest, LeaseRevokeResponse, LeaseStatus, PbLease, RequestWrapper,
-        ResponseHeader, ResponseWrapper,
+        Event, LeaseCheckpoint, LeaseCheckpointRequest, LeaseCheckpointResponse, LeaseGrantRequest,
+        LeaseGrantResponse, LeaseLeasesRequest, LeaseLeasesResponse, LeaseRevokeRequest,
+        LeaseRevokeResponse, LeaseStatus, PbLease, RequestWrapper, ResponseHeader, ResponseWrapper,
     },
     storage::KvStore,
 };
@@ -51,10 +50,10 @@ where
     db: Arc<DB>,
     /// Key to revision index
     index: Arc<Index>,
+    /// Per-tenant accounting of the kv store
+    tenants: Arc<Tenants>,
     /// Header generator
     header_gen: Arc<HeaderGenerator>,
-    /// KV update sender
-    kv_update_tx: mpsc::Sender<(i64, Vec<Event>)>,
     /// Primary flag
     is_primary: AtomicBool,
     /// cache unsynced lease id
@@ -73,15 +72,15 @@ where
         header_gen: Arc<HeaderGenerator>,
         db: Arc<DB>,
         index: Arc<Index>,
-        kv_update_tx: mpsc::Sender<(i64, Vec<Event>)>,
+        tenants: Arc<Tenants>,
         is_leader: bool,
     ) -> Self {
         Self {
             lease_collection,
             db,
             index,
+            tenants,
             header_gen,
-            kv_update_tx,
             is_primary: AtomicBool::new(is_leader),
             unsynced_cache: Arc::new(RwLock::new(HashSet::new())),
             sync_event: event_listener::Event::new(),
@@ -97,15 +96,15 @@ where
             .map(CommandResponse::new)
     }
 
-    /// sync a lease request
-    pub(crate) async fn after_sync(
+    /// sync a lease request, the events of the keys deleted by a revoke are returned
+    /// so that they can be notified once the changes are persisted
+    pub(crate) fn after_sync(
         &self,
         request: &RequestWrapper,
         revision: i64,
-    ) -> Result<(SyncResponse, Vec<WriteOp>), ExecuteError> {
+    ) -> Result<(SyncResponse, Vec<WriteOp>, Vec<Event>), ExecuteError> {
         self.sync_request(request, revision)
-            .await
-            .map(|(rev, ops)| (SyncResponse::new(rev), ops))
+            .map(|(rev, ops, events)| (SyncResponse::new(rev), ops, events))
     }
 
     /// Get lease by id
@@ -118,6 +117,11 @@ where
         self.lease_collection.leases()
     }
 
+    /// Get the remaining ttl of all leases to checkpoint
+    pub(crate) fn checkpoints(&self) -> Vec<LeaseCheckpoint> {
+        self.lease_collection.checkpoints()
+    }
+
     /// Find expired leases
     pub(crate) fn find_expired_leases(&self) -> Vec<i64> {
         self.lease_collection.find_expired_leases()
@@ -158,6 +162,9 @@ where
         let leases = self.get_all()?;
         for lease in leases {
             let _ignore = self.lease_collection.grant(lease.id, lease.ttl, false);
+            let _ignore = self
+                .lease_collection
+                .checkpoint(lease.id, lease.remaining_ttl);
         }
         Ok(())
     }
@@ -219,6 +226,13 @@ where
                 debug!("Receive LeaseLeasesRequest {:?}", req);
                 Ok(self.handle_lease_leases_request(req).into())
             }
+            RequestWrapper::LeaseCheckpointRequest(ref req) => {
+                debug!("Receive LeaseCheckpointRequest {:?}", req);
+                Ok(LeaseCheckpointResponse {
+                    header: Some(self.header_gen.gen_header()),
+                }
+                .into())
+            }
             _ => unreachable!("Other request should not be sent to this store"),
         };
         res
@@ -238,8 +252,10 @@ where
         if self.lease_collection.contains_lease(req.id) {
             return Err(ExecuteError::LeaseAlreadyExists(req.id));
         }
-
-        _ = self.unsynced_cache.write().insert(req.id);
+        // a grant with the same id may have been executed but not synced yet
+        if !self.unsynced_cache.write().insert(req.id) {
+            return Err(ExecuteError::LeaseAlreadyExists(req.id));
+        }
 
         Ok(LeaseGrantResponse {
             header: Some(self.header_gen.gen_header()),
@@ -280,28 +296,32 @@ where
     }
 
     /// Sync `RequestWithToken`
-    async fn sync_request(
+    fn sync_request(
         &self,
         wrapper: &RequestWrapper,
         revision: i64,
-    ) -> Result<(i64, Vec<WriteOp>), ExecuteError> {
+    ) -> Result<(i64, Vec<WriteOp>, Vec<Event>), ExecuteError> {
         #[allow(clippy::wildcard_enum_match_arm)]
-        let ops = match *wrapper {
+        let (ops, events) = match *wrapper {
             RequestWrapper::LeaseGrantRequest(ref req) => {
                 debug!("Sync LeaseGrantRequest {:?}", req);
-                self.sync_lease_grant_request(req)
+                (self.sync_lease_grant_request(req), vec![])
             }
             RequestWrapper::LeaseRevokeRequest(ref req) => {
                 debug!("Sync LeaseRevokeRequest {:?}", req);
-                self.sync_lease_revoke_request(req, revision).await?
+                self.sync_lease_revoke_request(req, revision)?
             }
             RequestWrapper::LeaseLeasesRequest(ref req) => {
                 debug!("Sync LeaseLeasesRequest {:?}", req);
-                vec![]
+                (vec![], vec![])
+            }
+            RequestWrapper::LeaseCheckpointRequest(ref req) => {
+                debug!("Sync LeaseCheckpointRequest {:?}", req);
+                (self.sync_lease_checkpoint_request(req), vec![])
             }
             _ => unreachable!("Other request should not be sent to this store"),
         };
-        Ok((revision, ops))
+        Ok((revision, ops, events))
     }
 
     /// Sync `LeaseGrantRequest`
@@ -312,6 +332,15 @@ where
         vec![WriteOp::PutLease(lease)]
     }
 
+    /// Sync `LeaseCheckpointRequest`, leases revoked in the meantime are skipped
+    fn sync_lease_checkpoint_request(&self, req: &LeaseCheckpointRequest) -> Vec<WriteOp> {
+        req.checkpoints
+            .iter()
+            .filter_map(|cp| self.lease_collection.checkpoint(cp.id, cp.remaining_ttl))
+            .map(WriteOp::PutLease)
+            .collect()
+    }
+
     /// Get all `PbLease`
     fn get_all(&self) -> Result<Vec<PbLease>, ExecuteError> {
         self.db
@@ -327,11 +356,11 @@ where
     }
 
     /// Sync `LeaseRevokeRequest`
-    async fn sync_lease_revoke_request(
+    fn sync_lease_revoke_request(
         &self,
         req: &LeaseRevokeRequest,
         revision: i64,
-    ) -> Result<Vec<WriteOp>, ExecuteError> {
+    ) -> Result<(Vec<WriteOp>, Vec<Event>), ExecuteError> {
         let mut ops = Vec::new();
         let mut updates = Vec::new();
         ops.push(WriteOp::DeleteLease(req.id));
@@ -343,13 +372,14 @@ where
 
         if del_keys.is_empty() {
             let _ignore = self.lease_collection.revoke(req.id);
-            return Ok(Vec::new());
+            return Ok((Vec::new(), Vec::new()));
         }
 
         for (key, sub_revision) in del_keys.iter().zip(0..) {
             let (mut del_ops, mut del_event) = KvStore::<DB>::delete_keys(
                 &self.index,
                 &self.lease_collection,
+                &self.tenants,
                 key,
                 &[],
                 revision,
@@ -360,11 +390,7 @@ where
         }
 
         let _ignore = self.lease_collection.revoke(req.id);
-        assert!(
-            self.kv_update_tx.send((revision, updates)).await.is_ok(),
-            "Failed to send updates to KV watcher"
-        );
-        Ok(ops)
+        Ok((ops, updates))
     }
 }
 
@@ -386,7 +412,7 @@ mod test {
         let revision_gen = lease_store.header_gen.general_revision_arc();
 
         let req1 = RequestWrapper::from(LeaseGrantRequest { ttl: 10, id: 1 });
-        let _ignore1 = exe_and_sync_req(&lease_store, &req1, -1).await?;
+        let _ignore1 = exe_and_sync_req(&lease_store, &req1, -1)?;
 
         let lo = lease_store.look_up(1).unwrap();
         assert_eq!(lo.id(), 1);
@@ -400,7 +426,7 @@ mod test {
         lease_store.lease_collection.detach(1, "key".as_bytes())?;
 
         let req2 = RequestWrapper::from(LeaseRevokeRequest { id: 1 });
-        let _ignore2 = exe_and_sync_req(&lease_store, &req2, revision_gen.next()).await?;
+        let _ignore2 = exe_and_sync_req(&lease_store, &req2, revision_gen.next())?;
         assert!(lease_store.look_up(1).is_none());
         assert!(lease_store.leases().is_empty());
 
@@ -408,9 +434,9 @@ mod test {
         let req4 = RequestWrapper::from(LeaseGrantRequest { ttl: 10, id: 4 });
         let req5 = RequestWrapper::from(LeaseRevokeRequest { id: 3 });
         let req6 = RequestWrapper::from(LeaseLeasesRequest {});
-        let _ignore3 = exe_and_sync_req(&lease_store, &req3, -1).await?;
-        let _ignore4 = exe_and_sync_req(&lease_store, &req4, -1).await?;
-        let resp_1 = exe_and_sync_req(&lease_store, &req6, -1).await?;
+        let _ignore3 = exe_and_sync_req(&lease_store, &req3, -1)?;
+        let _ignore4 = exe_and_sync_req(&lease_store, &req4, -1)?;
+        let resp_1 = exe_and_sync_req(&lease_store, &req6, -1)?;
 
         let ResponseWrapper::LeaseLeasesResponse(leases_1) = resp_1 else {
             panic!("wrong response type: {resp_1:?}");
@@ -418,8 +444,8 @@ mod test {
         assert_eq!(leases_1.leases[0].id, 3);
         assert_eq!(leases_1.leases[1].id, 4);
 
-        let _ignore5 = exe_and_sync_req(&lease_store, &req5, -1).await?;
-        let resp_2 = exe_and_sync_req(&lease_store, &req6, -1).await?;
+        let _ignore5 = exe_and_sync_req(&lease_store, &req5, -1)?;
+        let resp_2 = exe_and_sync_req(&lease_store, &req6, -1)?;
         let ResponseWrapper::LeaseLeasesResponse(leases_2) = resp_2 else {
             panic!("wrong response type: {resp_2:?}");
         };
@@ -444,8 +470,8 @@ mod test {
             "the future should block until the lease is synced"
         );
 
-        let (_ignore, ops) = lease_store.after_sync(&req1, -1).await?;
-        _ = lease_store.db.flush_ops(ops)?;
+        let (_ignore, ops, _events) = lease_store.after_sync(&req1, -1)?;
+        _ = lease_store.db.write_batch(ops, false)?;
         lease_store.mark_lease_synced(&req1);
 
         assert!(
@@ -465,8 +491,8 @@ mod test {
             "the future should block until the lease is synced"
         );
 
-        let (_ignore, ops) = lease_store.after_sync(&req2, -1).await?;
-        _ = lease_store.db.flush_ops(ops)?;
+        let (_ignore, ops, _events) = lease_store.after_sync(&req2, -1)?;
+        _ = lease_store.db.write_batch(ops, false)?;
         lease_store.mark_lease_synced(&req2);
 
         assert!(
@@ -479,6 +505,33 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    #[abort_on_panic]
+    async fn test_grant_with_duplicate_id_should_fail() -> Result<(), Box<dyn Error>> {
+        let db = DB::open(&EngineConfig::Memory)?;
+        let lease_store = init_store(db);
+
+        let req = RequestWrapper::from(LeaseGrantRequest { ttl: 10, id: 1 });
+        let _ignore = lease_store.execute(&req)?;
+        assert!(
+            matches!(
+                lease_store.execute(&req),
+                Err(ExecuteError::LeaseAlreadyExists(1))
+            ),
+            "an unsynced lease id should not be granted twice"
+        );
+
+        let (_ignore, ops, _events) = lease_store.after_sync(&req, -1)?;
+        _ = lease_store.db.write_batch(ops, false)?;
+        lease_store.mark_lease_synced(&req);
+        assert!(matches!(
+            lease_store.execute(&req),
+            Err(ExecuteError::LeaseAlreadyExists(1))
+        ));
+
+        Ok(())
+    }
+
     #[tokio::test]
     #[abort_on_panic]
     async fn test_recover() -> Result<(), ExecuteError> {
@@ -486,7 +539,7 @@ mod test {
         let store = init_store(Arc::clone(&db));
 
         let req1 = RequestWrapper::from(LeaseGrantRequest { ttl: 10, id: 1 });
-        let _ignore1 = exe_and_sync_req(&store, &req1, -1).await?;
+        let _ignore1 = exe_and_sync_req(&store, &req1, -1)?;
         store.lease_collection.attach(1, "key".into())?;
 
         let new_store = init_store(db);
@@ -504,22 +557,64 @@ mod test {
         Ok(())
     }
 
+    #[tokio::test]
+    #[abort_on_panic]
+    async fn test_checkpoint_should_be_recovered() -> Result<(), ExecuteError> {
+        let db = DB::open(&EngineConfig::Memory)?;
+        let store = init_store(Arc::clone(&db));
+
+        let req1 = RequestWrapper::from(LeaseGrantRequest { ttl: 100, id: 1 });
+        let _ignore1 = exe_and_sync_req(&store, &req1, -1)?;
+        let req2 = RequestWrapper::from(LeaseCheckpointRequest {
+            checkpoints: vec![
+                LeaseCheckpoint {
+                    id: 1,
+                    remaining_ttl: 10,
+                },
+                LeaseCheckpoint {
+                    id: 2,
+                    remaining_ttl: 10,
+                },
+            ],
+        });
+        let _ignore2 = exe_and_sync_req(&store, &req2, -1)?;
+        assert_eq!(
+            store.look_up(1).unwrap().remaining_ttl(),
+            Duration::from_secs(10)
+        );
+        assert!(store.look_up(2).is_none());
+
+        let new_store = init_store(db);
+        new_store.recover()?;
+        let lease = new_store.look_up(1).unwrap();
+        assert_eq!(lease.ttl(), Duration::from_secs(100));
+        assert_eq!(lease.remaining_ttl(), Duration::from_secs(10));
+
+        Ok(())
+    }
+
     fn init_store(db: Arc<DB>) -> LeaseStore<DB> {
         let lease_collection = Arc::new(LeaseCollection::new(0));
-        let (kv_update_tx, _) = mpsc::channel(1);
         let header_gen = Arc::new(HeaderGenerator::new(0, 0));
         let index = Arc::new(Index::new());
-        LeaseStore::new(lease_collection, header_gen, db, index, kv_update_tx, true)
+        LeaseStore::new(
+            lease_collection,
+            header_gen,
+            db,
+            index,
+            Arc::default(),
+            true,
+        )
     }
 
-    async fn exe_and_sync_req(
+    fn exe_and_sync_req(
         ls: &LeaseStore<DB>,
         req: &RequestWrapper,
         revision: i64,
     ) -> Result<ResponseWrapper, ExecuteError> {
         let cmd_res = ls.execute(req)?;
-        let (_ignore, ops) = ls.after_sync(req, revision).await?;
-        _ = ls.db.flush_ops(ops)?;
+        let (_ignore, ops, _events) = ls.after_sync(req, revision)?;
+        _ = ls.db.write_batch(ops, false)?;
         Ok(cmd_res.into_inner())
     }
 }
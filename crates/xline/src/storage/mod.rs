@@ -2,22 +2,38 @@
 pub(crate) mod alarm_store;
 /// Storage for Auth
 pub(crate) mod auth_store;
+/// Per-entry checksums of the values
+pub(crate) mod checksum;
 /// Compact module
 pub(super) mod compact;
+/// Exclusive lock on the data dir
+pub(crate) mod data_dir_lock;
 /// Database module
 pub mod db;
+/// At-rest encryption of values
+pub(crate) mod encryption;
 /// Index module
 pub(crate) mod index;
+/// Persisted snapshots of the index, which speed up the recovery of the index
+pub(crate) mod index_snapshot;
 /// Storage for KV
 pub(crate) mod kv_store;
 /// KV watcher module
 pub(crate) mod kvwatcher;
 /// Storage for lease
 pub(crate) mod lease_store;
+/// Uploading and downloading backups to S3-compatible object storage
+pub(crate) mod object_storage;
+/// Range tombstones of the deletions over many keys
+pub(crate) mod range_tombstone;
 /// Revision module
 pub(crate) mod revision;
+/// Schema version of the storage and the upgrades between versions
+pub(crate) mod schema;
 /// Persistent storage abstraction
 pub(crate) mod storage_api;
+/// Per-tenant accounting and quotas
+pub(crate) mod tenants;
 
 pub use self::revision::Revision;
 pub(crate) use self::{
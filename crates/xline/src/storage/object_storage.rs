@@ -0,0 +1,118 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use s3::{creds::Credentials, Bucket, Region};
+use tokio::fs;
+use utils::config::ObjectStorageConfig;
+
+/// A bucket of an S3-compatible object storage that keeps the backups
+#[derive(Debug)]
+pub(crate) struct BackupBucket {
+    /// The bucket
+    bucket: Bucket,
+    /// Prefix of the object keys of the backups
+    prefix: String,
+    /// Tags of the uploaded backups
+    tags: Vec<(String, String)>,
+}
+
+impl BackupBucket {
+    /// Create a new `BackupBucket`
+    pub(crate) fn new(config: &ObjectStorageConfig) -> Result<Self> {
+        let region = Region::Custom {
+            region: config.region().clone(),
+            endpoint: config.endpoint().clone(),
+        };
+        let credentials = match (config.access_key_id(), config.secret_access_key()) {
+            (&Some(ref access_key), &Some(ref secret_key)) => {
+                Credentials::new(Some(access_key), Some(secret_key), None, None, None)?
+            }
+            // read from the environment or the credential files
+            _ => Credentials::default()?,
+        };
+        let mut bucket = *Bucket::new(config.bucket(), region, credentials)?;
+        if *config.path_style() {
+            bucket = *bucket.with_path_style();
+        }
+        let mut tags: Vec<_> = config
+            .tags()
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect();
+        tags.sort_unstable();
+        Ok(Self {
+            bucket,
+            prefix: config.prefix().clone(),
+            tags,
+        })
+    }
+
+    /// Upload a backup file and tag it, returns the key of the uploaded object
+    pub(crate) async fn upload(&self, path: &Path) -> Result<String> {
+        let name = path
+            .file_name()
+            .ok_or_else(|| anyhow!("invalid backup path {path:?}"))?
+            .to_string_lossy();
+        let key = format!("{}{name}", self.prefix);
+        let mut file = fs::File::open(path).await?;
+        let _ignore = self.bucket.put_object_stream(&mut file, &key).await?;
+        if !self.tags.is_empty() {
+            let tags: Vec<_> = self
+                .tags
+                .iter()
+                .map(|&(ref k, ref v)| (k.as_str(), v.as_str()))
+                .collect();
+            let _ig = self.bucket.put_object_tagging(&key, &tags).await?;
+        }
+        Ok(key)
+    }
+
+    /// Download the latest backup to the given path, returns the key of the downloaded
+    /// object, or `None` if there is no backup in the bucket
+    pub(crate) async fn download_latest(
+        &self,
+        backup_prefix: &str,
+        dest: &Path,
+    ) -> Result<Option<String>> {
+        let name_prefix = format!("{}{backup_prefix}", self.prefix);
+        let pages = self.bucket.list(name_prefix, None).await?;
+        let Some(key) = latest_key(pages.iter().flat_map(|page| &page.contents).map(|o| &o.key))
+        else {
+            return Ok(None);
+        };
+        let mut file = fs::File::create(dest).await?;
+        let status = self.bucket.get_object_to_writer(&key, &mut file).await?;
+        if status != 200 {
+            return Err(anyhow!(
+                "failed to download backup {key}, status code {status}"
+            ));
+        }
+        file.sync_all().await?;
+        Ok(Some(key))
+    }
+}
+
+/// Get the key of the latest backup, the names of the backups are zero padded, so they
+/// are sorted by time
+fn latest_key<'a, I: Iterator<Item = &'a String>>(keys: I) -> Option<String> {
+    keys.max().cloned()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn latest_key_should_be_the_newest_backup() {
+        let keys = [
+            "node1/xline-backup-00000001700000000000.db".to_owned(),
+            "node1/xline-backup-00000001700000300000.db".to_owned(),
+            "node1/xline-backup-00000001699999999999.db".to_owned(),
+        ];
+        assert_eq!(
+            latest_key(keys.iter()).as_deref(),
+            Some("node1/xline-backup-00000001700000300000.db")
+        );
+        assert_eq!(latest_key([].iter()), None);
+    }
+}
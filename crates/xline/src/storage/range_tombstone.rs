@@ -0,0 +1,249 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+    time::Duration,
+};
+
+use clippy_utilities::{NumericCast, OverflowArithmetic};
+use prost::bytes::{Buf, BufMut};
+use tracing::warn;
+use utils::task_manager::Listener;
+use xlineapi::execute_error::ExecuteError;
+
+use super::{revision::Revision, storage_api::StorageApi, KvStore};
+
+/// Prefix of the keys of the range tombstones in the meta table
+pub(crate) const RANGE_TOMBSTONE_PREFIX: &[u8] = b"range_tombstone/";
+/// Minimum number of the keys deleted by a `DeleteRange` to write a range tombstone
+/// instead of a tombstone per key
+pub(crate) const RANGE_TOMBSTONE_MIN_KEYS: usize = 1000;
+
+/// A range tombstone, which records a `DeleteRange` over many keys in one entry of the
+/// meta table. The tombstones of the deleted keys are written to the kv table lazily in
+/// the background, and the entry is removed once all of them are written.
+///
+/// The deleted keys are not recorded, they are the keys that are alive right before
+/// `(revision, sub_revision)`, and the key at position `i` in the key order is deleted at
+/// the sub revision `sub_revision + i`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct RangeTombstone {
+    /// Start key of the deleted range
+    pub(crate) key: Vec<u8>,
+    /// End key of the deleted range
+    pub(crate) range_end: Vec<u8>,
+    /// Revision of the deletion
+    pub(crate) revision: i64,
+    /// Sub revision of the first deleted key
+    pub(crate) sub_revision: i64,
+}
+
+impl RangeTombstone {
+    /// New `RangeTombstone`
+    pub(crate) fn new(key: Vec<u8>, range_end: Vec<u8>, revision: i64, sub_revision: i64) -> Self {
+        Self {
+            key,
+            range_end,
+            revision,
+            sub_revision,
+        }
+    }
+
+    /// Key of the range tombstone in the meta table
+    pub(crate) fn meta_key(revision: Revision) -> Vec<u8> {
+        let mut key = RANGE_TOMBSTONE_PREFIX.to_vec();
+        key.extend(revision.encode_to_vec());
+        key
+    }
+
+    /// Revision of the first deleted key
+    pub(crate) fn first_revision(&self) -> Revision {
+        Revision::new(self.revision, self.sub_revision)
+    }
+
+    /// Encode the range tombstone
+    pub(crate) fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.put_i64(self.revision);
+        buf.put_i64(self.sub_revision);
+        buf.put_u64(self.key.len().numeric_cast());
+        buf.put_slice(&self.key);
+        buf.put_slice(&self.range_end);
+        buf
+    }
+
+    /// Decode a range tombstone
+    ///
+    /// # Errors
+    ///
+    /// Return `ExecuteError::DbError` if the range tombstone is truncated
+    pub(crate) fn decode(mut buf: &[u8]) -> Result<Self, ExecuteError> {
+        if buf.remaining() < 24 {
+            return Err(truncated());
+        }
+        let revision = buf.get_i64();
+        let sub_revision = buf.get_i64();
+        let len: usize = buf.get_u64().numeric_cast();
+        let key = buf.get(..len).ok_or_else(truncated)?.to_vec();
+        buf.advance(len);
+        Ok(Self::new(key, buf.to_vec(), revision, sub_revision))
+    }
+}
+
+/// Error of a truncated range tombstone
+fn truncated() -> ExecuteError {
+    ExecuteError::DbError("the range tombstone is truncated".to_owned())
+}
+
+/// A range tombstone and its deletions that are not written to the kv table yet
+#[derive(Debug)]
+struct PendingTombstone {
+    /// The range tombstone
+    tombstone: RangeTombstone,
+    /// Revisions and keys of the deletions
+    deletions: Vec<(Revision, Vec<u8>)>,
+    /// Number of the deletions that are written
+    written: usize,
+}
+
+/// Range tombstones whose deletions are not written to the kv table yet
+#[derive(Debug, Default)]
+pub(crate) struct PendingTombstones {
+    /// Pending range tombstones ordered by their revisions
+    tombstones: BTreeMap<Revision, PendingTombstone>,
+    /// Keys of the deletions that are not written yet, which are read in place of the
+    /// missing tombstones in the kv table
+    keys: HashMap<Revision, Vec<u8>>,
+}
+
+impl PendingTombstones {
+    /// Add a range tombstone and its deletions
+    pub(crate) fn add(&mut self, tombstone: RangeTombstone, deletions: Vec<(Revision, Vec<u8>)>) {
+        self.keys.extend(deletions.iter().cloned());
+        let _ignore = self.tombstones.insert(
+            tombstone.first_revision(),
+            PendingTombstone {
+                tombstone,
+                deletions,
+                written: 0,
+            },
+        );
+    }
+
+    /// Get the key deleted at the given revision if its tombstone is not written yet
+    pub(crate) fn get_key(&self, revision: &Revision) -> Option<&Vec<u8>> {
+        self.keys.get(revision)
+    }
+
+    /// Get the revisions and keys of all deletions that are not written yet
+    pub(crate) fn deletions(&self) -> impl Iterator<Item = (&Revision, &Vec<u8>)> {
+        self.keys.iter()
+    }
+
+    /// Get at most `limit` deletions of the oldest range tombstone at or below `until`
+    /// that are not written yet, the revision of the range tombstone and whether they
+    /// are the last deletions of it are returned as well
+    pub(crate) fn next_batch(
+        &self,
+        until: i64,
+        limit: usize,
+    ) -> Option<(Revision, Vec<(Revision, Vec<u8>)>, bool)> {
+        let (&revision, pending) = self.tombstones.first_key_value()?;
+        if pending.tombstone.revision > until {
+            return None;
+        }
+        let rest = pending.deletions.get(pending.written..).unwrap_or_default();
+        let batch = rest.get(..limit).unwrap_or(rest);
+        Some((revision, batch.to_vec(), batch.len() == rest.len()))
+    }
+
+    /// Mark the next `count` deletions of the range tombstone as written, the range
+    /// tombstone is removed if all of its deletions are written
+    pub(crate) fn written(&mut self, revision: Revision, count: usize) {
+        let Some(pending) = self.tombstones.get_mut(&revision) else {
+            return;
+        };
+        let end = pending.written.overflow_add(count);
+        for &(ref rev, _) in pending
+            .deletions
+            .get(pending.written..end)
+            .unwrap_or_default()
+        {
+            let _ignore = self.keys.remove(rev);
+        }
+        pending.written = end;
+        if pending.written >= pending.deletions.len() {
+            let _ignore = self.tombstones.remove(&revision);
+        }
+    }
+}
+
+/// Background task that writes the deletions of the range tombstones to the kv table in
+/// batches of `batch_limit`, and sleeps `interval` between two batches
+#[allow(clippy::arithmetic_side_effects, clippy::ignored_unit_patterns)] // introduced by tokio::select! macro
+pub(crate) async fn range_tombstone_bg_task<DB: StorageApi>(
+    kv_store: Arc<KvStore<DB>>,
+    batch_limit: usize,
+    interval: Duration,
+    shutdown_listener: Listener,
+) {
+    loop {
+        // listen before writing, so a range tombstone added in between is not missed
+        let added = kv_store.range_tombstone_listener();
+        let written = match kv_store.write_range_tombstones(i64::MAX, batch_limit) {
+            Ok(written) => written,
+            Err(e) => {
+                // retry after the interval
+                warn!("failed to write the deletions of the range tombstones due to {e}");
+                true
+            }
+        };
+        if !written {
+            tokio::select! {
+                _ = added => {}
+                _ = shutdown_listener.wait() => return,
+            }
+        } else {
+            tokio::select! {
+                _ = tokio::time::sleep(interval) => {}
+                _ = shutdown_listener.wait() => return,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn range_tombstone_should_be_decoded() {
+        let tombstone = RangeTombstone::new(b"a".to_vec(), b"b".to_vec(), 3, 1);
+        let encoded = tombstone.encode();
+        assert_eq!(RangeTombstone::decode(&encoded).unwrap(), tombstone);
+        assert!(RangeTombstone::decode(&encoded[..20]).is_err());
+    }
+
+    #[test]
+    fn pending_tombstones_should_be_written_in_batches() {
+        let mut pending = PendingTombstones::default();
+        let deletions: Vec<_> = (0..3)
+            .map(|i| (Revision::new(5, i), vec![u8::try_from(i).unwrap()]))
+            .collect();
+        pending.add(RangeTombstone::new(vec![0], vec![], 5, 0), deletions);
+        assert!(pending.next_batch(4, 2).is_none());
+
+        let (revision, batch, last) = pending.next_batch(5, 2).unwrap();
+        assert_eq!(batch.len(), 2);
+        assert!(!last);
+        pending.written(revision, batch.len());
+        assert!(pending.get_key(&Revision::new(5, 0)).is_none());
+        assert_eq!(pending.get_key(&Revision::new(5, 2)), Some(&vec![2]));
+
+        let (revision, batch, last) = pending.next_batch(5, 2).unwrap();
+        assert_eq!(batch.len(), 1);
+        assert!(last);
+        pending.written(revision, batch.len());
+        assert!(pending.next_batch(i64::MAX, 2).is_none());
+        assert!(pending.get_key(&Revision::new(5, 2)).is_none());
+    }
+}
@@ -55,7 +55,9 @@ impl Revision {
     }
 
     /// Get sub revision
-    pub(crate) fn sub_revision(&self) -> i64 {
+    #[must_use]
+    #[inline]
+    pub fn sub_revision(&self) -> i64 {
         self.sub_revision
     }
 
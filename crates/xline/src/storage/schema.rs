@@ -0,0 +1,151 @@
+use std::{cmp::Ordering, path::PathBuf};
+
+use tracing::info;
+use utils::{
+    config::EngineConfig,
+    table_names::{KV_TABLE, META_TABLE},
+};
+use xlineapi::execute_error::ExecuteError;
+
+use super::{db::WriteOp, storage_api::StorageApi};
+use crate::server::command::APPLIED_INDEX_KEY;
+
+/// Key of the schema version in the meta table
+pub(crate) const SCHEMA_VERSION_KEY: &str = "schema_version";
+/// Schema version of the storage written by this version of xline. Bump it and add an
+/// upgrade step to `upgrade_ops` when the encoding of the stored data changes.
+pub(crate) const CURRENT_SCHEMA_VERSION: u32 = 1;
+
+/// Check the schema version of the storage. A new storage is marked with the current
+/// version, and the version to upgrade from is returned if the storage is written by an
+/// older version of xline.
+///
+/// # Errors
+///
+/// Return `ExecuteError::DbError` if the storage is written by a newer version of xline,
+/// which can not be downgraded
+pub(crate) fn check_schema<S: StorageApi>(db: &S) -> Result<Option<u32>, ExecuteError> {
+    let version = match db.get_value(META_TABLE, SCHEMA_VERSION_KEY)? {
+        Some(bytes) => {
+            let buf: [u8; 4] = bytes.try_into().map_err(|e| {
+                ExecuteError::DbError(format!("cannot decode schema version: {e:?}"))
+            })?;
+            u32::from_le_bytes(buf)
+        }
+        // storages written before the schema version is introduced have no version
+        None if is_empty(db)? => {
+            _ = db.write_batch(
+                vec![WriteOp::PutSchemaVersion(CURRENT_SCHEMA_VERSION)],
+                true,
+            )?;
+            return Ok(None);
+        }
+        None => 0,
+    };
+    match version.cmp(&CURRENT_SCHEMA_VERSION) {
+        Ordering::Less => Ok(Some(version)),
+        Ordering::Equal => Ok(None),
+        Ordering::Greater => Err(ExecuteError::DbError(format!(
+            "the schema version {version} of the storage is newer than {CURRENT_SCHEMA_VERSION}, \
+             which is written by a newer version of xline and can not be downgraded"
+        ))),
+    }
+}
+
+/// Upgrade the schema of the storage from the given version to the current version. Every
+/// step is written in a single batch along with its version, so an interrupted upgrade
+/// continues from the last finished step.
+///
+/// # Errors
+///
+/// Return `ExecuteError::DbError` if a step failed
+pub(crate) fn upgrade_schema<S: StorageApi>(db: &S, from: u32) -> Result<(), ExecuteError> {
+    for version in from.saturating_add(1)..=CURRENT_SCHEMA_VERSION {
+        let (description, mut ops) = upgrade_ops(db, version)?;
+        ops.push(WriteOp::PutSchemaVersion(version));
+        _ = db.write_batch(ops, true)?;
+        info!("storage schema is upgraded to version {version}: {description}");
+    }
+    Ok(())
+}
+
+/// Path of the snapshot taken before upgrading from the given version, `None` if the
+/// engine is not persistent
+pub(crate) fn pre_upgrade_snapshot_path(engine: &EngineConfig, from: u32) -> Option<PathBuf> {
+    #[allow(clippy::wildcard_enum_match_arm)]
+    match *engine {
+        EngineConfig::RocksDB(ref path) | EngineConfig::Redb(ref path) => {
+            let mut name = path.file_name()?.to_os_string();
+            name.push(format!("-pre-upgrade-v{from}.db"));
+            Some(path.with_file_name(name))
+        }
+        _ => None,
+    }
+}
+
+/// Get the description and the operations of the step that upgrades the storage to the
+/// given version
+fn upgrade_ops<S: StorageApi>(
+    _db: &S,
+    version: u32,
+) -> Result<(&'static str, Vec<WriteOp<'static>>), ExecuteError> {
+    match version {
+        1 => Ok(("add the schema version", Vec::new())),
+        _ => Err(ExecuteError::DbError(format!(
+            "no upgrade to schema version {version}"
+        ))),
+    }
+}
+
+/// Check if nothing is written to the storage
+fn is_empty<S: StorageApi>(db: &S) -> Result<bool, ExecuteError> {
+    Ok(db.get_value(META_TABLE, APPLIED_INDEX_KEY)?.is_none() && db.get_all(KV_TABLE)?.is_empty())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        rpc::KeyValue,
+        storage::{db::DB, Revision},
+    };
+
+    #[test]
+    fn new_storage_should_be_marked_with_current_version() -> Result<(), ExecuteError> {
+        let db = DB::open(&EngineConfig::Memory)?;
+        assert_eq!(check_schema(db.as_ref())?, None);
+        assert!(db.get_value(META_TABLE, SCHEMA_VERSION_KEY)?.is_some());
+        assert_eq!(check_schema(db.as_ref())?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn old_storage_should_be_upgraded() -> Result<(), ExecuteError> {
+        let db = DB::open(&EngineConfig::Memory)?;
+        let kv = KeyValue {
+            key: b"foo".to_vec(),
+            ..Default::default()
+        };
+        let _ignore = db.write_batch(vec![WriteOp::PutKeyValue(Revision::new(1, 0), kv)], false)?;
+        assert_eq!(check_schema(db.as_ref())?, Some(0));
+        upgrade_schema(db.as_ref(), 0)?;
+        assert_eq!(check_schema(db.as_ref())?, None);
+
+        let _ignore = db.write_batch(
+            vec![WriteOp::PutSchemaVersion(CURRENT_SCHEMA_VERSION + 1)],
+            false,
+        )?;
+        assert!(check_schema(db.as_ref()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn pre_upgrade_snapshot_should_be_next_to_the_data_dir() {
+        let engine = EngineConfig::RocksDB(PathBuf::from("/var/lib/xline/data"));
+        assert_eq!(
+            pre_upgrade_snapshot_path(&engine, 0),
+            Some(PathBuf::from("/var/lib/xline/data-pre-upgrade-v0.db"))
+        );
+        assert_eq!(pre_upgrade_snapshot_path(&EngineConfig::Memory, 0), None);
+    }
+}
@@ -3,7 +3,10 @@ use std::path::Path;
 use engine::Snapshot;
 use xlineapi::execute_error::ExecuteError;
 
-use super::{db::WriteOp, revision::KeyRevision};
+use super::{
+    db::{EncodedBatch, WriteOp},
+    revision::KeyRevision,
+};
 
 /// The Stable Storage Api
 #[async_trait::async_trait]
@@ -38,6 +41,18 @@ pub trait StorageApi: Send + Sync + 'static + std::fmt::Debug {
     #[allow(clippy::type_complexity)] // it's clear that (Vec<u8>, Vec<u8>) is a key-value pair
     fn get_all(&self, table: &'static str) -> Result<Vec<(Vec<u8>, Vec<u8>)>, ExecuteError>;
 
+    /// Get the values of the given table whose keys are not less than `from`
+    ///
+    /// # Errors
+    ///
+    /// if error occurs in storage, return `Err(error)`
+    #[allow(clippy::type_complexity)] // it's clear that (Vec<u8>, Vec<u8>) is a key-value pair
+    fn get_from(
+        &self,
+        table: &'static str,
+        from: &[u8],
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>, ExecuteError>;
+
     /// Reset the storage by given snapshot
     ///
     /// # Errors
@@ -48,15 +63,82 @@ pub trait StorageApi: Send + Sync + 'static + std::fmt::Debug {
     /// Get the snapshot of the storage
     fn get_snapshot(&self, snap_path: impl AsRef<Path>) -> Result<Snapshot, ExecuteError>;
 
-    /// Flush the operations to storage
-    fn flush_ops(&self, ops: Vec<WriteOp>) -> Result<Vec<(Vec<u8>, KeyRevision)>, ExecuteError>;
+    /// Write the operations to storage in a single atomic batch, even if they touch
+    /// different tables, and return the revisions of the written keys. If `sync` is
+    /// true, the batch is fsynced before returning.
+    ///
+    /// # Errors
+    ///
+    /// if error occurs in storage, return `Err(error)`
+    fn write_batch(
+        &self,
+        ops: Vec<WriteOp>,
+        sync: bool,
+    ) -> Result<Vec<(Vec<u8>, KeyRevision)>, ExecuteError> {
+        let mut batch = self.encode_batch(ops)?;
+        let revisions = batch.take_revisions();
+        self.write_encoded(vec![batch], sync)?;
+        Ok(revisions)
+    }
+
+    /// Encode, encrypt and seal the operations into a batch without writing it, so that
+    /// batches can be prepared concurrently and written together by `write_encoded`
+    ///
+    /// # Errors
+    ///
+    /// if error occurs in storage, return `Err(error)`
+    fn encode_batch(&self, ops: Vec<WriteOp>) -> Result<EncodedBatch, ExecuteError>;
+
+    /// Write the encoded batches in order in a single atomic batch. If `sync` is true,
+    /// the batch is fsynced before returning.
+    ///
+    /// # Errors
+    ///
+    /// if error occurs in storage, return `Err(error)`
+    fn write_encoded(&self, batches: Vec<EncodedBatch>, sync: bool) -> Result<(), ExecuteError>;
+
+    /// Fsync the batches that are written without fsync
+    ///
+    /// # Errors
+    ///
+    /// if error occurs in storage, return `Err(error)`
+    fn sync(&self) -> Result<(), ExecuteError>;
+
+    /// Calculate the hash of the storage. The `pending_kvs` are hashed as if they were in
+    /// the kv table, and the range tombstones in the meta table are not hashed, so the
+    /// hash doesn't depend on how many deletions of the range tombstones are written.
+    ///
+    /// # Errors
+    ///
+    /// if error occurs in storage, return `Err(error)`
+    fn hash(&self, pending_kvs: Vec<(Vec<u8>, Vec<u8>)>) -> Result<u32, ExecuteError>;
 
-    /// Calculate the hash of the storage
-    fn hash(&self) -> Result<u32, ExecuteError>;
+    /// Check at most `limit` values of the kv table and re-encrypt those encrypted by
+    /// retired data keys, return the number of checked values. The retired data keys are
+    /// dropped once all values are re-encrypted, and `0` is returned since then.
+    ///
+    /// # Errors
+    ///
+    /// if error occurs in storage, return `Err(error)`
+    fn reencrypt(&self, limit: usize) -> Result<usize, ExecuteError>;
 
     /// Get the cached size of the engine
     fn estimated_file_size(&self) -> u64;
 
     /// Get the file size of the engine
     fn file_size(&self) -> Result<u64, ExecuteError>;
+
+    /// Get the estimated size of the live data in the engine
+    fn live_data_size(&self) -> Result<u64, ExecuteError>;
+
+    /// Compact all data of the engine to reclaim space
+    fn defragment(&self) -> Result<(), ExecuteError>;
+
+    /// Get the estimated size of a table, every table is stored separately, e.g. as a
+    /// column family of `RocksDB`
+    ///
+    /// # Errors
+    ///
+    /// if error occurs in storage, return `Err(error)`
+    fn table_size(&self, table: &'static str) -> Result<u64, ExecuteError>;
 }
@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+
+use clippy_utilities::{NumericCast, OverflowArithmetic};
+use parking_lot::RwLock;
+use utils::config::TenantQuota;
+use xlineapi::execute_error::ExecuteError;
+
+/// Usage and quota of a tenant
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct TenantUsage {
+    /// Quota of the tenant
+    pub(crate) quota: TenantQuota,
+    /// Number of keys under the prefix of the tenant
+    pub(crate) keys: u64,
+    /// Bytes of the keys and values under the prefix of the tenant
+    pub(crate) bytes: u64,
+}
+
+impl TenantUsage {
+    /// Check if the usage exceeds the quota
+    fn exceeds(&self, keys: u64, bytes: u64) -> bool {
+        (self.quota.max_keys != 0 && keys > self.quota.max_keys)
+            || (self.quota.max_bytes != 0 && bytes > self.quota.max_bytes)
+    }
+}
+
+/// Per-tenant accounting of the keys in the kv store, a key belongs to the tenant with the
+/// longest prefix of it, keys of no tenant are not tracked.
+///
+/// Usages are updated when requests are synced, so that all members agree on them. Quotas
+/// are checked when requests are executed, every put of a txn is checked against the usage
+/// before the txn, so a txn may exceed the quota by the size of its other puts.
+#[derive(Debug, Default)]
+pub(crate) struct Tenants {
+    /// Inner data of `Tenants`
+    inner: RwLock<TenantsInner>,
+}
+
+/// Inner data of `Tenants`
+#[derive(Debug, Default)]
+struct TenantsInner {
+    /// Usages of tenants
+    usages: Vec<TenantUsage>,
+    /// Sizes of the tracked keys, including the length of the keys and values
+    sizes: HashMap<Vec<u8>, u64>,
+}
+
+impl TenantsInner {
+    /// Get the index of the tenant that a key belongs to
+    fn tenant_of(&self, key: &[u8]) -> Option<usize> {
+        self.usages
+            .iter()
+            .enumerate()
+            .filter(|&(_, usage)| key.starts_with(usage.quota.prefix.as_bytes()))
+            .max_by_key(|&(_, usage)| usage.quota.prefix.len())
+            .map(|(idx, _)| idx)
+    }
+
+    /// Account the size of `key`, which belongs to the tenant at `idx`
+    fn put_size(&mut self, idx: usize, key: &[u8], size: u64) {
+        let prev_size = self.sizes.insert(key.to_vec(), size);
+        #[allow(clippy::indexing_slicing)] // the index is returned by `tenant_of`
+        let usage = &mut self.usages[idx];
+        match prev_size {
+            Some(prev_size) => usage.bytes = usage.bytes.overflow_sub(prev_size),
+            None => usage.keys = usage.keys.overflow_add(1),
+        }
+        usage.bytes = usage.bytes.overflow_add(size);
+    }
+}
+
+impl Tenants {
+    /// New `Tenants`
+    pub(crate) fn new(quotas: Vec<TenantQuota>) -> Self {
+        let usages = quotas
+            .into_iter()
+            .map(|quota| TenantUsage {
+                quota,
+                keys: 0,
+                bytes: 0,
+            })
+            .collect();
+        Self {
+            inner: RwLock::new(TenantsInner {
+                usages,
+                sizes: HashMap::new(),
+            }),
+        }
+    }
+
+    /// Check if putting a value of `value_len` bytes to `key` exceeds the quota of its tenant
+    pub(crate) fn check_put(&self, key: &[u8], value_len: usize) -> Result<(), ExecuteError> {
+        let inner = self.inner.read();
+        let Some(idx) = inner.tenant_of(key) else {
+            return Ok(());
+        };
+        #[allow(clippy::indexing_slicing)] // the index is returned by `tenant_of`
+        let usage = &inner.usages[idx];
+        let size: u64 = key.len().overflow_add(value_len).numeric_cast();
+        let (keys, bytes) = match inner.sizes.get(key) {
+            Some(&prev_size) => (
+                usage.keys,
+                usage.bytes.overflow_sub(prev_size).overflow_add(size),
+            ),
+            None => (usage.keys.overflow_add(1), usage.bytes.overflow_add(size)),
+        };
+        if usage.exceeds(keys, bytes) {
+            return Err(ExecuteError::TenantQuotaExceeded(
+                usage.quota.prefix.clone(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Account a put of a value of `value_len` bytes to `key`
+    pub(crate) fn put(&self, key: &[u8], value_len: usize) {
+        let mut inner = self.inner.write();
+        let Some(idx) = inner.tenant_of(key) else {
+            return;
+        };
+        let size: u64 = key.len().overflow_add(value_len).numeric_cast();
+        inner.put_size(idx, key, size);
+    }
+
+    /// Account a deletion of `key`
+    pub(crate) fn delete(&self, key: &[u8]) {
+        let mut inner = self.inner.write();
+        let Some(prev_size) = inner.sizes.remove(key) else {
+            return;
+        };
+        if let Some(idx) = inner.tenant_of(key) {
+            #[allow(clippy::indexing_slicing)] // the index is returned by `tenant_of`
+            let usage = &mut inner.usages[idx];
+            usage.keys = usage.keys.overflow_sub(1);
+            usage.bytes = usage.bytes.overflow_sub(prev_size);
+        }
+    }
+
+    /// Get usages of all tenants
+    pub(crate) fn usages(&self) -> Vec<TenantUsage> {
+        self.inner.read().usages.clone()
+    }
+
+    /// Get the prefixes of all tenants
+    pub(crate) fn prefixes(&self) -> Vec<String> {
+        self.inner
+            .read()
+            .usages
+            .iter()
+            .map(|usage| usage.quota.prefix.clone())
+            .collect()
+    }
+
+    /// Get the sizes of the tracked keys
+    pub(crate) fn sizes(&self) -> Vec<(Vec<u8>, u64)> {
+        self.inner
+            .read()
+            .sizes
+            .iter()
+            .map(|(key, &size)| (key.clone(), size))
+            .collect()
+    }
+
+    /// Restore the sizes of the tracked keys, which are taken by `sizes` with the same
+    /// tenants
+    pub(crate) fn restore(&self, sizes: Vec<(Vec<u8>, u64)>) {
+        let mut inner = self.inner.write();
+        for (key, size) in sizes {
+            if let Some(idx) = inner.tenant_of(&key) {
+                inner.put_size(idx, &key, size);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn usage_should_follow_puts_and_deletes() {
+        let tenants = Tenants::new(vec![
+            TenantQuota::new("a/".to_owned(), 0, 0),
+            TenantQuota::new("a/b/".to_owned(), 0, 0),
+        ]);
+        tenants.put(b"a/1", 3);
+        tenants.put(b"a/b/1", 5);
+        tenants.put(b"a/1", 1);
+        tenants.put(b"c/1", 100);
+        let usages = tenants.usages();
+        assert_eq!((usages[0].keys, usages[0].bytes), (1, 4));
+        assert_eq!((usages[1].keys, usages[1].bytes), (1, 10));
+
+        tenants.delete(b"a/b/1");
+        tenants.delete(b"c/1");
+        let usages = tenants.usages();
+        assert_eq!((usages[0].keys, usages[0].bytes), (1, 4));
+        assert_eq!((usages[1].keys, usages[1].bytes), (0, 0));
+    }
+
+    #[test]
+    fn put_exceeding_quota_should_be_rejected() {
+        let tenants = Tenants::new(vec![
+            TenantQuota::new("keys/".to_owned(), 1, 0),
+            TenantQuota::new("bytes/".to_owned(), 0, 16),
+        ]);
+        assert!(tenants.check_put(b"keys/1", 100).is_ok());
+        tenants.put(b"keys/1", 100);
+        assert!(tenants.check_put(b"keys/1", 200).is_ok());
+        assert!(matches!(
+            tenants.check_put(b"keys/2", 0),
+            Err(ExecuteError::TenantQuotaExceeded(ref prefix)) if prefix == "keys/"
+        ));
+
+        tenants.put(b"bytes/1", 4);
+        assert!(tenants.check_put(b"bytes/1", 9).is_ok());
+        assert!(tenants.check_put(b"bytes/1", 10).is_err());
+        assert!(tenants.check_put(b"bytes/2", 2).is_err());
+        assert!(tenants.check_put(b"other", 1024).is_ok());
+    }
+}
@@ -1,27 +1,44 @@
 use std::{collections::HashMap, env, path::PathBuf, time::Duration};
 
 use anyhow::Result;
-use clap::Parser;
+use clap::{ArgAction, Parser};
 use tokio::fs;
 use utils::{
     config::{
-        default_batch_max_size, default_batch_timeout, default_candidate_timeout_ticks,
-        default_client_id_keep_alive_interval, default_client_wait_synced_timeout,
-        default_cmd_workers, default_compact_batch_size, default_compact_sleep_interval,
-        default_compact_timeout, default_follower_timeout_ticks, default_gc_interval,
-        default_heartbeat_interval, default_initial_retry_timeout, default_log_entries_cap,
-        default_log_level, default_max_retry_timeout, default_metrics_enable, default_metrics_path,
-        default_metrics_port, default_metrics_push_endpoint, default_metrics_push_protocol,
-        default_propose_timeout, default_quota, default_range_retry_timeout, default_retry_count,
+        default_auth_lockout_duration, default_auth_max_failures, default_auth_token_ttl,
+        default_backup_retention, default_batch_max_size, default_batch_timeout,
+        default_candidate_timeout_ticks, default_client_cert_auth,
+        default_client_health_check_interval, default_client_id_keep_alive_interval,
+        default_client_wait_synced_timeout, default_cmd_workers, default_compact_batch_size,
+        default_compact_sleep_interval, default_compact_timeout, default_cors_max_age,
+        default_follower_timeout_ticks, default_gc_interval, default_grpc_client_rate_burst,
+        default_grpc_keepalive_interval, default_grpc_keepalive_timeout,
+        default_grpc_max_concurrent_streams, default_grpc_max_connection_age,
+        default_heartbeat_interval, default_index_snapshot_interval, default_initial_retry_timeout,
+        default_kv_update_channel_size, default_lease_checkpoint_interval, default_log_entries_cap,
+        default_log_level, default_max_apply_queue_depth, default_max_request_bytes,
+        default_max_retry_timeout, default_max_txn_ops, default_memory_limit,
+        default_metrics_enable, default_metrics_path, default_metrics_port,
+        default_metrics_push_endpoint, default_metrics_push_protocol,
+        default_password_hash_memory_cost, default_password_hash_parallelism,
+        default_password_hash_time_cost, default_propose_timeout, default_quota,
+        default_range_retry_timeout, default_resolve_interval, default_retry_count,
         default_rotation, default_rpc_timeout, default_server_wait_synced_timeout,
-        default_sync_victims_interval, default_watch_progress_notify_interval, AuthConfig,
-        AutoCompactConfig, ClientConfig, ClusterConfig, CompactConfig, CurpConfigBuilder,
-        EngineConfig, InitialClusterState, LevelConfig, LogConfig, MetricsConfig,
-        MetricsPushProtocol, RotationConfig, ServerTimeout, StorageConfig, TlsConfig, TraceConfig,
-        XlineServerConfig,
+        default_shutdown_drain_timeout, default_slow_request_threshold,
+        default_snapshot_max_resumes, default_snapshot_rate_limit, default_sync_victims_interval,
+        default_trace_sampling_ratio, default_watch_progress_notify_interval,
+        default_watcher_buffer_size, AuditConfig, AuthConfig, AuthLockoutConfig, AutoCompactConfig,
+        BackupConfig, ChecksumPolicy, ClientConfig, ClusterConfig, CompactConfig,
+        CurpConfigBuilder, DurabilityPolicy, EncryptionConfig, EngineConfig, GrpcCompression,
+        GrpcConfig, GrpcWebConfig, InitialClusterState, KeySource, KvUpdateBackpressure,
+        LevelConfig, LogConfig, MetricsConfig, MetricsPushProtocol, PasswordHashConfig,
+        RotationConfig, ServerTimeout, SlowWatcherPolicy, StorageConfig, TenantQuota, TlsConfig,
+        TraceConfig, UserQosClass, XlineServerConfig,
     },
-    parse_batch_bytes, parse_duration, parse_log_level, parse_members, parse_metrics_push_protocol,
-    parse_rotation, parse_state, ConfigFileError,
+    parse_backup_retention, parse_batch_bytes, parse_duration, parse_grpc_compression,
+    parse_kv_update_backpressure, parse_log_level, parse_members, parse_metrics_push_protocol,
+    parse_rotation, parse_slow_watcher_policy, parse_state, parse_tenant_quota,
+    parse_user_qos_class, ConfigFileError,
 };
 
 /// Xline server config path env name
@@ -49,18 +66,107 @@ pub struct ServerArgs {
     /// Node client advertise urls
     #[clap(long, num_args = 1.., value_delimiter = ',')]
     client_advertise_urls: Vec<String>,
+    /// Node client listen urls of the HTTP/JSON gateway, the gateway is disabled if not set
+    #[clap(long, num_args = 1.., value_delimiter = ',')]
+    client_http_listen_urls: Vec<String>,
     /// Cluster peers. eg: node1=192.168.x.x:8080,192.168.x.x:8081,node2=192.168.x.x:8083
-    #[clap(long, value_parser = parse_members)]
-    members: HashMap<String, Vec<String>>,
+    #[clap(long, value_parser = parse_members, required_unless_present = "discovery_srv")]
+    members: Option<HashMap<String, Vec<String>>>,
+    /// The domain to discover cluster peers from DNS SRV records, used when `members` is empty
+    #[clap(long)]
+    discovery_srv: Option<String>,
     /// If node is leader
     #[clap(long)]
     is_leader: bool,
+    /// Whether to register the gRPC reflection service on the client endpoint
+    #[clap(long)]
+    enable_grpc_reflection: bool,
+    /// Whether to accept grpc-web requests from browser clients on the client endpoint
+    #[clap(long)]
+    enable_grpc_web: bool,
+    /// Origins allowed by CORS for grpc-web requests, cross-origin requests are denied if
+    /// not set
+    #[clap(long, num_args = 1.., value_delimiter = ',')]
+    cors_allowed_origins: Vec<String>,
+    /// How long the results of a CORS preflight request can be cached [default: 1d]
+    #[clap(long, value_parser = parse_duration)]
+    cors_max_age: Option<Duration>,
+    /// Max number of operations in a txn request
+    #[clap(long, default_value_t = default_max_txn_ops())]
+    max_txn_ops: usize,
+    /// Max size of a request that will be proposed, eg: 512KB, 2MB [default: 1.5MB]
+    #[clap(long, value_parser = parse_batch_bytes)]
+    max_request_bytes: Option<u64>,
+    /// Compression of the sent gRPC messages, `none`, `gzip` or `zstd` [default: none]
+    #[clap(long, value_parser = parse_grpc_compression)]
+    grpc_compression: Option<GrpcCompression>,
+    /// QoS classes of the requests of users, eg: kube-apiserver=critical,importer=batch means
+    /// requests of "kube-apiserver" are executed before the others and requests of "importer"
+    /// after them, a request can lower its class by the `qos-class` metadata, eg: batch
+    #[clap(long, value_parser = parse_user_qos_class, num_args = 1.., value_delimiter = ',')]
+    qos_classes: Vec<UserQosClass>,
+    /// Interval of the HTTP/2 keepalive pings, `0s` disables them [default: 2h]
+    #[clap(long, value_parser = parse_duration)]
+    grpc_keepalive_interval: Option<Duration>,
+    /// Timeout of the HTTP/2 keepalive pings [default: 20s]
+    #[clap(long, value_parser = parse_duration)]
+    grpc_keepalive_timeout: Option<Duration>,
+    /// Max number of concurrent streams of each gRPC connection
+    #[clap(long, default_value_t = default_grpc_max_concurrent_streams())]
+    grpc_max_concurrent_streams: u32,
+    /// Initial HTTP/2 window size of each stream in bytes, `0` keeps the default
+    #[clap(long, default_value_t = 0)]
+    grpc_initial_stream_window_size: u32,
+    /// Initial HTTP/2 window size of each connection in bytes, `0` keeps the default
+    #[clap(long, default_value_t = 0)]
+    grpc_initial_connection_window_size: u32,
+    /// Max age of the client connections, `0s` keeps them forever [default: 0s]
+    #[clap(long, value_parser = parse_duration)]
+    grpc_max_connection_age: Option<Duration>,
+    /// Max number of client connections, `0` means unlimited
+    #[clap(long, default_value_t = 0)]
+    max_connections: usize,
+    /// Max number of watch streams of each client connection, `0` means unlimited
+    #[clap(long, default_value_t = 0)]
+    max_watch_streams_per_connection: usize,
+    /// Max requests per second of each client user or ip, `0` means unlimited
+    #[clap(long, default_value_t = 0)]
+    client_rate_limit: u32,
+    /// Max requests a client may send in a burst
+    #[clap(long, default_value_t = default_grpc_client_rate_burst())]
+    client_rate_burst: u32,
     /// Private key used to sign the token
     #[clap(long)]
     auth_private_key: Option<PathBuf>,
     /// Public key used to verify the token
     #[clap(long)]
     auth_public_key: Option<PathBuf>,
+    /// Public keys of previous private keys, tokens signed by them are still accepted
+    #[clap(long, value_delimiter = ',')]
+    auth_previous_public_keys: Vec<PathBuf>,
+    /// How long an assigned token is valid [default: 5m]
+    #[clap(long, value_parser = parse_duration)]
+    auth_token_ttl: Option<Duration>,
+    /// Memory size in KiB used to hash user passwords with argon2id
+    #[clap(long, default_value_t = default_password_hash_memory_cost())]
+    password_hash_memory_cost: u32,
+    /// Number of iterations used to hash user passwords with argon2id
+    #[clap(long, default_value_t = default_password_hash_time_cost())]
+    password_hash_time_cost: u32,
+    /// Degree of parallelism used to hash user passwords with argon2id
+    #[clap(long, default_value_t = default_password_hash_parallelism())]
+    password_hash_parallelism: u32,
+    /// Number of consecutive failed authentications that locks a client address, or a user
+    /// on that address, 0 disables lockout. Failures are counted by each member separately
+    #[clap(long, default_value_t = default_auth_max_failures())]
+    auth_max_failures: u32,
+    /// How long a user or client address is locked after failed authentications [default: 1m]
+    #[clap(long, value_parser = parse_duration)]
+    auth_lockout_duration: Option<Duration>,
+    /// Whether to authenticate clients by the common name of their certificates, pass
+    /// `--client-cert-auth false` to turn it off
+    #[clap(long, default_value_t = default_client_cert_auth(), action = ArgAction::Set)]
+    client_cert_auth: bool,
     /// Open jaeger offline
     #[clap(long)]
     jaeger_offline: bool,
@@ -73,6 +179,12 @@ pub struct ServerArgs {
     /// Trace level of jaeger
     #[clap(long, value_parser = parse_log_level, default_value_t = default_log_level())]
     jaeger_level: LevelConfig,
+    /// The OTLP endpoint that spans are exported to when jaeger online is on
+    #[clap(long)]
+    otlp_endpoint: Option<String>,
+    /// The ratio of traces to be sampled, ranging from 0.0 to 1.0
+    #[clap(long, default_value_t = default_trace_sampling_ratio())]
+    trace_sampling_ratio: f64,
     /// Whether to enable metrics
     #[clap(long, default_value_t = default_metrics_enable())]
     metrics_enable: bool,
@@ -100,6 +212,15 @@ pub struct ServerArgs {
     /// Log verbosity level, eg: trace, debug, info, warn, error
     #[clap(long, value_parser = parse_log_level, default_value_t = default_log_level())]
     log_level: LevelConfig,
+    /// Enable the audit log
+    #[clap(long)]
+    audit_enable: bool,
+    /// Directory of the audit log files
+    #[clap(long)]
+    audit_path: Option<PathBuf>,
+    /// Audit log rotate strategy, eg: never, hourly, daily
+    #[clap(long, value_parser = parse_rotation, default_value_t = default_rotation())]
+    audit_rotate: RotationConfig,
     /// Heartbeat interval between curp server nodes [default: 300ms]
     #[clap(long, value_parser = parse_duration)]
     heartbeat_interval: Option<Duration>,
@@ -130,6 +251,15 @@ pub struct ServerArgs {
     /// Number of log entries to keep in memory
     #[clap(long, default_value_t = default_log_entries_cap())]
     log_entries_cap: usize,
+    /// Maximum bytes per second sent when installing a snapshot to a follower, `0` means unlimited
+    #[clap(long, default_value_t = default_snapshot_rate_limit())]
+    snapshot_rate_limit: u64,
+    /// How many times an interrupted snapshot transfer is resumed
+    #[clap(long, default_value_t = default_snapshot_max_resumes())]
+    snapshot_max_resumes: usize,
+    /// Interval to re-resolve the hostnames of the peers, `0s` disables it [default: 30s]
+    #[clap(long, value_parser = parse_duration)]
+    resolve_interval: Option<Duration>,
     /// Curp client wait synced timeout [default: 2s]
     #[clap(long, value_parser = parse_duration)]
     client_wait_synced_timeout: Option<Duration>,
@@ -148,6 +278,13 @@ pub struct ServerArgs {
     /// Curp client id keep alive interval [default: 1s]
     #[clap(long, value_parser = parse_duration)]
     client_keep_alive_interval: Option<Duration>,
+    /// Curp client endpoint health check interval, `0s` disables it [default: 0s]
+    #[clap(long, value_parser = parse_duration)]
+    client_health_check_interval: Option<Duration>,
+    /// Curp client interval to re-resolve the hostnames of the members, `0s` disables it
+    /// [default: 30s]
+    #[clap(long, value_parser = parse_duration)]
+    client_resolve_interval: Option<Duration>,
     /// How often should the gc task run [default: 20s]
     #[clap(long, value_parser = parse_duration)]
     gc_interval: Option<Duration>,
@@ -163,8 +300,39 @@ pub struct ServerArgs {
     /// How often should watch progress notify send a response [default: 600s]
     #[clap(long, value_parser = parse_duration)]
     watch_progress_notify_interval: Option<Duration>,
-    /// Storage engine
+    /// Max number of pending events buffered for a slow watcher [default: 4096]
+    #[clap(long)]
+    watcher_buffer_size: Option<usize>,
+    /// What to do when a slow watcher exceeds its buffer, `resync` or `cancel` [default: resync]
+    #[clap(long, value_parser = parse_slow_watcher_policy)]
+    slow_watcher_policy: Option<SlowWatcherPolicy>,
+    /// Size of the channel of the kv updates sent to the watchers [default: 1024]
     #[clap(long)]
+    kv_update_channel_size: Option<usize>,
+    /// What to do when the kv update channel is full, `block` the writes or `mark_victims`
+    /// to let the watchers catch up from the storage [default: block]
+    #[clap(long, value_parser = parse_kv_update_backpressure)]
+    kv_update_backpressure: Option<KvUpdateBackpressure>,
+    /// How often should the leader checkpoint the remaining ttl of leases [default: 300s]
+    #[clap(long, value_parser = parse_duration)]
+    lease_checkpoint_interval: Option<Duration>,
+    /// Requests that take longer than this threshold are logged as slow requests [default: 300ms]
+    #[clap(long, value_parser = parse_duration)]
+    slow_request_threshold: Option<Duration>,
+    /// Max number of commands waiting to be applied before the server sheds load, `0` means
+    /// unlimited [default: 10000]
+    #[clap(long)]
+    max_apply_queue_depth: Option<usize>,
+    /// Resident memory in bytes above which the server sheds load, `0` means unlimited
+    /// [default: 0]
+    #[clap(long)]
+    memory_limit: Option<u64>,
+    /// Max time to wait for the in-flight client requests to finish on graceful shutdown,
+    /// keep it below the termination grace period of the orchestrator [default: 10s]
+    #[clap(long, value_parser = parse_duration)]
+    shutdown_drain_timeout: Option<Duration>,
+    /// Storage engine, one of memory, rocksdb and redb
+    #[clap(long, value_parser = ["memory", "rocksdb", "redb"])]
     storage_engine: String,
     /// DB directory
     #[clap(long)]
@@ -195,6 +363,50 @@ pub struct ServerArgs {
     /// Quota
     #[clap(long)]
     quota: Option<u64>,
+    /// Quotas of tenants, eg: tenant-a/=1000:1048576,tenant-b/=0:4096 means at most 1000 keys
+    /// and 1048576 bytes under "tenant-a/", and at most 4096 bytes under "tenant-b/"
+    #[clap(long, value_parser = parse_tenant_quota, num_args = 1.., value_delimiter = ',')]
+    tenant_quotas: Vec<TenantQuota>,
+    /// File of the key encryption key, which is a base64 encoded 256-bit key. Stored
+    /// values are encrypted if it is given. All members must use the same key encryption
+    /// key, since snapshots are sent to other members as is, so rotate it on all members
+    #[clap(long)]
+    encryption_kek_file: Option<PathBuf>,
+    /// Files of the previous key encryption keys, which are used to decrypt the data
+    /// keys after the key encryption key is rotated
+    #[clap(long, num_args = 1.., value_delimiter = ',')]
+    encryption_previous_kek_files: Vec<PathBuf>,
+    /// Durability policy of the storage, one of always, periodic and commit [default: commit]
+    #[clap(
+        long,
+        value_parser = ["always", "periodic", "commit"],
+        requires_if("periodic", "durability_sync_interval")
+    )]
+    durability_policy: Option<String>,
+    /// Interval between two fsyncs of the storage, required when the durability policy is
+    /// periodic
+    #[clap(long, value_parser = parse_duration)]
+    durability_sync_interval: Option<Duration>,
+    /// Checksums of the values in the storage, one of off, write and verify [default: off]
+    #[clap(long, value_parser = ["off", "write", "verify"])]
+    checksum_policy: Option<String>,
+    /// Interval between two snapshots of the in-memory index, 0 means the snapshots are
+    /// not taken [default: 5m]
+    #[clap(long, value_parser = parse_duration)]
+    index_snapshot_interval: Option<Duration>,
+    /// Directory of the periodic local backups, backups are taken if it is given
+    #[clap(long)]
+    backup_dir: Option<PathBuf>,
+    /// Interval between two backups, 0 means backups are not taken by time [default: 0s]
+    #[clap(long, value_parser = parse_duration)]
+    backup_interval: Option<Duration>,
+    /// Number of applied log entries between two backups, 0 means backups are not taken
+    /// by entries
+    #[clap(long, default_value_t = 0)]
+    backup_entries: u64,
+    /// Number of the most recent backups to keep, at least 1
+    #[clap(long, value_parser = parse_backup_retention, default_value_t = default_backup_retention())]
+    backup_retention: usize,
     /// Server ca certificate path, used to verify client certificate
     #[clap(long)]
     peer_ca_cert_path: Option<PathBuf>,
@@ -213,6 +425,15 @@ pub struct ServerArgs {
     /// Client private key path
     #[clap(long)]
     client_key_path: Option<PathBuf>,
+    /// Client-facing server certificate path, fall back to `peer_cert_path` if unset
+    #[clap(long)]
+    server_cert_path: Option<PathBuf>,
+    /// Client-facing server private key path, fall back to `peer_key_path` if unset
+    #[clap(long)]
+    server_key_path: Option<PathBuf>,
+    /// Client-facing server ca certificate path, used to verify client certificate
+    #[clap(long)]
+    server_ca_cert_path: Option<PathBuf>,
 }
 
 #[allow(clippy::too_many_lines)] // will be refactored in #604
@@ -230,10 +451,65 @@ impl From<ServerArgs> for XlineServerConfig {
                     path
                 })),
             ),
-            &_ => unreachable!("xline only supports memory and rocksdb engine"),
+            "redb" => (
+                EngineConfig::Redb(args.data_dir.clone()),
+                EngineConfig::Redb(args.curp_dir.unwrap_or_else(|| {
+                    let mut path = args.data_dir;
+                    path.push("curp");
+                    path
+                })),
+            ),
+            &_ => unreachable!("xline only supports memory, rocksdb and redb engine"),
         };
 
-        let storage = StorageConfig::new(engine, args.quota.unwrap_or_else(default_quota));
+        let durability = match args.durability_policy.as_deref() {
+            Some("always") => DurabilityPolicy::Always,
+            Some("periodic") => {
+                let interval = args.durability_sync_interval.unwrap_or_else(|| {
+                    unreachable!("clap requires durability_sync_interval for the periodic policy")
+                });
+                DurabilityPolicy::Periodic(interval)
+            }
+            Some("commit") | None => DurabilityPolicy::Commit,
+            Some(_) => unreachable!(
+                "xline only supports three durability policies: always, periodic, commit"
+            ),
+        };
+        let checksum = match args.checksum_policy.as_deref() {
+            Some("write") => ChecksumPolicy::Write,
+            Some("verify") => ChecksumPolicy::Verify,
+            Some("off") | None => ChecksumPolicy::Off,
+            Some(_) => {
+                unreachable!("xline only supports three checksum policies: off, write, verify")
+            }
+        };
+        let storage = StorageConfig::new(
+            engine,
+            args.quota.unwrap_or_else(default_quota),
+            args.tenant_quotas,
+            args.encryption_kek_file.map(|path| {
+                EncryptionConfig::new(
+                    KeySource::File { path },
+                    args.encryption_previous_kek_files
+                        .into_iter()
+                        .map(|path| KeySource::File { path })
+                        .collect(),
+                )
+            }),
+            durability,
+            args.backup_dir.map(|dir| {
+                BackupConfig::new(
+                    dir,
+                    args.backup_interval.unwrap_or_default(),
+                    args.backup_entries,
+                    args.backup_retention,
+                    None,
+                )
+            }),
+            checksum,
+            args.index_snapshot_interval
+                .unwrap_or_else(default_index_snapshot_interval),
+        );
         let Ok(curp_config) = CurpConfigBuilder::default()
             .heartbeat_interval(
                 args.heartbeat_interval
@@ -251,6 +527,12 @@ impl From<ServerArgs> for XlineServerConfig {
             .engine_cfg(curp_engine)
             .gc_interval(args.gc_interval.unwrap_or_else(default_gc_interval))
             .cmd_workers(args.cmd_workers)
+            .snapshot_rate_limit(args.snapshot_rate_limit)
+            .snapshot_max_resumes(args.snapshot_max_resumes)
+            .resolve_interval(
+                args.resolve_interval
+                    .unwrap_or_else(default_resolve_interval),
+            )
             .build()
         else {
             panic!("failed to create curp config")
@@ -268,6 +550,10 @@ impl From<ServerArgs> for XlineServerConfig {
             args.client_fixed_backoff,
             args.client_keep_alive_interval
                 .unwrap_or_else(default_client_id_keep_alive_interval),
+            args.client_health_check_interval
+                .unwrap_or_else(default_client_health_check_interval),
+            args.client_resolve_interval
+                .unwrap_or_else(default_resolve_interval),
         );
         let server_timeout = ServerTimeout::new(
             args.range_retry_timeout
@@ -277,20 +563,66 @@ impl From<ServerArgs> for XlineServerConfig {
                 .unwrap_or_else(default_sync_victims_interval),
             args.watch_progress_notify_interval
                 .unwrap_or_else(default_watch_progress_notify_interval),
+            args.watcher_buffer_size
+                .unwrap_or_else(default_watcher_buffer_size),
+            args.slow_watcher_policy.unwrap_or_default(),
+            args.kv_update_channel_size
+                .unwrap_or_else(default_kv_update_channel_size),
+            args.kv_update_backpressure.unwrap_or_default(),
+            args.lease_checkpoint_interval
+                .unwrap_or_else(default_lease_checkpoint_interval),
+            args.slow_request_threshold
+                .unwrap_or_else(default_slow_request_threshold),
+            args.max_apply_queue_depth
+                .unwrap_or_else(default_max_apply_queue_depth),
+            args.memory_limit.unwrap_or_else(default_memory_limit),
+            args.shutdown_drain_timeout
+                .unwrap_or_else(default_shutdown_drain_timeout),
         );
         let initial_cluster_state = args.initial_cluster_state.unwrap_or_default();
+        let grpc_web = args.enable_grpc_web.then(|| {
+            GrpcWebConfig::new(
+                args.cors_allowed_origins,
+                args.cors_max_age.unwrap_or_else(default_cors_max_age),
+            )
+        });
+        let grpc = GrpcConfig::new(
+            args.grpc_keepalive_interval
+                .unwrap_or_else(default_grpc_keepalive_interval),
+            args.grpc_keepalive_timeout
+                .unwrap_or_else(default_grpc_keepalive_timeout),
+            args.grpc_max_concurrent_streams,
+            args.grpc_initial_stream_window_size,
+            args.grpc_initial_connection_window_size,
+            args.grpc_max_connection_age
+                .unwrap_or_else(default_grpc_max_connection_age),
+            args.max_connections,
+            args.max_watch_streams_per_connection,
+            args.client_rate_limit,
+            args.client_rate_burst,
+        );
         let cluster = ClusterConfig::new(
             args.name,
             args.peer_listen_urls,
             args.peer_advertise_urls,
             args.client_listen_urls,
             args.client_advertise_urls,
-            args.members,
+            args.members.unwrap_or_default(),
             args.is_leader,
             curp_config,
             client_config,
             server_timeout,
             initial_cluster_state,
+            args.discovery_srv,
+            args.enable_grpc_reflection,
+            args.client_http_listen_urls,
+            grpc_web,
+            args.max_txn_ops,
+            args.max_request_bytes
+                .unwrap_or_else(default_max_request_bytes),
+            args.grpc_compression.unwrap_or_default(),
+            grpc,
+            args.qos_classes,
         );
         let log = LogConfig::new(args.log_file, args.log_rotate, args.log_level);
         let trace = TraceConfig::new(
@@ -298,8 +630,26 @@ impl From<ServerArgs> for XlineServerConfig {
             args.jaeger_offline,
             args.jaeger_output_dir,
             args.jaeger_level,
+            args.otlp_endpoint,
+            args.trace_sampling_ratio,
+        );
+        let auth = AuthConfig::new(
+            args.auth_public_key,
+            args.auth_private_key,
+            args.auth_previous_public_keys,
+            args.auth_token_ttl.unwrap_or_else(default_auth_token_ttl),
+            PasswordHashConfig::new(
+                args.password_hash_memory_cost,
+                args.password_hash_time_cost,
+                args.password_hash_parallelism,
+            ),
+            AuthLockoutConfig::new(
+                args.auth_max_failures,
+                args.auth_lockout_duration
+                    .unwrap_or_else(default_auth_lockout_duration),
+            ),
+            args.client_cert_auth,
         );
-        let auth = AuthConfig::new(args.auth_public_key, args.auth_private_key);
         let auto_compactor_cfg = if let Some(mode) = args.auto_compact_mode {
             match mode.as_str() {
                 "periodic" => {
@@ -334,6 +684,9 @@ impl From<ServerArgs> for XlineServerConfig {
             args.client_ca_cert_path,
             args.client_cert_path,
             args.client_key_path,
+            args.server_cert_path,
+            args.server_key_path,
+            args.server_ca_cert_path,
         );
         let metrics = MetricsConfig::new(
             args.metrics_enable,
@@ -343,7 +696,10 @@ impl From<ServerArgs> for XlineServerConfig {
             args.metrics_push_endpoint,
             args.metrics_push_protocol,
         );
-        XlineServerConfig::new(cluster, storage, log, trace, auth, compact, tls, metrics)
+        let audit = AuditConfig::new(args.audit_enable, args.audit_path, args.audit_rotate);
+        XlineServerConfig::new(
+            cluster, storage, log, trace, auth, compact, tls, metrics, audit,
+        )
     }
 }
 
@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use hickory_resolver::TokioAsyncResolver;
+
+/// The SRV service of xline peers serving without TLS
+const SRV_SERVICE: &str = "_xline-server._tcp";
+
+/// The SRV service of xline peers serving with TLS
+const SRV_SERVICE_SSL: &str = "_xline-server-ssl._tcp";
+
+/// Discover the members of a cluster when bootstrapping
+#[async_trait]
+pub trait Discovery: Send + Sync {
+    /// Get the peer urls of all members, keyed by the member name
+    async fn discover(&self) -> Result<HashMap<String, Vec<String>>>;
+}
+
+/// Discovery from the members listed in the config
+#[derive(Debug, Clone)]
+pub struct StaticDiscovery {
+    /// All members and their peer urls
+    members: HashMap<String, Vec<String>>,
+}
+
+impl StaticDiscovery {
+    /// New `StaticDiscovery`
+    #[inline]
+    #[must_use]
+    pub fn new(members: HashMap<String, Vec<String>>) -> Self {
+        Self { members }
+    }
+}
+
+#[async_trait]
+impl Discovery for StaticDiscovery {
+    #[inline]
+    async fn discover(&self) -> Result<HashMap<String, Vec<String>>> {
+        Ok(self.members.clone())
+    }
+}
+
+/// Discovery from the DNS SRV records of a domain
+///
+/// The records are looked up under `_xline-server-ssl._tcp.<domain>` when
+/// peers use TLS, or `_xline-server._tcp.<domain>` otherwise. Each member is
+/// named after the first label of its target host, so a node should use that
+/// label as its name.
+#[derive(Debug, Clone)]
+pub struct DnsSrvDiscovery {
+    /// The domain to look up
+    domain: String,
+    /// Whether peers use TLS
+    tls: bool,
+}
+
+impl DnsSrvDiscovery {
+    /// New `DnsSrvDiscovery`
+    #[inline]
+    #[must_use]
+    pub fn new(domain: impl Into<String>, tls: bool) -> Self {
+        Self {
+            domain: domain.into(),
+            tls,
+        }
+    }
+}
+
+#[async_trait]
+impl Discovery for DnsSrvDiscovery {
+    #[inline]
+    async fn discover(&self) -> Result<HashMap<String, Vec<String>>> {
+        let (service, scheme) = if self.tls {
+            (SRV_SERVICE_SSL, "https")
+        } else {
+            (SRV_SERVICE, "http")
+        };
+        let resolver = TokioAsyncResolver::tokio_from_system_conf()?;
+        let lookup = resolver
+            .srv_lookup(format!("{service}.{}", self.domain))
+            .await?;
+        let members = members_from_srv_records(
+            lookup
+                .iter()
+                .map(|srv| (srv.target().to_utf8(), srv.port())),
+            scheme,
+        );
+        if members.is_empty() {
+            return Err(anyhow!(
+                "no SRV records found for {service}.{}",
+                self.domain
+            ));
+        }
+        Ok(members)
+    }
+}
+
+/// Build the members map from SRV records of `(target, port)`
+fn members_from_srv_records(
+    records: impl IntoIterator<Item = (String, u16)>,
+    scheme: &str,
+) -> HashMap<String, Vec<String>> {
+    let mut members: HashMap<String, Vec<String>> = HashMap::new();
+    for (target, port) in records {
+        let host = target.trim_end_matches('.');
+        let name = host.split('.').next().unwrap_or(host).to_owned();
+        members
+            .entry(name)
+            .or_default()
+            .push(format!("{scheme}://{host}:{port}"));
+    }
+    members
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn members_should_be_built_from_srv_records() {
+        let records = [
+            ("node1.xline.local.".to_owned(), 2380),
+            ("node2.xline.local.".to_owned(), 2380),
+            ("node1.xline.local.".to_owned(), 2381),
+        ];
+        let members = members_from_srv_records(records, "http");
+        assert_eq!(members.len(), 2);
+        assert_eq!(
+            members["node1"],
+            vec![
+                "http://node1.xline.local:2380".to_owned(),
+                "http://node1.xline.local:2381".to_owned()
+            ]
+        );
+        assert_eq!(members["node2"], vec!["http://node2.xline.local:2380"]);
+    }
+
+    #[tokio::test]
+    async fn static_discovery_should_return_configured_members() -> Result<()> {
+        let members = HashMap::from([("node1".to_owned(), vec!["127.0.0.1:2380".to_owned()])]);
+        let discovered = StaticDiscovery::new(members.clone()).discover().await?;
+        assert_eq!(discovered, members);
+        Ok(())
+    }
+}
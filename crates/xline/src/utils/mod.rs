@@ -1,5 +1,7 @@
 /// Xline command line arguments
 mod args;
+/// Cluster members discovery
+mod discovery;
 /// Xline tracing init
 mod trace;
 
@@ -7,5 +9,6 @@ mod trace;
 mod metrics;
 
 pub use args::{parse_config, ServerArgs};
+pub use discovery::{Discovery, DnsSrvDiscovery, StaticDiscovery};
 pub use metrics::init_metrics;
-pub use trace::init_subscriber;
+pub use trace::{init_subscriber, LogLevelHandle};
@@ -1,9 +1,31 @@
 use anyhow::Result;
 use opentelemetry_contrib::trace::exporter::jaeger_json::JaegerJsonExporter;
-use opentelemetry_sdk::runtime::Tokio;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{runtime::Tokio, trace::Sampler};
 use tracing_appender::non_blocking::WorkerGuard;
-use tracing_subscriber::{fmt::format, layer::SubscriberExt, util::SubscriberInitExt, Layer};
-use utils::config::{file_appender, LogConfig, TraceConfig};
+use tracing_subscriber::{
+    filter::FilterExt, fmt::format, layer::SubscriberExt, reload, util::SubscriberInitExt, Layer,
+    Registry,
+};
+use utils::config::{file_appender, LevelConfig, LogConfig, TraceConfig};
+
+/// Handle to change the log level at runtime
+#[derive(Debug, Clone)]
+pub struct LogLevelHandle {
+    /// The reload handle of the log level filter
+    inner: reload::Handle<LevelConfig, Registry>,
+}
+
+impl LogLevelHandle {
+    /// Change the log level
+    /// # Errors
+    /// Return error if the subscriber has been dropped
+    #[inline]
+    pub fn reload(&self, level: LevelConfig) -> Result<()> {
+        self.inner.reload(level)?;
+        Ok(())
+    }
+}
 
 /// init tracing subscriber
 /// # Errors
@@ -13,28 +35,47 @@ pub fn init_subscriber(
     name: &str,
     log_config: &LogConfig,
     trace_config: &TraceConfig,
-) -> Result<Option<WorkerGuard>> {
+) -> Result<(Option<WorkerGuard>, LogLevelHandle)> {
     let mut guard = None;
-    let log_file_layer = log_config.path().as_ref().map(|log_path| {
-        let file_appender = file_appender(*log_config.rotation(), log_path, name);
-        // `WorkerGuard` should be assigned in the `main` function or whatever the entrypoint of the program is.
-        let (non_blocking, guard_inner) = tracing_appender::non_blocking(file_appender);
-        guard = Some(guard_inner);
-        tracing_subscriber::fmt::layer()
-            .event_format(format().compact())
-            .with_writer(non_blocking)
-            .with_ansi(false)
-            .with_filter(*log_config.level())
-    });
+    let (level_filter, level_handle) = reload::Layer::new(*log_config.level());
+    // The configured level applies to the log file, or to stdout along with `RUST_LOG` if
+    // there is no log file
+    let log_layer: Box<dyn Layer<Registry> + Send + Sync> = match *log_config.path() {
+        Some(ref log_path) => {
+            let file_appender = file_appender(*log_config.rotation(), log_path, name);
+            // `WorkerGuard` should be assigned in the `main` function or whatever the entrypoint of the program is.
+            let (non_blocking, guard_inner) = tracing_appender::non_blocking(file_appender);
+            guard = Some(guard_inner);
+            Box::new(
+                tracing_subscriber::fmt::layer()
+                    .event_format(format().compact())
+                    .with_writer(non_blocking)
+                    .with_ansi(false)
+                    .with_filter(level_filter),
+            )
+        }
+        None => Box::new(
+            tracing_subscriber::fmt::layer()
+                .with_filter(tracing_subscriber::EnvFilter::from_default_env().or(level_filter)),
+        ),
+    };
 
     let jaeger_level = *trace_config.jaeger_level();
     let jaeger_online_layer = trace_config
         .jaeger_online()
         .then(|| {
-            let otlp_exporter = opentelemetry_otlp::new_exporter().tonic();
+            let mut otlp_exporter = opentelemetry_otlp::new_exporter().tonic();
+            if let Some(endpoint) = trace_config.otlp_endpoint().as_ref() {
+                otlp_exporter = otlp_exporter.with_endpoint(endpoint);
+            }
+            // Follow the sampling decision of the remote parent if there is one
+            let sampler = Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+                *trace_config.sampling_ratio(),
+            )));
             opentelemetry_otlp::new_pipeline()
                 .tracing()
                 .with_exporter(otlp_exporter)
+                .with_trace_config(opentelemetry_sdk::trace::config().with_sampler(sampler))
                 .install_batch(Tokio)
                 .ok()
         })
@@ -56,14 +97,22 @@ pub fn init_subscriber(
         )
     });
 
-    let jaeger_fmt_layer = tracing_subscriber::fmt::layer()
-        .with_filter(tracing_subscriber::EnvFilter::from_default_env());
+    // without a log file, `RUST_LOG` is applied by the stdout layer above
+    let jaeger_fmt_layer = log_config.path().is_some().then(|| {
+        tracing_subscriber::fmt::layer()
+            .with_filter(tracing_subscriber::EnvFilter::from_default_env())
+    });
 
     tracing_subscriber::registry()
-        .with(log_file_layer)
+        .with(log_layer)
         .with(jaeger_fmt_layer)
         .with(jaeger_online_layer)
         .with(jaeger_offline_layer)
         .try_init()?;
-    Ok(guard)
+    Ok((
+        guard,
+        LogLevelHandle {
+            inner: level_handle,
+        },
+    ))
 }
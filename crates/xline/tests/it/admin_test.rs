@@ -0,0 +1,109 @@
+use std::{error::Error, time::Duration};
+
+use test_macros::abort_on_panic;
+use utils::config::TenantQuota;
+use xline_client::error::XlineClientError;
+use xline_test_utils::{
+    types::kv::{DeleteRangeRequest, PutRequest},
+    types::lease::LeaseGrantRequest,
+    Cluster,
+};
+use xlineapi::{
+    execute_error::ExecuteError, AdminClient, LeaseDumpRequest, RuntimeStatusRequest,
+    TenantUsageRequest,
+};
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn admin_should_report_runtime_status_and_dump_leases() -> Result<(), Box<dyn Error>> {
+    let mut cluster = Cluster::new(3).await;
+    cluster.start().await;
+    let client = cluster.client().await;
+    let lease_id = client
+        .lease_client()
+        .grant(LeaseGrantRequest::new(60))
+        .await?
+        .id;
+    let _ignore = client
+        .kv_client()
+        .put(PutRequest::new("foo", "bar").with_lease(lease_id))
+        .await?;
+
+    let mut leader_url = None;
+    for url in cluster.all_client_addrs() {
+        let mut admin_client = AdminClient::connect(url.clone()).await?;
+        let status = admin_client
+            .runtime_status(RuntimeStatusRequest {})
+            .await?
+            .into_inner();
+        assert_ne!(status.leader, 0);
+        if status.is_leader {
+            assert!(leader_url.is_none(), "there should be only one leader");
+            assert_eq!(status.leases, 1);
+            leader_url = Some(url);
+        }
+    }
+
+    let mut admin_client = AdminClient::connect(leader_url.unwrap()).await?;
+    let leases = admin_client
+        .lease_dump(LeaseDumpRequest {})
+        .await?
+        .into_inner()
+        .leases;
+    assert_eq!(leases.len(), 1);
+    assert_eq!(leases[0].id, lease_id);
+    assert_eq!(leases[0].ttl, 60);
+    assert_eq!(leases[0].keys, vec![b"foo".to_vec()]);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn tenant_quota_should_reject_puts_and_report_usage() -> Result<(), Box<dyn Error>> {
+    let configs = (0..3)
+        .map(|_| {
+            Cluster::default_tenant_quota_config(vec![TenantQuota::new(
+                "tenant-a/".to_owned(),
+                2,
+                0,
+            )])
+        })
+        .collect();
+    let mut cluster = Cluster::new_with_configs(configs).await;
+    cluster.start().await;
+    let client = cluster.client().await.kv_client();
+
+    let _ignore = client.put(PutRequest::new("tenant-a/1", "v")).await?;
+    let _ignore = client.put(PutRequest::new("tenant-a/2", "v")).await?;
+    let _ignore = client.put(PutRequest::new("tenant-b/1", "v")).await?;
+    // usages are updated after the puts are synced
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    let res = client.put(PutRequest::new("tenant-a/3", "v")).await;
+    assert!(matches!(
+        res,
+        Err(XlineClientError::ExecuteError(ExecuteError::TenantQuotaExceeded(ref prefix)))
+            if prefix == "tenant-a/"
+    ));
+    // overwriting an existing key doesn't add a key
+    let _ignore = client.put(PutRequest::new("tenant-a/2", "value")).await?;
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let mut admin_client = AdminClient::connect(cluster.all_client_addrs()[0].clone()).await?;
+    let tenants = admin_client
+        .tenant_usage(TenantUsageRequest {})
+        .await?
+        .into_inner()
+        .tenants;
+    assert_eq!(tenants.len(), 1);
+    assert_eq!(tenants[0].prefix, b"tenant-a/");
+    assert_eq!(tenants[0].keys, 2);
+    assert_eq!(tenants[0].bytes, 26);
+    assert_eq!(tenants[0].max_keys, 2);
+
+    let _ignore = client.delete(DeleteRangeRequest::new("tenant-a/1")).await?;
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    let _ignore = client.put(PutRequest::new("tenant-a/3", "v")).await?;
+
+    Ok(())
+}
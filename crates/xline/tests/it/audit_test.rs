@@ -0,0 +1,82 @@
+use std::{error::Error, time::Duration};
+
+use test_macros::abort_on_panic;
+use xline_test_utils::{
+    types::{
+        auth::AuthUserAddRequest,
+        kv::{CompactionRequest, DeleteRangeRequest, PutRequest, TxnOp, TxnRequest},
+    },
+    Cluster,
+};
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn test_audit_log_records_applied_changes() -> Result<(), Box<dyn Error>> {
+    let tmp_dir = tempfile::tempdir()?;
+    let dir = tmp_dir.path();
+    let configs = (0..3)
+        .map(|i| Cluster::default_audit_config(dir.join(i.to_string())))
+        .collect();
+    let mut cluster = Cluster::new_with_configs(configs).await;
+    cluster.start().await;
+    let client = cluster.client().await;
+
+    let put_resp = client
+        .kv_client()
+        .put(PutRequest::new("audit/1", "v"))
+        .await?;
+    let _ignore = client
+        .kv_client()
+        .delete(DeleteRangeRequest::new("audit/").with_prefix())
+        .await?;
+    // only the deletions of the applied branch of a txn are recorded
+    let _ignore = client
+        .kv_client()
+        .txn(
+            TxnRequest::new()
+                .and_then(&[TxnOp::delete(DeleteRangeRequest::new("txn/").with_prefix())][..])
+                .or_else(
+                    &[TxnOp::delete(
+                        DeleteRangeRequest::new("skipped/").with_prefix(),
+                    )][..],
+                ),
+        )
+        .await?;
+    let _ignore = client
+        .kv_client()
+        .compact(CompactionRequest::new(put_resp.header.unwrap().revision))
+        .await?;
+    let _ignore = client
+        .auth_client()
+        .user_add(AuthUserAddRequest::new("u").with_pwd("secret"))
+        .await?;
+
+    // every member records the applied commands
+    for i in 0..3 {
+        let path = dir.join(i.to_string()).join("xline_audit.log");
+        let mut log = String::new();
+        for _ in 0..50 {
+            log = tokio::fs::read_to_string(&path).await.unwrap_or_default();
+            if log.lines().count() >= 4 {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+        let actions: Vec<String> = log
+            .lines()
+            .map(|line| {
+                let entry: serde_json::Value = serde_json::from_str(line).unwrap();
+                entry["action"].as_str().unwrap().to_owned()
+            })
+            .collect();
+        assert_eq!(
+            actions,
+            ["delete_range", "delete_range", "compaction", "user_add"]
+        );
+        assert!(log.contains("txn/"));
+        assert!(!log.contains("skipped/"));
+        assert!(!log.contains("secret"));
+    }
+
+    Ok(())
+}
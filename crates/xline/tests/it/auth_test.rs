@@ -2,17 +2,22 @@ use std::{error::Error, iter, path::PathBuf};
 
 use test_macros::abort_on_panic;
 use utils::config::{
-    AuthConfig, ClusterConfig, CompactConfig, LogConfig, MetricsConfig, StorageConfig, TlsConfig,
-    TraceConfig, XlineServerConfig,
+    default_auth_max_failures, default_auth_token_ttl, AuditConfig, AuthConfig, AuthLockoutConfig,
+    ClusterConfig, CompactConfig, LogConfig, MetricsConfig, PasswordHashConfig, StorageConfig,
+    TlsConfig, TraceConfig, XlineServerConfig,
 };
 use xline_test_utils::{
     enable_auth, set_user,
     types::{
-        auth::{AuthRoleDeleteRequest, AuthUserAddRequest, AuthUserGetRequest},
+        auth::{
+            AuthRoleDeleteRequest, AuthUserAddRequest, AuthUserGetRequest, AuthenticateRequest,
+        },
+        cluster::{MemberListRequest, MemberRemoveRequest},
         kv::{PutRequest, RangeRequest},
     },
     Client, ClientOptions, Cluster,
 };
+use xlineapi::{AdminClient, RotateAuthKeyRequest};
 
 #[tokio::test(flavor = "multi_thread")]
 #[abort_on_panic]
@@ -199,6 +204,49 @@ async fn test_no_root_user_do_admin_ops() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn test_no_root_user_do_membership_ops() -> Result<(), Box<dyn Error>> {
+    let mut cluster = Cluster::new_with_configs(configs_with_auth(3)).await;
+    cluster.start().await;
+    let client = cluster.client().await;
+
+    set_user(client, "u", "123", "r", &[], &[]).await?;
+    enable_auth(client).await?;
+    let mut user_client = Client::connect(
+        vec![cluster.get_client_url(0)],
+        ClientOptions::default().with_user("u", "123"),
+    )
+    .await?
+    .cluster_client();
+    let mut root_client = Client::connect(
+        vec![cluster.get_client_url(0)],
+        ClientOptions::default().with_user("root", "123"),
+    )
+    .await?
+    .cluster_client();
+
+    let members = root_client
+        .member_list(MemberListRequest::new(false))
+        .await?
+        .members;
+    assert_eq!(members.len(), 3);
+    let result = user_client
+        .member_remove(MemberRemoveRequest::new(members[0].id))
+        .await;
+    assert!(
+        result.is_err(),
+        "normal user should not allow to remove member when auth is enabled: {result:?}"
+    );
+    let result = user_client.member_list(MemberListRequest::new(false)).await;
+    assert!(
+        result.is_ok(),
+        "normal user failed to list members: {result:?}"
+    );
+
+    Ok(())
+}
+
 #[tokio::test(flavor = "multi_thread")]
 #[abort_on_panic]
 async fn test_auth_wrong_password() -> Result<(), Box<dyn Error>> {
@@ -225,6 +273,181 @@ async fn test_auth_wrong_password() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn test_auth_lockout_after_failed_authentications() -> Result<(), Box<dyn Error>> {
+    let mut cluster = Cluster::new_with_configs(configs_with_auth(3)).await;
+    cluster.start().await;
+    let client = cluster.client().await;
+    enable_auth(client).await?;
+
+    for _ in 0..default_auth_max_failures() {
+        let result = Client::connect(
+            vec![cluster.get_client_url(0)],
+            ClientOptions::default().with_user("root", "wrong"),
+        )
+        .await;
+        assert!(result.is_err());
+    }
+    let result = Client::connect(
+        vec![cluster.get_client_url(0)],
+        ClientOptions::default().with_user("root", "123"),
+    )
+    .await;
+    assert!(result
+        .unwrap_err()
+        .to_string()
+        .contains("too many failed authentications"));
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn test_auth_refresh_token() -> Result<(), Box<dyn Error>> {
+    let mut cluster = Cluster::new_with_configs(configs_with_auth(3)).await;
+    cluster.start().await;
+    let client = cluster.client().await;
+    enable_auth(client).await?;
+    assert!(client.auth_client().refresh_token().await.is_err());
+
+    let authed_client = Client::connect(
+        vec![cluster.get_client_url(0)],
+        ClientOptions::default().with_user("root", "123"),
+    )
+    .await?;
+    let token = authed_client.auth_client().refresh_token().await?.token;
+    let refreshed_client = Client::connect(
+        vec![cluster.get_client_url(0)],
+        ClientOptions::default().with_token(token),
+    )
+    .await?;
+    refreshed_client
+        .kv_client()
+        .put(PutRequest::new("foo", "bar"))
+        .await?;
+    let _resp = refreshed_client.auth_client().refresh_token().await?;
+
+    // tokens issued before a change of auth can not be refreshed
+    authed_client
+        .auth_client()
+        .user_add(AuthUserAddRequest::new("u").with_pwd("123"))
+        .await?;
+    assert!(refreshed_client
+        .auth_client()
+        .refresh_token()
+        .await
+        .is_err());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn test_auth_key_rotation_keeps_issued_tokens_valid() -> Result<(), Box<dyn Error>> {
+    let tmp_dir = tempfile::tempdir()?;
+    let dir = tmp_dir.path();
+    let private_key = dir.join("private.pem");
+    let public_key = dir.join("public.pem");
+    tokio::fs::copy("../../fixtures/private.pem", &private_key).await?;
+    tokio::fs::copy("../../fixtures/public.pem", &public_key).await?;
+    // every member accepts tokens signed by the new key before any of them rotates
+    let configs = iter::repeat_with(|| {
+        XlineServerConfig::new(
+            ClusterConfig::default(),
+            StorageConfig::default(),
+            LogConfig::default(),
+            TraceConfig::default(),
+            AuthConfig::new(
+                Some(public_key.clone()),
+                Some(private_key.clone()),
+                vec![PathBuf::from("../../fixtures/public_rotated.pem")],
+                default_auth_token_ttl(),
+                PasswordHashConfig::default(),
+                AuthLockoutConfig::default(),
+                true,
+            ),
+            CompactConfig::default(),
+            TlsConfig::default(),
+            MetricsConfig::default(),
+            AuditConfig::default(),
+        )
+    })
+    .take(3)
+    .collect();
+    let mut cluster = Cluster::new_with_configs(configs).await;
+    cluster.start().await;
+    let client = cluster.client().await;
+    enable_auth(client).await?;
+    let old_client = Client::connect(
+        vec![cluster.get_client_url(0)],
+        ClientOptions::default().with_user("root", "123"),
+    )
+    .await?;
+    let token = client
+        .auth_client()
+        .authenticate(AuthenticateRequest::new("root", "123"))
+        .await?
+        .token;
+
+    tokio::fs::copy("../../fixtures/private_rotated.pem", &private_key).await?;
+    tokio::fs::copy("../../fixtures/public_rotated.pem", &public_key).await?;
+    for url in cluster.all_client_addrs() {
+        let mut admin_client = AdminClient::connect(url).await?;
+        let mut request = tonic::Request::new(RotateAuthKeyRequest {});
+        let _ignore = request.metadata_mut().insert("token", token.parse()?);
+        let _ignore = admin_client.rotate_auth_key(request).await?;
+    }
+
+    old_client
+        .kv_client()
+        .put(PutRequest::new("foo", "bar"))
+        .await?;
+    let new_client = Client::connect(
+        vec![cluster.get_client_url(1)],
+        ClientOptions::default().with_user("root", "123"),
+    )
+    .await?;
+    let resp = new_client
+        .kv_client()
+        .range(RangeRequest::new("foo"))
+        .await?;
+    assert_eq!(resp.kvs[0].value, b"bar");
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn test_unlock_lock_of_another_user_should_fail() -> Result<(), Box<dyn Error>> {
+    let mut cluster = Cluster::new_with_configs(configs_with_auth(3)).await;
+    cluster.start().await;
+    let client = cluster.client().await;
+
+    set_user(client, "u1", "123", "r1", b"lock/", b"lock0").await?;
+    set_user(client, "u2", "123", "r2", b"lock/", b"lock0").await?;
+    enable_auth(client).await?;
+
+    let mut u1_client = etcd_client::Client::connect(
+        [cluster.get_client_url(0)],
+        Some(etcd_client::ConnectOptions::new().with_user("u1", "123")),
+    )
+    .await?;
+    let mut u2_client = etcd_client::Client::connect(
+        [cluster.get_client_url(0)],
+        Some(etcd_client::ConnectOptions::new().with_user("u2", "123")),
+    )
+    .await?;
+
+    let res = u1_client.lock("lock", None).await?;
+    assert!(u2_client.unlock(res.key()).await.is_err());
+    assert_eq!(u1_client.get(res.key(), None).await?.kvs().len(), 1);
+    let _res = u1_client.unlock(res.key()).await?;
+    assert!(u1_client.get(res.key(), None).await?.kvs().is_empty());
+
+    Ok(())
+}
+
 fn configs_with_auth(size: usize) -> Vec<XlineServerConfig> {
     iter::repeat_with(|| {
         (
@@ -238,10 +461,19 @@ fn configs_with_auth(size: usize) -> Vec<XlineServerConfig> {
             StorageConfig::default(),
             LogConfig::default(),
             TraceConfig::default(),
-            AuthConfig::new(auth_public_key, auth_private_key),
+            AuthConfig::new(
+                auth_public_key,
+                auth_private_key,
+                vec![],
+                default_auth_token_ttl(),
+                PasswordHashConfig::default(),
+                AuthLockoutConfig::default(),
+                true,
+            ),
             CompactConfig::default(),
             TlsConfig::default(),
             MetricsConfig::default(),
+            AuditConfig::default(),
         )
     })
     .take(size)
@@ -1,15 +1,19 @@
 use std::{error::Error, time::Duration};
 
 use test_macros::abort_on_panic;
-use tokio::{net::TcpListener, time::sleep};
+use tokio::{
+    net::TcpListener,
+    time::{sleep, timeout},
+};
 use xline_client::{
     types::{
         cluster::{MemberAddRequest, MemberListRequest, MemberRemoveRequest, MemberUpdateRequest},
-        kv::PutRequest,
+        kv::{PutRequest, RangeRequest},
+        watch::WatchRequest,
     },
     Client, ClientOptions,
 };
-use xline_test_utils::Cluster;
+use xline_test_utils::{Cluster, TestCluster};
 
 #[tokio::test(flavor = "multi_thread")]
 #[abort_on_panic]
@@ -101,3 +105,81 @@ async fn xline_update_node() -> Result<(), Box<dyn Error>> {
 
     Ok(())
 }
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn xline_graceful_shutdown_should_transfer_leadership() -> Result<(), Box<dyn Error>> {
+    let mut cluster = Cluster::new(3).await;
+    cluster.start().await;
+    let mut client0 = Client::connect(vec![cluster.get_client_url(0)], ClientOptions::default())
+        .await?
+        .maintenance_client();
+    let status = client0.status().await?;
+    let self_id = status.header.unwrap().member_id;
+    assert_eq!(
+        status.leader, self_id,
+        "server0 should be the initial leader"
+    );
+
+    cluster.shutdown_node(0).await;
+    sleep(Duration::from_millis(500)).await;
+
+    let mut client1 = Client::connect(vec![cluster.get_client_url(1)], ClientOptions::default())
+        .await?
+        .maintenance_client();
+    let status = client1.status().await?;
+    assert_ne!(status.leader, 0);
+    assert_ne!(status.leader, self_id);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn xline_graceful_shutdown_should_end_watch_streams() -> Result<(), Box<dyn Error>> {
+    let mut cluster = Cluster::new(3).await;
+    cluster.start().await;
+    let mut watch_client =
+        Client::connect(vec![cluster.get_client_url(1)], ClientOptions::default())
+            .await?
+            .watch_client();
+    let (_watcher, mut stream) = watch_client.watch(WatchRequest::new("foo")).await?;
+
+    // the open watch stream must not hold the shutdown until the drain times out
+    timeout(Duration::from_secs(5), cluster.shutdown_node(1)).await?;
+    assert!(stream.message().await.is_err());
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn test_cluster_should_survive_leader_restart() -> Result<(), Box<dyn Error>> {
+    let mut cluster = TestCluster::new(3).await;
+    let client = cluster.client().await;
+    client
+        .kv_client()
+        .put(PutRequest::new("foo", "bar"))
+        .await?;
+
+    let leader = cluster.wait_leader().await;
+    cluster.kill(leader).await;
+    let new_leader = cluster.wait_leader().await;
+    assert_ne!(new_leader, leader);
+    client
+        .kv_client()
+        .put(PutRequest::new("foo", "baz"))
+        .await?;
+
+    cluster.restart(leader).await;
+    let _leader = cluster.wait_leader().await;
+    let res = cluster
+        .node_client(leader)
+        .await
+        .kv_client()
+        .range(RangeRequest::new("foo"))
+        .await?;
+    assert_eq!(res.kvs.len(), 1);
+    assert_eq!(res.kvs[0].value, b"baz");
+    Ok(())
+}
@@ -0,0 +1,61 @@
+use std::{error::Error, time::Duration};
+
+use etcd_client::{ProclaimOptions, ResignOptions};
+use test_macros::abort_on_panic;
+use tokio::time::timeout;
+use xline_test_utils::Cluster;
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn test_election_campaign_proclaim_and_resign() -> Result<(), Box<dyn Error>> {
+    let mut cluster = Cluster::new(3).await;
+    cluster.start().await;
+    let mut client = etcd_client::Client::connect([cluster.get_client_url(0)], None).await?;
+    let lease1 = client.lease_grant(60, None).await?.id();
+    let lease2 = client.lease_grant(60, None).await?.id();
+
+    let res = client.campaign("election", "v1", lease1).await?;
+    let leader1 = res.leader().unwrap().clone();
+    assert_eq!(leader1.name(), b"election");
+    let res = client.leader("election").await?;
+    assert_eq!(res.kv().unwrap().value(), b"v1");
+
+    let mut observer = client.observe("election").await?;
+    let res = observer.message().await?.unwrap();
+    assert_eq!(res.kv().unwrap().value(), b"v1");
+
+    let campaign_handle = tokio::spawn({
+        let mut c = client.clone();
+        async move { c.campaign("election", "v2", lease2).await }
+    });
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert!(
+        !campaign_handle.is_finished(),
+        "the second campaign should wait for the first leader"
+    );
+
+    client
+        .proclaim(
+            "v1-updated",
+            Some(ProclaimOptions::new().with_leader(leader1.clone())),
+        )
+        .await?;
+    let res = observer.message().await?.unwrap();
+    assert_eq!(res.kv().unwrap().value(), b"v1-updated");
+
+    client
+        .resign(Some(ResignOptions::new().with_leader(leader1.clone())))
+        .await?;
+    let res = timeout(Duration::from_secs(3), campaign_handle).await???;
+    let leader2 = res.leader().unwrap().clone();
+    assert_eq!(leader2.lease(), lease2);
+    let res = observer.message().await?.unwrap();
+    assert_eq!(res.kv().unwrap().value(), b"v2");
+
+    let res = client
+        .proclaim("v3", Some(ProclaimOptions::new().with_leader(leader1)))
+        .await;
+    assert!(res.is_err(), "a resigned leader should not proclaim");
+
+    Ok(())
+}
@@ -0,0 +1,27 @@
+use std::error::Error;
+
+use test_macros::abort_on_panic;
+use xline::embedded::EmbeddedXline;
+use xline_test_utils::{
+    types::kv::{PutRequest, RangeRequest},
+    Client, ClientOptions,
+};
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn embedded_xline_should_serve_requests() -> Result<(), Box<dyn Error>> {
+    let xline = EmbeddedXline::start().await?;
+    xline
+        .client()
+        .kv_client()
+        .put(PutRequest::new("foo", "bar"))
+        .await?;
+
+    let client = Client::connect([xline.client_url()], ClientOptions::default()).await?;
+    let res = client.kv_client().range(RangeRequest::new("foo")).await?;
+    assert_eq!(res.kvs.len(), 1);
+    assert_eq!(res.kvs[0].value, b"bar");
+
+    xline.shutdown().await;
+    Ok(())
+}
@@ -3,8 +3,8 @@ use std::{error::Error, time::Duration};
 use test_macros::abort_on_panic;
 use xline_test_utils::{
     types::kv::{
-        Compare, CompareResult, DeleteRangeRequest, PutRequest, RangeRequest, Response, SortOrder,
-        SortTarget, TxnOp, TxnRequest,
+        CompactionRequest, Compare, CompareResult, DeleteRangeRequest, PutRequest, RangeRequest,
+        Response, SortOrder, SortTarget, TxnOp, TxnRequest,
     },
     Client, ClientOptions, Cluster,
 };
@@ -330,3 +330,109 @@ async fn test_txn() -> Result<(), Box<dyn Error>> {
 
     Ok(())
 }
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn test_kv_compact_physical() -> Result<(), Box<dyn Error>> {
+    let mut cluster = Cluster::new(3).await;
+    cluster.start().await;
+    let client = cluster.client().await.kv_client();
+
+    let mut revisions = vec![];
+    for value in ["bar1", "bar2", "bar3"] {
+        let resp = client.put(PutRequest::new("foo", value)).await?;
+        revisions.push(resp.header.unwrap().revision);
+    }
+    let compact_rev = revisions[2];
+    client
+        .compact(CompactionRequest::new(compact_rev).with_physical())
+        .await?;
+
+    let res = client
+        .range(RangeRequest::new("foo").with_revision(revisions[0]))
+        .await;
+    assert!(res.is_err(), "range a compacted revision should fail");
+    let res = client
+        .range(RangeRequest::new("foo").with_revision(compact_rev))
+        .await?;
+    assert_eq!(res.kvs[0].value, b"bar3");
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn test_kv_prev_kv_under_concurrent_writes() -> Result<(), Box<dyn Error>> {
+    const WRITERS: usize = 10;
+
+    let mut cluster = Cluster::new(3).await;
+    cluster.start().await;
+    let client = cluster.client().await.kv_client();
+
+    let mut handles = Vec::with_capacity(WRITERS);
+    for i in 0..WRITERS {
+        let client = client.clone();
+        handles.push(tokio::spawn(async move {
+            client
+                .put(PutRequest::new("foo", i.to_string()).with_prev_kv(true))
+                .await
+        }));
+    }
+    let mut prev_values = vec![];
+    for handle in handles {
+        let resp = handle.await??;
+        if let Some(prev_kv) = resp.prev_kv {
+            assert!(prev_kv.mod_revision < resp.header.unwrap().revision);
+            prev_values.push(prev_kv.value);
+        }
+    }
+    // every put except the first one overwrites exactly one other put
+    assert_eq!(prev_values.len(), WRITERS - 1);
+    prev_values.sort();
+    prev_values.dedup();
+    assert_eq!(prev_values.len(), WRITERS - 1);
+
+    let last_value = client.range(RangeRequest::new("foo")).await?.kvs[0]
+        .value
+        .clone();
+    assert!(!prev_values.contains(&last_value));
+
+    let resp = client
+        .delete(DeleteRangeRequest::new("foo").with_prev_kv(true))
+        .await?;
+    assert_eq!(resp.deleted, 1);
+    assert_eq!(resp.prev_kvs[0].value, last_value);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn test_kv_request_limits() -> Result<(), Box<dyn Error>> {
+    let mut cluster = Cluster::new(3).await;
+    cluster.start().await;
+    let mut client = etcd_client::Client::connect([cluster.get_client_url(0)], None).await?;
+
+    let err = client
+        .put("foo", vec![0_u8; 2 * 1024 * 1024], None)
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(err, etcd_client::Error::GRpcStatus(ref status) if status.message() == "etcdserver: request is too large"),
+        "unexpected error: {err:?}"
+    );
+
+    let ops: Vec<_> = (0..129)
+        .map(|i| etcd_client::TxnOp::put(format!("foo{i}"), "bar", None))
+        .collect();
+    let err = client
+        .txn(etcd_client::Txn::new().and_then(ops))
+        .await
+        .unwrap_err();
+    assert!(
+        matches!(err, etcd_client::Error::GRpcStatus(ref status) if status.message() == "etcdserver: too many operations in txn request"),
+        "unexpected error: {err:?}"
+    );
+
+    Ok(())
+}
@@ -5,7 +5,7 @@ use tracing::info;
 use xline_test_utils::{
     types::{
         kv::{PutRequest, RangeRequest},
-        lease::{LeaseGrantRequest, LeaseKeepAliveRequest},
+        lease::{LeaseGrantRequest, LeaseKeepAliveRequest, LeaseRevokeRequest},
     },
     Client, ClientOptions, Cluster,
 };
@@ -89,3 +89,41 @@ async fn test_lease_keep_alive() -> Result<(), Box<dyn Error>> {
 
     Ok(())
 }
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn test_lease_leases_should_list_active_leases() -> Result<(), Box<dyn Error>> {
+    let mut cluster = Cluster::new(3).await;
+    cluster.start().await;
+    let client = cluster.client().await;
+    let mut non_leader_client =
+        Client::connect(vec![cluster.get_client_url(1)], ClientOptions::default())
+            .await?
+            .lease_client();
+
+    let lease1 = client
+        .lease_client()
+        .grant(LeaseGrantRequest::new(60))
+        .await?
+        .id;
+    let lease2 = client
+        .lease_client()
+        .grant(LeaseGrantRequest::new(60))
+        .await?
+        .id;
+    let res = non_leader_client.leases().await?;
+    let mut leases: Vec<_> = res.leases.iter().map(|status| status.id).collect();
+    leases.sort_unstable();
+    let mut expected = vec![lease1, lease2];
+    expected.sort_unstable();
+    assert_eq!(leases, expected);
+
+    non_leader_client
+        .revoke(LeaseRevokeRequest::new(lease1))
+        .await?;
+    let res = client.lease_client().leases().await?;
+    let leases: Vec<_> = res.leases.iter().map(|status| status.id).collect();
+    assert_eq!(leases, vec![lease2]);
+
+    Ok(())
+}
@@ -1,5 +1,6 @@
 use std::{error::Error, time::Duration};
 
+use etcd_client::LockOptions;
 use test_macros::abort_on_panic;
 use tokio::time::{self, timeout};
 use xline_test_utils::{
@@ -64,3 +65,27 @@ async fn test_lock_timeout() -> Result<(), Box<dyn Error>> {
 
     Ok(())
 }
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn test_unlock_should_reject_key_not_held_by_lock() -> Result<(), Box<dyn Error>> {
+    let mut cluster = Cluster::new(3).await;
+    cluster.start().await;
+    let mut client = etcd_client::Client::connect([cluster.get_client_url(0)], None).await?;
+
+    let _res = client.put("test/foo", "bar", None).await?;
+    let res = client.unlock("test/foo").await;
+    assert!(res.is_err());
+    let res = client.get("test/foo", None).await?;
+    assert_eq!(res.kvs().len(), 1);
+
+    let lease_id = client.lease_grant(60, None).await?.id();
+    let res = client
+        .lock("test", Some(LockOptions::new().with_lease(lease_id)))
+        .await?;
+    let _res = client.unlock(res.key()).await?;
+    let res = client.get(res.key(), None).await?;
+    assert!(res.kvs().is_empty());
+
+    Ok(())
+}
@@ -1,5 +1,9 @@
+mod admin_test;
+mod audit_test;
 mod auth_test;
 mod cluster_test;
+mod election_test;
+mod embedded_test;
 mod kv_test;
 mod lease_test;
 mod lock_test;
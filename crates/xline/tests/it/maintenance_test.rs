@@ -3,10 +3,10 @@ use std::{path::PathBuf, time::Duration};
 use test_macros::abort_on_panic;
 use tokio::io::AsyncWriteExt;
 #[cfg(test)]
-use xline::restore::restore;
+use xline::{migrate::migrate_from_etcd, restore::restore};
 use xline_client::error::XlineClientError;
 use xline_test_utils::{
-    types::kv::{PutRequest, RangeRequest},
+    types::kv::{CompactionRequest, DeleteRangeRequest, PutRequest, RangeRequest},
     Client, ClientOptions, Cluster,
 };
 use xlineapi::{execute_error::ExecuteError, AlarmAction, AlarmRequest, AlarmType};
@@ -39,6 +39,13 @@ async fn test_snapshot_and_restore() -> Result<(), Box<dyn std::error::Error>> {
             snapshot.write_all(chunk.blob.as_slice()).await?;
         }
     }
+    let mut corrupted = tokio::fs::read(&snapshot_path).await?;
+    corrupted[0] ^= 0xff;
+    let corrupted_path = dir.join("corrupted_snapshot");
+    tokio::fs::write(&corrupted_path, corrupted).await?;
+    assert!(restore(&corrupted_path, dir.join("restore_corrupted"))
+        .await
+        .is_err());
     for restore_dir in restore_dirs {
         restore(&snapshot_path, &restore_dir).await?;
     }
@@ -49,10 +56,75 @@ async fn test_snapshot_and_restore() -> Result<(), Box<dyn std::error::Error>> {
     assert_eq!(res.kvs.len(), 1);
     assert_eq!(res.kvs[0].key, b"key");
     assert_eq!(res.kvs[0].value, b"value");
+    // the restored cluster should be able to apply new proposals
+    let _ignore = client.put(PutRequest::new("key2", "value2")).await?;
+    let res = client.range(RangeRequest::new("key2")).await?;
+    assert_eq!(res.kvs.len(), 1);
+    assert_eq!(res.kvs[0].value, b"value2");
     tokio::fs::remove_dir_all(&dir).await?;
     Ok(())
 }
 
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn test_migrate_from_etcd() -> Result<(), Box<dyn std::error::Error>> {
+    let tmp_dir = tempfile::tempdir()?;
+    let dir = tmp_dir.path();
+    let data_dirs: Vec<PathBuf> = (0..3).map(|i| dir.join(format!("migrate_{i}"))).collect();
+    let cluster_configs = data_dirs
+        .iter()
+        .cloned()
+        .map(Cluster::default_rocks_config_with_path)
+        .collect();
+    let lease_id = {
+        // xline serves the etcd api, so it is used as the source of the migration
+        let mut source = Cluster::new(3).await;
+        source.start().await;
+        let mut client = etcd_client::Client::connect([source.get_client_url(0)], None).await?;
+        let lease_id = client.lease_grant(600, None).await?.id();
+        let _ignore = client.put("foo", "bar", None).await?;
+        let _ignore = client
+            .put(
+                "leased",
+                "value",
+                Some(etcd_client::PutOptions::new().with_lease(lease_id)),
+            )
+            .await?;
+        let _ignore = client.user_add("alice", "pass", None).await?;
+        let _ignore = client.role_add("reader").await?;
+        let _ignore = client
+            .role_grant_permission("reader", etcd_client::Permission::read("foo"))
+            .await?;
+        let _ignore = client.user_grant_role("alice", "reader").await?;
+        for data_dir in &data_dirs {
+            let summary = migrate_from_etcd(&[source.get_client_url(0)], None, data_dir).await?;
+            assert_eq!(summary.keys, 2);
+            assert_eq!(summary.leases, 1);
+            assert_eq!(summary.users, 1);
+            assert_eq!(summary.roles, 1);
+        }
+        assert!(
+            migrate_from_etcd(&[source.get_client_url(0)], None, &data_dirs[0])
+                .await
+                .is_err()
+        );
+        lease_id
+    };
+
+    let mut new_cluster = Cluster::new_with_configs(cluster_configs).await;
+    new_cluster.start().await;
+    let mut client = etcd_client::Client::connect([new_cluster.get_client_url(0)], None).await?;
+    let resp = client.get("leased", None).await?;
+    assert_eq!(resp.kvs()[0].value(), b"value");
+    assert_eq!(resp.kvs()[0].lease(), lease_id);
+    let resp = client.user_get("alice").await?;
+    assert_eq!(resp.roles(), ["reader".to_owned()]);
+    // the migrated cluster should be able to apply new proposals
+    let _ignore = client.put("foo", "baz", None).await?;
+    assert_eq!(client.get("foo", None).await?.kvs()[0].value(), b"baz");
+    Ok(())
+}
+
 #[tokio::test(flavor = "multi_thread")]
 #[abort_on_panic]
 async fn leader_should_detect_no_space_alarm() {
@@ -102,6 +174,38 @@ async fn test_alarm(idx: usize) {
     assert!(!res.alarms.is_empty());
 }
 
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn test_quota_exceeded_rejects_puts_but_allows_deletes() {
+    let q = 8 * 1024;
+    let configs = (0..3).map(|_| Cluster::default_quota_config(q)).collect();
+    let mut cluster = Cluster::new_with_configs(configs).await;
+    cluster.start().await;
+    let k_client = cluster.client().await.kv_client();
+
+    let mut exceeded = false;
+    for i in 1..100u8 {
+        if let Err(err) = k_client.put(PutRequest::new(vec![i], vec![i])).await {
+            assert!(matches!(
+                err,
+                XlineClientError::ExecuteError(ExecuteError::Nospace)
+            ));
+            exceeded = true;
+            break;
+        }
+    }
+    assert!(exceeded, "quota should be exceeded");
+    let res = k_client.put(PutRequest::new("foo", "bar")).await;
+    assert!(matches!(
+        res,
+        Err(XlineClientError::ExecuteError(ExecuteError::Nospace))
+    ));
+    k_client
+        .delete(DeleteRangeRequest::new(vec![1u8]))
+        .await
+        .unwrap();
+}
+
 #[tokio::test(flavor = "multi_thread")]
 #[abort_on_panic]
 async fn test_status() -> Result<(), Box<dyn std::error::Error>> {
@@ -121,3 +225,103 @@ async fn test_status() -> Result<(), Box<dyn std::error::Error>> {
 
     Ok(())
 }
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn test_hash_kv_should_be_consistent_across_members() -> Result<(), Box<dyn std::error::Error>>
+{
+    let mut cluster = Cluster::new_rocks(3).await;
+    cluster.start().await;
+    let k_client = cluster.client().await.kv_client();
+    let mut revision = 0;
+    for i in 0..10u8 {
+        let res = k_client.put(PutRequest::new(vec![i], vec![i])).await?;
+        revision = res.header.unwrap().revision;
+    }
+    tokio::time::sleep(Duration::from_millis(500)).await;
+
+    let mut hashes = vec![];
+    for i in 0..3 {
+        let mut m_client =
+            Client::connect(vec![cluster.get_client_url(i)], ClientOptions::default())
+                .await?
+                .maintenance_client();
+        let res = m_client.hash_kv(revision).await?;
+        assert_ne!(res.hash, 0);
+        hashes.push(res.hash);
+    }
+    assert!(
+        hashes.iter().all(|hash| *hash == hashes[0]),
+        "hashes of members diverge: {hashes:?}"
+    );
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn test_move_leader() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cluster = Cluster::new_rocks(3).await;
+    cluster.start().await;
+    let mut clients = vec![];
+    for i in 0..3 {
+        clients.push(
+            Client::connect(vec![cluster.get_client_url(i)], ClientOptions::default())
+                .await?
+                .maintenance_client(),
+        );
+    }
+    let mut leader_idx = None;
+    let mut follower_id = None;
+    for (i, client) in clients.iter_mut().enumerate() {
+        let res = client.status().await?;
+        let member_id = res.header.unwrap().member_id;
+        if member_id == res.leader {
+            leader_idx = Some(i);
+        } else {
+            follower_id = Some(member_id);
+        }
+    }
+    let (leader_idx, follower_id) = (leader_idx.unwrap(), follower_id.unwrap());
+
+    let _ignore = clients[leader_idx].move_leader(follower_id).await?;
+    let res = clients[leader_idx].status().await?;
+    assert_eq!(res.leader, follower_id);
+
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn test_defragment() -> Result<(), Box<dyn std::error::Error>> {
+    let mut cluster = Cluster::new_rocks(3).await;
+    cluster.start().await;
+    let client = cluster.client().await;
+    let k_client = client.kv_client();
+    let mut revision = 0;
+    for i in 0..64u8 {
+        let _ignore = k_client
+            .put(PutRequest::new(vec![i], vec![i; 4096]))
+            .await?;
+    }
+    for i in 0..64u8 {
+        let res = k_client.delete(DeleteRangeRequest::new(vec![i])).await?;
+        revision = res.header.unwrap().revision;
+    }
+    let _ignore = k_client
+        .compact(CompactionRequest::new(revision).with_physical())
+        .await?;
+
+    let mut m_client = Client::connect(vec![cluster.get_client_url(0)], ClientOptions::default())
+        .await?
+        .maintenance_client();
+    let size_before = m_client.status().await?.db_size;
+    let _ignore = m_client.defragment().await?;
+    let size_after = m_client.status().await?.db_size;
+    assert!(
+        size_after <= size_before,
+        "db size should not grow after defragment: {size_before} -> {size_after}"
+    );
+
+    Ok(())
+}
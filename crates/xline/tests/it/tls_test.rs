@@ -4,7 +4,8 @@ use etcd_client::ConnectOptions;
 use test_macros::abort_on_panic;
 use tonic::transport::{Certificate, ClientTlsConfig, Identity};
 use utils::config::{
-    AuthConfig, ClusterConfig, CompactConfig, LogConfig, MetricsConfig, StorageConfig, TlsConfig,
+    default_auth_token_ttl, AuditConfig, AuthConfig, AuthLockoutConfig, ClusterConfig,
+    CompactConfig, LogConfig, MetricsConfig, PasswordHashConfig, StorageConfig, TlsConfig,
     TraceConfig, XlineServerConfig,
 };
 use xline_client::types::kv::PutRequest;
@@ -23,6 +24,19 @@ async fn test_basic_tls() {
     assert!(res.is_ok());
 }
 
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn test_client_tls_with_plaintext_peers() {
+    let mut cluster = Cluster::new_with_configs(client_only_tls_configs(3)).await;
+    cluster.start().await;
+
+    let client = cluster
+        .client_with_tls_config(basic_tls_client_config())
+        .await;
+    let res = client.kv_client().put(PutRequest::new("foo", "bar")).await;
+    assert!(res.is_ok());
+}
+
 #[tokio::test(flavor = "multi_thread")]
 #[abort_on_panic]
 async fn test_mtls() {
@@ -36,6 +50,30 @@ async fn test_mtls() {
     assert!(res.is_ok());
 }
 
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn test_peer_mtls_with_peer_identity() {
+    let tls_config = TlsConfig::new(
+        Some(PathBuf::from("../../fixtures/ca.crt")),
+        Some(PathBuf::from("../../fixtures/server.crt")),
+        Some(PathBuf::from("../../fixtures/server.key")),
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+    );
+    let mut cluster = Cluster::new_with_configs(configs_with_tls_config(3, tls_config)).await;
+    cluster.start().await;
+
+    let client = cluster
+        .client_with_tls_config(mtls_client_config("root"))
+        .await;
+    let res = client.kv_client().put(PutRequest::new("foo", "bar")).await;
+    assert!(res.is_ok());
+}
+
 #[tokio::test(flavor = "multi_thread")]
 #[abort_on_panic]
 async fn test_certificate_authenticate() {
@@ -81,7 +119,62 @@ async fn test_certificate_authenticate() {
     assert!(res.is_ok());
 }
 
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn test_client_mtls_with_client_cert_auth_disabled() {
+    let tls_config = TlsConfig::new(
+        None,
+        None,
+        None,
+        None,
+        None,
+        None,
+        Some(PathBuf::from("../../fixtures/server.crt")),
+        Some(PathBuf::from("../../fixtures/server.key")),
+        Some(PathBuf::from("../../fixtures/ca.crt")),
+    );
+    let configs = configs_with_tls_and_auth_config(
+        3,
+        tls_config,
+        AuthConfig::new(
+            None,
+            None,
+            vec![],
+            default_auth_token_ttl(),
+            PasswordHashConfig::default(),
+            AuthLockoutConfig::default(),
+            false,
+        ),
+    );
+    let mut cluster = Cluster::new_with_configs(configs).await;
+    cluster.start().await;
+
+    let root_client = cluster
+        .client_with_tls_config(mtls_client_config("root"))
+        .await;
+    let res = root_client
+        .kv_client()
+        .put(PutRequest::new("foo", "bar"))
+        .await;
+    assert!(res.is_ok());
+
+    enable_auth(&root_client).await.unwrap();
+    let res = root_client
+        .kv_client()
+        .put(PutRequest::new("foo", "bar"))
+        .await;
+    assert!(res.is_err());
+}
+
 fn configs_with_tls_config(size: usize, tls_config: TlsConfig) -> Vec<XlineServerConfig> {
+    configs_with_tls_and_auth_config(size, tls_config, AuthConfig::default())
+}
+
+fn configs_with_tls_and_auth_config(
+    size: usize,
+    tls_config: TlsConfig,
+    auth_config: AuthConfig,
+) -> Vec<XlineServerConfig> {
     iter::repeat(tls_config)
         .map(|tls_config| {
             XlineServerConfig::new(
@@ -89,10 +182,11 @@ fn configs_with_tls_config(size: usize, tls_config: TlsConfig) -> Vec<XlineServe
                 StorageConfig::default(),
                 LogConfig::default(),
                 TraceConfig::default(),
-                AuthConfig::default(),
+                auth_config.clone(),
                 CompactConfig::default(),
                 tls_config,
                 MetricsConfig::default(),
+                AuditConfig::default(),
             )
         })
         .take(size)
@@ -115,6 +209,26 @@ fn basic_tls_configs(size: usize) -> Vec<XlineServerConfig> {
             Some(PathBuf::from("../../fixtures/ca.crt")),
             None,
             None,
+            None,
+            None,
+            None,
+        ),
+    )
+}
+
+fn client_only_tls_configs(size: usize) -> Vec<XlineServerConfig> {
+    configs_with_tls_config(
+        size,
+        TlsConfig::new(
+            None,
+            None,
+            None,
+            None,
+            None,
+            None,
+            Some(PathBuf::from("../../fixtures/server.crt")),
+            Some(PathBuf::from("../../fixtures/server.key")),
+            None,
         ),
     )
 }
@@ -140,6 +254,9 @@ fn mtls_configs(size: usize) -> Vec<XlineServerConfig> {
             Some(PathBuf::from("../../fixtures/ca.crt")),
             Some(PathBuf::from("../../fixtures/root_client.crt")),
             Some(PathBuf::from("../../fixtures/root_client.key")),
+            None,
+            None,
+            None,
         ),
     )
 }
@@ -52,3 +52,43 @@ async fn test_watch() -> Result<(), Box<dyn Error>> {
 
     Ok(())
 }
+
+#[tokio::test(flavor = "multi_thread")]
+#[abort_on_panic]
+async fn test_watch_prev_kv_under_concurrent_writes() -> Result<(), Box<dyn Error>> {
+    const WRITERS: usize = 10;
+
+    let mut cluster = Cluster::new(3).await;
+    cluster.start().await;
+    let client = cluster.client().await;
+    let mut watch_client = client.watch_client();
+    let kv_client = client.kv_client();
+
+    let (_watcher, mut stream) = watch_client
+        .watch(WatchRequest::new("foo").with_prev_kv())
+        .await?;
+    let mut handles = Vec::with_capacity(WRITERS);
+    for i in 0..WRITERS {
+        let kv_client = kv_client.clone();
+        handles.push(tokio::spawn(async move {
+            kv_client.put(PutRequest::new("foo", i.to_string())).await
+        }));
+    }
+    for handle in handles {
+        let _resp = handle.await??;
+    }
+    kv_client.delete(DeleteRangeRequest::new("foo")).await?;
+
+    let mut events = vec![];
+    while events.len() < WRITERS + 1 {
+        let res = stream.message().await?.unwrap();
+        events.extend(res.events);
+    }
+    assert!(events[0].prev_kv.is_none());
+    for pair in events.windows(2) {
+        assert_eq!(pair[1].prev_kv, pair[0].kv);
+    }
+    assert_eq!(event_type(events[WRITERS].r#type), EventType::Delete);
+
+    Ok(())
+}
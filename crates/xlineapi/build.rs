@@ -1,17 +1,44 @@
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+/// Protos of the xline-proto submodule
+const PROTOS: [&str; 7] = [
+    "kv.proto",
+    "rpc.proto",
+    "auth.proto",
+    "v3lock.proto",
+    "lease.proto",
+    "xline-command.proto",
+    "xline-error.proto",
+];
+
+/// Protos kept in this crate, which are not in the xline-proto submodule
+const EXT_PROTOS: [&str; 4] = [
+    "lease-checkpoint.proto",
+    "v3election.proto",
+    "xline-admin.proto",
+    "xline-internal.proto",
+];
+
 fn main() {
+    let out_dir =
+        PathBuf::from(env::var("OUT_DIR").unwrap_or_else(|e| panic!("OUT_DIR is not set: {e}")));
+    println!("cargo:rerun-if-changed=proto/src");
+    println!("cargo:rerun-if-changed=proto-ext");
+    let protos: Vec<PathBuf> = PROTOS
+        .iter()
+        .map(|file| Path::new("proto/src").join(file))
+        .chain(
+            EXT_PROTOS
+                .iter()
+                .map(|file| Path::new("proto-ext").join(file)),
+        )
+        .collect();
     tonic_build::configure()
+        .file_descriptor_set_path(out_dir.join("xline_descriptor.bin"))
         .type_attribute(".", "#[derive(serde::Deserialize, serde::Serialize)]")
-        .compile(
-            &[
-                "proto/src/kv.proto",
-                "proto/src/rpc.proto",
-                "proto/src/auth.proto",
-                "proto/src/v3lock.proto",
-                "proto/src/lease.proto",
-                "proto/src/xline-command.proto",
-                "proto/src/xline-error.proto",
-            ],
-            &["./proto/src"],
-        )
+        .compile(&protos, &["proto/src", "proto-ext"])
         .unwrap_or_else(|e| panic!("Failed to compile proto, error is {:?}", e));
 }
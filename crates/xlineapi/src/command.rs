@@ -4,14 +4,17 @@ use std::{
 };
 
 use curp::{client::ClientApi, cmd::Command as CurpCommand};
-use curp_external_api::cmd::{ConflictCheck, PbCodec, PbSerializeError};
+use curp_external_api::cmd::{ConflictCheck, PbCodec, PbSerializeError, Priority};
 use itertools::Itertools;
 use prost::Message;
 use serde::{Deserialize, Serialize};
+use utils::config::QosClass;
 
 use crate::{
-    execute_error::ExecuteError, AuthInfo, PbCommand, PbCommandResponse, PbKeyRange,
-    PbSyncResponse, Request, RequestWrapper, ResponseWrapper,
+    execute_error::ExecuteError,
+    internalpb::{InternalRequest, InternalResponse},
+    AuthInfo, PbCommand, PbCommandResponse, PbKeyRange, PbSyncResponse, Request, RequestWrapper,
+    ResponseWrapper,
 };
 
 /// The curp client trait object on the command of xline
@@ -219,6 +222,42 @@ pub struct Command {
     compact_id: u64,
     /// Auth info
     auth_info: Option<AuthInfo>,
+    /// QoS class, the command is scheduled by its class. It is encoded along with the
+    /// command by `PbCodec`, so it is proposed and replicated with the log entries, but
+    /// the log storage does not persist it and recovered commands are normal.
+    #[serde(skip)]
+    qos_class: QosClass,
+}
+
+/// Fields of the encoded command not defined in `PbCommand`. It is merged into the
+/// encoded `PbCommand`, and decoders that do not know it skip the unknown field.
+#[derive(Clone, PartialEq, Message)]
+struct PbCommandExt {
+    /// QoS class, `0` for normal, `1` for critical and `2` for batch
+    #[prost(int32, tag = "1000")]
+    qos_class: i32,
+}
+
+impl PbCommandExt {
+    /// Encode the qos class
+    #[allow(clippy::wildcard_enum_match_arm)] // new classes are encoded as normal
+    fn new(qos_class: QosClass) -> Self {
+        let qos_class = match qos_class {
+            QosClass::Critical => 1,
+            QosClass::Batch => 2,
+            _ => 0,
+        };
+        Self { qos_class }
+    }
+
+    /// Decode the qos class, unknown classes are normal
+    fn qos_class(&self) -> QosClass {
+        match self.qos_class {
+            1 => QosClass::Critical,
+            2 => QosClass::Batch,
+            _ => QosClass::Normal,
+        }
+    }
 }
 
 /// get all lease ids in the request wrapper
@@ -226,6 +265,9 @@ pub fn get_lease_ids(wrapper: &RequestWrapper) -> HashSet<i64> {
     match *wrapper {
         RequestWrapper::LeaseGrantRequest(ref req) => HashSet::from_iter(vec![req.id]),
         RequestWrapper::LeaseRevokeRequest(ref req) => HashSet::from_iter(vec![req.id]),
+        RequestWrapper::LeaseCheckpointRequest(ref req) => {
+            req.checkpoints.iter().map(|cp| cp.id).collect()
+        }
         RequestWrapper::PutRequest(ref req) if req.lease != 0 => {
             HashSet::from_iter(vec![req.lease])
         }
@@ -277,6 +319,7 @@ pub fn get_lease_ids(wrapper: &RequestWrapper) -> HashSet<i64> {
         | RequestWrapper::AuthUserListRequest(_)
         | RequestWrapper::AuthUserRevokeRoleRequest(_)
         | RequestWrapper::AuthenticateRequest(_)
+        | RequestWrapper::InternalAuthenticateRequest(_)
         | RequestWrapper::LeaseLeasesRequest(_)
         | RequestWrapper::AlarmRequest(_) => HashSet::new(),
     }
@@ -363,6 +406,7 @@ impl Command {
             keys,
             compact_id: 0,
             auth_info: None,
+            qos_class: QosClass::default(),
         }
     }
 
@@ -379,6 +423,7 @@ impl Command {
             keys,
             compact_id: 0,
             auth_info,
+            qos_class: QosClass::default(),
         }
     }
 
@@ -397,6 +442,21 @@ impl Command {
         self.compact_id
     }
 
+    /// With `qos_class`
+    #[must_use]
+    #[inline]
+    pub fn with_qos_class(mut self, qos_class: QosClass) -> Self {
+        self.qos_class = qos_class;
+        self
+    }
+
+    /// Get qos class
+    #[must_use]
+    #[inline]
+    pub fn qos_class(&self) -> QosClass {
+        self.qos_class
+    }
+
     /// get request
     #[must_use]
     #[inline]
@@ -441,21 +501,29 @@ pub struct CommandResponse {
 impl PbCodec for CommandResponse {
     #[inline]
     fn encode(&self) -> Vec<u8> {
-        PbCommandResponse {
-            response_wrapper: Some(self.response.clone()),
+        match self.response.clone().into_pb() {
+            Ok(response) => PbCommandResponse {
+                response_wrapper: Some(response),
+            }
+            .encode_to_vec(),
+            Err(response) => InternalResponse {
+                response: Some(response),
+            }
+            .encode_to_vec(),
         }
-        .encode_to_vec()
     }
 
     #[inline]
     fn decode(buf: &[u8]) -> Result<Self, PbSerializeError> {
-        let pb_cmd_resp = PbCommandResponse::decode(buf)?;
+        let response = match PbCommandResponse::decode(buf)?.response_wrapper {
+            Some(response) => response.into(),
+            None => InternalResponse::decode(buf)?
+                .response
+                .ok_or(PbSerializeError::EmptyField)?
+                .into(),
+        };
 
-        Ok(CommandResponse {
-            response: pb_cmd_resp
-                .response_wrapper
-                .ok_or(PbSerializeError::EmptyField)?,
-        })
+        Ok(CommandResponse { response })
     }
 }
 
@@ -545,30 +613,63 @@ impl CurpCommand for Command {
     fn is_read_only(&self) -> bool {
         self.request().is_read_only()
     }
+
+    #[inline]
+    #[allow(clippy::wildcard_enum_match_arm)] // new classes are scheduled as normal
+    fn priority(&self) -> Priority {
+        match self.qos_class {
+            QosClass::Critical => Priority::High,
+            QosClass::Batch => Priority::Low,
+            _ => Priority::Normal,
+        }
+    }
 }
 
 impl PbCodec for Command {
     #[inline]
     fn encode(&self) -> Vec<u8> {
+        let (request_wrapper, internal) = match self.request.clone().into_pb() {
+            Ok(request) => (Some(request), None),
+            Err(request) => (None, Some(request)),
+        };
         let rpc_cmd = PbCommand {
             keys: self.keys.iter().cloned().map(Into::into).collect(),
             compact_id: self.compact_id,
             auth_info: self.auth_info.clone(),
-            request_wrapper: Some(self.request.clone()),
+            request_wrapper,
         };
-        rpc_cmd.encode_to_vec()
+        let mut buf = rpc_cmd.encode_to_vec();
+        if let Some(request) = internal {
+            buf.extend(
+                InternalRequest {
+                    request: Some(request),
+                }
+                .encode_to_vec(),
+            );
+        }
+        if self.qos_class != QosClass::Normal {
+            buf.extend(PbCommandExt::new(self.qos_class).encode_to_vec());
+        }
+        buf
     }
 
     #[inline]
     fn decode(buf: &[u8]) -> Result<Self, PbSerializeError> {
         let rpc_cmd = PbCommand::decode(buf)?;
+        let ext = PbCommandExt::decode(buf)?;
+        let request = match rpc_cmd.request_wrapper {
+            Some(request) => request.into(),
+            None => InternalRequest::decode(buf)?
+                .request
+                .ok_or(PbSerializeError::EmptyField)?
+                .into(),
+        };
         Ok(Self {
             keys: rpc_cmd.keys.into_iter().map(Into::into).collect(),
             compact_id: rpc_cmd.compact_id,
             auth_info: rpc_cmd.auth_info,
-            request: rpc_cmd
-                .request_wrapper
-                .ok_or(PbSerializeError::EmptyField)?,
+            request,
+            qos_class: ext.qos_class(),
         })
     }
 }
@@ -578,8 +679,9 @@ mod test {
     use super::*;
     use crate::{
         AuthEnableRequest, AuthStatusRequest, CommandKeys, CompactionRequest, Compare,
-        LeaseGrantRequest, LeaseLeasesRequest, LeaseRevokeRequest, PutRequest, PutResponse,
-        RangeRequest, RequestOp, TxnRequest,
+        LeaseCheckpoint, LeaseCheckpointRequest, LeaseCheckpointResponse, LeaseGrantRequest,
+        LeaseLeasesRequest, LeaseRevokeRequest, PutRequest, PutResponse, RangeRequest, RequestOp,
+        TxnRequest,
     };
 
     #[test]
@@ -792,6 +894,44 @@ mod test {
         assert_eq!(cmd, decoded_cmd);
     }
 
+    #[test]
+    fn command_qos_class_should_be_encoded() {
+        let cmd = Command::new(
+            vec![KeyRange::new("a", "e")],
+            RequestWrapper::PutRequest(PutRequest::default()),
+        )
+        .with_qos_class(QosClass::Critical);
+        let decoded_cmd =
+            <Command as PbCodec>::decode(&cmd.encode()).expect("decode should success");
+        assert_eq!(decoded_cmd.qos_class(), QosClass::Critical);
+        assert_eq!(decoded_cmd.priority(), Priority::High);
+        // decoders without the extension see the same command
+        let rpc_cmd = PbCommand::decode(cmd.encode().as_slice()).expect("decode should success");
+        assert_eq!(rpc_cmd.compact_id, cmd.compact_id());
+    }
+
+    #[test]
+    fn internal_command_serialization_is_ok() {
+        let cmd = Command::new(
+            vec![],
+            RequestWrapper::LeaseCheckpointRequest(LeaseCheckpointRequest {
+                checkpoints: vec![LeaseCheckpoint {
+                    id: 1,
+                    remaining_ttl: 10,
+                }],
+            }),
+        );
+        let decoded_cmd =
+            <Command as PbCodec>::decode(&cmd.encode()).expect("decode should success");
+        assert_eq!(cmd, decoded_cmd);
+        let cmd_resp = CommandResponse::new(ResponseWrapper::LeaseCheckpointResponse(
+            LeaseCheckpointResponse::default(),
+        ));
+        let decoded_cmd_resp = <CommandResponse as PbCodec>::decode(&cmd_resp.encode())
+            .expect("decode should success");
+        assert_eq!(cmd_resp, decoded_cmd_resp);
+    }
+
     #[test]
     fn command_resp_serialization_is_ok() {
         let cmd_resp = CommandResponse::new(ResponseWrapper::PutResponse(PutResponse::default()));
@@ -5,7 +5,10 @@ use prost::Message;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
-use crate::{PbExecuteError, PbExecuteErrorOuter, PbRevisions, PbUserRole};
+use crate::{
+    internalpb::{internal_execute_error::Error as PbInternalExecuteError, InternalExecuteError},
+    PbExecuteError, PbExecuteErrorOuter, PbRevisions, PbUserRole,
+};
 
 /// Error met when executing commands
 #[cfg_attr(test, derive(strum_macros::EnumIter))]
@@ -99,6 +102,10 @@ pub enum ExecuteError {
     /// no space left in quota
     #[error("no space left in quota")]
     Nospace,
+
+    /// The quota of a tenant is exceeded
+    #[error("quota of tenant {0} is exceeded")]
+    TenantQuotaExceeded(String),
 }
 
 impl From<PbExecuteError> for ExecuteError {
@@ -144,10 +151,20 @@ impl From<PbExecuteError> for ExecuteError {
     }
 }
 
-impl From<ExecuteError> for PbExecuteError {
+impl From<PbInternalExecuteError> for ExecuteError {
     #[inline]
-    fn from(err: ExecuteError) -> Self {
+    fn from(err: PbInternalExecuteError) -> Self {
         match err {
+            PbInternalExecuteError::TenantQuotaExceeded(t) => ExecuteError::TenantQuotaExceeded(t),
+        }
+    }
+}
+
+impl ExecuteError {
+    /// Convert into the error of xline-proto, or into the internal error for the errors
+    /// that xline-proto does not have
+    fn into_pb(self) -> Result<PbExecuteError, PbInternalExecuteError> {
+        Ok(match self {
             ExecuteError::KeyNotFound => PbExecuteError::KeyNotFound(()),
             ExecuteError::RevisionTooLarge(required_revision, current_revision) => {
                 PbExecuteError::RevisionTooLarge(PbRevisions {
@@ -192,25 +209,31 @@ impl From<ExecuteError> for PbExecuteError {
             ExecuteError::DbError(e) => PbExecuteError::DbError(e),
             ExecuteError::PermissionDenied => PbExecuteError::PermissionDenied(()),
             ExecuteError::Nospace => PbExecuteError::Nospace(()),
-        }
+            ExecuteError::TenantQuotaExceeded(t) => {
+                return Err(PbInternalExecuteError::TenantQuotaExceeded(t))
+            }
+        })
     }
 }
 
 impl PbCodec for ExecuteError {
     #[inline]
     fn encode(&self) -> Vec<u8> {
-        PbExecuteErrorOuter {
-            error: Some(self.clone().into()),
+        match self.clone().into_pb() {
+            Ok(error) => PbExecuteErrorOuter { error: Some(error) }.encode_to_vec(),
+            Err(error) => InternalExecuteError { error: Some(error) }.encode_to_vec(),
         }
-        .encode_to_vec()
     }
 
     #[inline]
     fn decode(buf: &[u8]) -> Result<Self, PbSerializeError> {
-        Ok(PbExecuteErrorOuter::decode(buf)?
-            .error
-            .ok_or(PbSerializeError::EmptyField)?
-            .into())
+        Ok(match PbExecuteErrorOuter::decode(buf)?.error {
+            Some(error) => error.into(),
+            None => InternalExecuteError::decode(buf)?
+                .error
+                .ok_or(PbSerializeError::EmptyField)?
+                .into(),
+        })
     }
 }
 
@@ -286,10 +309,19 @@ impl From<ExecuteError> for tonic::Status {
                 tonic::Code::InvalidArgument,
                 "etcdserver: permission not given".to_owned(),
             ),
-            ExecuteError::InvalidAuthToken | ExecuteError::TokenOldRevision(_, _) => (
+            ExecuteError::InvalidAuthToken => (
                 tonic::Code::Unauthenticated,
                 "etcdserver: invalid auth token".to_owned(),
             ),
+            // The etcd client refreshes its token and retries on this message
+            ExecuteError::TokenOldRevision(_, _) => (
+                tonic::Code::InvalidArgument,
+                "etcdserver: revision of auth store is old".to_owned(),
+            ),
+            ExecuteError::TokenNotProvided => (
+                tonic::Code::InvalidArgument,
+                "etcdserver: user name is empty".to_owned(),
+            ),
             ExecuteError::PermissionDenied => (
                 tonic::Code::PermissionDenied,
                 "etcdserver: permission denied".to_owned(),
@@ -302,13 +334,18 @@ impl From<ExecuteError> for tonic::Status {
                 tonic::Code::ResourceExhausted,
                 "etcdserver: mvcc: database space exceeded".to_owned(),
             ),
-            ExecuteError::LeaseExpired(_) => (tonic::Code::DeadlineExceeded, err.to_string()),
+            ExecuteError::TenantQuotaExceeded(_) => {
+                (tonic::Code::ResourceExhausted, err.to_string())
+            }
+            ExecuteError::LeaseExpired(_) => (
+                tonic::Code::NotFound,
+                "etcdserver: requested lease not found".to_owned(),
+            ),
             ExecuteError::UserAlreadyHasRole(_, _)
             | ExecuteError::NoPasswordUser
             | ExecuteError::TokenManagerNotInit => {
                 (tonic::Code::FailedPrecondition, err.to_string())
             }
-            ExecuteError::TokenNotProvided => (tonic::Code::InvalidArgument, err.to_string()),
             ExecuteError::DbError(_) => (tonic::Code::Internal, err.to_string()),
         };
 
@@ -330,4 +367,45 @@ mod test {
             assert!(matches!(err, _decoded_err));
         }
     }
+
+    #[test]
+    fn status_should_be_compatible_with_etcd() {
+        let cases = [
+            (
+                ExecuteError::RevisionCompacted(1, 2),
+                tonic::Code::OutOfRange,
+                "etcdserver: mvcc: required revision has been compacted",
+            ),
+            (
+                ExecuteError::LeaseExpired(1),
+                tonic::Code::NotFound,
+                "etcdserver: requested lease not found",
+            ),
+            (
+                ExecuteError::InvalidAuthToken,
+                tonic::Code::Unauthenticated,
+                "etcdserver: invalid auth token",
+            ),
+            (
+                ExecuteError::TokenOldRevision(1, 2),
+                tonic::Code::InvalidArgument,
+                "etcdserver: revision of auth store is old",
+            ),
+            (
+                ExecuteError::TokenNotProvided,
+                tonic::Code::InvalidArgument,
+                "etcdserver: user name is empty",
+            ),
+            (
+                ExecuteError::Nospace,
+                tonic::Code::ResourceExhausted,
+                "etcdserver: mvcc: database space exceeded",
+            ),
+        ];
+        for (err, code, message) in cases {
+            let status = tonic::Status::from(err);
+            assert_eq!(status.code(), code);
+            assert_eq!(status.message(), message);
+        }
+    }
 }
@@ -176,6 +176,7 @@ pub mod command;
 pub mod execute_error;
 pub mod interval;
 pub mod request_validation;
+mod wrapper;
 
 mod etcdserverpb {
     tonic::include_proto!("etcdserverpb");
@@ -193,10 +194,18 @@ mod v3lockpb {
     tonic::include_proto!("v3lockpb");
 }
 
+mod v3electionpb {
+    tonic::include_proto!("v3electionpb");
+}
+
 mod leasepb {
     tonic::include_proto!("leasepb");
 }
 
+mod leasecheckpointpb {
+    tonic::include_proto!("leasecheckpointpb");
+}
+
 mod commandpb {
     tonic::include_proto!("commandpb");
 }
@@ -205,16 +214,37 @@ mod errorpb {
     tonic::include_proto!("errorpb");
 }
 
+mod internalpb {
+    tonic::include_proto!("internalpb");
+}
+
+mod adminpb {
+    tonic::include_proto!("adminpb");
+}
+
+/// The encoded file descriptor set of all xline protos, used by the gRPC reflection service
+/// and the JSON gateway
+pub const FILE_DESCRIPTOR_SET: &[u8] =
+    include_bytes!(concat!(env!("OUT_DIR"), "/xline_descriptor.bin"));
+
 use std::fmt::Display;
 
 use command::KeyRange;
 use utils::write_vec;
 
 pub use self::{
+    adminpb::{
+        admin_client::AdminClient,
+        admin_server::{Admin, AdminServer},
+        DropWatcherRequest, DropWatcherResponse, ForceCompactRequest, ForceCompactResponse,
+        LeaseDumpRequest, LeaseDumpResponse, LeaseInfo, RotateAuthKeyRequest,
+        RotateAuthKeyResponse, RuntimeStatusRequest, RuntimeStatusResponse, SetLogLevelRequest,
+        SetLogLevelResponse, TenantInfo, TenantUsageRequest, TenantUsageResponse,
+    },
     authpb::{permission::Type, Permission, Role, User, UserAddOptions},
     commandpb::{
-        command::{AuthInfo, RequestWrapper},
-        command_response::ResponseWrapper,
+        command::{AuthInfo, RequestWrapper as PbRequestWrapper},
+        command_response::ResponseWrapper as PbResponseWrapper,
         Command as PbCommand, CommandResponse as PbCommandResponse, KeyRange as PbKeyRange,
         SyncResponse as PbSyncResponse,
     },
@@ -229,6 +259,7 @@ pub use self::{
         cluster_client::ClusterClient,
         cluster_server::{Cluster, ClusterServer},
         compare::{CompareResult, CompareTarget, TargetUnion},
+        downgrade_request::DowngradeAction,
         kv_client::KvClient,
         kv_server::{Kv, KvServer},
         lease_client::LeaseClient,
@@ -265,12 +296,20 @@ pub use self::{
         SnapshotResponse, StatusRequest, StatusResponse, TxnRequest, TxnResponse,
         WatchCancelRequest, WatchCreateRequest, WatchProgressRequest, WatchRequest, WatchResponse,
     },
+    internalpb::InternalAuthenticateRequest,
+    leasecheckpointpb::{LeaseCheckpoint, LeaseCheckpointRequest, LeaseCheckpointResponse},
     leasepb::Lease as PbLease,
     mvccpb::{event::EventType, Event, KeyValue},
+    v3electionpb::{
+        election_server::{Election, ElectionServer},
+        CampaignRequest, CampaignResponse, LeaderKey, LeaderRequest, LeaderResponse,
+        ProclaimRequest, ProclaimResponse, ResignRequest, ResignResponse,
+    },
     v3lockpb::{
         lock_server::{Lock, LockServer},
         LockRequest, LockResponse, UnlockRequest, UnlockResponse,
     },
+    wrapper::{RequestWrapper, ResponseWrapper},
 };
 
 impl User {
@@ -309,6 +348,7 @@ impl ResponseWrapper {
             ResponseWrapper::LeaseGrantResponse(ref mut resp) => &mut resp.header,
             ResponseWrapper::LeaseRevokeResponse(ref mut resp) => &mut resp.header,
             ResponseWrapper::LeaseLeasesResponse(ref mut resp) => &mut resp.header,
+            ResponseWrapper::LeaseCheckpointResponse(ref mut resp) => &mut resp.header,
             ResponseWrapper::AlarmResponse(ref mut resp) => &mut resp.header,
         };
         if let Some(ref mut header) = *header {
@@ -429,10 +469,12 @@ impl RequestWrapper {
             | RequestWrapper::AuthUserGrantRoleRequest(_)
             | RequestWrapper::AuthUserListRequest(_)
             | RequestWrapper::AuthUserRevokeRoleRequest(_)
-            | RequestWrapper::AuthenticateRequest(_) => RequestBackend::Auth,
+            | RequestWrapper::AuthenticateRequest(_)
+            | RequestWrapper::InternalAuthenticateRequest(_) => RequestBackend::Auth,
             RequestWrapper::LeaseGrantRequest(_)
             | RequestWrapper::LeaseRevokeRequest(_)
-            | RequestWrapper::LeaseLeasesRequest(_) => RequestBackend::Lease,
+            | RequestWrapper::LeaseLeasesRequest(_)
+            | RequestWrapper::LeaseCheckpointRequest(_) => RequestBackend::Lease,
             RequestWrapper::AlarmRequest(_) => RequestBackend::Alarm,
         }
     }
@@ -467,8 +509,10 @@ impl RequestWrapper {
             | RequestWrapper::AuthUserGrantRoleRequest(_)
             | RequestWrapper::AuthUserRevokeRoleRequest(_)
             | RequestWrapper::AuthenticateRequest(_)
+            | RequestWrapper::InternalAuthenticateRequest(_)
             | RequestWrapper::LeaseGrantRequest(_)
             | RequestWrapper::LeaseRevokeRequest(_)
+            | RequestWrapper::LeaseCheckpointRequest(_)
             | RequestWrapper::AlarmRequest(_) => false,
         }
     }
@@ -490,7 +534,9 @@ impl RequestWrapper {
         self.is_auth_read_request()
             || matches!(
                 *self,
-                RequestWrapper::AuthEnableRequest(_) | RequestWrapper::AuthenticateRequest(_)
+                RequestWrapper::AuthEnableRequest(_)
+                    | RequestWrapper::AuthenticateRequest(_)
+                    | RequestWrapper::InternalAuthenticateRequest(_)
             )
     }
 
@@ -499,6 +545,7 @@ impl RequestWrapper {
         match self {
             RequestWrapper::RangeRequest(_)
             | RequestWrapper::LeaseGrantRequest(_)
+            | RequestWrapper::LeaseCheckpointRequest(_)
             | RequestWrapper::CompactionRequest(_) => true,
             RequestWrapper::TxnRequest(req) => req.is_read_only(),
             _ => false,
@@ -606,9 +653,11 @@ impl_from_requests!(
     AuthUserListRequest,
     AuthUserRevokeRoleRequest,
     AuthenticateRequest,
+    InternalAuthenticateRequest,
     LeaseGrantRequest,
     LeaseRevokeRequest,
     LeaseLeasesRequest,
+    LeaseCheckpointRequest,
     AlarmRequest
 );
 
@@ -638,6 +687,7 @@ impl_from_responses!(
     LeaseGrantResponse,
     LeaseRevokeResponse,
     LeaseLeasesResponse,
+    LeaseCheckpointResponse,
     AlarmResponse
 );
 
@@ -59,12 +59,19 @@ impl RequestValidator for DeleteRangeRequest {
 
 impl RequestValidator for TxnRequest {
     fn validation(&self) -> Result<(), ValidationError> {
+        self.validation_with_max_ops(DEFAULT_MAX_TXN_OPS)
+    }
+}
+
+impl TxnRequest {
+    /// Validate the request, allowing at most `max_txn_ops` operations in compares and each branch
+    pub fn validation_with_max_ops(&self, max_txn_ops: usize) -> Result<(), ValidationError> {
         let opc = self
             .compare
             .len()
             .max(self.success.len())
             .max(self.failure.len());
-        if opc > DEFAULT_MAX_TXN_OPS {
+        if opc > max_txn_ops {
             return Err(ValidationError::TooManyOps);
         }
         for c in &self.compare {
@@ -78,7 +85,7 @@ impl RequestValidator for TxnRequest {
                     Request::RequestRange(ref r) => r.validation(),
                     Request::RequestPut(ref r) => r.validation(),
                     Request::RequestDeleteRange(ref r) => r.validation(),
-                    Request::RequestTxn(ref r) => r.validation(),
+                    Request::RequestTxn(ref r) => r.validation_with_max_ops(max_txn_ops),
                 }?;
             } else {
                 return Err(ValidationError::RequestNotProvided);
@@ -168,8 +175,18 @@ impl RequestValidator for AuthRoleAddRequest {
 
 impl RequestValidator for AuthRoleGrantPermissionRequest {
     fn validation(&self) -> Result<(), ValidationError> {
-        if self.perm.is_none() {
+        let Some(ref perm) = self.perm else {
             return Err(ValidationError::PermissionNotGiven);
+        };
+        // The key can't be empty, like etcd, which rejects it as an invalid range. The range
+        // end can be empty for a single key, `\0` for all keys from the key, or any key
+        // greater than the key, e.g. the prefix end of the key
+        let valid_range = !perm.key.is_empty()
+            && (perm.range_end.is_empty()
+                || perm.range_end.as_slice() == [0]
+                || perm.key < perm.range_end);
+        if !valid_range {
+            return Err(ValidationError::InvalidPermissionRange);
         }
 
         Ok(())
@@ -190,6 +207,9 @@ pub enum ValidationError {
     /// Ignore lease is set but lease is provided
     #[error("ignore lease is set but lease is provided")]
     LeaseProvided,
+    /// Request is larger than the max request size
+    #[error("request is too large")]
+    RequestTooLarge,
     /// Invalid sort option
     #[error("invalid sort option")]
     InvalidSortOption,
@@ -214,6 +234,9 @@ pub enum ValidationError {
     /// Permission not given
     #[error("permission not given")]
     PermissionNotGiven,
+    /// Key range of the permission is invalid
+    #[error("invalid key range of permission")]
+    InvalidPermissionRange,
 }
 
 // The etcd client relies on GRPC error messages for error type interpretation.
@@ -236,6 +259,10 @@ impl From<ValidationError> for tonic::Status {
                 tonic::Code::InvalidArgument,
                 "etcdserver: lease is provided".to_owned(),
             ),
+            ValidationError::RequestTooLarge => (
+                tonic::Code::InvalidArgument,
+                "etcdserver: request is too large".to_owned(),
+            ),
             ValidationError::InvalidSortOption => (
                 tonic::Code::InvalidArgument,
                 "etcdserver: invalid sort option".to_owned(),
@@ -260,6 +287,10 @@ impl From<ValidationError> for tonic::Status {
                 tonic::Code::InvalidArgument,
                 "etcdserver: permission not given".to_owned(),
             ),
+            ValidationError::InvalidPermissionRange => (
+                tonic::Code::InvalidArgument,
+                "etcdserver: invalid auth management".to_owned(),
+            ),
             ValidationError::RequestNotProvided | ValidationError::PasswordEmpty => {
                 (tonic::Code::InvalidArgument, err.to_string())
             }
@@ -272,7 +303,7 @@ impl From<ValidationError> for tonic::Status {
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::{Compare, RequestOp, UserAddOptions};
+    use crate::{Compare, Permission, RequestOp, UserAddOptions};
 
     struct TestCase<T: RequestValidator> {
         req: T,
@@ -408,6 +439,33 @@ mod test {
         run_test(testcases);
     }
 
+    #[test]
+    fn txn_request_should_respect_max_ops() {
+        let put_op = |key: &str| RequestOp {
+            request: Some(Request::RequestPut(PutRequest {
+                key: key.into(),
+                ..Default::default()
+            })),
+        };
+        let req = TxnRequest {
+            compare: vec![],
+            success: vec![RequestOp {
+                request: Some(Request::RequestTxn(TxnRequest {
+                    compare: vec![],
+                    success: vec![put_op("a"), put_op("b"), put_op("c")],
+                    failure: vec![],
+                })),
+            }],
+            failure: vec![],
+        };
+        assert!(req.validation().is_ok());
+        assert!(req.validation_with_max_ops(3).is_ok());
+        assert_eq!(
+            req.validation_with_max_ops(2),
+            Err(ValidationError::TooManyOps)
+        );
+    }
+
     #[test]
     fn invalid_user_add_request_should_have_correct_error_msg() {
         let testcases = vec![
@@ -447,17 +505,64 @@ mod test {
 
     #[test]
     fn invalid_role_grant_perm_request_should_have_correct_error_msg() {
-        let testcases = vec![TestCase {
-            req: AuthRoleGrantPermissionRequest {
-                name: "role".to_owned(),
-                perm: None,
+        let perm = |key: &str, range_end: &str| AuthRoleGrantPermissionRequest {
+            name: "role".to_owned(),
+            perm: Some(Permission {
+                perm_type: 0,
+                key: key.into(),
+                range_end: range_end.into(),
+            }),
+        };
+        let testcases = vec![
+            TestCase {
+                req: AuthRoleGrantPermissionRequest {
+                    name: "role".to_owned(),
+                    perm: None,
+                },
+                expected_err: ValidationError::PermissionNotGiven,
             },
-            expected_err: ValidationError::PermissionNotGiven,
-        }];
+            TestCase {
+                req: perm("", ""),
+                expected_err: ValidationError::InvalidPermissionRange,
+            },
+            TestCase {
+                req: perm("", "\0"),
+                expected_err: ValidationError::InvalidPermissionRange,
+            },
+            TestCase {
+                req: perm("b", "a"),
+                expected_err: ValidationError::InvalidPermissionRange,
+            },
+            TestCase {
+                req: perm("a", "a"),
+                expected_err: ValidationError::InvalidPermissionRange,
+            },
+        ];
 
         run_test(testcases);
     }
 
+    #[test]
+    fn valid_role_grant_perm_request_should_pass_validation() {
+        for (key, range_end) in [
+            ("a", ""),
+            ("a", "\0"),
+            ("\0", "\0"),
+            ("a", "b"),
+            ("a/", "a0"),
+        ] {
+            let req = AuthRoleGrantPermissionRequest {
+                name: "role".to_owned(),
+                perm: Some(Permission {
+                    perm_type: 0,
+                    key: key.into(),
+                    range_end: range_end.into(),
+                }),
+            };
+            assert!(req.validation().is_ok(), "{key:?} {range_end:?}");
+        }
+    }
+
     #[test]
     fn check_intervals_txn_duplicate_should_return_error() {
         let put_op = RequestOp {
@@ -0,0 +1,143 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    internalpb::{
+        internal_request::Request as InternalRequest,
+        internal_response::Response as InternalResponse,
+    },
+    AlarmRequest, AlarmResponse, AuthDisableRequest, AuthDisableResponse, AuthEnableRequest,
+    AuthEnableResponse, AuthRoleAddRequest, AuthRoleAddResponse, AuthRoleDeleteRequest,
+    AuthRoleDeleteResponse, AuthRoleGetRequest, AuthRoleGetResponse,
+    AuthRoleGrantPermissionRequest, AuthRoleGrantPermissionResponse, AuthRoleListRequest,
+    AuthRoleListResponse, AuthRoleRevokePermissionRequest, AuthRoleRevokePermissionResponse,
+    AuthStatusRequest, AuthStatusResponse, AuthUserAddRequest, AuthUserAddResponse,
+    AuthUserChangePasswordRequest, AuthUserChangePasswordResponse, AuthUserDeleteRequest,
+    AuthUserDeleteResponse, AuthUserGetRequest, AuthUserGetResponse, AuthUserGrantRoleRequest,
+    AuthUserGrantRoleResponse, AuthUserListRequest, AuthUserListResponse,
+    AuthUserRevokeRoleRequest, AuthUserRevokeRoleResponse, AuthenticateRequest,
+    AuthenticateResponse, CompactionRequest, CompactionResponse, DeleteRangeRequest,
+    DeleteRangeResponse, InternalAuthenticateRequest, LeaseCheckpointRequest,
+    LeaseCheckpointResponse, LeaseGrantRequest, LeaseGrantResponse, LeaseLeasesRequest,
+    LeaseLeasesResponse, LeaseRevokeRequest, LeaseRevokeResponse, PbRequestWrapper,
+    PbResponseWrapper, PutRequest, PutResponse, RangeRequest, RangeResponse, TxnRequest,
+    TxnResponse,
+};
+
+/// Define a wrapper with the variants of the oneof in xline-proto and the variants of the
+/// oneof in the internal message, each variant is named after the type it holds
+macro_rules! define_wrapper {
+    (
+        $(#[$attr:meta])*
+        $wrapper:ident, $pb:ident, $internal:ident,
+        [$($variant:ident),* $(,)?],
+        [$($internal_variant:ident),* $(,)?]
+    ) => {
+        $(#[$attr])*
+        #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+        pub enum $wrapper {
+            $($variant($variant),)*
+            $($internal_variant($internal_variant),)*
+        }
+
+        impl From<$pb> for $wrapper {
+            #[inline]
+            fn from(pb: $pb) -> Self {
+                match pb {
+                    $($pb::$variant(inner) => Self::$variant(inner),)*
+                }
+            }
+        }
+
+        impl From<$internal> for $wrapper {
+            #[inline]
+            fn from(internal: $internal) -> Self {
+                match internal {
+                    $($internal::$internal_variant(inner) => Self::$internal_variant(inner),)*
+                }
+            }
+        }
+
+        impl $wrapper {
+            /// Convert into the oneof of xline-proto, or into the oneof of the internal
+            /// message for the variants that xline-proto does not have
+            pub(crate) fn into_pb(self) -> Result<$pb, $internal> {
+                match self {
+                    $(Self::$variant(inner) => Ok($pb::$variant(inner)),)*
+                    $(Self::$internal_variant(inner) => Err($internal::$internal_variant(inner)),)*
+                }
+            }
+        }
+    };
+}
+
+define_wrapper!(
+    /// Request of a command, either sent by a client or proposed by the members themselves
+    RequestWrapper,
+    PbRequestWrapper,
+    InternalRequest,
+    [
+        RangeRequest,
+        PutRequest,
+        DeleteRangeRequest,
+        TxnRequest,
+        CompactionRequest,
+        AuthEnableRequest,
+        AuthDisableRequest,
+        AuthStatusRequest,
+        AuthRoleAddRequest,
+        AuthRoleDeleteRequest,
+        AuthRoleGetRequest,
+        AuthRoleGrantPermissionRequest,
+        AuthRoleListRequest,
+        AuthRoleRevokePermissionRequest,
+        AuthUserAddRequest,
+        AuthUserChangePasswordRequest,
+        AuthUserDeleteRequest,
+        AuthUserGetRequest,
+        AuthUserGrantRoleRequest,
+        AuthUserListRequest,
+        AuthUserRevokeRoleRequest,
+        AuthenticateRequest,
+        LeaseGrantRequest,
+        LeaseRevokeRequest,
+        LeaseLeasesRequest,
+        AlarmRequest,
+    ],
+    [LeaseCheckpointRequest, InternalAuthenticateRequest]
+);
+
+define_wrapper!(
+    /// Response of a command
+    ResponseWrapper,
+    PbResponseWrapper,
+    InternalResponse,
+    [
+        RangeResponse,
+        PutResponse,
+        DeleteRangeResponse,
+        TxnResponse,
+        CompactionResponse,
+        AuthEnableResponse,
+        AuthDisableResponse,
+        AuthStatusResponse,
+        AuthRoleAddResponse,
+        AuthRoleDeleteResponse,
+        AuthRoleGetResponse,
+        AuthRoleGrantPermissionResponse,
+        AuthRoleListResponse,
+        AuthRoleRevokePermissionResponse,
+        AuthUserAddResponse,
+        AuthUserChangePasswordResponse,
+        AuthUserDeleteResponse,
+        AuthUserGetResponse,
+        AuthUserGrantRoleResponse,
+        AuthUserListResponse,
+        AuthUserRevokeRoleResponse,
+        AuthenticateResponse,
+        LeaseGrantResponse,
+        LeaseRevokeResponse,
+        LeaseLeasesResponse,
+        AlarmResponse,
+    ],
+    [LeaseCheckpointResponse]
+);
@@ -276,6 +276,10 @@ async fn main() -> Result<()> {
         *matches.get_one("retry_count").expect("Required"),
         true,
         Duration::from_millis(*matches.get_one("keep_alive_interval").expect("Required")),
+        // short-lived command, no need to check the health of endpoints
+        Duration::ZERO,
+        // nor to re-resolve their hostnames
+        Duration::ZERO,
     );
     let ca_path: Option<PathBuf> = matches.get_one("ca_cert_pem_path").cloned();
     let tls_config = match ca_path {
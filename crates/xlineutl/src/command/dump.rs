@@ -0,0 +1,281 @@
+use std::collections::HashSet;
+
+use anyhow::Result;
+use clap::{arg, ArgMatches, Command};
+use engine::ReadOnlyRocksEngine;
+use prost::Message;
+use serde::Serialize;
+use utils::table_names::{
+    AUTH_TABLE, KV_TABLE, LEASE_TABLE, META_TABLE, ROLE_TABLE, USER_TABLE, XLINE_TABLES,
+};
+use xline::storage::Revision;
+use xlineapi::{KeyValue, PbLease, Role, User};
+
+use crate::printer::Printer;
+
+/// Keys of the meta table that hold a revision
+const META_REVISION_KEYS: [&str; 2] = ["finished_compact_revision", "scheduled_compact_revision"];
+/// Key of the meta table that holds the applied index
+const META_APPLIED_INDEX_KEY: &str = "applied_index";
+/// Key of the auth table that holds the auth enable flag
+const AUTH_ENABLE_KEY: &[u8] = b"enable";
+/// Key of the meta table that marks the values carry checksums
+const META_CHECKSUMS_KEY: &[u8] = b"checksums";
+/// Length of the checksum appended to the values
+const CHECKSUM_LEN: usize = 4;
+
+/// Definition of `dump` command
+pub(crate) fn command() -> Command {
+    Command::new("dump")
+        .about("Prints the keys, leases, auth users and stats of a stopped xline member")
+        .arg(arg!(<data_dir> "Path to the data directory of the member"))
+        .arg(arg!(--values "Print the values of the keys as well"))
+        .arg(arg!(--history "Print all revisions of the keys instead of the latest ones"))
+}
+
+/// Execute the command
+pub(crate) fn execute(matches: &ArgMatches) -> Result<()> {
+    let data_dir = matches.get_one::<String>("data_dir").expect("required");
+    let values = matches.get_flag("values");
+    let history = matches.get_flag("history");
+    let engine = ReadOnlyRocksEngine::open(data_dir, &XLINE_TABLES)?;
+    dump(&engine, values, history)?.print();
+    Ok(())
+}
+
+/// Dump of a data dir
+#[derive(Debug, Default, Serialize)]
+struct Dump {
+    /// Stats of the store
+    stats: Stats,
+    /// Keys of the kv table
+    kvs: Vec<KvEntry>,
+    /// Leases
+    leases: Vec<LeaseEntry>,
+    /// Whether the auth is enabled
+    auth_enabled: bool,
+    /// Users
+    users: Vec<UserEntry>,
+    /// Names of the roles
+    roles: Vec<String>,
+}
+
+/// Stats of a store
+#[derive(Debug, Default, Serialize)]
+struct Stats {
+    /// Latest revision
+    revision: i64,
+    /// Revisions in the meta table, such as the compacted revision
+    meta_revisions: Vec<(String, i64)>,
+    /// Applied index of the state machine
+    applied_index: Option<u64>,
+    /// Stats of every table
+    tables: Vec<TableStats>,
+}
+
+/// Stats of a table
+#[derive(Debug, Default, Serialize)]
+struct TableStats {
+    /// Name of the table
+    name: String,
+    /// Number of the entries
+    count: usize,
+    /// Total sst file size of the table
+    size: u64,
+    /// Number of the entries that can not be decoded, which are corrupted or encrypted
+    undecodable: usize,
+}
+
+/// A revision of a key
+#[derive(Debug, Serialize)]
+struct KvEntry {
+    /// Key
+    key: String,
+    /// Revision
+    revision: i64,
+    /// Sub revision
+    sub_revision: i64,
+    /// Create revision
+    create_revision: i64,
+    /// Version, 0 means the key is deleted at this revision
+    version: i64,
+    /// Lease id
+    lease: i64,
+    /// Value
+    value: Option<String>,
+}
+
+/// A lease
+#[derive(Debug, Serialize)]
+struct LeaseEntry {
+    /// Lease id
+    id: i64,
+    /// Time to live
+    ttl: i64,
+}
+
+/// A user
+#[derive(Debug, Serialize)]
+struct UserEntry {
+    /// Name
+    name: String,
+    /// Roles
+    roles: Vec<String>,
+}
+
+/// Dump the data dir
+#[allow(clippy::arithmetic_side_effects)] // usize is big enough
+fn dump(engine: &ReadOnlyRocksEngine, values: bool, history: bool) -> Result<Dump> {
+    let mut dump = Dump::default();
+    // the checksums are not verified, a corrupted value is reported as undecodable
+    let checksums = engine
+        .get_all(META_TABLE)?
+        .iter()
+        .any(|&(ref key, _)| key == META_CHECKSUMS_KEY);
+    for table in XLINE_TABLES {
+        let Ok(pairs) = engine.get_all(table) else {
+            // tables added by later versions may not exist
+            continue;
+        };
+        let mut stats = TableStats {
+            name: table.to_owned(),
+            count: pairs.len(),
+            size: engine.table_size(table)?,
+            undecodable: 0,
+        };
+        for (key, mut value) in pairs {
+            if checksums && table != META_TABLE {
+                value.truncate(value.len().saturating_sub(CHECKSUM_LEN));
+            }
+            if !dump_entry(&mut dump, table, &key, &value, values) {
+                stats.undecodable += 1;
+            }
+        }
+        dump.stats.tables.push(stats);
+    }
+    if !history {
+        // only keep the latest revision of every key, revisions are in ascending order
+        let mut seen = HashSet::new();
+        dump.kvs.reverse();
+        dump.kvs
+            .retain(|kv| seen.insert(kv.key.clone()) && kv.version != 0);
+        dump.kvs.reverse();
+    }
+    Ok(dump)
+}
+
+/// Dump an entry of a table, returns `false` if it can not be decoded
+fn dump_entry(dump: &mut Dump, table: &str, key: &[u8], value: &[u8], values: bool) -> bool {
+    match table {
+        KV_TABLE => {
+            let Ok(kv) = KeyValue::decode(value) else {
+                return false;
+            };
+            let rev = Revision::decode(key);
+            dump.stats.revision = dump.stats.revision.max(rev.revision());
+            dump.kvs.push(KvEntry {
+                key: String::from_utf8_lossy(&kv.key).into_owned(),
+                revision: rev.revision(),
+                sub_revision: rev.sub_revision(),
+                create_revision: kv.create_revision,
+                version: kv.version,
+                lease: kv.lease,
+                value: values.then(|| String::from_utf8_lossy(&kv.value).into_owned()),
+            });
+        }
+        LEASE_TABLE => {
+            let Ok(lease) = PbLease::decode(value) else {
+                return false;
+            };
+            dump.leases.push(LeaseEntry {
+                id: lease.id,
+                ttl: lease.ttl,
+            });
+        }
+        USER_TABLE => {
+            let Ok(user) = User::decode(value) else {
+                return false;
+            };
+            dump.users.push(UserEntry {
+                name: String::from_utf8_lossy(&user.name).into_owned(),
+                roles: user.roles,
+            });
+        }
+        ROLE_TABLE => {
+            let Ok(role) = Role::decode(value) else {
+                return false;
+            };
+            dump.roles
+                .push(String::from_utf8_lossy(&role.name).into_owned());
+        }
+        AUTH_TABLE if key == AUTH_ENABLE_KEY => {
+            dump.auth_enabled = value.first().is_some_and(|b| *b != 0);
+        }
+        META_TABLE => {
+            let Ok(bytes) = <[u8; 8]>::try_from(value) else {
+                // other values of the meta table, such as the data keys, are not dumped
+                return true;
+            };
+            match String::from_utf8_lossy(key).as_ref() {
+                META_APPLIED_INDEX_KEY => {
+                    dump.stats.applied_index = Some(u64::from_le_bytes(bytes));
+                }
+                name if META_REVISION_KEYS.contains(&name) => {
+                    dump.stats
+                        .meta_revisions
+                        .push((name.to_owned(), i64::from_le_bytes(bytes)));
+                }
+                _ => {}
+            }
+        }
+        _ => {}
+    }
+    true
+}
+
+impl Printer for Dump {
+    fn simple(&self) {
+        for kv in &self.kvs {
+            match kv.value {
+                Some(ref value) => println!("{} {} {value}", kv.revision, kv.key),
+                None => println!("{} {}", kv.revision, kv.key),
+            }
+        }
+    }
+
+    fn field(&self) {
+        println!("Revision : {}", self.stats.revision);
+        for &(ref name, revision) in &self.stats.meta_revisions {
+            println!("{name} : {revision}");
+        }
+        if let Some(index) = self.stats.applied_index {
+            println!("Applied index : {index}");
+        }
+        for table in &self.stats.tables {
+            println!(
+                "Table {} : {} entries, {} bytes, {} undecodable",
+                table.name, table.count, table.size, table.undecodable
+            );
+        }
+        println!("Keys :");
+        for kv in &self.kvs {
+            println!(
+                "  {} rev={}.{} create={} version={} lease={}",
+                kv.key, kv.revision, kv.sub_revision, kv.create_revision, kv.version, kv.lease
+            );
+            if let Some(ref value) = kv.value {
+                println!("    value={value}");
+            }
+        }
+        println!("Leases :");
+        for lease in &self.leases {
+            println!("  {} ttl={}", lease.id, lease.ttl);
+        }
+        println!("Auth enabled : {}", self.auth_enabled);
+        println!("Users :");
+        for user in &self.users {
+            println!("  {} roles={:?}", user.name, user.roles);
+        }
+        println!("Roles : {:?}", self.roles);
+    }
+}
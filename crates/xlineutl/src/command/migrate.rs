@@ -0,0 +1,89 @@
+use anyhow::{anyhow, Result};
+use clap::{arg, ArgMatches, Command};
+use serde::Serialize;
+use xline::migrate::migrate_from_etcd;
+
+use crate::printer::Printer;
+
+/// Definition of `migrate` command
+pub(crate) fn command() -> Command {
+    Command::new("migrate")
+        .about("Migrates the keys, leases and auth config of a live etcd cluster to an xline data directory")
+        .arg(
+            arg!(--endpoints <ENDPOINTS> "Etcd endpoints, using the format of [addr0, addr1, ...]")
+                .num_args(1..)
+                .default_values(["127.0.0.1:2379"])
+                .value_delimiter(','),
+        )
+        .arg(arg!(--user <"USERNAME:PASSWD"> "The etcd user if the auth of etcd is enabled"))
+        .arg(arg!(--"data-dir" <DATA_DIR> "Path to the output data directory, which must not exist"))
+}
+
+/// Execute the command
+pub(crate) async fn execute(matches: &ArgMatches) -> Result<()> {
+    let endpoints: Vec<String> = matches
+        .get_many::<String>("endpoints")
+        .expect("required")
+        .cloned()
+        .collect();
+    let user = matches
+        .get_one::<String>("user")
+        .map(|user| {
+            user.split_once(':')
+                .map(|(name, password)| (name.to_owned(), password.to_owned()))
+                .ok_or_else(|| anyhow!("the user should be in the format of USERNAME:PASSWD"))
+        })
+        .transpose()?;
+    let data_dir = matches.get_one::<String>("data-dir").expect("required");
+    let summary = migrate_from_etcd(&endpoints, user, data_dir).await?;
+    Summary {
+        revision: summary.revision,
+        keys: summary.keys,
+        leases: summary.leases,
+        users: summary.users,
+        roles: summary.roles,
+        auth_enabled: summary.auth_enabled,
+    }
+    .print();
+    Ok(())
+}
+
+/// Summary of a migration
+#[derive(Debug, Serialize)]
+struct Summary {
+    /// The etcd revision that the keys are read at
+    revision: i64,
+    /// Number of the migrated keys
+    keys: usize,
+    /// Number of the migrated leases
+    leases: usize,
+    /// Number of the migrated users
+    users: usize,
+    /// Number of the migrated roles
+    roles: usize,
+    /// Whether the auth is enabled in etcd
+    auth_enabled: bool,
+}
+
+impl Printer for Summary {
+    fn simple(&self) {
+        println!(
+            "{}, {}, {}, {}, {}",
+            self.revision, self.keys, self.leases, self.users, self.roles
+        );
+        if self.auth_enabled {
+            println!("auth is left disabled, set the passwords of the users before enabling it");
+        }
+    }
+
+    fn field(&self) {
+        println!("Revision : {}", self.revision);
+        println!("Keys : {}", self.keys);
+        println!("Leases : {}", self.leases);
+        println!("Users : {}", self.users);
+        println!("Roles : {}", self.roles);
+        if self.auth_enabled {
+            println!("Auth is left disabled, set the passwords of the users before enabling it");
+        }
+    }
+}
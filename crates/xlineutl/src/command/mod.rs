@@ -1,2 +1,6 @@
+/// Dump command
+pub(super) mod dump;
+/// Migrate command
+pub(super) mod migrate;
 /// Snapshot command
 pub(super) mod snapshot;
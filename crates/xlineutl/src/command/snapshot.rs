@@ -4,12 +4,15 @@ use std::{
 };
 
 use anyhow::Result;
-use clap::{arg, ArgMatches, Command};
+use clap::{arg, value_parser, ArgMatches, Command};
 use engine::{Engine, EngineType, StorageEngine};
 use serde::Serialize;
 use tempfile::tempdir;
 use utils::table_names::{KV_TABLE, XLINE_TABLES};
-use xline::storage::Revision;
+use xline::{
+    restore::{restore, restore_to_revision},
+    storage::Revision,
+};
 
 use crate::printer::Printer;
 
@@ -21,7 +24,11 @@ pub(crate) fn command() -> Command {
             Command::new("restore")
                 .about("Restores an xline member snapshot to an xline directory")
                 .arg(arg!(<filename> "Path to the snapshot file"))
-                .arg(arg!(--"data-dir" <DATA_DIR> "Path to the output data directory")),
+                .arg(arg!(--"data-dir" <DATA_DIR> "Path to the output data directory"))
+                .arg(
+                    arg!(--revision <REVISION> "Restore the keys as of the revision, the changes after it are dropped. Leases and auth are restored as they are in the snapshot")
+                        .value_parser(value_parser!(i64)),
+                ),
         )
         .subcommand(
             Command::new("status")
@@ -36,7 +43,8 @@ pub(crate) async fn execute(matches: &ArgMatches) -> Result<()> {
         Some(("restore", sub_matches)) => {
             let snapshot_path = sub_matches.get_one::<String>("filename").expect("required");
             let data_dir = sub_matches.get_one::<String>("data-dir").expect("required");
-            handle_restore(snapshot_path, data_dir).await?;
+            let revision = sub_matches.get_one::<i64>("revision").copied();
+            handle_restore(snapshot_path, data_dir, revision).await?;
         }
         Some(("status", sub_matches)) => {
             let snapshot_path = sub_matches.get_one::<String>("filename").expect("required");
@@ -53,12 +61,12 @@ pub(crate) async fn execute(matches: &ArgMatches) -> Result<()> {
 async fn handle_restore<P: AsRef<Path>, D: Into<PathBuf>>(
     snapshot_path: P,
     data_dir: D,
+    revision: Option<i64>,
 ) -> Result<()> {
-    let restore_rocks_engine = Engine::new(EngineType::Rocks(data_dir.into()), &XLINE_TABLES)?;
-    restore_rocks_engine
-        .apply_snapshot_from_file(snapshot_path, &XLINE_TABLES)
-        .await?;
-    Ok(())
+    match revision {
+        Some(revision) => restore_to_revision(snapshot_path, data_dir, revision, None).await,
+        None => restore(snapshot_path, data_dir).await,
+    }
 }
 
 /// Snapshot status
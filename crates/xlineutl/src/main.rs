@@ -157,7 +157,7 @@
 
 use anyhow::Result;
 use clap::{arg, Command};
-use command::snapshot;
+use command::{dump, migrate, snapshot};
 use printer::{set_printer_type, PrinterType};
 
 /// Command definitions and parsers
@@ -180,6 +180,8 @@ fn cli() -> Command {
                 .default_value("SIMPLE"),
         )
         .subcommand(snapshot::command())
+        .subcommand(dump::command())
+        .subcommand(migrate::command())
 }
 
 #[tokio::main]
@@ -196,8 +198,11 @@ async fn main() -> Result<()> {
         _ => unreachable!("already checked by clap"),
     };
     set_printer_type(printer_type);
-    if let Some(("snapshot", sub_matches)) = matches.subcommand() {
-        snapshot::execute(sub_matches).await?;
+    match matches.subcommand() {
+        Some(("snapshot", sub_matches)) => snapshot::execute(sub_matches).await?,
+        Some(("dump", sub_matches)) => dump::execute(sub_matches)?,
+        Some(("migrate", sub_matches)) => migrate::execute(sub_matches).await?,
+        _ => {}
     }
     Ok(())
 }